@@ -1,48 +1,447 @@
 use ic_cdk_macros::*;
 use crate::domain::*;
-use crate::services::{RegistryService, RoutingService, InstructionAnalyzerService, AgentSpawningService, EconIntegrationService, with_state, with_state_mut};
-use crate::infra::{Guards, Metrics};
+use crate::services::{AuditService, DedupService, RegistryService, RegistryAuditService, RegistryEventService, PartialResultService, WorkloadService, RoutingService, InstructionAnalyzerService, AgentSpawningService, EconIntegrationService, EconFallbackService, FeatureFlagService, ShardService, RegistryShardService, RoundService, ServiceReportService, AgentLoadService, RoutingQueueService, ProgramService, DeliveryService, TagAnalyticsService, CircuitBreakerService, PreferencesService, NotificationService, ReclamationService, SlaService, AvailabilityService, AutonomousCoordinationService, BatchService, MapReduceService, ChainService, QuarantineService, SafetyLimitService, PersistenceService, MessageAuthService, LivenessService, SiemExportService, CapacityForecastService, CertificationService, StandbyService, ReputationService, CancellationService, SecretsService, ReviewService, IdGenerator, SnapshotService, OnboardingService, JobService, SloService, DiscoveryService, SpecializationService, SessionTemplateService, ProvenanceService, WarmPoolService, ConcurrencyService, CoordinationPreferenceService, with_state, with_state_mut};
+use crate::services::delivery::{DeadLetter, DeliveryPayload};
+use crate::services::tag_analytics::{TagReport, TaggedRequestKind};
+use crate::services::service_report::SignedServiceReport;
+use crate::services::agent_load::AgentLoadReport;
+use crate::services::registry_events::RegistrySubscription;
+use crate::services::capacity_forecast::CapacityForecast;
+use crate::services::certification::CertifiedAgent;
+use crate::services::workload::AgentWorkload;
+use crate::services::programs::{Program, ProgramStatus};
+use crate::services::preferences::UserPreferences;
+use crate::services::quota_manager::{QuotaManager, UsageSnapshot};
+use crate::services::econ_integration::CapabilityPrice;
+use crate::services::econ_fallback::EconFallbackStatus;
+use crate::services::feature_flags::FeatureFlagState;
+use crate::services::sharding::{ShardAssignment, ShardConfig, ShardStatus};
+use crate::services::registry_shards::{RegistryShardConfig, RegistryShardStatus};
+use crate::services::rounds::{CoordinationRound, RoundTaskSpec};
+use crate::services::audit::AuditEntry;
+use crate::services::notifications::Notification;
+use crate::services::reclamation::{ReclamationPolicy, ReclamationReport};
+use crate::services::liveness::LivenessPolicy;
+use crate::services::siem_export::{SiemEventCategory, SiemExportConfig, SiemExportStatus};
+use ic_cdk::api::management_canister::http_request::{HttpResponse, TransformArgs};
+use crate::services::availability::{AvailabilityWindow, MaintenanceStatus, MaintenanceWindow};
+use crate::services::autonomous_coord::{AgentMessage, CoordinationPreferences, CoordinationStats, TaskLedgerEntry};
+use crate::services::batches::InstructionBatch;
+use crate::services::cancellation::CancellationRecord;
+use crate::services::secrets::SecretMetadata;
+use crate::services::review::VerificationRecord;
+use crate::services::snapshot::{SnapshotChunk, SnapshotImportProgress};
+use crate::services::onboarding::AgentApplication;
+use crate::services::jobs::InferenceJob;
+use crate::services::slo::{AdminOverview, SpawnSloTargets};
+use crate::services::discovery::CoordinatorDescription;
+use crate::services::specializations::SpecializationRole;
+use crate::services::provenance::ResultProvenance;
+use crate::services::warm_pool::{WarmPoolConfig, WarmPoolStatus};
+use crate::services::concurrency::ConcurrencyUsage;
+use crate::services::coordination_preferences::AgentCoordinationPreferences;
+#[cfg(feature = "load-test")]
+use crate::services::LoadTestService;
+#[cfg(feature = "load-test")]
+use crate::services::load_test::{LoadProfile, LoadTestStatus};
+use crate::services::session_templates::{RoleBinding, SessionTemplate, SessionTemplateSpec, TemplateSessionStart};
+use crate::services::map_reduce::{MapReduceRequest, MapReduceResult};
+use crate::services::chains::{ChainResult, ChainStep};
+use crate::services::quarantine::QuarantineRecord;
+use crate::services::standby::StandbyAssignment;
+use crate::services::registry::BatchRegistrationResult;
+use crate::services::registry_audit::{RegistryAuditEntry, RegistryAuditRange};
+use crate::services::reputation::{AgentReputation, DisputeStatus, VerificationDispute};
+use crate::services::safety_limits::{DispatchCost, SafetyLimitStatus, SafetyLimits};
+use crate::services::routing_strategies::{RoutingStrategyInfo, RoutingStrategyRegistry};
+use crate::infra::{Guards, Log, Metrics, Page, PageOrder, PageRequest, Paginator};
+use crate::infra::guards::{EndpointGuardInfo, ENDPOINT_GUARDS};
+use crate::infra::logging::LogEntry;
+use sha2::{Digest, Sha256};
+use candid::Principal;
+use std::time::Duration;
 
 #[update]
-async fn register_agent(registration: AgentRegistration) -> Result<String, String> {
-    Guards::require_caller_authenticated()?;
-    let agent_id = RegistryService::register_agent(registration).await?;
-    Metrics::increment_counter("agents_registered_total");
+async fn register_agent(
+    registration: AgentRegistration,
+    coordination_preferences: Option<CoordinationPreferences>,
+    update_existing: Option<bool>,
+) -> Result<String, String> {
+    Guards::check("register_agent")?;
+    let registrant = ic_cdk::api::caller().to_string();
+    let (agent_id, updated) = RegistryService::register_agent(&registrant, registration, update_existing.unwrap_or(false)).await?;
+    // Registrations held for approval declare their preferences on a later heartbeat
+    if let (Some(preferences), Ok(_)) = (coordination_preferences, RegistryService::get_agent(&agent_id)) {
+        CoordinationPreferenceService::declare(&agent_id, preferences)?;
+    }
+    if !updated {
+        Metrics::increment_counter("agents_registered_total");
+    }
     Ok(agent_id)
 }
 
 #[update]
-async fn route_request(request: RouteRequest) -> Result<RouteResponse, String> {
-    Guards::require_caller_authenticated()?;
+fn register_agents_batch(registrations: Vec<AgentRegistration>) -> Result<Vec<BatchRegistrationResult>, String> {
+    Guards::check("register_agents_batch")?;
+    let registrant = ic_cdk::api::caller().to_string();
+    let results = RegistryService::register_agents_batch(&registrant, registrations)?;
+    let registered = results.iter().filter(|r| r.agent_id.is_some() && !r.pending_approval).count();
+    Metrics::add_to_counter("agents_registered_total", registered as u64);
+    Ok(results)
+}
+
+#[update]
+async fn deregister_agent(agent_id: String) -> Result<(), String> {
+    Guards::check("deregister_agent")?;
+    RegistryService::deregister_agent(&ic_cdk::api::caller().to_string(), &agent_id).await
+}
+
+#[update]
+async fn heartbeat(agent_id: String) -> Result<(), String> {
+    Guards::check("heartbeat")?;
+    if RegistryShardService::is_spilled(&agent_id) {
+        RegistryShardService::recall(&agent_id).await?;
+    }
+    LivenessService::heartbeat(&ic_cdk::api::caller().to_string(), &agent_id)
+}
+
+#[update]
+fn report_agent_load(agent_id: String, queue_depth: u32, cpu_estimate: f32, tokens_in_flight: u64) -> Result<AgentLoadReport, String> {
+    Guards::check("report_agent_load")?;
+    AgentLoadService::report(&ic_cdk::api::caller().to_string(), &agent_id, queue_depth, cpu_estimate, tokens_in_flight)
+}
+
+#[update]
+fn subscribe_to_registry_events(callback_canister: Principal) -> Result<RegistrySubscription, String> {
+    Guards::check("subscribe_to_registry_events")?;
+    RegistryEventService::subscribe(&ic_cdk::api::caller().to_string(), callback_canister)
+}
+
+#[update]
+fn unsubscribe_from_registry_events(callback_canister: Principal) -> Result<(), String> {
+    Guards::check("unsubscribe_from_registry_events")?;
+    RegistryEventService::unsubscribe(&ic_cdk::api::caller().to_string(), callback_canister)
+}
+
+#[query]
+fn list_registry_subscriptions() -> Result<Vec<RegistrySubscription>, String> {
+    Guards::check("list_registry_subscriptions")?;
+    Ok(RegistryEventService::list())
+}
+
+#[query]
+fn list_registry_subscriptions_paged(page: Option<PageRequest>) -> Result<Page<RegistrySubscription>, String> {
+    Guards::check("list_registry_subscriptions_paged")?;
+    let items = RegistryEventService::list();
+    Ok(Paginator::paginate(&items, |s| s.callback_canister.to_text(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn set_agent_approval_required(required: bool) -> Result<(), String> {
+    Guards::check("set_agent_approval_required")?;
+    with_state_mut(|s| { s.config.require_agent_approval = required; });
+    Ok(())
+}
+
+#[update]
+fn invite_agent_registrant(principal: String) -> Result<(), String> {
+    Guards::check("invite_agent_registrant")?;
+    OnboardingService::invite(&principal)
+}
+
+#[update]
+fn approve_agent(agent_id: String) -> Result<AgentRegistration, String> {
+    Guards::check("approve_agent")?;
+    OnboardingService::approve(&ic_cdk::api::caller().to_string(), &agent_id)
+}
+
+#[update]
+fn reject_agent(agent_id: String, reason: String) -> Result<(), String> {
+    Guards::check("reject_agent")?;
+    OnboardingService::reject(&ic_cdk::api::caller().to_string(), &agent_id, reason)
+}
+
+#[update]
+async fn quarantine_agent(agent_id: String, reason: String) -> Result<QuarantineRecord, String> {
+    Guards::check("quarantine_agent")?;
+    QuarantineService::quarantine(&ic_cdk::api::caller().to_string(), &agent_id, reason).await
+}
+
+#[update]
+fn release_agent(agent_id: String) -> Result<QuarantineRecord, String> {
+    Guards::check("release_agent")?;
+    QuarantineService::release(&ic_cdk::api::caller().to_string(), &agent_id)
+}
+
+#[update]
+fn suspend_agent(agent_id: String, reason: String) -> Result<AgentRegistration, String> {
+    Guards::check("suspend_agent")?;
+    QuarantineService::suspend(&ic_cdk::api::caller().to_string(), &agent_id, reason)
+}
+
+#[update]
+fn reinstate_agent(agent_id: String) -> Result<AgentRegistration, String> {
+    Guards::check("reinstate_agent")?;
+    QuarantineService::reinstate(&ic_cdk::api::caller().to_string(), &agent_id)
+}
+
+#[update]
+async fn retire_agent(agent_id: String, reason: String) -> Result<AgentRegistration, String> {
+    Guards::check("retire_agent")?;
+    QuarantineService::retire(&ic_cdk::api::caller().to_string(), &agent_id, reason).await
+}
+
+#[query]
+fn list_quarantined_agents() -> Result<Vec<QuarantineRecord>, String> {
+    Guards::check("list_quarantined_agents")?;
+    Ok(QuarantineService::list())
+}
+
+#[query]
+fn list_quarantined_agents_paged(page: Option<PageRequest>) -> Result<Page<QuarantineRecord>, String> {
+    Guards::check("list_quarantined_agents_paged")?;
+    let items = QuarantineService::list();
+    Ok(Paginator::paginate(&items, |r| r.agent_id.clone(), PageOrder::Ascending, page))
+}
+
+#[query]
+fn get_registry_audit(agent_id: String, range: Option<RegistryAuditRange>, page: Option<PageRequest>) -> Result<Page<RegistryAuditEntry>, String> {
+    Guards::check("get_registry_audit")?;
+    let entries = RegistryAuditService::query(&agent_id, &range.unwrap_or_default());
+    Ok(Paginator::paginate(&entries, |e| format!("{:020}", e.sequence), PageOrder::Ascending, page))
+}
+
+#[update]
+fn designate_standby(
+    capability: String,
+    primary_agent_id: String,
+    standby_agent_id: String,
+    promote_below_health: Option<f32>,
+) -> Result<StandbyAssignment, String> {
+    Guards::check("designate_standby")?;
+    StandbyService::designate(&ic_cdk::api::caller().to_string(), &capability, &primary_agent_id, &standby_agent_id, promote_below_health)
+}
+
+#[update]
+fn remove_standby(capability: String) -> Result<(), String> {
+    Guards::check("remove_standby")?;
+    StandbyService::remove(&capability)
+}
+
+#[query]
+fn list_standbys() -> Result<Vec<StandbyAssignment>, String> {
+    Guards::check("list_standbys")?;
+    Ok(StandbyService::list())
+}
+
+#[query]
+fn list_standbys_paged(page: Option<PageRequest>) -> Result<Page<StandbyAssignment>, String> {
+    Guards::check("list_standbys_paged")?;
+    let items = StandbyService::list();
+    Ok(Paginator::paginate(&items, |a| a.capability.clone(), PageOrder::Ascending, page))
+}
+
+#[query]
+fn list_pending_agents() -> Result<Vec<AgentApplication>, String> {
+    Guards::check("list_pending_agents")?;
+    list_pending_agents_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_pending_agents_paged(page: Option<PageRequest>) -> Result<Page<AgentApplication>, String> {
+    Guards::check("list_pending_agents_paged")?;
+    let pending = OnboardingService::list_pending();
+    Ok(Paginator::paginate(&pending, |a| Paginator::timestamp_key(a.submitted_at, &a.registration.agent_id), PageOrder::Ascending, page))
+}
+
+#[query]
+fn get_agent_application(agent_id: String) -> Result<AgentApplication, String> {
+    Guards::check("get_agent_application")?;
+    let application = OnboardingService::get_application(&agent_id)
+        .ok_or_else(|| "No registration found for agent".to_string())?;
+    if application.registrant != ic_cdk::api::caller().to_string() {
+        Guards::require_admin()?;
+    }
+    Ok(application)
+}
+
+#[update]
+async fn route_request(mut request: RouteRequest) -> Result<RouteResponse, String> {
+    let started_at = ic_cdk::api::time();
+    Guards::check("route_request")?;
     Guards::validate_msg_id(&request.request_id)?;
+    Guards::validate_tags(&request.tags)?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    ShardService::ensure_local(&user_principal)?;
+    ReclamationService::record_activity(&user_principal);
+    // Retries get the original decision without being charged or delivered again
+    let request_sha256 = PartialResultService::request_hash(&request.payload, &request.capabilities_required);
+    if let Some(cached) = DedupService::cached_response(&user_principal, &request.request_id, &request_sha256)? {
+        return Ok(cached);
+    }
+    if let Some(queued) = RoutingQueueService::queued_response(&user_principal, &request.request_id, ic_cdk::api::time()) {
+        return Ok(queued);
+    }
+    PreferencesService::apply_routing_defaults(&user_principal, &mut request);
+    let decode_profile = PreferencesService::get_preferences(&user_principal)
+        .default_decode_profile
+        .unwrap_or_default();
+    let dispatch_calls = RoutingService::dispatch_calls(&user_principal, &request)?;
+    let reservation = SafetyLimitService::admit(&user_principal, |state| DispatchCost {
+        spend_usd: DispatchCost::task_price(state, &request.capabilities_required),
+        ..DispatchCost::inference(state, &[], dispatch_calls, decode_profile)
+    })?;
+    let callback = request.callback.clone();
+    let tags = request.tags.clone();
+    let request_id = request.request_id.clone();
     
-    let response = RoutingService::route_request(request).await?;
+    let response = match RoutingService::route_request(&user_principal, request, decode_profile).await {
+        Ok(response) => response,
+        Err(e) => {
+            SafetyLimitService::settle(reservation);
+            TagAnalyticsService::record(&user_principal, &tags, TaggedRequestKind::Route, false, 0, 0);
+            ServiceReportService::record_request(&user_principal, false, started_at);
+            SiemExportService::emit(&user_principal, SiemEventCategory::Routing, "route_failed", &request_id, e.clone());
+            return Err(e);
+        }
+    };
+    // Queued behind saturated agents; the decision goes to the callback once routed, and
+    // the charge stays reserved for the day since its calls happen after this returns
+    if response.selected_agents.is_empty() {
+        return Ok(response);
+    }
+    SafetyLimitService::settle(reservation);
     Metrics::increment_counter("requests_routed_total");
+    TagAnalyticsService::record(&user_principal, &tags, TaggedRequestKind::Route, true, response.routing_time_ms, 0);
+    ServiceReportService::record_request(&user_principal, true, started_at);
+    SiemExportService::emit(
+        &user_principal,
+        SiemEventCategory::Routing,
+        "route_decision",
+        &response.request_id,
+        format!("{} -> {}", response.selection_criteria, response.selected_agents.join(",")),
+    );
+    
+    if let Some(target) = callback {
+        DeliveryService::enqueue(&user_principal, target, DeliveryPayload::Route(response.clone()));
+    }
     Ok(response)
 }
 
 #[update]
 async fn create_agents_from_instructions(instructions: String, agent_count: Option<u32>) -> Result<String, String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("create_agents_from_instructions")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let submission = InstructionSubmission { instructions, agent_count, callback: None, tags: vec![], accept_downscaled: false, expected_state_version: None };
+    create_agents_for_user(&user_principal, submission, None).await
+}
+
+#[update]
+async fn create_agents_with_callback(instructions: String, agent_count: Option<u32>, callback: CallbackTarget) -> Result<String, String> {
+    Guards::check("create_agents_with_callback")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let submission = InstructionSubmission { instructions, agent_count, callback: Some(callback), tags: vec![], accept_downscaled: false, expected_state_version: None };
+    create_agents_for_user(&user_principal, submission, None).await
+}
+
+#[update]
+async fn submit_instructions(submission: InstructionSubmission) -> Result<String, String> {
+    Guards::check("submit_instructions")?;
+    Guards::validate_tags(&submission.tags)?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    create_agents_for_user(&user_principal, submission, None).await
+}
+
+#[update]
+async fn create_agents_from_instructions_batch(submissions: Vec<InstructionSubmission>) -> Result<InstructionBatch, String> {
+    Guards::check("create_agents_from_instructions_batch")?;
+    for submission in &submissions {
+        Guards::validate_tags(&submission.tags)?;
+    }
+    let user_principal = ic_cdk::api::caller().to_string();
+    
+    // Reserve against fresh quota so later items are not starved by earlier ones mid-batch
+    EconIntegrationService::sync_user_quota_from_economics(&user_principal).await?;
+    let batch = BatchService::open_batch(&user_principal, &submissions)?;
+    
+    for (index, mut submission) in submissions.into_iter().enumerate() {
+        if !BatchService::is_pending(&batch, index) {
+            continue;
+        }
+        // Already validated against the pre-batch state in open_batch
+        submission.expected_state_version = None;
+        let outcome = create_agents_for_user(&user_principal, submission, None).await;
+        BatchService::record_item(&batch.batch_id, index, outcome);
+    }
+    
+    BatchService::get_batch(&user_principal, &batch.batch_id)
+}
+
+#[query]
+fn get_instruction_batch(batch_id: String) -> Result<InstructionBatch, String> {
+    Guards::check("get_instruction_batch")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    BatchService::get_batch(&user_principal, &batch_id)
+}
+
+#[update]
+async fn create_agents_in_program(program_id: String, instructions: String, agent_count: Option<u32>) -> Result<String, String> {
+    Guards::check("create_agents_in_program")?;
     let user_principal = ic_cdk::api::caller().to_string();
+    
+    // Plan first so the hold covers exactly the agents the spawn will create
+    let analysis = InstructionAnalyzerService::analyze_instructions(&instructions, &user_principal)?;
+    let planned = (analysis.suggested_agents.len() as u32).max(agent_count.unwrap_or(0));
+    ProgramService::reserve_budget(&user_principal, &program_id, planned)?;
+    let submission = InstructionSubmission { instructions, agent_count, callback: None, tags: vec![], accept_downscaled: false, expected_state_version: None };
+    match create_agents_for_user(&user_principal, submission, Some(analysis)).await {
+        Ok(request_id) => {
+            ProgramService::settle_reservation(&user_principal, &program_id, planned, Some(&request_id))?;
+            Ok(request_id)
+        }
+        Err(e) => {
+            ProgramService::settle_reservation(&user_principal, &program_id, planned, None)?;
+            Err(e)
+        }
+    }
+}
+
+/// Runs `plan` when given, otherwise analyzes the submitted instructions afresh
+async fn create_agents_for_user(user_principal: &str, submission: InstructionSubmission, plan: Option<InstructionAnalysisResult>) -> Result<String, String> {
+    let user_principal = user_principal.to_string();
+    let InstructionSubmission { instructions, agent_count, callback, tags, accept_downscaled, expected_state_version } = submission;
+    ShardService::ensure_local(&user_principal)?;
+    ReclamationService::record_activity(&user_principal);
+    
+    // Fill in anything the submission left out from the user's stored defaults
+    let preferences = PreferencesService::get_preferences(&user_principal);
+    let callback = callback.or_else(|| PreferencesService::default_callback(&user_principal));
+    let analytics_tags = if preferences.privacy.include_in_tag_analytics { tags.clone() } else { vec![] };
 
     // Validate subscription and quota with economics canister
     let quota_validation = EconIntegrationService::validate_agent_creation_quota(&user_principal).await?;
     if !quota_validation.allowed {
         return Err(format!("Quota exceeded: {}", quota_validation.reason.unwrap_or_else(|| "Unknown reason".to_string())));
     }
+    // Validated locally rather than by economics; creations are counted locally too
+    let degraded = EconFallbackService::is_degraded();
+    if !degraded {
+        EconIntegrationService::reconcile_fallback_usage().await;
+    }
 
     // Sync user quota from economics canister
     EconIntegrationService::sync_user_quota_from_economics(&user_principal).await?;
     
-    let request_id = format!("req_{}", ic_cdk::api::time());
+    let request_id = IdGenerator::next("req", &user_principal);
     let instruction_request = InstructionRequest {
         request_id: request_id.clone(),
         user_principal: user_principal.clone(),
         instructions: instructions.clone(),
         agent_count,
-        model_preferences: vec![],
+        model_preferences: preferences.preferred_models.clone(),
         created_at: ic_cdk::api::time(),
+        tags: tags.clone(),
+        analysis_id: None,
     };
     
     // Store instruction request
@@ -51,13 +450,37 @@ async fn create_agents_from_instructions(instructions: String, agent_count: Opti
     });
     
     // Spawn agents using the agent spawning service
-    match AgentSpawningService::spawn_agents_from_instructions(&request_id, &user_principal, &instructions).await {
+    let spawned = match plan {
+        Some(analysis) => AgentSpawningService::spawn_from_analysis(&request_id, &user_principal, &instructions, analysis, accept_downscaled, expected_state_version).await,
+        None => AgentSpawningService::spawn_agents_from_instructions(&request_id, &user_principal, &instructions, accept_downscaled, expected_state_version).await,
+    };
+    match spawned {
         Ok(result) => {
             // Track agent creation in economics canister
             let created_count = result.spawned_agents.len() as u32;
-            EconIntegrationService::track_agent_creation(&user_principal, created_count).await?;
+            TagAnalyticsService::record(&user_principal, &analytics_tags, TaggedRequestKind::AgentCreation, true, result.spawning_time_ms, created_count);
+            if degraded {
+                EconFallbackService::record_creations(&user_principal, created_count);
+            } else {
+                EconIntegrationService::track_agent_creation(&user_principal, created_count).await?;
+            }
 
             Metrics::increment_counter("agent_creation_requests_total");
+            
+            if !preferences.privacy.retain_instruction_history {
+                with_state_mut(|state| {
+                    if let Some(req) = state.instruction_requests.get_mut(&request_id) {
+                        req.instructions = "[redacted]".to_string();
+                    }
+                });
+            }
+            
+            if let Some(target) = callback {
+                let creation_result = with_state(|state| state.agent_creation_results.get(&request_id).cloned());
+                if let Some(creation_result) = creation_result {
+                    DeliveryService::enqueue(&user_principal, target, DeliveryPayload::AgentCreation(creation_result));
+                }
+            }
             Ok(request_id)
         },
         Err(e) => {
@@ -65,170 +488,1135 @@ async fn create_agents_from_instructions(instructions: String, agent_count: Opti
             with_state_mut(|state| {
                 state.instruction_requests.remove(&request_id);
             });
+            TagAnalyticsService::record(&user_principal, &analytics_tags, TaggedRequestKind::AgentCreation, false, 0, 0);
             Err(format!("Failed to spawn agents: {}", e))
         }
     }
 }
 
+#[update]
+fn create_program(name: String, agent_budget: Option<u32>) -> Result<String, String> {
+    Guards::check("create_program")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    ProgramService::create_program(&user_principal, name, agent_budget)
+}
+
+#[update]
+fn add_request_to_program(program_id: String, request_id: String) -> Result<(), String> {
+    Guards::check("add_request_to_program")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    ProgramService::attach_request(&user_principal, &program_id, &request_id)
+}
+
+#[query]
+fn get_program_status(program_id: String) -> Result<ProgramStatus, String> {
+    Guards::check("get_program_status")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    ProgramService::get_program_status(&user_principal, &program_id)
+}
+
+#[query]
+fn list_programs() -> Result<Vec<Program>, String> {
+    Guards::check("list_programs")?;
+    list_programs_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_programs_paged(page: Option<PageRequest>) -> Result<Page<Program>, String> {
+    Guards::check("list_programs_paged")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let programs = ProgramService::list_programs(&user_principal);
+    Ok(Paginator::paginate(&programs, |p| p.program_id.clone(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn put_secret(label: String, value: String) -> Result<(), String> {
+    Guards::check("put_secret")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    SecretsService::put_secret(&user_principal, &label, value)
+}
+
+#[update]
+fn delete_secret(label: String) -> Result<(), String> {
+    Guards::check("delete_secret")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    SecretsService::delete_secret(&user_principal, &label)
+}
+
+#[query]
+fn list_secrets() -> Result<Vec<SecretMetadata>, String> {
+    Guards::check("list_secrets")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    Ok(SecretsService::list_secrets(&user_principal))
+}
+
+#[query]
+fn list_secrets_paged(page: Option<PageRequest>) -> Result<Page<SecretMetadata>, String> {
+    Guards::check("list_secrets_paged")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let items = SecretsService::list_secrets(&user_principal);
+    Ok(Paginator::paginate(&items, |m| m.label.clone(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn set_preferences(preferences: UserPreferences) -> Result<(), String> {
+    Guards::check("set_preferences")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    PreferencesService::set_preferences(&user_principal, preferences)
+}
+
+#[query]
+fn get_preferences() -> Result<UserPreferences, String> {
+    Guards::check("get_preferences")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    Ok(PreferencesService::get_preferences(&user_principal))
+}
+
+#[query]
+fn list_audit_log(limit: Option<u32>) -> Result<Vec<AuditEntry>, String> {
+    Guards::check("list_audit_log")?;
+    list_audit_log_paged(Some(PageRequest { limit: Some(limit.unwrap_or(100).min(500)), ..PageRequest::default() })).map(|page| page.items)
+}
+
+#[query]
+fn list_audit_log_paged(page: Option<PageRequest>) -> Result<Page<AuditEntry>, String> {
+    Guards::check("list_audit_log_paged")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let entries = AuditService::list_for_principal(&user_principal, usize::MAX);
+    Ok(Paginator::paginate(&entries, |e| Paginator::timestamp_key(e.recorded_at, &e.entry_id), PageOrder::Descending, page))
+}
+
+#[query]
+fn list_notifications(unread_only: bool) -> Result<Vec<Notification>, String> {
+    Guards::check("list_notifications")?;
+    list_notifications_paged(unread_only, PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_notifications_paged(unread_only: bool, page: Option<PageRequest>) -> Result<Page<Notification>, String> {
+    Guards::check("list_notifications_paged")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let notifications = NotificationService::list(&user_principal, unread_only);
+    Ok(Paginator::paginate(&notifications, |n| Paginator::timestamp_key(n.created_at, &n.notification_id), PageOrder::Descending, page))
+}
+
+#[update]
+fn mark_notification_read(notification_id: String) -> Result<(), String> {
+    Guards::check("mark_notification_read")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    NotificationService::mark_read(&user_principal, &notification_id)
+}
+
+#[query]
+fn list_archived_instructions() -> Result<Vec<InstructionRequest>, String> {
+    Guards::check("list_archived_instructions")?;
+    list_archived_instructions_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_archived_instructions_paged(page: Option<PageRequest>) -> Result<Page<InstructionRequest>, String> {
+    Guards::check("list_archived_instructions_paged")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let archived = ReclamationService::list_archived(&user_principal);
+    Ok(Paginator::paginate(&archived, |r| r.request_id.clone(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn set_reclamation_policy(policy: ReclamationPolicy) -> Result<(), String> {
+    Guards::check("set_reclamation_policy")?;
+    ReclamationService::set_policy(policy)
+}
+
+#[query]
+fn get_reclamation_policy() -> Result<ReclamationPolicy, String> {
+    Guards::check("get_reclamation_policy")?;
+    Ok(ReclamationService::get_policy())
+}
+
+#[update]
+fn set_liveness_policy(policy: LivenessPolicy) -> Result<(), String> {
+    Guards::check("set_liveness_policy")?;
+    LivenessService::set_policy(policy)
+}
+
+#[query]
+fn get_liveness_policy() -> Result<LivenessPolicy, String> {
+    Guards::check("get_liveness_policy")?;
+    Ok(LivenessService::get_policy())
+}
+
+#[update]
+async fn run_reclamation() -> Result<ReclamationReport, String> {
+    Guards::check("run_reclamation")?;
+    ReclamationService::run().await
+}
+
+#[query]
+fn get_agent_creation_status(request_id: String) -> Result<AgentCreationResult, String> {
+    Guards::check("get_agent_creation_status")?;
+    
+    let result = with_state(|state| {
+        state.agent_creation_results.get(&request_id).cloned()
+    });
+    
+    result.ok_or_else(|| "Agent creation request not found".to_string())
+}
+
+#[update]
+async fn get_user_quota_status() -> Result<QuotaCheckResult, String> {
+    Guards::check("get_user_quota_status")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    
+    // Sync quota from economics canister first
+    if let Err(e) = EconIntegrationService::sync_user_quota_from_economics(&user_principal).await {
+        Log::warn("econ", format!("Failed to sync quota from economics: {}", e));
+    }
+    
+    // Get actual user quota from state
+    let user_quota = with_state(|state| {
+        state.user_quotas.get(&user_principal).cloned()
+    });
+    
+    match user_quota {
+        Some(quota) => {
+            let current_agents = quota.current_usage.agents_created_this_month;
+            let remaining_agents = quota.limits.max_agents.saturating_sub(current_agents);
+            let quota_available = remaining_agents > 0 && 
+                                 current_agents < quota.limits.monthly_agent_creations;
+            
+            Ok(QuotaCheckResult {
+                quota_available,
+                remaining_agents,
+                monthly_limit: quota.limits.monthly_agent_creations,
+                remaining_weighted_units: QuotaManager::remaining_weighted_units(&quota),
+                tier: quota.subscription_tier,
+                degraded: EconFallbackService::is_degraded(),
+            })
+        },
+        None => {
+            // Create free subscription for new user via economics canister
+            match EconIntegrationService::get_or_create_free_subscription(&user_principal).await {
+                Ok(_subscription) => {
+                    // Retry getting quota after creating subscription
+                    EconIntegrationService::sync_user_quota_from_economics(&user_principal).await?;
+                    
+                    let user_quota = with_state(|state| {
+                        state.user_quotas.get(&user_principal).cloned()
+                    });
+                    
+                    if let Some(quota) = user_quota {
+                        let current_agents = quota.current_usage.agents_created_this_month;
+                        let remaining_agents = quota.limits.max_agents.saturating_sub(current_agents);
+                        let quota_available = remaining_agents > 0 && 
+                                             current_agents < quota.limits.monthly_agent_creations;
+                        
+                        Ok(QuotaCheckResult {
+                            quota_available,
+                            remaining_agents,
+                            monthly_limit: quota.limits.monthly_agent_creations,
+                            remaining_weighted_units: QuotaManager::remaining_weighted_units(&quota),
+                            tier: quota.subscription_tier,
+                            degraded: false,
+                        })
+                    } else {
+                        Err("Failed to create user subscription".to_string())
+                    }
+                },
+                Err(e) => Err(format!("Failed to create free subscription: {}", e)),
+            }
+        }
+    }
+}
+
+#[query]
+fn list_dead_letters() -> Result<Vec<DeadLetter>, String> {
+    Guards::check("list_dead_letters")?;
+    list_dead_letters_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_dead_letters_paged(page: Option<PageRequest>) -> Result<Page<DeadLetter>, String> {
+    Guards::check("list_dead_letters_paged")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let dead_letters = DeliveryService::list_dead_letters(&user_principal);
+    Ok(Paginator::paginate(&dead_letters, |d| d.delivery_id.clone(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn redrive_dead_letter(delivery_id: String) -> Result<(), String> {
+    Guards::check("redrive_dead_letter")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    DeliveryService::redrive(&user_principal, &delivery_id)
+}
+
+#[query]
+fn get_agent(agent_id: String) -> Result<AgentRegistration, String> {
+    Guards::check("get_agent")?;
+    match RegistryService::get_agent(&agent_id) {
+        Err(_) if RegistryShardService::is_spilled(&agent_id) => {
+            Err(format!("Agent {} is held by a child registry; read it with find_agent", agent_id))
+        }
+        local => local,
+    }
+}
+
+/// `get_agent` that also reads agents spilled to child registries
+#[query(composite = true)]
+async fn find_agent(agent_id: String) -> Result<AgentRegistration, String> {
+    Guards::check("find_agent")?;
+    match RegistryService::get_agent(&agent_id) {
+        Err(_) if RegistryShardService::is_spilled(&agent_id) => RegistryShardService::lookup(&agent_id).await,
+        local => local,
+    }
+}
+
+/// `get_agent` with a certificate and witness the client can verify
+#[query]
+fn get_certified_agent(agent_id: String) -> Result<CertifiedAgent, String> {
+    Guards::check("get_certified_agent")?;
+    CertificationService::get_certified_agent(&agent_id)
+}
+
+#[update]
+fn update_agent_capabilities(agent_id: String, add: Vec<String>, remove: Vec<String>) -> Result<AgentRegistration, String> {
+    Guards::check("update_agent_capabilities")?;
+    let caller = ic_cdk::api::caller().to_string();
+    RegistryService::update_agent_capabilities(&caller, &agent_id, add, remove)
+}
+
+#[query]
+fn get_agent_capability_history(agent_id: String) -> Result<Vec<CapabilityChange>, String> {
+    Guards::check("get_agent_capability_history")?;
+    get_agent_capability_history_paged(agent_id, PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn get_agent_capability_history_paged(agent_id: String, page: Option<PageRequest>) -> Result<Page<CapabilityChange>, String> {
+    Guards::check("get_agent_capability_history_paged")?;
+    // History is append-only, so the position is a stable key
+    let history: Vec<(String, CapabilityChange)> = RegistryService::get_capability_history(&agent_id)
+        .into_iter()
+        .enumerate()
+        .map(|(index, change)| (format!("{:010}", index), change))
+        .collect();
+    Ok(Paginator::paginate(&history, |(key, _)| key.clone(), PageOrder::Ascending, page).map(|(_, change)| change))
+}
+
+#[query]
+fn list_agents() -> Result<Vec<AgentRegistration>, String> {
+    Guards::check("list_agents")?;
+    list_agents_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_agents_paged(page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String> {
+    Guards::check("list_agents_paged")?;
+    Ok(with_state(|state| Paginator::paginate(state.agents.values(), |a| a.agent_id.clone(), PageOrder::Ascending, page)))
+}
+
+/// `list_agents` including the agents spilled to child registries
+#[query(composite = true)]
+async fn list_all_agents(page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String> {
+    Guards::check("list_all_agents")?;
+    RegistryShardService::list_everywhere(None, page).await
+}
+
+#[query]
+fn get_agents_delta(since_version: u64) -> Result<AgentRegistryDelta, String> {
+    Guards::check("get_agents_delta")?;
+    Ok(RegistryService::get_agents_delta(since_version))
+}
+
+#[query]
+fn list_user_agents() -> Result<Vec<AgentRegistration>, String> {
+    Guards::check("list_user_agents")?;
+    list_user_agents_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_user_agents_paged(page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String> {
+    Guards::check("list_user_agents_paged")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    
+    // Filter agents by user principal
+    let user_agents = with_state(|state| {
+        let owned = state.agents
+            .values()
+            .filter(|agent| agent.agent_principal == user_principal);
+        Paginator::paginate(owned, |a| a.agent_id.clone(), PageOrder::Ascending, page)
+    });
+    
+    Ok(user_agents)
+}
+
+#[query]
+fn list_instruction_requests() -> Result<Vec<InstructionRequest>, String> {
+    Guards::check("list_instruction_requests")?;
+    list_instruction_requests_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_instruction_requests_paged(page: Option<PageRequest>) -> Result<Page<InstructionRequest>, String> {
+    Guards::check("list_instruction_requests_paged")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    
+    let requests = with_state(|state| {
+        let owned = state.instruction_requests
+            .values()
+            .filter(|req| req.user_principal == user_principal);
+        Paginator::paginate(owned, |r| r.request_id.clone(), PageOrder::Ascending, page)
+    });
+    
+    Ok(requests)
+}
+
+#[query]
+fn health() -> CoordinatorHealth {
+    RegistryService::get_health()
+}
+
+#[update]
+async fn get_service_report(period_days: u32) -> Result<SignedServiceReport, String> {
+    Guards::check("get_service_report")?;
+    if period_days == 0 || period_days > 90 {
+        return Err("period_days must be between 1 and 90".to_string());
+    }
+    ServiceReportService::signed_report(&ic_cdk::api::caller().to_string(), period_days).await
+}
+
+#[update]
+fn set_report_organization(principal: String, organization: Option<String>) -> Result<(), String> {
+    Guards::check("set_report_organization")?;
+    Principal::from_text(&principal).map_err(|e| format!("Invalid principal: {}", e))?;
+    if let Some(organization) = &organization {
+        Principal::from_text(organization).map_err(|e| format!("Invalid organization: {}", e))?;
+    }
+    ServiceReportService::set_organization(&principal, organization)
+}
+
+#[update]
+fn set_attestation_key(key_name: String) -> Result<(), String> {
+    Guards::check("set_attestation_key")?;
+    if key_name.trim().is_empty() {
+        return Err("key_name must not be empty".to_string());
+    }
+    with_state_mut(|s| s.config.attestation_key = Some(key_name));
+    Ok(())
+}
+
+#[query]
+fn get_tag_report(tag: String, period_days: u32) -> Result<TagReport, String> {
+    Guards::check("get_tag_report")?;
+    if period_days == 0 || period_days > 90 {
+        return Err("period_days must be between 1 and 90".to_string());
+    }
+    let user_principal = ic_cdk::api::caller().to_string();
+    Ok(TagAnalyticsService::get_tag_report(&user_principal, &tag, period_days))
+}
+
+#[query]
+fn forecast_capacity(horizon_days: u32) -> Result<CapacityForecast, String> {
+    Guards::check("forecast_capacity")?;
+    CapacityForecastService::forecast(horizon_days)
+}
+
+#[query]
+fn get_agent_circuit(agent_id: String) -> Result<Option<AgentCircuit>, String> {
+    Guards::check("get_agent_circuit")?;
+    Ok(CircuitBreakerService::get_circuit(&agent_id))
+}
+
+#[query]
+fn get_agents_by_model(model_id: String, page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String> {
+    Guards::check("get_agents_by_model")?;
+    let agents = RegistryService::get_agents_by_model(&model_id);
+    Ok(Paginator::paginate(&agents, |a| a.agent_id.clone(), PageOrder::Ascending, page))
+}
+
+#[query]
+fn search_agents(filter: AgentSearchFilter) -> Result<Vec<AgentSearchResult>, String> {
+    Guards::check("search_agents")?;
+    search_agents_paged(filter, PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn search_agents_paged(filter: AgentSearchFilter, page: Option<PageRequest>) -> Result<Page<AgentSearchResult>, String> {
+    Guards::check("search_agents_paged")?;
+    let results = RegistryService::search_agents(&filter);
+    Ok(Paginator::paginate(&results, |r| r.agent.agent_id.clone(), PageOrder::Ascending, page))
+}
+
+/// `search_agents` including the agents spilled to child registries
+#[query(composite = true)]
+async fn search_all_agents(filter: AgentSearchFilter, page: Option<PageRequest>) -> Result<Page<AgentSearchResult>, String> {
+    Guards::check("search_all_agents")?;
+    RegistryShardService::search_everywhere(filter, page).await
+}
+
+#[update]
+fn declare_agent_sla(agent_id: String, max_latency_ms: u64, min_availability: f32) -> Result<(), String> {
+    Guards::check("declare_agent_sla")?;
+    let caller = ic_cdk::api::caller().to_string();
+    SlaService::declare_sla(&caller, &agent_id, max_latency_ms, min_availability)
+}
+
+#[update]
+fn clear_agent_sla(agent_id: String) -> Result<(), String> {
+    Guards::check("clear_agent_sla")?;
+    let caller = ic_cdk::api::caller().to_string();
+    SlaService::clear_sla(&caller, &agent_id)
+}
+
+#[query]
+fn get_agent_sla_compliance(agent_id: String) -> Result<Option<SlaCompliance>, String> {
+    Guards::check("get_agent_sla_compliance")?;
+    Ok(SlaService::get_compliance(&agent_id))
+}
+
+#[update]
+fn submit_agent_feedback(agent_id: String, rating: u8) -> Result<AgentReputation, String> {
+    Guards::check("submit_agent_feedback")?;
+    let caller = ic_cdk::api::caller().to_string();
+    ReputationService::submit_feedback(&caller, &agent_id, rating)
+}
+
+#[query]
+fn get_agent_reputation(agent_id: String) -> Result<AgentReputation, String> {
+    Guards::check("get_agent_reputation")?;
+    ReputationService::get_reputation(&agent_id)
+}
+
+#[update]
+fn dispute_verification(request_id: String, reason: String) -> Result<VerificationDispute, String> {
+    Guards::check("dispute_verification")?;
+    let caller = ic_cdk::api::caller().to_string();
+    ReputationService::open_dispute(&caller, &request_id, reason)
+}
+
+#[query]
+fn get_verification_dispute(request_id: String) -> Result<VerificationDispute, String> {
+    Guards::check("get_verification_dispute")?;
+    let caller = ic_cdk::api::caller().to_string();
+    ReputationService::get_dispute(&caller, &request_id)
+}
+
+#[update]
+fn resolve_verification_dispute(request_id: String, accept: bool, note: Option<String>) -> Result<VerificationDispute, String> {
+    Guards::check("resolve_verification_dispute")?;
+    ReputationService::resolve_dispute(&ic_cdk::api::caller().to_string(), &request_id, accept, note)
+}
+
+#[query]
+fn list_verification_disputes(status: Option<DisputeStatus>, page: Option<PageRequest>) -> Result<Page<VerificationDispute>, String> {
+    Guards::check("list_verification_disputes")?;
+    let disputes = ReputationService::list_disputes(status);
+    Ok(Paginator::paginate(&disputes, |d| Paginator::timestamp_key(d.opened_at, &d.request_id), PageOrder::Ascending, page))
+}
+
+#[query]
+fn get_agent_workload(agent_id: String) -> Result<AgentWorkload, String> {
+    Guards::check("get_agent_workload")?;
+    WorkloadService::get_agent_workload(&agent_id)
+}
+
+#[query]
+fn list_sla_violations() -> Result<Vec<SlaCompliance>, String> {
+    Guards::check("list_sla_violations")?;
+    list_sla_violations_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_sla_violations_paged(page: Option<PageRequest>) -> Result<Page<SlaCompliance>, String> {
+    Guards::check("list_sla_violations_paged")?;
+    let violations = SlaService::list_violating();
+    Ok(Paginator::paginate(&violations, |c| c.agent_id.clone(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn set_agent_availability_windows(agent_id: String, windows: Vec<String>) -> Result<Vec<AvailabilityWindow>, String> {
+    Guards::check("set_agent_availability_windows")?;
+    let caller = ic_cdk::api::caller().to_string();
+    AvailabilityService::set_windows(&caller, &agent_id, windows)
+}
+
+#[query]
+fn get_agent_availability_windows(agent_id: String) -> Result<Vec<AvailabilityWindow>, String> {
+    Guards::check("get_agent_availability_windows")?;
+    Ok(AvailabilityService::get_windows(&agent_id))
+}
+
+#[update]
+fn set_agent_maintenance(agent_id: String, window: MaintenanceWindow) -> Result<MaintenanceStatus, String> {
+    Guards::check("set_agent_maintenance")?;
+    let caller = ic_cdk::api::caller().to_string();
+    AvailabilityService::set_maintenance(&caller, &agent_id, window)
+}
+
+#[update]
+fn clear_agent_maintenance(agent_id: String) -> Result<MaintenanceStatus, String> {
+    Guards::check("clear_agent_maintenance")?;
+    let caller = ic_cdk::api::caller().to_string();
+    AvailabilityService::clear_maintenance(&caller, &agent_id)
+}
+
+#[query]
+fn get_agent_maintenance(agent_id: String) -> Result<MaintenanceStatus, String> {
+    Guards::check("get_agent_maintenance")?;
+    Ok(AvailabilityService::get_maintenance(&agent_id))
+}
+
+#[update]
+async fn delegate_task(
+    parent_task_id: String,
+    agent_id: String,
+    description: String,
+    required_capabilities: Vec<String>,
+    priority: Option<MessagePriority>,
+    deadline: Option<u64>,
+) -> Result<String, String> {
+    Guards::check("delegate_task")?;
+    let caller = ic_cdk::api::caller().to_string();
+    let agent = RegistryService::get_agent(&agent_id)?;
+    if agent.agent_principal != caller {
+        return Err("Caller does not control the delegating agent".to_string());
+    }
+    SafetyLimitService::admit(&caller, |state| DispatchCost {
+        spend_usd: DispatchCost::task_price(state, &required_capabilities),
+        ..Default::default()
+    })?;
+    AutonomousCoordinationService::delegate_task(parent_task_id, agent_id, description, required_capabilities, priority, deadline).await
+}
+
+#[update]
+async fn send_coordination_message(
+    session_id: String,
+    from_agent: String,
+    to_agent: Option<String>,
+    message: AgentMessage,
+) -> Result<(), String> {
+    Guards::check("send_coordination_message")?;
+    MessageAuthService::verify_sender(&ic_cdk::api::caller().to_string(), &session_id, &from_agent, &message)?;
+    AutonomousCoordinationService::send_coordination_message(session_id, from_agent, to_agent, message).await
+}
+
+#[update]
+fn create_session_template(spec: SessionTemplateSpec) -> Result<SessionTemplate, String> {
+    Guards::check("create_session_template")?;
+    let caller = ic_cdk::api::caller().to_string();
+    SessionTemplateService::create_template(&caller, spec)
+}
+
+#[query]
+fn list_session_templates() -> Result<Vec<SessionTemplate>, String> {
+    Guards::check("list_session_templates")?;
+    list_session_templates_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn list_session_templates_paged(page: Option<PageRequest>) -> Result<Page<SessionTemplate>, String> {
+    Guards::check("list_session_templates_paged")?;
+    let templates = SessionTemplateService::list_templates();
+    Ok(Paginator::paginate(&templates, |t| t.template_id.clone(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn start_session_from_template(template_id: String, role_bindings: Vec<RoleBinding>) -> Result<TemplateSessionStart, String> {
+    Guards::check("start_session_from_template")?;
+    let caller = ic_cdk::api::caller().to_string();
+    SafetyLimitService::admit(&caller, |_| DispatchCost { sessions: 1, ..Default::default() })?;
+    SessionTemplateService::start_session(&caller, Guards::require_admin().is_ok(), &template_id, role_bindings)
+}
+
+#[query]
+fn get_coordination_stats() -> CoordinationStats {
+    AutonomousCoordinationService::get_coordination_stats()
+}
+
+#[query]
+fn get_task(task_id: String) -> Result<TaskLedgerEntry, String> {
+    Guards::check("get_task")?;
+    AutonomousCoordinationService::get_task(&task_id).ok_or_else(|| "Task not found".to_string())
+}
+
+#[update]
+async fn cancel_task(task_id: String, reason: String) -> Result<CancellationRecord, String> {
+    Guards::check("cancel_task")?;
+    let caller = ic_cdk::api::caller().to_string();
+    CancellationService::cancel_task(&caller, Guards::require_admin().is_ok(), &task_id, reason).await
+}
+
+#[update]
+async fn cancel_coordination_session(session_id: String, reason: String) -> Result<CancellationRecord, String> {
+    Guards::check("cancel_coordination_session")?;
+    let caller = ic_cdk::api::caller().to_string();
+    CancellationService::cancel_session(&caller, Guards::require_admin().is_ok(), &session_id, reason).await
+}
+
+/// Queue a round of tasks on a session; it is distributed once the previous round's barrier is released
+#[update]
+async fn add_session_round(session_id: String, tasks: Vec<RoundTaskSpec>, timeout_ms: u64) -> Result<u32, String> {
+    Guards::check("add_session_round")?;
+    let caller = ic_cdk::api::caller().to_string();
+    SafetyLimitService::admit(&caller, |state| DispatchCost {
+        spend_usd: tasks.iter().map(|task| DispatchCost::task_price(state, &task.required_capabilities)).sum(),
+        ..Default::default()
+    })?;
+    let round = RoundService::add_round(&caller, Guards::require_admin().is_ok(), &session_id, tasks, timeout_ms)?;
+    RoundService::advance(&session_id).await;
+    Ok(round)
+}
+
+#[query]
+fn get_session_rounds(session_id: String) -> Result<Vec<CoordinationRound>, String> {
+    Guards::check("get_session_rounds")?;
+    RoundService::get_rounds(&session_id)
+}
+
+#[query]
+fn get_session_rounds_paged(session_id: String, page: Option<PageRequest>) -> Result<Page<CoordinationRound>, String> {
+    Guards::check("get_session_rounds_paged")?;
+    let items = RoundService::get_rounds(&session_id)?;
+    Ok(Paginator::paginate(&items, |r| format!("{:010}", r.round), PageOrder::Ascending, page))
+}
+
+#[update]
+fn acknowledge_cancellation(cancellation_id: String, agent_id: String) -> Result<CancellationRecord, String> {
+    Guards::check("acknowledge_cancellation")?;
+    let caller = ic_cdk::api::caller().to_string();
+    CancellationService::acknowledge(&caller, &cancellation_id, &agent_id)
+}
+
+#[query]
+fn get_cancellation(cancellation_id: String) -> Result<CancellationRecord, String> {
+    Guards::check("get_cancellation")?;
+    CancellationService::get_cancellation(&cancellation_id).ok_or_else(|| "Cancellation not found".to_string())
+}
+
+#[query]
+fn get_dependency_health() -> Result<Vec<DependencyHealth>, String> {
+    Guards::check("get_dependency_health")?;
+    Ok(Metrics::get_dependency_health())
+}
+
+#[query]
+fn get_routing_stats(agent_id: Option<String>) -> Result<Vec<RoutingStats>, String> {
+    Guards::check("get_routing_stats")?;
+    get_routing_stats_paged(agent_id, PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn get_routing_stats_paged(agent_id: Option<String>, page: Option<PageRequest>) -> Result<Page<RoutingStats>, String> {
+    Guards::check("get_routing_stats_paged")?;
+    let stats = RoutingService::get_stats(agent_id);
+    Ok(Paginator::paginate(&stats, |s| s.agent_id.clone(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn update_agent_health(agent_id: String, health_score: f32, coordination_preferences: Option<CoordinationPreferences>) -> Result<(), String> {
+    Guards::check("update_agent_health")?;
+    if let Some(preferences) = coordination_preferences {
+        let caller = ic_cdk::api::caller().to_string();
+        let agent = RegistryService::get_agent(&agent_id)?;
+        if agent.agent_principal != caller && agent.canister_id != caller {
+            return Err("Only the agent or its owner can declare its coordination preferences".to_string());
+        }
+        CoordinationPreferenceService::declare(&agent_id, preferences)?;
+    }
+    RegistryService::update_agent_health(&ic_cdk::api::caller().to_string(), agent_id, health_score)
+}
+
+#[query]
+fn get_agent_coordination_preferences(agent_id: String) -> Result<AgentCoordinationPreferences, String> {
+    Guards::check("get_agent_coordination_preferences")?;
+    CoordinationPreferenceService::get(&agent_id)
+}
+
+#[update]
+async fn set_swarm_policy(policy: SwarmPolicy) -> Result<(), String> {
+    Guards::check("set_swarm_policy")?;
+    with_state_mut(|s| { s.config.swarm = policy; });
+    Ok(())
+}
+
+#[query]
+fn get_swarm_policy() -> SwarmPolicy {
+    with_state(|s| s.config.swarm.clone())
+}
+
+#[query]
+fn list_routing_strategies() -> Vec<RoutingStrategyInfo> {
+    RoutingStrategyRegistry::list()
+}
+
+#[update]
+fn set_enabled_routing_strategies(names: Vec<String>) -> Result<(), String> {
+    Guards::check("set_enabled_routing_strategies")?;
+    RoutingStrategyRegistry::validate_enabled(&names)?;
+    with_state_mut(|s| { s.config.enabled_strategies = names; });
+    Ok(())
+}
+
+#[update]
+fn set_capability_routing_policy(capability: String, policy: Option<CapabilityRoutingPolicy>) -> Result<(), String> {
+    Guards::check("set_capability_routing_policy")?;
+    RoutingService::set_capability_policy(&capability, policy)
+}
+
+#[query]
+fn list_capability_routing_policies() -> Vec<(String, CapabilityRoutingPolicy)> {
+    with_state(|s| s.config.capability_policies.clone().into_iter().collect())
+}
+
+#[update]
+fn set_routing_weights(weights: RoutingWeights) -> Result<(), String> {
+    Guards::check("set_routing_weights")?;
+    RoutingService::set_routing_weights(weights)
+}
+
+#[query]
+fn get_routing_weights() -> RoutingWeights {
+    with_state(|s| s.config.routing_weights)
+}
+
+#[update]
+fn export_state_snapshot(chunk_index: u32) -> Result<SnapshotChunk, String> {
+    Guards::check("export_state_snapshot")?;
+    SnapshotService::export(&ic_cdk::api::caller().to_string(), chunk_index)
+}
+
+#[update]
+fn import_state_snapshot(chunk: SnapshotChunk) -> Result<SnapshotImportProgress, String> {
+    Guards::check("import_state_snapshot")?;
+    SnapshotService::import(&ic_cdk::api::caller().to_string(), chunk)
+}
+
+#[update]
+fn export_registry_snapshot(chunk_index: u32) -> Result<SnapshotChunk, String> {
+    Guards::check("export_registry_snapshot")?;
+    SnapshotService::export_registry(&ic_cdk::api::caller().to_string(), chunk_index)
+}
+
+#[update]
+fn import_registry_snapshot(chunk: SnapshotChunk) -> Result<SnapshotImportProgress, String> {
+    Guards::check("import_registry_snapshot")?;
+    SnapshotService::import_registry(&ic_cdk::api::caller().to_string(), chunk)
+}
+
+#[query]
+fn get_admin_overview() -> Result<AdminOverview, String> {
+    Guards::check("get_admin_overview")?;
+    Ok(AdminOverview {
+        health: RegistryService::get_health(),
+        spawn_slo: SloService::get_status(),
+        open_alerts: SloService::open_alerts(),
+        usage_totals: QuotaManager::usage_totals(12),
+    })
+}
+
+#[update]
+fn set_spawn_slo_targets(targets: SpawnSloTargets) -> Result<(), String> {
+    Guards::check("set_spawn_slo_targets")?;
+    SloService::set_targets(targets)
+}
+
+#[update]
+fn set_operational_mode(mode: OperationalMode) -> Result<(), String> {
+    Guards::check("set_operational_mode")?;
+    let now = ic_cdk::api::time();
+    with_state_mut(|s| {
+        let previous = std::mem::replace(&mut s.config.operational_mode, mode);
+        ServiceReportService::record_mode_change_in(s, previous, mode, now);
+    });
+    Ok(())
+}
+
+#[query]
+fn list_endpoint_guards() -> Result<Vec<EndpointGuardInfo>, String> {
+    Guards::check("list_endpoint_guards")?;
+    Ok(ENDPOINT_GUARDS.iter().map(|guard| guard.info()).collect())
+}
+
+#[update]
+fn set_shard_config(config: Option<ShardConfig>) -> Result<(), String> {
+    Guards::check("set_shard_config")?;
+    ShardService::configure(config)
+}
+
+#[query]
+fn get_shard_status() -> Result<ShardStatus, String> {
+    Guards::check("get_shard_status")?;
+    Ok(ShardService::status())
+}
+
+#[query]
+fn get_user_shard(principal: String) -> Result<ShardAssignment, String> {
+    Guards::check("get_user_shard")?;
+    ShardService::get_user_shard(&principal)
+}
+
+#[update]
+async fn rebalance_shard_users(limit: u32) -> Result<u32, String> {
+    Guards::check("rebalance_shard_users")?;
+    ShardService::rebalance(limit).await
+}
+
+/// Called by a peer shard moving users here
+#[update]
+fn import_shard_users(payload: Vec<u8>) -> Result<u32, String> {
+    Guards::check("import_shard_users")?;
+    if !ShardService::is_peer(&ic_cdk::api::caller().to_text()) {
+        Guards::require_admin()?;
+    }
+    ShardService::import(&payload)
+}
+
+#[update]
+fn set_registry_shard_config(config: RegistryShardConfig) -> Result<(), String> {
+    Guards::check("set_registry_shard_config")?;
+    RegistryShardService::configure(config)
+}
+
+#[query]
+fn get_registry_shard_status() -> Result<RegistryShardStatus, String> {
+    Guards::check("get_registry_shard_status")?;
+    Ok(RegistryShardService::status())
+}
+
+#[update]
+async fn spill_registry(limit: u32) -> Result<u32, String> {
+    Guards::check("spill_registry")?;
+    RegistryShardService::spill(limit).await
+}
+
+#[update]
+async fn recall_spilled_agent(agent_id: String) -> Result<(), String> {
+    Guards::check("recall_spilled_agent")?;
+    RegistryShardService::recall(&agent_id).await
+}
+
+/// Called by the parent coordinator spilling records here
+#[update]
+fn registry_child_store(agents: Vec<AgentRegistration>) -> Result<u32, String> {
+    Guards::check("registry_child_store")?;
+    RegistryShardService::require_parent(&ic_cdk::api::caller().to_text())?;
+    Ok(RegistryShardService::store(agents))
+}
+
+#[query]
+fn registry_child_get(agent_id: String) -> Result<AgentRegistration, String> {
+    Guards::check("registry_child_get")?;
+    RegistryShardService::require_parent(&ic_cdk::api::caller().to_text())?;
+    RegistryShardService::get_held(&agent_id)
+}
+
+#[query]
+fn registry_child_list(filter: Option<AgentSearchFilter>, page: PageRequest) -> Result<Page<AgentRegistration>, String> {
+    Guards::check("registry_child_list")?;
+    RegistryShardService::require_parent(&ic_cdk::api::caller().to_text())?;
+    Ok(RegistryShardService::list_held(filter.as_ref(), page))
+}
+
+#[update]
+fn registry_child_take(agent_id: String) -> Result<AgentRegistration, String> {
+    Guards::check("registry_child_take")?;
+    RegistryShardService::require_parent(&ic_cdk::api::caller().to_text())?;
+    RegistryShardService::take_held(&agent_id)
+}
+
 #[query]
-fn get_agent_creation_status(request_id: String) -> Result<AgentCreationResult, String> {
-    Guards::require_caller_authenticated()?;
-    
-    let result = with_state(|state| {
-        state.agent_creation_results.get(&request_id).cloned()
-    });
-    
-    result.ok_or_else(|| "Agent creation request not found".to_string())
+fn get_recent_logs(level: Option<LogLevel>, module: Option<String>, limit: u32) -> Result<Vec<LogEntry>, String> {
+    Guards::check("get_recent_logs")?;
+    Ok(Log::recent(level, module.as_deref(), limit as usize))
 }
 
 #[update]
-async fn get_user_quota_status() -> Result<QuotaCheckResult, String> {
-    Guards::require_caller_authenticated()?;
-    let user_principal = ic_cdk::api::caller().to_string();
-    
-    // Sync quota from economics canister first
-    if let Err(e) = EconIntegrationService::sync_user_quota_from_economics(&user_principal).await {
-        ic_cdk::println!("Warning: Failed to sync quota from economics: {}", e);
-    }
-    
-    // Get actual user quota from state
-    let user_quota = with_state(|state| {
-        state.user_quotas.get(&user_principal).cloned()
-    });
-    
-    match user_quota {
-        Some(quota) => {
-            let current_agents = quota.current_usage.agents_created_this_month;
-            let remaining_agents = quota.limits.max_agents.saturating_sub(current_agents);
-            let quota_available = remaining_agents > 0 && 
-                                 current_agents < quota.limits.monthly_agent_creations;
-            
-            Ok(QuotaCheckResult {
-                quota_available,
-                remaining_agents,
-                monthly_limit: quota.limits.monthly_agent_creations,
-                tier: quota.subscription_tier,
-            })
-        },
-        None => {
-            // Create free subscription for new user via economics canister
-            match EconIntegrationService::get_or_create_free_subscription(&user_principal).await {
-                Ok(_subscription) => {
-                    // Retry getting quota after creating subscription
-                    EconIntegrationService::sync_user_quota_from_economics(&user_principal).await?;
-                    
-                    let user_quota = with_state(|state| {
-                        state.user_quotas.get(&user_principal).cloned()
-                    });
-                    
-                    if let Some(quota) = user_quota {
-                        let current_agents = quota.current_usage.agents_created_this_month;
-                        let remaining_agents = quota.limits.max_agents.saturating_sub(current_agents);
-                        let quota_available = remaining_agents > 0 && 
-                                             current_agents < quota.limits.monthly_agent_creations;
-                        
-                        Ok(QuotaCheckResult {
-                            quota_available,
-                            remaining_agents,
-                            monthly_limit: quota.limits.monthly_agent_creations,
-                            tier: quota.subscription_tier,
-                        })
-                    } else {
-                        Err("Failed to create user subscription".to_string())
-                    }
-                },
-                Err(e) => Err(format!("Failed to create free subscription: {}", e)),
-            }
-        }
-    }
+fn set_log_config(config: LogConfig) -> Result<(), String> {
+    Guards::check("set_log_config")?;
+    Log::configure(config.clone());
+    with_state_mut(|s| s.config.logging = config);
+    Ok(())
 }
 
 #[query]
-fn get_agent(agent_id: String) -> Result<AgentRegistration, String> {
-    Guards::require_caller_authenticated()?;
-    RegistryService::get_agent(&agent_id)
+fn get_log_config() -> Result<LogConfig, String> {
+    Guards::check("get_log_config")?;
+    Ok(with_state(|s| s.config.logging.clone()))
 }
 
-#[query]
-fn list_agents() -> Result<Vec<AgentRegistration>, String> {
-    Guards::require_caller_authenticated()?;
-    Ok(RegistryService::list_agents())
+#[update]
+async fn route_best_result(request: RouteRequest, top_k: u32, window_ms: u64) -> Result<RouteResponse, String> {
+    let started_at = ic_cdk::api::time();
+    Guards::check("route_best_result")?;
+    Guards::validate_msg_id(&request.request_id)?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    ShardService::ensure_local(&user_principal)?;
+    let decode_profile = PreferencesService::get_preferences(&user_principal)
+        .default_decode_profile
+        .unwrap_or_default();
+    let top_k = RoutingService::capability_policy(&request.capabilities_required)
+        .and_then(|(_, policy)| policy.top_k)
+        .unwrap_or(top_k);
+    let top_k = (top_k as usize).min(QuotaManager::fanout_cap(&user_principal));
+    // A retry only pays for agents that have not already answered this request
+    let request_sha256 = PartialResultService::request_hash(&request.payload, &request.capabilities_required);
+    let fresh_calls = top_k.saturating_sub(PartialResultService::cached_answers(&request.request_id, &request_sha256));
+    // The reviewer may be asked twice when a rejected answer is repaired
+    let review_calls = request.review.as_ref().map_or(0, |review| if review.repair_on_reject { 3 } else { 1 });
+    let reservation = SafetyLimitService::admit(&user_principal, |state| {
+        DispatchCost::inference(state, &request.capabilities_required, fresh_calls as u32, decode_profile)
+            + DispatchCost { spend_usd: 0.0, ..DispatchCost::inference(state, &[], review_calls, decode_profile) }
+    })?;
+    let result = RoutingService::fanout_best_result(&user_principal, request, top_k, window_ms, decode_profile).await;
+    SafetyLimitService::settle(reservation);
+    ServiceReportService::record_request(&user_principal, result.is_ok(), started_at);
+    result
 }
 
-#[query]
-fn list_user_agents() -> Result<Vec<AgentRegistration>, String> {
-    Guards::require_caller_authenticated()?;
+#[update]
+async fn route_hedged(request: RouteRequest, hedge_delay_ms: Option<u64>) -> Result<RouteResponse, String> {
+    let started_at = ic_cdk::api::time();
+    Guards::check("route_hedged")?;
+    Guards::validate_msg_id(&request.request_id)?;
     let user_principal = ic_cdk::api::caller().to_string();
-    
-    // Filter agents by user principal
-    let user_agents = with_state(|state| {
-        state.agents
-            .values()
-            .filter(|agent| agent.agent_principal == user_principal)
-            .cloned()
-            .collect::<Vec<_>>()
-    });
-    
-    Ok(user_agents)
+    ShardService::ensure_local(&user_principal)?;
+    let decode_profile = PreferencesService::get_preferences(&user_principal)
+        .default_decode_profile
+        .unwrap_or_default();
+    // Admitted for both calls, since the hedge may fire
+    let reservation = SafetyLimitService::admit(&user_principal, |state| DispatchCost::inference(state, &request.capabilities_required, 2, decode_profile))?;
+    let result = RoutingService::hedged_route(&user_principal, request, hedge_delay_ms.unwrap_or(RoutingService::DEFAULT_HEDGE_DELAY_MS), decode_profile).await;
+    SafetyLimitService::settle(reservation);
+    ServiceReportService::record_request(&user_principal, result.is_ok(), started_at);
+    result
 }
 
-#[query]
-fn list_instruction_requests() -> Result<Vec<InstructionRequest>, String> {
-    Guards::require_caller_authenticated()?;
+#[update]
+async fn submit_inference_job(request: RouteRequest) -> Result<InferenceJob, String> {
+    Guards::check("submit_inference_job")?;
+    Guards::validate_msg_id(&request.request_id)?;
     let user_principal = ic_cdk::api::caller().to_string();
-    
-    let requests = with_state(|state| {
-        state.instruction_requests
-            .values()
-            .filter(|req| req.user_principal == user_principal)
-            .cloned()
-            .collect::<Vec<_>>()
-    });
-    
-    Ok(requests)
+    let decode_profile = PreferencesService::get_preferences(&user_principal)
+        .default_decode_profile
+        .unwrap_or_default();
+    // The job runs after this returns, so its charge stays reserved for the day
+    SafetyLimitService::admit(&user_principal, |state| DispatchCost::inference(state, &request.capabilities_required, 1, decode_profile))?;
+    JobService::submit(&user_principal, request, decode_profile).await
+}
+
+#[update]
+fn report_job_progress(job_id: String, percent: u8, partial: Option<String>) -> Result<InferenceJob, String> {
+    Guards::check("report_job_progress")?;
+    let caller = ic_cdk::api::caller().to_string();
+    JobService::report_progress(&caller, &job_id, percent, partial)
+}
+
+#[update]
+fn report_job_failure(job_id: String, error: String) -> Result<InferenceJob, String> {
+    Guards::check("report_job_failure")?;
+    let caller = ic_cdk::api::caller().to_string();
+    JobService::report_failure(&caller, &job_id, error)
 }
 
 #[query]
-fn health() -> CoordinatorHealth {
-    RegistryService::get_health()
+fn get_inference_job(job_id: String) -> Result<InferenceJob, String> {
+    Guards::check("get_inference_job")?;
+    let job = JobService::get(&job_id).ok_or_else(|| "Job not found".to_string())?;
+    if job.requester != ic_cdk::api::caller().to_string() {
+        Guards::require_admin()?;
+    }
+    Ok(job)
 }
 
 #[query]
-fn get_routing_stats(agent_id: Option<String>) -> Result<Vec<RoutingStats>, String> {
-    Guards::require_caller_authenticated()?;
-    Ok(RoutingService::get_stats(agent_id))
+fn get_verification_evidence(request_id: String) -> Result<VerificationRecord, String> {
+    Guards::check("get_verification_evidence")?;
+    ReviewService::get_evidence(&request_id).ok_or_else(|| "No review recorded for request".to_string())
+}
+
+#[query]
+fn get_result_provenance(request_id: String) -> Result<ResultProvenance, String> {
+    Guards::check("get_result_provenance")?;
+    ProvenanceService::get(&request_id).ok_or_else(|| "No provenance recorded for request".to_string())
 }
 
 #[update]
-fn update_agent_health(agent_id: String, health_score: f32) -> Result<(), String> {
-    Guards::require_caller_authenticated()?;
-    RegistryService::update_agent_health(agent_id, health_score)
+async fn route_map_reduce(request: MapReduceRequest) -> Result<MapReduceResult, String> {
+    Guards::check("route_map_reduce")?;
+    Guards::validate_msg_id(&request.route.request_id)?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let decode_profile = PreferencesService::get_preferences(&user_principal)
+        .default_decode_profile
+        .unwrap_or_default();
+    let calls = MapReduceService::planned_calls(&request)?;
+    let reservation = SafetyLimitService::admit(&user_principal, |state| {
+        DispatchCost::inference(state, &request.route.capabilities_required, calls - 1, decode_profile)
+            + DispatchCost::inference(state, &request.reducer_capabilities, 1, decode_profile)
+    })?;
+    let result = MapReduceService::run(request, decode_profile).await;
+    SafetyLimitService::settle(reservation);
+    result
 }
 
 #[update]
-async fn set_swarm_policy(policy: SwarmPolicy) -> Result<(), String> {
-    Guards::require_caller_authenticated()?;
-    with_state_mut(|s| { s.config.swarm = policy; });
-    Ok(())
+async fn route_chain(steps: Vec<ChainStep>) -> Result<ChainResult, String> {
+    Guards::check("route_chain")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let decode_profile = PreferencesService::get_preferences(&user_principal)
+        .default_decode_profile
+        .unwrap_or_default();
+    let reservation = SafetyLimitService::admit(&user_principal, |state| ChainService::estimate_cost(state, &steps, decode_profile))?;
+    let result = ChainService::run(&user_principal, steps, decode_profile).await;
+    SafetyLimitService::settle(reservation);
+    result
+}
+
+#[update]
+async fn analyze_instructions(instructions: String) -> Result<InstructionAnalysisResult, String> {
+    Guards::check("analyze_instructions")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    
+    // Stamp against the same synced quota that spawning will see
+    EconIntegrationService::sync_user_quota_from_economics(&user_principal).await?;
+    let analysis = InstructionAnalyzerService::analyze_instructions(&instructions, &user_principal)?;
+    InstructionAnalyzerService::store_analysis(&user_principal, &instructions, &analysis, None);
+    Ok(analysis)
 }
 
 #[query]
-fn get_swarm_policy() -> SwarmPolicy {
-    with_state(|s| s.config.swarm.clone())
+fn validate_instructions(instructions: String) -> Result<InstructionValidation, String> {
+    Guards::check("validate_instructions")?;
+    Ok(InstructionAnalyzerService::validate_instructions(&instructions))
 }
 
+/// Spawn exactly the plan of a stored `analyze_instructions` result
 #[update]
-async fn route_best_result(request: RouteRequest, top_k: u32, window_ms: u64) -> Result<RouteResponse, String> {
-    Guards::require_caller_authenticated()?;
-    Guards::validate_msg_id(&request.request_id)?;
-    RoutingService::fanout_best_result(request, top_k as usize, window_ms).await
+async fn spawn_from_analysis(request_id: String, accept_downscaled: bool) -> Result<String, String> {
+    Guards::check("spawn_from_analysis")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    let stored = InstructionAnalyzerService::claim_analysis(&user_principal, &request_id)?;
+    let submission = InstructionSubmission {
+        instructions: stored.instructions,
+        agent_count: None,
+        callback: None,
+        tags: vec![],
+        accept_downscaled,
+        expected_state_version: Some(stored.analysis.state_version),
+    };
+    let result = create_agents_for_user(&user_principal, submission, Some(stored.analysis)).await;
+    if result.is_err() {
+        InstructionAnalyzerService::release_analysis(&request_id);
+    }
+    result
 }
 
 #[query]
 fn get_instruction_analysis(request_id: String) -> Result<InstructionAnalysisResult, String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("get_instruction_analysis")?;
+    if let Some(analysis) = InstructionAnalyzerService::get_stored_analysis(&request_id) {
+        return Ok(analysis);
+    }
     
-    // Get the instruction request
+    // Requests from before analyses were stored: get the instruction request
     let instruction_request = with_state(|state| {
         state.instruction_requests.get(&request_id).cloned()
     });
@@ -239,9 +1627,73 @@ fn get_instruction_analysis(request_id: String) -> Result<InstructionAnalysisRes
     InstructionAnalyzerService::analyze_instructions(&instruction_request.instructions, &instruction_request.user_principal)
 }
 
+#[query]
+fn list_specialization_roles() -> Result<Vec<SpecializationRole>, String> {
+    Guards::check("list_specialization_roles")?;
+    Ok(SpecializationService::list_roles())
+}
+
+#[query]
+fn list_specialization_roles_paged(page: Option<PageRequest>) -> Result<Page<SpecializationRole>, String> {
+    Guards::check("list_specialization_roles_paged")?;
+    let items = SpecializationService::list_roles();
+    Ok(Paginator::paginate(&items, |r| r.name.clone(), PageOrder::Ascending, page))
+}
+
+#[query]
+fn get_specialization_fallbacks(specialization: String) -> Result<Vec<String>, String> {
+    Guards::check("get_specialization_fallbacks")?;
+    SpecializationService::resolve(&specialization)
+        .ok_or_else(|| format!("Unknown specialization: {}", specialization))?;
+    Ok(SpecializationService::fallbacks(&specialization))
+}
+
+#[update]
+fn set_specialization_role(role: SpecializationRole) -> Result<(), String> {
+    Guards::check("set_specialization_role")?;
+    SpecializationService::set_role(role)
+}
+
+#[query]
+fn list_capability_taxonomy() -> Result<Vec<CapabilityEdge>, String> {
+    Guards::check("list_capability_taxonomy")?;
+    Ok(RegistryService::list_capability_taxonomy())
+}
+
+#[query]
+fn list_capability_taxonomy_paged(page: Option<PageRequest>) -> Result<Page<CapabilityEdge>, String> {
+    Guards::check("list_capability_taxonomy_paged")?;
+    let items = RegistryService::list_capability_taxonomy();
+    Ok(Paginator::paginate(&items, |e| e.capability.clone(), PageOrder::Ascending, page))
+}
+
+#[update]
+fn set_capability_parent(capability: String, parent: Option<String>) -> Result<(), String> {
+    Guards::check("set_capability_parent")?;
+    RegistryService::set_capability_parent(&capability, parent.as_deref())
+}
+
+#[update]
+fn set_warm_pool_config(config: WarmPoolConfig) -> Result<WarmPoolStatus, String> {
+    Guards::check("set_warm_pool_config")?;
+    WarmPoolService::set_config(config)
+}
+
+#[query]
+fn get_warm_pool_status() -> Result<WarmPoolStatus, String> {
+    Guards::check("get_warm_pool_status")?;
+    Ok(WarmPoolService::status())
+}
+
+#[update]
+fn refill_warm_pool() -> Result<WarmPoolStatus, String> {
+    Guards::check("refill_warm_pool")?;
+    Ok(WarmPoolService::replenish())
+}
+
 #[update]
 async fn update_agent_status(agent_id: String, status: String) -> Result<(), String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("update_agent_status")?;
     let user_principal = ic_cdk::api::caller().to_string();
     
     // Verify agent belongs to user
@@ -259,16 +1711,19 @@ async fn update_agent_status(agent_id: String, status: String) -> Result<(), Str
     let agent_status = match status.as_str() {
         "ready" => crate::services::agent_spawning::AgentStatus::Ready,
         "active" => crate::services::agent_spawning::AgentStatus::Active,
+        "provisioning" => crate::services::agent_spawning::AgentStatus::Initializing,
+        "draining" => crate::services::agent_spawning::AgentStatus::Draining,
+        "decommissioned" => crate::services::agent_spawning::AgentStatus::Decommissioned,
         "error" => crate::services::agent_spawning::AgentStatus::Error,
-        _ => return Err("Invalid status. Must be 'ready', 'active', or 'error'".to_string()),
+        _ => return Err("Invalid status. Must be 'provisioning', 'ready', 'active', 'draining', 'decommissioned', or 'error'".to_string()),
     };
     
-    AgentSpawningService::update_agent_status(&agent_id, agent_status)
+    AgentSpawningService::update_agent_status(&user_principal, &agent_id, agent_status)
 }
 
 #[query]
 fn get_agent_spawning_metrics() -> Result<AgentSpawningMetrics, String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("get_agent_spawning_metrics")?;
     let user_principal = ic_cdk::api::caller().to_string();
     
     let metrics = with_state(|state| {
@@ -280,14 +1735,26 @@ fn get_agent_spawning_metrics() -> Result<AgentSpawningMetrics, String> {
         let active_agents = state.agents.values()
             .filter(|agent| agent.agent_principal == user_principal && agent.health_score > 0.5)
             .count() as u32;
+        let average_creation_time_ms = if total_creations == 0 {
+            0
+        } else {
+            state.agent_creation_results.values().map(|r| r.creation_time_ms).sum::<u64>() / total_creations as u64
+        };
+        let completed = state.agent_creation_results.values()
+            .filter(|r| r.status == AgentCreationStatus::Completed)
+            .count();
+        let failed = state.agent_creation_results.values()
+            .filter(|r| r.status == AgentCreationStatus::Failed)
+            .count();
+        let success_rate = if completed + failed == 0 { 1.0 } else { completed as f32 / (completed + failed) as f32 };
         
         AgentSpawningMetrics {
             total_instruction_requests: total_requests,
             total_agent_creations: total_creations,
             user_agents_created: user_agents,
             user_active_agents: active_agents,
-            average_creation_time_ms: 1500, // Real average from actual data
-            success_rate: 0.95, // Real success rate
+            average_creation_time_ms,
+            success_rate,
         }
     });
     
@@ -296,7 +1763,13 @@ fn get_agent_spawning_metrics() -> Result<AgentSpawningMetrics, String> {
 
 #[query]
 fn get_coordination_networks() -> Result<Vec<CoordinationNetworkInfo>, String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("get_coordination_networks")?;
+    get_coordination_networks_paged(PageRequest::capped()).map(|page| page.items)
+}
+
+#[query]
+fn get_coordination_networks_paged(page: Option<PageRequest>) -> Result<Page<CoordinationNetworkInfo>, String> {
+    Guards::check("get_coordination_networks_paged")?;
     let user_principal = ic_cdk::api::caller().to_string();
     
     let networks = with_state(|state| {
@@ -324,12 +1797,49 @@ fn get_coordination_networks() -> Result<Vec<CoordinationNetworkInfo>, String> {
         }
     });
     
-    Ok(networks)
+    Ok(Paginator::paginate(&networks, |n| n.network_id.clone(), PageOrder::Ascending, page))
+}
+
+#[query]
+fn get_concurrency_usage() -> Result<ConcurrencyUsage, String> {
+    Guards::check("get_concurrency_usage")?;
+    let user_principal = ic_cdk::api::caller().to_string();
+    Ok(ConcurrencyService::usage(&user_principal))
+}
+
+#[update]
+fn set_safety_limits(limits: SafetyLimits) -> Result<SafetyLimitStatus, String> {
+    Guards::check("set_safety_limits")?;
+    SafetyLimitService::set_limits(&ic_cdk::api::caller().to_string(), limits)
+}
+
+#[query]
+fn get_safety_limits() -> Result<SafetyLimitStatus, String> {
+    Guards::check("get_safety_limits")?;
+    Ok(SafetyLimitService::status(&ic_cdk::api::caller().to_string()))
+}
+
+#[update]
+fn configure_siem_export(config: SiemExportConfig) -> Result<SiemExportStatus, String> {
+    Guards::check("configure_siem_export")?;
+    SiemExportService::configure(&ic_cdk::api::caller().to_string(), config)
+}
+
+#[query]
+fn get_siem_export_status() -> Result<SiemExportStatus, String> {
+    Guards::check("get_siem_export_status")?;
+    SiemExportService::status(&ic_cdk::api::caller().to_string())
+}
+
+/// Transform for SIEM export outcalls, invoked by the management canister
+#[query]
+fn siem_transform(args: TransformArgs) -> HttpResponse {
+    SiemExportService::transform(args)
 }
 
 #[update]
 async fn upgrade_subscription_tier(tier: String) -> Result<(), String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("upgrade_subscription_tier")?;
     let user_principal = ic_cdk::api::caller().to_string();
     
     // Validate tier
@@ -345,34 +1855,9 @@ async fn upgrade_subscription_tier(tier: String) -> Result<(), String> {
             quota.last_updated = ic_cdk::api::time();
             
             // Update limits based on tier
-            let new_limits = match tier.as_str() {
-                "Free" => crate::services::quota_manager::QuotaLimits {
-                    max_agents: 3,
-                    monthly_agent_creations: 5,
-                    token_limit: 1024,
-                    inference_rate: crate::services::quota_manager::InferenceRate::Standard,
-                },
-                "Basic" => crate::services::quota_manager::QuotaLimits {
-                    max_agents: 10,
-                    monthly_agent_creations: 15,
-                    token_limit: 2048,
-                    inference_rate: crate::services::quota_manager::InferenceRate::Standard,
-                },
-                "Pro" => crate::services::quota_manager::QuotaLimits {
-                    max_agents: 25,
-                    monthly_agent_creations: 25,
-                    token_limit: 4096,
-                    inference_rate: crate::services::quota_manager::InferenceRate::Priority,
-                },
-                "Enterprise" => crate::services::quota_manager::QuotaLimits {
-                    max_agents: 100,
-                    monthly_agent_creations: 100,
-                    token_limit: 8192,
-                    inference_rate: crate::services::quota_manager::InferenceRate::Premium,
-                },
-                _ => quota.limits.clone(),
-            };
-            quota.limits = new_limits;
+            if let Some(new_limits) = QuotaManager::limits_for_tier(&tier) {
+                quota.limits = new_limits;
+            }
         }
     });
     
@@ -380,11 +1865,18 @@ async fn upgrade_subscription_tier(tier: String) -> Result<(), String> {
     Ok(())
 }
 
+#[query]
+fn get_usage_history(months: u32) -> Result<Vec<UsageSnapshot>, String> {
+    Guards::check("get_usage_history")?;
+    Ok(QuotaManager::get_usage_history(&ic_cdk::api::caller().to_string(), months))
+}
+
 #[query]
 fn get_subscription_tier_info() -> Result<SubscriptionTierInfo, String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("get_subscription_tier_info")?;
     let user_principal = ic_cdk::api::caller().to_string();
     
+    let coordination = QuotaManager::coordination_usage(&user_principal);
     let tier_info = with_state(|state| {
         if let Some(quota) = state.user_quotas.get(&user_principal) {
             SubscriptionTierInfo {
@@ -396,6 +1888,12 @@ fn get_subscription_tier_info() -> Result<SubscriptionTierInfo, String> {
                 agents_created_this_month: quota.current_usage.agents_created_this_month,
                 tokens_used_this_month: quota.current_usage.tokens_used_this_month,
                 last_reset_date: quota.current_usage.last_reset_date,
+                max_active_sessions: coordination.max_active_sessions,
+                active_sessions: coordination.active_sessions,
+                daily_messages: coordination.daily_messages,
+                messages_today: coordination.messages_today,
+                artifact_storage_bytes: coordination.artifact_storage_bytes,
+                artifact_bytes_stored: coordination.artifact_bytes_stored,
             }
         } else {
             // Default tier info for new users
@@ -408,6 +1906,12 @@ fn get_subscription_tier_info() -> Result<SubscriptionTierInfo, String> {
                 agents_created_this_month: 0,
                 tokens_used_this_month: 0,
                 last_reset_date: ic_cdk::api::time(),
+                max_active_sessions: coordination.max_active_sessions,
+                active_sessions: coordination.active_sessions,
+                daily_messages: coordination.daily_messages,
+                messages_today: coordination.messages_today,
+                artifact_storage_bytes: coordination.artifact_storage_bytes,
+                artifact_bytes_stored: coordination.artifact_bytes_stored,
             }
         }
     });
@@ -417,13 +1921,197 @@ fn get_subscription_tier_info() -> Result<SubscriptionTierInfo, String> {
 
 #[update]
 async fn get_economics_health() -> Result<EconHealth, String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("get_economics_health")?;
     EconIntegrationService::get_economics_health().await
 }
 
+#[update]
+async fn sync_capability_pricing() -> Result<u32, String> {
+    Guards::check("sync_capability_pricing")?;
+    EconIntegrationService::sync_capability_pricing().await
+}
+
+#[query]
+fn get_capability_pricing() -> Vec<CapabilityPrice> {
+    EconIntegrationService::get_capability_pricing()
+}
+
 #[update]
 async fn validate_token_usage_quota(tokens: u64) -> Result<QuotaValidation, String> {
-    Guards::require_caller_authenticated()?;
+    Guards::check("validate_token_usage_quota")?;
     let user_principal = ic_cdk::api::caller().to_string();
     EconIntegrationService::validate_token_usage_quota(&user_principal, tokens).await
-}
\ No newline at end of file
+}
+
+#[query]
+fn get_econ_fallback_status() -> Result<EconFallbackStatus, String> {
+    Guards::check("get_econ_fallback_status")?;
+    Ok(EconFallbackService::status())
+}
+
+#[query]
+fn get_interface_version() -> InterfaceVersion {
+    InterfaceVersion {
+        package_version: env!("CARGO_PKG_VERSION").to_string(),
+        interface_hash: interface_hash(),
+    }
+}
+
+#[query]
+fn describe_coordinator() -> CoordinatorDescription {
+    let interface = get_interface_version();
+    DiscoveryService::describe(interface, with_state(|s| s.config.operational_mode))
+}
+
+#[update]
+fn set_feature_flag(name: String, enabled: bool) -> Result<(), String> {
+    Guards::check("set_feature_flag")?;
+    FeatureFlagService::set(&name, enabled)
+}
+
+#[query]
+fn list_feature_flags() -> Vec<FeatureFlagState> {
+    FeatureFlagService::list()
+}
+
+// Load testing: synthetic agents, routing traffic and sessions for test deployments only
+
+#[cfg(feature = "load-test")]
+#[update]
+fn load_test_register_agents(count: u32, capabilities: Vec<String>) -> Result<u32, String> {
+    Guards::check("load_test_register_agents")?;
+    LoadTestService::register_agents(count, capabilities)
+}
+
+#[cfg(feature = "load-test")]
+#[update]
+fn load_test_start(profile: LoadProfile) -> Result<LoadTestStatus, String> {
+    Guards::check("load_test_start")?;
+    LoadTestService::start(profile)
+}
+
+#[cfg(feature = "load-test")]
+#[update]
+fn load_test_stop() -> Result<LoadTestStatus, String> {
+    Guards::check("load_test_stop")?;
+    Ok(LoadTestService::stop())
+}
+
+#[cfg(feature = "load-test")]
+#[query]
+fn load_test_status() -> Result<LoadTestStatus, String> {
+    Guards::check("load_test_status")?;
+    Ok(LoadTestService::status())
+}
+
+#[cfg(feature = "load-test")]
+#[update]
+fn load_test_teardown() -> Result<LoadTestStatus, String> {
+    Guards::check("load_test_teardown")?;
+    Ok(LoadTestService::teardown())
+}
+
+/// Period of the maintenance timer; each job also keeps its own interval on top of this
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(2);
+
+#[init]
+fn init() {
+    start_maintenance_timer();
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    match PersistenceService::save() {
+        Ok(bytes) => Log::info("upgrade", format!("Saved {} bytes of coordinator state for upgrade", bytes)),
+        Err(e) => ic_cdk::trap(&e),
+    }
+}
+
+#[post_upgrade]
+fn post_upgrade() {
+    match PersistenceService::restore() {
+        Ok(true) => Metrics::increment_counter("state_restored_after_upgrade"),
+        Ok(false) => Log::info("upgrade", "No saved coordinator state; starting empty"),
+        Err(e) => ic_cdk::trap(&e),
+    }
+    CertificationService::certify();
+    // Timers do not survive an upgrade
+    start_maintenance_timer();
+}
+
+fn start_maintenance_timer() {
+    ic_cdk_timers::set_timer_interval(MAINTENANCE_INTERVAL, maintenance_tick);
+}
+
+/// Periodic jobs, run from a timer rather than the canister heartbeat so the coordinator
+/// is not invoked every round
+fn maintenance_tick() {
+    LivenessService::tick();
+    StandbyService::tick();
+    SiemExportService::tick();
+    CertificationService::certify();
+    ShardService::tick();
+    RoundService::tick();
+    RegistryEventService::tick();
+    RoutingService::drain_queue();
+    WarmPoolService::tick();
+    SloService::tick();
+    SafetyLimitService::tick();
+    #[cfg(feature = "load-test")]
+    LoadTestService::tick();
+}
+
+/// SHA-256 of the Candid service generated from the Rust endpoint signatures
+fn interface_hash() -> String {
+    Sha256::digest(__export_service().as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// Must stay below every endpoint so they are all collected into the exported service
+ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::guards::GuardAccess;
+    use candid_parser::utils::CandidSource;
+
+    // Load-test builds export extra endpoints that are not part of the published interface
+    #[cfg(not(feature = "load-test"))]
+    #[test]
+    fn test_candid_interface_matches_did_file() {
+        use candid_parser::utils::service_equal;
+        use std::path::Path;
+
+        let generated = __export_service();
+        let did_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/ohms_coordinator.did");
+        service_equal(CandidSource::Text(&generated), CandidSource::File(&did_path))
+            .unwrap_or_else(|e| panic!("src/ohms_coordinator.did is out of sync with the Rust interface: {}", e));
+    }
+
+    #[test]
+    fn test_every_endpoint_declares_its_guards() {
+        let generated = __export_service();
+        let (env, actor) = CandidSource::Text(&generated).load().unwrap();
+        let actor = actor.unwrap();
+        let methods = env.as_service(&actor).unwrap();
+        let source = include_str!("api.rs");
+        for (method, _) in methods {
+            let guard = Guards::guard_for(method)
+                .unwrap_or_else(|| panic!("{} has no entry in ENDPOINT_GUARDS", method));
+            // Public endpoints return bare values and have nothing to refuse
+            if guard.access != GuardAccess::Public {
+                assert!(source.contains(&format!("Guards::check(\"{}\")", method)), "{} does not run its guard chain", method);
+            }
+        }
+        assert_eq!(methods.len(), ENDPOINT_GUARDS.len(), "ENDPOINT_GUARDS lists endpoints that do not exist");
+    }
+
+    #[test]
+    fn test_interface_hash_is_stable() {
+        assert_eq!(interface_hash(), interface_hash());
+        assert_eq!(interface_hash().len(), 64);
+    }
+}
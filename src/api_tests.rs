@@ -45,11 +45,16 @@ mod tests {
                 monthly_agent_creations: 15,
                 token_limit: 2048,
                 inference_rate: quota_manager::InferenceRate::Standard,
+                monthly_weighted_units: 30,
             },
             current_usage: quota_manager::QuotaUsage {
                 agents_created_this_month: 5,
                 tokens_used_this_month: 1000,
                 inferences_this_month: 50,
+                weighted_units_used_this_month: 0,
+                creations_refunded_this_month: 0,
+                offline_creations_this_month: 0,
+                creations_reserved: 0,
                 last_reset_date: time(),
             },
             last_updated: time(),
@@ -85,6 +90,11 @@ mod tests {
                 health_score: 1.0,
                 registered_at: time(),
                 last_seen: time(),
+                tags: Default::default(),
+                agent_version: None,
+                protocol_version: None,
+                state: None,
+                lifecycle: None,
             },
             AgentRegistration {
                 agent_id: "agent2".to_string(),
@@ -95,6 +105,11 @@ mod tests {
                 health_score: 0.8,
                 registered_at: time(),
                 last_seen: time(),
+                tags: Default::default(),
+                agent_version: None,
+                protocol_version: None,
+                state: None,
+                lifecycle: None,
             },
         ];
         
@@ -107,6 +122,11 @@ mod tests {
             health_score: 0.9,
             registered_at: time(),
             last_seen: time(),
+            tags: Default::default(),
+            agent_version: None,
+            protocol_version: None,
+            state: None,
+            lifecycle: None,
         };
         
         with_state_mut(|state| {
@@ -140,6 +160,8 @@ mod tests {
             agent_count: Some(2),
             model_preferences: vec!["llama".to_string()],
             created_at: time(),
+            tags: vec![],
+            analysis_id: None,
         };
         
         let request2 = InstructionRequest {
@@ -149,6 +171,8 @@ mod tests {
             agent_count: Some(1),
             model_preferences: vec!["mistral".to_string()],
             created_at: time(),
+            tags: vec![],
+            analysis_id: None,
         };
         
         // Add agent creation results
@@ -157,6 +181,7 @@ mod tests {
             created_agents: vec!["agent1".to_string(), "agent2".to_string()],
             creation_time_ms: 1500,
             status: AgentCreationStatus::Completed,
+            coordination_network_id: None,
         };
         
         // Add agents
@@ -169,6 +194,11 @@ mod tests {
             health_score: 1.0,
             registered_at: time(),
             last_seen: time(),
+            tags: Default::default(),
+            agent_version: None,
+            protocol_version: None,
+            state: None,
+            lifecycle: None,
         };
         
         let agent2 = AgentRegistration {
@@ -180,6 +210,11 @@ mod tests {
             health_score: 0.5, // Below threshold
             registered_at: time(),
             last_seen: time(),
+            tags: Default::default(),
+            agent_version: None,
+            protocol_version: None,
+            state: None,
+            lifecycle: None,
         };
         
         with_state_mut(|state| {
@@ -200,7 +235,7 @@ mod tests {
         assert_eq!(metrics.user_agents_created, 2);
         assert_eq!(metrics.user_active_agents, 1); // Only agent1 has health_score > 0.5
         assert_eq!(metrics.average_creation_time_ms, 1500);
-        assert_eq!(metrics.success_rate, 0.95);
+        assert_eq!(metrics.success_rate, 1.0);
     }
 
     #[test]
@@ -217,11 +252,16 @@ mod tests {
                 monthly_agent_creations: 100,
                 token_limit: 8192,
                 inference_rate: quota_manager::InferenceRate::Premium,
+                monthly_weighted_units: 200,
             },
             current_usage: quota_manager::QuotaUsage {
                 agents_created_this_month: 25,
                 tokens_used_this_month: 4000,
                 inferences_this_month: 200,
+                weighted_units_used_this_month: 0,
+                creations_refunded_this_month: 0,
+                offline_creations_this_month: 0,
+                creations_reserved: 0,
                 last_reset_date: time(),
             },
             last_updated: time(),
@@ -259,11 +299,16 @@ mod tests {
                 monthly_agent_creations: 15,
                 token_limit: 2048,
                 inference_rate: quota_manager::InferenceRate::Standard,
+                monthly_weighted_units: 30,
             },
             current_usage: quota_manager::QuotaUsage {
                 agents_created_this_month: 5,
                 tokens_used_this_month: 1000,
                 inferences_this_month: 50,
+                weighted_units_used_this_month: 0,
+                creations_refunded_this_month: 0,
+                offline_creations_this_month: 0,
+                creations_reserved: 0,
                 last_reset_date: time(),
             },
             last_updated: time(),
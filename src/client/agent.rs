@@ -0,0 +1,27 @@
+use super::{CallKind, ClientError, OhmsCoordinator};
+use candid::Principal;
+use ic_agent::Agent;
+
+/// Calls the coordinator from outside the IC through an agent-rs `Agent`, which carries
+/// the caller identity and the replica URL. Update calls wait for the certified reply.
+#[derive(Clone)]
+pub struct AgentClient {
+    agent: Agent,
+    canister_id: Principal,
+}
+
+impl AgentClient {
+    pub fn new(agent: Agent, canister_id: Principal) -> Self {
+        Self { agent, canister_id }
+    }
+}
+
+impl OhmsCoordinator for AgentClient {
+    async fn call_raw(&self, method: &str, kind: CallKind, arg: Vec<u8>) -> Result<Vec<u8>, ClientError> {
+        let reply = match kind {
+            CallKind::Query => self.agent.query(&self.canister_id, method).with_arg(arg).call().await,
+            CallKind::Update => self.agent.update(&self.canister_id, method).with_arg(arg).call_and_wait().await,
+        };
+        reply.map_err(|e| ClientError::Rejected { method: method.to_string(), message: e.to_string() })
+    }
+}
@@ -0,0 +1,420 @@
+//! Typed client for the coordinator's Candid interface. `OhmsCoordinator` has one method
+//! per exported endpoint with the endpoint's own argument and result types, so other OHMS
+//! canisters and off-chain Rust services call it with compile-time checked types instead of
+//! mirroring them by hand. The methods are generated from one endpoint table passed to
+//! `coordinator_client!`, and a test renders that table as Candid and checks it against
+//! `ohms_coordinator.did`. Implementors only supply `call_raw`; `CanisterClient` does so with
+//! inter-canister calls, and `AgentClient` (feature `agent-client`) through agent-rs.
+//!
+//! Depend on this crate with `default-features = false` so the coordinator's own endpoints
+//! are not exported from the calling canister.
+
+use crate::domain::*;
+use crate::services::delivery::DeadLetter;
+use crate::services::tag_analytics::TagReport;
+use crate::services::service_report::SignedServiceReport;
+use crate::services::agent_load::AgentLoadReport;
+use crate::services::registry_events::RegistrySubscription;
+use crate::services::capacity_forecast::CapacityForecast;
+use crate::services::certification::CertifiedAgent;
+use crate::services::workload::AgentWorkload;
+use crate::services::programs::{Program, ProgramStatus};
+use crate::services::preferences::UserPreferences;
+use crate::services::quota_manager::UsageSnapshot;
+use crate::services::econ_integration::CapabilityPrice;
+use crate::services::econ_fallback::EconFallbackStatus;
+use crate::services::feature_flags::FeatureFlagState;
+use crate::services::sharding::{ShardAssignment, ShardConfig, ShardStatus};
+use crate::services::registry_shards::{RegistryShardConfig, RegistryShardStatus};
+use crate::services::rounds::{CoordinationRound, RoundTaskSpec};
+use crate::services::audit::AuditEntry;
+use crate::services::notifications::Notification;
+use crate::services::reclamation::{ReclamationPolicy, ReclamationReport};
+use crate::services::liveness::LivenessPolicy;
+use crate::services::siem_export::{SiemExportConfig, SiemExportStatus};
+use crate::services::availability::{AvailabilityWindow, MaintenanceStatus, MaintenanceWindow};
+use crate::services::autonomous_coord::{AgentMessage, CoordinationPreferences, CoordinationStats, TaskLedgerEntry};
+use crate::services::batches::InstructionBatch;
+use crate::services::cancellation::CancellationRecord;
+use crate::services::secrets::SecretMetadata;
+use crate::services::review::VerificationRecord;
+use crate::services::snapshot::{SnapshotChunk, SnapshotImportProgress};
+use crate::services::onboarding::AgentApplication;
+use crate::services::jobs::InferenceJob;
+use crate::services::slo::{AdminOverview, SpawnSloTargets};
+use crate::services::discovery::CoordinatorDescription;
+use crate::services::specializations::SpecializationRole;
+use crate::services::provenance::ResultProvenance;
+use crate::services::warm_pool::{WarmPoolConfig, WarmPoolStatus};
+use crate::services::concurrency::ConcurrencyUsage;
+use crate::services::coordination_preferences::AgentCoordinationPreferences;
+#[cfg(feature = "load-test")]
+use crate::services::load_test::{LoadProfile, LoadTestStatus};
+use crate::services::session_templates::{RoleBinding, SessionTemplate, SessionTemplateSpec, TemplateSessionStart};
+use crate::services::map_reduce::{MapReduceRequest, MapReduceResult};
+use crate::services::chains::{ChainResult, ChainStep};
+use crate::services::quarantine::QuarantineRecord;
+use crate::services::standby::StandbyAssignment;
+use crate::services::registry::BatchRegistrationResult;
+use crate::services::registry_audit::{RegistryAuditEntry, RegistryAuditRange};
+use crate::services::reputation::{AgentReputation, DisputeStatus, VerificationDispute};
+use crate::services::safety_limits::{SafetyLimitStatus, SafetyLimits};
+use crate::services::routing_strategies::RoutingStrategyInfo;
+use crate::infra::{Page, PageRequest};
+use crate::infra::guards::EndpointGuardInfo;
+use crate::infra::logging::LogEntry;
+use candid::utils::ArgumentEncoder;
+use candid::{CandidType, Principal};
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "agent-client")]
+mod agent;
+#[cfg(feature = "agent-client")]
+pub use agent::AgentClient;
+
+/// How a method is called; queries are answered by a single replica without consensus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Query,
+    Update,
+}
+
+/// Failure to reach the coordinator or to read its reply. An endpoint refusing a request
+/// is not a `ClientError`: it arrives as the endpoint's own `Err`.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ClientError {
+    #[error("call to {method} rejected: {message}")]
+    Rejected { method: String, message: String },
+    #[error("candid error on {method}: {message}")]
+    Candid { method: String, message: String },
+}
+
+/// Maps an interface entry's mode to how it is called; composite queries are queries to callers
+macro_rules! call_kind {
+    (query) => { CallKind::Query };
+    (composite_query) => { CallKind::Query };
+    (update) => { CallKind::Update };
+}
+
+#[cfg(all(test, not(feature = "load-test")))]
+macro_rules! func_modes {
+    (query) => { vec![candid::types::FuncMode::Query] };
+    (composite_query) => { vec![candid::types::FuncMode::CompositeQuery] };
+    (update) => { vec![] };
+}
+
+/// Declares the client from one table of endpoints. Each entry becomes a typed trait method,
+/// and in tests the same table is rendered as a Candid service and compared with
+/// `ohms_coordinator.did`, so names, argument and result types, and query/update modes cannot
+/// drift from the published interface.
+macro_rules! coordinator_client {
+    ($($(#[cfg($cfg:meta)])? $kind:ident $name:ident($($arg:ident: $ty:ty),* $(,)?) -> $ret:ty;)*) => {
+        /// The coordinator's public interface, checked against `ohms_coordinator.did`
+        #[allow(async_fn_in_trait)]
+        pub trait OhmsCoordinator {
+            /// Send Candid-encoded arguments to `method` and return the Candid-encoded reply
+            async fn call_raw(&self, method: &str, kind: CallKind, arg: Vec<u8>) -> Result<Vec<u8>, ClientError>;
+
+            async fn call_typed<A: ArgumentEncoder, R: CandidType + DeserializeOwned>(
+                &self,
+                method: &str,
+                kind: CallKind,
+                args: A,
+            ) -> Result<R, ClientError> {
+                let candid_error = |e: candid::Error| ClientError::Candid { method: method.to_string(), message: e.to_string() };
+                let arg = candid::utils::encode_args(args).map_err(candid_error)?;
+                let reply = self.call_raw(method, kind, arg).await?;
+                candid::decode_one(&reply).map_err(candid_error)
+            }
+
+            $(
+                $(#[cfg($cfg)])?
+                async fn $name(&self, $($arg: $ty),*) -> Result<$ret, ClientError> {
+                    self.call_typed(stringify!($name), call_kind!($kind), ($($arg,)*)).await
+                }
+            )*
+        }
+
+        /// The client's interface rendered as a Candid service
+        #[cfg(all(test, not(feature = "load-test")))]
+        fn client_service() -> String {
+            use candid::types::internal::TypeContainer;
+            use candid::types::{Function, Type, TypeInner};
+
+            let mut env = TypeContainer::new();
+            let mut methods: Vec<(String, Type)> = Vec::new();
+            $(
+                $(#[cfg($cfg)])?
+                {
+                    let func = Function {
+                        modes: func_modes!($kind),
+                        args: vec![$(env.add::<$ty>()),*],
+                        rets: vec![env.add::<$ret>()],
+                    };
+                    methods.push((stringify!($name).to_string(), TypeInner::Func(func).into()));
+                }
+            )*
+            methods.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            let actor: Type = TypeInner::Service(methods).into();
+            candid::pretty::candid::compile(&env.env, &Some(actor))
+        }
+    };
+}
+
+coordinator_client! {
+    update register_agent(registration: AgentRegistration, coordination_preferences: Option<CoordinationPreferences>, update_existing: Option<bool>) -> Result<String, String>;
+    update register_agents_batch(registrations: Vec<AgentRegistration>) -> Result<Vec<BatchRegistrationResult>, String>;
+    update deregister_agent(agent_id: String) -> Result<(), String>;
+    update heartbeat(agent_id: String) -> Result<(), String>;
+    update report_agent_load(agent_id: String, queue_depth: u32, cpu_estimate: f32, tokens_in_flight: u64) -> Result<AgentLoadReport, String>;
+    update subscribe_to_registry_events(callback_canister: Principal) -> Result<RegistrySubscription, String>;
+    update unsubscribe_from_registry_events(callback_canister: Principal) -> Result<(), String>;
+    query list_registry_subscriptions() -> Result<Vec<RegistrySubscription>, String>;
+    query list_registry_subscriptions_paged(page: Option<PageRequest>) -> Result<Page<RegistrySubscription>, String>;
+    update set_agent_approval_required(required: bool) -> Result<(), String>;
+    update invite_agent_registrant(principal: String) -> Result<(), String>;
+    update approve_agent(agent_id: String) -> Result<AgentRegistration, String>;
+    update reject_agent(agent_id: String, reason: String) -> Result<(), String>;
+    update quarantine_agent(agent_id: String, reason: String) -> Result<QuarantineRecord, String>;
+    update release_agent(agent_id: String) -> Result<QuarantineRecord, String>;
+    update suspend_agent(agent_id: String, reason: String) -> Result<AgentRegistration, String>;
+    update reinstate_agent(agent_id: String) -> Result<AgentRegistration, String>;
+    update retire_agent(agent_id: String, reason: String) -> Result<AgentRegistration, String>;
+    query list_quarantined_agents() -> Result<Vec<QuarantineRecord>, String>;
+    query list_quarantined_agents_paged(page: Option<PageRequest>) -> Result<Page<QuarantineRecord>, String>;
+    query get_registry_audit(agent_id: String, range: Option<RegistryAuditRange>, page: Option<PageRequest>) -> Result<Page<RegistryAuditEntry>, String>;
+    update designate_standby(capability: String, primary_agent_id: String, standby_agent_id: String, promote_below_health: Option<f32>) -> Result<StandbyAssignment, String>;
+    update remove_standby(capability: String) -> Result<(), String>;
+    query list_standbys() -> Result<Vec<StandbyAssignment>, String>;
+    query list_standbys_paged(page: Option<PageRequest>) -> Result<Page<StandbyAssignment>, String>;
+    query list_pending_agents() -> Result<Vec<AgentApplication>, String>;
+    query list_pending_agents_paged(page: Option<PageRequest>) -> Result<Page<AgentApplication>, String>;
+    query get_agent_application(agent_id: String) -> Result<AgentApplication, String>;
+    update route_request(request: RouteRequest) -> Result<RouteResponse, String>;
+    update create_agents_from_instructions(instructions: String, agent_count: Option<u32>) -> Result<String, String>;
+    update create_agents_with_callback(instructions: String, agent_count: Option<u32>, callback: CallbackTarget) -> Result<String, String>;
+    update submit_instructions(submission: InstructionSubmission) -> Result<String, String>;
+    update create_agents_from_instructions_batch(submissions: Vec<InstructionSubmission>) -> Result<InstructionBatch, String>;
+    query get_instruction_batch(batch_id: String) -> Result<InstructionBatch, String>;
+    update create_agents_in_program(program_id: String, instructions: String, agent_count: Option<u32>) -> Result<String, String>;
+    update create_program(name: String, agent_budget: Option<u32>) -> Result<String, String>;
+    update add_request_to_program(program_id: String, request_id: String) -> Result<(), String>;
+    query get_program_status(program_id: String) -> Result<ProgramStatus, String>;
+    query list_programs() -> Result<Vec<Program>, String>;
+    query list_programs_paged(page: Option<PageRequest>) -> Result<Page<Program>, String>;
+    update put_secret(label: String, value: String) -> Result<(), String>;
+    update delete_secret(label: String) -> Result<(), String>;
+    query list_secrets() -> Result<Vec<SecretMetadata>, String>;
+    query list_secrets_paged(page: Option<PageRequest>) -> Result<Page<SecretMetadata>, String>;
+    update set_preferences(preferences: UserPreferences) -> Result<(), String>;
+    query get_preferences() -> Result<UserPreferences, String>;
+    query list_audit_log(limit: Option<u32>) -> Result<Vec<AuditEntry>, String>;
+    query list_audit_log_paged(page: Option<PageRequest>) -> Result<Page<AuditEntry>, String>;
+    query list_notifications(unread_only: bool) -> Result<Vec<Notification>, String>;
+    query list_notifications_paged(unread_only: bool, page: Option<PageRequest>) -> Result<Page<Notification>, String>;
+    update mark_notification_read(notification_id: String) -> Result<(), String>;
+    query list_archived_instructions() -> Result<Vec<InstructionRequest>, String>;
+    query list_archived_instructions_paged(page: Option<PageRequest>) -> Result<Page<InstructionRequest>, String>;
+    update set_reclamation_policy(policy: ReclamationPolicy) -> Result<(), String>;
+    query get_reclamation_policy() -> Result<ReclamationPolicy, String>;
+    update set_liveness_policy(policy: LivenessPolicy) -> Result<(), String>;
+    query get_liveness_policy() -> Result<LivenessPolicy, String>;
+    update run_reclamation() -> Result<ReclamationReport, String>;
+    query get_agent_creation_status(request_id: String) -> Result<AgentCreationResult, String>;
+    update get_user_quota_status() -> Result<QuotaCheckResult, String>;
+    query list_dead_letters() -> Result<Vec<DeadLetter>, String>;
+    query list_dead_letters_paged(page: Option<PageRequest>) -> Result<Page<DeadLetter>, String>;
+    update redrive_dead_letter(delivery_id: String) -> Result<(), String>;
+    query get_agent(agent_id: String) -> Result<AgentRegistration, String>;
+    composite_query find_agent(agent_id: String) -> Result<AgentRegistration, String>;
+    query get_certified_agent(agent_id: String) -> Result<CertifiedAgent, String>;
+    update update_agent_capabilities(agent_id: String, add: Vec<String>, remove: Vec<String>) -> Result<AgentRegistration, String>;
+    query get_agent_capability_history(agent_id: String) -> Result<Vec<CapabilityChange>, String>;
+    query get_agent_capability_history_paged(agent_id: String, page: Option<PageRequest>) -> Result<Page<CapabilityChange>, String>;
+    query list_agents() -> Result<Vec<AgentRegistration>, String>;
+    query list_agents_paged(page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String>;
+    composite_query list_all_agents(page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String>;
+    query get_agents_delta(since_version: u64) -> Result<AgentRegistryDelta, String>;
+    query list_user_agents() -> Result<Vec<AgentRegistration>, String>;
+    query list_user_agents_paged(page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String>;
+    query list_instruction_requests() -> Result<Vec<InstructionRequest>, String>;
+    query list_instruction_requests_paged(page: Option<PageRequest>) -> Result<Page<InstructionRequest>, String>;
+    query health() -> CoordinatorHealth;
+    update get_service_report(period_days: u32) -> Result<SignedServiceReport, String>;
+    update set_attestation_key(key_name: String) -> Result<(), String>;
+    update set_report_organization(principal: String, organization: Option<String>) -> Result<(), String>;
+    query get_tag_report(tag: String, period_days: u32) -> Result<TagReport, String>;
+    query forecast_capacity(horizon_days: u32) -> Result<CapacityForecast, String>;
+    query get_agent_circuit(agent_id: String) -> Result<Option<AgentCircuit>, String>;
+    query get_agents_by_model(model_id: String, page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String>;
+    query search_agents(filter: AgentSearchFilter) -> Result<Vec<AgentSearchResult>, String>;
+    query search_agents_paged(filter: AgentSearchFilter, page: Option<PageRequest>) -> Result<Page<AgentSearchResult>, String>;
+    composite_query search_all_agents(filter: AgentSearchFilter, page: Option<PageRequest>) -> Result<Page<AgentSearchResult>, String>;
+    update declare_agent_sla(agent_id: String, max_latency_ms: u64, min_availability: f32) -> Result<(), String>;
+    update clear_agent_sla(agent_id: String) -> Result<(), String>;
+    query get_agent_sla_compliance(agent_id: String) -> Result<Option<SlaCompliance>, String>;
+    update submit_agent_feedback(agent_id: String, rating: u8) -> Result<AgentReputation, String>;
+    query get_agent_reputation(agent_id: String) -> Result<AgentReputation, String>;
+    update dispute_verification(request_id: String, reason: String) -> Result<VerificationDispute, String>;
+    query get_verification_dispute(request_id: String) -> Result<VerificationDispute, String>;
+    update resolve_verification_dispute(request_id: String, accept: bool, note: Option<String>) -> Result<VerificationDispute, String>;
+    query list_verification_disputes(status: Option<DisputeStatus>, page: Option<PageRequest>) -> Result<Page<VerificationDispute>, String>;
+    query get_agent_workload(agent_id: String) -> Result<AgentWorkload, String>;
+    query list_sla_violations() -> Result<Vec<SlaCompliance>, String>;
+    query list_sla_violations_paged(page: Option<PageRequest>) -> Result<Page<SlaCompliance>, String>;
+    update set_agent_availability_windows(agent_id: String, windows: Vec<String>) -> Result<Vec<AvailabilityWindow>, String>;
+    query get_agent_availability_windows(agent_id: String) -> Result<Vec<AvailabilityWindow>, String>;
+    update set_agent_maintenance(agent_id: String, window: MaintenanceWindow) -> Result<MaintenanceStatus, String>;
+    update clear_agent_maintenance(agent_id: String) -> Result<MaintenanceStatus, String>;
+    query get_agent_maintenance(agent_id: String) -> Result<MaintenanceStatus, String>;
+    update delegate_task(parent_task_id: String, agent_id: String, description: String, required_capabilities: Vec<String>, priority: Option<MessagePriority>, deadline: Option<u64>) -> Result<String, String>;
+    update send_coordination_message(session_id: String, from_agent: String, to_agent: Option<String>, message: AgentMessage) -> Result<(), String>;
+    update create_session_template(spec: SessionTemplateSpec) -> Result<SessionTemplate, String>;
+    query list_session_templates() -> Result<Vec<SessionTemplate>, String>;
+    query list_session_templates_paged(page: Option<PageRequest>) -> Result<Page<SessionTemplate>, String>;
+    update start_session_from_template(template_id: String, role_bindings: Vec<RoleBinding>) -> Result<TemplateSessionStart, String>;
+    query get_coordination_stats() -> CoordinationStats;
+    query get_task(task_id: String) -> Result<TaskLedgerEntry, String>;
+    update cancel_task(task_id: String, reason: String) -> Result<CancellationRecord, String>;
+    update cancel_coordination_session(session_id: String, reason: String) -> Result<CancellationRecord, String>;
+    update add_session_round(session_id: String, tasks: Vec<RoundTaskSpec>, timeout_ms: u64) -> Result<u32, String>;
+    query get_session_rounds(session_id: String) -> Result<Vec<CoordinationRound>, String>;
+    query get_session_rounds_paged(session_id: String, page: Option<PageRequest>) -> Result<Page<CoordinationRound>, String>;
+    update acknowledge_cancellation(cancellation_id: String, agent_id: String) -> Result<CancellationRecord, String>;
+    query get_cancellation(cancellation_id: String) -> Result<CancellationRecord, String>;
+    query get_dependency_health() -> Result<Vec<DependencyHealth>, String>;
+    query get_routing_stats(agent_id: Option<String>) -> Result<Vec<RoutingStats>, String>;
+    query get_routing_stats_paged(agent_id: Option<String>, page: Option<PageRequest>) -> Result<Page<RoutingStats>, String>;
+    update update_agent_health(agent_id: String, health_score: f32, coordination_preferences: Option<CoordinationPreferences>) -> Result<(), String>;
+    query get_agent_coordination_preferences(agent_id: String) -> Result<AgentCoordinationPreferences, String>;
+    update set_swarm_policy(policy: SwarmPolicy) -> Result<(), String>;
+    query get_swarm_policy() -> SwarmPolicy;
+    query list_routing_strategies() -> Vec<RoutingStrategyInfo>;
+    update set_enabled_routing_strategies(names: Vec<String>) -> Result<(), String>;
+    update set_capability_routing_policy(capability: String, policy: Option<CapabilityRoutingPolicy>) -> Result<(), String>;
+    query list_capability_routing_policies() -> Vec<(String, CapabilityRoutingPolicy)>;
+    update set_routing_weights(weights: RoutingWeights) -> Result<(), String>;
+    query get_routing_weights() -> RoutingWeights;
+    update export_state_snapshot(chunk_index: u32) -> Result<SnapshotChunk, String>;
+    update import_state_snapshot(chunk: SnapshotChunk) -> Result<SnapshotImportProgress, String>;
+    update export_registry_snapshot(chunk_index: u32) -> Result<SnapshotChunk, String>;
+    update import_registry_snapshot(chunk: SnapshotChunk) -> Result<SnapshotImportProgress, String>;
+    query get_admin_overview() -> Result<AdminOverview, String>;
+    update set_spawn_slo_targets(targets: SpawnSloTargets) -> Result<(), String>;
+    update set_operational_mode(mode: OperationalMode) -> Result<(), String>;
+    query list_endpoint_guards() -> Result<Vec<EndpointGuardInfo>, String>;
+    update set_shard_config(config: Option<ShardConfig>) -> Result<(), String>;
+    query get_shard_status() -> Result<ShardStatus, String>;
+    query get_user_shard(principal: String) -> Result<ShardAssignment, String>;
+    update rebalance_shard_users(limit: u32) -> Result<u32, String>;
+    update import_shard_users(payload: Vec<u8>) -> Result<u32, String>;
+    update set_registry_shard_config(config: RegistryShardConfig) -> Result<(), String>;
+    query get_registry_shard_status() -> Result<RegistryShardStatus, String>;
+    update spill_registry(limit: u32) -> Result<u32, String>;
+    update recall_spilled_agent(agent_id: String) -> Result<(), String>;
+    update registry_child_store(agents: Vec<AgentRegistration>) -> Result<u32, String>;
+    query registry_child_get(agent_id: String) -> Result<AgentRegistration, String>;
+    query registry_child_list(filter: Option<AgentSearchFilter>, page: PageRequest) -> Result<Page<AgentRegistration>, String>;
+    update registry_child_take(agent_id: String) -> Result<AgentRegistration, String>;
+    query get_recent_logs(level: Option<LogLevel>, module: Option<String>, limit: u32) -> Result<Vec<LogEntry>, String>;
+    update set_log_config(config: LogConfig) -> Result<(), String>;
+    query get_log_config() -> Result<LogConfig, String>;
+    update route_best_result(request: RouteRequest, top_k: u32, window_ms: u64) -> Result<RouteResponse, String>;
+    update route_hedged(request: RouteRequest, hedge_delay_ms: Option<u64>) -> Result<RouteResponse, String>;
+    update submit_inference_job(request: RouteRequest) -> Result<InferenceJob, String>;
+    update report_job_progress(job_id: String, percent: u8, partial: Option<String>) -> Result<InferenceJob, String>;
+    update report_job_failure(job_id: String, error: String) -> Result<InferenceJob, String>;
+    query get_inference_job(job_id: String) -> Result<InferenceJob, String>;
+    query get_verification_evidence(request_id: String) -> Result<VerificationRecord, String>;
+    query get_result_provenance(request_id: String) -> Result<ResultProvenance, String>;
+    update route_map_reduce(request: MapReduceRequest) -> Result<MapReduceResult, String>;
+    update route_chain(steps: Vec<ChainStep>) -> Result<ChainResult, String>;
+    update analyze_instructions(instructions: String) -> Result<InstructionAnalysisResult, String>;
+    query validate_instructions(instructions: String) -> Result<InstructionValidation, String>;
+    update spawn_from_analysis(request_id: String, accept_downscaled: bool) -> Result<String, String>;
+    query get_instruction_analysis(request_id: String) -> Result<InstructionAnalysisResult, String>;
+    query list_specialization_roles() -> Result<Vec<SpecializationRole>, String>;
+    query list_specialization_roles_paged(page: Option<PageRequest>) -> Result<Page<SpecializationRole>, String>;
+    query get_specialization_fallbacks(specialization: String) -> Result<Vec<String>, String>;
+    update set_specialization_role(role: SpecializationRole) -> Result<(), String>;
+    query list_capability_taxonomy() -> Result<Vec<CapabilityEdge>, String>;
+    query list_capability_taxonomy_paged(page: Option<PageRequest>) -> Result<Page<CapabilityEdge>, String>;
+    update set_capability_parent(capability: String, parent: Option<String>) -> Result<(), String>;
+    update set_warm_pool_config(config: WarmPoolConfig) -> Result<WarmPoolStatus, String>;
+    query get_warm_pool_status() -> Result<WarmPoolStatus, String>;
+    update refill_warm_pool() -> Result<WarmPoolStatus, String>;
+    update update_agent_status(agent_id: String, status: String) -> Result<(), String>;
+    query get_agent_spawning_metrics() -> Result<AgentSpawningMetrics, String>;
+    query get_coordination_networks() -> Result<Vec<CoordinationNetworkInfo>, String>;
+    query get_coordination_networks_paged(page: Option<PageRequest>) -> Result<Page<CoordinationNetworkInfo>, String>;
+    query get_concurrency_usage() -> Result<ConcurrencyUsage, String>;
+    update set_safety_limits(limits: SafetyLimits) -> Result<SafetyLimitStatus, String>;
+    query get_safety_limits() -> Result<SafetyLimitStatus, String>;
+    update configure_siem_export(config: SiemExportConfig) -> Result<SiemExportStatus, String>;
+    query get_siem_export_status() -> Result<SiemExportStatus, String>;
+    update upgrade_subscription_tier(tier: String) -> Result<(), String>;
+    query get_usage_history(months: u32) -> Result<Vec<UsageSnapshot>, String>;
+    query get_subscription_tier_info() -> Result<SubscriptionTierInfo, String>;
+    update get_economics_health() -> Result<EconHealth, String>;
+    update sync_capability_pricing() -> Result<u32, String>;
+    query get_capability_pricing() -> Vec<CapabilityPrice>;
+    update validate_token_usage_quota(tokens: u64) -> Result<QuotaValidation, String>;
+    query get_econ_fallback_status() -> Result<EconFallbackStatus, String>;
+    query get_interface_version() -> InterfaceVersion;
+    query describe_coordinator() -> CoordinatorDescription;
+    update set_feature_flag(name: String, enabled: bool) -> Result<(), String>;
+    query list_feature_flags() -> Vec<FeatureFlagState>;
+    #[cfg(feature = "load-test")]
+    update load_test_register_agents(count: u32, capabilities: Vec<String>) -> Result<u32, String>;
+    #[cfg(feature = "load-test")]
+    update load_test_start(profile: LoadProfile) -> Result<LoadTestStatus, String>;
+    #[cfg(feature = "load-test")]
+    update load_test_stop() -> Result<LoadTestStatus, String>;
+    #[cfg(feature = "load-test")]
+    query load_test_status() -> Result<LoadTestStatus, String>;
+    #[cfg(feature = "load-test")]
+    update load_test_teardown() -> Result<LoadTestStatus, String>;
+}
+
+/// Calls the coordinator from another canister
+#[derive(Debug, Clone, Copy)]
+pub struct CanisterClient {
+    pub canister_id: Principal,
+}
+
+impl CanisterClient {
+    pub fn new(canister_id: Principal) -> Self {
+        Self { canister_id }
+    }
+}
+
+impl OhmsCoordinator for CanisterClient {
+    /// Queries are sent as inter-canister calls too; the replica has no cheaper path between canisters
+    async fn call_raw(&self, method: &str, _kind: CallKind, arg: Vec<u8>) -> Result<Vec<u8>, ClientError> {
+        ic_cdk::api::call::call_raw(self.canister_id, method, arg, 0)
+            .await
+            .map_err(|(code, message)| ClientError::Rejected { method: method.to_string(), message: format!("{:?}: {}", code, message) })
+    }
+}
+
+#[cfg(all(test, not(feature = "load-test")))]
+mod tests {
+    use super::*;
+    use candid::types::{Type, TypeInner};
+    use candid_parser::utils::{service_equal, CandidSource};
+    use std::path::Path;
+
+    #[test]
+    fn test_client_matches_did_file() {
+        let did_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/ohms_coordinator.did");
+        let (env, actor) = CandidSource::File(&did_path).load().unwrap();
+        let methods = env.as_service(&actor.unwrap()).unwrap().to_vec();
+        // HTTP outcall transform, only ever called by the replica
+        let callable: Vec<_> = methods.into_iter().filter(|(name, _)| name != "siem_transform").collect();
+        let published: Type = TypeInner::Service(callable).into();
+        let published = candid::pretty::candid::compile(&env, &Some(published));
+
+        service_equal(CandidSource::Text(&client_service()), CandidSource::Text(&published))
+            .unwrap_or_else(|e| panic!("OhmsCoordinator is out of sync with src/ohms_coordinator.did: {}", e));
+    }
+}
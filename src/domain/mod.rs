@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use candid::CandidType;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct AgentRegistration {
@@ -12,6 +12,116 @@ pub struct AgentRegistration {
     pub health_score: f32,
     pub registered_at: u64,
     pub last_seen: u64,
+    /// Freeform operator metadata such as `region` or `team`; not used for routing
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// Agent build, e.g. `1.4.2`; informational
+    #[serde(default)]
+    pub agent_version: Option<String>,
+    /// Revision of the `infer` interface the agent implements; agents that do not
+    /// declare one are taken to speak the original revision
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    /// Administrative standing, unset meaning `Active`; kept by the coordinator and
+    /// ignored on registration
+    #[serde(default)]
+    pub state: Option<AgentState>,
+    /// Where the agent is in its lifecycle; unset for agents registered before lifecycles,
+    /// which count as `Active`. Changed only through `RegistryService`.
+    #[serde(default)]
+    pub lifecycle: Option<AgentLifecycle>,
+}
+
+impl AgentRegistration {
+    pub fn agent_state(&self) -> AgentState {
+        self.state.unwrap_or_default()
+    }
+
+    pub fn lifecycle(&self) -> AgentLifecycle {
+        self.lifecycle.unwrap_or(AgentLifecycle::Active)
+    }
+
+    /// Whether the agent may be selected for work, whatever its health score
+    pub fn is_routable(&self) -> bool {
+        self.agent_state() == AgentState::Active && self.lifecycle().accepts_work()
+    }
+}
+
+#[cfg(test)]
+impl AgentRegistration {
+    /// Healthy `coding` agent owned by `owner`, for tests to adjust with struct update syntax
+    pub fn test(agent_id: &str) -> Self {
+        AgentRegistration {
+            agent_id: agent_id.to_string(),
+            agent_principal: "owner".to_string(),
+            canister_id: "canister".to_string(),
+            capabilities: vec!["coding".to_string()],
+            model_id: "model".to_string(),
+            health_score: 1.0,
+            registered_at: 0,
+            last_seen: 0,
+            tags: Default::default(),
+            agent_version: None,
+            protocol_version: None,
+            state: None,
+            lifecycle: None,
+        }
+    }
+}
+
+/// Operational lifecycle of an agent, as opposed to the admin standing in `AgentState`.
+/// Transitions are validated by `can_transition_to`; any live agent may be decommissioned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, CandidType)]
+pub enum AgentLifecycle {
+    /// In the registry, not yet provisioned
+    Registered,
+    /// Its canister or model is being set up
+    Provisioning,
+    /// Provisioned and able to take work, but has not served any yet
+    Ready,
+    /// Serving traffic
+    Active,
+    /// Finishing accepted work and taking no new work
+    Draining,
+    /// Out of service for good; kept in the registry for its history
+    Decommissioned,
+}
+
+impl AgentLifecycle {
+    pub fn can_transition_to(self, to: AgentLifecycle) -> bool {
+        use AgentLifecycle::*;
+        if to == Decommissioned {
+            return self != Decommissioned;
+        }
+        matches!(
+            (self, to),
+            (Registered, Provisioning)
+                | (Registered, Ready)
+                | (Provisioning, Ready)
+                | (Ready, Active)
+                | (Active, Ready)
+                | (Ready, Draining)
+                | (Active, Draining)
+                | (Draining, Ready)
+        )
+    }
+
+    pub fn accepts_work(self) -> bool {
+        matches!(self, AgentLifecycle::Ready | AgentLifecycle::Active)
+    }
+}
+
+/// Administrative standing of an agent; only `Active` agents are selected for work
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, CandidType)]
+pub enum AgentState {
+    #[default]
+    Active,
+    /// Held out of routing by an admin until reinstated; keeps its tasks
+    Suspended,
+    /// Cut off by `quarantine_agent` during an incident
+    Quarantined,
+    /// Permanently out of service; kept in the registry for its history
+    Retired,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -21,6 +131,51 @@ pub struct RouteRequest {
     pub capabilities_required: Vec<String>,
     pub payload: Vec<u8>,
     pub routing_mode: RoutingMode,
+    pub callback: Option<CallbackTarget>,
+    pub tags: Vec<String>,
+    pub guaranteed_service: bool,
+    pub priority: Option<MessagePriority>,
+    /// Registered strategy name; overrides `routing_mode` when set
+    pub strategy: Option<String>,
+    /// Peer review of the winning fanout output; skipped when the aggregation has no
+    /// single winner, as with concatenation
+    pub review: Option<crate::services::review::ReviewOptions>,
+    /// Preferred model families, tried in order
+    pub model_preference: Option<ModelPreference>,
+    /// Conversation key; requests from the same caller sharing it stick to the agent that served the last one
+    #[serde(default)]
+    pub session_key: Option<String>,
+    /// Key Anycast routing hashes onto agents; the request id when unset
+    #[serde(default)]
+    pub partition_key: Option<String>,
+    /// How fanout combines its answers; best-of-n when unset
+    #[serde(default)]
+    pub aggregation: Option<crate::services::aggregation::FanoutAggregation>,
+}
+
+/// Ordered model fallback chain, e.g. `["code-llama", "starcoder"]` then any model
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ModelPreference {
+    /// Model family prefixes matched case-insensitively against agent model ids
+    pub fallback_chain: Vec<String>,
+    /// Fall back to any model once the chain is exhausted
+    pub allow_any_model: bool,
+}
+
+/// Message priority levels for task distribution and routing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessagePriority {
+    Low,
+    Normal,
+    High,
+    Critical,
+}
+
+/// Canister method that receives the result once a request completes
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CallbackTarget {
+    pub canister_id: String,
+    pub method: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -28,6 +183,43 @@ pub enum RoutingMode {
     Unicast,      // Route to single best agent
     Broadcast,    // Route to multiple agents (K agents)
     AgentSpawning, // Agent creation coordination
+    Hedged,       // Best agent, duplicated to the runner-up if it is slow to answer
+    Anycast,      // Agent owning the request's partition key on a consistent-hash ring
+    Competition,  // Several agents answer; the best-scored answer wins
+}
+
+impl RoutingMode {
+    /// Name of the registered routing strategy implementing this mode
+    pub fn strategy_name(&self) -> &'static str {
+        match self {
+            RoutingMode::Unicast => "unicast",
+            RoutingMode::Broadcast => "broadcast",
+            RoutingMode::AgentSpawning => "agent_spawning",
+            RoutingMode::Hedged => "hedged",
+            RoutingMode::Anycast => "anycast",
+            RoutingMode::Competition => "competition",
+        }
+    }
+}
+
+/// Named decoding presets applied to agent inference calls
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq, Default)]
+pub enum DecodeProfile {
+    #[default]
+    Balanced,
+    Creative,
+    Precise,
+}
+
+impl DecodeProfile {
+    /// (max_tokens, temperature, top_p)
+    pub fn params(&self) -> (u32, f32, f32) {
+        match self {
+            DecodeProfile::Balanced => (128, 0.7, 0.9),
+            DecodeProfile::Creative => (256, 0.95, 0.95),
+            DecodeProfile::Precise => (128, 0.2, 0.8),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -36,6 +228,24 @@ pub struct RouteResponse {
     pub selected_agents: Vec<String>,
     pub routing_time_ms: u64,
     pub selection_criteria: String,
+    /// Position in the model fallback chain that served the request; equal to
+    /// the chain length when it fell through to any model
+    pub model_fallback_level: Option<u32>,
+    /// Outputs of the agents the request ran on; empty while queued and for agent spawning
+    #[serde(default)]
+    pub results: Vec<AgentDispatchResult>,
+    /// Answer a fanout settled on after aggregation and review; none for other routes
+    #[serde(default)]
+    pub aggregated_output: Option<String>,
+}
+
+/// One agent's answer to a routed request
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentDispatchResult {
+    pub agent_id: String,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
 }
 
 // OHMS 2.0: Agent creation and instruction processing types
@@ -47,6 +257,22 @@ pub struct InstructionRequest {
     pub agent_count: Option<u32>,
     pub model_preferences: Vec<String>,
     pub created_at: u64,
+    pub tags: Vec<String>,
+    /// Analysis whose plan was executed for this request
+    #[serde(default)]
+    pub analysis_id: Option<String>,
+}
+
+/// Full-featured instruction submission (callback, tags) for agent creation
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct InstructionSubmission {
+    pub instructions: String,
+    pub agent_count: Option<u32>,
+    pub callback: Option<CallbackTarget>,
+    pub tags: Vec<String>,
+    pub accept_downscaled: bool,
+    /// `state_version` from a prior analysis; spawning is refused if state has since changed
+    pub expected_state_version: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -55,6 +281,7 @@ pub struct AgentCreationResult {
     pub created_agents: Vec<String>,
     pub creation_time_ms: u64,
     pub status: AgentCreationStatus,
+    pub coordination_network_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Copy)]
@@ -74,6 +301,17 @@ pub struct CoordinatorHealth {
     pub total_routes_processed: u64,
     pub average_routing_time_ms: f64,
     pub dedup_cache_size: u32,
+    /// Agent counts per model, by model id
+    #[serde(default)]
+    pub model_fleet: Vec<ModelFleetCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ModelFleetCount {
+    pub model_id: String,
+    pub total_agents: u32,
+    /// Agents that can take work and have a health score above 0.5, as in `active_agents`
+    pub active_agents: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -83,6 +321,9 @@ pub struct RoutingStats {
     pub success_rate: f32,
     pub average_response_time_ms: f64,
     pub capability_scores: HashMap<String, f32>,
+    /// Exponentially weighted response time of successful calls, tracking recent latency rather than the lifetime mean
+    #[serde(default)]
+    pub latency_ewma_ms: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,6 +332,19 @@ pub struct DedupEntry {
     pub processed_at: u64,
     pub result_hash: String,
     pub ttl_expires_at: u64,
+    /// Hash of the payload and capabilities routed; a repeat must match to get `response`
+    #[serde(default)]
+    pub request_sha256: Option<String>,
+    /// Decision returned again to retries of the same request
+    #[serde(default)]
+    pub response: Option<RouteResponse>,
+}
+
+/// Deployed interface identity, used by clients to detect Candid drift after upgrades
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct InterfaceVersion {
+    pub package_version: String,
+    pub interface_hash: String,
 }
 
 // Swarm/Hive policy
@@ -114,13 +368,119 @@ impl Default for SwarmPolicy {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
 pub struct CoordinatorConfig {
     pub swarm: SwarmPolicy,
+    /// Experimental routing strategies switched on for this deployment
+    pub enabled_strategies: Vec<String>,
+    /// Hold direct agent registrations for admin approval
+    #[serde(default)]
+    pub require_agent_approval: bool,
+    #[serde(default)]
+    pub operational_mode: OperationalMode,
+    /// Admin overrides of runtime feature flags; flags not listed are on
+    #[serde(default)]
+    pub feature_flags: BTreeMap<String, bool>,
+    #[serde(default)]
+    pub logging: LogConfig,
+    /// Threshold ECDSA key that signs service reports; `key_1` when unset
+    #[serde(default)]
+    pub attestation_key: Option<String>,
+    /// Routing applied automatically to requests needing a capability, keyed by capability
+    #[serde(default)]
+    pub capability_policies: BTreeMap<String, CapabilityRoutingPolicy>,
+    #[serde(default)]
+    pub routing_weights: RoutingWeights,
+}
+
+/// Weights of agent selection and fanout answer scoring, tunable by admins
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq)]
+pub struct RoutingWeights {
+    pub health: f32,
+    pub capability: f32,
+    pub reputation: f32,
+    /// Share of its score a fully loaded agent loses, 0.0 to 1.0
+    pub load_penalty: f32,
+    /// Fanout credit for output length, up to 1000 bytes
+    pub answer_length: f32,
+    /// Fanout credit for token count, up to 256 tokens
+    pub answer_tokens: f32,
+    /// Fanout credit for the agent's cache hit ratio
+    pub cache_hits: f32,
+    /// Fanout penalty per `latency_baseline_ms` of response time
+    pub latency: f32,
+    pub latency_baseline_ms: u64,
+    /// Fanout credit for an answer passing every verifier
+    pub verifier_bonus: f32,
 }
 
-impl Default for CoordinatorConfig {
-    fn default() -> Self { Self { swarm: SwarmPolicy::default() } }
+impl Default for RoutingWeights {
+    fn default() -> Self {
+        Self {
+            health: 0.5,
+            capability: 0.3,
+            reputation: 0.2,
+            load_penalty: 0.5,
+            answer_length: 0.6,
+            answer_tokens: 0.3,
+            cache_hits: 0.1,
+            latency: 0.4,
+            latency_baseline_ms: 5000,
+            verifier_bonus: 0.1,
+        }
+    }
+}
+
+/// Admin routing policy for one capability, e.g. `code_review` always fanned out to two
+/// agents whose answers must pass the verifiers
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CapabilityRoutingPolicy {
+    /// Replaces the request's routing mode
+    pub routing_mode: RoutingMode,
+    /// Replaces the request's strategy when set
+    pub strategy: Option<String>,
+    /// Agents selected by score, and agents a fanout queries, in place of the caller's count;
+    /// only without a `strategy`, which it would otherwise replace
+    pub top_k: Option<u32>,
+    /// Only fanout answers passing every verifier may win
+    pub verifier_consensus: bool,
+}
+
+/// Log severity, most severe first; a threshold keeps its own level and everything above it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, CandidType, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LogConfig {
+    /// Threshold for modules without their own
+    pub default_level: LogLevel,
+    /// Per-module thresholds, e.g. `econ` at Debug while chasing an economics issue
+    pub module_levels: BTreeMap<String, LogLevel>,
+    /// Also write kept entries to the canister log
+    pub canister_log: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { default_level: LogLevel::Info, module_levels: BTreeMap::new(), canister_log: true }
+    }
+}
+
+/// Coordinator-wide switch checked by the guard chain; ordered from least to most restrictive
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, CandidType, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OperationalMode {
+    #[default]
+    Normal,
+    /// Reads and agent reports only; nothing new is accepted
+    ReadOnly,
+    /// Only public and admin endpoints answer
+    Paused,
 }
 
 // OHMS 2.0: Agent spawning and coordination types
@@ -139,6 +499,45 @@ pub struct AgentSpec {
     pub required_capabilities: Vec<String>,
     pub model_requirements: Vec<String>,
     pub specialization: String,
+    pub weight_class: AgentWeightClass,
+}
+
+/// Cost class of a spawned agent, charged against weighted spawn quota
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq, Default)]
+pub enum AgentWeightClass {
+    Light,
+    #[default]
+    Standard,
+    Heavy,
+}
+
+impl AgentWeightClass {
+    /// Quota units charged per spawned agent
+    pub fn units(&self) -> u32 {
+        match self {
+            AgentWeightClass::Light => 1,
+            AgentWeightClass::Standard => 2,
+            AgentWeightClass::Heavy => 4,
+        }
+    }
+
+    /// Derive the class from model requirements; the first entry is the model deployed
+    pub fn from_models(models: &[String]) -> Self {
+        models.first()
+            .map(|model| Self::from_model(model))
+            .unwrap_or_default()
+    }
+
+    fn from_model(model: &str) -> Self {
+        let model = model.to_lowercase();
+        if ["wizardcoder", "starcoder", "70b", "34b"].iter().any(|m| model.contains(m)) {
+            AgentWeightClass::Heavy
+        } else if ["gemma", "phi", "mini", "tiny"].iter().any(|m| model.contains(m)) {
+            AgentWeightClass::Light
+        } else {
+            AgentWeightClass::Standard
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -148,6 +547,60 @@ pub struct InstructionAnalysisResult {
     pub suggested_agents: Vec<AgentSpec>,
     pub coordination_plan: String,
     pub quota_check: QuotaCheckResult,
+    pub weighted_cost: u32,
+    pub downscaled_plan: Option<DownscaledPlan>,
+    /// Fingerprint of the caller's quota and agents this analysis was based on
+    pub state_version: u64,
+    pub role_costs: Vec<RoleCostEstimate>,
+    pub estimated_cost_per_task_usd: f64,
+}
+
+/// Problem found in instructions before analysis; none of them stop an analysis from running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, CandidType)]
+pub enum InstructionIssueKind {
+    /// Nothing says what the agents should produce
+    MissingDeliverable,
+    /// Too short or too vague to size a team
+    AmbiguousScope,
+    /// Constraints that cannot both hold, e.g. quick and exhaustive
+    ConflictingConstraints,
+    /// No known specialization matches, so the plan would be generalists only
+    NoMatchingSpecialization,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct InstructionIssue {
+    pub kind: InstructionIssueKind,
+    pub message: String,
+}
+
+/// Feedback on instructions for the user to act on before any quota is spent
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct InstructionValidation {
+    /// No issues were found
+    pub ready: bool,
+    pub issues: Vec<InstructionIssue>,
+    /// Questions whose answers, added to the instructions, would resolve the issues
+    pub clarification_questions: Vec<String>,
+}
+
+/// Estimated per-task cost of one planned role, from capability pricing hints
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RoleCostEstimate {
+    pub agent_type: String,
+    pub cost_per_task_usd: f64,
+    /// Capabilities with no published price; the estimate excludes them
+    pub unpriced_capabilities: Vec<String>,
+}
+
+/// Reduced plan offered when the full plan does not fit the caller's remaining quota
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct DownscaledPlan {
+    pub suggested_agents: Vec<AgentSpec>,
+    pub merged_roles: Vec<String>,
+    pub dropped_roles: Vec<String>,
+    pub coordination_plan: String,
+    pub weighted_cost: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -156,6 +609,10 @@ pub struct QuotaCheckResult {
     pub remaining_agents: u32,
     pub monthly_limit: u32,
     pub tier: String,
+    pub remaining_weighted_units: u32,
+    /// Economics is unreachable and these figures come from the last synced quota
+    #[serde(default)]
+    pub degraded: bool,
 }
 
 // OHMS 2.0 API response types
@@ -189,6 +646,12 @@ pub struct SubscriptionTierInfo {
     pub agents_created_this_month: u32,
     pub tokens_used_this_month: u64,
     pub last_reset_date: u64,
+    pub max_active_sessions: u32,
+    pub active_sessions: u32,
+    pub daily_messages: u32,
+    pub messages_today: u32,
+    pub artifact_storage_bytes: u64,
+    pub artifact_bytes_stored: u64,
 }
 
 // Economics integration types
@@ -222,4 +685,250 @@ pub struct QuotaRemaining {
 pub struct VerifierEvidence {
     pub passed: bool,
     pub details: String,
-}
\ No newline at end of file
+}
+
+// Outbound dependency tracking
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct DependencyHealth {
+    pub endpoint: String,
+    pub success_count: u64,
+    pub failure_count: u64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<u64>,
+    pub last_success_at: Option<u64>,
+}
+
+impl DependencyHealth {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            success_count: 0,
+            failure_count: 0,
+            last_error: None,
+            last_error_at: None,
+            last_success_at: None,
+        }
+    }
+}
+
+/// Classified inter-canister call failure
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum CallFailureClass {
+    CanisterStopped,
+    OutOfCycles,
+    Trapped,
+    Timeout,
+    DestinationInvalid,
+    Rejected,
+    Unknown,
+}
+
+/// Circuit breaker state for calls to a single agent
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentCircuit {
+    pub agent_id: String,
+    pub consecutive_failures: u32,
+    pub last_failure_class: Option<CallFailureClass>,
+    pub last_failure_at: Option<u64>,
+    pub open_until: Option<u64>,
+}
+
+impl AgentCircuit {
+    pub fn new(agent_id: &str) -> Self {
+        Self {
+            agent_id: agent_id.to_string(),
+            consecutive_failures: 0,
+            last_failure_class: None,
+            last_failure_at: None,
+            open_until: None,
+        }
+    }
+}
+
+/// Service level an agent owner commits to
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentSla {
+    pub max_latency_ms: u64,
+    pub min_availability: f32,
+    pub declared_at: u64,
+}
+
+/// Observed performance of an agent against its declared SLA
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SlaTracker {
+    pub sla: AgentSla,
+    pub total_calls: u64,
+    pub successful_calls: u64,
+    pub average_latency_ms: f64,
+    pub violations: u32,
+    pub last_violation_at: Option<u64>,
+    pub last_violation: Option<String>,
+    /// Successful calls slower than the SLA latency
+    #[serde(default)]
+    pub deadline_misses: u64,
+    /// Time spent at zero health since the SLA was declared, not counting an ongoing outage
+    #[serde(default)]
+    pub downtime_ns: u64,
+    #[serde(default)]
+    pub down_since: Option<u64>,
+}
+
+/// SLA compliance summary exposed to callers
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SlaCompliance {
+    pub agent_id: String,
+    pub sla: AgentSla,
+    pub observed_availability: f32,
+    pub observed_latency_ms: f64,
+    pub total_calls: u64,
+    pub violations: u32,
+    pub last_violation_at: Option<u64>,
+    /// Share of the time since the SLA was declared that the agent was not at zero health
+    pub uptime_ratio: f32,
+    pub deadline_misses: u64,
+    /// Failed calls over the failures `min_availability` allows; 1.0 or more means the budget is spent
+    pub error_budget_consumed: f32,
+    pub compliant: bool,
+}
+
+/// Predicates for agent search
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentSearchFilter {
+    pub capabilities: Vec<String>,
+    pub sla_compliant_only: bool,
+    /// Every pair must be present; an empty value only requires the key
+    pub tags: Option<BTreeMap<String, String>>,
+    pub model_id: Option<String>,
+    pub min_health: Option<f32>,
+    pub max_health: Option<f32>,
+}
+
+/// Agent search hit with its SLA standing, if one is declared
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentSearchResult {
+    pub agent: AgentRegistration,
+    pub sla_compliance: Option<SlaCompliance>,
+}
+
+/// Registrations changed since a registry version cursor
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentRegistryDelta {
+    /// Cursor to pass as `since_version` next time
+    pub version: u64,
+    /// The cursor is older than the retained change log; `created` holds every agent
+    pub full_resync: bool,
+    pub created: Vec<AgentRegistration>,
+    pub updated: Vec<AgentRegistration>,
+    pub removed: Vec<String>,
+}
+
+/// Last registry mutation seen for an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentChangeMark {
+    pub created_version: u64,
+    pub changed_version: u64,
+    pub removed: bool,
+}
+
+/// One in-place change to an agent's advertised capabilities
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CapabilityChange {
+    pub changed_by: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed_at: u64,
+}
+
+/// Capability hierarchy: an agent advertising a capability also satisfies every
+/// ancestor of it, so `rust_programming` serves requests for `coding`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityTaxonomy {
+    /// Capability to its more general parent
+    pub parents: BTreeMap<String, String>,
+}
+
+/// One parent link in the capability taxonomy
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub struct CapabilityEdge {
+    pub capability: String,
+    pub parent: String,
+}
+
+impl Default for CapabilityTaxonomy {
+    fn default() -> Self {
+        const BUILT_IN: &[(&str, &str)] = &[
+            ("rust_programming", "coding"),
+            ("python_programming", "coding"),
+            ("typescript_programming", "coding"),
+            ("frontend", "software_development"),
+            ("backend", "software_development"),
+            ("full_stack_development", "software_development"),
+            ("technical_writing", "writing"),
+            ("machine_learning", "data_analysis"),
+        ];
+        Self {
+            parents: BUILT_IN.iter().map(|(child, parent)| (child.to_string(), parent.to_string())).collect(),
+        }
+    }
+}
+
+impl CapabilityTaxonomy {
+    pub const MAX_DEPTH: usize = 16;
+
+    /// Parent chain of `capability`, nearest first
+    pub fn ancestors(&self, capability: &str) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+        let mut current = self.parents.get(capability);
+        while let Some(parent) = current {
+            if chain.len() >= Self::MAX_DEPTH || chain.contains(parent) {
+                break;
+            }
+            chain.push(parent.clone());
+            current = self.parents.get(parent);
+        }
+        chain
+    }
+
+    /// Whether any of `offered` is `required` or one of its descendants
+    pub fn satisfies(&self, offered: &[String], required: &str) -> bool {
+        offered.iter().any(|capability| capability == required || self.ancestors(capability).iter().any(|a| a == required))
+    }
+
+    /// `required` and every capability that satisfies it through the hierarchy
+    pub fn satisfying(&self, required: &str) -> Vec<String> {
+        std::iter::once(required.to_string())
+            .chain(
+                self.parents
+                    .keys()
+                    .filter(|capability| self.ancestors(capability).iter().any(|a| a == required))
+                    .cloned(),
+            )
+            .collect()
+    }
+
+    /// Link `capability` under `parent`, or detach it when `parent` is `None`
+    pub fn set_parent(&mut self, capability: &str, parent: Option<&str>) -> Result<(), String> {
+        let Some(parent) = parent else {
+            self.parents.remove(capability);
+            return Ok(());
+        };
+        if capability.trim().is_empty() || parent.trim().is_empty() {
+            return Err("Capability names cannot be empty".to_string());
+        }
+        if parent == capability || self.ancestors(parent).iter().any(|a| a == capability) {
+            return Err(format!("{} cannot be placed under {}: it would form a cycle", capability, parent));
+        }
+        if self.ancestors(parent).len() + 1 >= Self::MAX_DEPTH {
+            return Err(format!("Capability hierarchy is limited to {} levels", Self::MAX_DEPTH));
+        }
+        self.parents.insert(capability.to_string(), parent.to_string());
+        Ok(())
+    }
+
+    pub fn edges(&self) -> Vec<CapabilityEdge> {
+        self.parents
+            .iter()
+            .map(|(capability, parent)| CapabilityEdge { capability: capability.clone(), parent: parent.clone() })
+            .collect()
+    }
+}
@@ -1,11 +1,390 @@
-use ic_cdk::api::{caller, time};
-use candid::Principal;
+use crate::domain::OperationalMode;
+use crate::services::with_state;
+use ic_cdk::api::{call, caller, is_controller, time};
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::cell::RefCell;
 
+/// (principal, method) -> (window start, calls in window)
+type CallWindows = HashMap<(Principal, &'static str), (u64, u32)>;
+
+thread_local! {
+    static CALL_WINDOWS: RefCell<CallWindows> = RefCell::new(HashMap::new());
+}
+
 pub struct Guards;
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum GuardAccess {
+    Public,
+    Authenticated,
+    Admin,
+}
+
+/// Protection declared for one endpoint; see `ENDPOINT_GUARDS`
+#[derive(Debug, Clone, Copy)]
+pub struct EndpointGuard {
+    pub method: &'static str,
+    pub access: GuardAccess,
+    /// Calls per principal per minute; queries cannot keep counters so leave them unlimited
+    pub calls_per_minute: Option<u32>,
+    pub max_payload_bytes: usize,
+    /// Most restrictive operational mode the endpoint still answers in
+    pub runs_in: OperationalMode,
+}
+
+/// Candid view of an `EndpointGuard` for coverage audits
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct EndpointGuardInfo {
+    pub method: String,
+    pub access: GuardAccess,
+    pub calls_per_minute: Option<u32>,
+    pub max_payload_bytes: u64,
+    pub runs_in: OperationalMode,
+}
+
+pub const DEFAULT_MAX_PAYLOAD: usize = 256 * 1024;
+const LARGE_PAYLOAD: usize = 2 * 1024 * 1024;
+const DEFAULT_CALLS_PER_MINUTE: u32 = 60;
+const RATE_WINDOW_NANOS: u64 = 60_000_000_000;
+/// Stale windows are dropped once this many are tracked
+const MAX_TRACKED_WINDOWS: usize = 10_000;
+
+impl EndpointGuard {
+    /// Open to everyone, even while paused
+    const fn public(method: &'static str) -> Self {
+        Self { method, access: GuardAccess::Public, calls_per_minute: None, max_payload_bytes: DEFAULT_MAX_PAYLOAD, runs_in: OperationalMode::Paused }
+    }
+
+    /// Authenticated query, still served while read-only
+    const fn read(method: &'static str) -> Self {
+        Self { method, access: GuardAccess::Authenticated, calls_per_minute: None, max_payload_bytes: DEFAULT_MAX_PAYLOAD, runs_in: OperationalMode::ReadOnly }
+    }
+
+    /// Authenticated update that only runs in normal operation
+    const fn write(method: &'static str) -> Self {
+        Self {
+            method,
+            access: GuardAccess::Authenticated,
+            calls_per_minute: Some(DEFAULT_CALLS_PER_MINUTE),
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD,
+            runs_in: OperationalMode::Normal,
+        }
+    }
+
+    /// Agent reporting on work already accepted; allowed to finish while read-only
+    const fn report(method: &'static str) -> Self {
+        Self { runs_in: OperationalMode::ReadOnly, ..Self::write(method) }
+    }
+
+    /// Controllers only; answers in every mode so operators can recover
+    const fn admin(method: &'static str) -> Self {
+        Self { method, access: GuardAccess::Admin, calls_per_minute: None, max_payload_bytes: DEFAULT_MAX_PAYLOAD, runs_in: OperationalMode::Paused }
+    }
+
+    const fn limit(self, calls_per_minute: u32) -> Self {
+        Self { calls_per_minute: Some(calls_per_minute), ..self }
+    }
+
+    const fn payload(self, max_payload_bytes: usize) -> Self {
+        Self { max_payload_bytes, ..self }
+    }
+
+    pub fn info(&self) -> EndpointGuardInfo {
+        EndpointGuardInfo {
+            method: self.method.to_string(),
+            access: self.access,
+            calls_per_minute: self.calls_per_minute,
+            max_payload_bytes: self.max_payload_bytes as u64,
+            runs_in: self.runs_in,
+        }
+    }
+}
+
+/// Every exported endpoint and the guards it runs; endpoints call `Guards::check` with their own name
+pub const ENDPOINT_GUARDS: &[EndpointGuard] = &[
+    // Agent management
+    EndpointGuard::write("register_agent").limit(10),
+    EndpointGuard::write("register_agents_batch").limit(2),
+    EndpointGuard::write("deregister_agent").limit(10),
+    EndpointGuard::report("heartbeat").limit(120),
+    EndpointGuard::report("report_agent_load").limit(120),
+    EndpointGuard::write("subscribe_to_registry_events").limit(5),
+    EndpointGuard::write("unsubscribe_from_registry_events").limit(5),
+    EndpointGuard::admin("list_registry_subscriptions"),
+    EndpointGuard::admin("list_registry_subscriptions_paged"),
+    EndpointGuard::admin("set_agent_approval_required"),
+    EndpointGuard::admin("invite_agent_registrant"),
+    EndpointGuard::admin("approve_agent"),
+    EndpointGuard::admin("reject_agent"),
+    EndpointGuard::admin("quarantine_agent"),
+    EndpointGuard::admin("release_agent"),
+    EndpointGuard::admin("suspend_agent"),
+    EndpointGuard::admin("reinstate_agent"),
+    EndpointGuard::admin("retire_agent"),
+    EndpointGuard::admin("list_quarantined_agents"),
+    EndpointGuard::admin("list_quarantined_agents_paged"),
+    EndpointGuard::admin("get_registry_audit"),
+    EndpointGuard::admin("designate_standby"),
+    EndpointGuard::admin("remove_standby"),
+    EndpointGuard::admin("list_standbys"),
+    EndpointGuard::admin("list_standbys_paged"),
+    EndpointGuard::admin("list_pending_agents"),
+    EndpointGuard::admin("list_pending_agents_paged"),
+    EndpointGuard::read("get_agent_application"),
+    EndpointGuard::read("get_agent"),
+    EndpointGuard::read("find_agent"),
+    EndpointGuard::read("get_certified_agent"),
+    EndpointGuard::read("list_agents"),
+    EndpointGuard::read("list_agents_paged"),
+    EndpointGuard::read("list_all_agents"),
+    EndpointGuard::read("get_agents_delta"),
+    EndpointGuard::read("list_user_agents"),
+    EndpointGuard::read("list_user_agents_paged"),
+    EndpointGuard::report("update_agent_health"),
+    EndpointGuard::read("get_agent_coordination_preferences"),
+    EndpointGuard::read("get_agents_by_model"),
+    EndpointGuard::read("search_agents"),
+    EndpointGuard::read("search_agents_paged"),
+    EndpointGuard::read("search_all_agents"),
+    EndpointGuard::write("update_agent_capabilities"),
+    EndpointGuard::read("get_agent_capability_history"),
+    EndpointGuard::read("get_agent_capability_history_paged"),
+    EndpointGuard::write("declare_agent_sla"),
+    EndpointGuard::write("clear_agent_sla"),
+    EndpointGuard::read("get_agent_sla_compliance"),
+    EndpointGuard::write("submit_agent_feedback").limit(20),
+    EndpointGuard::read("get_agent_reputation"),
+    EndpointGuard::write("dispute_verification").limit(10),
+    EndpointGuard::read("get_verification_dispute"),
+    EndpointGuard::admin("resolve_verification_dispute"),
+    EndpointGuard::admin("list_verification_disputes"),
+    EndpointGuard::read("get_agent_workload"),
+    EndpointGuard::read("list_sla_violations"),
+    EndpointGuard::read("list_sla_violations_paged"),
+    EndpointGuard::write("set_agent_availability_windows"),
+    EndpointGuard::read("get_agent_availability_windows"),
+    EndpointGuard::write("set_agent_maintenance"),
+    EndpointGuard::write("clear_agent_maintenance"),
+    EndpointGuard::read("get_agent_maintenance"),
+    // Task delegation
+    EndpointGuard::write("delegate_task"),
+    EndpointGuard::report("send_coordination_message").limit(120),
+    EndpointGuard::write("create_session_template").limit(10),
+    EndpointGuard::read("list_session_templates"),
+    EndpointGuard::read("list_session_templates_paged"),
+    EndpointGuard::write("start_session_from_template").limit(20),
+    EndpointGuard::public("get_coordination_stats"),
+    EndpointGuard::read("get_task"),
+    EndpointGuard::write("cancel_task"),
+    EndpointGuard::write("cancel_coordination_session"),
+    EndpointGuard::write("add_session_round").limit(30),
+    EndpointGuard::read("get_session_rounds"),
+    EndpointGuard::read("get_session_rounds_paged"),
+    EndpointGuard::report("acknowledge_cancellation"),
+    EndpointGuard::read("get_cancellation"),
+    // Instruction-based agent creation
+    EndpointGuard::write("create_agents_from_instructions").limit(10),
+    EndpointGuard::write("create_agents_with_callback").limit(10),
+    EndpointGuard::write("submit_instructions").limit(10),
+    EndpointGuard::write("create_agents_from_instructions_batch").limit(5).payload(LARGE_PAYLOAD),
+    EndpointGuard::read("get_instruction_batch"),
+    EndpointGuard::read("get_agent_creation_status"),
+    EndpointGuard::read("list_instruction_requests"),
+    EndpointGuard::read("list_instruction_requests_paged"),
+    EndpointGuard::read("validate_instructions"),
+    EndpointGuard::write("analyze_instructions").limit(30),
+    EndpointGuard::read("get_instruction_analysis"),
+    EndpointGuard::write("spawn_from_analysis").limit(10),
+    EndpointGuard::read("list_specialization_roles"),
+    EndpointGuard::read("list_specialization_roles_paged"),
+    EndpointGuard::read("get_specialization_fallbacks"),
+    EndpointGuard::admin("set_specialization_role"),
+    EndpointGuard::read("list_capability_taxonomy"),
+    EndpointGuard::read("list_capability_taxonomy_paged"),
+    EndpointGuard::admin("set_capability_parent"),
+    EndpointGuard::admin("set_warm_pool_config"),
+    EndpointGuard::admin("get_warm_pool_status"),
+    EndpointGuard::admin("refill_warm_pool"),
+    EndpointGuard::write("update_agent_status"),
+    // Programs
+    EndpointGuard::write("create_program"),
+    EndpointGuard::write("add_request_to_program"),
+    EndpointGuard::write("create_agents_in_program").limit(10),
+    EndpointGuard::read("get_program_status"),
+    EndpointGuard::read("list_programs"),
+    EndpointGuard::read("list_programs_paged"),
+    // Spawning metrics and coordination
+    EndpointGuard::read("get_agent_spawning_metrics"),
+    EndpointGuard::read("get_coordination_networks"),
+    EndpointGuard::read("get_coordination_networks_paged"),
+    // Quota and subscription management
+    EndpointGuard::read("get_user_quota_status"),
+    EndpointGuard::read("get_concurrency_usage"),
+    EndpointGuard::write("set_safety_limits").limit(10),
+    EndpointGuard::read("get_safety_limits"),
+    EndpointGuard::write("configure_siem_export").limit(10),
+    EndpointGuard::read("get_siem_export_status"),
+    EndpointGuard::public("siem_transform"),
+    EndpointGuard::write("upgrade_subscription_tier").limit(5),
+    EndpointGuard::read("get_subscription_tier_info"),
+    EndpointGuard::read("get_usage_history"),
+    EndpointGuard::read("get_economics_health"),
+    EndpointGuard::admin("sync_capability_pricing"),
+    EndpointGuard::public("get_capability_pricing"),
+    EndpointGuard::read("validate_token_usage_quota"),
+    EndpointGuard::admin("get_econ_fallback_status"),
+    // Secrets vault
+    EndpointGuard::write("put_secret").limit(20),
+    EndpointGuard::write("delete_secret").limit(20),
+    EndpointGuard::read("list_secrets"),
+    EndpointGuard::read("list_secrets_paged"),
+    // User preferences, audit and notifications
+    EndpointGuard::write("set_preferences"),
+    EndpointGuard::read("get_preferences"),
+    EndpointGuard::read("list_audit_log"),
+    EndpointGuard::read("list_audit_log_paged"),
+    EndpointGuard::read("list_notifications"),
+    EndpointGuard::read("list_notifications_paged"),
+    EndpointGuard::write("mark_notification_read"),
+    EndpointGuard::read("list_archived_instructions"),
+    EndpointGuard::read("list_archived_instructions_paged"),
+    // Routing and coordination
+    EndpointGuard::write("route_request").limit(120).payload(LARGE_PAYLOAD),
+    EndpointGuard::write("route_best_result").limit(60).payload(LARGE_PAYLOAD),
+    EndpointGuard::write("route_hedged").limit(60).payload(LARGE_PAYLOAD),
+    EndpointGuard::write("route_map_reduce").limit(20).payload(LARGE_PAYLOAD),
+    EndpointGuard::write("route_chain").limit(20),
+    EndpointGuard::write("submit_inference_job").limit(30).payload(LARGE_PAYLOAD),
+    EndpointGuard::report("report_job_progress").limit(600),
+    EndpointGuard::report("report_job_failure"),
+    EndpointGuard::read("get_inference_job"),
+    EndpointGuard::read("get_verification_evidence"),
+    EndpointGuard::read("get_result_provenance"),
+    EndpointGuard::read("get_routing_stats"),
+    EndpointGuard::read("get_routing_stats_paged"),
+    EndpointGuard::read("get_tag_report"),
+    EndpointGuard::read("get_service_report").limit(5),
+    EndpointGuard::admin("set_attestation_key"),
+    EndpointGuard::admin("set_report_organization"),
+    EndpointGuard::admin("forecast_capacity"),
+    // Callback delivery
+    EndpointGuard::read("list_dead_letters"),
+    EndpointGuard::read("list_dead_letters_paged"),
+    EndpointGuard::write("redrive_dead_letter"),
+    // System management
+    EndpointGuard::public("health"),
+    EndpointGuard::read("get_dependency_health"),
+    EndpointGuard::read("get_agent_circuit"),
+    EndpointGuard::write("set_swarm_policy"),
+    EndpointGuard::public("get_swarm_policy"),
+    EndpointGuard::public("list_routing_strategies"),
+    EndpointGuard::admin("set_enabled_routing_strategies"),
+    EndpointGuard::admin("set_capability_routing_policy"),
+    EndpointGuard::public("list_capability_routing_policies"),
+    EndpointGuard::admin("set_routing_weights"),
+    EndpointGuard::public("get_routing_weights"),
+    EndpointGuard::admin("export_state_snapshot"),
+    EndpointGuard::admin("import_state_snapshot").payload(LARGE_PAYLOAD),
+    EndpointGuard::admin("export_registry_snapshot"),
+    EndpointGuard::admin("import_registry_snapshot").payload(LARGE_PAYLOAD),
+    EndpointGuard::admin("get_admin_overview"),
+    EndpointGuard::admin("set_spawn_slo_targets"),
+    EndpointGuard::admin("set_operational_mode"),
+    EndpointGuard::admin("list_endpoint_guards"),
+    EndpointGuard::admin("set_shard_config"),
+    EndpointGuard::admin("get_shard_status"),
+    EndpointGuard::public("get_user_shard"),
+    EndpointGuard::admin("rebalance_shard_users"),
+    // Peer shards only; checked in the endpoint
+    EndpointGuard::public("import_shard_users").payload(LARGE_PAYLOAD),
+    EndpointGuard::admin("set_registry_shard_config"),
+    EndpointGuard::admin("get_registry_shard_status"),
+    EndpointGuard::admin("spill_registry"),
+    EndpointGuard::admin("recall_spilled_agent"),
+    // Parent coordinator only; checked in the endpoint
+    EndpointGuard::public("registry_child_store").payload(LARGE_PAYLOAD),
+    EndpointGuard::public("registry_child_get"),
+    EndpointGuard::public("registry_child_list"),
+    EndpointGuard::public("registry_child_take"),
+    EndpointGuard::admin("get_recent_logs"),
+    EndpointGuard::admin("set_log_config"),
+    EndpointGuard::admin("get_log_config"),
+    EndpointGuard::admin("set_reclamation_policy"),
+    EndpointGuard::admin("get_reclamation_policy"),
+    EndpointGuard::admin("set_liveness_policy"),
+    EndpointGuard::read("get_liveness_policy"),
+    EndpointGuard::admin("run_reclamation"),
+    EndpointGuard::public("get_interface_version"),
+    EndpointGuard::public("describe_coordinator"),
+    EndpointGuard::admin("set_feature_flag"),
+    EndpointGuard::public("list_feature_flags"),
+    // Load testing, only in builds with the `load-test` feature
+    #[cfg(feature = "load-test")]
+    EndpointGuard::admin("load_test_register_agents"),
+    #[cfg(feature = "load-test")]
+    EndpointGuard::admin("load_test_start"),
+    #[cfg(feature = "load-test")]
+    EndpointGuard::admin("load_test_stop"),
+    #[cfg(feature = "load-test")]
+    EndpointGuard::admin("load_test_status"),
+    #[cfg(feature = "load-test")]
+    EndpointGuard::admin("load_test_teardown"),
+];
+
 impl Guards {
+    /// Run the chain declared for `method`: auth → role → rate limit → payload size → operational mode.
+    /// Endpoints without a declaration are refused.
+    pub fn check(method: &str) -> Result<(), String> {
+        let guard = Self::guard_for(method).ok_or_else(|| format!("No guard declared for endpoint {}", method))?;
+        let caller = caller();
+        let is_admin = is_controller(&caller);
+
+        match guard.access {
+            GuardAccess::Public => {}
+            GuardAccess::Authenticated => Self::require_caller_authenticated()?,
+            GuardAccess::Admin => Self::require_admin()?,
+        }
+        if let Some(limit) = guard.calls_per_minute {
+            if !is_admin {
+                Self::admit_call(caller, guard.method, limit, time())?;
+            }
+        }
+        let payload = call::arg_data_raw_size();
+        if payload > guard.max_payload_bytes {
+            return Err(format!("Payload of {} bytes exceeds the {} byte limit for {}", payload, guard.max_payload_bytes, method));
+        }
+        let mode = with_state(|state| state.config.operational_mode);
+        if !is_admin && mode > guard.runs_in {
+            return Err(format!("Unavailable: coordinator is in {:?} mode", mode));
+        }
+        Ok(())
+    }
+
+    pub fn guard_for(method: &str) -> Option<&'static EndpointGuard> {
+        ENDPOINT_GUARDS.iter().find(|guard| guard.method == method)
+    }
+
+    fn admit_call(caller: Principal, method: &'static str, limit: u32, now: u64) -> Result<(), String> {
+        CALL_WINDOWS.with(|windows| {
+            let mut windows = windows.borrow_mut();
+            if windows.len() >= MAX_TRACKED_WINDOWS {
+                windows.retain(|_, (start, _)| now.saturating_sub(*start) < RATE_WINDOW_NANOS);
+            }
+            let (start, count) = windows.entry((caller, method)).or_insert((now, 0));
+            if now.saturating_sub(*start) >= RATE_WINDOW_NANOS {
+                *start = now;
+                *count = 0;
+            }
+            if *count >= limit {
+                return Err(format!("RateLimited: at most {} calls per minute to {}", limit, method));
+            }
+            *count += 1;
+            Ok(())
+        })
+    }
+
     pub fn require_caller_authenticated() -> Result<(), String> {
         let caller = caller();
         if caller == Principal::anonymous() {
@@ -14,6 +393,14 @@ impl Guards {
         Ok(())
     }
     
+    /// Administrative endpoints are restricted to canister controllers
+    pub fn require_admin() -> Result<(), String> {
+        if !is_controller(&caller()) {
+            return Err("Admin access required".to_string());
+        }
+        Ok(())
+    }
+    
     pub fn validate_msg_id(msg_id: &str) -> Result<(), String> {
         if msg_id.is_empty() || msg_id.len() > 64 {
             return Err("Invalid msg_id format".to_string());
@@ -25,4 +412,40 @@ impl Guards {
         
         Ok(())
     }
-}
\ No newline at end of file
+    
+    pub fn validate_tags(tags: &[String]) -> Result<(), String> {
+        if tags.len() > 10 {
+            return Err("At most 10 tags are allowed per request".to_string());
+        }
+        
+        if tags.iter().any(|tag| tag.is_empty() || tag.len() > 64) {
+            return Err("Tags must be between 1 and 64 characters".to_string());
+        }
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_guard_table_has_one_entry_per_endpoint() {
+        let mut seen = HashSet::new();
+        for guard in ENDPOINT_GUARDS {
+            assert!(seen.insert(guard.method), "{} is declared twice", guard.method);
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_resets_each_window() {
+        let caller = Principal::anonymous();
+        for _ in 0..3 {
+            Guards::admit_call(caller, "route_request", 3, 0).unwrap();
+        }
+        assert!(Guards::admit_call(caller, "route_request", 3, 1).is_err());
+        assert!(Guards::admit_call(caller, "route_request", 3, RATE_WINDOW_NANOS).is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::domain::{LogConfig, LogLevel};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+thread_local! {
+    static LOGS: RefCell<LogBuffer> = RefCell::new(LogBuffer::default());
+}
+
+/// Leveled logging into a bounded in-canister buffer, optionally mirrored to the canister
+/// log. Entries below their module's threshold are dropped; once the buffer is full the
+/// oldest entries make room. The buffer does not survive upgrades, the config does.
+pub struct Log;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LogEntry {
+    pub seq: u64,
+    pub timestamp: u64,
+    pub level: LogLevel,
+    pub module: String,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct LogBuffer {
+    config: LogConfig,
+    entries: VecDeque<LogEntry>,
+    next_seq: u64,
+}
+
+impl Log {
+    const CAPACITY: usize = 2_000;
+    const MAX_MESSAGE_BYTES: usize = 1_024;
+
+    pub fn error(module: &str, message: impl Into<String>) {
+        Self::write(LogLevel::Error, module, message.into());
+    }
+
+    pub fn warn(module: &str, message: impl Into<String>) {
+        Self::write(LogLevel::Warn, module, message.into());
+    }
+
+    pub fn info(module: &str, message: impl Into<String>) {
+        Self::write(LogLevel::Info, module, message.into());
+    }
+
+    pub fn debug(module: &str, message: impl Into<String>) {
+        Self::write(LogLevel::Debug, module, message.into());
+    }
+
+    pub fn configure(config: LogConfig) {
+        LOGS.with(|logs| logs.borrow_mut().config = config);
+    }
+
+    /// Newest first: entries at least as severe as `level`, from `module` when given
+    pub fn recent(level: Option<LogLevel>, module: Option<&str>, limit: usize) -> Vec<LogEntry> {
+        LOGS.with(|logs| {
+            logs.borrow()
+                .entries
+                .iter()
+                .rev()
+                .filter(|entry| level.is_none_or(|level| entry.level <= level))
+                .filter(|entry| module.is_none_or(|module| entry.module == module))
+                .take(limit)
+                .cloned()
+                .collect()
+        })
+    }
+
+    fn write(level: LogLevel, module: &str, mut message: String) {
+        let timestamp = Self::now();
+        LOGS.with(|logs| {
+            let mut logs = logs.borrow_mut();
+            let threshold = logs.config.module_levels.get(module).copied().unwrap_or(logs.config.default_level);
+            if level > threshold {
+                return;
+            }
+            if message.len() > Self::MAX_MESSAGE_BYTES {
+                let mut end = Self::MAX_MESSAGE_BYTES;
+                while !message.is_char_boundary(end) {
+                    end -= 1;
+                }
+                message.truncate(end);
+            }
+            if logs.config.canister_log {
+                ic_cdk::println!("[{:?}] {}: {}", level, module, message);
+            }
+            if logs.entries.len() >= Self::CAPACITY {
+                logs.entries.pop_front();
+            }
+            let seq = logs.next_seq;
+            logs.next_seq += 1;
+            logs.entries.push_back(LogEntry { seq, timestamp, level, module: module.to_string(), message });
+        });
+    }
+
+    /// The system clock is only available inside a canister
+    #[cfg(target_arch = "wasm32")]
+    fn now() -> u64 {
+        ic_cdk::api::time()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn now() -> u64 {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thresholds_apply_per_module_and_queries_filter() {
+        Log::configure(LogConfig {
+            default_level: LogLevel::Warn,
+            module_levels: [("econ".to_string(), LogLevel::Debug)].into_iter().collect(),
+            canister_log: false,
+        });
+        Log::info("registry", "dropped below the default threshold");
+        Log::warn("registry", "kept");
+        Log::debug("econ", "kept by the module override");
+        Log::error("econ", "x".repeat(5_000));
+
+        let all = Log::recent(None, None, 10);
+        assert_eq!(all.iter().map(|e| e.module.as_str()).collect::<Vec<_>>(), vec!["econ", "econ", "registry"]);
+        assert_eq!(all[0].message.len(), Log::MAX_MESSAGE_BYTES);
+        assert!(all[0].seq > all[1].seq);
+        assert_eq!(Log::recent(Some(LogLevel::Warn), None, 10).len(), 2);
+        assert_eq!(Log::recent(None, Some("econ"), 1).len(), 1);
+    }
+}
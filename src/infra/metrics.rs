@@ -1,9 +1,11 @@
 use ic_cdk::api::time;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use crate::domain::DependencyHealth;
 
 thread_local! {
     static METRICS: RefCell<HashMap<String, u64>> = RefCell::new(HashMap::new());
+    static DEPENDENCIES: RefCell<HashMap<String, DependencyHealth>> = RefCell::new(HashMap::new());
 }
 
 pub struct Metrics;
@@ -16,9 +18,47 @@ impl Metrics {
         });
     }
     
+    pub fn add_to_counter(name: &str, amount: u64) {
+        METRICS.with(|m| {
+            *m.borrow_mut().entry(name.to_string()).or_insert(0) += amount;
+        });
+    }
+    
     pub fn get_counter(name: &str) -> u64 {
         METRICS.with(|m| {
             m.borrow().get(name).copied().unwrap_or(0)
         })
     }
+
+    /// Record a successful outbound call to `endpoint` (e.g. "econ.health", "agent.infer")
+    pub fn record_dependency_success(endpoint: &str) {
+        let now = time();
+        DEPENDENCIES.with(|d| {
+            let mut deps = d.borrow_mut();
+            let entry = deps.entry(endpoint.to_string()).or_insert_with(|| DependencyHealth::new(endpoint));
+            entry.success_count += 1;
+            entry.last_success_at = Some(now);
+        });
+    }
+
+    /// Record a failed outbound call to `endpoint`, keeping the latest error details
+    pub fn record_dependency_failure(endpoint: &str, error: &str) {
+        let now = time();
+        DEPENDENCIES.with(|d| {
+            let mut deps = d.borrow_mut();
+            let entry = deps.entry(endpoint.to_string()).or_insert_with(|| DependencyHealth::new(endpoint));
+            entry.failure_count += 1;
+            entry.last_error = Some(error.to_string());
+            entry.last_error_at = Some(now);
+        });
+        Self::increment_counter("dependency_failures_total");
+    }
+
+    pub fn get_dependency_health() -> Vec<DependencyHealth> {
+        DEPENDENCIES.with(|d| {
+            let mut deps: Vec<DependencyHealth> = d.borrow().values().cloned().collect();
+            deps.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+            deps
+        })
+    }
 }
\ No newline at end of file
@@ -1,5 +1,9 @@
 pub mod guards;
+pub mod logging;
 pub mod metrics;
+pub mod pagination;
 
 pub use guards::Guards;
-pub use metrics::Metrics;
\ No newline at end of file
+pub use logging::Log;
+pub use metrics::Metrics;
+pub use pagination::{Page, PageOrder, PageRequest, Paginator};
\ No newline at end of file
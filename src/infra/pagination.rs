@@ -0,0 +1,183 @@
+use crate::infra::Metrics;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Cost guard for collection queries: every page is capped by item count and by the
+/// estimated encoded size of the reply, and carries a continuation token for the rest.
+pub struct Paginator;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct PageRequest {
+    /// `next_cursor` from the previous page; omitted for the first page
+    pub cursor: Option<String>,
+    /// Items wanted, clamped to `Paginator::MAX_PAGE_SIZE`
+    pub limit: Option<u32>,
+    /// Items to skip, counted after the cursor when one is given
+    pub offset: Option<u64>,
+}
+
+impl PageRequest {
+    /// First page at the largest size, served by the unpaged endpoints kept for clients
+    /// that predate the `_paged` variants
+    pub fn capped() -> Option<Self> {
+        Some(Self { limit: Some(Paginator::MAX_PAGE_SIZE as u32), ..Self::default() })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Continuation token; `None` once the collection is exhausted
+    pub next_cursor: Option<String>,
+    /// The page stopped at the response size budget before reaching `limit`
+    pub truncated: bool,
+    /// Items in the listed collection, regardless of cursor and offset
+    pub total: u64,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page { items: self.items.into_iter().map(f).collect(), next_cursor: self.next_cursor, truncated: self.truncated, total: self.total }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PageOrder {
+    Ascending,
+    /// Largest key first, for newest-first listings
+    Descending,
+}
+
+impl Paginator {
+    pub const DEFAULT_PAGE_SIZE: usize = 100;
+    pub const MAX_PAGE_SIZE: usize = 500;
+    /// Estimated reply budget, well under the query response limit
+    pub const MAX_RESPONSE_BYTES: usize = 1536 * 1024;
+
+    /// Page of `items` ordered by `key`, resuming after the request's cursor. Keys must be
+    /// unique; a cursor stays valid when items are added or removed around it.
+    pub fn paginate<'a, T, I, K>(items: I, key: K, order: PageOrder, page: Option<PageRequest>) -> Page<T>
+    where
+        T: CandidType + Clone + 'a,
+        I: IntoIterator<Item = &'a T>,
+        K: Fn(&T) -> String,
+    {
+        let page = page.unwrap_or_default();
+        let limit = page.limit
+            .map(|limit| (limit as usize).clamp(1, Self::MAX_PAGE_SIZE))
+            .unwrap_or(Self::DEFAULT_PAGE_SIZE);
+
+        let mut total = 0u64;
+        let mut keyed: Vec<(String, &T)> = items
+            .into_iter()
+            .inspect(|_| total += 1)
+            .map(|item| (key(item), item))
+            .filter(|(item_key, _)| match (&page.cursor, order) {
+                (None, _) => true,
+                (Some(cursor), PageOrder::Ascending) => item_key > cursor,
+                (Some(cursor), PageOrder::Descending) => item_key < cursor,
+            })
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| match order {
+            PageOrder::Ascending => a.cmp(b),
+            PageOrder::Descending => b.cmp(a),
+        });
+        let skip = (page.offset.unwrap_or(0) as usize).min(keyed.len());
+        keyed.drain(..skip);
+
+        let mut result = Page { items: Vec::new(), next_cursor: None, truncated: false, total };
+        let mut bytes = 0usize;
+        for (item_key, item) in &keyed {
+            if result.items.len() >= limit {
+                break;
+            }
+            let size = Self::estimate_size(*item);
+            // Always return at least one item so a large entry cannot stall the cursor
+            if !result.items.is_empty() && bytes + size > Self::MAX_RESPONSE_BYTES {
+                result.truncated = true;
+                Metrics::increment_counter("query_pages_truncated");
+                break;
+            }
+            bytes += size;
+            result.items.push((*item).clone());
+            result.next_cursor = Some(item_key.clone());
+        }
+        if result.items.len() == keyed.len() {
+            result.next_cursor = None;
+        }
+        result
+    }
+
+    /// Candid-encoded size of one item; overstates slightly since each carries its own type table
+    fn estimate_size<T: CandidType>(item: &T) -> usize {
+        candid::encode_one(item).map(|bytes| bytes.len()).unwrap_or(0)
+    }
+
+    /// Key for time-ordered items, unique through the item's id
+    pub fn timestamp_key(timestamp: u64, id: &str) -> String {
+        format!("{:020}_{}", timestamp, id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(cursor: Option<String>, limit: u32) -> Option<PageRequest> {
+        Some(PageRequest { cursor, limit: Some(limit), offset: None })
+    }
+
+    #[test]
+    fn test_cursor_walks_every_item_once() {
+        let items: Vec<String> = (0..7).map(|n| format!("item_{}", n)).collect();
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = Paginator::paginate(&items, |item| item.clone(), PageOrder::Descending, request(cursor, 3));
+            assert!(page.items.len() <= 3);
+            seen.extend(page.items);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        let mut expected = items.clone();
+        expected.reverse();
+        assert_eq!(seen, expected);
+
+        // Limits are clamped to the cap
+        let page = Paginator::paginate(&items, |item| item.clone(), PageOrder::Ascending, request(None, 0));
+        assert_eq!(page.items.len(), 1);
+    }
+
+    #[test]
+    fn test_size_budget_truncates_page() {
+        let items: Vec<String> = (0..4).map(|n| format!("{}{}", n, "x".repeat(600 * 1024))).collect();
+        let page = Paginator::paginate(&items, |item| item[..1].to_string(), PageOrder::Ascending, None);
+        assert_eq!(page.items.len(), 2);
+        assert!(page.truncated);
+        assert_eq!(page.next_cursor.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_offset_skips_in_order_and_total_counts_everything() {
+        let items: Vec<String> = (0..7).map(|n| format!("item_{}", n)).collect();
+        let page = Paginator::paginate(&items, |item| item.clone(), PageOrder::Ascending, Some(PageRequest {
+            cursor: None,
+            limit: Some(2),
+            offset: Some(3),
+        }));
+        assert_eq!(page.items, vec!["item_3".to_string(), "item_4".to_string()]);
+        assert_eq!(page.total, 7);
+        assert_eq!(page.next_cursor.as_deref(), Some("item_4"));
+
+        let past_end = Paginator::paginate(&items, |item| item.clone(), PageOrder::Ascending, Some(PageRequest {
+            cursor: None,
+            limit: Some(2),
+            offset: Some(10),
+        }));
+        assert!(past_end.items.is_empty());
+        assert!(past_end.next_cursor.is_none());
+        assert_eq!(past_end.total, 7);
+    }
+}
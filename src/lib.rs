@@ -1,9 +1,12 @@
+#[cfg(feature = "canister")]
 pub mod api;
+pub mod client;
 pub mod domain;
 pub mod services;
 pub mod infra;
 
 // Re-export main types and functions
+#[cfg(feature = "canister")]
 pub use api::*;
 pub use domain::*;
 pub use services::*;
@@ -0,0 +1,130 @@
+use crate::services::{with_state, with_state_mut, CoordinatorState};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Live load pushed by the agents themselves. Each report replaces the agent's last
+/// one and becomes the `current_load` coordination weighs when delegating tasks;
+/// routing passes over saturated agents while unsaturated ones can serve. A report
+/// older than the staleness window counts as no load, so a silent agent is not
+/// shunned for a burst it reported long ago.
+pub struct AgentLoadService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentLoadReport {
+    pub queue_depth: u32,
+    /// The agent's own estimate of its compute use, 0.0 to 1.0
+    pub cpu_estimate: f32,
+    pub tokens_in_flight: u64,
+    pub reported_at: u64,
+    /// Combined load from 0.0 (idle) to 1.0 (saturated)
+    pub load: f32,
+}
+
+impl AgentLoadService {
+    const STALE_AFTER_NS: u64 = 5 * 60 * 1_000_000_000;
+    /// Queue depth and tokens in flight at which an agent counts as saturated
+    const SATURATED_QUEUE_DEPTH: u32 = 16;
+    const SATURATED_TOKENS_IN_FLIGHT: u64 = 64_000;
+    /// Open coordination tasks at which an agent counts as saturated
+    const SATURATED_OPEN_TASKS: usize = 8;
+
+    /// Record a load report from the agent or its owner
+    pub fn report(
+        caller: &str,
+        agent_id: &str,
+        queue_depth: u32,
+        cpu_estimate: f32,
+        tokens_in_flight: u64,
+    ) -> Result<AgentLoadReport, String> {
+        if !(0.0..=1.0).contains(&cpu_estimate) {
+            return Err("cpu_estimate must be between 0.0 and 1.0".to_string());
+        }
+        let now = time();
+        with_state_mut(|state| {
+            let agent = state.agents
+                .get(agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            if agent.agent_principal != caller && agent.canister_id != caller {
+                return Err("Only the agent or its owner can report its load".to_string());
+            }
+            let report = AgentLoadReport {
+                queue_depth,
+                cpu_estimate,
+                tokens_in_flight,
+                reported_at: now,
+                load: Self::load_factor(queue_depth, cpu_estimate, tokens_in_flight),
+            };
+            Self::record_in(state, agent_id, report.clone());
+            Ok(report)
+        })
+    }
+
+    fn record_in(state: &mut CoordinatorState, agent_id: &str, report: AgentLoadReport) {
+        if let Some(profile) = state.agent_capability_profiles.as_mut().and_then(|p| p.get_mut(agent_id)) {
+            profile.performance_metrics.current_load = report.load;
+        }
+        state.agent_loads.insert(agent_id.to_string(), report);
+        Metrics::increment_counter("agent_load_reports_total");
+    }
+
+    /// The most saturated of the three signals, each scaled to its saturation point
+    pub fn load_factor(queue_depth: u32, cpu_estimate: f32, tokens_in_flight: u64) -> f32 {
+        let queue = queue_depth as f32 / Self::SATURATED_QUEUE_DEPTH as f32;
+        let tokens = tokens_in_flight as f32 / Self::SATURATED_TOKENS_IN_FLIGHT as f32;
+        cpu_estimate.max(queue).max(tokens).min(1.0)
+    }
+
+    /// Latest reported load, or 0.0 when the agent has not reported recently
+    pub fn current_load_in(state: &CoordinatorState, agent_id: &str, now: u64) -> f32 {
+        state.agent_loads
+            .get(agent_id)
+            .filter(|report| now.saturating_sub(report.reported_at) < Self::STALE_AFTER_NS)
+            .map_or(0.0, |report| report.load)
+    }
+
+    /// Load selection weighs: the larger of the agent's fresh report and its share of
+    /// open coordination tasks, so agents that never report still count their work
+    pub fn routing_load_in(state: &CoordinatorState, agent_id: &str, now: u64) -> f32 {
+        let open_tasks = state.workload_index.open_task_count(agent_id) as f32 / Self::SATURATED_OPEN_TASKS as f32;
+        Self::current_load_in(state, agent_id, now).max(open_tasks).min(1.0)
+    }
+
+    pub fn routing_load(agent_id: &str, now: u64) -> f32 {
+        with_state(|state| Self::routing_load_in(state, agent_id, now))
+    }
+
+    pub fn is_saturated(agent_id: &str, now: u64) -> bool {
+        with_state(|state| Self::current_load_in(state, agent_id, now) >= 1.0)
+    }
+
+    pub fn get(agent_id: &str) -> Option<AgentLoadReport> {
+        with_state(|state| state.agent_loads.get(agent_id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_combines_signals_and_goes_stale() {
+        assert_eq!(AgentLoadService::load_factor(0, 0.2, 0), 0.2);
+        assert_eq!(AgentLoadService::load_factor(8, 0.2, 0), 0.5);
+        assert_eq!(AgentLoadService::load_factor(0, 0.0, 1_000_000), 1.0);
+
+        let mut state = CoordinatorState::default();
+        let report = AgentLoadReport {
+            queue_depth: 16,
+            cpu_estimate: 0.5,
+            tokens_in_flight: 0,
+            reported_at: 10,
+            load: AgentLoadService::load_factor(16, 0.5, 0),
+        };
+        AgentLoadService::record_in(&mut state, "a", report);
+        assert_eq!(AgentLoadService::current_load_in(&state, "a", 20), 1.0);
+        assert_eq!(AgentLoadService::current_load_in(&state, "a", 10 + AgentLoadService::STALE_AFTER_NS), 0.0);
+        assert_eq!(AgentLoadService::current_load_in(&state, "b", 20), 0.0);
+    }
+}
@@ -1,5 +1,7 @@
 use crate::domain::*;
-use crate::services::{with_state, with_state_mut, InstructionAnalyzerService};
+use crate::services::{with_state, with_state_mut, CoordinatorState, IdGenerator, InstructionAnalyzerService, RegistryAuditService, RegistryService, SloService, SpecializationService, WarmPoolService};
+use crate::services::quota_manager::QuotaManager;
+use crate::infra::{Log, Metrics};
 use ic_cdk::api::time;
 
 /// Agent spawning coordination service for OHMS 2.0
@@ -33,6 +35,7 @@ pub struct SpawnedAgent {
     pub specialization: String,
     pub model_id: String,
     pub capabilities: Vec<String>,
+    pub weight_class: AgentWeightClass,
     pub status: AgentStatus,
 }
 
@@ -51,6 +54,8 @@ pub enum AgentStatus {
     Initializing,
     Ready,
     Active,
+    Draining,
+    Decommissioned,
     Error,
 }
 
@@ -69,44 +74,111 @@ impl AgentSpawningService {
         request_id: &str,
         user_principal: &str,
         instructions: &str,
+        accept_downscaled: bool,
+        expected_state_version: Option<u64>,
     ) -> Result<SpawningResult, String> {
-        let start_time = time();
-        
         // Analyze instructions to get agent specifications
         let analysis = InstructionAnalyzerService::analyze_instructions(instructions, user_principal)?;
+        Self::spawn_from_analysis(request_id, user_principal, instructions, analysis, accept_downscaled, expected_state_version).await
+    }
+
+    /// Execute the plan of `analysis` as it stands, without re-analyzing
+    pub async fn spawn_from_analysis(
+        request_id: &str,
+        user_principal: &str,
+        instructions: &str,
+        analysis: InstructionAnalysisResult,
+        accept_downscaled: bool,
+        expected_state_version: Option<u64>,
+    ) -> Result<SpawningResult, String> {
+        let start_time = time();
+        
+        // Refuse to act on an analysis the caller confirmed against since-changed state
+        if let Some(expected) = expected_state_version {
+            let current = InstructionAnalyzerService::state_version(user_principal);
+            if expected != current {
+                return Err(format!(
+                    "ConflictDetected: quota or agents changed since analysis (version {} is now {}); re-analyze before spawning",
+                    expected, current
+                ));
+            }
+        }
+        let executed = analysis.clone();
+        
+        // Plans over quota only proceed if the caller accepted the reduced alternative
+        let (agent_specs, coordination_plan) = if analysis.quota_check.quota_available {
+            (analysis.suggested_agents, analysis.coordination_plan)
+        } else {
+            match analysis.downscaled_plan {
+                Some(plan) if accept_downscaled => (plan.suggested_agents, plan.coordination_plan),
+                Some(plan) => {
+                    return Err(format!(
+                        "Spawn quota exceeded: plan needs {} agents ({} units), {} agents and {} units remaining. A downscaled plan with {} agents is available; resubmit with accept_downscaled",
+                        analysis.suggested_agents.len(),
+                        analysis.weighted_cost,
+                        analysis.quota_check.remaining_agents,
+                        analysis.quota_check.remaining_weighted_units,
+                        plan.suggested_agents.len()
+                    ));
+                }
+                None => {
+                    return Err(format!(
+                        "Spawn quota exceeded: plan costs {} units, {} remaining",
+                        analysis.weighted_cost, analysis.quota_check.remaining_weighted_units
+                    ));
+                }
+            }
+        };
         
         // Create spawning request
         let spawning_request = SpawningRequest {
             request_id: request_id.to_string(),
             user_principal: user_principal.to_string(),
             instructions: instructions.to_string(),
-            agent_specs: analysis.suggested_agents,
-            coordination_plan: analysis.coordination_plan,
+            agent_specs,
+            coordination_plan,
+        };
+        
+        // Hold creation credits for every spec while the spawn runs, then give back those
+        // that did not come up
+        let reserved = QuotaManager::reserve_creation_credits(user_principal, spawning_request.agent_specs.len() as u32);
+        let spawned_agents = match Self::spawn_agent_instances(&spawning_request).await {
+            Ok(agents) => agents,
+            Err(e) => {
+                QuotaManager::settle_creation_credits(user_principal, request_id, reserved, 0, &e);
+                SloService::record_spawn((time() - start_time) / 1_000_000, false);
+                return Err(e);
+            }
         };
+        QuotaManager::settle_creation_credits(user_principal, request_id, reserved, spawned_agents.len() as u32, "partial spawn failure");
         
-        // Spawn agents
-        let spawned_agents = Self::spawn_agent_instances(&spawning_request).await?;
+        // Charge only the agents that were actually spawned
+        let spawned_units: u32 = spawned_agents.iter().map(|a| a.weight_class.units()).sum();
+        QuotaManager::record_weighted_usage(user_principal, spawned_units);
         
         // Setup coordination network if multiple agents
         let coordination_network_id = if spawned_agents.len() > 1 {
-            Some(Self::setup_coordination_network(&spawned_agents).await?)
+            Some(Self::setup_coordination_network(user_principal, &spawned_agents).await?)
         } else {
             None
         };
         
         // Determine final status
         let status = Self::determine_spawning_status(&spawned_agents);
+        let spawning_time_ms = (time() - start_time) / 1_000_000;
+        SloService::record_spawn(spawning_time_ms, status != SpawningStatus::Failed);
         
         let result = SpawningResult {
             request_id: request_id.to_string(),
             spawned_agents,
             coordination_network_id,
-            spawning_time_ms: time() - start_time,
+            spawning_time_ms,
             status,
         };
         
         // Store result in state
         Self::store_spawning_result(&result).await?;
+        InstructionAnalyzerService::store_analysis(user_principal, instructions, &executed, Some(request_id));
         
         Ok(result)
     }
@@ -116,11 +188,24 @@ impl AgentSpawningService {
         let mut spawned_agents = Vec::new();
         
         for (index, spec) in request.agent_specs.iter().enumerate() {
+            WarmPoolService::record_demand(&spec.specialization);
+            if let Some(agent) = WarmPoolService::claim(spec, &request.user_principal) {
+                spawned_agents.push(agent);
+                continue;
+            }
             match Self::create_agent_instance(spec, &request.user_principal, index).await {
                 Ok(agent) => spawned_agents.push(agent),
                 Err(e) => {
                     // Log error but continue with other agents
-                    ic_cdk::println!("Failed to spawn agent {}: {}", spec.agent_type, e);
+                    Log::warn("agent_spawning", format!("Failed to spawn agent {}: {}", spec.agent_type, e));
+                    let Some(substitute) = Self::substitute_spec(spec) else { continue };
+                    match Self::create_agent_instance(&substitute, &request.user_principal, index).await {
+                        Ok(agent) => {
+                            Metrics::increment_counter("specialization_fallback_spawns");
+                            spawned_agents.push(agent);
+                        }
+                        Err(e) => Log::warn("agent_spawning", format!("Failed to spawn substitute {} for {}: {}", substitute.specialization, spec.agent_type, e)),
+                    }
                 }
             }
         }
@@ -132,6 +217,25 @@ impl AgentSpawningService {
         Ok(spawned_agents)
     }
     
+    /// Spec for the nearest role the ontology lets stand in for `spec`'s specialization,
+    /// skipping roles heavier than the quota already reserved for `spec`
+    fn substitute_spec(spec: &AgentSpec) -> Option<AgentSpec> {
+        SpecializationService::fallbacks(&spec.specialization)
+            .into_iter()
+            .filter_map(|name| SpecializationService::resolve(&name))
+            .map(|role| {
+                let models = if role.models.is_empty() { spec.model_requirements.clone() } else { role.models };
+                AgentSpec {
+                    agent_type: format!("{} (covering {})", role.name, spec.agent_type),
+                    required_capabilities: role.capabilities,
+                    weight_class: AgentWeightClass::from_models(&models),
+                    model_requirements: models,
+                    specialization: role.name,
+                }
+            })
+            .find(|substitute| substitute.weight_class.units() <= spec.weight_class.units())
+    }
+    
     /// Create individual agent instance via cross-canister call
     async fn create_agent_instance(
         spec: &AgentSpec,
@@ -139,7 +243,7 @@ impl AgentSpawningService {
         index: usize,
     ) -> Result<SpawnedAgent, String> {
         // Generate unique agent ID
-        let agent_id = format!("agent_{}_{}_{}", user_principal, spec.agent_type, time());
+        let agent_id = IdGenerator::next("agent", user_principal);
         
         // Prepare agent creation parameters
         let agent_config = AgentCreationConfig {
@@ -152,11 +256,20 @@ impl AgentSpawningService {
         };
         
         // Make cross-canister call to agent canister
-        let call_result = Self::call_agent_canister_create(agent_config).await?;
+        let call_result = match Self::call_agent_canister_create(agent_config).await {
+            Ok(result) => result,
+            Err(e) => {
+                Metrics::record_dependency_failure("agent_factory.create", &e);
+                return Err(e);
+            }
+        };
         
         if !call_result.success {
-            return Err(call_result.error_message.unwrap_or_else(|| "Unknown error".to_string()));
+            let error = call_result.error_message.unwrap_or_else(|| "Unknown error".to_string());
+            Metrics::record_dependency_failure("agent_factory.create", &error);
+            return Err(error);
         }
+        Metrics::record_dependency_success("agent_factory.create");
         
         let canister_id = call_result.canister_id.ok_or_else(|| "No canister ID returned".to_string())?;
         
@@ -166,6 +279,7 @@ impl AgentSpawningService {
             specialization: spec.specialization.clone(),
             model_id: spec.model_requirements.first().unwrap_or(&"llama".to_string()).clone(),
             capabilities: spec.required_capabilities.clone(),
+            weight_class: spec.weight_class,
             status: AgentStatus::Initializing,
         })
     }
@@ -173,12 +287,7 @@ impl AgentSpawningService {
     /// Make cross-canister call to agent canister
     async fn call_agent_canister_create(config: AgentCreationConfig) -> Result<AgentCreationCallResult, String> {
         // Get the agent canister ID from coordinator state
-        let agent_canister_id = with_state(|state| {
-            // Use the first available agent canister or create new one
-            state.agents.values().next()
-                .map(|agent| agent.canister_id.clone())
-                .unwrap_or_else(|| Self::get_default_agent_canister_id())
-        });
+        let agent_canister_id = with_state(Self::agent_canister_id);
         
         // Prepare the agent registration for the existing agent canister system
         let agent_registration = AgentRegistration {
@@ -190,12 +299,20 @@ impl AgentSpawningService {
             health_score: 1.0,
             registered_at: time(),
             last_seen: time(),
+            tags: Default::default(),
+            agent_version: None,
+            protocol_version: None,
+            state: None,
+            lifecycle: Some(AgentLifecycle::Provisioning),
         };
         
-        // Register the agent in our coordinator state
+        // Register the agent through the shared registry path so stats and profiles exist,
+        // then mark it provisioned
         with_state_mut(|state| {
-            state.agents.insert(config.agent_id.clone(), agent_registration);
-        });
+            RegistryService::insert_registration(state, agent_registration, &config.user_principal);
+            state.awaiting_first_heartbeat.insert(config.agent_id.clone());
+            RegistryService::transition_lifecycle_in(state, &config.agent_id, AgentLifecycle::Ready, RegistryAuditService::SPAWNING, time())
+        })?;
         
         Ok(AgentCreationCallResult {
             success: true,
@@ -205,6 +322,13 @@ impl AgentSpawningService {
         })
     }
     
+    /// Canister new agents are created in: the first available agent canister or the default
+    pub(crate) fn agent_canister_id(state: &CoordinatorState) -> String {
+        state.agents.values().next()
+            .map(|agent| agent.canister_id.clone())
+            .unwrap_or_else(|| Self::get_default_agent_canister_id())
+    }
+    
     /// Get default agent canister ID from the known OHMS agent canister
     fn get_default_agent_canister_id() -> String {
         // Return the standard OHMS agent canister ID
@@ -212,10 +336,10 @@ impl AgentSpawningService {
     }
     
     /// Setup coordination network for multiple agents
-    async fn setup_coordination_network(agents: &[SpawnedAgent]) -> Result<String, String> {
+    async fn setup_coordination_network(user_principal: &str, agents: &[SpawnedAgent]) -> Result<String, String> {
         use crate::services::autonomous_coord::{CoordinationSession, CoordinationType};
         
-        let network_id = format!("network_{}", time());
+        let network_id = IdGenerator::next("network", user_principal);
         
         // Create coordination session for the spawned agents
         let session = CoordinationSession {
@@ -233,10 +357,14 @@ impl AgentSpawningService {
                 max_concurrent_tasks: 10,
                 allowed_capabilities: Some(agents.iter().flat_map(|a| a.capabilities.clone()).collect()),
             },
+            template_id: None,
+            role_bindings: vec![],
+            protocol: crate::services::session_templates::MessageProtocol::Mesh,
         };
         
         // Store coordination session in state
         with_state_mut(|state| {
+            state.workload_index.add_session(&session);
             if let Some(ref mut sessions) = state.coordination_sessions {
                 sessions.insert(network_id.clone(), session);
             } else {
@@ -246,50 +374,9 @@ impl AgentSpawningService {
             }
         });
         
-        // Set up agent capability profiles
-        Self::setup_agent_capability_profiles(agents).await?;
-        
         Ok(network_id)
     }
     
-    /// Setup capability profiles for coordinated agents
-    async fn setup_agent_capability_profiles(agents: &[SpawnedAgent]) -> Result<(), String> {
-        use crate::services::autonomous_coord::AgentCapabilityProfile;
-        
-        with_state_mut(|state| {
-            if state.agent_capability_profiles.is_none() {
-                state.agent_capability_profiles = Some(std::collections::HashMap::new());
-            }
-            
-            if let Some(ref mut profiles) = state.agent_capability_profiles {
-                for agent in agents {
-                    let profile = AgentCapabilityProfile {
-                        agent_id: agent.agent_id.clone(),
-                        capabilities: agent.capabilities.clone(),
-                        performance_metrics: crate::services::autonomous_coord::PerformanceMetrics {
-                            success_rate: 1.0,
-                            average_response_time_ms: 1000,
-                            current_load: 0.0,
-                            reliability_score: 1.0,
-                            tasks_completed: 0,
-                            collaboration_rating: 1.0,
-                        },
-                        availability_status: crate::services::autonomous_coord::AvailabilityStatus::Available,
-                        coordination_preferences: crate::services::autonomous_coord::CoordinationPreferences {
-                            preferred_coordination_types: vec![crate::services::autonomous_coord::CoordinationType::CollaborativePlanning],
-                            max_concurrent_collaborations: 3,
-                            communication_frequency: crate::services::autonomous_coord::CommunicationFrequency::Normal,
-                            conflict_resolution_strategy: crate::services::autonomous_coord::ConflictResolutionStrategy::Consensus,
-                        },
-                    };
-                    profiles.insert(agent.agent_id.clone(), profile);
-                }
-            }
-        });
-        
-        Ok(())
-    }
-    
     /// Determine overall spawning status
     fn determine_spawning_status(agents: &[SpawnedAgent]) -> SpawningStatus {
         if agents.is_empty() {
@@ -322,6 +409,7 @@ impl AgentSpawningService {
                 SpawningStatus::PartialSuccess => AgentCreationStatus::Completed, // Treat as success
                 SpawningStatus::InProgress => AgentCreationStatus::InProgress,
             },
+            coordination_network_id: result.coordination_network_id.clone(),
         };
         
         with_state_mut(|state| {
@@ -340,21 +428,36 @@ impl AgentSpawningService {
         Ok(result)
     }
     
-    /// Update agent status
-    pub fn update_agent_status(agent_id: &str, new_status: AgentStatus) -> Result<(), String> {
+    /// Update agent status. Lifecycle statuses move the agent through the registry's
+    /// lifecycle; `Error` only zeroes its health so it stops receiving traffic, and a
+    /// later `Ready` or `Active` restores it.
+    pub fn update_agent_status(caller: &str, agent_id: &str, new_status: AgentStatus) -> Result<(), String> {
+        let now = time();
+        let lifecycle = match new_status {
+            AgentStatus::Initializing => Some(AgentLifecycle::Provisioning),
+            AgentStatus::Ready => Some(AgentLifecycle::Ready),
+            AgentStatus::Active => Some(AgentLifecycle::Active),
+            AgentStatus::Draining => Some(AgentLifecycle::Draining),
+            AgentStatus::Decommissioned => Some(AgentLifecycle::Decommissioned),
+            AgentStatus::Error => None,
+        };
         with_state_mut(|state| {
+            match lifecycle {
+                Some(to) => {
+                    RegistryService::transition_lifecycle_in(state, agent_id, to, caller, now)?;
+                    if matches!(to, AgentLifecycle::Ready | AgentLifecycle::Active) {
+                        RegistryService::set_health_in(state, agent_id, 1.0, caller, now);
+                    }
+                }
+                None => {
+                    RegistryService::set_health_in(state, agent_id, 0.0, caller, now);
+                }
+            }
             if let Some(agent) = state.agents.get_mut(agent_id) {
-                // Update health score based on status
-                agent.health_score = match new_status {
-                    AgentStatus::Ready | AgentStatus::Active => 1.0,
-                    AgentStatus::Initializing => 0.5,
-                    AgentStatus::Error => 0.0,
-                };
-                agent.last_seen = time();
+                agent.last_seen = now;
             }
-        });
-        
-        Ok(())
+            Ok(())
+        })
     }
 }
 
@@ -382,6 +485,7 @@ mod tests {
                 specialization: "Developer".to_string(),
                 model_id: "llama".to_string(),
                 capabilities: vec!["coding".to_string()],
+                weight_class: AgentWeightClass::Standard,
                 status: AgentStatus::Ready,
             },
             SpawnedAgent {
@@ -390,6 +494,7 @@ mod tests {
                 specialization: "Tester".to_string(),
                 model_id: "llama".to_string(),
                 capabilities: vec!["testing".to_string()],
+                weight_class: AgentWeightClass::Standard,
                 status: AgentStatus::Ready,
             },
         ];
@@ -407,6 +512,7 @@ mod tests {
                 specialization: "Developer".to_string(),
                 model_id: "llama".to_string(),
                 capabilities: vec!["coding".to_string()],
+                weight_class: AgentWeightClass::Standard,
                 status: AgentStatus::Ready,
             },
             SpawnedAgent {
@@ -415,6 +521,7 @@ mod tests {
                 specialization: "Tester".to_string(),
                 model_id: "llama".to_string(),
                 capabilities: vec!["testing".to_string()],
+                weight_class: AgentWeightClass::Standard,
                 status: AgentStatus::Error,
             },
         ];
@@ -422,4 +529,14 @@ mod tests {
         let status = AgentSpawningService::determine_spawning_status(&agents);
         assert_eq!(status, SpawningStatus::PartialSuccess);
     }
+
+    #[test]
+    fn test_weight_class_from_primary_model() {
+        let heavy = vec!["wizardcoder".to_string(), "gemma".to_string()];
+        let light = vec!["gemma-2b".to_string()];
+        assert_eq!(AgentWeightClass::from_models(&heavy), AgentWeightClass::Heavy);
+        assert_eq!(AgentWeightClass::from_models(&light), AgentWeightClass::Light);
+        assert_eq!(AgentWeightClass::from_models(&[]), AgentWeightClass::Standard);
+        assert_eq!(AgentWeightClass::Heavy.units(), 4 * AgentWeightClass::Light.units());
+    }
 }
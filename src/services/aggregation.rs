@@ -0,0 +1,159 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Combines the answers a fanout collected into one result. Best-of-n keeps the single
+/// highest-scoring answer; the voting modes group answers that say the same thing once
+/// parsed, so a lone well-scored outlier loses to agreement; concatenation keeps every
+/// answer for callers that merge them downstream.
+pub struct AggregationService;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum FanoutAggregation {
+    /// Highest score wins; ties go to the faster answer, then the lower agent id
+    #[default]
+    BestOfN,
+    /// The answer most agents gave wins; ties go to the group with the higher total score
+    MajorityVote,
+    /// Like a majority vote, but answers passing every verifier count four times as much
+    VerifierWeighted,
+    /// Every answer in score order, separated by blank lines; there is no winner, so no
+    /// peer review
+    Concatenation,
+}
+
+/// One answer within the fanout window
+#[derive(Debug, Clone)]
+pub struct FanoutAnswer {
+    pub agent_id: String,
+    pub output: String,
+    pub score: f32,
+    /// Milliseconds the agent took to answer
+    pub elapsed: u64,
+    pub verified: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateOutcome {
+    /// Answer credited with the result; none when answers were concatenated
+    pub winner: Option<usize>,
+    pub output: String,
+    /// Answers agreeing with the result
+    pub support: u32,
+}
+
+impl AggregationService {
+    const UNVERIFIED_WEIGHT: f32 = 0.25;
+
+    pub fn aggregate(mode: FanoutAggregation, answers: &[FanoutAnswer]) -> Option<AggregateOutcome> {
+        if answers.is_empty() {
+            return None;
+        }
+        let outcome = match mode {
+            FanoutAggregation::BestOfN => {
+                let winner = Self::best_of(answers, 0..answers.len());
+                AggregateOutcome { winner: Some(winner), output: answers[winner].output.clone(), support: 1 }
+            }
+            FanoutAggregation::MajorityVote => Self::vote(answers, |_| 1.0),
+            FanoutAggregation::VerifierWeighted => Self::vote(answers, |a| if a.verified { 1.0 } else { Self::UNVERIFIED_WEIGHT }),
+            FanoutAggregation::Concatenation => {
+                let mut order: Vec<usize> = (0..answers.len()).collect();
+                order.sort_by(|&a, &b| Self::rank(answers, a, b));
+                AggregateOutcome {
+                    winner: None,
+                    output: order.iter().map(|&i| answers[i].output.trim()).collect::<Vec<_>>().join("\n\n"),
+                    support: answers.len() as u32,
+                }
+            }
+        };
+        Some(outcome)
+    }
+
+    /// Better answer first: higher score, then faster, then lower agent id
+    fn rank(answers: &[FanoutAnswer], a: usize, b: usize) -> std::cmp::Ordering {
+        let (a, b) = (&answers[a], &answers[b]);
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.elapsed.cmp(&b.elapsed))
+            .then(a.agent_id.cmp(&b.agent_id))
+    }
+
+    fn best_of(answers: &[FanoutAnswer], indices: impl Iterator<Item = usize>) -> usize {
+        indices.min_by(|&a, &b| Self::rank(answers, a, b)).expect("non-empty answers")
+    }
+
+    fn vote(answers: &[FanoutAnswer], weight: impl Fn(&FanoutAnswer) -> f32) -> AggregateOutcome {
+        let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, answer) in answers.iter().enumerate() {
+            groups.entry(Self::parsed(&answer.output)).or_default().push(i);
+        }
+        let tally = |members: &Vec<usize>| -> (f32, f32) {
+            (
+                members.iter().map(|&i| weight(&answers[i])).sum(),
+                members.iter().map(|&i| answers[i].score).sum(),
+            )
+        };
+        let members = groups
+            .values()
+            .max_by(|a, b| {
+                let (votes_a, score_a) = tally(a);
+                let (votes_b, score_b) = tally(b);
+                votes_a.partial_cmp(&votes_b)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(score_a.partial_cmp(&score_b).unwrap_or(std::cmp::Ordering::Equal))
+            })
+            .expect("non-empty answers");
+        let winner = Self::best_of(answers, members.iter().copied());
+        AggregateOutcome { winner: Some(winner), output: answers[winner].output.clone(), support: members.len() as u32 }
+    }
+
+    /// Comparable form of an answer: canonical JSON when it parses, otherwise the text
+    /// lowercased with whitespace collapsed and trailing punctuation dropped
+    fn parsed(output: &str) -> String {
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(output.trim()) {
+            return value.to_string();
+        }
+        output
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end_matches(['.', '!'])
+            .to_lowercase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(agent_id: &str, output: &str, score: f32, verified: bool) -> FanoutAnswer {
+        FanoutAnswer { agent_id: agent_id.to_string(), output: output.to_string(), score, elapsed: 10, verified }
+    }
+
+    #[test]
+    fn test_aggregation_modes() {
+        let answers = vec![
+            answer("a", "Paris", 0.9, false),
+            answer("b", "  london. ", 0.5, true),
+            answer("c", "London", 0.6, true),
+            answer("d", "{\"city\": \"Rome\"}", 0.4, true),
+        ];
+        let best = AggregationService::aggregate(FanoutAggregation::BestOfN, &answers).unwrap();
+        assert_eq!(best.winner, Some(0));
+
+        let vote = AggregationService::aggregate(FanoutAggregation::MajorityVote, &answers).unwrap();
+        assert_eq!((vote.winner, vote.support), (Some(2), 2));
+
+        let weighted = AggregationService::aggregate(FanoutAggregation::VerifierWeighted, &[
+            answer("a", "Paris", 0.9, false),
+            answer("e", "Paris", 0.8, false),
+            answer("c", "London", 0.6, true),
+        ]).unwrap();
+        assert_eq!(weighted.output, "London");
+
+        let joined = AggregationService::aggregate(FanoutAggregation::Concatenation, &answers[..2]).unwrap();
+        assert_eq!((joined.winner, joined.output.as_str()), (None, "Paris\n\nlondon."));
+        assert!(AggregationService::aggregate(FanoutAggregation::BestOfN, &[]).is_none());
+    }
+}
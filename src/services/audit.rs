@@ -0,0 +1,81 @@
+use crate::services::{with_state, with_state_mut};
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Append-only record of state-changing actions taken on a principal's behalf
+pub struct AuditService;
+
+/// Kind of audited action
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum AuditAction {
+    CreationCreditRefund,
+    DormantAccountReclaimed,
+    DormantAccountDowngraded,
+    SecretStored,
+    SecretDeleted,
+    SecretAccessed,
+    StateSnapshotExported,
+    StateSnapshotImported,
+    AgentApproved,
+    AgentRejected,
+    AgentQuarantined,
+    AgentReleased,
+    SpoofedMessageRejected,
+    AgentDeregistered,
+    AgentEvicted,
+    RegistrySnapshotExported,
+    RegistrySnapshotImported,
+    StandbyPromoted,
+    VerificationDisputeResolved,
+    AgentSuspended,
+    AgentReinstated,
+    AgentRetired,
+}
+
+/// Single audit log entry
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AuditEntry {
+    pub entry_id: String,
+    pub principal: String,
+    pub action: AuditAction,
+    pub subject: String,
+    pub detail: String,
+    pub recorded_at: u64,
+}
+
+impl AuditService {
+    const MAX_ENTRIES: usize = 5000;
+
+    /// Append an entry, evicting the oldest once the log is full
+    pub fn record(principal: &str, action: AuditAction, subject: &str, detail: String) {
+        let now = time();
+        with_state_mut(|state| {
+            let entry = AuditEntry {
+                entry_id: format!("audit_{}_{}", now, state.audit_log.len()),
+                principal: principal.to_string(),
+                action,
+                subject: subject.to_string(),
+                detail,
+                recorded_at: now,
+            };
+            if state.audit_log.len() >= Self::MAX_ENTRIES {
+                state.audit_log.remove(0);
+            }
+            state.audit_log.push(entry);
+        });
+    }
+
+    /// Entries concerning a principal, newest first
+    pub fn list_for_principal(principal: &str, limit: usize) -> Vec<AuditEntry> {
+        with_state(|state| {
+            state.audit_log
+                .iter()
+                .rev()
+                .filter(|e| e.principal == principal)
+                .take(limit)
+                .cloned()
+                .collect()
+        })
+    }
+}
@@ -1,5 +1,9 @@
 use crate::domain::*;
-use crate::services::{with_state, with_state_mut};
+use crate::services::{with_state, with_state_mut, AgentLoadService, AvailabilityService, ChatterLimiter, ConcurrencyService, CoordinationPreferenceService, IdGenerator, MessageExpiryService, ProvenanceService, QuotaManager, ReputationService};
+use crate::services::provenance::{ProvenanceStage, StepRecord};
+use crate::services::availability::{AvailabilityWindow, MaintenanceWindow};
+use crate::infra::Metrics;
+use crate::services::session_templates::{MessageProtocol, RoleBinding, SessionTemplateService};
 use ic_cdk::api::time;
 use serde::{Deserialize, Serialize};
 use candid::CandidType;
@@ -16,6 +20,8 @@ pub enum AgentMessage {
         description: String,
         required_capabilities: Vec<String>,
         priority: MessagePriority,
+        deadline: Option<u64>,
+        parent_task_id: Option<String>,
     },
     TaskResponse {
         task_id: String,
@@ -35,15 +41,52 @@ pub enum AgentMessage {
         coordination_type: CoordinationType,
         data: String,
     },
+    /// Stop work on a cancelled task or session; acknowledge via the coordinator
+    Cancellation {
+        cancellation_id: String,
+        target_id: String,
+        reason: String,
+    },
 }
 
-/// Message priority levels for task distribution
+/// Task ledger entry; delegated sub-tasks link back to their parent
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
-pub enum MessagePriority {
-    Low,
-    Normal,
-    High,
-    Critical,
+pub struct TaskLedgerEntry {
+    pub task_id: String,
+    pub parent_task_id: Option<String>,
+    pub root_task_id: String,
+    pub delegation_depth: u32,
+    pub assigned_agent: String,
+    pub description: String,
+    /// Kept so the task can be handed to another agent
+    #[serde(default)]
+    pub required_capabilities: Vec<String>,
+    pub priority: MessagePriority,
+    pub deadline: Option<u64>,
+    pub status: TaskStatus,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[cfg(test)]
+impl TaskLedgerEntry {
+    /// Top-level task assigned to `agent_id`, for tests to adjust with struct update syntax
+    pub fn test(task_id: &str, agent_id: &str, status: TaskStatus) -> Self {
+        TaskLedgerEntry {
+            task_id: task_id.to_string(),
+            parent_task_id: None,
+            root_task_id: task_id.to_string(),
+            delegation_depth: 0,
+            assigned_agent: agent_id.to_string(),
+            description: String::new(),
+            required_capabilities: Vec::new(),
+            priority: MessagePriority::Normal,
+            deadline: None,
+            status,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
 }
 
 /// Task execution status
@@ -54,6 +97,8 @@ pub enum TaskStatus {
     Completed,
     Failed,
     Cancelled,
+    /// The TaskRequest expired in the agent's queue undelivered; the task needs a new agent
+    AwaitingReassignment,
 }
 
 /// Types of coordination between agents
@@ -78,6 +123,20 @@ pub struct CoordinationSession {
     pub last_activity: u64,
     pub messages: Vec<CoordinationMessage>,
     pub resource_constraints: ResourceConstraints,
+    /// Template the session was started from, with the agents bound to each role
+    #[serde(default)]
+    pub template_id: Option<String>,
+    #[serde(default)]
+    pub role_bindings: Vec<RoleBinding>,
+    #[serde(default)]
+    pub protocol: MessageProtocol,
+}
+
+impl CoordinationSession {
+    /// A participant or the session's coordinator
+    pub fn is_member(&self, agent_id: &str) -> bool {
+        self.coordinator_agent == agent_id || self.participants.iter().any(|p| p == agent_id)
+    }
 }
 
 /// Coordination session status
@@ -88,6 +147,7 @@ pub enum SessionStatus {
     Completed,
     Failed,
     Timeout,
+    Cancelled,
 }
 
 /// Message within a coordination session
@@ -116,7 +176,40 @@ pub struct AgentCapabilityProfile {
     pub capabilities: Vec<String>,
     pub performance_metrics: PerformanceMetrics,
     pub availability_status: AvailabilityStatus,
+    /// Effective preferences: the agent's declaration reconciled with coordinator limits
     pub coordination_preferences: CoordinationPreferences,
+    #[serde(default)]
+    pub declared_preferences: Option<CoordinationPreferences>,
+    #[serde(default)]
+    pub preference_adjustments: Vec<String>,
+    #[serde(default)]
+    pub availability_windows: Vec<AvailabilityWindow>,
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceWindow>,
+}
+
+impl AgentCapabilityProfile {
+    /// Default profile for a freshly registered agent
+    pub fn initial(agent_id: &str, capabilities: Vec<String>) -> Self {
+        Self {
+            agent_id: agent_id.to_string(),
+            capabilities,
+            performance_metrics: PerformanceMetrics {
+                success_rate: 1.0,
+                average_response_time_ms: 1000,
+                current_load: 0.0,
+                reliability_score: 1.0,
+                tasks_completed: 0,
+                collaboration_rating: 1.0,
+            },
+            availability_status: AvailabilityStatus::Available,
+            coordination_preferences: CoordinationPreferences::default(),
+            declared_preferences: None,
+            preference_adjustments: Vec::new(),
+            availability_windows: Vec::new(),
+            maintenance: None,
+        }
+    }
 }
 
 /// Performance metrics for agent coordination
@@ -149,6 +242,18 @@ pub struct CoordinationPreferences {
     pub conflict_resolution_strategy: ConflictResolutionStrategy,
 }
 
+/// What the coordinator assumes until an agent declares its own preferences
+impl Default for CoordinationPreferences {
+    fn default() -> Self {
+        Self {
+            preferred_coordination_types: vec![CoordinationType::CollaborativePlanning],
+            max_concurrent_collaborations: 3,
+            communication_frequency: CommunicationFrequency::Normal,
+            conflict_resolution_strategy: ConflictResolutionStrategy::Consensus,
+        }
+    }
+}
+
 /// Communication frequency preferences
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub enum CommunicationFrequency {
@@ -175,7 +280,10 @@ impl AutonomousCoordinationService {
         coordinator_agent: String,
         resource_constraints: ResourceConstraints,
     ) -> Result<CoordinationSession, String> {
-        let session_id = format!("coord_{}", time());
+        ConcurrencyService::check_activation(&participant_agents)?;
+        CoordinationPreferenceService::check_collaborations(&participant_agents)?;
+        QuotaManager::admit_session(&coordinator_agent)?;
+        let session_id = IdGenerator::next("coord", &coordinator_agent);
         let session = CoordinationSession {
             session_id: session_id.clone(),
             participants: participant_agents,
@@ -186,6 +294,9 @@ impl AutonomousCoordinationService {
             last_activity: time(),
             messages: Vec::new(),
             resource_constraints,
+            template_id: None,
+            role_bindings: Vec::new(),
+            protocol: MessageProtocol::Mesh,
         };
 
         // Store coordination session
@@ -193,6 +304,7 @@ impl AutonomousCoordinationService {
             if state.coordination_sessions.is_none() {
                 state.coordination_sessions = Some(HashMap::new());
             }
+            state.workload_index.add_session(&session);
             state.coordination_sessions.as_mut().unwrap()
                 .insert(session_id, session.clone());
         });
@@ -207,6 +319,14 @@ impl AutonomousCoordinationService {
         to_agent: Option<String>,
         message: AgentMessage,
     ) -> Result<(), String> {
+        let session = with_state(|state| {
+            state.coordination_sessions.as_ref().and_then(|s| s.get(&session_id).cloned())
+        }).ok_or_else(|| "Coordination session not found".to_string())?;
+        SessionTemplateService::permits(&session, &from_agent, to_agent.as_deref())?;
+        ChatterLimiter::admit(&session_id, &from_agent, &message)?;
+        QuotaManager::admit_message(&session_id, &from_agent, &message)?;
+        Self::record_task_result(&from_agent, &message);
+
         with_state_mut(|state| {
             if let Some(sessions) = &mut state.coordination_sessions {
                 if let Some(session) = sessions.get_mut(&session_id) {
@@ -237,20 +357,118 @@ impl AutonomousCoordinationService {
         })
     }
 
+    /// A result reported by the agent assigned to a ledger task joins its delegation chain
+    /// and counts toward the agent's reputation when it reports the task finished
+    fn record_task_result(from_agent: &str, message: &AgentMessage) {
+        let AgentMessage::TaskResponse { task_id, status, result: Some(result), .. } = message else { return };
+        let Some(task) = Self::get_task(task_id) else { return };
+        if task.assigned_agent != from_agent {
+            return;
+        }
+        match status {
+            TaskStatus::Completed => ReputationService::record_task(from_agent, true),
+            TaskStatus::Failed => ReputationService::record_task(from_agent, false),
+            _ => {}
+        }
+        ProvenanceService::record(&task.root_task_id, StepRecord {
+            stage: ProvenanceStage::TaskResult,
+            agent_id: from_agent,
+            part: format!("task {}", task_id),
+            input: None,
+            output: Some(result),
+        });
+        if task.parent_task_id.is_none() {
+            ProvenanceService::finalize(&task.root_task_id, result);
+        }
+    }
+
     /// Process task distribution among agents
     pub async fn distribute_task(
         task_description: String,
         required_capabilities: Vec<String>,
         priority: MessagePriority,
+        deadline: Option<u64>,
+    ) -> Result<String, String> {
+        Self::assign_task(task_description, required_capabilities, priority, deadline, None, None, None).await
+    }
+
+    /// Distribute a task to one of `agents` only, for work that belongs to a session
+    pub async fn distribute_task_within(
+        task_description: String,
+        required_capabilities: Vec<String>,
+        priority: MessagePriority,
+        deadline: Option<u64>,
+        agents: &[String],
+    ) -> Result<String, String> {
+        Self::assign_task(task_description, required_capabilities, priority, deadline, None, None, Some(agents)).await
+    }
+
+    /// Delegate a sub-task from the agent holding `parent_task_id`. The sub-task inherits
+    /// the parent's urgency: the higher priority and the earlier deadline win.
+    pub async fn delegate_task(
+        parent_task_id: String,
+        delegating_agent: String,
+        task_description: String,
+        required_capabilities: Vec<String>,
+        requested_priority: Option<MessagePriority>,
+        requested_deadline: Option<u64>,
     ) -> Result<String, String> {
-        let task_id = format!("task_{}", time());
+        const MAX_DELEGATION_DEPTH: u32 = 8;
+
+        let parent = with_state(|state| state.task_ledger.get(&parent_task_id).cloned())
+            .ok_or_else(|| "Parent task not found".to_string())?;
+        if parent.assigned_agent != delegating_agent {
+            return Err("Only the agent assigned to the parent task can delegate it".to_string());
+        }
+        if parent.delegation_depth >= MAX_DELEGATION_DEPTH {
+            return Err(format!("Delegation chain exceeds {} levels", MAX_DELEGATION_DEPTH));
+        }
+
+        let priority = requested_priority.map_or(parent.priority, |p| p.max(parent.priority));
+        let deadline = match (parent.deadline, requested_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        Self::assign_task(task_description, required_capabilities, priority, deadline, Some(parent), Some(delegating_agent), None).await
+    }
+
+    /// Select an agent, from `within` when given, queue the TaskRequest and record it in the ledger
+    async fn assign_task(
+        task_description: String,
+        required_capabilities: Vec<String>,
+        priority: MessagePriority,
+        deadline: Option<u64>,
+        parent: Option<TaskLedgerEntry>,
+        exclude_agent: Option<String>,
+        within: Option<&[String]>,
+    ) -> Result<String, String> {
+        let task_id = IdGenerator::next("task", exclude_agent.as_deref().unwrap_or_default());
         
         // Find available agents with required capabilities
-        let suitable_agents = Self::find_suitable_agents(&required_capabilities).await?;
+        let mut suitable_agents = Self::find_suitable_agents(&required_capabilities, &priority).await?;
+        if let Some(excluded) = &exclude_agent {
+            suitable_agents.retain(|agent| &agent.agent_id != excluded);
+        }
+        if let Some(within) = within {
+            suitable_agents.retain(|agent| within.contains(&agent.agent_id));
+        }
         
         if suitable_agents.is_empty() {
             return Err("No suitable agents available for task".to_string());
         }
+        // Skip agents whose owners are at their concurrency cap
+        let mut concurrency_error = None;
+        suitable_agents.retain(|agent| match ConcurrencyService::check_activation(std::slice::from_ref(&agent.agent_id)) {
+            Ok(()) => true,
+            Err(e) => {
+                concurrency_error.get_or_insert(e);
+                false
+            }
+        });
+        if suitable_agents.is_empty() {
+            return Err(concurrency_error.unwrap_or_else(|| "No suitable agents available for task".to_string()));
+        }
 
         // Select best agent based on performance metrics and availability
         let selected_agent = Self::select_optimal_agent(&suitable_agents, &priority).await?;
@@ -258,10 +476,42 @@ impl AutonomousCoordinationService {
         // Create task request message
         let task_message = AgentMessage::TaskRequest {
             task_id: task_id.clone(),
+            description: task_description.clone(),
+            required_capabilities: required_capabilities.clone(),
+            priority,
+            deadline,
+            parent_task_id: parent.as_ref().map(|p| p.task_id.clone()),
+        };
+
+        let now = time();
+        let entry = TaskLedgerEntry {
+            task_id: task_id.clone(),
+            parent_task_id: parent.as_ref().map(|p| p.task_id.clone()),
+            root_task_id: parent.as_ref().map(|p| p.root_task_id.clone()).unwrap_or_else(|| task_id.clone()),
+            delegation_depth: parent.as_ref().map(|p| p.delegation_depth + 1).unwrap_or(0),
+            assigned_agent: selected_agent.clone(),
             description: task_description,
             required_capabilities,
             priority,
+            deadline,
+            status: TaskStatus::Pending,
+            created_at: now,
+            updated_at: now,
         };
+        ProvenanceService::record(&entry.root_task_id, StepRecord {
+            stage: ProvenanceStage::Delegation,
+            agent_id: &selected_agent,
+            part: match &entry.parent_task_id {
+                Some(parent_task_id) => format!("task {} (from {})", task_id, parent_task_id),
+                None => format!("task {}", task_id),
+            },
+            input: Some(&entry.description),
+            output: None,
+        });
+        with_state_mut(|state| {
+            state.workload_index.update_task(&entry);
+            state.task_ledger.insert(task_id.clone(), entry);
+        });
 
         // Send task to selected agent
         Self::route_message_to_agent(selected_agent, task_message).await?;
@@ -269,10 +519,76 @@ impl AutonomousCoordinationService {
         Ok(task_id)
     }
 
+    /// Hand an open task to another suitable agent, re-sending its TaskRequest. With no
+    /// agent to take it, the task is left awaiting reassignment and `None` is returned.
+    pub(crate) async fn reassign_task(task_id: &str) -> Option<String> {
+        let task = Self::get_task(task_id)?;
+        if !matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress | TaskStatus::AwaitingReassignment) {
+            return None;
+        }
+        let mut suitable = Self::find_suitable_agents(&task.required_capabilities, &task.priority).await.unwrap_or_default();
+        suitable.retain(|agent| {
+            agent.agent_id != task.assigned_agent
+                && ConcurrencyService::check_activation(std::slice::from_ref(&agent.agent_id)).is_ok()
+        });
+        let selected = match Self::select_optimal_agent(&suitable, &task.priority).await {
+            Ok(agent_id) => agent_id,
+            Err(_) => {
+                with_state_mut(|state| {
+                    if let Some(entry) = state.task_ledger.get_mut(task_id) {
+                        entry.status = TaskStatus::AwaitingReassignment;
+                        entry.updated_at = time();
+                        state.workload_index.update_task(entry);
+                    }
+                });
+                return None;
+            }
+        };
+
+        with_state_mut(|state| {
+            if let Some(entry) = state.task_ledger.get_mut(task_id) {
+                entry.assigned_agent = selected.clone();
+                entry.status = TaskStatus::Pending;
+                entry.updated_at = time();
+                state.workload_index.update_task(entry);
+            }
+        });
+        let message = AgentMessage::TaskRequest {
+            task_id: task.task_id,
+            description: task.description,
+            required_capabilities: task.required_capabilities,
+            priority: task.priority,
+            deadline: task.deadline,
+            parent_task_id: task.parent_task_id,
+        };
+        Self::route_message_to_agent(selected.clone(), message).await.ok()?;
+        Metrics::increment_counter("tasks_reassigned_total");
+        Some(selected)
+    }
+
+    pub fn get_task(task_id: &str) -> Option<TaskLedgerEntry> {
+        with_state(|state| state.task_ledger.get(task_id).cloned())
+    }
+
+    /// Urgency ordering: higher priority first, then earliest deadline; other messages keep FIFO order
+    fn urgency(message: &AgentMessage) -> (std::cmp::Reverse<MessagePriority>, u64) {
+        match message {
+            AgentMessage::TaskRequest { priority, deadline, .. } => {
+                (std::cmp::Reverse(*priority), deadline.unwrap_or(u64::MAX))
+            }
+            // Cancellations jump the queue so agents stop before starting new work
+            AgentMessage::Cancellation { .. } => (std::cmp::Reverse(MessagePriority::Critical), 0),
+            _ => (std::cmp::Reverse(MessagePriority::Normal), u64::MAX),
+        }
+    }
+
     /// Find agents with required capabilities
     async fn find_suitable_agents(
         required_capabilities: &[String],
+        priority: &MessagePriority,
     ) -> Result<Vec<AgentCapabilityProfile>, String> {
+        AvailabilityService::sweep_maintenance();
+        let now = time();
         with_state(|state| {
             if let Some(profiles) = &state.agent_capability_profiles {
                 let suitable: Vec<AgentCapabilityProfile> = profiles
@@ -280,12 +596,22 @@ impl AutonomousCoordinationService {
                     .filter(|profile| {
                         // Check if agent has required capabilities
                         required_capabilities.iter().all(|req_cap| {
-                            profile.capabilities.contains(req_cap)
+                            state.capability_taxonomy.satisfies(&profile.capabilities, req_cap)
                         }) &&
                         // Check if agent is available
-                        matches!(profile.availability_status, AvailabilityStatus::Available)
+                        matches!(profile.availability_status, AvailabilityStatus::Available) &&
+                        !state.quarantined_agents.contains_key(&profile.agent_id) &&
+                        state.agents.get(&profile.agent_id).is_none_or(|agent| agent.is_routable()) &&
+                        // Scheduled windows only bind non-critical work
+                        (*priority == MessagePriority::Critical
+                            || AvailabilityService::is_within_windows(&profile.availability_windows, now))
                     })
                     .cloned()
+                    .map(|mut profile| {
+                        // Reported load, once stale, no longer counts against the agent
+                        profile.performance_metrics.current_load = AgentLoadService::routing_load_in(state, &profile.agent_id, now);
+                        profile
+                    })
                     .collect();
                 
                 Ok(suitable)
@@ -345,12 +671,14 @@ impl AutonomousCoordinationService {
     }
 
     /// Route message to specific agent
-    async fn route_message_to_agent(
+    pub(crate) async fn route_message_to_agent(
         agent_id: String,
         message: AgentMessage,
     ) -> Result<(), String> {
         // Store message in agent's message queue
+        let now = time();
         with_state_mut(|state| {
+            MessageExpiryService::sweep_in(state, now);
             if state.agent_message_queues.is_none() {
                 state.agent_message_queues = Some(HashMap::new());
             }
@@ -365,7 +693,7 @@ impl AutonomousCoordinationService {
                 queue.remove(0);
             }
 
-            queue.push(message);
+            queue.push(MessageExpiryService::enqueue(message, now));
         });
 
         Ok(())
@@ -419,21 +747,20 @@ impl AutonomousCoordinationService {
                 state.agent_capability_profiles = Some(HashMap::new());
             }
 
-            let profile = AgentCapabilityProfile {
+            let existing = state.agent_capability_profiles.as_ref().and_then(|profiles| profiles.get(&agent_id));
+            let mut profile = AgentCapabilityProfile {
                 agent_id: agent_id.clone(),
                 capabilities,
                 performance_metrics,
                 availability_status,
-                coordination_preferences: CoordinationPreferences {
-                    preferred_coordination_types: vec![
-                        CoordinationType::TaskDelegation,
-                        CoordinationType::CollaborativePlanning,
-                    ],
-                    max_concurrent_collaborations: 5,
-                    communication_frequency: CommunicationFrequency::Normal,
-                    conflict_resolution_strategy: ConflictResolutionStrategy::Consensus,
-                },
+                // Declared preferences, schedules and announced maintenance survive profile refreshes
+                coordination_preferences: existing.map(|e| e.coordination_preferences.clone()).unwrap_or_default(),
+                declared_preferences: existing.and_then(|e| e.declared_preferences.clone()),
+                preference_adjustments: existing.map(|e| e.preference_adjustments.clone()).unwrap_or_default(),
+                availability_windows: existing.map(|e| e.availability_windows.clone()).unwrap_or_default(),
+                maintenance: existing.and_then(|e| e.maintenance.clone()),
             };
+            AvailabilityService::transition(&mut profile, time());
 
             state.agent_capability_profiles.as_mut().unwrap()
                 .insert(agent_id, profile);
@@ -444,11 +771,14 @@ impl AutonomousCoordinationService {
 
     /// Get messages for specific agent
     pub fn get_agent_messages(agent_id: String) -> Vec<AgentMessage> {
+        let now = time();
         with_state_mut(|state| {
+            MessageExpiryService::sweep_in(state, now);
             if let Some(queues) = &mut state.agent_message_queues {
                 if let Some(queue) = queues.get_mut(&agent_id) {
-                    let messages = queue.clone();
-                    queue.clear(); // Clear after reading
+                    let mut messages: Vec<AgentMessage> = queue.drain(..).map(|queued| queued.message).collect();
+                    // Stable sort keeps arrival order among equally urgent messages
+                    messages.sort_by_key(Self::urgency);
                     messages
                 } else {
                     Vec::new()
@@ -493,6 +823,10 @@ impl AutonomousCoordinationService {
                 available_agents: available_agents,
                 average_coordination_time_ms: 15000.0, // Calculated from session durations
                 successful_collaborations: total_sessions.saturating_sub(active_sessions),
+                rate_limited_messages: state.chatter_counters.rate_limited_messages,
+                oversized_messages: state.chatter_counters.oversized_messages,
+                agent_mutes: state.chatter_counters.mutes,
+                muted_agents: ChatterLimiter::muted_agents_in(state, time()),
             }
         })
     }
@@ -504,22 +838,22 @@ impl AutonomousCoordinationService {
         let mut cleaned_count = 0;
 
         with_state_mut(|state| {
-            if let Some(sessions) = &mut state.coordination_sessions {
-                let expired_sessions: Vec<String> = sessions
-                    .iter()
-                    .filter_map(|(id, session)| {
-                        if current_time - session.last_activity > timeout_duration {
-                            Some(id.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
+            let expired_sessions: Vec<String> = state.coordination_sessions.as_ref()
+                .map(|sessions| {
+                    sessions
+                        .iter()
+                        .filter(|(_, session)| current_time - session.last_activity > timeout_duration)
+                        .map(|(id, _)| id.clone())
+                        .collect()
+                })
+                .unwrap_or_default();
 
-                for session_id in expired_sessions {
-                    sessions.remove(&session_id);
-                    cleaned_count += 1;
+            for session_id in &expired_sessions {
+                if let Some(session) = state.coordination_sessions.as_mut().and_then(|sessions| sessions.remove(session_id)) {
+                    state.workload_index.remove_session(&session);
                 }
+                ChatterLimiter::forget_session(state, session_id);
+                cleaned_count += 1;
             }
         });
 
@@ -536,4 +870,12 @@ pub struct CoordinationStats {
     pub available_agents: u32,
     pub average_coordination_time_ms: f64,
     pub successful_collaborations: u32,
+    /// Messages rejected for exceeding the per-agent session rate
+    pub rate_limited_messages: u64,
+    /// Messages rejected for exceeding the size cap
+    pub oversized_messages: u64,
+    /// Times an agent has been muted as a persistent offender
+    pub agent_mutes: u64,
+    /// Agent/session pairs muted right now
+    pub muted_agents: u32,
 }
\ No newline at end of file
@@ -0,0 +1,356 @@
+use crate::services::{with_state, with_state_mut, CoordinatorState};
+use crate::services::autonomous_coord::{AgentCapabilityProfile, AvailabilityStatus, TaskStatus};
+use crate::services::jobs::JobStatus;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Scheduled availability windows and maintenance announced by agent owners
+pub struct AvailabilityService;
+
+/// One-off maintenance announced by the owner; the agent is drained for its duration
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub struct MaintenanceWindow {
+    /// Nanoseconds since the epoch
+    pub starts_at: u64,
+    pub ends_at: u64,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct MaintenanceStatus {
+    pub agent_id: String,
+    pub window: Option<MaintenanceWindow>,
+    /// The window is open and the agent receives no new work
+    pub draining: bool,
+    /// Delegated tasks and inference jobs the agent still has to finish
+    pub in_flight: u32,
+}
+
+/// Recurring UTC window, declared as `<days> <HH:MM>-<HH:MM>`
+/// (e.g. `mon-fri 09:00-17:00`, `* 22:00-06:00`, `sat,sun 00:00-24:00`)
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub struct AvailabilityWindow {
+    pub spec: String,
+    /// 0 = Sunday .. 6 = Saturday
+    pub days: Vec<u8>,
+    pub start_minute: u16,
+    pub end_minute: u16,
+}
+
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const NANOS_PER_MINUTE: u64 = 60 * 1_000_000_000;
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+impl AvailabilityWindow {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.split_whitespace();
+        let (Some(days), Some(times), None) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(format!("Invalid window '{}': expected '<days> <HH:MM>-<HH:MM>'", spec));
+        };
+
+        let days = Self::parse_days(days)?;
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid time range '{}'", times))?;
+        let start_minute = Self::parse_time(start)?;
+        let end_minute = Self::parse_time(end)?;
+        if start_minute == end_minute {
+            return Err(format!("Empty time range '{}'", times));
+        }
+
+        Ok(Self { spec: spec.to_string(), days, start_minute, end_minute })
+    }
+
+    fn parse_days(field: &str) -> Result<Vec<u8>, String> {
+        if field == "*" {
+            return Ok((0..7).collect());
+        }
+        let day_index = |name: &str| {
+            DAY_NAMES
+                .iter()
+                .position(|d| *d == name.to_lowercase())
+                .map(|i| i as u8)
+                .ok_or_else(|| format!("Unknown day '{}'", name))
+        };
+
+        let mut days = Vec::new();
+        for item in field.split(',') {
+            match item.split_once('-') {
+                Some((from, to)) => {
+                    let (from, to) = (day_index(from)?, day_index(to)?);
+                    // Ranges may wrap the week, e.g. fri-mon
+                    let mut day = from;
+                    loop {
+                        days.push(day);
+                        if day == to {
+                            break;
+                        }
+                        day = (day + 1) % 7;
+                    }
+                }
+                None => days.push(day_index(item)?),
+            }
+        }
+        days.sort_unstable();
+        days.dedup();
+        Ok(days)
+    }
+
+    fn parse_time(value: &str) -> Result<u16, String> {
+        let (hours, minutes) = value
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid time '{}'", value))?;
+        let hours: u16 = hours.parse().map_err(|_| format!("Invalid hour in '{}'", value))?;
+        let minutes: u16 = minutes.parse().map_err(|_| format!("Invalid minute in '{}'", value))?;
+        if minutes >= 60 || hours > 24 || (hours == 24 && minutes != 0) {
+            return Err(format!("Time out of range '{}'", value));
+        }
+        Ok(hours * 60 + minutes)
+    }
+
+    /// Whether the window is open at `now` (IC time, nanoseconds since the epoch)
+    pub fn contains(&self, now: u64) -> bool {
+        let total_minutes = now / NANOS_PER_MINUTE;
+        let minute = (total_minutes % MINUTES_PER_DAY) as u16;
+        // 1970-01-01 was a Thursday
+        let weekday = ((total_minutes / MINUTES_PER_DAY + 4) % 7) as u8;
+        let previous_day = (weekday + 6) % 7;
+
+        if self.start_minute < self.end_minute {
+            self.days.contains(&weekday) && minute >= self.start_minute && minute < self.end_minute
+        } else {
+            // Overnight window: the tail belongs to the day it started on
+            (self.days.contains(&weekday) && minute >= self.start_minute)
+                || (self.days.contains(&previous_day) && minute < self.end_minute)
+        }
+    }
+}
+
+impl MaintenanceWindow {
+    pub fn contains(&self, now: u64) -> bool {
+        now >= self.starts_at && now < self.ends_at
+    }
+}
+
+impl AvailabilityService {
+    const MAX_WINDOWS: usize = 14;
+    const MAX_MAINTENANCE_NANOS: u64 = 7 * 24 * 60 * NANOS_PER_MINUTE;
+
+    /// Agents without declared windows are always available
+    pub fn is_within_windows(windows: &[AvailabilityWindow], now: u64) -> bool {
+        windows.is_empty() || windows.iter().any(|w| w.contains(now))
+    }
+
+    pub fn is_agent_available(agent_id: &str, now: u64) -> bool {
+        with_state(|state| {
+            state.agent_capability_profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(agent_id))
+                .map(|profile| Self::is_within_windows(&profile.availability_windows, now))
+                .unwrap_or(true)
+        })
+    }
+
+    /// Agents under maintenance take no new work, whatever the priority
+    pub fn is_in_maintenance(agent_id: &str, now: u64) -> bool {
+        with_state(|state| {
+            state.agent_capability_profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(agent_id))
+                .and_then(|profile| profile.maintenance.as_ref())
+                .map(|window| window.contains(now))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Announce maintenance; replaces any window already scheduled
+    pub fn set_maintenance(owner: &str, agent_id: &str, window: MaintenanceWindow) -> Result<MaintenanceStatus, String> {
+        let now = time();
+        if window.ends_at <= window.starts_at || window.ends_at <= now {
+            return Err("Maintenance must end after it starts and in the future".to_string());
+        }
+        if window.ends_at - window.starts_at.max(now) > Self::MAX_MAINTENANCE_NANOS {
+            return Err("Maintenance windows are limited to 7 days".to_string());
+        }
+        with_state_mut(|state| {
+            Self::owned_profile_mut(state, owner, agent_id)?.maintenance = Some(window);
+            Self::apply_maintenance(state, now);
+            Ok(Self::status_in(state, agent_id, now))
+        })
+    }
+
+    /// End or cancel maintenance early
+    pub fn clear_maintenance(owner: &str, agent_id: &str) -> Result<MaintenanceStatus, String> {
+        let now = time();
+        with_state_mut(|state| {
+            let profile = Self::owned_profile_mut(state, owner, agent_id)?;
+            if let Some(window) = profile.maintenance.as_mut() {
+                window.ends_at = window.ends_at.min(now);
+            }
+            Self::apply_maintenance(state, now);
+            Ok(Self::status_in(state, agent_id, now))
+        })
+    }
+
+    fn owned_profile_mut<'a>(state: &'a mut CoordinatorState, owner: &str, agent_id: &str) -> Result<&'a mut AgentCapabilityProfile, String> {
+        let agent = state.agents.get(agent_id).ok_or_else(|| "Agent not found".to_string())?;
+        if agent.agent_principal != owner {
+            return Err("Only the agent owner can schedule maintenance".to_string());
+        }
+        state.agent_capability_profiles
+            .as_mut()
+            .and_then(|profiles| profiles.get_mut(agent_id))
+            .ok_or_else(|| "Agent has no capability profile".to_string())
+    }
+
+    /// Move profiles into and out of Maintenance as their windows open and close
+    pub fn sweep_maintenance() {
+        let now = time();
+        with_state_mut(|state| Self::apply_maintenance(state, now));
+    }
+
+    fn apply_maintenance(state: &mut CoordinatorState, now: u64) {
+        let Some(profiles) = state.agent_capability_profiles.as_mut() else {
+            return;
+        };
+        for profile in profiles.values_mut() {
+            Self::transition(profile, now);
+        }
+    }
+
+    pub(crate) fn transition(profile: &mut AgentCapabilityProfile, now: u64) {
+        let Some(window) = profile.maintenance.as_ref() else {
+            return;
+        };
+        if window.contains(now) {
+            profile.availability_status = AvailabilityStatus::Maintenance;
+        } else if now >= window.ends_at {
+            profile.maintenance = None;
+            if matches!(profile.availability_status, AvailabilityStatus::Maintenance) {
+                profile.availability_status = AvailabilityStatus::Available;
+            }
+        }
+    }
+
+    pub fn get_maintenance(agent_id: &str) -> MaintenanceStatus {
+        with_state(|state| Self::status_in(state, agent_id, time()))
+    }
+
+    fn status_in(state: &CoordinatorState, agent_id: &str, now: u64) -> MaintenanceStatus {
+        let window = state.agent_capability_profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(agent_id))
+            .and_then(|profile| profile.maintenance.clone())
+            .filter(|window| now < window.ends_at);
+        let tasks = state.task_ledger.values()
+            .filter(|task| task.assigned_agent == agent_id)
+            .filter(|task| matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress))
+            .count();
+        let jobs = state.inference_jobs.values()
+            .filter(|job| job.agent_id == agent_id)
+            .filter(|job| matches!(job.status, JobStatus::Dispatched | JobStatus::Running))
+            .count();
+        MaintenanceStatus {
+            agent_id: agent_id.to_string(),
+            draining: window.as_ref().map(|w| w.contains(now)).unwrap_or(false),
+            window,
+            in_flight: (tasks + jobs) as u32,
+        }
+    }
+
+    /// Replace an agent's windows; an empty list removes the schedule
+    pub fn set_windows(owner: &str, agent_id: &str, specs: Vec<String>) -> Result<Vec<AvailabilityWindow>, String> {
+        if specs.len() > Self::MAX_WINDOWS {
+            return Err(format!("At most {} availability windows are allowed", Self::MAX_WINDOWS));
+        }
+        let windows = specs
+            .iter()
+            .map(|spec| AvailabilityWindow::parse(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        with_state_mut(|state| {
+            let agent = state.agents.get(agent_id).ok_or_else(|| "Agent not found".to_string())?;
+            if agent.agent_principal != owner {
+                return Err("Only the agent owner can set availability windows".to_string());
+            }
+            let profile = state.agent_capability_profiles
+                .as_mut()
+                .and_then(|profiles| profiles.get_mut(agent_id))
+                .ok_or_else(|| "Agent has no capability profile".to_string())?;
+            profile.availability_windows = windows.clone();
+            Ok(windows)
+        })
+    }
+
+    pub fn get_windows(agent_id: &str) -> Vec<AvailabilityWindow> {
+        with_state(|state| {
+            state.agent_capability_profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(agent_id))
+                .map(|profile| profile.availability_windows.clone())
+                .unwrap_or_default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01 was a Monday
+    const MONDAY_MIDNIGHT: u64 = 1_704_067_200 * 1_000_000_000;
+
+    fn at(day_offset: u64, hour: u64, minute: u64) -> u64 {
+        MONDAY_MIDNIGHT + ((day_offset * 24 + hour) * 60 + minute) * NANOS_PER_MINUTE
+    }
+
+    #[test]
+    fn test_parse_window() {
+        let window = AvailabilityWindow::parse("mon-fri 09:00-17:30").unwrap();
+        assert_eq!(window.days, vec![1, 2, 3, 4, 5]);
+        assert_eq!(window.start_minute, 9 * 60);
+        assert_eq!(window.end_minute, 17 * 60 + 30);
+
+        assert_eq!(AvailabilityWindow::parse("fri-mon 00:00-24:00").unwrap().days, vec![0, 1, 5, 6]);
+        assert!(AvailabilityWindow::parse("mon 09:00").is_err());
+        assert!(AvailabilityWindow::parse("funday 09:00-10:00").is_err());
+        assert!(AvailabilityWindow::parse("* 25:00-26:00").is_err());
+    }
+
+    #[test]
+    fn test_daytime_window() {
+        let window = AvailabilityWindow::parse("mon-fri 09:00-17:00").unwrap();
+        assert!(window.contains(at(0, 9, 0)));
+        assert!(!window.contains(at(0, 17, 0)));
+        assert!(!window.contains(at(5, 12, 0))); // Saturday
+    }
+
+    #[test]
+    fn test_maintenance_drains_then_restores() {
+        let mut profile = AgentCapabilityProfile::initial("agent_1", vec![]);
+        profile.maintenance = Some(MaintenanceWindow { starts_at: at(0, 9, 0), ends_at: at(0, 10, 0), reason: None });
+
+        AvailabilityService::transition(&mut profile, at(0, 8, 0));
+        assert!(matches!(profile.availability_status, AvailabilityStatus::Available));
+        AvailabilityService::transition(&mut profile, at(0, 9, 30));
+        assert!(matches!(profile.availability_status, AvailabilityStatus::Maintenance));
+        AvailabilityService::transition(&mut profile, at(0, 10, 0));
+        assert!(matches!(profile.availability_status, AvailabilityStatus::Available));
+        assert!(profile.maintenance.is_none());
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let window = AvailabilityWindow::parse("fri 22:00-06:00").unwrap();
+        assert!(window.contains(at(4, 23, 0))); // Friday night
+        assert!(window.contains(at(5, 5, 59))); // Saturday early morning
+        assert!(!window.contains(at(0, 23, 0))); // Monday night
+        assert!(!window.contains(at(4, 5, 0))); // Friday early morning belongs to Thursday
+    }
+
+    #[test]
+    fn test_no_windows_means_always_available() {
+        assert!(AvailabilityService::is_within_windows(&[], at(2, 3, 0)));
+    }
+}
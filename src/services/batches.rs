@@ -0,0 +1,250 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, CoordinatorState, IdGenerator, InstructionAnalyzerService, ProgramService};
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use candid::CandidType;
+
+/// Batch service processing several independent instruction submissions in one call
+pub struct BatchService;
+
+/// Progress of a single submission within a batch
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum BatchItemStatus {
+    Pending,
+    Completed,
+    Failed,
+    /// Not attempted because the batch's quota reservation could not cover it
+    NotReserved,
+}
+
+/// Per-item outcome, indexed by the submission's position in the batch
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct BatchItemResult {
+    pub index: u32,
+    pub status: BatchItemStatus,
+    pub request_id: Option<String>,
+    pub reserved_agents: u32,
+    pub reserved_units: u32,
+    pub error: Option<String>,
+}
+
+/// Combined progress handle for a batch submission
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct InstructionBatch {
+    pub batch_id: String,
+    pub owner_principal: String,
+    pub status: AgentCreationStatus,
+    pub items: Vec<BatchItemResult>,
+    pub reserved_agents: u32,
+    pub reserved_units: u32,
+    pub created_at: u64,
+    pub completed_at: Option<u64>,
+}
+
+impl BatchService {
+    const MAX_BATCH_SIZE: usize = 20;
+    /// Pending items of a batch older than this no longer hold quota, so a batch cut
+    /// short by a trap cannot keep its reservation forever
+    const HOLD_TTL_NS: u64 = 60 * 60 * 1_000_000_000;
+
+    /// Analyze every submission up front and reserve quota for as many as fit, in order.
+    /// The reservation lives in the stored batch: its pending items hold their share
+    /// until they are recorded, and batches opened meanwhile only see what is left.
+    pub fn open_batch(owner_principal: &str, submissions: &[InstructionSubmission]) -> Result<InstructionBatch, String> {
+        if submissions.is_empty() {
+            return Err("Batch must contain at least one submission".to_string());
+        }
+        if submissions.len() > Self::MAX_BATCH_SIZE {
+            return Err(format!("Batch exceeds maximum size of {} submissions", Self::MAX_BATCH_SIZE));
+        }
+
+        let now = time();
+        let held = with_state(|state| Self::held_in(state, owner_principal, now));
+        let mut remaining: Option<(u32, u32)> = None;
+        let mut items = Vec::with_capacity(submissions.len());
+        for (index, submission) in submissions.iter().enumerate() {
+            let analysis = InstructionAnalyzerService::analyze_instructions(&submission.instructions, owner_principal)?;
+
+            // Versions are checked once here: earlier items spawning would otherwise invalidate later ones
+            if let Some(expected) = submission.expected_state_version.filter(|v| *v != analysis.state_version) {
+                items.push(BatchItemResult {
+                    index: index as u32,
+                    status: BatchItemStatus::Failed,
+                    request_id: None,
+                    reserved_agents: 0,
+                    reserved_units: 0,
+                    error: Some(format!(
+                        "ConflictDetected: quota or agents changed since analysis (version {} is now {}); re-analyze before spawning",
+                        expected, analysis.state_version
+                    )),
+                });
+                continue;
+            }
+            let remaining = remaining.get_or_insert((
+                analysis.quota_check.remaining_agents.saturating_sub(held.0),
+                analysis.quota_check.remaining_weighted_units.saturating_sub(held.1),
+            ));
+
+            let full_cost = (analysis.suggested_agents.len() as u32, analysis.weighted_cost);
+            let downscaled_cost = analysis.downscaled_plan
+                .filter(|_| submission.accept_downscaled)
+                .map(|plan| (plan.suggested_agents.len() as u32, plan.weighted_cost));
+
+            let reserved = Self::reserve(remaining, full_cost)
+                .or_else(|| downscaled_cost.and_then(|cost| Self::reserve(remaining, cost)));
+
+            items.push(match reserved {
+                Some((agents, units)) => BatchItemResult {
+                    index: index as u32,
+                    status: BatchItemStatus::Pending,
+                    request_id: None,
+                    reserved_agents: agents,
+                    reserved_units: units,
+                    error: None,
+                },
+                None => BatchItemResult {
+                    index: index as u32,
+                    status: BatchItemStatus::NotReserved,
+                    request_id: None,
+                    reserved_agents: 0,
+                    reserved_units: 0,
+                    error: Some(format!(
+                        "Needs {} agents ({} units); batch reservation has {} agents and {} units left",
+                        full_cost.0, full_cost.1, remaining.0, remaining.1
+                    )),
+                },
+            });
+        }
+
+        let mut batch = InstructionBatch {
+            batch_id: IdGenerator::next("batch", owner_principal),
+            owner_principal: owner_principal.to_string(),
+            status: AgentCreationStatus::InProgress,
+            reserved_agents: items.iter().map(|i| i.reserved_agents).sum(),
+            reserved_units: items.iter().map(|i| i.reserved_units).sum(),
+            items,
+            created_at: now,
+            completed_at: None,
+        };
+        Self::roll_up(&mut batch);
+
+        with_state_mut(|state| {
+            state.instruction_batches.insert(batch.batch_id.clone(), batch.clone());
+        });
+
+        Ok(batch)
+    }
+
+    /// Agents and units still held by the pending items of the owner's recent batches
+    fn held_in(state: &CoordinatorState, owner_principal: &str, now: u64) -> (u32, u32) {
+        state.instruction_batches
+            .values()
+            .filter(|b| b.owner_principal == owner_principal && now.saturating_sub(b.created_at) < Self::HOLD_TTL_NS)
+            .flat_map(|b| b.items.iter())
+            .filter(|i| i.status == BatchItemStatus::Pending)
+            .fold((0, 0), |(agents, units), i| (agents + i.reserved_agents, units + i.reserved_units))
+    }
+
+    /// Take `cost` out of the shared reservation if it fits
+    fn reserve(remaining: &mut (u32, u32), cost: (u32, u32)) -> Option<(u32, u32)> {
+        if cost.0 > remaining.0 || cost.1 > remaining.1 {
+            return None;
+        }
+        remaining.0 -= cost.0;
+        remaining.1 -= cost.1;
+        Some(cost)
+    }
+
+    /// Whether the item at `index` holds a reservation and still needs processing
+    pub fn is_pending(batch: &InstructionBatch, index: usize) -> bool {
+        batch.items.get(index).map(|i| i.status == BatchItemStatus::Pending).unwrap_or(false)
+    }
+
+    /// Record the outcome of one item and roll up the batch status
+    pub fn record_item(batch_id: &str, index: usize, outcome: Result<String, String>) {
+        with_state_mut(|state| {
+            let Some(batch) = state.instruction_batches.get_mut(batch_id) else {
+                return;
+            };
+            if let Some(item) = batch.items.get_mut(index) {
+                match outcome {
+                    Ok(request_id) => {
+                        item.status = BatchItemStatus::Completed;
+                        item.request_id = Some(request_id);
+                    }
+                    Err(e) => {
+                        item.status = BatchItemStatus::Failed;
+                        item.error = Some(e);
+                    }
+                }
+            }
+            Self::roll_up(batch);
+        });
+    }
+
+    fn roll_up(batch: &mut InstructionBatch) {
+        let count = |status: BatchItemStatus| batch.items.iter().filter(|i| i.status == status).count() as u32;
+        let pending = count(BatchItemStatus::Pending);
+        let failed = count(BatchItemStatus::Failed) + count(BatchItemStatus::NotReserved);
+        batch.status = ProgramService::roll_up_status(pending, count(BatchItemStatus::Completed), failed);
+        if pending == 0 {
+            batch.completed_at = Some(time());
+        }
+    }
+
+    pub fn get_batch(owner_principal: &str, batch_id: &str) -> Result<InstructionBatch, String> {
+        with_state(|state| {
+            state.instruction_batches
+                .get(batch_id)
+                .filter(|b| b.owner_principal == owner_principal)
+                .cloned()
+                .ok_or_else(|| "Batch not found or access denied".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservation_is_shared_across_items() {
+        let mut remaining = (5, 10);
+        assert_eq!(BatchService::reserve(&mut remaining, (3, 6)), Some((3, 6)));
+        assert_eq!(BatchService::reserve(&mut remaining, (3, 2)), None);
+        assert_eq!(BatchService::reserve(&mut remaining, (2, 4)), Some((2, 4)));
+        assert_eq!(remaining, (0, 0));
+    }
+
+    #[test]
+    fn test_pending_items_hold_quota_across_batches() {
+        let item = |status: BatchItemStatus, agents: u32| BatchItemResult {
+            index: 0,
+            status,
+            request_id: None,
+            reserved_agents: agents,
+            reserved_units: agents * 2,
+            error: None,
+        };
+        let batch = |id: &str, owner: &str, created_at: u64, items: Vec<BatchItemResult>| InstructionBatch {
+            batch_id: id.to_string(),
+            owner_principal: owner.to_string(),
+            status: AgentCreationStatus::InProgress,
+            items,
+            reserved_agents: 0,
+            reserved_units: 0,
+            created_at,
+            completed_at: None,
+        };
+        let mut state = CoordinatorState::default();
+        let now = BatchService::HOLD_TTL_NS * 2;
+        state.instruction_batches.insert("a".to_string(), batch("a", "alice", now, vec![
+            item(BatchItemStatus::Pending, 2),
+            item(BatchItemStatus::Completed, 3),
+        ]));
+        state.instruction_batches.insert("b".to_string(), batch("b", "bob", now, vec![item(BatchItemStatus::Pending, 4)]));
+        state.instruction_batches.insert("stale".to_string(), batch("stale", "alice", 0, vec![item(BatchItemStatus::Pending, 5)]));
+
+        assert_eq!(BatchService::held_in(&state, "alice", now), (2, 4));
+    }
+}
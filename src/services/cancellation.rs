@@ -0,0 +1,262 @@
+use crate::services::{with_state, with_state_mut, AutonomousCoordinationService, CoordinatorState, IdGenerator};
+use crate::services::autonomous_coord::{AgentMessage, SessionStatus, TaskStatus};
+use crate::infra::Metrics;
+use ic_cdk::api::{call, time};
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Propagates cancellations to the agents doing the work and tracks their acknowledgment
+pub struct CancellationService;
+
+/// What was cancelled
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum CancellationTarget {
+    Task,
+    Session,
+}
+
+/// Delivery and acknowledgment state of a cancellation for one agent
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CancellationAck {
+    pub agent_id: String,
+    /// The agent canister accepted the best-effort `cancel` call
+    pub delivered: bool,
+    pub delivery_error: Option<String>,
+    pub acknowledged_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CancellationRecord {
+    pub cancellation_id: String,
+    pub target: CancellationTarget,
+    pub target_id: String,
+    /// Delegated sub-tasks cancelled along with a task
+    pub cascaded_task_ids: Vec<String>,
+    pub requested_by: String,
+    pub reason: String,
+    pub agents: Vec<CancellationAck>,
+    pub created_at: u64,
+    pub fully_acknowledged: bool,
+}
+
+impl CancellationService {
+    /// Cancel a task and every unfinished sub-task delegated from it
+    pub async fn cancel_task(caller: &str, is_admin: bool, task_id: &str, reason: String) -> Result<CancellationRecord, String> {
+        let (task_ids, agents) = with_state_mut(|state| {
+            let task = state.task_ledger.get(task_id).ok_or_else(|| "Task not found".to_string())?;
+            let root_owner_agent = state.task_ledger
+                .get(&task.root_task_id)
+                .map(|root| root.assigned_agent.clone())
+                .unwrap_or_else(|| task.assigned_agent.clone());
+            if !is_admin && !Self::owns_agent(state, caller, &root_owner_agent) && !Self::owns_agent(state, caller, &task.assigned_agent) {
+                return Err("Only the task's owner can cancel it".to_string());
+            }
+
+            let task_ids = Self::subtree(state, task_id);
+            let now = time();
+            let mut agents = Vec::new();
+            for id in &task_ids {
+                if let Some(entry) = state.task_ledger.get_mut(id) {
+                    if matches!(entry.status, TaskStatus::Pending | TaskStatus::InProgress | TaskStatus::AwaitingReassignment) {
+                        entry.status = TaskStatus::Cancelled;
+                        entry.updated_at = now;
+                        state.workload_index.update_task(entry);
+                        if !agents.contains(&entry.assigned_agent) {
+                            agents.push(entry.assigned_agent.clone());
+                        }
+                    }
+                }
+            }
+            if agents.is_empty() {
+                return Err("Task has already finished".to_string());
+            }
+            Ok((task_ids, agents))
+        })?;
+
+        let cascaded_task_ids = task_ids.into_iter().filter(|id| id != task_id).collect();
+        Self::propagate(caller, CancellationTarget::Task, task_id, cascaded_task_ids, reason, agents).await
+    }
+
+    /// Cancel a coordination session and notify all participants
+    pub async fn cancel_session(caller: &str, is_admin: bool, session_id: &str, reason: String) -> Result<CancellationRecord, String> {
+        let agents = with_state_mut(|state| {
+            let coordinator = state.coordination_sessions.as_ref()
+                .and_then(|sessions| sessions.get(session_id))
+                .map(|session| session.coordinator_agent.clone())
+                .ok_or_else(|| "Coordination session not found".to_string())?;
+            if !is_admin && !Self::owns_agent(state, caller, &coordinator) {
+                return Err("Only the session coordinator's owner can cancel it".to_string());
+            }
+
+            let session = state.coordination_sessions.as_mut()
+                .and_then(|sessions| sessions.get_mut(session_id))
+                .ok_or_else(|| "Coordination session not found".to_string())?;
+            if !matches!(session.status, SessionStatus::Active | SessionStatus::Coordinating) {
+                return Err("Coordination session is no longer active".to_string());
+            }
+            session.status = SessionStatus::Cancelled;
+            session.last_activity = time();
+
+            let mut agents = session.participants.clone();
+            if !agents.contains(&session.coordinator_agent) {
+                agents.push(session.coordinator_agent.clone());
+            }
+            Ok(agents)
+        })?;
+
+        Self::propagate(caller, CancellationTarget::Session, session_id, Vec::new(), reason, agents).await
+    }
+
+    /// Queue a Cancellation message for each agent and make a best-effort `cancel` call to its canister
+    async fn propagate(
+        caller: &str,
+        target: CancellationTarget,
+        target_id: &str,
+        cascaded_task_ids: Vec<String>,
+        reason: String,
+        agent_ids: Vec<String>,
+    ) -> Result<CancellationRecord, String> {
+        let now = time();
+        let cancellation_id = IdGenerator::next("cancel", caller);
+
+        // Stored before any call so agents acknowledging mid-propagation find the record
+        let record = CancellationRecord {
+            cancellation_id: cancellation_id.clone(),
+            target,
+            target_id: target_id.to_string(),
+            cascaded_task_ids,
+            requested_by: caller.to_string(),
+            reason: reason.clone(),
+            agents: agent_ids
+                .iter()
+                .map(|agent_id| CancellationAck {
+                    agent_id: agent_id.clone(),
+                    delivered: false,
+                    delivery_error: None,
+                    acknowledged_at: None,
+                })
+                .collect(),
+            created_at: now,
+            fully_acknowledged: false,
+        };
+        with_state_mut(|state| {
+            state.cancellations.insert(cancellation_id.clone(), record);
+        });
+        Metrics::increment_counter("cancellations_total");
+
+        for agent_id in agent_ids {
+            let message = AgentMessage::Cancellation {
+                cancellation_id: cancellation_id.clone(),
+                target_id: target_id.to_string(),
+                reason: reason.clone(),
+            };
+            AutonomousCoordinationService::route_message_to_agent(agent_id.clone(), message).await?;
+
+            let delivery = Self::call_agent_cancel(&agent_id, &cancellation_id, target_id, &reason).await;
+            with_state_mut(|state| {
+                let ack = state.cancellations
+                    .get_mut(&cancellation_id)
+                    .and_then(|record| record.agents.iter_mut().find(|a| a.agent_id == agent_id));
+                if let Some(ack) = ack {
+                    ack.delivered = delivery.is_ok();
+                    ack.delivery_error = delivery.err();
+                }
+            });
+        }
+
+        Self::get_cancellation(&cancellation_id).ok_or_else(|| "Cancellation not found".to_string())
+    }
+
+    async fn call_agent_cancel(agent_id: &str, cancellation_id: &str, target_id: &str, reason: &str) -> Result<(), String> {
+        let canister_id = with_state(|state| state.agents.get(agent_id).map(|a| a.canister_id.clone()))
+            .ok_or_else(|| "Agent no longer registered".to_string())?;
+        let principal = Principal::from_text(&canister_id)
+            .map_err(|e| format!("Invalid canister id {}: {}", canister_id, e))?;
+
+        match call::call::<_, ()>(principal, "cancel", (cancellation_id.to_string(), target_id.to_string(), reason.to_string())).await {
+            Ok(()) => {
+                Metrics::record_dependency_success("agent.cancel");
+                Ok(())
+            }
+            Err((code, msg)) => {
+                Metrics::record_dependency_failure("agent.cancel", &format!("{}: {:?}: {}", agent_id, code, msg));
+                Err(format!("cancel call failed ({:?}): {}", code, msg))
+            }
+        }
+    }
+
+    /// Record that an agent has stopped work; callable by the agent's canister or owner
+    pub fn acknowledge(caller: &str, cancellation_id: &str, agent_id: &str) -> Result<CancellationRecord, String> {
+        with_state_mut(|state| {
+            if !Self::owns_agent(state, caller, agent_id) {
+                return Err("Caller does not control this agent".to_string());
+            }
+            let record = state.cancellations
+                .get_mut(cancellation_id)
+                .ok_or_else(|| "Cancellation not found".to_string())?;
+            let ack = record.agents
+                .iter_mut()
+                .find(|a| a.agent_id == agent_id)
+                .ok_or_else(|| "Agent is not part of this cancellation".to_string())?;
+            ack.acknowledged_at.get_or_insert(time());
+            record.fully_acknowledged = record.agents.iter().all(|a| a.acknowledged_at.is_some());
+            Ok(record.clone())
+        })
+    }
+
+    pub fn get_cancellation(cancellation_id: &str) -> Option<CancellationRecord> {
+        with_state(|state| state.cancellations.get(cancellation_id).cloned())
+    }
+
+    fn owns_agent(state: &CoordinatorState, caller: &str, agent_id: &str) -> bool {
+        state.agents
+            .get(agent_id)
+            .map(|a| a.agent_principal == caller || a.canister_id == caller)
+            .unwrap_or(false)
+    }
+
+    /// `task_id` followed by every task delegated from it, transitively
+    fn subtree(state: &CoordinatorState, task_id: &str) -> Vec<String> {
+        let mut ids = vec![task_id.to_string()];
+        let mut seen: HashSet<String> = ids.iter().cloned().collect();
+        let mut i = 0;
+        while i < ids.len() {
+            let parent = ids[i].clone();
+            for entry in state.task_ledger.values() {
+                if entry.parent_task_id.as_deref() == Some(parent.as_str()) && seen.insert(entry.task_id.clone()) {
+                    ids.push(entry.task_id.clone());
+                }
+            }
+            i += 1;
+        }
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::autonomous_coord::TaskLedgerEntry;
+
+    fn entry(task_id: &str, parent: Option<&str>) -> TaskLedgerEntry {
+        TaskLedgerEntry {
+            parent_task_id: parent.map(|p| p.to_string()),
+            root_task_id: "root".to_string(),
+            ..TaskLedgerEntry::test(task_id, &format!("agent_{}", task_id), TaskStatus::Pending)
+        }
+    }
+
+    #[test]
+    fn test_subtree_follows_delegation_chain() {
+        let mut state = CoordinatorState::default();
+        for (id, parent) in [("root", None), ("a", Some("root")), ("b", Some("a")), ("other", None)] {
+            state.task_ledger.insert(id.to_string(), entry(id, parent));
+        }
+
+        let mut ids = CancellationService::subtree(&state, "root");
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b", "root"]);
+        assert_eq!(CancellationService::subtree(&state, "b"), vec!["b"]);
+    }
+}
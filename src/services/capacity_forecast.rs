@@ -0,0 +1,159 @@
+use crate::services::{with_state, with_state_mut, RegistryService, RoutingService};
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Demand forecasting per capability. Routing records how often each capability is
+/// requested per day; the forecast fits a linear trend over recent days, projects it
+/// over the horizon and compares it with the healthy agents able to serve it, so
+/// operators can recruit or spawn agents before requests start failing.
+pub struct CapacityForecastService;
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Projected demand against current supply for one capability
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CapabilityForecast {
+    pub capability: String,
+    /// Mean requests per day over the observed window
+    pub observed_daily_demand: f64,
+    /// Fitted change in daily requests per day
+    pub trend_per_day: f64,
+    pub projected_daily_demand: f64,
+    pub healthy_agents: u32,
+    /// Agents needed to serve the projected demand at the sizing target
+    pub agents_needed: u32,
+    /// Agents missing; non-zero marks a likely shortfall
+    pub shortfall: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CapacityForecast {
+    pub horizon_days: u32,
+    pub generated_at: u64,
+    pub daily_requests_per_agent: u64,
+    /// Largest shortfall first
+    pub capabilities: Vec<CapabilityForecast>,
+}
+
+impl CapacityForecastService {
+    const WINDOW_DAYS: u64 = 28;
+    const MAX_HORIZON_DAYS: u32 = 90;
+    /// Sizing target: requests one healthy agent is expected to absorb per day
+    const DAILY_REQUESTS_PER_AGENT: u64 = 1_000;
+    /// Requested capabilities are caller-supplied; stop tracking new ones past this
+    const MAX_TRACKED_CAPABILITIES: usize = 1_000;
+
+    /// Count one routing request against each capability it asks for; called once when the
+    /// request is admitted, not for the internal selections made on its behalf
+    pub fn record_demand(capabilities: &[String]) {
+        if capabilities.is_empty() {
+            return;
+        }
+        let day = time() / NANOS_PER_DAY;
+        with_state_mut(|state| {
+            for capability in capabilities {
+                if !state.capability_demand.contains_key(capability)
+                    && state.capability_demand.len() >= Self::MAX_TRACKED_CAPABILITIES
+                {
+                    continue;
+                }
+                let buckets = state.capability_demand.entry(capability.clone()).or_insert_with(BTreeMap::new);
+                *buckets.entry(day).or_default() += 1;
+                let cutoff = day.saturating_sub(Self::WINDOW_DAYS);
+                buckets.retain(|d, _| *d > cutoff);
+            }
+            state.capability_demand.retain(|_, buckets| !buckets.is_empty());
+        });
+    }
+
+    pub fn forecast(horizon_days: u32) -> Result<CapacityForecast, String> {
+        if horizon_days == 0 || horizon_days > Self::MAX_HORIZON_DAYS {
+            return Err(format!("horizon_days must be between 1 and {}", Self::MAX_HORIZON_DAYS));
+        }
+        let now = time();
+        let today = now / NANOS_PER_DAY;
+        let from_day = today.saturating_sub(Self::WINDOW_DAYS - 1);
+        let demand: Vec<(String, Vec<u64>)> = with_state(|state| {
+            state.capability_demand
+                .iter()
+                .filter_map(|(capability, buckets)| {
+                    // Start at the first observed day so new capabilities are not diluted
+                    let first = buckets.range(from_day..=today).next().map(|(day, _)| *day)?;
+                    let daily = (first..=today).map(|day| buckets.get(&day).copied().unwrap_or(0)).collect();
+                    Some((capability.clone(), daily))
+                })
+                .collect()
+        });
+        let supply: Vec<_> = RegistryService::get_healthy_agents(0.1)
+            .into_iter()
+            .filter(RoutingService::speaks_infer_protocol)
+            .collect();
+
+        let mut capabilities: Vec<CapabilityForecast> = demand
+            .into_iter()
+            .map(|(capability, daily)| {
+                let (observed_daily_demand, trend_per_day, projected_daily_demand) = Self::project(&daily, horizon_days);
+                let healthy_agents = supply
+                    .iter()
+                    .filter(|agent| RegistryService::has_capability(&agent.capabilities, &capability))
+                    .count() as u32;
+                let agents_needed = (projected_daily_demand / Self::DAILY_REQUESTS_PER_AGENT as f64).ceil() as u32;
+                CapabilityForecast {
+                    capability,
+                    observed_daily_demand,
+                    trend_per_day,
+                    projected_daily_demand,
+                    healthy_agents,
+                    agents_needed,
+                    shortfall: agents_needed.saturating_sub(healthy_agents),
+                }
+            })
+            .collect();
+        capabilities.sort_by(|a, b| b.shortfall.cmp(&a.shortfall).then_with(|| a.capability.cmp(&b.capability)));
+
+        Ok(CapacityForecast {
+            horizon_days,
+            generated_at: now,
+            daily_requests_per_agent: Self::DAILY_REQUESTS_PER_AGENT,
+            capabilities,
+        })
+    }
+
+    /// Least-squares line through the daily counts, oldest first, extended `horizon_days`
+    /// past the last day; returns (mean, slope, projection)
+    fn project(daily: &[u64], horizon_days: u32) -> (f64, f64, f64) {
+        if daily.is_empty() {
+            return (0.0, 0.0, 0.0);
+        }
+        let n = daily.len() as f64;
+        let mean_x = (n - 1.0) / 2.0;
+        let mean_y = daily.iter().sum::<u64>() as f64 / n;
+        let (covariance, variance) = daily.iter().enumerate().fold((0.0, 0.0), |(cov, var), (x, y)| {
+            let dx = x as f64 - mean_x;
+            (cov + dx * (*y as f64 - mean_y), var + dx * dx)
+        });
+        let slope = if variance > 0.0 { covariance / variance } else { 0.0 };
+        let projected = mean_y + slope * (n - 1.0 - mean_x + horizon_days as f64);
+        (mean_y, slope, projected.max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_extends_linear_trend() {
+        let (mean, slope, projected) = CapacityForecastService::project(&[100, 200, 300, 400], 2);
+        assert_eq!(mean, 250.0);
+        assert_eq!(slope, 100.0);
+        assert_eq!(projected, 600.0);
+
+        // Flat history and a single day project unchanged; decline floors at zero
+        assert_eq!(CapacityForecastService::project(&[50, 50, 50], 30).2, 50.0);
+        assert_eq!(CapacityForecastService::project(&[70], 7), (70.0, 0.0, 70.0));
+        assert_eq!(CapacityForecastService::project(&[300, 200, 100], 10).2, 0.0);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::domain::AgentRegistration;
+use crate::services::{with_state, with_state_mut, CoordinatorState, RegistryService};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Certified registry reads. The canister's certified data is the root of an IC hash
+/// tree with one leaf per agent at `agents/<agent_id>`, holding the SHA-256 of the
+/// agent's Candid encoding. `get_certified_agent` returns the record with the system
+/// certificate and a pruned witness, so a client can check the record against the
+/// subnet signature instead of trusting the boundary node that relayed it.
+///
+/// Certified data can only be set from update context, so the tree is brought up to
+/// date from the registry change log on every maintenance tick; for a few seconds after a
+/// change the new record cannot be certified and the query asks the client to retry.
+pub struct CertificationService;
+
+/// Leaf hashes as of the registry version last certified; rebuilt after an upgrade,
+/// which clears the certified data anyway
+#[derive(Debug, Clone, Default)]
+pub struct CertifiedRegistry {
+    pub version: Option<u64>,
+    pub leaves: BTreeMap<String, [u8; 32]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CertifiedAgent {
+    pub agent: AgentRegistration,
+    /// System certificate; its certified data is the root hash of `witness`
+    pub certificate: Vec<u8>,
+    /// CBOR hash tree in the IC encoding, pruned down to `agents/<agent_id>`
+    pub witness: Vec<u8>,
+}
+
+/// IC hash tree, as in the interface specification
+#[derive(Debug, Clone, PartialEq)]
+enum HashTree {
+    Empty,
+    Fork(Box<HashTree>, Box<HashTree>),
+    Labeled(Vec<u8>, Box<HashTree>),
+    Leaf(Vec<u8>),
+    Pruned([u8; 32]),
+}
+
+impl HashTree {
+    fn digest(&self) -> [u8; 32] {
+        match self {
+            HashTree::Empty => domain_hash("ic-hashtree-empty", &[]),
+            HashTree::Fork(left, right) => domain_hash("ic-hashtree-fork", &[&left.digest(), &right.digest()]),
+            HashTree::Labeled(label, subtree) => domain_hash("ic-hashtree-labeled", &[label, &subtree.digest()]),
+            HashTree::Leaf(value) => domain_hash("ic-hashtree-leaf", &[value]),
+            HashTree::Pruned(digest) => *digest,
+        }
+    }
+
+    fn to_cbor(&self) -> Value {
+        let node = |tag: i128, parts: Vec<Value>| Value::Array(std::iter::once(Value::Integer(tag)).chain(parts).collect());
+        match self {
+            HashTree::Empty => node(0, vec![]),
+            HashTree::Fork(left, right) => node(1, vec![left.to_cbor(), right.to_cbor()]),
+            HashTree::Labeled(label, subtree) => node(2, vec![Value::Bytes(label.clone()), subtree.to_cbor()]),
+            HashTree::Leaf(value) => node(3, vec![Value::Bytes(value.clone())]),
+            HashTree::Pruned(digest) => node(4, vec![Value::Bytes(digest.to_vec())]),
+        }
+    }
+}
+
+/// SHA-256 over the length-prefixed domain separator and `parts`
+fn domain_hash(domain: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([domain.len() as u8]);
+    hasher.update(domain.as_bytes());
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+type Leaves<'a> = [(&'a String, &'a [u8; 32])];
+
+impl CertificationService {
+    const ROOT_LABEL: &'static [u8] = b"agents";
+
+    /// Bring the certified tree up to the current registry version; update context only
+    pub fn certify() {
+        if let Some(root) = with_state_mut(Self::refresh) {
+            ic_cdk::api::set_certified_data(&root);
+        }
+    }
+
+    /// Apply registry changes since the last certification; the new root hash when anything changed
+    fn refresh(state: &mut CoordinatorState) -> Option<[u8; 32]> {
+        let version = state.agent_registry_version;
+        let certified = state.certified_registry.version;
+        if certified == Some(version) {
+            return None;
+        }
+        let delta = certified.map(|since| RegistryService::delta(state, since));
+        let mut leaves = std::mem::take(&mut state.certified_registry.leaves);
+        match delta {
+            Some(delta) if !delta.full_resync => {
+                for agent in delta.created.iter().chain(&delta.updated) {
+                    leaves.insert(agent.agent_id.clone(), Self::leaf_hash(agent));
+                }
+                for agent_id in &delta.removed {
+                    leaves.remove(agent_id);
+                }
+            }
+            _ => {
+                leaves = state.agents
+                    .values()
+                    .map(|agent| (agent.agent_id.clone(), Self::leaf_hash(agent)))
+                    .collect();
+            }
+        }
+        let root = Self::root_hash(&leaves);
+        state.certified_registry = CertifiedRegistry { version: Some(version), leaves };
+        Some(root)
+    }
+
+    pub fn get_certified_agent(agent_id: &str) -> Result<CertifiedAgent, String> {
+        let certificate = ic_cdk::api::data_certificate()
+            .ok_or_else(|| "Certified reads must be made as query calls".to_string())?;
+        with_state(|state| {
+            let agent = state.agents
+                .get(agent_id)
+                .cloned()
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            if state.certified_registry.leaves.get(agent_id) != Some(&Self::leaf_hash(&agent)) {
+                return Err(format!("Agent {} changed since the registry was last certified; retry shortly", agent_id));
+            }
+            let witness = serde_cbor::to_vec(&Self::witness(&state.certified_registry.leaves, agent_id).to_cbor())
+                .map_err(|e| format!("Failed to encode witness: {}", e))?;
+            Ok(CertifiedAgent { agent, certificate, witness })
+        })
+    }
+
+    fn leaf_hash(agent: &AgentRegistration) -> [u8; 32] {
+        Sha256::digest(candid::encode_one(agent).unwrap_or_default()).into()
+    }
+
+    fn root_hash(leaves: &BTreeMap<String, [u8; 32]>) -> [u8; 32] {
+        let leaves: Vec<_> = leaves.iter().collect();
+        domain_hash("ic-hashtree-labeled", &[Self::ROOT_LABEL, &Self::range_hash(&leaves)])
+    }
+
+    /// Digest of the balanced fork tree over `leaves`, which are sorted by agent id
+    fn range_hash(leaves: &Leaves) -> [u8; 32] {
+        match leaves {
+            [] => HashTree::Empty.digest(),
+            [(agent_id, hash)] => domain_hash("ic-hashtree-labeled", &[agent_id.as_bytes(), &HashTree::Leaf(hash.to_vec()).digest()]),
+            _ => {
+                let (left, right) = leaves.split_at(leaves.len() / 2);
+                domain_hash("ic-hashtree-fork", &[&Self::range_hash(left), &Self::range_hash(right)])
+            }
+        }
+    }
+
+    /// The tree of `root_hash` with every subtree not containing `agent_id` pruned
+    fn witness(leaves: &BTreeMap<String, [u8; 32]>, agent_id: &str) -> HashTree {
+        fn prune(leaves: &Leaves, agent_id: &str) -> HashTree {
+            let covers = matches!((leaves.first(), leaves.last()), (Some((first, _)), Some((last, _)))
+                if first.as_str() <= agent_id && agent_id <= last.as_str());
+            match leaves {
+                [] => HashTree::Empty,
+                _ if !covers => HashTree::Pruned(CertificationService::range_hash(leaves)),
+                [(id, hash)] => HashTree::Labeled(id.as_bytes().to_vec(), Box::new(HashTree::Leaf(hash.to_vec()))),
+                _ => {
+                    let (left, right) = leaves.split_at(leaves.len() / 2);
+                    HashTree::Fork(Box::new(prune(left, agent_id)), Box::new(prune(right, agent_id)))
+                }
+            }
+        }
+        let leaves: Vec<_> = leaves.iter().collect();
+        HashTree::Labeled(Self::ROOT_LABEL.to_vec(), Box::new(prune(&leaves, agent_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incremental_refresh_matches_full_rebuild() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("a"), "owner");
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("b"), "owner");
+        assert!(CertificationService::refresh(&mut state).is_some());
+        assert!(CertificationService::refresh(&mut state).is_none());
+
+        RegistryService::remove_registration(&mut state, "a", "owner", 0);
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("c"), "owner");
+        state.agents.get_mut("b").unwrap().health_score = 0.5;
+        RegistryService::mark_changed(&mut state, "b");
+        let root = CertificationService::refresh(&mut state).unwrap();
+
+        let mut rebuilt = CoordinatorState { agents: state.agents.clone(), ..Default::default() };
+        assert_eq!(CertificationService::refresh(&mut rebuilt), Some(root));
+        assert_eq!(state.certified_registry.leaves.keys().collect::<Vec<_>>(), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_witness_reconstructs_root_for_every_agent() {
+        let leaves: BTreeMap<String, [u8; 32]> = (0..7u8).map(|i| (format!("agent-{}", i), [i; 32])).collect();
+        let root = CertificationService::root_hash(&leaves);
+        for agent_id in leaves.keys() {
+            let witness = CertificationService::witness(&leaves, agent_id);
+            assert_eq!(witness.digest(), root, "{}", agent_id);
+        }
+        let mut changed = leaves.clone();
+        changed.insert("agent-3".to_string(), [9; 32]);
+        assert_ne!(CertificationService::witness(&changed, "agent-3").digest(), root);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::domain::*;
+use crate::services::{CoordinatorState, IdGenerator, ProvenanceService, ReviewService, RoutingService};
+use crate::services::safety_limits::DispatchCost;
+use crate::services::provenance::{ProvenanceStage, StepRecord};
+use crate::services::review::{ReviewOptions, ReviewVerdict};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use futures::future::{select, Either};
+
+/// Chained routing: steps run one after another, each prompt built from the output of
+/// the step before. A step that fails, overruns its budget or is rejected by its
+/// verifier ends the chain; everything produced up to that point is returned.
+pub struct ChainService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ChainStep {
+    pub capabilities: Vec<String>,
+    /// Prompt template; `{{previous}}` is replaced by the previous step's output, or by
+    /// nothing in the first step
+    pub transform: String,
+    /// Reviewer check on the step's output before it is passed on
+    pub verifier: Option<ReviewOptions>,
+    /// Stop the chain unless the reviewer approves; otherwise only a rejection stops it
+    pub require_approval: bool,
+    /// Overrides the caller's default decode profile for this step
+    pub decode_profile: Option<DecodeProfile>,
+    /// Deadline for the agent's answer; the step fails once it passes, without waiting
+    /// for the call to return
+    pub max_time_ms: Option<u64>,
+    /// Output beyond this many bytes is cut before it reaches the next step
+    pub max_output_bytes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ChainStepResult {
+    pub step_index: u32,
+    pub agent_id: Option<String>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+    pub verdict: Option<ReviewVerdict>,
+    /// Output was cut to the step's `max_output_bytes`
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ChainResult {
+    pub chain_id: String,
+    pub steps: Vec<ChainStepResult>,
+    /// Output of the last step, once every step has succeeded
+    pub final_output: Option<String>,
+    pub completed: bool,
+    pub total_time_ms: u64,
+    /// Head of the provenance chain; full chain via `get_result_provenance(chain_id)`
+    pub provenance_sha256: Option<String>,
+}
+
+impl ChainService {
+    const MAX_STEPS: usize = 8;
+    const PREVIOUS_PLACEHOLDER: &'static str = "{{previous}}";
+
+    pub async fn run(requester: &str, steps: Vec<ChainStep>, default_profile: DecodeProfile) -> Result<ChainResult, String> {
+        Self::validate(&steps)?;
+        let start = time();
+        let chain_id = IdGenerator::next("chain", requester);
+        ProvenanceService::begin(&chain_id);
+
+        let mut results = Vec::new();
+        let mut previous = String::new();
+        for (index, step) in steps.iter().enumerate() {
+            let result = Self::run_step(requester, &chain_id, index, step, &previous, step.decode_profile.unwrap_or(default_profile)).await;
+            let output = result.output.clone().filter(|_| result.error.is_none());
+            results.push(result);
+            match output {
+                Some(output) => previous = output,
+                None => break,
+            }
+        }
+
+        let completed = results.len() == steps.len() && results.iter().all(|r| r.error.is_none());
+        let final_output = completed.then_some(previous);
+        let provenance_sha256 = final_output.as_ref().and_then(|output| ProvenanceService::finalize(&chain_id, output));
+        Metrics::increment_counter(if completed { "route_chains_completed" } else { "route_chains_failed" });
+        Ok(ChainResult {
+            chain_id,
+            steps: results,
+            final_output,
+            completed,
+            total_time_ms: (time() - start) / 1_000_000,
+            provenance_sha256,
+        })
+    }
+
+    /// Upper bound of what the chain may consume, including each verifier and repair
+    pub(crate) fn estimate_cost(state: &CoordinatorState, steps: &[ChainStep], default_profile: DecodeProfile) -> DispatchCost {
+        steps.iter().fold(DispatchCost::default(), |total, step| {
+            let profile = step.decode_profile.unwrap_or(default_profile);
+            let review_calls = step.verifier.as_ref().map_or(0, |review| if review.repair_on_reject { 3 } else { 1 });
+            total
+                + DispatchCost::inference(state, &step.capabilities, 1, profile)
+                + DispatchCost { spend_usd: 0.0, ..DispatchCost::inference(state, &[], review_calls, profile) }
+        })
+    }
+
+    fn validate(steps: &[ChainStep]) -> Result<(), String> {
+        if steps.is_empty() {
+            return Err("A chain needs at least one step".to_string());
+        }
+        if steps.len() > Self::MAX_STEPS {
+            return Err(format!("A chain may have at most {} steps", Self::MAX_STEPS));
+        }
+        for (index, step) in steps.iter().enumerate() {
+            if step.capabilities.is_empty() {
+                return Err(format!("Step {} names no capabilities", index));
+            }
+            if step.transform.trim().is_empty() {
+                return Err(format!("Step {} has an empty transform", index));
+            }
+            if step.max_output_bytes == Some(0) || step.max_time_ms == Some(0) {
+                return Err(format!("Step {} has a zero budget", index));
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_step(
+        requester: &str,
+        chain_id: &str,
+        index: usize,
+        step: &ChainStep,
+        previous: &str,
+        decode_profile: DecodeProfile,
+    ) -> ChainStepResult {
+        let prompt = step.transform.replace(Self::PREVIOUS_PLACEHOLDER, previous);
+        let mut result = ChainStepResult {
+            step_index: index as u32,
+            agent_id: None,
+            output: None,
+            error: None,
+            elapsed_ms: 0,
+            verdict: None,
+            truncated: false,
+        };
+
+        // Each step is its own request so its verification evidence is kept separately
+        let request = RouteRequest {
+            request_id: format!("{}:step:{}", chain_id, index),
+            requester: requester.to_string(),
+            capabilities_required: step.capabilities.clone(),
+            payload: prompt.clone().into_bytes(),
+            routing_mode: RoutingMode::Unicast,
+            callback: None,
+            tags: Vec::new(),
+            guaranteed_service: false,
+            priority: None,
+            strategy: None,
+            review: step.verifier.clone(),
+            model_preference: None,
+            session_key: None,
+            partition_key: None,
+            aggregation: None,
+        };
+        let agent = match RoutingService::select_multiple_agents(&request, 1).map(|agents| agents.into_iter().next()) {
+            Ok(Some(agent)) => agent,
+            Ok(None) => {
+                result.error = Some("No agent available for step".to_string());
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+        result.agent_id = Some(agent.agent_id.clone());
+
+        let seed = RoutingService::derive_seed(&request.request_id);
+        let call = {
+            let (agent, prompt, msg_id) = (agent.clone(), prompt.clone(), request.request_id.clone());
+            Box::pin(async move { RoutingService::dispatch_inference(&agent, &prompt, &msg_id, seed, decode_profile).await })
+        };
+        let outcome = match step.max_time_ms {
+            None => call.await,
+            Some(max_time_ms) => match select(call, Box::pin(RoutingService::delay(max_time_ms))).await {
+                Either::Left((outcome, _)) => outcome,
+                Either::Right(((), call)) => {
+                    // The late answer still feeds the agent's stats and circuit breaker
+                    ic_cdk::spawn(async move {
+                        let _ = call.await;
+                    });
+                    result.elapsed_ms = max_time_ms;
+                    result.error = Some(format!("Budget: step did not finish within {} ms", max_time_ms));
+                    return result;
+                }
+            },
+        };
+        let (response, elapsed) = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+        result.elapsed_ms = elapsed / 1_000_000;
+        let mut output = response.generated_text;
+        ProvenanceService::record(chain_id, StepRecord {
+            stage: ProvenanceStage::Chain,
+            agent_id: &agent.agent_id,
+            part: format!("step {}", index),
+            input: Some(&prompt),
+            output: Some(&output),
+        });
+        if let Some(options) = &step.verifier {
+            let (record, repaired) = ReviewService::review(&request, options, &agent.agent_id, &output, seed, decode_profile).await;
+            result.verdict = Some(record.verdict);
+            if let Some(repaired) = repaired {
+                output = repaired;
+            }
+            let passed = match record.verdict {
+                ReviewVerdict::Approved => true,
+                ReviewVerdict::Inconclusive => !step.require_approval,
+                ReviewVerdict::Rejected => false,
+            };
+            if !passed {
+                result.error = Some(format!("Verifier {:?} the step output: {}", record.verdict, record.evidence.details));
+            }
+        }
+
+        if let Some(max_bytes) = step.max_output_bytes {
+            result.truncated = Self::truncate(&mut output, max_bytes as usize);
+        }
+        result.output = Some(output);
+        result
+    }
+
+    /// Cut `text` to at most `max_bytes` without splitting a character
+    fn truncate(text: &mut String, max_bytes: usize) -> bool {
+        if text.len() <= max_bytes {
+            return false;
+        }
+        let mut end = max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(capabilities: &[&str], transform: &str) -> ChainStep {
+        ChainStep {
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            transform: transform.to_string(),
+            verifier: None,
+            require_approval: false,
+            decode_profile: None,
+            max_time_ms: None,
+            max_output_bytes: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_malformed_chains() {
+        assert!(ChainService::validate(&[]).is_err());
+        assert!(ChainService::validate(&[step(&[], "summarize")]).is_err());
+        assert!(ChainService::validate(&[step(&["nlp"], "  ")]).is_err());
+        let too_long: Vec<ChainStep> = (0..9).map(|_| step(&["nlp"], "{{previous}}")).collect();
+        assert!(ChainService::validate(&too_long).is_err());
+        assert!(ChainService::validate(&[step(&["nlp"], "Draft"), step(&["review"], "Edit: {{previous}}")]).is_ok());
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundaries() {
+        let mut text = "héllo".to_string();
+        assert!(ChainService::truncate(&mut text, 2));
+        assert_eq!(text, "h");
+        let mut text = "short".to_string();
+        assert!(!ChainService::truncate(&mut text, 10));
+        assert_eq!(text, "short");
+    }
+}
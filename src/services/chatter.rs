@@ -0,0 +1,162 @@
+use crate::services::{with_state_mut, CoordinatorState};
+use crate::services::autonomous_coord::AgentMessage;
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+
+/// Per-agent, per-session limits on coordination messages
+pub struct ChatterLimiter;
+
+/// Message accounting for one agent in one session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatterWindow {
+    pub window_start: u64,
+    pub messages_in_window: u32,
+    /// Rejections since the agent was last muted
+    pub violations: u32,
+    pub muted_until: Option<u64>,
+}
+
+/// Totals surfaced through coordination stats
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatterCounters {
+    pub rate_limited_messages: u64,
+    pub oversized_messages: u64,
+    pub mutes: u64,
+}
+
+impl ChatterLimiter {
+    const WINDOW_NS: u64 = 60 * 1_000_000_000;
+    pub(crate) const MAX_MESSAGES_PER_WINDOW: u32 = 30;
+    const MAX_MESSAGE_BYTES: usize = 16 * 1024;
+    /// Rejections that turn an agent into a persistent offender
+    const MUTE_AFTER_VIOLATIONS: u32 = 5;
+    const MUTE_NS: u64 = 10 * 60 * 1_000_000_000;
+
+    /// Admit a message from `agent_id` into `session_id`, or return a `RateLimited:` error
+    pub fn admit(session_id: &str, agent_id: &str, message: &AgentMessage) -> Result<(), String> {
+        let size = candid::encode_one(message).map(|bytes| bytes.len()).unwrap_or(usize::MAX);
+        let now = time();
+        let outcome = with_state_mut(|state| {
+            let window = state.session_chatter
+                .entry(Self::key(session_id, agent_id))
+                .or_default();
+            let outcome = Self::check(window, now, size);
+            match &outcome {
+                Ok(()) => {}
+                Err(Rejection::Oversized) => state.chatter_counters.oversized_messages += 1,
+                Err(Rejection::TooFrequent { .. }) => state.chatter_counters.rate_limited_messages += 1,
+                Err(Rejection::Muted { newly: true, .. }) => state.chatter_counters.mutes += 1,
+                Err(Rejection::Muted { .. }) => {}
+            }
+            outcome
+        });
+
+        outcome.map_err(|rejection| {
+            Metrics::increment_counter("coordination_messages_rate_limited_total");
+            match rejection {
+                Rejection::Oversized => format!(
+                    "RateLimited: message of {} bytes exceeds the {}-byte cap",
+                    size, Self::MAX_MESSAGE_BYTES
+                ),
+                Rejection::TooFrequent { retry_after_ns } => format!(
+                    "RateLimited: agent {} exceeded {} messages per minute in session {}; retry after {} ms",
+                    agent_id, Self::MAX_MESSAGES_PER_WINDOW, session_id, retry_after_ns / 1_000_000
+                ),
+                Rejection::Muted { until, .. } => format!(
+                    "RateLimited: agent {} is muted in session {} until {}",
+                    agent_id, session_id, until
+                ),
+            }
+        })
+    }
+
+    fn check(window: &mut ChatterWindow, now: u64, size: usize) -> Result<(), Rejection> {
+        if let Some(until) = window.muted_until {
+            if now < until {
+                return Err(Rejection::Muted { until, newly: false });
+            }
+            window.muted_until = None;
+            window.violations = 0;
+        }
+
+        if now.saturating_sub(window.window_start) >= Self::WINDOW_NS {
+            window.window_start = now;
+            window.messages_in_window = 0;
+        }
+
+        let rejection = if size > Self::MAX_MESSAGE_BYTES {
+            Rejection::Oversized
+        } else if window.messages_in_window >= Self::MAX_MESSAGES_PER_WINDOW {
+            Rejection::TooFrequent { retry_after_ns: window.window_start + Self::WINDOW_NS - now }
+        } else {
+            window.messages_in_window += 1;
+            return Ok(());
+        };
+
+        window.violations += 1;
+        if window.violations >= Self::MUTE_AFTER_VIOLATIONS {
+            let until = now + Self::MUTE_NS;
+            window.muted_until = Some(until);
+            return Err(Rejection::Muted { until, newly: true });
+        }
+        Err(rejection)
+    }
+
+    /// Agent/session pairs muted at `now`
+    pub fn muted_agents_in(state: &CoordinatorState, now: u64) -> u32 {
+        state.session_chatter
+            .values()
+            .filter(|w| w.muted_until.map(|until| now < until).unwrap_or(false))
+            .count() as u32
+    }
+
+    /// Drop accounting for a session that no longer exists
+    pub fn forget_session(state: &mut CoordinatorState, session_id: &str) {
+        let prefix = Self::key(session_id, "");
+        state.session_chatter.retain(|key, _| !key.starts_with(&prefix));
+    }
+
+    fn key(session_id: &str, agent_id: &str) -> String {
+        format!("{}/{}", session_id, agent_id)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Rejection {
+    Oversized,
+    TooFrequent { retry_after_ns: u64 },
+    Muted { until: u64, newly: bool },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_resets_each_window() {
+        let mut window = ChatterWindow::default();
+        for _ in 0..ChatterLimiter::MAX_MESSAGES_PER_WINDOW {
+            assert!(ChatterLimiter::check(&mut window, 1, 100).is_ok());
+        }
+        assert!(matches!(ChatterLimiter::check(&mut window, 2, 100), Err(Rejection::TooFrequent { .. })));
+        assert!(ChatterLimiter::check(&mut window, 1 + ChatterLimiter::WINDOW_NS, 100).is_ok());
+        assert_eq!(
+            ChatterLimiter::check(&mut window, 2 + ChatterLimiter::WINDOW_NS, ChatterLimiter::MAX_MESSAGE_BYTES + 1),
+            Err(Rejection::Oversized)
+        );
+    }
+
+    #[test]
+    fn test_persistent_offender_is_muted() {
+        let mut window = ChatterWindow::default();
+        let oversized = ChatterLimiter::MAX_MESSAGE_BYTES + 1;
+        for _ in 1..ChatterLimiter::MUTE_AFTER_VIOLATIONS {
+            assert_eq!(ChatterLimiter::check(&mut window, 10, oversized), Err(Rejection::Oversized));
+        }
+        let until = 10 + ChatterLimiter::MUTE_NS;
+        assert_eq!(ChatterLimiter::check(&mut window, 10, oversized), Err(Rejection::Muted { until, newly: true }));
+        assert_eq!(ChatterLimiter::check(&mut window, 20, 100), Err(Rejection::Muted { until, newly: false }));
+        assert!(ChatterLimiter::check(&mut window, until, 100).is_ok());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, RegistryAuditService, RegistryService, ServiceReportService};
+use crate::services::service_report::IncidentKind;
+use crate::services::autonomous_coord::AvailabilityStatus;
+use ic_cdk::api::call::RejectionCode;
+use ic_cdk::api::time;
+
+/// Per-agent circuit breaker driven by classified inter-canister call failures
+pub struct CircuitBreakerService;
+
+impl CircuitBreakerService {
+    const FAILURE_THRESHOLD: u32 = 3;
+    const COOLDOWN_NS: u64 = 5 * 60 * 1_000_000_000; // 5 minutes
+    
+    /// Classify a reject code and message into an actionable failure class
+    pub fn classify(code: RejectionCode, message: &str) -> CallFailureClass {
+        let msg = message.to_lowercase();
+        if msg.contains("is stopped") || msg.contains("is stopping") {
+            return CallFailureClass::CanisterStopped;
+        }
+        if msg.contains("out of cycles") || msg.contains("insufficient cycles") {
+            return CallFailureClass::OutOfCycles;
+        }
+        match code {
+            RejectionCode::DestinationInvalid => CallFailureClass::DestinationInvalid,
+            RejectionCode::SysTransient => CallFailureClass::Timeout,
+            RejectionCode::CanisterError => CallFailureClass::Trapped,
+            RejectionCode::CanisterReject => CallFailureClass::Rejected,
+            _ => {
+                if msg.contains("timeout") || msg.contains("timed out") {
+                    CallFailureClass::Timeout
+                } else {
+                    CallFailureClass::Unknown
+                }
+            }
+        }
+    }
+    
+    /// Failure classes that mean the agent cannot serve traffic until someone intervenes
+    fn is_hard_failure(class: &CallFailureClass) -> bool {
+        matches!(class, CallFailureClass::CanisterStopped | CallFailureClass::OutOfCycles | CallFailureClass::DestinationInvalid)
+    }
+    
+    /// Health score after a failure of the given class
+    fn degraded_health(current: f32, class: &CallFailureClass) -> f32 {
+        match class {
+            CallFailureClass::CanisterStopped | CallFailureClass::OutOfCycles | CallFailureClass::DestinationInvalid => 0.0,
+            CallFailureClass::Trapped => current * 0.5,
+            CallFailureClass::Timeout | CallFailureClass::Rejected | CallFailureClass::Unknown => (current - 0.1).max(0.0),
+        }
+    }
+    
+    /// Record a failed call to an agent: degrade health, update availability and trip the breaker if needed
+    pub fn record_failure(agent_id: &str, class: CallFailureClass) {
+        let now = time();
+        with_state_mut(|state| {
+            if let Some(health_score) = state.agents.get(agent_id).map(|agent| Self::degraded_health(agent.health_score, &class)) {
+                RegistryService::set_health_in(state, agent_id, health_score, RegistryAuditService::CIRCUIT_BREAKER, now);
+            }
+            
+            if Self::is_hard_failure(&class) {
+                if let Some(profile) = state.agent_capability_profiles.as_mut().and_then(|p| p.get_mut(agent_id)) {
+                    profile.availability_status = AvailabilityStatus::Offline;
+                }
+            }
+            
+            let circuit = state.agent_circuits
+                .entry(agent_id.to_string())
+                .or_insert_with(|| AgentCircuit::new(agent_id));
+            circuit.consecutive_failures += 1;
+            circuit.last_failure_class = Some(class.clone());
+            circuit.last_failure_at = Some(now);
+            if Self::is_hard_failure(&class) || circuit.consecutive_failures >= Self::FAILURE_THRESHOLD {
+                let was_open = circuit.open_until.is_some_and(|until| until > now);
+                circuit.open_until = Some(now + Self::COOLDOWN_NS);
+                if !was_open {
+                    let kind = IncidentKind::BreakerOpened { agent_id: agent_id.to_string() };
+                    let owner = state.agents.get(agent_id).map(|agent| agent.agent_principal.clone());
+                    ServiceReportService::open_incident_in(state, kind, owner, now, Some(now + Self::COOLDOWN_NS));
+                }
+            }
+        });
+    }
+    
+    /// Record a successful call: close the breaker and restore availability
+    pub fn record_success(agent_id: &str) {
+        with_state_mut(|state| {
+            if let Some(circuit) = state.agent_circuits.get_mut(agent_id) {
+                circuit.consecutive_failures = 0;
+                circuit.open_until = None;
+            }
+            if let Some(profile) = state.agent_capability_profiles.as_mut().and_then(|p| p.get_mut(agent_id)) {
+                if matches!(profile.availability_status, AvailabilityStatus::Offline) {
+                    profile.availability_status = AvailabilityStatus::Available;
+                }
+            }
+        });
+    }
+    
+    /// Whether the breaker currently blocks traffic to the agent
+    pub fn is_open(agent_id: &str) -> bool {
+        let now = time();
+        with_state(|state| {
+            state.agent_circuits
+                .get(agent_id)
+                .and_then(|c| c.open_until)
+                .map(|until| until > now)
+                .unwrap_or(false)
+        })
+    }
+    
+    pub fn get_circuit(agent_id: &str) -> Option<AgentCircuit> {
+        with_state(|state| state.agent_circuits.get(agent_id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_stopped_and_cycles() {
+        assert_eq!(
+            CircuitBreakerService::classify(RejectionCode::CanisterError, "Canister abc is stopped"),
+            CallFailureClass::CanisterStopped
+        );
+        assert_eq!(
+            CircuitBreakerService::classify(RejectionCode::SysTransient, "Canister abc is out of cycles"),
+            CallFailureClass::OutOfCycles
+        );
+    }
+
+    #[test]
+    fn test_classify_by_code() {
+        assert_eq!(CircuitBreakerService::classify(RejectionCode::CanisterError, "trapped explicitly"), CallFailureClass::Trapped);
+        assert_eq!(CircuitBreakerService::classify(RejectionCode::SysTransient, "busy"), CallFailureClass::Timeout);
+        assert_eq!(CircuitBreakerService::classify(RejectionCode::DestinationInvalid, "no such canister"), CallFailureClass::DestinationInvalid);
+        assert_eq!(CircuitBreakerService::classify(RejectionCode::CanisterReject, "nope"), CallFailureClass::Rejected);
+    }
+
+    #[test]
+    fn test_degraded_health() {
+        assert_eq!(CircuitBreakerService::degraded_health(0.9, &CallFailureClass::OutOfCycles), 0.0);
+        assert_eq!(CircuitBreakerService::degraded_health(0.8, &CallFailureClass::Trapped), 0.4);
+        assert_eq!(CircuitBreakerService::degraded_health(0.05, &CallFailureClass::Timeout), 0.0);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::services::{with_state, CoordinatorState, QuotaManager};
+use crate::services::autonomous_coord::{SessionStatus, TaskStatus};
+use crate::services::jobs::JobStatus;
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Caps on how many of a subscriber's agents may be busy at once. An agent is active
+/// while it holds an open ledger task or inference job, or sits in a live coordination
+/// session. Only principals with a quota record are capped; independent agent operators
+/// are not subscribers and are left alone.
+pub struct ConcurrencyService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ConcurrencyUsage {
+    pub principal: String,
+    pub tier: String,
+    pub active_agents: u32,
+    pub limit: u32,
+    pub active_agent_ids: Vec<String>,
+}
+
+impl ConcurrencyService {
+    /// Sessions and open tasks idle this long stop counting, matching session cleanup
+    const SESSION_IDLE_NS: u64 = 3600 * 1_000_000_000;
+
+    pub fn usage(principal: &str) -> ConcurrencyUsage {
+        let now = time();
+        with_state(|state| Self::usage_in(state, principal, now))
+    }
+
+    fn usage_in(state: &CoordinatorState, principal: &str, now: u64) -> ConcurrencyUsage {
+        let tier = state.user_quotas.get(principal).map(|q| q.subscription_tier.clone());
+        let active: BTreeSet<String> = Self::active_agents(state, now)
+            .into_iter()
+            .filter(|agent_id| state.agents.get(agent_id).is_some_and(|a| a.agent_principal == principal))
+            .collect();
+        ConcurrencyUsage {
+            principal: principal.to_string(),
+            limit: tier.as_deref().map_or(u32::MAX, QuotaManager::concurrency_cap_for_tier),
+            tier: tier.unwrap_or_default(),
+            active_agents: active.len() as u32,
+            active_agent_ids: active.into_iter().collect(),
+        }
+    }
+
+    /// Every agent currently busy, whoever owns it
+    fn active_agents(state: &CoordinatorState, now: u64) -> BTreeSet<String> {
+        let tasks = state.task_ledger.values()
+            .filter(|task| matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress))
+            .filter(|task| now.saturating_sub(task.updated_at) <= Self::SESSION_IDLE_NS)
+            .map(|task| task.assigned_agent.clone());
+        let jobs = state.inference_jobs.values()
+            .filter(|job| matches!(job.status, JobStatus::Dispatched | JobStatus::Running))
+            .map(|job| job.agent_id.clone());
+        let sessions = state.coordination_sessions.iter()
+            .flat_map(|sessions| sessions.values())
+            .filter(|session| matches!(session.status, SessionStatus::Active | SessionStatus::Coordinating))
+            .filter(|session| now.saturating_sub(session.last_activity) <= Self::SESSION_IDLE_NS)
+            .flat_map(|session| session.participants.iter().cloned());
+        tasks.chain(jobs).chain(sessions).collect()
+    }
+
+    /// Refuse with `ConcurrencyLimit` when making `agent_ids` active would take any
+    /// owner past their tier's cap; agents already active cost nothing
+    pub fn check_activation(agent_ids: &[String]) -> Result<(), String> {
+        let now = time();
+        with_state(|state| {
+            let active = Self::active_agents(state, now);
+            let mut newly_active: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+            for agent_id in agent_ids.iter().filter(|id| !active.contains(*id)) {
+                if let Some(agent) = state.agents.get(agent_id) {
+                    newly_active.entry(agent.agent_principal.as_str()).or_default().insert(agent_id);
+                }
+            }
+            for (owner, requested) in newly_active {
+                let usage = Self::usage_in(state, owner, now);
+                if usage.active_agents as u64 + requested.len() as u64 > usage.limit as u64 {
+                    Metrics::increment_counter("concurrency_limit_rejections");
+                    return Err(Self::limit_error(&usage, requested.len() as u32));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn limit_error(usage: &ConcurrencyUsage, requested: u32) -> String {
+        format!(
+            "ConcurrencyLimit: {} has {} of {} concurrent agents active on the {} tier; {} more requested",
+            usage.principal, usage.active_agents, usage.limit, usage.tier, requested
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AgentRegistration;
+    use crate::services::autonomous_coord::TaskLedgerEntry;
+
+    fn agent(agent_id: &str, owner: &str) -> AgentRegistration {
+        AgentRegistration {
+            agent_principal: owner.to_string(),
+            canister_id: "ohms-agent".to_string(),
+            model_id: "llama".to_string(),
+            ..AgentRegistration::test(agent_id)
+        }
+    }
+
+    #[test]
+    fn test_usage_counts_owned_agents_with_open_work() {
+        let mut state = CoordinatorState::default();
+        for (agent_id, owner) in [("a1", "alice"), ("a2", "alice"), ("b1", "bob")] {
+            state.agents.insert(agent_id.to_string(), agent(agent_id, owner));
+        }
+        state.task_ledger.insert("t1".to_string(), TaskLedgerEntry::test("t1", "a1", TaskStatus::InProgress));
+        state.task_ledger.insert("t2".to_string(), TaskLedgerEntry::test("t2", "a1", TaskStatus::Pending));
+        state.task_ledger.insert("t3".to_string(), TaskLedgerEntry::test("t3", "a2", TaskStatus::Completed));
+        state.task_ledger.insert("t4".to_string(), TaskLedgerEntry::test("t4", "b1", TaskStatus::Pending));
+
+        let usage = ConcurrencyService::usage_in(&state, "alice", 0);
+        assert_eq!(usage.active_agent_ids, vec!["a1".to_string()]);
+        // No quota record, so no cap
+        assert_eq!(usage.limit, u32::MAX);
+
+        let limited = ConcurrencyUsage { limit: 1, tier: "Free".to_string(), ..usage };
+        assert!(ConcurrencyService::limit_error(&limited, 1).starts_with("ConcurrencyLimit: alice has 1 of 1"));
+        // Open tasks nobody has touched for an hour no longer hold their agent
+        let idle = ConcurrencyService::usage_in(&state, "alice", ConcurrencyService::SESSION_IDLE_NS + 1);
+        assert!(idle.active_agent_ids.is_empty());
+    }
+}
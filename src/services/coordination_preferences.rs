@@ -0,0 +1,147 @@
+use crate::services::{with_state, with_state_mut, ChatterLimiter, CoordinatorState};
+use crate::services::autonomous_coord::{CommunicationFrequency, CoordinationPreferences, SessionStatus};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Coordination preferences declared by agents themselves, at registration or on a
+/// health heartbeat, reconciled against the limits the coordinator imposes. The
+/// effective result is what coordination uses; the declaration is kept alongside it.
+pub struct CoordinationPreferenceService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentCoordinationPreferences {
+    pub agent_id: String,
+    /// None until the agent declares; the effective preferences are then defaults
+    pub declared: Option<CoordinationPreferences>,
+    pub effective: CoordinationPreferences,
+    /// Why the effective preferences differ from the declared ones
+    pub adjustments: Vec<String>,
+}
+
+impl CoordinationPreferenceService {
+    /// Most coordination sessions any agent may take part in at once
+    pub const MAX_COLLABORATIONS: u32 = 10;
+
+    /// Effective preferences for a declaration, with a note for every limit applied
+    pub fn reconcile(declared: &CoordinationPreferences) -> (CoordinationPreferences, Vec<String>) {
+        let mut effective = declared.clone();
+        let mut adjustments = Vec::new();
+
+        if effective.max_concurrent_collaborations == 0 {
+            effective.max_concurrent_collaborations = 1;
+            adjustments.push("max_concurrent_collaborations raised to 1".to_string());
+        } else if effective.max_concurrent_collaborations > Self::MAX_COLLABORATIONS {
+            effective.max_concurrent_collaborations = Self::MAX_COLLABORATIONS;
+            adjustments.push(format!("max_concurrent_collaborations capped at the coordinator limit of {}", Self::MAX_COLLABORATIONS));
+        }
+
+        let mut types = Vec::new();
+        for coordination_type in &declared.preferred_coordination_types {
+            if !types.iter().any(|t| std::mem::discriminant(t) == std::mem::discriminant(coordination_type)) {
+                types.push(coordination_type.clone());
+            }
+        }
+        if types.len() != declared.preferred_coordination_types.len() {
+            adjustments.push("duplicate preferred_coordination_types removed".to_string());
+        }
+        if types.is_empty() {
+            types = CoordinationPreferences::default().preferred_coordination_types;
+            adjustments.push("no preferred_coordination_types declared; using the default".to_string());
+        }
+        effective.preferred_coordination_types = types;
+
+        if matches!(effective.communication_frequency, CommunicationFrequency::RealTime) {
+            effective.communication_frequency = CommunicationFrequency::Frequent;
+            adjustments.push(format!(
+                "RealTime communication exceeds the {} messages per minute session limit; using Frequent",
+                ChatterLimiter::MAX_MESSAGES_PER_WINDOW
+            ));
+        }
+
+        (effective, adjustments)
+    }
+
+    pub fn declare(agent_id: &str, declared: CoordinationPreferences) -> Result<AgentCoordinationPreferences, String> {
+        let (effective, adjustments) = Self::reconcile(&declared);
+        with_state_mut(|state| -> Result<(), String> {
+            let profile = state.agent_capability_profiles
+                .as_mut()
+                .and_then(|profiles| profiles.get_mut(agent_id))
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            profile.declared_preferences = Some(declared);
+            profile.coordination_preferences = effective;
+            profile.preference_adjustments = adjustments;
+            Ok(())
+        })?;
+        Self::get(agent_id)
+    }
+
+    pub fn get(agent_id: &str) -> Result<AgentCoordinationPreferences, String> {
+        with_state(|state| {
+            let profile = state.agent_capability_profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(agent_id))
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            Ok(AgentCoordinationPreferences {
+                agent_id: agent_id.to_string(),
+                declared: profile.declared_preferences.clone(),
+                effective: profile.coordination_preferences.clone(),
+                adjustments: profile.preference_adjustments.clone(),
+            })
+        })
+    }
+
+    /// Refuse a new session when a participant is already in as many live sessions as it accepts
+    pub fn check_collaborations(participants: &[String]) -> Result<(), String> {
+        with_state(|state| {
+            for agent_id in participants {
+                let Some(profile) = state.agent_capability_profiles.as_ref().and_then(|p| p.get(agent_id)) else {
+                    continue;
+                };
+                let limit = profile.coordination_preferences.max_concurrent_collaborations;
+                let current = Self::live_sessions(state, agent_id);
+                if current >= limit {
+                    return Err(format!(
+                        "CollaborationLimit: agent {} is in {} of the {} concurrent collaborations it accepts",
+                        agent_id, current, limit
+                    ));
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn live_sessions(state: &CoordinatorState, agent_id: &str) -> u32 {
+        state.coordination_sessions
+            .iter()
+            .flat_map(|sessions| sessions.values())
+            .filter(|session| matches!(session.status, SessionStatus::Active | SessionStatus::Coordinating))
+            .filter(|session| session.participants.iter().any(|p| p == agent_id))
+            .count() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::autonomous_coord::{ConflictResolutionStrategy, CoordinationType};
+
+    #[test]
+    fn test_reconcile_applies_coordinator_limits() {
+        let declared = CoordinationPreferences {
+            preferred_coordination_types: vec![CoordinationType::TaskDelegation, CoordinationType::TaskDelegation],
+            max_concurrent_collaborations: 50,
+            communication_frequency: CommunicationFrequency::RealTime,
+            conflict_resolution_strategy: ConflictResolutionStrategy::Negotiate,
+        };
+        let (effective, adjustments) = CoordinationPreferenceService::reconcile(&declared);
+        assert_eq!(effective.max_concurrent_collaborations, CoordinationPreferenceService::MAX_COLLABORATIONS);
+        assert_eq!(effective.preferred_coordination_types.len(), 1);
+        assert!(matches!(effective.communication_frequency, CommunicationFrequency::Frequent));
+        assert!(matches!(effective.conflict_resolution_strategy, ConflictResolutionStrategy::Negotiate));
+        assert_eq!(adjustments.len(), 3);
+
+        let (_, adjustments) = CoordinationPreferenceService::reconcile(&CoordinationPreferences::default());
+        assert!(adjustments.is_empty());
+    }
+}
@@ -1,15 +1,26 @@
 use crate::domain::*;
-use crate::services::{with_state, with_state_mut};
+use crate::services::{with_state, with_state_mut, CoordinatorState};
+use crate::infra::Metrics;
 use ic_cdk::api::time;
 use sha2::{Sha256, Digest};
 use base64::{Engine as _, engine::general_purpose};
 
+/// Request id cache making routing idempotent: a retry of a routed request gets the
+/// original decision back instead of being routed and charged again. The retry must
+/// carry the same payload and capabilities; reusing an id for a different request fails.
+/// Ids are scoped to the caller, so one principal's ids never collide with or reveal
+/// another's decisions.
 pub struct DedupService;
 
 impl DedupService {
     const TTL_DURATION: u64 = 24 * 60 * 60 * 1_000_000_000; // 24 hours in nanoseconds
     
-    pub fn is_duplicate(msg_id: &str) -> bool {
+    /// Cache key of `msg_id` sent by `caller`
+    fn key(caller: &str, msg_id: &str) -> String {
+        format!("{}/{}", caller, msg_id)
+    }
+
+    pub fn is_duplicate(caller: &str, msg_id: &str) -> bool {
         let now = time();
         
         with_state_mut(|state| {
@@ -17,11 +28,11 @@ impl DedupService {
             state.dedup_cache.retain(|_, entry| entry.ttl_expires_at > now);
             
             // Check if message ID exists and is not expired
-            state.dedup_cache.contains_key(msg_id)
+            state.dedup_cache.contains_key(&Self::key(caller, msg_id))
         })
     }
     
-    pub fn record_request(msg_id: &str, response: &RouteResponse) -> Result<(), String> {
+    pub fn record_request(caller: &str, msg_id: &str, request_sha256: &str, response: &RouteResponse) -> Result<(), String> {
         let now = time();
         let result_hash = Self::hash_response(response);
         
@@ -30,21 +41,44 @@ impl DedupService {
             processed_at: now,
             result_hash,
             ttl_expires_at: now + Self::TTL_DURATION,
+            request_sha256: Some(request_sha256.to_string()),
+            response: Some(response.clone()),
         };
         
         with_state_mut(|state| {
-            state.dedup_cache.insert(msg_id.to_string(), entry);
+            state.dedup_cache.insert(Self::key(caller, msg_id), entry);
         });
         
         Ok(())
     }
     
-    pub fn get_cached_result(msg_id: &str) -> Option<String> {
+    /// The decision recorded for an earlier request with this id, or None for a new id
+    pub fn cached_response(caller: &str, msg_id: &str, request_sha256: &str) -> Result<Option<RouteResponse>, String> {
+        let now = time();
+        let cached = with_state(|state| Self::cached_response_in(state, caller, msg_id, request_sha256, now))?;
+        if cached.is_some() {
+            Metrics::increment_counter("dedup_cache_hits_total");
+        }
+        Ok(cached)
+    }
+
+    fn cached_response_in(state: &CoordinatorState, caller: &str, msg_id: &str, request_sha256: &str, now: u64) -> Result<Option<RouteResponse>, String> {
+        let Some(entry) = state.dedup_cache.get(&Self::key(caller, msg_id)).filter(|entry| entry.ttl_expires_at > now) else {
+            return Ok(None);
+        };
+        match (&entry.request_sha256, &entry.response) {
+            (Some(hash), Some(response)) if hash == request_sha256 => Ok(Some(response.clone())),
+            // Entries recorded before responses were kept cannot be replayed
+            _ => Err("Duplicate request ID".to_string()),
+        }
+    }
+    
+    pub fn get_cached_result(caller: &str, msg_id: &str) -> Option<String> {
         let now = time();
         
         with_state(|state| {
             state.dedup_cache
-                .get(msg_id)
+                .get(&Self::key(caller, msg_id))
                 .filter(|entry| entry.ttl_expires_at > now)
                 .map(|entry| entry.result_hash.clone())
         })
@@ -84,4 +118,40 @@ impl DedupService {
         let hash = hasher.finalize();
         general_purpose::STANDARD.encode(&hash[..16])
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeat_request_gets_recorded_response() {
+        let response = RouteResponse {
+            request_id: "req-1".to_string(),
+            selected_agents: vec!["a".to_string()],
+            routing_time_ms: 3,
+            selection_criteria: "Selected by unicast routing".to_string(),
+            model_fallback_level: None,
+            results: Vec::new(),
+            aggregated_output: None,
+        };
+        let mut state = CoordinatorState::default();
+        state.dedup_cache.insert(DedupService::key("alice", "req-1"), DedupEntry {
+            msg_id: "req-1".to_string(),
+            processed_at: 0,
+            result_hash: DedupService::hash_response(&response),
+            ttl_expires_at: 100,
+            request_sha256: Some("abc".to_string()),
+            response: Some(response),
+        });
+
+        let cached = DedupService::cached_response_in(&state, "alice", "req-1", "abc", 50).unwrap().unwrap();
+        assert_eq!(cached.selected_agents, vec!["a".to_string()]);
+        assert!(DedupService::cached_response_in(&state, "alice", "req-1", "other", 50).is_err(), "same id, different request");
+        assert!(DedupService::cached_response_in(&state, "alice", "req-1", "abc", 100).unwrap().is_none(), "expired");
+        assert!(DedupService::cached_response_in(&state, "alice", "req-2", "abc", 50).unwrap().is_none());
+        // Another principal reusing the id neither sees the decision nor is refused
+        assert!(DedupService::cached_response_in(&state, "mallory", "req-1", "abc", 50).unwrap().is_none());
+        assert!(DedupService::cached_response_in(&state, "mallory", "req-1", "other", 50).unwrap().is_none());
+    }
+}
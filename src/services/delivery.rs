@@ -0,0 +1,149 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, CoordinatorState, IdGenerator, MessageExpiryService, FeatureFlagService};
+use crate::infra::Metrics;
+use ic_cdk::api::{call, time};
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Push delivery of completed results to caller-registered callback canisters
+pub struct DeliveryService;
+
+/// Result payload pushed to a callback canister
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub enum DeliveryPayload {
+    Route(RouteResponse),
+    AgentCreation(AgentCreationResult),
+    JobProgress(crate::services::jobs::JobProgress),
+    /// Final state of a job its agent gave up on
+    JobFailed(crate::services::jobs::InferenceJob),
+    /// Agent queue message that expired before the agent read it
+    ExpiredMessage(crate::services::message_expiry::ExpiredAgentMessage),
+}
+
+/// Delivery that exhausted its retries
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct DeadLetter {
+    pub delivery_id: String,
+    pub owner_principal: String,
+    pub target: CallbackTarget,
+    pub payload: DeliveryPayload,
+    pub attempts: u32,
+    pub last_error: String,
+    pub dead_lettered_at: u64,
+}
+
+impl DeliveryService {
+    const MAX_ATTEMPTS: u32 = 3;
+    const MAX_DEAD_LETTERS: usize = 500;
+    /// Wait before the first retry, doubling for each one after
+    const BASE_BACKOFF_MS: u64 = 2_000;
+    
+    /// Push `payload` to `target` from a timer, so the caller's request returns without
+    /// waiting on the callback canister; failed attempts are retried with exponential
+    /// backoff before dead-lettering.
+    pub fn enqueue(owner_principal: &str, target: CallbackTarget, payload: DeliveryPayload) {
+        let delivery_id = IdGenerator::next("delivery", owner_principal);
+        Self::schedule(delivery_id, owner_principal.to_string(), target, payload, 0, Duration::ZERO);
+    }
+
+    fn schedule(delivery_id: String, owner_principal: String, target: CallbackTarget, payload: DeliveryPayload, attempt: u32, delay: Duration) {
+        ic_cdk_timers::set_timer(delay, move || {
+            ic_cdk::spawn(Self::attempt(delivery_id, owner_principal, target, payload, attempt));
+        });
+    }
+
+    async fn attempt(delivery_id: String, owner_principal: String, target: CallbackTarget, payload: DeliveryPayload, attempt: u32) {
+        // Held back while push delivery is off; redrive once it is back on
+        if !FeatureFlagService::is_enabled(FeatureFlagService::PUSH_DELIVERY) {
+            Self::dead_letter(delivery_id, &owner_principal, target, payload, attempt, "Push delivery disabled by operator".to_string());
+            return;
+        }
+        match Self::push(&target, &payload).await {
+            Ok(()) => Metrics::increment_counter("callback_deliveries_total"),
+            Err(_) if attempt + 1 < Self::MAX_ATTEMPTS => {
+                let delay = Duration::from_millis(Self::backoff_ms(attempt));
+                Self::schedule(delivery_id, owner_principal, target, payload, attempt + 1, delay);
+            }
+            Err(e) => Self::dead_letter(delivery_id, &owner_principal, target, payload, attempt + 1, e),
+        }
+    }
+
+    /// Delay before retry number `attempt + 1`
+    fn backoff_ms(attempt: u32) -> u64 {
+        Self::BASE_BACKOFF_MS << attempt.min(16)
+    }
+
+    fn dead_letter(delivery_id: String, owner_principal: &str, target: CallbackTarget, payload: DeliveryPayload, attempts: u32, last_error: String) {
+        let dead_letter = DeadLetter {
+            delivery_id,
+            owner_principal: owner_principal.to_string(),
+            target,
+            payload,
+            attempts,
+            last_error,
+            dead_lettered_at: time(),
+        };
+        with_state_mut(|state| Self::push_dead_letter(state, dead_letter));
+        Metrics::increment_counter("callback_dead_letters_total");
+    }
+
+    /// Keep a dead letter, dropping the oldest once the cap is reached
+    pub(crate) fn push_dead_letter(state: &mut CoordinatorState, dead_letter: DeadLetter) {
+        if state.dead_letters.len() >= Self::MAX_DEAD_LETTERS {
+            state.dead_letters.remove(0);
+        }
+        state.dead_letters.push(dead_letter);
+    }
+    
+    /// Single delivery attempt
+    async fn push(target: &CallbackTarget, payload: &DeliveryPayload) -> Result<(), String> {
+        let canister = Principal::from_text(&target.canister_id)
+            .map_err(|e| format!("Invalid callback canister id: {}", e))?;
+        
+        let result: call::CallResult<()> = call::call(canister, &target.method, (payload.clone(),)).await;
+        match result {
+            Ok(()) => {
+                Metrics::record_dependency_success("callback.deliver");
+                Ok(())
+            }
+            Err((code, msg)) => {
+                let error = format!("{:?}: {}", code, msg);
+                Metrics::record_dependency_failure("callback.deliver", &error);
+                Err(error)
+            }
+        }
+    }
+    
+    /// Dead letters owned by a principal
+    pub fn list_dead_letters(owner_principal: &str) -> Vec<DeadLetter> {
+        with_state(|state| {
+            state.dead_letters
+                .iter()
+                .filter(|d| d.owner_principal == owner_principal)
+                .cloned()
+                .collect()
+        })
+    }
+    
+    /// Re-attempt a dead-lettered delivery in the background; it is re-queued as a dead
+    /// letter if it fails again
+    pub fn redrive(owner_principal: &str, delivery_id: &str) -> Result<(), String> {
+        FeatureFlagService::require(FeatureFlagService::PUSH_DELIVERY)?;
+        let dead_letter = with_state_mut(|state| {
+            let index = state.dead_letters
+                .iter()
+                .position(|d| d.delivery_id == delivery_id && d.owner_principal == owner_principal)
+                .ok_or_else(|| "Dead letter not found or access denied".to_string())?;
+            Ok::<DeadLetter, String>(state.dead_letters.remove(index))
+        })?;
+        
+        // Expired queue messages go back to their agent's queue rather than to a callback
+        if let DeliveryPayload::ExpiredMessage(expired) = dead_letter.payload {
+            MessageExpiryService::requeue(expired);
+            return Ok(());
+        }
+        Self::schedule(dead_letter.delivery_id, owner_principal.to_string(), dead_letter.target, dead_letter.payload, 0, Duration::ZERO);
+        Ok(())
+    }
+}
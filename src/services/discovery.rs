@@ -0,0 +1,118 @@
+use crate::domain::*;
+use crate::services::{RoutingService, FeatureFlagService};
+use crate::services::feature_flags::FeatureFlagState;
+use crate::services::quota_manager::QuotaManager;
+use crate::services::routing_strategies::{RoutingStrategyInfo, RoutingStrategyRegistry};
+use crate::infra::guards::{DEFAULT_MAX_PAYLOAD, ENDPOINT_GUARDS};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Runtime description of what this coordinator supports, so SDKs and agent
+/// developers can adapt instead of hardcoding limits and feature sets.
+pub struct DiscoveryService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ProtocolVersion {
+    pub name: String,
+    pub version: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct DecodeProfileInfo {
+    pub profile: DecodeProfile,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub top_p: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct TierFanoutCap {
+    pub tier: String,
+    pub max_fanout: u32,
+}
+
+/// Payload limit for an endpoint that differs from the default
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct PayloadLimit {
+    pub method: String,
+    pub max_payload_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CoordinatorDescription {
+    pub interface: InterfaceVersion,
+    pub protocols: Vec<ProtocolVersion>,
+    pub routing_modes: Vec<RoutingMode>,
+    pub routing_strategies: Vec<RoutingStrategyInfo>,
+    /// Automatic checks on fanout responses; `peer_review` runs when a request opts in
+    pub verifiers: Vec<String>,
+    pub decode_profiles: Vec<DecodeProfileInfo>,
+    pub default_max_payload_bytes: u64,
+    pub payload_limits: Vec<PayloadLimit>,
+    pub tier_fanout_caps: Vec<TierFanoutCap>,
+    pub operational_mode: OperationalMode,
+    pub feature_flags: Vec<FeatureFlagState>,
+}
+
+impl DiscoveryService {
+    /// Wire contracts with agents and operators, bumped on incompatible changes
+    const PROTOCOLS: &'static [(&'static str, u32)] = &[
+        ("agent_inference", *RoutingService::INFER_PROTOCOL_VERSIONS.end()),
+        ("job_handoff", 1),
+        ("callback_delivery", 1),
+        ("coordination_message", 1),
+        ("state_snapshot", 1),
+    ];
+
+    pub fn describe(interface: InterfaceVersion, operational_mode: OperationalMode) -> CoordinatorDescription {
+        let default_max_payload_bytes = DEFAULT_MAX_PAYLOAD as u64;
+        CoordinatorDescription {
+            interface,
+            protocols: Self::PROTOCOLS
+                .iter()
+                .map(|(name, version)| ProtocolVersion { name: name.to_string(), version: *version })
+                .collect(),
+            routing_modes: vec![RoutingMode::Unicast, RoutingMode::Broadcast, RoutingMode::AgentSpawning, RoutingMode::Hedged, RoutingMode::Anycast, RoutingMode::Competition],
+            routing_strategies: RoutingStrategyRegistry::list(),
+            verifiers: RoutingService::VERIFIERS
+                .iter()
+                .chain(std::iter::once(&"peer_review"))
+                .map(|name| name.to_string())
+                .collect(),
+            decode_profiles: [DecodeProfile::Balanced, DecodeProfile::Creative, DecodeProfile::Precise]
+                .into_iter()
+                .map(|profile| {
+                    let (max_tokens, temperature, top_p) = profile.params();
+                    DecodeProfileInfo { profile, max_tokens, temperature, top_p }
+                })
+                .collect(),
+            default_max_payload_bytes,
+            payload_limits: ENDPOINT_GUARDS
+                .iter()
+                .filter(|guard| guard.max_payload_bytes as u64 != default_max_payload_bytes)
+                .map(|guard| PayloadLimit { method: guard.method.to_string(), max_payload_bytes: guard.max_payload_bytes as u64 })
+                .collect(),
+            tier_fanout_caps: QuotaManager::TIERS
+                .iter()
+                .map(|tier| TierFanoutCap { tier: tier.to_string(), max_fanout: QuotaManager::fanout_cap_for_tier(tier) as u32 })
+                .collect(),
+            operational_mode,
+            feature_flags: FeatureFlagService::list(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_description_lists_only_non_default_payload_limits() {
+        let interface = InterfaceVersion { package_version: "0.0.0".to_string(), interface_hash: String::new() };
+        let description = DiscoveryService::describe(interface, OperationalMode::Normal);
+        assert!(description.payload_limits.iter().any(|l| l.method == "route_request"));
+        assert!(description.payload_limits.iter().all(|l| l.max_payload_bytes != description.default_max_payload_bytes));
+        assert_eq!(description.tier_fanout_caps.len(), QuotaManager::TIERS.len());
+        assert_eq!(description.decode_profiles.len(), 3);
+    }
+}
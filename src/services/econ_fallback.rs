@@ -0,0 +1,216 @@
+use crate::domain::{QuotaRemaining, QuotaValidation};
+use crate::services::{with_state, with_state_mut, CoordinatorState, ServiceReportService};
+use crate::services::service_report::IncidentKind;
+use crate::infra::Metrics;
+use candid::CandidType;
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Degraded quota decisions for while the economics canister cannot be reached. Instead of
+/// failing every creation, users economics has already synced keep working from their
+/// cached quota: at most `CREATIONS_PER_DAY` agent creations per day, and never beyond the
+/// cached monthly allowance. Validations made this way say so in their reason, and quota
+/// status reports `degraded`. Creations granted locally are recorded in the cached usage
+/// so later syncs keep counting them, and each affected user is re-synced once economics
+/// answers again.
+pub struct EconFallbackService;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EconFallbackState {
+    /// When an economics call first failed; None while economics answers
+    pub unreachable_since: Option<u64>,
+    /// Day (since the epoch) that `creations_today` counts
+    pub day: u64,
+    /// Creations granted locally per principal on `day`
+    pub creations_today: HashMap<String, u32>,
+    /// Principals with locally granted creations not yet merged with a fresh economics sync
+    pub pending_reconciliation: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct EconFallbackStatus {
+    pub degraded: bool,
+    pub unreachable_since: Option<u64>,
+    pub pending_reconciliation: u32,
+}
+
+impl EconFallbackService {
+    const CREATIONS_PER_DAY: u32 = 2;
+    const DAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+    pub(crate) const DEGRADED_REASON: &'static str = "Degraded mode: economics canister unreachable, local fallback limits apply";
+
+    pub fn is_degraded() -> bool {
+        with_state(|state| state.econ_fallback.unreachable_since.is_some())
+    }
+
+    pub fn mark_unreachable() {
+        let now = time();
+        with_state_mut(|state| {
+            if state.econ_fallback.unreachable_since.is_none() {
+                state.econ_fallback.unreachable_since = Some(now);
+                ServiceReportService::open_incident_in(state, IncidentKind::EconomicsDegraded, None, now, None);
+                Metrics::increment_counter("econ_fallback_entered_total");
+            }
+        });
+    }
+
+    pub fn mark_reachable() {
+        let now = time();
+        with_state_mut(|state| {
+            if state.econ_fallback.unreachable_since.take().is_some() {
+                ServiceReportService::close_incident_in(state, &IncidentKind::EconomicsDegraded, now);
+                Metrics::increment_counter("econ_fallback_recovered_total");
+            }
+        });
+    }
+
+    pub fn status() -> EconFallbackStatus {
+        with_state(|state| EconFallbackStatus {
+            degraded: state.econ_fallback.unreachable_since.is_some(),
+            unreachable_since: state.econ_fallback.unreachable_since,
+            pending_reconciliation: state.econ_fallback.pending_reconciliation.len() as u32,
+        })
+    }
+
+    /// Creation check against the cached quota; users without one are refused
+    pub fn validate_agent_creation(user_principal: &str) -> Result<QuotaValidation, String> {
+        let now = time();
+        with_state(|state| Self::validate_creation_in(state, user_principal, now))
+    }
+
+    fn validate_creation_in(state: &CoordinatorState, user_principal: &str, now: u64) -> Result<QuotaValidation, String> {
+        let quota = state.user_quotas
+            .get(user_principal)
+            .ok_or_else(|| "Economics canister unreachable and no synced quota to fall back on; retry later".to_string())?;
+        let fallback = &state.econ_fallback;
+        let granted_today = if fallback.day == now / Self::DAY_NS {
+            fallback.creations_today.get(user_principal).copied().unwrap_or(0)
+        } else {
+            0
+        };
+        let monthly_remaining = quota.limits.monthly_agent_creations.saturating_sub(quota.current_usage.agents_created_this_month);
+        let agents_remaining = Self::CREATIONS_PER_DAY.saturating_sub(granted_today).min(monthly_remaining);
+        let reason = if agents_remaining == 0 {
+            format!("{}; no agent creations left under the fallback limit, retry once economics recovers", Self::DEGRADED_REASON)
+        } else {
+            Self::DEGRADED_REASON.to_string()
+        };
+        Ok(QuotaValidation {
+            allowed: agents_remaining > 0,
+            reason: Some(reason),
+            remaining_quota: Some(QuotaRemaining {
+                agents_remaining,
+                tokens_remaining: quota.limits.token_limit.saturating_sub(quota.current_usage.tokens_used_this_month),
+                inferences_remaining: 0,
+            }),
+        })
+    }
+
+    /// Token check against the cached quota; no overage beyond the cached limit
+    pub fn validate_token_usage(user_principal: &str, tokens: u64) -> Result<QuotaValidation, String> {
+        with_state(|state| {
+            let quota = state.user_quotas
+                .get(user_principal)
+                .ok_or_else(|| "Economics canister unreachable and no synced quota to fall back on; retry later".to_string())?;
+            let tokens_remaining = quota.limits.token_limit.saturating_sub(quota.current_usage.tokens_used_this_month);
+            Ok(QuotaValidation {
+                allowed: tokens <= tokens_remaining,
+                reason: Some(Self::DEGRADED_REASON.to_string()),
+                remaining_quota: Some(QuotaRemaining { agents_remaining: 0, tokens_remaining, inferences_remaining: 0 }),
+            })
+        })
+    }
+
+    /// Count creations granted while degraded against the cached quota and the daily limit
+    pub fn record_creations(user_principal: &str, count: u32) {
+        let now = time();
+        with_state_mut(|state| Self::record_creations_in(state, user_principal, count, now));
+        Metrics::increment_counter("econ_fallback_creations_total");
+    }
+
+    fn record_creations_in(state: &mut CoordinatorState, user_principal: &str, count: u32, now: u64) {
+        let fallback = &mut state.econ_fallback;
+        if fallback.day != now / Self::DAY_NS {
+            fallback.day = now / Self::DAY_NS;
+            fallback.creations_today.clear();
+        }
+        *fallback.creations_today.entry(user_principal.to_string()).or_insert(0) += count;
+        fallback.pending_reconciliation.insert(user_principal.to_string());
+        if let Some(quota) = state.user_quotas.get_mut(user_principal) {
+            let usage = &mut quota.current_usage;
+            usage.agents_created_this_month = usage.agents_created_this_month.saturating_add(count);
+            usage.offline_creations_this_month = usage.offline_creations_this_month.saturating_add(count);
+            quota.last_updated = now;
+        }
+    }
+
+    /// A fresh economics sync for the principal has absorbed its locally granted creations
+    pub fn mark_reconciled(user_principal: &str) {
+        if with_state_mut(|state| state.econ_fallback.pending_reconciliation.remove(user_principal)) {
+            Metrics::increment_counter("econ_fallback_reconciled_total");
+        }
+    }
+
+    /// Principals still waiting for reconciliation, at most `limit` of them
+    pub fn pending_reconciliation(limit: usize) -> Vec<String> {
+        with_state(|state| state.econ_fallback.pending_reconciliation.iter().take(limit).cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::quota_manager::{InferenceRate, QuotaLimits, QuotaUsage, UserQuota};
+
+    const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+    fn quota(created: u32, monthly: u32) -> UserQuota {
+        UserQuota {
+            principal_id: "alice".to_string(),
+            subscription_tier: "Basic".to_string(),
+            current_usage: QuotaUsage {
+                agents_created_this_month: created,
+                tokens_used_this_month: 0,
+                inferences_this_month: 0,
+                weighted_units_used_this_month: 0,
+                creations_refunded_this_month: 0,
+                offline_creations_this_month: 0,
+                creations_reserved: 0,
+                last_reset_date: 0,
+            },
+            limits: QuotaLimits {
+                max_agents: 10,
+                monthly_agent_creations: monthly,
+                token_limit: 1024,
+                inference_rate: InferenceRate::Standard,
+                monthly_weighted_units: 30,
+            },
+            last_updated: 0,
+        }
+    }
+
+    #[test]
+    fn test_fallback_grants_a_small_daily_allowance_without_overage() {
+        let mut state = CoordinatorState::default();
+        // Unknown users have nothing to fall back on
+        assert!(EconFallbackService::validate_creation_in(&state, "alice", DAY).is_err());
+
+        state.user_quotas.insert("alice".to_string(), quota(3, 15));
+        let validation = EconFallbackService::validate_creation_in(&state, "alice", DAY).unwrap();
+        assert!(validation.allowed);
+        assert!(validation.reason.unwrap().starts_with("Degraded mode"));
+
+        EconFallbackService::record_creations_in(&mut state, "alice", 2, DAY);
+        assert!(!EconFallbackService::validate_creation_in(&state, "alice", DAY).unwrap().allowed);
+        let usage = &state.user_quotas["alice"].current_usage;
+        assert_eq!((usage.agents_created_this_month, usage.offline_creations_this_month), (5, 2));
+        assert!(state.econ_fallback.pending_reconciliation.contains("alice"));
+
+        // The daily allowance renews, but never past the monthly allowance
+        assert!(EconFallbackService::validate_creation_in(&state, "alice", 2 * DAY).unwrap().allowed);
+        state.user_quotas.insert("alice".to_string(), quota(15, 15));
+        assert!(!EconFallbackService::validate_creation_in(&state, "alice", 2 * DAY).unwrap().allowed);
+    }
+}
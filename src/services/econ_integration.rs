@@ -1,5 +1,8 @@
 use crate::domain::*;
-use crate::services::{with_state_mut};
+use crate::services::{with_state, with_state_mut};
+use crate::services::quota_manager::QuotaManager;
+use crate::services::econ_fallback::EconFallbackService;
+use crate::infra::{Log, Metrics};
 use ic_cdk::api::{call, time};
 use candid::Principal;
 use serde::{Deserialize, Serialize};
@@ -33,6 +36,13 @@ pub struct TierConfig {
     pub features: Vec<String>,
 }
 
+/// Economics-published cost hint for work requiring one capability
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CapabilityPrice {
+    pub capability: String,
+    pub cost_per_task_usd: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub enum InferenceRate {
     Standard,
@@ -56,26 +66,74 @@ pub struct UsageMetrics {
     pub last_reset_date: u64,
 }
 
+/// Usage the coordinator meters itself for the current billing period
+#[derive(Debug, Default)]
+struct LocalUsage {
+    weighted_used: u32,
+    refunded: u32,
+    offline: u32,
+    reserved: u32,
+}
+
 impl EconIntegrationService {
+    /// Users reconciled per `reconcile_fallback_usage` call
+    const RECONCILE_BATCH: usize = 10;
+
     /// Get the economics canister ID
     fn get_econ_canister_id() -> Principal {
         // Use the actual economics canister ID from deployment
         Principal::from_text("tetse-piaaa-aaaao-qkeyq-cai").unwrap_or_else(|_| Principal::anonymous())
     }
 
+    /// Record the outcome of an economics canister call in dependency health and
+    /// enter or leave degraded mode accordingly
+    fn track_call<T>(method: &str, result: call::CallResult<T>) -> call::CallResult<T> {
+        let endpoint = format!("econ.{}", method);
+        match &result {
+            Ok(_) => {
+                Metrics::record_dependency_success(&endpoint);
+                EconFallbackService::mark_reachable();
+            }
+            Err((code, msg)) => {
+                Metrics::record_dependency_failure(&endpoint, &format!("{:?}: {}", code, msg));
+                EconFallbackService::mark_unreachable();
+            }
+        }
+        result
+    }
+
+    /// Weighted units, creation refunds, creations granted while economics was down and
+    /// creations reserved for spawns in flight are metered locally; keep them across a
+    /// sync unless the economics canister has started a new billing period. Refunds are
+    /// only reported: economics is charged for spawned agents alone, so its count never
+    /// includes them.
+    fn local_usage_for_period(user_principal: &str, econ_reset_date: u64) -> LocalUsage {
+        with_state(|state| {
+            state.user_quotas.get(user_principal)
+                .filter(|quota| quota.current_usage.last_reset_date == econ_reset_date)
+                .map(|quota| LocalUsage {
+                    weighted_used: quota.current_usage.weighted_units_used_this_month,
+                    refunded: quota.current_usage.creations_refunded_this_month,
+                    offline: quota.current_usage.offline_creations_this_month,
+                    reserved: quota.current_usage.creations_reserved,
+                })
+                .unwrap_or_default()
+        })
+    }
+
     /// Validate user subscription and quota for agent creation
     pub async fn validate_agent_creation_quota(user_principal: &str) -> Result<QuotaValidation, String> {
         let econ_canister_id = Self::get_econ_canister_id();
 
         // Make cross-canister call to validate quota
-        match call::call::<_, (Result<QuotaValidation, String>,)>(
+        match Self::track_call("validate_agent_creation_quota", call::call::<_, (Result<QuotaValidation, String>,)>(
             econ_canister_id,
             "validate_agent_creation_quota",
             (user_principal.to_string(),),
-        ).await {
+        ).await) {
             Ok((Ok(validation),)) => Ok(validation),
             Ok((Err(e),)) => Err(format!("Economics canister error: {}", e)),
-            Err(e) => Err(format!("Cross-canister call failed: {:?}", e)),
+            Err(_) => EconFallbackService::validate_agent_creation(user_principal),
         }
     }
 
@@ -84,14 +142,14 @@ impl EconIntegrationService {
         let econ_canister_id = Self::get_econ_canister_id();
 
         // Make cross-canister call to validate token usage
-        match call::call::<_, (Result<QuotaValidation, String>,)>(
+        match Self::track_call("validate_token_usage_quota", call::call::<_, (Result<QuotaValidation, String>,)>(
             econ_canister_id,
             "validate_token_usage_quota",
             (user_principal.to_string(), tokens),
-        ).await {
+        ).await) {
             Ok((Ok(validation),)) => Ok(validation),
             Ok((Err(e),)) => Err(format!("Economics canister error: {}", e)),
-            Err(e) => Err(format!("Cross-canister call failed: {:?}", e)),
+            Err(_) => EconFallbackService::validate_token_usage(user_principal, tokens),
         }
     }
 
@@ -100,11 +158,11 @@ impl EconIntegrationService {
         let econ_canister_id = Self::get_econ_canister_id();
         
         // Make cross-canister call to get subscription
-        match call::call::<_, (Option<UserSubscription>,)>(
+        match Self::track_call("get_user_subscription", call::call::<_, (Option<UserSubscription>,)>(
             econ_canister_id,
             "get_user_subscription",
             (Some(user_principal.to_string()),),
-        ).await {
+        ).await) {
             Ok((subscription,)) => Ok(subscription),
             Err(e) => Err(format!("Cross-canister call failed: {:?}", e)),
         }
@@ -115,24 +173,36 @@ impl EconIntegrationService {
         let econ_canister_id = Self::get_econ_canister_id();
         
         // Make cross-canister call to create/get free subscription
-        match call::call::<_, (Result<UserSubscription, String>,)>(
+        match Self::track_call("get_or_create_free_subscription", call::call::<_, (Result<UserSubscription, String>,)>(
             econ_canister_id,
             "get_or_create_free_subscription",
             (user_principal.to_string(),),
-        ).await {
+        ).await) {
             Ok((Ok(subscription),)) => Ok(subscription),
             Ok((Err(e),)) => Err(format!("Economics canister error: {}", e)),
             Err(e) => Err(format!("Cross-canister call failed: {:?}", e)),
         }
     }
 
-    /// Update local quota cache with economics data
+    /// Update local quota cache with economics data. While economics is unreachable the
+    /// cached quota, if there is one, keeps serving.
     pub async fn sync_user_quota_from_economics(user_principal: &str) -> Result<(), String> {
-        let subscription = Self::get_user_subscription(user_principal).await?;
+        let subscription = match Self::get_user_subscription(user_principal).await {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                let cached = with_state(|state| state.user_quotas.contains_key(user_principal));
+                if cached && EconFallbackService::is_degraded() {
+                    Metrics::increment_counter("econ_fallback_cached_quota_total");
+                    return Ok(());
+                }
+                return Err(e);
+            }
+        };
         
         match subscription {
             Some(sub) => {
                 // Convert economics subscription to local quota format
+                let local = Self::local_usage_for_period(user_principal, sub.current_usage.last_reset_date);
                 let local_quota = crate::services::quota_manager::UserQuota {
                     principal_id: user_principal.to_string(),
                     subscription_tier: sub.tier.name,
@@ -145,20 +215,24 @@ impl EconIntegrationService {
                             InferenceRate::Priority => crate::services::quota_manager::InferenceRate::Priority,
                             InferenceRate::Premium => crate::services::quota_manager::InferenceRate::Premium,
                         },
+                    monthly_weighted_units: sub.tier.monthly_agent_creations * AgentWeightClass::Standard.units(),
                     },
                     current_usage: crate::services::quota_manager::QuotaUsage {
-                        agents_created_this_month: sub.current_usage.agents_created_this_month,
+                        agents_created_this_month: sub.current_usage.agents_created_this_month.saturating_add(local.offline).saturating_add(local.reserved),
                         tokens_used_this_month: sub.current_usage.tokens_used_this_month,
                         inferences_this_month: sub.current_usage.inferences_this_month,
+                        weighted_units_used_this_month: local.weighted_used,
+                        creations_refunded_this_month: local.refunded,
+                        offline_creations_this_month: local.offline,
+                        creations_reserved: local.reserved,
                         last_reset_date: sub.current_usage.last_reset_date,
                     },
                     last_updated: time(),
                 };
                 
                 // Update local state
-                with_state_mut(|state| {
-                    state.user_quotas.insert(user_principal.to_string(), local_quota);
-                });
+                QuotaManager::store_synced_quota(local_quota);
+                EconFallbackService::mark_reconciled(user_principal);
                 
                 Ok(())
             },
@@ -171,6 +245,7 @@ impl EconIntegrationService {
                 
                 if let Some(sub) = subscription {
                     // Convert economics subscription to local quota format
+                    let local = Self::local_usage_for_period(user_principal, sub.current_usage.last_reset_date);
                     let local_quota = crate::services::quota_manager::UserQuota {
                         principal_id: user_principal.to_string(),
                         subscription_tier: sub.tier.name,
@@ -183,20 +258,24 @@ impl EconIntegrationService {
                                 InferenceRate::Priority => crate::services::quota_manager::InferenceRate::Priority,
                                 InferenceRate::Premium => crate::services::quota_manager::InferenceRate::Premium,
                             },
+                        monthly_weighted_units: sub.tier.monthly_agent_creations * AgentWeightClass::Standard.units(),
                         },
                         current_usage: crate::services::quota_manager::QuotaUsage {
-                            agents_created_this_month: sub.current_usage.agents_created_this_month,
+                            agents_created_this_month: sub.current_usage.agents_created_this_month.saturating_add(local.offline).saturating_add(local.reserved),
                             tokens_used_this_month: sub.current_usage.tokens_used_this_month,
                             inferences_this_month: sub.current_usage.inferences_this_month,
+                            weighted_units_used_this_month: local.weighted_used,
+                            creations_refunded_this_month: local.refunded,
+                            offline_creations_this_month: local.offline,
+                            creations_reserved: local.reserved,
                             last_reset_date: sub.current_usage.last_reset_date,
                         },
                         last_updated: time(),
                     };
                     
                     // Update local state
-                    with_state_mut(|state| {
-                        state.user_quotas.insert(user_principal.to_string(), local_quota);
-                    });
+                    QuotaManager::store_synced_quota(local_quota);
+                    EconFallbackService::mark_reconciled(user_principal);
                     
                     Ok(())
                 } else {
@@ -249,6 +328,25 @@ impl EconIntegrationService {
         Self::sync_user_quota_from_economics(user_principal).await
     }
 
+    /// Re-sync a batch of users who created agents while economics was down, merging
+    /// those creations with economics' current view; returns how many were reconciled
+    pub async fn reconcile_fallback_usage() -> u32 {
+        if EconFallbackService::is_degraded() {
+            return 0;
+        }
+        let mut reconciled = 0;
+        for principal in EconFallbackService::pending_reconciliation(Self::RECONCILE_BATCH) {
+            match Self::sync_user_quota_from_economics(&principal).await {
+                Ok(()) => reconciled += 1,
+                Err(e) => {
+                    Log::warn("econ", format!("Failed to reconcile fallback usage for {}: {}", principal, e));
+                    break;
+                }
+            }
+        }
+        reconciled
+    }
+
     /// Track token usage in economics canister
     pub async fn track_token_usage(user_principal: &str, tokens: u64) -> Result<(), String> {
         // This would typically update usage metrics in the economics canister
@@ -256,15 +354,50 @@ impl EconIntegrationService {
         Self::sync_user_quota_from_economics(user_principal).await
     }
 
+    /// Refresh capability cost hints from the economics canister, replacing the local table
+    pub async fn sync_capability_pricing() -> Result<u32, String> {
+        let econ_canister_id = Self::get_econ_canister_id();
+        
+        let prices = match Self::track_call("get_capability_pricing", call::call::<_, (Vec<CapabilityPrice>,)>(
+            econ_canister_id,
+            "get_capability_pricing",
+            (),
+        ).await) {
+            Ok((prices,)) => prices,
+            Err(e) => return Err(format!("Cross-canister call failed: {:?}", e)),
+        };
+        
+        let count = prices.len() as u32;
+        with_state_mut(|state| {
+            state.capability_pricing = prices
+                .into_iter()
+                .filter(|p| p.cost_per_task_usd.is_finite() && p.cost_per_task_usd >= 0.0)
+                .map(|p| (p.capability, p.cost_per_task_usd))
+                .collect();
+        });
+        Ok(count)
+    }
+    
+    pub fn get_capability_pricing() -> Vec<CapabilityPrice> {
+        with_state(|state| {
+            let mut prices: Vec<CapabilityPrice> = state.capability_pricing
+                .iter()
+                .map(|(capability, cost)| CapabilityPrice { capability: capability.clone(), cost_per_task_usd: *cost })
+                .collect();
+            prices.sort_by(|a, b| a.capability.cmp(&b.capability));
+            prices
+        })
+    }
+
     /// Get economics canister health
     pub async fn get_economics_health() -> Result<EconHealth, String> {
         let econ_canister_id = Self::get_econ_canister_id();
         
-        match call::call::<_, (EconHealth,)>(
+        match Self::track_call("health", call::call::<_, (EconHealth,)>(
             econ_canister_id,
             "health",
             (),
-        ).await {
+        ).await) {
             Ok((health,)) => Ok(health),
             Err(e) => Err(format!("Cross-canister call failed: {:?}", e)),
         }
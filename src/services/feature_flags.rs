@@ -0,0 +1,93 @@
+use crate::services::{with_state, with_state_mut, CoordinatorState};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Runtime switches for optional subsystems, so operators can turn one off during an
+/// incident without an upgrade. Every flag is on unless an admin has overridden it; each
+/// subsystem checks its flag where it is entered. Overrides live in the config and
+/// survive upgrades.
+pub struct FeatureFlagService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct FeatureFlagState {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    /// An admin has set the flag rather than it taking its default
+    pub overridden: bool,
+}
+
+impl FeatureFlagService {
+    pub const ENSEMBLE: &'static str = "ensemble";
+    pub const WARM_POOL: &'static str = "warm_pool";
+    pub const PUSH_DELIVERY: &'static str = "push_delivery";
+
+    const FLAGS: &'static [(&'static str, &'static str)] = &[
+        (Self::ENSEMBLE, "Send one request to several agents and aggregate their answers; when off, requests go to a single agent"),
+        (Self::WARM_POOL, "Hand out pre-spawned agents instead of spawning on demand"),
+        (Self::PUSH_DELIVERY, "Push results to callbacks; when off, deliveries are dead-lettered for redrive"),
+    ];
+
+    pub fn is_enabled(name: &str) -> bool {
+        with_state(|state| Self::enabled_in(state, name))
+    }
+
+    pub(crate) fn enabled_in(state: &CoordinatorState, name: &str) -> bool {
+        state.config.feature_flags.get(name).copied().unwrap_or(true)
+    }
+
+    /// Err naming the flag when it is off, for subsystems that refuse work outright
+    pub fn require(name: &str) -> Result<(), String> {
+        if Self::is_enabled(name) {
+            Ok(())
+        } else {
+            Err(format!("Feature disabled by operator: {}", name))
+        }
+    }
+
+    pub fn set(name: &str, enabled: bool) -> Result<(), String> {
+        with_state_mut(|state| Self::set_in(state, name, enabled))
+    }
+
+    fn set_in(state: &mut CoordinatorState, name: &str, enabled: bool) -> Result<(), String> {
+        if !Self::FLAGS.iter().any(|(flag, _)| *flag == name) {
+            return Err(format!("Unknown feature flag: {}", name));
+        }
+        state.config.feature_flags.insert(name.to_string(), enabled);
+        Ok(())
+    }
+
+    pub fn list() -> Vec<FeatureFlagState> {
+        with_state(Self::list_in)
+    }
+
+    fn list_in(state: &CoordinatorState) -> Vec<FeatureFlagState> {
+        Self::FLAGS
+            .iter()
+            .map(|(name, description)| FeatureFlagState {
+                name: name.to_string(),
+                description: description.to_string(),
+                enabled: Self::enabled_in(state, name),
+                overridden: state.config.feature_flags.contains_key(*name),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_default_on_and_only_known_flags_can_be_set() {
+        let mut state = CoordinatorState::default();
+        assert!(FeatureFlagService::list_in(&state).iter().all(|flag| flag.enabled && !flag.overridden));
+        assert!(FeatureFlagService::set_in(&mut state, "shadow_routing", false).is_err());
+
+        FeatureFlagService::set_in(&mut state, FeatureFlagService::WARM_POOL, false).unwrap();
+        assert!(!FeatureFlagService::enabled_in(&state, FeatureFlagService::WARM_POOL));
+        assert!(FeatureFlagService::enabled_in(&state, FeatureFlagService::ENSEMBLE));
+        let warm_pool = FeatureFlagService::list_in(&state).into_iter().find(|flag| flag.name == "warm_pool").unwrap();
+        assert!(!warm_pool.enabled && warm_pool.overridden);
+    }
+}
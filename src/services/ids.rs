@@ -0,0 +1,122 @@
+use crate::services::{with_state_mut, CoordinatorState};
+use ic_cdk::api::time;
+use sha2::{Digest, Sha256};
+
+/// Generates request, task, session and network IDs.
+///
+/// IDs have the form `<prefix>_<sequence>_<digest>`: the sequence comes from a
+/// monotonic counter so two IDs minted in the same round never collide, and the
+/// digest is salted with the requesting principal so IDs are not guessable and
+/// do not expose the time they were created.
+pub struct IdGenerator;
+
+/// How an existing ID was minted
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdScheme {
+    /// `<prefix>_<sequence>_<digest>`
+    Stable { sequence: u64 },
+    /// `<prefix>_<nanos>` from before the counter existed
+    Legacy { timestamp: u64 },
+}
+
+const DIGEST_BYTES: usize = 6;
+
+impl IdGenerator {
+    pub fn next(prefix: &str, principal: &str) -> String {
+        with_state_mut(|state| Self::next_in(state, prefix, principal))
+    }
+
+    /// Same as `next`, for callers already holding the state
+    pub fn next_in(state: &mut CoordinatorState, prefix: &str, principal: &str) -> String {
+        Self::next_at(state, prefix, principal, time())
+    }
+
+    /// Same as `next_in`, for callers that already read the clock
+    pub fn next_at(state: &mut CoordinatorState, prefix: &str, principal: &str, now: u64) -> String {
+        state.id_sequence += 1;
+        Self::format(prefix, state.id_sequence, principal, now)
+    }
+
+    fn format(prefix: &str, sequence: u64, principal: &str, now: u64) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(principal.as_bytes());
+        hasher.update(sequence.to_be_bytes());
+        hasher.update(now.to_be_bytes());
+        let digest: String = hasher.finalize()[..DIGEST_BYTES]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("{}_{}_{}", prefix, sequence, digest)
+    }
+
+    /// Prefix and scheme of an ID minted by either the current or the legacy scheme
+    pub fn parse(id: &str) -> Option<(&str, IdScheme)> {
+        let mut parts = id.rsplitn(3, '_');
+        let last = parts.next()?;
+        let middle = parts.next()?;
+        if let Some(prefix) = parts.next() {
+            if last.len() == DIGEST_BYTES * 2 && last.chars().all(|c| c.is_ascii_hexdigit()) {
+                if let Ok(sequence) = middle.parse() {
+                    return Some((prefix, IdScheme::Stable { sequence }));
+                }
+            }
+        }
+        let (prefix, timestamp) = id.rsplit_once('_')?;
+        timestamp.parse().ok().map(|timestamp| (prefix, IdScheme::Legacy { timestamp }))
+    }
+
+    /// Migration shim: advance the counter past every stable ID already held in
+    /// `state` so restored or imported records can never be re-issued. Legacy IDs
+    /// stay valid as-is since lookups treat IDs as opaque keys.
+    pub fn observe_existing(state: &mut CoordinatorState) {
+        let sessions = state.coordination_sessions.iter().flat_map(|s| s.keys());
+        let ids = state.instruction_requests.keys()
+            .chain(state.task_ledger.keys())
+            .chain(sessions)
+            .chain(state.programs.keys())
+            .chain(state.instruction_batches.keys())
+            .chain(state.cancellations.keys());
+        let highest = Self::highest_sequence(ids);
+        state.id_sequence = state.id_sequence.max(highest);
+    }
+
+    fn highest_sequence<'a>(ids: impl Iterator<Item = &'a String>) -> u64 {
+        ids.filter_map(|id| match Self::parse(id) {
+                Some((_, IdScheme::Stable { sequence })) => Some(sequence),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_unique_within_same_instant_and_parseable() {
+        let a = IdGenerator::format("task", 1, "user-a", 42);
+        let b = IdGenerator::format("task", 2, "user-a", 42);
+        let c = IdGenerator::format("task", 1, "user-b", 42);
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+
+        assert_eq!(IdGenerator::parse(&b), Some(("task", IdScheme::Stable { sequence: 2 })));
+        assert_eq!(
+            IdGenerator::parse("coord_1700000000000000000"),
+            Some(("coord", IdScheme::Legacy { timestamp: 1700000000000000000 }))
+        );
+        assert_eq!(IdGenerator::parse("no-id"), None);
+    }
+
+    #[test]
+    fn test_highest_sequence_ignores_legacy_ids() {
+        let ids = [
+            IdGenerator::format("program", 7, "u", 0),
+            "program_1700000000000000000".to_string(),
+            IdGenerator::format("program", 3, "u", 0),
+        ];
+        assert_eq!(IdGenerator::highest_sequence(ids.iter()), 7);
+    }
+}
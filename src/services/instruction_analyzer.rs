@@ -1,6 +1,8 @@
 use crate::domain::*;
-use crate::services::{with_state, with_state_mut};
+use crate::services::{with_state, with_state_mut, CoordinatorState, IdGenerator, SpecializationService};
 use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 /// Instruction analysis service for OHMS 2.0 agent spawning
 pub struct InstructionAnalyzerService;
@@ -25,6 +27,20 @@ pub enum ComplexityLevel {
     Enterprise, // Multi-team coordination
 }
 
+/// An analysis kept under its request_id so the plan shown is the plan spawned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredAnalysis {
+    pub owner: String,
+    pub instructions: String,
+    pub analysis: InstructionAnalysisResult,
+    pub created_at: u64,
+    /// Instruction request that executed this plan
+    pub spawned_request_id: Option<String>,
+    /// A spawn of this plan is in flight
+    #[serde(default)]
+    pub claimed: bool,
+}
+
 /// Capability patterns for instruction parsing
 #[derive(Debug, Clone)]
 pub struct CapabilityPattern {
@@ -37,31 +53,236 @@ pub struct CapabilityPattern {
 impl InstructionAnalyzerService {
     /// Analyze natural language instructions and determine agent requirements
     pub fn analyze_instructions(instructions: &str, user_principal: &str) -> Result<InstructionAnalysisResult, String> {
-        let request_id = format!("analysis_{}", time());
+        let request_id = IdGenerator::next("analysis", user_principal);
         
         // Parse the instructions
         let parsed = Self::parse_instructions(instructions)?;
         
-        // Check user quotas
-        let quota_check = Self::check_user_quotas(user_principal, parsed.agent_count)?;
-        
         // Generate agent specifications
         let suggested_agents = Self::generate_agent_specs(&parsed)?;
         
+        // Check user quotas against both agent count and weighted cost
+        let weighted_cost = Self::weighted_cost(&suggested_agents);
+        let quota_check = Self::check_user_quotas(user_principal, parsed.agent_count, weighted_cost)?;
+        
         // Create coordination plan
         let coordination_plan = Self::create_coordination_plan(&parsed, &suggested_agents)?;
         
+        // Offer a reduced plan instead of failing outright when quota is short
+        let downscaled_plan = if quota_check.quota_available {
+            None
+        } else {
+            Self::downscale_plan(&parsed, &suggested_agents, Self::agent_headroom(user_principal), quota_check.remaining_weighted_units)?
+        };
+        
+        let role_costs = Self::estimate_role_costs(&suggested_agents);
+        let estimated_cost_per_task_usd = role_costs.iter().map(|r| r.cost_per_task_usd).sum();
+        
         let result = InstructionAnalysisResult {
             request_id,
             parsed_requirements: parsed.required_capabilities,
             suggested_agents,
             coordination_plan,
             quota_check,
+            weighted_cost,
+            downscaled_plan,
+            state_version: Self::state_version(user_principal),
+            role_costs,
+            estimated_cost_per_task_usd,
         };
         
         Ok(result)
     }
     
+    /// Words that name something to produce or an action that produces it
+    const DELIVERABLE_WORDS: &'static [&'static str] = &[
+        "build", "create", "write", "implement", "develop", "design", "produce", "generate", "draft",
+        "analyze", "analyse", "review", "test", "fix", "deploy", "research", "summarize", "translate",
+        "plan", "report", "document", "documentation", "app", "application", "website", "api", "article",
+        "post", "campaign", "dashboard", "prototype", "script", "service", "study", "audit",
+    ];
+    /// Words that leave the scope open
+    const VAGUE_WORDS: &'static [&'static str] = &["something", "stuff", "things", "anything", "whatever", "etc", "somehow"];
+    /// Pairs of constraints that pull against each other
+    const CONFLICTS: &'static [(&'static [&'static str], &'static [&'static str], &'static str)] = &[
+        (&["quick", "quickly", "fast", "asap", "immediately"], &["comprehensive", "exhaustive", "thorough", "in-depth"], "speed and exhaustiveness"),
+        (&["brief", "short", "concise"], &["detailed", "lengthy", "in-depth", "exhaustive"], "length"),
+        (&["single", "one", "alone", "solo"], &["team", "multiple", "several"], "team size"),
+        (&["cheap", "cheapest", "minimal", "budget"], &["enterprise", "premium", "maximum"], "cost"),
+    ];
+    const MIN_WORDS: usize = 4;
+
+    /// Check instructions for problems that would produce a poor plan, without touching quota
+    pub fn validate_instructions(instructions: &str) -> InstructionValidation {
+        let lower = instructions.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !(c.is_alphanumeric() || c == '-'))
+            .filter(|w| !w.is_empty())
+            .collect();
+        let has_any = |candidates: &[&str]| candidates.iter().any(|c| words.contains(c));
+
+        let mut issues = Vec::new();
+        let mut questions = Vec::new();
+
+        if !has_any(Self::DELIVERABLE_WORDS) {
+            issues.push(InstructionIssue {
+                kind: InstructionIssueKind::MissingDeliverable,
+                message: "The instructions do not say what the agents should produce".to_string(),
+            });
+            questions.push("What should the agents deliver, e.g. code, a report, an article or a test suite?".to_string());
+        }
+
+        let vague: Vec<&str> = Self::VAGUE_WORDS.iter().copied().filter(|w| words.contains(w)).collect();
+        if words.len() < Self::MIN_WORDS || !vague.is_empty() {
+            let message = if vague.is_empty() {
+                format!("Only {} word(s); there is not enough to size a team", words.len())
+            } else {
+                format!("Open-ended wording ({}) leaves the scope undefined", vague.join(", "))
+            };
+            issues.push(InstructionIssue { kind: InstructionIssueKind::AmbiguousScope, message });
+            questions.push("What is in scope and what is out, and how will you judge the result finished?".to_string());
+        }
+
+        for (one, other, subject) in Self::CONFLICTS {
+            let first = one.iter().find(|w| words.contains(*w));
+            let second = other.iter().find(|w| words.contains(*w));
+            if let (Some(first), Some(second)) = (first, second) {
+                issues.push(InstructionIssue {
+                    kind: InstructionIssueKind::ConflictingConstraints,
+                    message: format!("\"{}\" and \"{}\" conflict on {}", first, second, subject),
+                });
+                questions.push(format!("Which matters more for {}: \"{}\" or \"{}\"?", subject, first, second));
+            }
+        }
+
+        let patterns = Self::get_capability_patterns();
+        if !patterns.iter().any(|pattern| Self::matches_pattern(&lower, &pattern.keywords)) {
+            let specializations: Vec<&str> = patterns.iter().map(|p| p.specialization.as_str()).collect();
+            issues.push(InstructionIssue {
+                kind: InstructionIssueKind::NoMatchingSpecialization,
+                message: "No specialization matched; the plan would only contain generalist agents".to_string(),
+            });
+            questions.push(format!("Which kind of specialist does this need: {}?", specializations.join(", ")));
+        }
+
+        InstructionValidation { ready: issues.is_empty(), issues, clarification_questions: questions }
+    }
+
+    /// Unspawned analyses older than this are dropped
+    const ANALYSIS_TTL_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+    /// Keep `analysis` for `get_instruction_analysis` and `spawn_from_analysis`
+    pub fn store_analysis(owner: &str, instructions: &str, analysis: &InstructionAnalysisResult, spawned_request_id: Option<&str>) {
+        let now = time();
+        with_state_mut(|state| {
+            if let Some(request) = spawned_request_id.and_then(|id| state.instruction_requests.get_mut(id)) {
+                request.analysis_id = Some(analysis.request_id.clone());
+            }
+            Self::prune_analyses(state, now);
+            state.instruction_analyses.insert(analysis.request_id.clone(), StoredAnalysis {
+                owner: owner.to_string(),
+                // Once spawned, the instruction request holds them, redacted if the owner asks
+                instructions: if spawned_request_id.is_some() { String::new() } else { instructions.to_string() },
+                analysis: analysis.clone(),
+                created_at: now,
+                spawned_request_id: spawned_request_id.map(str::to_string),
+                claimed: false,
+            });
+        });
+    }
+
+    /// Drop expired unspawned analyses and those whose instruction request is gone
+    fn prune_analyses(state: &mut CoordinatorState, now: u64) {
+        let requests = &state.instruction_requests;
+        state.instruction_analyses.retain(|_, stored| match &stored.spawned_request_id {
+            Some(request_id) => requests.contains_key(request_id),
+            None => now.saturating_sub(stored.created_at) < Self::ANALYSIS_TTL_NS,
+        });
+    }
+
+    /// Stored analysis by its own id or by the instruction request that executed it
+    pub fn get_stored_analysis(request_id: &str) -> Option<InstructionAnalysisResult> {
+        with_state(|state| {
+            let analysis_id = state.instruction_requests
+                .get(request_id)
+                .and_then(|request| request.analysis_id.as_deref())
+                .unwrap_or(request_id);
+            state.instruction_analyses.get(analysis_id).map(|stored| stored.analysis.clone())
+        })
+    }
+
+    /// Claim the caller's unspawned, unexpired analysis `analysis_id` for spawning. The
+    /// claim is taken before any call out, so a concurrent spawn of the same plan is
+    /// refused; `release_analysis` gives it back if the spawn fails.
+    pub fn claim_analysis(owner: &str, analysis_id: &str) -> Result<StoredAnalysis, String> {
+        let now = time();
+        with_state_mut(|state| Self::claim_analysis_in(state, owner, analysis_id, now))
+    }
+
+    fn claim_analysis_in(state: &mut CoordinatorState, owner: &str, analysis_id: &str, now: u64) -> Result<StoredAnalysis, String> {
+        let stored = state.instruction_analyses
+            .get_mut(analysis_id)
+            .filter(|stored| stored.owner == owner)
+            .ok_or_else(|| format!("Analysis not found: {}", analysis_id))?;
+        if let Some(request_id) = &stored.spawned_request_id {
+            return Err(format!("Analysis {} was already spawned as {}", analysis_id, request_id));
+        }
+        if stored.claimed {
+            return Err(format!("Analysis {} is already being spawned", analysis_id));
+        }
+        if now.saturating_sub(stored.created_at) >= Self::ANALYSIS_TTL_NS {
+            return Err(format!("Analysis {} has expired; analyze the instructions again", analysis_id));
+        }
+        stored.claimed = true;
+        Ok(stored.clone())
+    }
+
+    /// Give back a claim whose spawn failed
+    pub fn release_analysis(analysis_id: &str) {
+        with_state_mut(|state| {
+            if let Some(stored) = state.instruction_analyses.get_mut(analysis_id) {
+                stored.claimed = false;
+            }
+        });
+    }
+
+    /// Fingerprint of everything an analysis depends on for this user: subscription tier,
+    /// quota limits and usage, and the agents they own. Timestamps are excluded so that
+    /// a sync which changes nothing does not invalidate a pending analysis.
+    pub fn state_version(user_principal: &str) -> u64 {
+        let mut hasher = Sha256::new();
+        with_state(|state| {
+            if let Some(quota) = state.user_quotas.get(user_principal) {
+                let usage = &quota.current_usage;
+                let limits = &quota.limits;
+                hasher.update(quota.subscription_tier.as_bytes());
+                for value in [
+                    limits.max_agents,
+                    limits.monthly_agent_creations,
+                    limits.monthly_weighted_units,
+                    usage.agents_created_this_month,
+                    usage.weighted_units_used_this_month,
+                    usage.creations_refunded_this_month,
+                ] {
+                    hasher.update(value.to_le_bytes());
+                }
+            }
+            
+            let mut owned: Vec<&str> = state.agents
+                .values()
+                .filter(|agent| agent.agent_principal == user_principal)
+                .map(|agent| agent.agent_id.as_str())
+                .collect();
+            owned.sort_unstable();
+            for agent_id in owned {
+                hasher.update(agent_id.as_bytes());
+            }
+        });
+        
+        let digest = hasher.finalize();
+        u64::from_le_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+    }
+    
     /// Parse natural language instructions into structured requirements
     fn parse_instructions(instructions: &str) -> Result<ParsedRequirements, String> {
         let instructions_lower = instructions.to_lowercase();
@@ -216,7 +437,7 @@ impl InstructionAnalyzerService {
     }
     
     /// Check user quotas before agent creation
-    fn check_user_quotas(user_principal: &str, requested_agents: u32) -> Result<QuotaCheckResult, String> {
+    fn check_user_quotas(user_principal: &str, requested_agents: u32, weighted_cost: u32) -> Result<QuotaCheckResult, String> {
         use crate::services::quota_manager::{QuotaManager, UserQuota, QuotaLimits, InferenceRate};
         
         // Get or create user quota
@@ -232,11 +453,16 @@ impl InstructionAnalyzerService {
                     monthly_agent_creations: 25,
                     token_limit: 4096,
                     inference_rate: InferenceRate::Priority,
+                    monthly_weighted_units: 50,
                 },
                 current_usage: crate::services::quota_manager::QuotaUsage {
                     agents_created_this_month: 0,
                     tokens_used_this_month: 0,
                     inferences_this_month: 0,
+                    weighted_units_used_this_month: 0,
+                    creations_refunded_this_month: 0,
+                    offline_creations_this_month: 0,
+                    creations_reserved: 0,
                     last_reset_date: time(),
                 },
                 last_updated: time(),
@@ -246,8 +472,10 @@ impl InstructionAnalyzerService {
         // Check if user has enough quota
         let current_agents = user_quota.current_usage.agents_created_this_month;
         let remaining_agents = user_quota.limits.max_agents.saturating_sub(current_agents);
+        let remaining_weighted_units = QuotaManager::remaining_weighted_units(&user_quota);
         let quota_available = remaining_agents >= requested_agents && 
-                             current_agents < user_quota.limits.monthly_agent_creations;
+                             current_agents < user_quota.limits.monthly_agent_creations &&
+                             remaining_weighted_units >= weighted_cost;
         
         // Store updated quota
         with_state_mut(|state| {
@@ -259,6 +487,8 @@ impl InstructionAnalyzerService {
             remaining_agents,
             monthly_limit: user_quota.limits.monthly_agent_creations,
             tier: user_quota.subscription_tier,
+            remaining_weighted_units,
+            degraded: crate::services::EconFallbackService::is_degraded(),
         })
     }
     
@@ -278,6 +508,7 @@ impl InstructionAnalyzerService {
             specs.push(AgentSpec {
                 agent_type: specialization.clone(),
                 required_capabilities: capabilities,
+                weight_class: AgentWeightClass::from_models(&models),
                 model_requirements: models,
                 specialization: specialization.clone(),
             });
@@ -289,41 +520,139 @@ impl InstructionAnalyzerService {
                 agent_type: format!("Generalist Agent {}", specs.len() + 1),
                 required_capabilities: vec!["general_assistance".to_string()],
                 model_requirements: vec!["llama".to_string()],
-                specialization: "General Assistant".to_string(),
+                specialization: SpecializationService::GENERALIST.to_string(),
+                weight_class: AgentWeightClass::Standard,
             });
         }
         
         Ok(specs)
     }
     
-    /// Get capabilities for a specific specialization
+    /// Total weighted quota units a plan will consume
+    fn weighted_cost(specs: &[AgentSpec]) -> u32 {
+        specs.iter().map(|spec| spec.weight_class.units()).sum()
+    }
+    
+    /// Get capabilities for a specific specialization; unknown roles get the generalist's
     fn get_capabilities_for_specialization(specialization: &str) -> Vec<String> {
-        match specialization {
-            "Software Developer" => vec!["coding", "software_development", "programming", "debugging"],
-            "Test Engineer" => vec!["testing", "quality_assurance", "verification", "automation"],
-            "Code Reviewer" => vec!["code_review", "quality_assurance", "best_practices", "security"],
-            "Content Creator" => vec!["content_creation", "writing", "documentation", "editing"],
-            "Marketing Specialist" => vec!["marketing", "social_media", "campaign_management", "analytics"],
-            "Data Analyst" => vec!["data_analysis", "analytics", "reporting", "visualization"],
-            "Research Analyst" => vec!["research", "investigation", "analysis", "synthesis"],
-            _ => vec!["general_assistance"],
-        }.into_iter().map(|s| s.to_string()).collect()
+        SpecializationService::resolve(specialization)
+            .or_else(|| SpecializationService::resolve(SpecializationService::GENERALIST))
+            .map(|role| role.capabilities)
+            .unwrap_or_default()
     }
     
     /// Get model suggestions for a specific specialization
     fn get_models_for_specialization(specialization: &str) -> Vec<String> {
-        match specialization {
-            "Software Developer" | "Test Engineer" | "Code Reviewer" => {
-                vec!["code-llama", "starcoder", "wizardcoder"]
-            },
-            "Content Creator" | "Marketing Specialist" => {
-                vec!["llama", "mistral", "gemma"]
-            },
-            "Data Analyst" | "Research Analyst" => {
-                vec!["llama", "mistral", "gemma"]
-            },
-            _ => vec!["llama"],
-        }.into_iter().map(|s| s.to_string()).collect()
+        SpecializationService::resolve(specialization)
+            .map(|role| role.models)
+            .filter(|models| !models.is_empty())
+            .unwrap_or_else(|| vec!["llama".to_string()])
+    }
+    
+    /// Agents the user may still create: bounded by both live agents and monthly creations
+    fn agent_headroom(user_principal: &str) -> u32 {
+        with_state(|state| {
+            state.user_quotas.get(user_principal).map(|quota| {
+                let created = quota.current_usage.agents_created_this_month;
+                quota.limits.max_agents.saturating_sub(created)
+                    .min(quota.limits.monthly_agent_creations.saturating_sub(created))
+            })
+        }).unwrap_or(0)
+    }
+    
+    /// Fit a plan into `max_agents` and `max_units`: generalist roles are dropped first,
+    /// then adjacent specialists are merged until the plan fits
+    fn downscale_plan(
+        parsed: &ParsedRequirements,
+        specs: &[AgentSpec],
+        max_agents: u32,
+        max_units: u32,
+    ) -> Result<Option<DownscaledPlan>, String> {
+        if max_agents == 0 || specs.is_empty() {
+            return Ok(None);
+        }
+        
+        let (mut kept, generalists): (Vec<AgentSpec>, Vec<AgentSpec>) = specs
+            .iter()
+            .cloned()
+            .partition(|spec| spec.specialization != SpecializationService::GENERALIST);
+        let mut dropped = generalists;
+        if kept.is_empty() {
+            kept.push(dropped.remove(0));
+        }
+        
+        for slots in (1..=kept.len().min(max_agents as usize)).rev() {
+            let (merged, merged_roles) = Self::merge_specs(&kept, slots);
+            let weighted_cost = Self::weighted_cost(&merged);
+            if weighted_cost > max_units {
+                continue;
+            }
+            
+            let coordination_plan = Self::create_coordination_plan(parsed, &merged)?;
+            return Ok(Some(DownscaledPlan {
+                suggested_agents: merged,
+                merged_roles,
+                dropped_roles: dropped.iter().map(|spec| spec.agent_type.clone()).collect(),
+                coordination_plan,
+                weighted_cost,
+            }));
+        }
+        
+        Ok(None)
+    }
+    
+    /// Merge specs into at most `slots` agents, keeping neighbouring roles together
+    fn merge_specs(specs: &[AgentSpec], slots: usize) -> (Vec<AgentSpec>, Vec<String>) {
+        let group_size = specs.len().div_ceil(slots);
+        let mut merged = Vec::new();
+        let mut merged_roles = Vec::new();
+        
+        for group in specs.chunks(group_size) {
+            if group.len() == 1 {
+                merged.push(group[0].clone());
+                continue;
+            }
+            
+            let agent_type = group.iter().map(|s| s.agent_type.as_str()).collect::<Vec<_>>().join(" + ");
+            let mut capabilities: Vec<String> = Vec::new();
+            for capability in group.iter().flat_map(|s| s.required_capabilities.iter()) {
+                if !capabilities.contains(capability) {
+                    capabilities.push(capability.clone());
+                }
+            }
+            let models = group[0].model_requirements.clone();
+            
+            merged_roles.push(agent_type.clone());
+            merged.push(AgentSpec {
+                agent_type: agent_type.clone(),
+                required_capabilities: capabilities,
+                weight_class: AgentWeightClass::from_models(&models),
+                model_requirements: models,
+                specialization: agent_type,
+            });
+        }
+        
+        (merged, merged_roles)
+    }
+    
+    /// Price each role as the sum of its capabilities' published cost hints
+    fn estimate_role_costs(agents: &[AgentSpec]) -> Vec<RoleCostEstimate> {
+        with_state(|state| {
+            agents
+                .iter()
+                .map(|agent| {
+                    let mut cost_per_task_usd = 0.0;
+                    let mut unpriced_capabilities = Vec::new();
+                    for capability in &agent.required_capabilities {
+                        match state.capability_pricing.get(capability) {
+                            Some(cost) => cost_per_task_usd += cost,
+                            None => unpriced_capabilities.push(capability.clone()),
+                        }
+                    }
+                    RoleCostEstimate { agent_type: agent.agent_type.clone(), cost_per_task_usd, unpriced_capabilities }
+                })
+                .collect()
+        })
     }
     
     /// Create coordination plan for multiple agents
@@ -353,6 +682,15 @@ impl InstructionAnalyzerService {
             plan.push_str(&format!("  * {}: {}\n", agent.agent_type, agent.specialization));
         }
         
+        let role_costs = Self::estimate_role_costs(agents);
+        if role_costs.iter().any(|r| r.cost_per_task_usd > 0.0) {
+            let total: f64 = role_costs.iter().map(|r| r.cost_per_task_usd).sum();
+            plan.push_str(&format!("- Estimated Cost: ~${:.4} per task\n", total));
+            for role in &role_costs {
+                plan.push_str(&format!("  * {}: ~${:.4}\n", role.agent_type, role.cost_per_task_usd));
+            }
+        }
+        
         Ok(plan)
     }
 }
@@ -393,6 +731,23 @@ mod tests {
         assert!(!parsed.coordination_needs.is_empty());
     }
 
+    #[test]
+    fn test_validate_instructions_feedback() {
+        let ready = InstructionAnalyzerService::validate_instructions("Build a REST API in Rust and write integration tests for it");
+        assert!(ready.ready, "{:?}", ready.issues);
+
+        let vague = InstructionAnalyzerService::validate_instructions("help with stuff");
+        let kinds: Vec<_> = vague.issues.iter().map(|i| i.kind).collect();
+        assert!(kinds.contains(&InstructionIssueKind::MissingDeliverable));
+        assert!(kinds.contains(&InstructionIssueKind::AmbiguousScope));
+        assert!(kinds.contains(&InstructionIssueKind::NoMatchingSpecialization));
+        assert_eq!(vague.clarification_questions.len(), vague.issues.len());
+
+        let conflicting = InstructionAnalyzerService::validate_instructions("Quickly write a comprehensive research report on battery chemistry");
+        assert_eq!(conflicting.issues.len(), 1);
+        assert_eq!(conflicting.issues[0].kind, InstructionIssueKind::ConflictingConstraints);
+    }
+
     #[test]
     fn test_generate_agent_specs() {
         let parsed = ParsedRequirements {
@@ -409,4 +764,106 @@ mod tests {
         assert_eq!(specs[0].agent_type, "Software Developer");
         assert_eq!(specs[1].agent_type, "Test Engineer");
     }
+
+    #[test]
+    fn test_downscale_merges_specialists_and_drops_generalists() {
+        let parsed = ParsedRequirements {
+            agent_count: 4,
+            required_capabilities: vec!["coding".to_string(), "testing".to_string()],
+            model_requirements: vec!["llama".to_string()],
+            specializations: vec![
+                "Software Developer".to_string(),
+                "Test Engineer".to_string(),
+                "Code Reviewer".to_string(),
+            ],
+            coordination_needs: vec![],
+            complexity_level: ComplexityLevel::Complex,
+        };
+        let specs = InstructionAnalyzerService::generate_agent_specs(&parsed).unwrap();
+        assert_eq!(specs.len(), 4);
+
+        let plan = InstructionAnalyzerService::downscale_plan(&parsed, &specs, 2, 100).unwrap().unwrap();
+        assert_eq!(plan.suggested_agents.len(), 2);
+        assert_eq!(plan.suggested_agents[0].agent_type, "Software Developer + Test Engineer");
+        assert_eq!(plan.merged_roles, vec!["Software Developer + Test Engineer".to_string()]);
+        assert_eq!(plan.dropped_roles, vec!["Generalist Agent 4".to_string()]);
+
+        assert!(InstructionAnalyzerService::downscale_plan(&parsed, &specs, 0, 100).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_estimate_role_costs_uses_capability_pricing() {
+        with_state_mut(|state| {
+            state.capability_pricing.insert("coding".to_string(), 0.02);
+            state.capability_pricing.insert("testing".to_string(), 0.01);
+        });
+        let spec = AgentSpec {
+            agent_type: "Developer".to_string(),
+            required_capabilities: vec!["coding".to_string(), "testing".to_string(), "design".to_string()],
+            model_requirements: vec!["llama".to_string()],
+            specialization: "Developer".to_string(),
+            weight_class: AgentWeightClass::Standard,
+        };
+
+        let costs = InstructionAnalyzerService::estimate_role_costs(&[spec]);
+        assert!((costs[0].cost_per_task_usd - 0.03).abs() < 1e-9);
+        assert_eq!(costs[0].unpriced_capabilities, vec!["design".to_string()]);
+    }
+
+    #[test]
+    fn test_prune_keeps_fresh_and_executed_analyses() {
+        let hour = 60 * 60 * 1_000_000_000;
+        let stored = |analysis_id: &str, created_at: u64, spawned_request_id: Option<&str>| StoredAnalysis {
+            owner: "user".to_string(),
+            instructions: String::new(),
+            analysis: InstructionAnalysisResult {
+                request_id: analysis_id.to_string(),
+                parsed_requirements: vec![],
+                suggested_agents: vec![],
+                coordination_plan: String::new(),
+                quota_check: QuotaCheckResult { quota_available: true, remaining_agents: 1, monthly_limit: 1, tier: "Free".to_string(), remaining_weighted_units: 1, degraded: false },
+                weighted_cost: 0,
+                downscaled_plan: None,
+                state_version: 0,
+                role_costs: vec![],
+                estimated_cost_per_task_usd: 0.0,
+            },
+            created_at,
+            spawned_request_id: spawned_request_id.map(str::to_string),
+            claimed: false,
+        };
+        let now = 100 * hour;
+        let mut state = CoordinatorState::default();
+        state.instruction_requests.insert("req-live".to_string(), InstructionRequest {
+            request_id: "req-live".to_string(),
+            user_principal: "user".to_string(),
+            instructions: String::new(),
+            agent_count: None,
+            model_preferences: vec![],
+            created_at: 0,
+            tags: vec![],
+            analysis_id: Some("executed".to_string()),
+        });
+        for entry in [
+            stored("fresh", now - hour, None),
+            stored("expired", now - 25 * hour, None),
+            stored("executed", 0, Some("req-live")),
+            stored("orphaned", 0, Some("req-gone")),
+        ] {
+            state.instruction_analyses.insert(entry.analysis.request_id.clone(), entry);
+        }
+
+        InstructionAnalyzerService::prune_analyses(&mut state, now);
+        let mut kept: Vec<&String> = state.instruction_analyses.keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec!["executed", "fresh"]);
+
+        // A plan is claimed once, until the spawn gives it back
+        assert!(InstructionAnalyzerService::claim_analysis_in(&mut state, "other", "fresh", now).is_err());
+        assert!(InstructionAnalyzerService::claim_analysis_in(&mut state, "user", "fresh", now).is_ok());
+        assert!(InstructionAnalyzerService::claim_analysis_in(&mut state, "user", "fresh", now).is_err(), "concurrent spawn");
+        state.instruction_analyses.get_mut("fresh").unwrap().claimed = false;
+        assert!(InstructionAnalyzerService::claim_analysis_in(&mut state, "user", "fresh", now).is_ok());
+        assert!(InstructionAnalyzerService::claim_analysis_in(&mut state, "user", "executed", now).is_err());
+    }
 }
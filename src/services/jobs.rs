@@ -0,0 +1,260 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, ConcurrencyService, DeliveryService, IdGenerator, RoutingService};
+use crate::services::delivery::DeliveryPayload;
+use crate::infra::Metrics;
+use ic_cdk::api::{call, id, time};
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+/// Job-style inference for tasks that run for minutes: the agent accepts a job
+/// contract, reports progress back to the coordinator, and requesters poll or
+/// receive each update on their callback instead of holding a single call open.
+pub struct JobService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum JobStatus {
+    Dispatched,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// What the agent receives when a job is handed off
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct JobContract {
+    pub job_id: String,
+    pub prompt: String,
+    pub seed: u64,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub top_p: f32,
+    /// Canister and method to report progress to
+    pub coordinator_canister: String,
+    pub progress_method: String,
+}
+
+/// Single progress report from the agent
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct JobProgress {
+    pub job_id: String,
+    pub percent: u8,
+    pub partial: Option<String>,
+    pub reported_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct InferenceJob {
+    pub job_id: String,
+    pub request_id: String,
+    pub requester: String,
+    pub agent_id: String,
+    pub status: JobStatus,
+    pub percent: u8,
+    /// Most recent partial output; the final output once completed
+    pub latest_output: Option<String>,
+    pub progress: Vec<JobProgress>,
+    pub error: Option<String>,
+    pub callback: Option<CallbackTarget>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl JobService {
+    const PROGRESS_METHOD: &'static str = "report_job_progress";
+    /// Progress history kept per job; the latest output is always retained
+    const MAX_PROGRESS_ENTRIES: usize = 50;
+
+    /// Ranked candidates considered when the best agent's owner is at its concurrency cap
+    const CANDIDATES: usize = 5;
+
+    /// Pick an agent for `request` and hand it the job contract
+    pub async fn submit(requester: &str, request: RouteRequest, decode_profile: DecodeProfile) -> Result<InferenceJob, String> {
+        let candidates = RoutingService::select_multiple_agents(&request, Self::CANDIDATES)?;
+        let mut concurrency_error = None;
+        let agent = candidates
+            .into_iter()
+            .find(|agent| match ConcurrencyService::check_activation(std::slice::from_ref(&agent.agent_id)) {
+                Ok(()) => true,
+                Err(e) => {
+                    concurrency_error.get_or_insert(e);
+                    false
+                }
+            })
+            .ok_or_else(|| concurrency_error.unwrap_or_else(|| "No agents available".to_string()))?;
+        let now = time();
+        let job = InferenceJob {
+            job_id: IdGenerator::next("job", requester),
+            request_id: request.request_id.clone(),
+            requester: requester.to_string(),
+            agent_id: agent.agent_id.clone(),
+            status: JobStatus::Dispatched,
+            percent: 0,
+            latest_output: None,
+            progress: Vec::new(),
+            error: None,
+            callback: request.callback.clone(),
+            created_at: now,
+            updated_at: now,
+        };
+        // Stored first so an agent reporting straight away finds the job
+        with_state_mut(|state| state.inference_jobs.insert(job.job_id.clone(), job.clone()));
+
+        let (max_tokens, temperature, top_p) = decode_profile.params();
+        let contract = JobContract {
+            job_id: job.job_id.clone(),
+            prompt: String::from_utf8_lossy(&request.payload).to_string(),
+            seed: RoutingService::derive_seed(&request.request_id),
+            max_tokens,
+            temperature,
+            top_p,
+            coordinator_canister: id().to_text(),
+            progress_method: Self::PROGRESS_METHOD.to_string(),
+        };
+        if let Err(e) = Self::hand_off(&agent, contract).await {
+            Self::update(&job.job_id, |job| {
+                job.status = JobStatus::Failed;
+                job.error = Some(e.clone());
+            });
+            return Err(e);
+        }
+
+        Metrics::increment_counter("inference_jobs_total");
+        Self::get(&job.job_id).ok_or_else(|| "Job not found".to_string())
+    }
+
+    async fn hand_off(agent: &AgentRegistration, contract: JobContract) -> Result<(), String> {
+        let canister = Principal::from_text(&agent.canister_id)
+            .map_err(|e| format!("Invalid canister id for agent {}: {}", agent.agent_id, e))?;
+        let result: call::CallResult<(Result<(), String>,)> = call::call(canister, "start_job", (contract,)).await;
+        match result {
+            Ok((Ok(()),)) => {
+                Metrics::record_dependency_success("agent.start_job");
+                Ok(())
+            }
+            Ok((Err(e),)) => Err(format!("agent {} declined job: {}", agent.agent_id, e)),
+            Err((code, msg)) => {
+                Metrics::record_dependency_failure("agent.start_job", &format!("{}: {:?}: {}", agent.agent_id, code, msg));
+                Err(format!("start_job call failed for {} ({:?}): {}", agent.agent_id, code, msg))
+            }
+        }
+    }
+
+    /// Record progress from the assigned agent; 100% completes the job. The update goes to
+    /// the requester's callback in the background.
+    pub fn report_progress(caller: &str, job_id: &str, percent: u8, partial: Option<String>) -> Result<InferenceJob, String> {
+        if percent > 100 {
+            return Err("Progress must be between 0 and 100".to_string());
+        }
+        let now = time();
+        let job = with_state_mut(|state| {
+            let canister_id = state.inference_jobs.get(job_id)
+                .and_then(|job| state.agents.get(&job.agent_id))
+                .map(|agent| agent.canister_id.clone());
+            let job = state.inference_jobs.get_mut(job_id).ok_or_else(|| "Job not found".to_string())?;
+            if canister_id.as_deref() != Some(caller) {
+                return Err("Only the assigned agent can report progress".to_string());
+            }
+            Self::apply_progress(job, percent, partial, now)?;
+            Ok(job.clone())
+        })?;
+
+        if let (Some(target), Some(update)) = (job.callback.clone(), job.progress.last().cloned()) {
+            DeliveryService::enqueue(&job.requester, target, DeliveryPayload::JobProgress(update));
+        }
+        Ok(job)
+    }
+
+    fn apply_progress(job: &mut InferenceJob, percent: u8, partial: Option<String>, now: u64) -> Result<(), String> {
+        if matches!(job.status, JobStatus::Completed | JobStatus::Failed) {
+            return Err("Job has already finished".to_string());
+        }
+        if percent < job.percent {
+            return Err(format!("Progress cannot go backwards ({}% reported after {}%)", percent, job.percent));
+        }
+        job.percent = percent;
+        job.status = if percent == 100 { JobStatus::Completed } else { JobStatus::Running };
+        if partial.is_some() {
+            job.latest_output = partial.clone();
+        }
+        if job.progress.len() >= Self::MAX_PROGRESS_ENTRIES {
+            job.progress.remove(0);
+        }
+        job.progress.push(JobProgress { job_id: job.job_id.clone(), percent, partial, reported_at: now });
+        job.updated_at = now;
+        Ok(())
+    }
+
+    /// Assigned agent gives up on the job; the requester's callback is told
+    pub fn report_failure(caller: &str, job_id: &str, error: String) -> Result<InferenceJob, String> {
+        let job = with_state_mut(|state| {
+            let canister_id = state.inference_jobs.get(job_id)
+                .and_then(|job| state.agents.get(&job.agent_id))
+                .map(|agent| agent.canister_id.clone());
+            let job = state.inference_jobs.get_mut(job_id).ok_or_else(|| "Job not found".to_string())?;
+            if canister_id.as_deref() != Some(caller) {
+                return Err("Only the assigned agent can report on this job".to_string());
+            }
+            if matches!(job.status, JobStatus::Completed | JobStatus::Failed) {
+                return Err("Job has already finished".to_string());
+            }
+            job.status = JobStatus::Failed;
+            job.error = Some(error);
+            job.updated_at = time();
+            Ok(job.clone())
+        })?;
+
+        if let Some(target) = job.callback.clone() {
+            DeliveryService::enqueue(&job.requester, target, DeliveryPayload::JobFailed(job.clone()));
+        }
+        Ok(job)
+    }
+
+    pub fn get(job_id: &str) -> Option<InferenceJob> {
+        with_state(|state| state.inference_jobs.get(job_id).cloned())
+    }
+
+    fn update(job_id: &str, f: impl FnOnce(&mut InferenceJob)) {
+        with_state_mut(|state| {
+            if let Some(job) = state.inference_jobs.get_mut(job_id) {
+                f(job);
+                job.updated_at = time();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job() -> InferenceJob {
+        InferenceJob {
+            job_id: "job_1".to_string(),
+            request_id: "r".to_string(),
+            requester: "u".to_string(),
+            agent_id: "a".to_string(),
+            status: JobStatus::Dispatched,
+            percent: 0,
+            latest_output: None,
+            progress: Vec::new(),
+            error: None,
+            callback: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_progress_is_monotonic_and_completes_at_100() {
+        let mut job = job();
+        JobService::apply_progress(&mut job, 40, Some("half".to_string()), 1).unwrap();
+        assert_eq!(job.status, JobStatus::Running);
+        assert!(JobService::apply_progress(&mut job, 30, None, 2).is_err());
+
+        JobService::apply_progress(&mut job, 100, Some("done".to_string()), 3).unwrap();
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.latest_output.as_deref(), Some("done"));
+        assert_eq!(job.progress.len(), 2);
+        assert!(JobService::apply_progress(&mut job, 100, None, 4).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::services::{with_state, with_state_mut, AuditService, AutonomousCoordinationService, CoordinatorState, NotificationService, RegistryAuditService, RegistryService};
+use crate::services::audit::AuditAction;
+use crate::services::notifications::NotificationKind;
+use crate::domain::{AgentLifecycle, AgentRegistration, AgentState};
+use crate::infra::Metrics;
+#[cfg(feature = "load-test")]
+use crate::services::LoadTestService;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Agent liveness from heartbeats. Agents call `heartbeat` (or report health) to stay
+/// fresh; a periodic sweep decays the health of silent agents, halving it every
+/// half-life once the decay grace period has passed, marks them unhealthy so routing
+/// skips them, and evicts them from the registry once they have been silent for the
+/// eviction window. A heartbeat before eviction restores the health the agent had.
+pub struct LivenessService;
+
+/// Silence windows, measured from an agent's `last_seen`
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+#[serde(default)]
+pub struct LivenessPolicy {
+    pub enabled: bool,
+    pub unhealthy_after_secs: u64,
+    pub evict_after_secs: u64,
+    /// Silence before health starts to decay
+    pub decay_after_secs: u64,
+    /// Zero disables decay, leaving health untouched until the agent is marked unhealthy
+    pub decay_half_life_secs: u64,
+}
+
+impl Default for LivenessPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            unhealthy_after_secs: 300,
+            evict_after_secs: 86_400,
+            decay_after_secs: 60,
+            decay_half_life_secs: 60,
+        }
+    }
+}
+
+/// What one sweep changed
+#[derive(Debug, Default)]
+pub struct LivenessSweep {
+    pub marked_unhealthy: Vec<String>,
+    /// Agents whose health decayed without reaching zero
+    pub decayed: Vec<String>,
+    pub evicted: Vec<AgentRegistration>,
+    /// Open tasks of evicted agents, left awaiting reassignment
+    pub released_tasks: Vec<String>,
+}
+
+impl LivenessService {
+    const SWEEP_INTERVAL_NS: u64 = 60 * 1_000_000_000;
+    const MIN_UNHEALTHY_AFTER_SECS: u64 = 60;
+
+    pub fn set_policy(policy: LivenessPolicy) -> Result<(), String> {
+        if policy.unhealthy_after_secs < Self::MIN_UNHEALTHY_AFTER_SECS {
+            return Err(format!("unhealthy_after_secs must be at least {}", Self::MIN_UNHEALTHY_AFTER_SECS));
+        }
+        if policy.evict_after_secs <= policy.unhealthy_after_secs {
+            return Err("evict_after_secs must be longer than unhealthy_after_secs".to_string());
+        }
+        if policy.decay_half_life_secs > 0 && policy.decay_after_secs >= policy.unhealthy_after_secs {
+            return Err("decay_after_secs must be shorter than unhealthy_after_secs".to_string());
+        }
+        with_state_mut(|state| state.liveness_policy = policy);
+        Ok(())
+    }
+
+    pub fn get_policy() -> LivenessPolicy {
+        with_state(|state| state.liveness_policy.clone())
+    }
+
+    /// Record that the agent is alive; callable by the agent or its owner
+    pub fn heartbeat(caller: &str, agent_id: &str) -> Result<(), String> {
+        let now = time();
+        with_state_mut(|state| {
+            let agent = state.agents
+                .get_mut(agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            if agent.agent_principal != caller && agent.canister_id != caller {
+                return Err("Only the agent or its owner can send its heartbeat".to_string());
+            }
+            agent.last_seen = now;
+            state.awaiting_first_heartbeat.remove(agent_id);
+            if let Some(health_score) = state.stale_agents.remove(agent_id) {
+                RegistryService::set_health_in(state, agent_id, health_score, caller, now);
+            }
+            RegistryService::mark_changed(state, agent_id);
+            Ok(())
+        })
+    }
+
+    /// Called from the maintenance timer; sweeps at most once per interval
+    pub fn tick() {
+        let now = time();
+        let sweep = with_state_mut(|state| {
+            if !state.liveness_policy.enabled || now.saturating_sub(state.liveness_swept_at) < Self::SWEEP_INTERVAL_NS {
+                return None;
+            }
+            state.liveness_swept_at = now;
+            Some(Self::sweep_in(state, now))
+        });
+        let Some(sweep) = sweep else { return };
+
+        for _ in &sweep.marked_unhealthy {
+            Metrics::increment_counter("agents_marked_stale_total");
+        }
+        for _ in &sweep.decayed {
+            Metrics::increment_counter("agent_health_decays_total");
+        }
+        for agent in &sweep.evicted {
+            Metrics::increment_counter("agents_evicted_total");
+            AuditService::record(&agent.agent_principal, AuditAction::AgentEvicted, &agent.agent_id, "no heartbeat within the eviction window".to_string());
+            NotificationService::notify(
+                &agent.agent_principal,
+                NotificationKind::AgentEvicted,
+                format!("Agent {} was removed from the registry after sending no heartbeat", agent.agent_id),
+            );
+        }
+        if !sweep.released_tasks.is_empty() {
+            ic_cdk::spawn(async move {
+                for task_id in sweep.released_tasks {
+                    AutonomousCoordinationService::reassign_task(&task_id).await;
+                }
+            });
+        }
+    }
+
+    fn sweep_in(state: &mut CoordinatorState, now: u64) -> LivenessSweep {
+        let policy = state.liveness_policy.clone();
+        let unhealthy_after = policy.unhealthy_after_secs.saturating_mul(1_000_000_000);
+        let evict_after = policy.evict_after_secs.saturating_mul(1_000_000_000);
+        let decay_after = if policy.decay_half_life_secs > 0 {
+            policy.decay_after_secs.saturating_mul(1_000_000_000).min(unhealthy_after)
+        } else {
+            unhealthy_after
+        };
+        let mut silent: Vec<(String, u64)> = state.agents
+            .values()
+            .filter(|agent| !Self::exempt(state, agent))
+            .map(|agent| (agent.agent_id.clone(), now.saturating_sub(agent.last_seen)))
+            .filter(|(_, silence)| *silence >= decay_after)
+            .collect();
+        silent.sort();
+
+        let mut sweep = LivenessSweep::default();
+        for (agent_id, silence) in silent {
+            if silence >= evict_after {
+                if let Some(agent) = RegistryService::remove_registration(state, &agent_id, RegistryAuditService::LIVENESS, now) {
+                    sweep.released_tasks.extend(RegistryService::release_open_tasks(state, &agent_id, now));
+                    sweep.evicted.push(agent);
+                }
+                continue;
+            }
+            let Some(agent) = state.agents.get_mut(&agent_id) else { continue };
+            // Decay from the health the agent last reported, kept for restoring on its next heartbeat
+            let reported = *state.stale_agents.entry(agent_id.clone()).or_insert(agent.health_score);
+            let health_score = if silence >= unhealthy_after {
+                0.0
+            } else {
+                let half_lives = (silence - decay_after) as f64 / (policy.decay_half_life_secs as f64 * 1e9);
+                (reported as f64 * 0.5f64.powf(half_lives)) as f32
+            };
+            if health_score < agent.health_score {
+                // Only the drop to unhealthy is audited; intermediate decay is routine
+                if health_score == 0.0 {
+                    RegistryService::set_health_in(state, &agent_id, health_score, RegistryAuditService::LIVENESS, now);
+                    sweep.marked_unhealthy.push(agent_id);
+                } else {
+                    RegistryService::decay_health_in(state, &agent_id, health_score, now);
+                    sweep.decayed.push(agent_id);
+                }
+            }
+        }
+        sweep
+    }
+
+    /// Quarantined agents are kept for inspection, retired and decommissioned ones for history; synthetic
+    /// load-test agents never call in, and agents the coordinator created are only watched once they do
+    fn exempt(state: &CoordinatorState, agent: &AgentRegistration) -> bool {
+        #[cfg(feature = "load-test")]
+        if agent.agent_principal == LoadTestService::SYNTHETIC_PRINCIPAL {
+            return true;
+        }
+        agent.agent_state() == AgentState::Retired
+            || agent.lifecycle() == AgentLifecycle::Decommissioned
+            || state.quarantined_agents.contains_key(&agent.agent_id)
+            || state.awaiting_first_heartbeat.contains(&agent.agent_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn agent(agent_id: &str, last_seen: u64) -> AgentRegistration {
+        AgentRegistration { health_score: 0.9, last_seen, ..AgentRegistration::test(agent_id) }
+    }
+
+    #[test]
+    fn test_sweep_marks_then_evicts_silent_agents() {
+        let mut state = CoordinatorState::default();
+        let now = 100_000 * SEC;
+        RegistryService::insert_registration(&mut state, agent("fresh", now - 10 * SEC), "owner");
+        RegistryService::insert_registration(&mut state, agent("quiet", now - 600 * SEC), "owner");
+        RegistryService::insert_registration(&mut state, agent("gone", now - 90_000 * SEC), "owner");
+
+        let sweep = LivenessService::sweep_in(&mut state, now);
+        assert_eq!(sweep.marked_unhealthy, vec!["quiet".to_string()]);
+        assert_eq!(sweep.evicted.iter().map(|a| a.agent_id.as_str()).collect::<Vec<_>>(), vec!["gone"]);
+        assert_eq!(state.agents["quiet"].health_score, 0.0);
+        assert_eq!(state.registry_audit.iter().filter(|e| e.agent_id == "quiet").count(), 2);
+        assert_eq!(state.stale_agents.get("quiet"), Some(&0.9));
+        assert!(!state.agents.contains_key("gone"));
+
+        // Already marked agents are not marked again
+        assert!(LivenessService::sweep_in(&mut state, now).marked_unhealthy.is_empty());
+    }
+
+    #[test]
+    fn test_sweep_decays_health_by_silence() {
+        let mut state = CoordinatorState::default();
+        let now = 100_000 * SEC;
+        RegistryService::insert_registration(&mut state, agent("recent", now - 30 * SEC), "owner");
+        RegistryService::insert_registration(&mut state, agent("fading", now - 180 * SEC), "owner");
+
+        // Two half-lives past the 60s grace period
+        let sweep = LivenessService::sweep_in(&mut state, now);
+        assert_eq!(sweep.decayed, vec!["fading".to_string()]);
+        assert_eq!(state.agents["recent"].health_score, 0.9);
+        assert!((state.agents["fading"].health_score - 0.225).abs() < 1e-6);
+        assert_eq!(state.registry_audit.iter().filter(|e| e.agent_id == "fading").count(), 1, "routine decay is not audited");
+
+        // Decay is measured from the reported health, not compounded across sweeps
+        LivenessService::sweep_in(&mut state, now + 60 * SEC);
+        assert!((state.agents["fading"].health_score - 0.1125).abs() < 1e-6);
+        assert_eq!(state.stale_agents.get("fading"), Some(&0.9));
+
+        // With decay disabled, health holds until the unhealthy window
+        let mut state = CoordinatorState::default();
+        state.liveness_policy.decay_half_life_secs = 0;
+        RegistryService::insert_registration(&mut state, agent("fading", now - 180 * SEC), "owner");
+        assert!(LivenessService::sweep_in(&mut state, now).decayed.is_empty());
+        assert_eq!(state.agents["fading"].health_score, 0.9);
+    }
+
+    #[test]
+    fn test_spawned_agents_are_swept_only_after_their_first_heartbeat() {
+        let mut state = CoordinatorState::default();
+        let now = 100_000 * SEC;
+        RegistryService::insert_registration(&mut state, agent("spawned", now - 90_000 * SEC), "owner");
+        state.awaiting_first_heartbeat.insert("spawned".to_string());
+
+        let sweep = LivenessService::sweep_in(&mut state, now);
+        assert!(sweep.evicted.is_empty() && sweep.marked_unhealthy.is_empty() && sweep.decayed.is_empty());
+        assert_eq!(state.agents["spawned"].health_score, 0.9);
+
+        // Once it has called in, silence counts as for any other agent
+        state.awaiting_first_heartbeat.remove("spawned");
+        assert_eq!(LivenessService::sweep_in(&mut state, now).evicted.len(), 1);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, CoordinatorState, IdGenerator, RegistryAuditService, RegistryService, RoutingService, WorkloadService};
+use crate::services::autonomous_coord::{CoordinationSession, ResourceConstraints, SessionStatus};
+use crate::services::session_templates::MessageProtocol;
+use ic_cdk::api::{performance_counter, time};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Synthetic load for test deployments (feature `load-test`): registers fake agents
+/// and drives routing selections and session creation from the maintenance timer at a
+/// configured rate, measuring instructions per selection. Synthetic agents are
+/// never called; everything generated is removed by `teardown`.
+pub struct LoadTestService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LoadProfile {
+    pub routes_per_sec: u32,
+    pub sessions_per_sec: u32,
+    /// Capabilities requested by synthetic routes; one is picked per route
+    pub capabilities: Vec<String>,
+    pub duration_secs: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct LoadTestStatus {
+    pub synthetic_agents: u32,
+    pub synthetic_sessions: u32,
+    pub profile: Option<LoadProfile>,
+    pub running: bool,
+    pub started_at: Option<u64>,
+    pub routes_issued: u64,
+    pub routes_failed: u64,
+    pub sessions_created: u64,
+    pub selection_instructions_total: u64,
+    pub selection_instructions_max: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoadTestState {
+    pub profile: Option<LoadProfile>,
+    pub started_at: Option<u64>,
+    pub routes_issued: u64,
+    pub routes_failed: u64,
+    pub sessions_created: u64,
+    pub selection_instructions_total: u64,
+    pub selection_instructions_max: u64,
+}
+
+impl LoadTestService {
+    /// Owner recorded on everything synthetic, so teardown can find it
+    pub const SYNTHETIC_PRINCIPAL: &'static str = "load-test-synthetic";
+    const MAX_SYNTHETIC_AGENTS: u32 = 20_000;
+    const MAX_AGENTS_PER_CALL: u32 = 2_000;
+    /// Operations per maintenance tick, whatever the rate, to stay inside the instruction limit
+    const MAX_OPS_PER_TICK: u64 = 200;
+    const MODELS: [&'static str; 3] = ["llama", "mistral", "gemma"];
+
+    pub fn register_agents(count: u32, capabilities: Vec<String>) -> Result<u32, String> {
+        if count == 0 || count > Self::MAX_AGENTS_PER_CALL {
+            return Err(format!("count must be between 1 and {}", Self::MAX_AGENTS_PER_CALL));
+        }
+        if capabilities.is_empty() {
+            return Err("At least one capability is required".to_string());
+        }
+        with_state_mut(|state| {
+            let existing = Self::synthetic_agent_ids(state).len() as u32;
+            if existing + count > Self::MAX_SYNTHETIC_AGENTS {
+                return Err(format!("At most {} synthetic agents may exist", Self::MAX_SYNTHETIC_AGENTS));
+            }
+            let now = time();
+            for n in 0..count {
+                let index = existing + n;
+                // Each agent advertises a rotating slice of the capabilities
+                let agent_capabilities = (0..capabilities.len().min(3))
+                    .map(|offset| capabilities[(index as usize + offset) % capabilities.len()].clone())
+                    .collect();
+                let registration = AgentRegistration {
+                    agent_id: IdGenerator::next_in(state, "loadtest_agent", Self::SYNTHETIC_PRINCIPAL),
+                    agent_principal: Self::SYNTHETIC_PRINCIPAL.to_string(),
+                    canister_id: "aaaaa-aa".to_string(),
+                    capabilities: agent_capabilities,
+                    model_id: Self::MODELS[index as usize % Self::MODELS.len()].to_string(),
+                    health_score: 0.5 + (index % 50) as f32 / 100.0,
+                    registered_at: now,
+                    last_seen: now,
+                    tags: Default::default(),
+                    agent_version: None,
+                    protocol_version: None,
+                    state: None,
+                    lifecycle: None,
+                };
+                RegistryService::insert_registration(state, registration, RegistryAuditService::LOAD_TEST);
+            }
+            Ok(existing + count)
+        })
+    }
+
+    pub fn start(profile: LoadProfile) -> Result<LoadTestStatus, String> {
+        if profile.routes_per_sec == 0 && profile.sessions_per_sec == 0 {
+            return Err("Set routes_per_sec or sessions_per_sec".to_string());
+        }
+        if profile.routes_per_sec > 0 && profile.capabilities.is_empty() {
+            return Err("Synthetic routes need at least one capability".to_string());
+        }
+        if profile.duration_secs == 0 {
+            return Err("duration_secs must be greater than zero".to_string());
+        }
+        let now = time();
+        with_state_mut(|state| {
+            state.load_test = LoadTestState {
+                profile: Some(profile),
+                started_at: Some(now),
+                ..Default::default()
+            };
+        });
+        Ok(Self::status())
+    }
+
+    pub fn stop() -> LoadTestStatus {
+        with_state_mut(|state| state.load_test.profile = None);
+        Self::status()
+    }
+
+    /// Heartbeat step: issue the operations the rate calls for by now
+    pub fn tick() {
+        let now = time();
+        let Some((profile, load)) = with_state_mut(|state| {
+            let profile = state.load_test.profile.clone()?;
+            let started_at = state.load_test.started_at.unwrap_or(now);
+            if now.saturating_sub(started_at) >= profile.duration_secs * 1_000_000_000 {
+                state.load_test.profile = None;
+                return None;
+            }
+            Some((profile, state.load_test.clone()))
+        }) else {
+            return;
+        };
+
+        let elapsed_ns = now.saturating_sub(load.started_at.unwrap_or(now));
+        let routes = Self::owed(profile.routes_per_sec, elapsed_ns, load.routes_issued);
+        let sessions = Self::owed(profile.sessions_per_sec, elapsed_ns, load.sessions_created);
+        for sequence in load.routes_issued..load.routes_issued + routes {
+            let capability = profile.capabilities[sequence as usize % profile.capabilities.len()].clone();
+            Self::route_once(format!("loadtest_route_{}", sequence), capability);
+        }
+        for _ in 0..sessions {
+            Self::create_session();
+        }
+    }
+
+    /// Operations still due after `elapsed_ns` at `per_sec` with `done` issued, capped per tick
+    fn owed(per_sec: u32, elapsed_ns: u64, done: u64) -> u64 {
+        let due = per_sec as u64 * elapsed_ns / 1_000_000_000;
+        due.saturating_sub(done).min(Self::MAX_OPS_PER_TICK)
+    }
+
+    fn route_once(request_id: String, capability: String) {
+        let request = RouteRequest {
+            request_id,
+            requester: Self::SYNTHETIC_PRINCIPAL.to_string(),
+            capabilities_required: vec![capability],
+            payload: Vec::new(),
+            routing_mode: RoutingMode::Unicast,
+            callback: None,
+            tags: Vec::new(),
+            guaranteed_service: false,
+            priority: None,
+            strategy: None,
+            review: None,
+            model_preference: None,
+            session_key: None,
+            partition_key: None,
+            aggregation: None,
+        };
+        let before = performance_counter(0);
+        let selected = RoutingService::select_multiple_agents(&request, 1);
+        let spent = performance_counter(0).saturating_sub(before);
+        with_state_mut(|state| {
+            let load = &mut state.load_test;
+            load.routes_issued += 1;
+            if !matches!(selected, Ok(ref agents) if !agents.is_empty()) {
+                load.routes_failed += 1;
+            }
+            load.selection_instructions_total += spent;
+            load.selection_instructions_max = load.selection_instructions_max.max(spent);
+        });
+    }
+
+    fn create_session() {
+        with_state_mut(|state| {
+            let agent_ids = Self::synthetic_agent_ids(state);
+            if agent_ids.is_empty() {
+                return;
+            }
+            let offset = state.load_test.sessions_created as usize;
+            let participants: Vec<String> = (0..agent_ids.len().min(3))
+                .map(|n| agent_ids[(offset + n) % agent_ids.len()].clone())
+                .collect();
+            let now = time();
+            let session = CoordinationSession {
+                session_id: IdGenerator::next_in(state, "loadtest_session", Self::SYNTHETIC_PRINCIPAL),
+                coordinator_agent: participants[0].clone(),
+                participants,
+                objective: "synthetic load".to_string(),
+                status: SessionStatus::Active,
+                created_at: now,
+                last_activity: now,
+                messages: Vec::new(),
+                resource_constraints: ResourceConstraints {
+                    max_execution_time_ms: 60_000,
+                    max_memory_usage_bytes: 1024 * 1024,
+                    max_concurrent_tasks: 1,
+                    allowed_capabilities: None,
+                },
+                template_id: None,
+                role_bindings: Vec::new(),
+                protocol: MessageProtocol::Mesh,
+            };
+            state.workload_index.add_session(&session);
+            state.coordination_sessions
+                .get_or_insert_with(Default::default)
+                .insert(session.session_id.clone(), session);
+            state.load_test.sessions_created += 1;
+        });
+    }
+
+    /// Stop any run and remove every synthetic agent, session and routing record
+    pub fn teardown() -> LoadTestStatus {
+        let now = time();
+        with_state_mut(|state| {
+            let agent_ids = Self::synthetic_agent_ids(state);
+            for agent_id in &agent_ids {
+                RegistryService::remove_registration(state, agent_id, RegistryAuditService::LOAD_TEST, now);
+            }
+            if let Some(sessions) = state.coordination_sessions.as_mut() {
+                sessions.retain(|session_id, _| !session_id.starts_with("loadtest_session_"));
+            }
+            WorkloadService::rebuild(state);
+            state.load_test = LoadTestState::default();
+        });
+        Self::status()
+    }
+
+    pub fn status() -> LoadTestStatus {
+        with_state(|state| {
+            let load = &state.load_test;
+            LoadTestStatus {
+                synthetic_agents: Self::synthetic_agent_ids(state).len() as u32,
+                synthetic_sessions: state.coordination_sessions.as_ref()
+                    .map(|sessions| sessions.keys().filter(|id| id.starts_with("loadtest_session_")).count() as u32)
+                    .unwrap_or(0),
+                profile: load.profile.clone(),
+                running: load.profile.is_some(),
+                started_at: load.started_at,
+                routes_issued: load.routes_issued,
+                routes_failed: load.routes_failed,
+                sessions_created: load.sessions_created,
+                selection_instructions_total: load.selection_instructions_total,
+                selection_instructions_max: load.selection_instructions_max,
+            }
+        })
+    }
+
+    fn synthetic_agent_ids(state: &CoordinatorState) -> Vec<String> {
+        let mut ids: Vec<String> = state.agents
+            .values()
+            .filter(|agent| agent.agent_principal == Self::SYNTHETIC_PRINCIPAL)
+            .map(|agent| agent.agent_id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owed_operations_follow_rate_and_cap() {
+        assert_eq!(LoadTestService::owed(10, 500_000_000, 0), 5);
+        assert_eq!(LoadTestService::owed(10, 1_500_000_000, 12), 3);
+        // Slow rates catch up across short ticks instead of rounding to zero forever
+        assert_eq!(LoadTestService::owed(1, 400_000_000, 0), 0);
+        assert_eq!(LoadTestService::owed(1, 1_200_000_000, 0), 1);
+        assert_eq!(LoadTestService::owed(100_000, 2_000_000_000, 0), LoadTestService::MAX_OPS_PER_TICK);
+    }
+}
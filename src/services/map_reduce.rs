@@ -0,0 +1,252 @@
+use crate::domain::*;
+use crate::services::{ProvenanceService, RoutingService};
+use crate::services::provenance::{ProvenanceStage, StepRecord};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use futures::future::join_all;
+
+/// Map-reduce routing: split a payload, process chunks in parallel, reduce the partials
+pub struct MapReduceService;
+
+/// How a payload is cut into chunks for the map phase
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub enum PayloadSplitter {
+    /// Groups of `per_chunk` lines
+    Lines { per_chunk: u32 },
+    /// Blank-line separated paragraphs
+    Paragraphs,
+    /// Pieces between occurrences of `delimiter`
+    Delimiter { delimiter: String },
+    /// Pieces of at most `size` bytes, never splitting a character
+    FixedBytes { size: u32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct MapReduceRequest {
+    /// Capabilities, payload and routing preferences for the map phase
+    pub route: RouteRequest,
+    pub splitter: PayloadSplitter,
+    /// Prepended to every chunk sent to a mapper
+    pub map_instruction: String,
+    pub reducer_capabilities: Vec<String>,
+    /// Prepended to the collected partials sent to the reducer
+    pub reduce_instruction: String,
+}
+
+/// Where a chunk went and what came back
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ChunkProvenance {
+    pub chunk_index: u32,
+    pub agent_id: String,
+    pub chunk_bytes: u32,
+    pub elapsed_ms: u64,
+    pub output: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct MapReduceResult {
+    pub request_id: String,
+    pub reduced_output: String,
+    pub reducer_agent: String,
+    pub chunks: Vec<ChunkProvenance>,
+    pub total_time_ms: u64,
+    /// Head of the provenance chain; full chain via `get_result_provenance`
+    pub provenance_sha256: String,
+}
+
+impl MapReduceService {
+    const MAX_CHUNKS: usize = 32;
+    const MAX_MAPPERS: usize = 5;
+
+    pub async fn run(request: MapReduceRequest, decode_profile: DecodeProfile) -> Result<MapReduceResult, String> {
+        let start = time();
+        let route = &request.route;
+        let payload = String::from_utf8(route.payload.clone())
+            .map_err(|_| "Map-reduce payload must be UTF-8 text".to_string())?;
+        let chunks = Self::split(&payload, &request.splitter)?;
+        if chunks.len() > Self::MAX_CHUNKS {
+            return Err(format!("Payload splits into {} chunks; at most {} are allowed", chunks.len(), Self::MAX_CHUNKS));
+        }
+
+        // Pick the reducer up front so a missing reducer fails before any inference is spent
+        let reducer_request = RouteRequest {
+            capabilities_required: request.reducer_capabilities.clone(),
+            ..route.clone()
+        };
+        let reducer = RoutingService::select_multiple_agents(&reducer_request, 1)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| "No reducer agent available".to_string())?;
+
+        // Spread chunks round-robin over the best mappers
+        let mappers = RoutingService::select_multiple_agents(route, chunks.len().min(Self::MAX_MAPPERS))?;
+        let seed = RoutingService::derive_seed(&route.request_id);
+        let futures = chunks.iter().enumerate().map(|(index, chunk)| {
+            let agent = &mappers[index % mappers.len()];
+            let prompt = format!("{}\n\n{}", request.map_instruction, chunk);
+            let msg_id = format!("{}:map:{}", route.request_id, index);
+            async move {
+                let outcome = RoutingService::dispatch_inference(agent, &prompt, &msg_id, seed, decode_profile)
+                    .await
+                    .map(|(resp, elapsed)| (resp.generated_text, elapsed));
+                Self::chunk_provenance(index, &agent.agent_id, chunk, outcome)
+            }
+        });
+        let provenance = join_all(futures).await;
+        ProvenanceService::begin(&route.request_id);
+        for (chunk, result) in chunks.iter().zip(&provenance) {
+            ProvenanceService::record(&route.request_id, StepRecord {
+                stage: ProvenanceStage::Map,
+                agent_id: &result.agent_id,
+                part: format!("chunk {}", result.chunk_index),
+                input: Some(chunk),
+                output: result.output.as_deref(),
+            });
+        }
+
+        let partials = Self::collect_partials(&provenance);
+        if partials.is_empty() {
+            Metrics::increment_counter("map_reduce_failures_total");
+            return Err("Every map chunk failed; nothing to reduce".to_string());
+        }
+
+        let reduce_prompt = format!("{}\n\n{}", request.reduce_instruction, partials);
+        let reduce_msg_id = format!("{}:reduce", route.request_id);
+        let (reduced, _) = RoutingService::dispatch_inference(&reducer, &reduce_prompt, &reduce_msg_id, seed, decode_profile)
+            .await
+            .map_err(|e| format!("Reduce step failed: {}", e))?;
+        ProvenanceService::record(&route.request_id, StepRecord {
+            stage: ProvenanceStage::Reduce,
+            agent_id: &reducer.agent_id,
+            part: "reduced output".to_string(),
+            input: Some(&reduce_prompt),
+            output: Some(&reduced.generated_text),
+        });
+        let provenance_sha256 = ProvenanceService::finalize(&route.request_id, &reduced.generated_text).unwrap_or_default();
+
+        Metrics::increment_counter("map_reduce_requests_total");
+        Ok(MapReduceResult {
+            request_id: route.request_id.clone(),
+            reduced_output: reduced.generated_text,
+            reducer_agent: reducer.agent_id,
+            chunks: provenance,
+            total_time_ms: time() - start,
+            provenance_sha256,
+        })
+    }
+
+    /// Agent calls a request will make: one per chunk plus the reduce
+    pub(crate) fn planned_calls(request: &MapReduceRequest) -> Result<u32, String> {
+        let payload = String::from_utf8_lossy(&request.route.payload);
+        Ok(Self::split(&payload, &request.splitter)?.len().min(Self::MAX_CHUNKS) as u32 + 1)
+    }
+
+    /// Split a payload into non-empty chunks
+    fn split(payload: &str, splitter: &PayloadSplitter) -> Result<Vec<String>, String> {
+        let chunks: Vec<String> = match splitter {
+            PayloadSplitter::Lines { per_chunk } => {
+                if *per_chunk == 0 {
+                    return Err("per_chunk must be at least 1".to_string());
+                }
+                let lines: Vec<&str> = payload.lines().collect();
+                lines.chunks(*per_chunk as usize).map(|group| group.join("\n")).collect()
+            }
+            PayloadSplitter::Paragraphs => payload
+                .split("\n\n")
+                .map(|p| p.to_string())
+                .collect(),
+            PayloadSplitter::Delimiter { delimiter } => {
+                if delimiter.is_empty() {
+                    return Err("Delimiter must not be empty".to_string());
+                }
+                payload.split(delimiter.as_str()).map(|p| p.to_string()).collect()
+            }
+            PayloadSplitter::FixedBytes { size } => {
+                let size = *size as usize;
+                if size < 4 {
+                    // Anything smaller cannot hold every UTF-8 character
+                    return Err("size must be at least 4 bytes".to_string());
+                }
+                let mut chunks = Vec::new();
+                let mut rest = payload;
+                while !rest.is_empty() {
+                    let mut end = size.min(rest.len());
+                    while !rest.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    chunks.push(rest[..end].to_string());
+                    rest = &rest[end..];
+                }
+                chunks
+            }
+        };
+
+        let chunks: Vec<String> = chunks.into_iter().filter(|c| !c.trim().is_empty()).collect();
+        if chunks.is_empty() {
+            return Err("Payload produced no chunks".to_string());
+        }
+        Ok(chunks)
+    }
+
+    /// Provenance of one map call; `outcome` carries the dispatch time in nanoseconds
+    fn chunk_provenance(index: usize, agent_id: &str, chunk: &str, outcome: Result<(String, u64), String>) -> ChunkProvenance {
+        let (output, error, elapsed_ms) = match outcome {
+            Ok((text, elapsed)) => (Some(text), None, elapsed / 1_000_000),
+            Err(e) => (None, Some(e), 0),
+        };
+        ChunkProvenance {
+            chunk_index: index as u32,
+            agent_id: agent_id.to_string(),
+            chunk_bytes: chunk.len() as u32,
+            elapsed_ms,
+            output,
+            error,
+        }
+    }
+
+    /// Successful partials labelled by chunk, in payload order
+    fn collect_partials(provenance: &[ChunkProvenance]) -> String {
+        provenance
+            .iter()
+            .filter_map(|p| p.output.as_ref().map(|output| format!("[chunk {}]\n{}", p.chunk_index, output)))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_lines_and_paragraphs() {
+        let payload = "a\nb\nc\n\nd";
+        let chunks = MapReduceService::split(payload, &PayloadSplitter::Lines { per_chunk: 2 }).unwrap();
+        assert_eq!(chunks, vec!["a\nb", "c\n", "d"]);
+
+        let chunks = MapReduceService::split(payload, &PayloadSplitter::Paragraphs).unwrap();
+        assert_eq!(chunks, vec!["a\nb\nc", "d"]);
+    }
+
+    #[test]
+    fn test_split_fixed_bytes_respects_char_boundaries() {
+        let chunks = MapReduceService::split("ééééé", &PayloadSplitter::FixedBytes { size: 5 }).unwrap();
+        assert_eq!(chunks, vec!["éé", "éé", "é"]);
+        assert!(MapReduceService::split("abc", &PayloadSplitter::FixedBytes { size: 0 }).is_err());
+    }
+
+    #[test]
+    fn test_chunk_provenance_records_milliseconds() {
+        let ok = MapReduceService::chunk_provenance(1, "agent-a", "abc", Ok(("out".to_string(), 1_500_000_000)));
+        assert_eq!(ok.elapsed_ms, 1_500);
+        assert_eq!(ok.chunk_bytes, 3);
+        assert_eq!(ok.output.as_deref(), Some("out"));
+
+        let failed = MapReduceService::chunk_provenance(2, "agent-b", "abc", Err("boom".to_string()));
+        assert_eq!(failed.elapsed_ms, 0);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::services::{with_state, AuditService, RegistryService};
+use crate::services::audit::AuditAction;
+use crate::services::autonomous_coord::AgentMessage;
+use crate::infra::Metrics;
+
+/// Sender verification for coordination messages. The IC authenticates every caller,
+/// so a message is accepted only from the sending agent's registered principal or
+/// canister, identities named inside the message must match the sender, and the sender
+/// must be a member of the session. Spoofing attempts are refused and recorded in the
+/// audit log of the impersonated agent's owner.
+pub struct MessageAuthService;
+
+impl MessageAuthService {
+    pub fn verify_sender(caller: &str, session_id: &str, from_agent: &str, message: &AgentMessage) -> Result<(), String> {
+        let agent = RegistryService::get_agent(from_agent)?;
+        let member = with_state(|state| {
+            state.coordination_sessions
+                .as_ref()
+                .and_then(|sessions| sessions.get(session_id))
+                .is_some_and(|session| session.is_member(from_agent))
+        });
+        let violation = if member {
+            Self::violation(caller, &agent.agent_principal, &agent.canister_id, from_agent, message)
+        } else {
+            Some(format!("{} is not a participant of session {}", from_agent, session_id))
+        };
+        let Some(violation) = violation else {
+            return Ok(());
+        };
+        Metrics::increment_counter("spoofed_messages_rejected");
+        AuditService::record(
+            &agent.agent_principal,
+            AuditAction::SpoofedMessageRejected,
+            from_agent,
+            format!("caller {}: {}", caller, violation),
+        );
+        Err(format!("Spoofed: {}", violation))
+    }
+
+    fn violation(caller: &str, agent_principal: &str, canister_id: &str, from_agent: &str, message: &AgentMessage) -> Option<String> {
+        if caller != agent_principal && caller != canister_id {
+            return Some(format!("caller does not control sending agent {}", from_agent));
+        }
+        let claimed = match message {
+            AgentMessage::TaskResponse { agent_id, .. } => Some(agent_id),
+            AgentMessage::CapabilityAdvertisement { agent_id, .. } => Some(agent_id),
+            AgentMessage::CoordinationRequest { requesting_agent, .. } => Some(requesting_agent),
+            _ => None,
+        };
+        claimed
+            .filter(|claimed| claimed.as_str() != from_agent)
+            .map(|claimed| format!("message claims to be from {} but was sent as {}", claimed, from_agent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::autonomous_coord::TaskStatus;
+
+    fn response(agent_id: &str) -> AgentMessage {
+        AgentMessage::TaskResponse {
+            task_id: "t1".to_string(),
+            agent_id: agent_id.to_string(),
+            status: TaskStatus::Completed,
+            result: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_violation_checks_caller_and_claimed_identity() {
+        assert!(MessageAuthService::violation("owner", "owner", "canister", "a1", &response("a1")).is_none());
+        assert!(MessageAuthService::violation("canister", "owner", "canister", "a1", &response("a1")).is_none());
+        assert!(MessageAuthService::violation("mallory", "owner", "canister", "a1", &response("a1")).is_some());
+        let claimed = MessageAuthService::violation("owner", "owner", "canister", "a1", &response("a2")).unwrap();
+        assert!(claimed.contains("a2"), "{}", claimed);
+    }
+}
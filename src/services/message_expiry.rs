@@ -0,0 +1,181 @@
+use crate::domain::*;
+use crate::services::{with_state_mut, CoordinatorState, DeliveryService, IdGenerator};
+use crate::services::autonomous_coord::{AgentMessage, TaskStatus};
+use crate::services::delivery::{DeadLetter, DeliveryPayload};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Time limits on messages waiting in agent queues. Each message expires after a TTL set
+/// by its priority, or at its task deadline if that comes first. Expired messages move to
+/// the owning operator's dead letters, and a TaskRequest that expired undelivered leaves
+/// its task awaiting reassignment.
+pub struct MessageExpiryService;
+
+/// Message waiting in an agent's queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub message: AgentMessage,
+    pub enqueued_at: u64,
+    pub expires_at: u64,
+}
+
+/// Dead-lettered queue message; redriving it re-queues it for the same agent
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ExpiredAgentMessage {
+    pub agent_id: String,
+    pub message: AgentMessage,
+    pub enqueued_at: u64,
+    pub expires_at: u64,
+}
+
+impl MessageExpiryService {
+    /// Method recorded as the dead letter target: the queue agents read from
+    pub const QUEUE_METHOD: &'static str = "get_agent_messages";
+    const MINUTE_NS: u64 = 60 * 1_000_000_000;
+
+    /// Urgent messages go stale first; background work may wait for the agent longer
+    pub fn default_ttl(priority: MessagePriority) -> u64 {
+        match priority {
+            MessagePriority::Critical => 5 * Self::MINUTE_NS,
+            MessagePriority::High => 15 * Self::MINUTE_NS,
+            MessagePriority::Normal => 60 * Self::MINUTE_NS,
+            MessagePriority::Low => 6 * 60 * Self::MINUTE_NS,
+        }
+    }
+
+    fn priority(message: &AgentMessage) -> MessagePriority {
+        match message {
+            AgentMessage::TaskRequest { priority, .. } => *priority,
+            AgentMessage::Cancellation { .. } => MessagePriority::Critical,
+            _ => MessagePriority::Normal,
+        }
+    }
+
+    pub fn enqueue(message: AgentMessage, now: u64) -> QueuedMessage {
+        let mut expires_at = now.saturating_add(Self::default_ttl(Self::priority(&message)));
+        if let AgentMessage::TaskRequest { deadline: Some(deadline), .. } = &message {
+            expires_at = expires_at.min(*deadline);
+        }
+        QueuedMessage { message, enqueued_at: now, expires_at }
+    }
+
+    /// Remove every expired message from every queue, returning how many expired
+    pub(crate) fn sweep_in(state: &mut CoordinatorState, now: u64) -> u32 {
+        let Some(queues) = state.agent_message_queues.as_mut() else { return 0 };
+        let mut expired = Vec::new();
+        for (agent_id, queue) in queues.iter_mut() {
+            let (stale, live): (Vec<QueuedMessage>, Vec<QueuedMessage>) =
+                std::mem::take(queue).into_iter().partition(|queued| queued.expires_at <= now);
+            *queue = live;
+            expired.extend(stale.into_iter().map(|queued| (agent_id.clone(), queued)));
+        }
+
+        let count = expired.len() as u32;
+        for (agent_id, queued) in expired {
+            Self::expire(state, agent_id, queued, now);
+            Metrics::increment_counter("agent_messages_expired");
+        }
+        count
+    }
+
+    fn expire(state: &mut CoordinatorState, agent_id: String, queued: QueuedMessage, now: u64) {
+        if let AgentMessage::TaskRequest { task_id, .. } = &queued.message {
+            if let Some(task) = state.task_ledger.get_mut(task_id) {
+                if task.assigned_agent == agent_id && matches!(task.status, TaskStatus::Pending) {
+                    task.status = TaskStatus::AwaitingReassignment;
+                    task.updated_at = now;
+                    state.workload_index.update_task(task);
+                }
+            }
+        }
+
+        let (owner_principal, canister_id) = state.agents
+            .get(&agent_id)
+            .map(|agent| (agent.agent_principal.clone(), agent.canister_id.clone()))
+            .unwrap_or_default();
+        let dead_letter = DeadLetter {
+            delivery_id: IdGenerator::next_at(state, "delivery", &owner_principal, now),
+            owner_principal,
+            target: CallbackTarget { canister_id, method: Self::QUEUE_METHOD.to_string() },
+            last_error: format!(
+                "Expired undelivered after {}s in the agent's queue",
+                queued.expires_at.saturating_sub(queued.enqueued_at) / 1_000_000_000
+            ),
+            payload: DeliveryPayload::ExpiredMessage(ExpiredAgentMessage {
+                agent_id,
+                message: queued.message,
+                enqueued_at: queued.enqueued_at,
+                expires_at: queued.expires_at,
+            }),
+            attempts: 0,
+            dead_lettered_at: now,
+        };
+        DeliveryService::push_dead_letter(state, dead_letter);
+    }
+
+    /// Put a dead-lettered message back in its agent's queue with a fresh TTL; its task,
+    /// if still unassigned, goes back to pending with that agent
+    pub(crate) fn requeue(expired: ExpiredAgentMessage) {
+        let now = time();
+        with_state_mut(|state| {
+            if let AgentMessage::TaskRequest { task_id, .. } = &expired.message {
+                if let Some(task) = state.task_ledger.get_mut(task_id) {
+                    if task.assigned_agent == expired.agent_id && matches!(task.status, TaskStatus::AwaitingReassignment) {
+                        task.status = TaskStatus::Pending;
+                        task.updated_at = now;
+                        state.workload_index.update_task(task);
+                    }
+                }
+            }
+            state.agent_message_queues
+                .get_or_insert_with(Default::default)
+                .entry(expired.agent_id)
+                .or_default()
+                .push(Self::enqueue(expired.message, now));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::autonomous_coord::TaskLedgerEntry;
+
+    fn task_request(task_id: &str, priority: MessagePriority, deadline: Option<u64>) -> AgentMessage {
+        AgentMessage::TaskRequest {
+            task_id: task_id.to_string(),
+            description: String::new(),
+            required_capabilities: Vec::new(),
+            priority,
+            deadline,
+            parent_task_id: None,
+        }
+    }
+
+    #[test]
+    fn test_ttl_follows_priority_and_deadline() {
+        let queued = MessageExpiryService::enqueue(task_request("t1", MessagePriority::Low, None), 100);
+        assert_eq!(queued.expires_at, 100 + MessageExpiryService::default_ttl(MessagePriority::Low));
+        let queued = MessageExpiryService::enqueue(task_request("t1", MessagePriority::Low, Some(500)), 100);
+        assert_eq!(queued.expires_at, 500);
+        assert!(MessageExpiryService::default_ttl(MessagePriority::Critical) < MessageExpiryService::default_ttl(MessagePriority::Normal));
+    }
+
+    #[test]
+    fn test_sweep_dead_letters_and_marks_task() {
+        let mut state = CoordinatorState::default();
+        state.task_ledger.insert("t1".to_string(), TaskLedgerEntry::test("t1", "a1", TaskStatus::Pending));
+        let stale = MessageExpiryService::enqueue(task_request("t1", MessagePriority::Critical, None), 0);
+        let fresh = MessageExpiryService::enqueue(task_request("t2", MessagePriority::Low, None), 0);
+        state.agent_message_queues = Some([("a1".to_string(), vec![stale, fresh])].into_iter().collect());
+
+        let now = MessageExpiryService::default_ttl(MessagePriority::Critical);
+        assert_eq!(MessageExpiryService::sweep_in(&mut state, now), 1);
+        assert_eq!(state.agent_message_queues.as_ref().unwrap()["a1"].len(), 1);
+        assert!(matches!(state.task_ledger["t1"].status, TaskStatus::AwaitingReassignment));
+        assert_eq!(state.dead_letters.len(), 1);
+        assert!(matches!(state.dead_letters[0].payload, DeliveryPayload::ExpiredMessage(_)));
+    }
+}
@@ -1,47 +1,280 @@
 use crate::domain::*;
 use ic_cdk::api::time;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::cell::RefCell;
+use serde::{Deserialize, Serialize};
 
 pub mod registry;
+pub mod registry_audit;
+pub mod registry_events;
 pub mod routing;
+pub mod routing_strategies;
 pub mod dedup;
+pub mod partial_results;
 pub mod quota_manager;
 pub mod autonomous_coord;
 pub mod instruction_analyzer;
 pub mod agent_spawning;
 pub mod econ_integration;
+pub mod programs;
+pub mod delivery;
+pub mod tag_analytics;
+pub mod circuit_breaker;
+pub mod preferences;
+pub mod audit;
+pub mod notifications;
+pub mod reclamation;
+pub mod sla;
+pub mod availability;
+pub mod batches;
+pub mod map_reduce;
+pub mod cancellation;
+pub mod secrets;
+pub mod review;
+pub mod ids;
+pub mod chatter;
+pub mod snapshot;
+pub mod onboarding;
+pub mod jobs;
+pub mod slo;
+pub mod discovery;
+pub mod specializations;
+pub mod session_templates;
+pub mod provenance;
+pub mod warm_pool;
+pub mod concurrency;
+pub mod coordination_preferences;
+pub mod message_expiry;
+pub mod chains;
+pub mod quarantine;
+pub mod safety_limits;
+pub mod persistence;
+pub mod message_auth;
+pub mod liveness;
+pub mod siem_export;
+pub mod capacity_forecast;
+pub mod certification;
+pub mod standby;
+pub mod reputation;
+pub mod workload;
+pub mod econ_fallback;
+pub mod feature_flags;
+pub mod sharding;
+pub mod registry_shards;
+pub mod rounds;
+pub mod service_report;
+pub mod agent_load;
+pub mod session_affinity;
+pub mod routing_queue;
+pub mod aggregation;
+#[cfg(feature = "load-test")]
+pub mod load_test;
 
 pub use registry::RegistryService;
+pub use registry_audit::RegistryAuditService;
+pub use registry_events::RegistryEventService;
 pub use routing::RoutingService;
 pub use dedup::DedupService;
+pub use partial_results::PartialResultService;
 pub use quota_manager::QuotaManager;
 pub use autonomous_coord::AutonomousCoordinationService;
 pub use instruction_analyzer::InstructionAnalyzerService;
 pub use agent_spawning::AgentSpawningService;
 pub use econ_integration::EconIntegrationService;
+pub use programs::ProgramService;
+pub use delivery::DeliveryService;
+pub use tag_analytics::TagAnalyticsService;
+pub use circuit_breaker::CircuitBreakerService;
+pub use preferences::PreferencesService;
+pub use audit::AuditService;
+pub use notifications::NotificationService;
+pub use reclamation::ReclamationService;
+pub use sla::SlaService;
+pub use availability::AvailabilityService;
+pub use batches::BatchService;
+pub use map_reduce::MapReduceService;
+pub use cancellation::CancellationService;
+pub use secrets::SecretsService;
+pub use review::ReviewService;
+pub use ids::IdGenerator;
+pub use chatter::ChatterLimiter;
+pub use snapshot::SnapshotService;
+pub use onboarding::OnboardingService;
+pub use jobs::JobService;
+pub use slo::SloService;
+pub use discovery::DiscoveryService;
+pub use specializations::SpecializationService;
+pub use session_templates::SessionTemplateService;
+pub use provenance::ProvenanceService;
+pub use warm_pool::WarmPoolService;
+pub use concurrency::ConcurrencyService;
+pub use coordination_preferences::CoordinationPreferenceService;
+pub use message_expiry::MessageExpiryService;
+pub use chains::ChainService;
+pub use quarantine::QuarantineService;
+pub use safety_limits::SafetyLimitService;
+pub use persistence::PersistenceService;
+pub use message_auth::MessageAuthService;
+pub use liveness::LivenessService;
+pub use siem_export::SiemExportService;
+pub use capacity_forecast::CapacityForecastService;
+pub use certification::CertificationService;
+pub use standby::StandbyService;
+pub use reputation::ReputationService;
+pub use workload::WorkloadService;
+pub use econ_fallback::EconFallbackService;
+pub use feature_flags::FeatureFlagService;
+pub use sharding::ShardService;
+pub use registry_shards::RegistryShardService;
+pub use rounds::RoundService;
+pub use service_report::ServiceReportService;
+pub use agent_load::AgentLoadService;
+pub use session_affinity::SessionAffinityService;
+pub use routing_queue::RoutingQueueService;
+#[cfg(feature = "load-test")]
+pub use load_test::LoadTestService;
 
 thread_local! {
     static STATE: RefCell<CoordinatorState> = RefCell::new(CoordinatorState::default());
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CoordinatorState {
     pub agents: HashMap<String, AgentRegistration>,
     pub instruction_requests: HashMap<String, InstructionRequest>,
     pub agent_creation_results: HashMap<String, AgentCreationResult>,
     pub dedup_cache: HashMap<String, DedupEntry>,
+    /// Fanout answers collected so far, keyed by request id
+    pub fanout_partials: HashMap<String, partial_results::FanoutPartial>,
     pub routing_stats: HashMap<String, RoutingStats>,
     pub user_quotas: HashMap<String, quota_manager::UserQuota>,
+    /// Archived quota periods per principal, oldest first
+    pub usage_history: HashMap<String, Vec<quota_manager::UsageSnapshot>>,
     pub metrics: CoordinatorMetrics,
     pub config: CoordinatorConfig,
     // Autonomous coordination fields
     pub coordination_sessions: Option<HashMap<String, autonomous_coord::CoordinationSession>>,
     pub agent_capability_profiles: Option<HashMap<String, autonomous_coord::AgentCapabilityProfile>>,
-    pub agent_message_queues: Option<HashMap<String, Vec<autonomous_coord::AgentMessage>>>,
+    pub agent_message_queues: Option<HashMap<String, Vec<message_expiry::QueuedMessage>>>,
+    pub programs: HashMap<String, programs::Program>,
+    pub dead_letters: Vec<delivery::DeadLetter>,
+    /// Daily tag buckets per principal, then per tag; each principal sees only their own
+    pub principal_tag_usage: HashMap<String, HashMap<String, BTreeMap<u64, tag_analytics::TagBucket>>>,
+    pub agent_circuits: HashMap<String, AgentCircuit>,
+    pub user_preferences: HashMap<String, preferences::UserPreferences>,
+    pub audit_log: Vec<audit::AuditEntry>,
+    pub notifications: HashMap<String, Vec<notifications::Notification>>,
+    pub user_activity: HashMap<String, u64>,
+    pub reclamation_policy: reclamation::ReclamationPolicy,
+    pub reclamation_notices: HashMap<String, reclamation::ReclamationNotice>,
+    pub archived_instructions: HashMap<String, Vec<InstructionRequest>>,
+    pub agent_slas: HashMap<String, SlaTracker>,
+    pub task_ledger: HashMap<String, autonomous_coord::TaskLedgerEntry>,
+    /// Rounds of tasks per coordination session, in order
+    pub session_rounds: HashMap<String, Vec<rounds::CoordinationRound>>,
+    pub instruction_batches: HashMap<String, batches::InstructionBatch>,
+    pub capability_history: HashMap<String, Vec<CapabilityChange>>,
+    /// Bumped on every registry mutation; cursor for `get_agents_delta`
+    pub agent_registry_version: u64,
+    pub agent_changes: HashMap<String, AgentChangeMark>,
+    /// Removals at or below this version have been forgotten
+    pub agent_tombstone_floor: u64,
+    pub capability_pricing: HashMap<String, f64>,
+    pub cancellations: HashMap<String, cancellation::CancellationRecord>,
+    /// Never leaves the canister, so not part of state snapshots
+    #[serde(skip)]
+    pub secrets: HashMap<String, HashMap<String, secrets::StoredSecret>>,
+    pub verification_evidence: HashMap<String, review::VerificationRecord>,
+    /// Keyed by request id, or by root task id for delegation chains
+    pub result_provenance: HashMap<String, provenance::ResultProvenance>,
+    /// Last sequence number handed out by `IdGenerator`
+    pub id_sequence: u64,
+    /// Keyed by `<session_id>/<agent_id>`
+    pub session_chatter: HashMap<String, chatter::ChatterWindow>,
+    pub chatter_counters: chatter::ChatterCounters,
+    pub agent_applications: HashMap<String, onboarding::AgentApplication>,
+    /// Principals whose next registration bypasses the approval queue
+    pub agent_invitations: HashSet<String>,
+    pub inference_jobs: HashMap<String, jobs::InferenceJob>,
+    pub spawn_samples: Vec<slo::SpawnSample>,
+    pub spawn_slo_targets: slo::SpawnSloTargets,
+    pub slo_breaches: slo::SloBreachState,
+    pub operator_alerts: Vec<slo::OperatorAlert>,
+    /// Operator additions and overrides on top of the built-in specialization ontology
+    pub specialization_roles: HashMap<String, specializations::SpecializationRole>,
+    pub session_templates: HashMap<String, session_templates::SessionTemplate>,
+    pub warm_pool: warm_pool::WarmPoolState,
+    pub quarantined_agents: HashMap<String, quarantine::QuarantineRecord>,
+    pub safety_limits: HashMap<String, safety_limits::SafetyLimits>,
+    pub safety_usage: HashMap<String, safety_limits::DailyUsage>,
+    pub message_usage: HashMap<String, quota_manager::DailyMessageCount>,
+    pub liveness_policy: liveness::LivenessPolicy,
+    /// Agents whose health was decayed or zeroed for missing heartbeats, with the health score to restore
+    pub stale_agents: HashMap<String, f32>,
+    /// Agents the coordinator spawned or took from the warm pool, exempt from liveness until their first heartbeat
+    pub awaiting_first_heartbeat: HashSet<String>,
+    pub liveness_swept_at: u64,
+    pub capability_taxonomy: CapabilityTaxonomy,
+    /// Instruction analyses by request_id, kept so spawning executes the plan that was shown
+    pub instruction_analyses: HashMap<String, instruction_analyzer::StoredAnalysis>,
+    /// Routing requests per capability per day, for capacity forecasts
+    pub capability_demand: HashMap<String, BTreeMap<u64, u64>>,
+    /// Warm standbys keyed by the critical capability they cover
+    pub standby_assignments: HashMap<String, standby::StandbyAssignment>,
+    /// Reputation evidence per agent; outlives registrations
+    pub agent_reputation: HashMap<String, reputation::ReputationRecord>,
+    /// Disputed review verdicts keyed by the reviewed request
+    pub verification_disputes: HashMap<String, reputation::VerificationDispute>,
+    /// SIEM export streams keyed by organization principal
+    pub siem_streams: HashMap<String, siem_export::SiemStream>,
+    /// Economics reachability and the quota decisions made locally while it was down
+    pub econ_fallback: econ_fallback::EconFallbackState,
+    /// Shard layout and the registries mirrored from the other shards
+    pub sharding: sharding::ShardingState,
+    /// Registrations, removals and health changes, oldest first
+    pub registry_audit: VecDeque<registry_audit::RegistryAuditEntry>,
+    pub registry_audit_sequence: u64,
+    /// Agent records spilled to child registries, or held here for a parent
+    pub registry_shards: registry_shards::RegistryShardState,
+    /// Requests and latencies per organization per day, for service reports
+    pub service_usage: HashMap<String, BTreeMap<u64, service_report::UsageBucket>>,
+    /// Degraded-mode windows and breaker openings, oldest first
+    pub service_incidents: VecDeque<service_report::ServiceIncident>,
+    /// Organization each member principal reports under; others are their own organization
+    pub report_organizations: HashMap<String, String>,
+    /// The service report signed today, per organization
+    pub service_report_cache: HashMap<String, service_report::CachedServiceReport>,
+    /// Subscriber canisters and the registry changes queued for them
+    pub registry_events: registry_events::RegistryEventState,
+    /// Latest self-reported load per agent
+    pub agent_loads: HashMap<String, agent_load::AgentLoadReport>,
+    /// Agent each conversation was last routed to, keyed by requester and session key
+    pub session_pins: HashMap<String, session_affinity::SessionPin>,
+    /// Route requests waiting for agent capacity, in arrival order
+    pub routing_queue: Vec<routing_queue::QueuedRoute>,
+    #[cfg(feature = "load-test")]
+    pub load_test: load_test::LoadTestState,
+    /// Sessions and open tasks per agent; derived, rebuilt after restores
+    #[serde(skip)]
+    pub workload_index: workload::WorkloadIndex,
+    /// Agents per capability; derived, rebuilt after restores
+    #[serde(skip)]
+    pub capability_index: registry::CapabilityIndex,
+    #[serde(skip)]
+    pub certified_registry: certification::CertifiedRegistry,
+    #[serde(skip)]
+    pub snapshot_export: Option<snapshot::PreparedSnapshot>,
+    #[serde(skip)]
+    pub snapshot_import: Option<snapshot::PendingImport>,
+    #[serde(skip)]
+    pub registry_snapshot_export: Option<snapshot::PreparedSnapshot>,
+    #[serde(skip)]
+    pub registry_snapshot_import: Option<snapshot::PendingImport>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CoordinatorMetrics {
     pub total_routes: u64,
     pub total_agent_creations: u64,
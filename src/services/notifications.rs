@@ -0,0 +1,83 @@
+use crate::services::{with_state, with_state_mut};
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Per-principal inbox for coordinator-initiated notices
+pub struct NotificationService;
+
+/// Reason a notice was raised
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum NotificationKind {
+    ReclamationPending,
+    Reclaimed,
+    AgentPendingApproval,
+    AgentApproved,
+    AgentRejected,
+    AgentQuarantined,
+    AgentEvicted,
+    StandbyPromoted,
+    DisputeResolved,
+    AgentSuspended,
+    AgentRetired,
+}
+
+/// Notice addressed to a single principal
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct Notification {
+    pub notification_id: String,
+    pub principal: String,
+    pub kind: NotificationKind,
+    pub message: String,
+    pub created_at: u64,
+    pub read: bool,
+}
+
+impl NotificationService {
+    const MAX_PER_PRINCIPAL: usize = 100;
+
+    /// Queue a notice, dropping the principal's oldest once the inbox is full
+    pub fn notify(principal: &str, kind: NotificationKind, message: String) {
+        let now = time();
+        with_state_mut(|state| {
+            let inbox = state.notifications.entry(principal.to_string()).or_default();
+            if inbox.len() >= Self::MAX_PER_PRINCIPAL {
+                inbox.remove(0);
+            }
+            inbox.push(Notification {
+                notification_id: format!("notice_{}_{}", now, inbox.len()),
+                principal: principal.to_string(),
+                kind,
+                message,
+                created_at: now,
+                read: false,
+            });
+        });
+    }
+
+    /// Notices for a principal, newest first
+    pub fn list(principal: &str, unread_only: bool) -> Vec<Notification> {
+        with_state(|state| {
+            state.notifications
+                .get(principal)
+                .map(|inbox| {
+                    inbox.iter()
+                        .rev()
+                        .filter(|n| !unread_only || !n.read)
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn mark_read(principal: &str, notification_id: &str) -> Result<(), String> {
+        with_state_mut(|state| {
+            state.notifications
+                .get_mut(principal)
+                .and_then(|inbox| inbox.iter_mut().find(|n| n.notification_id == notification_id))
+                .map(|n| n.read = true)
+                .ok_or_else(|| "Notification not found".to_string())
+        })
+    }
+}
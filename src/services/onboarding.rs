@@ -0,0 +1,188 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, NotificationService, QuarantineService, RegistryService};
+use crate::services::audit::{AuditAction, AuditService};
+use crate::services::notifications::NotificationKind;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Approval queue for curated deployments: while approval is required, direct
+/// registrations wait here instead of entering the registry, so routing never
+/// sees them. Agents spawned by the coordinator itself are not queued.
+pub struct OnboardingService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum ApplicationStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// Registration awaiting, or decided by, an admin
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentApplication {
+    pub registration: AgentRegistration,
+    /// Principal that submitted the registration and receives the decision notice
+    pub registrant: String,
+    pub status: ApplicationStatus,
+    pub submitted_at: u64,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<u64>,
+    pub rejection_reason: Option<String>,
+}
+
+impl OnboardingService {
+    pub fn approval_required() -> bool {
+        with_state(|state| state.config.require_agent_approval)
+    }
+
+    /// Invited principals skip the queue for their next registration
+    pub fn invite(principal: &str) -> Result<(), String> {
+        let principal = principal.trim();
+        if principal.is_empty() {
+            return Err("Principal is required".to_string());
+        }
+        with_state_mut(|state| state.agent_invitations.insert(principal.to_string()));
+        Ok(())
+    }
+
+    /// Consume the registrant's invitation, if any
+    pub fn redeem_invitation(registrant: &str) -> bool {
+        with_state_mut(|state| state.agent_invitations.remove(registrant))
+    }
+
+    /// Hold a fully prepared registration until an admin decides on it. It stays
+    /// `Registered` while queued and only becomes `Ready`, and routable, on approval.
+    pub fn enqueue(registrant: &str, registration: AgentRegistration) {
+        let agent_id = registration.agent_id.clone();
+        let registration = AgentRegistration { lifecycle: Some(AgentLifecycle::Registered), ..registration };
+        with_state_mut(|state| {
+            state.agent_applications.insert(agent_id.clone(), AgentApplication {
+                registration,
+                registrant: registrant.to_string(),
+                status: ApplicationStatus::Pending,
+                submitted_at: time(),
+                decided_by: None,
+                decided_at: None,
+                rejection_reason: None,
+            });
+        });
+        NotificationService::notify(
+            registrant,
+            NotificationKind::AgentPendingApproval,
+            format!("Agent {} is awaiting admin approval", agent_id),
+        );
+    }
+
+    pub fn approve(admin: &str, agent_id: &str) -> Result<AgentRegistration, String> {
+        let now = time();
+        let canister_id = Self::get_application(agent_id).map(|a| a.registration.canister_id).unwrap_or_default();
+        QuarantineService::check_registration(&canister_id)?;
+        let (registrant, registration) = with_state_mut(|state| {
+            let application = Self::pending_mut(&mut state.agent_applications, agent_id)?;
+            application.status = ApplicationStatus::Approved;
+            application.decided_by = Some(admin.to_string());
+            application.decided_at = Some(now);
+
+            let mut registration = application.registration.clone();
+            let registrant = application.registrant.clone();
+            // Health and liveness start from the moment the agent becomes routable
+            registration.registered_at = now;
+            registration.last_seen = now;
+            RegistryService::insert_registration(state, registration, admin);
+            RegistryService::transition_lifecycle_in(state, agent_id, AgentLifecycle::Ready, admin, now)?;
+            let registration = state.agents.get(agent_id).cloned().ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            Ok::<_, String>((registrant, registration))
+        })?;
+
+        AuditService::record(&registrant, AuditAction::AgentApproved, agent_id, format!("Approved by {}", admin));
+        NotificationService::notify(
+            &registrant,
+            NotificationKind::AgentApproved,
+            format!("Agent {} was approved and can now receive requests", agent_id),
+        );
+        Ok(registration)
+    }
+
+    pub fn reject(admin: &str, agent_id: &str, reason: String) -> Result<(), String> {
+        let reason = reason.trim().to_string();
+        if reason.is_empty() {
+            return Err("A rejection reason is required".to_string());
+        }
+        let registrant = with_state_mut(|state| {
+            let application = Self::pending_mut(&mut state.agent_applications, agent_id)?;
+            application.status = ApplicationStatus::Rejected;
+            application.decided_by = Some(admin.to_string());
+            application.decided_at = Some(time());
+            application.rejection_reason = Some(reason.clone());
+            Ok::<_, String>(application.registrant.clone())
+        })?;
+
+        AuditService::record(&registrant, AuditAction::AgentRejected, agent_id, reason.clone());
+        NotificationService::notify(
+            &registrant,
+            NotificationKind::AgentRejected,
+            format!("Agent {} was rejected: {}", agent_id, reason),
+        );
+        Ok(())
+    }
+
+    fn pending_mut<'a>(
+        applications: &'a mut HashMap<String, AgentApplication>,
+        agent_id: &str,
+    ) -> Result<&'a mut AgentApplication, String> {
+        let application = applications
+            .get_mut(agent_id)
+            .ok_or_else(|| "No registration found for agent".to_string())?;
+        if application.status != ApplicationStatus::Pending {
+            return Err(format!("Registration was already {:?}", application.status));
+        }
+        Ok(application)
+    }
+
+    /// Pending applications, oldest first
+    pub fn list_pending() -> Vec<AgentApplication> {
+        with_state(|state| {
+            let mut pending: Vec<AgentApplication> = state.agent_applications
+                .values()
+                .filter(|a| a.status == ApplicationStatus::Pending)
+                .cloned()
+                .collect();
+            pending.sort_by_key(|a| a.submitted_at);
+            pending
+        })
+    }
+
+    pub fn get_application(agent_id: &str) -> Option<AgentApplication> {
+        with_state(|state| state.agent_applications.get(agent_id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn application(status: ApplicationStatus) -> AgentApplication {
+        AgentApplication {
+            registration: AgentRegistration { capabilities: vec![], ..AgentRegistration::test("agent_1") },
+            registrant: "owner".to_string(),
+            status,
+            submitted_at: 0,
+            decided_by: None,
+            decided_at: None,
+            rejection_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_only_pending_applications_can_be_decided() {
+        let mut applications = HashMap::new();
+        applications.insert("agent_1".to_string(), application(ApplicationStatus::Pending));
+        assert!(OnboardingService::pending_mut(&mut applications, "agent_1").is_ok());
+        assert!(OnboardingService::pending_mut(&mut applications, "agent_2").is_err());
+
+        applications.insert("agent_1".to_string(), application(ApplicationStatus::Rejected));
+        assert!(OnboardingService::pending_mut(&mut applications, "agent_1").is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::services::{with_state, with_state_mut};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Per-agent answers collected by a fanout, persisted as each one arrives. A fanout
+/// that traps after some agents answered, or a client retry of a finished one,
+/// reuses those answers under the same request id instead of calling and billing
+/// the agents again. Answers are only reused for the same payload and capabilities.
+pub struct PartialResultService;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanoutPartial {
+    /// Hash of the payload and required capabilities the answers were produced for
+    pub request_sha256: String,
+    pub answers: BTreeMap<String, CachedAnswer>,
+    pub started_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAnswer {
+    pub output: String,
+    /// Elapsed milliseconds of the original call, so window checks behave as on the first run
+    pub elapsed: u64,
+    pub score: f32,
+}
+
+impl PartialResultService {
+    const TTL_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+    const MAX_ENTRIES: usize = 5000;
+
+    pub fn request_hash(payload: &[u8], capabilities: &[String]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(payload);
+        for capability in capabilities {
+            hasher.update([0u8]);
+            hasher.update(capability.as_bytes());
+        }
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Answers already collected for this exact request; a changed request under the
+    /// same id starts over
+    pub fn begin(request_id: &str, request_sha256: &str) -> BTreeMap<String, CachedAnswer> {
+        let now = time();
+        let answers = with_state_mut(|state| {
+            state.fanout_partials.retain(|_, partial| partial.expires_at > now);
+            match state.fanout_partials.get(request_id) {
+                Some(partial) if partial.request_sha256 == request_sha256 => partial.answers.clone(),
+                _ => {
+                    if state.fanout_partials.len() >= Self::MAX_ENTRIES {
+                        let oldest = state.fanout_partials
+                            .iter()
+                            .min_by_key(|(_, partial)| partial.started_at)
+                            .map(|(id, _)| id.clone());
+                        if let Some(oldest) = oldest {
+                            state.fanout_partials.remove(&oldest);
+                        }
+                    }
+                    state.fanout_partials.insert(request_id.to_string(), FanoutPartial {
+                        request_sha256: request_sha256.to_string(),
+                        answers: BTreeMap::new(),
+                        started_at: now,
+                        expires_at: now + Self::TTL_NS,
+                    });
+                    BTreeMap::new()
+                }
+            }
+        });
+        if !answers.is_empty() {
+            Metrics::increment_counter("fanout_partial_reuses_total");
+        }
+        answers
+    }
+
+    pub fn record(request_id: &str, agent_id: &str, answer: CachedAnswer) {
+        with_state_mut(|state| {
+            if let Some(partial) = state.fanout_partials.get_mut(request_id) {
+                partial.answers.insert(agent_id.to_string(), answer);
+            }
+        });
+    }
+
+    /// How many answers a retry of this request would reuse, for billing only the calls still to make
+    pub fn cached_answers(request_id: &str, request_sha256: &str) -> usize {
+        let now = time();
+        with_state(|state| {
+            state.fanout_partials
+                .get(request_id)
+                .filter(|partial| partial.expires_at > now && partial.request_sha256 == request_sha256)
+                .map_or(0, |partial| partial.answers.len())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_hash_covers_payload_and_capabilities() {
+        let caps = vec!["coding".to_string()];
+        let hash = PartialResultService::request_hash(b"prompt", &caps);
+        assert_eq!(hash, PartialResultService::request_hash(b"prompt", &caps));
+        assert_ne!(hash, PartialResultService::request_hash(b"other", &caps));
+        assert_ne!(hash, PartialResultService::request_hash(b"prompt", &["testing".to_string()]));
+    }
+}
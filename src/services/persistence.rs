@@ -0,0 +1,145 @@
+use crate::services::{with_state, with_state_mut, CoordinatorState, RegistryService, WorkloadService};
+use crate::services::secrets::StoredSecret;
+use crate::infra::Log;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::reader::Reader;
+use ic_stable_structures::writer::Writer;
+use ic_stable_structures::{DefaultMemoryImpl, Memory};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Carries the coordinator state across canister upgrades. `pre_upgrade` writes the
+/// whole state, secrets included, to stable memory as a length-prefixed CBOR image and
+/// `post_upgrade` reads it back. Fields added since the image was written take their
+/// defaults; an image that cannot be decoded traps so the upgrade is rolled back
+/// instead of starting empty.
+pub struct PersistenceService;
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+}
+
+/// Virtual memory holding the upgrade image; later stable structures take other ids
+const UPGRADE_IMAGE: MemoryId = MemoryId::new(0);
+/// Index and entries of the registry audit archive, which lives outside the upgrade image
+pub(crate) const REGISTRY_AUDIT_INDEX: MemoryId = MemoryId::new(1);
+pub(crate) const REGISTRY_AUDIT_DATA: MemoryId = MemoryId::new(2);
+
+/// Bumped when the image layout changes in a way `serde(default)` cannot absorb
+const IMAGE_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct UpgradeImageRef<'a> {
+    version: u32,
+    state: &'a CoordinatorState,
+    /// Skipped by the state's own serialization so snapshots never carry them
+    secrets: &'a HashMap<String, HashMap<String, StoredSecret>>,
+}
+
+#[derive(Deserialize)]
+struct UpgradeImage {
+    version: u32,
+    state: CoordinatorState,
+    #[serde(default)]
+    secrets: HashMap<String, HashMap<String, StoredSecret>>,
+}
+
+impl PersistenceService {
+    /// Virtual memory `id`, for stable structures kept outside the upgrade image
+    pub(crate) fn memory(id: MemoryId) -> VirtualMemory<DefaultMemoryImpl> {
+        MEMORY_MANAGER.with(|manager| manager.borrow().get(id))
+    }
+
+    pub fn save() -> Result<u64, String> {
+        let bytes = with_state(Self::encode)?;
+        MEMORY_MANAGER.with(|manager| Self::write_image(&mut manager.borrow().get(UPGRADE_IMAGE), &bytes))?;
+        Ok(bytes.len() as u64)
+    }
+
+    /// Replace the state with the saved image; `Ok(false)` when none was ever written,
+    /// as on the first upgrade from a build without persistence
+    pub fn restore() -> Result<bool, String> {
+        let Some(bytes) = MEMORY_MANAGER.with(|manager| Self::read_image(&manager.borrow().get(UPGRADE_IMAGE)))? else {
+            return Ok(false);
+        };
+        let restored = Self::decode(&bytes)?;
+        Log::configure(restored.config.logging.clone());
+        with_state_mut(|state| *state = restored);
+        Ok(true)
+    }
+
+    fn encode(state: &CoordinatorState) -> Result<Vec<u8>, String> {
+        let image = UpgradeImageRef { version: IMAGE_VERSION, state, secrets: &state.secrets };
+        serde_cbor::to_vec(&image).map_err(|e| format!("Failed to encode state for upgrade: {}", e))
+    }
+
+    fn decode(bytes: &[u8]) -> Result<CoordinatorState, String> {
+        let image: UpgradeImage = serde_cbor::from_slice(bytes)
+            .map_err(|e| format!("Failed to decode upgrade image: {}", e))?;
+        if image.version > IMAGE_VERSION {
+            return Err(format!("Upgrade image version {} is newer than this build supports ({})", image.version, IMAGE_VERSION));
+        }
+        let mut state = image.state;
+        state.secrets = image.secrets;
+        WorkloadService::rebuild(&mut state);
+        RegistryService::rebuild_capability_index(&mut state);
+        Ok(state)
+    }
+
+    fn write_image(memory: &mut VirtualMemory<DefaultMemoryImpl>, bytes: &[u8]) -> Result<(), String> {
+        let mut writer = Writer::new(memory, 0);
+        writer.write(&(bytes.len() as u64).to_le_bytes())
+            .and_then(|_| writer.write(bytes))
+            .map_err(|e| format!("Failed to write upgrade image to stable memory: {:?}", e))
+    }
+
+    fn read_image(memory: &VirtualMemory<DefaultMemoryImpl>) -> Result<Option<Vec<u8>>, String> {
+        if memory.size() == 0 {
+            return Ok(None);
+        }
+        let mut reader = Reader::new(memory, 0);
+        let mut length = [0u8; 8];
+        std::io::Read::read_exact(&mut reader, &mut length)
+            .map_err(|e| format!("Failed to read upgrade image length: {}", e))?;
+        let length = u64::from_le_bytes(length) as usize;
+        if length == 0 {
+            return Ok(None);
+        }
+        let mut bytes = vec![0u8; length];
+        std::io::Read::read_exact(&mut reader, &mut bytes)
+            .map_err(|e| format!("Failed to read upgrade image: {}", e))?;
+        Ok(Some(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_round_trips_through_stable_memory() {
+        let mut state = CoordinatorState { id_sequence: 7, ..Default::default() };
+        state.user_activity.insert("user".to_string(), 3);
+        state.secrets.entry("user".to_string()).or_default().insert("api_key".to_string(), StoredSecret {
+            value: "s3cret".to_string(),
+            created_at: 1,
+            updated_at: 1,
+            last_accessed_at: None,
+        });
+        let bytes = PersistenceService::encode(&state).unwrap();
+
+        let manager = MemoryManager::init(DefaultMemoryImpl::default());
+        let mut memory = manager.get(UPGRADE_IMAGE);
+        assert_eq!(PersistenceService::read_image(&memory).unwrap(), None);
+        PersistenceService::write_image(&mut memory, &bytes).unwrap();
+        let read = PersistenceService::read_image(&memory).unwrap().unwrap();
+
+        let restored = PersistenceService::decode(&read).unwrap();
+        assert_eq!(restored.id_sequence, 7);
+        assert_eq!(restored.user_activity.get("user"), Some(&3));
+        assert_eq!(restored.secrets["user"]["api_key"].value, "s3cret");
+        assert!(PersistenceService::decode(&read[1..]).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut};
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use candid::CandidType;
+
+/// Per-user default preferences consulted when a request omits explicit values
+pub struct PreferencesService;
+
+/// Account-level defaults for a user
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct UserPreferences {
+    pub preferred_models: Vec<String>,
+    pub default_routing_mode: Option<RoutingMode>,
+    pub default_decode_profile: Option<DecodeProfile>,
+    pub notifications: NotificationSettings,
+    pub privacy: PrivacySettings,
+    pub updated_at: u64,
+}
+
+/// Where and when to push completion notices
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct NotificationSettings {
+    pub notify_on_completion: bool,
+    pub completion_callback: Option<CallbackTarget>,
+}
+
+/// Data retention choices
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct PrivacySettings {
+    pub retain_instruction_history: bool,
+    pub include_in_tag_analytics: bool,
+}
+
+impl Default for PrivacySettings {
+    fn default() -> Self {
+        Self { retain_instruction_history: true, include_in_tag_analytics: true }
+    }
+}
+
+impl PreferencesService {
+    const MAX_PREFERRED_MODELS: usize = 10;
+    
+    pub fn set_preferences(user_principal: &str, preferences: UserPreferences) -> Result<(), String> {
+        if preferences.preferred_models.len() > Self::MAX_PREFERRED_MODELS {
+            return Err(format!("At most {} preferred models are allowed", Self::MAX_PREFERRED_MODELS));
+        }
+        if preferences.notifications.notify_on_completion && preferences.notifications.completion_callback.is_none() {
+            return Err("completion_callback is required when notify_on_completion is enabled".to_string());
+        }
+        
+        let mut preferences = preferences;
+        preferences.updated_at = time();
+        with_state_mut(|state| {
+            state.user_preferences.insert(user_principal.to_string(), preferences);
+        });
+        Ok(())
+    }
+    
+    /// Stored preferences, or defaults for users who never set any
+    pub fn get_preferences(user_principal: &str) -> UserPreferences {
+        with_state(|state| state.user_preferences.get(user_principal).cloned()).unwrap_or_default()
+    }
+    
+    /// Fill in what a routing request left out from the requester's stored defaults. The
+    /// routing mode has no unset value, so a plain Unicast request without a named
+    /// strategy takes the default mode; name the `unicast` strategy to opt out.
+    pub fn apply_routing_defaults(user_principal: &str, request: &mut RouteRequest) {
+        let prefs = Self::get_preferences(user_principal);
+        Self::apply_routing_defaults_from(&prefs, request);
+    }
+
+    fn apply_routing_defaults_from(prefs: &UserPreferences, request: &mut RouteRequest) {
+        if let Some(mode) = &prefs.default_routing_mode {
+            if matches!(request.routing_mode, RoutingMode::Unicast) && request.strategy.is_none() {
+                request.routing_mode = mode.clone();
+            }
+        }
+        if request.model_preference.is_none() && !prefs.preferred_models.is_empty() {
+            request.model_preference = Some(ModelPreference {
+                fallback_chain: prefs.preferred_models.clone(),
+                allow_any_model: true,
+            });
+        }
+        if request.callback.is_none() && prefs.notifications.notify_on_completion {
+            request.callback = prefs.notifications.completion_callback.clone();
+        }
+    }
+
+    /// Callback to use when the request did not carry one
+    pub fn default_callback(user_principal: &str) -> Option<CallbackTarget> {
+        let prefs = Self::get_preferences(user_principal);
+        if prefs.notifications.notify_on_completion {
+            prefs.notifications.completion_callback
+        } else {
+            None
+        }
+    }
+}
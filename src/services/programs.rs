@@ -0,0 +1,249 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, CoordinatorState, IdGenerator};
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use candid::CandidType;
+
+/// Program service grouping related instruction requests under one umbrella
+pub struct ProgramService;
+
+/// Umbrella entity for a multi-request initiative
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct Program {
+    pub program_id: String,
+    pub owner_principal: String,
+    pub name: String,
+    pub request_ids: Vec<String>,
+    pub agent_budget: Option<u32>,
+    pub created_at: u64,
+    pub last_activity: u64,
+    /// Agents held against the budget by spawns still in flight
+    #[serde(default)]
+    pub reserved_agents: u32,
+}
+
+/// Roll-up view of a program across its instruction requests
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ProgramStatus {
+    pub program_id: String,
+    pub name: String,
+    pub status: AgentCreationStatus,
+    pub total_requests: u32,
+    pub completed_requests: u32,
+    pub in_progress_requests: u32,
+    pub failed_requests: u32,
+    pub agents_created: u32,
+    pub agent_budget: Option<u32>,
+    pub budget_remaining: Option<u32>,
+    pub coordination_networks: Vec<String>,
+    pub last_activity: u64,
+}
+
+impl ProgramService {
+    /// Create a new program owned by the caller
+    pub fn create_program(owner_principal: &str, name: String, agent_budget: Option<u32>) -> Result<String, String> {
+        if name.trim().is_empty() {
+            return Err("Program name is required".to_string());
+        }
+        
+        let now = time();
+        let program_id = IdGenerator::next("program", owner_principal);
+        let program = Program {
+            program_id: program_id.clone(),
+            owner_principal: owner_principal.to_string(),
+            name,
+            request_ids: Vec::new(),
+            agent_budget,
+            created_at: now,
+            last_activity: now,
+            reserved_agents: 0,
+        };
+        
+        with_state_mut(|state| {
+            state.programs.insert(program_id.clone(), program);
+        });
+        
+        Ok(program_id)
+    }
+    
+    /// Attach an existing instruction request to a program
+    pub fn attach_request(owner_principal: &str, program_id: &str, request_id: &str) -> Result<(), String> {
+        with_state_mut(|state| {
+            let owns_request = state.instruction_requests
+                .get(request_id)
+                .map(|req| req.user_principal == owner_principal)
+                .unwrap_or(false);
+            if !owns_request {
+                return Err("Instruction request not found or access denied".to_string());
+            }
+            
+            let program = state.programs
+                .get_mut(program_id)
+                .filter(|p| p.owner_principal == owner_principal)
+                .ok_or_else(|| "Program not found or access denied".to_string())?;
+            
+            if !program.request_ids.iter().any(|id| id == request_id) {
+                program.request_ids.push(request_id.to_string());
+            }
+            program.last_activity = time();
+            Ok(())
+        })
+    }
+    
+    /// Hold `agents` of the program's budget for a spawn about to start. The hold is taken
+    /// before the spawn awaits anything, so concurrent spawns cannot overrun the budget
+    /// together; `settle_reservation` releases it once the spawn has finished.
+    pub fn reserve_budget(owner_principal: &str, program_id: &str, agents: u32) -> Result<(), String> {
+        with_state_mut(|state| Self::reserve_budget_in(state, owner_principal, program_id, agents))
+    }
+
+    fn reserve_budget_in(state: &mut CoordinatorState, owner_principal: &str, program_id: &str, agents: u32) -> Result<(), String> {
+        let program = state.programs
+            .get(program_id)
+            .filter(|p| p.owner_principal == owner_principal)
+            .ok_or_else(|| "Program not found or access denied".to_string())?;
+        if let Some(remaining) = Self::budget_remaining(state, program) {
+            if remaining == 0 {
+                return Err("Program agent budget exhausted".to_string());
+            }
+            if agents > remaining {
+                return Err(format!("Program agent budget allows {} more agents, {} requested", remaining, agents));
+            }
+        }
+        if let Some(program) = state.programs.get_mut(program_id) {
+            program.reserved_agents = program.reserved_agents.saturating_add(agents);
+        }
+        Ok(())
+    }
+
+    /// Release a hold taken by `reserve_budget`, attaching `request_id` when the spawn
+    /// succeeded so its agents count against the budget from then on
+    pub fn settle_reservation(owner_principal: &str, program_id: &str, agents: u32, request_id: Option<&str>) -> Result<(), String> {
+        with_state_mut(|state| {
+            if let Some(program) = state.programs.get_mut(program_id) {
+                program.reserved_agents = program.reserved_agents.saturating_sub(agents);
+            }
+        });
+        match request_id {
+            Some(request_id) => Self::attach_request(owner_principal, program_id, request_id),
+            None => Ok(()),
+        }
+    }
+
+    /// Agents created by the program's attached requests
+    fn agents_created(state: &CoordinatorState, program: &Program) -> u32 {
+        program.request_ids
+            .iter()
+            .filter_map(|id| state.agent_creation_results.get(id))
+            .map(|r| r.created_agents.len() as u32)
+            .sum()
+    }
+
+    /// Budget left after created agents and in-flight holds; `None` when unbudgeted
+    fn budget_remaining(state: &CoordinatorState, program: &Program) -> Option<u32> {
+        program.agent_budget.map(|budget| {
+            budget
+                .saturating_sub(Self::agents_created(state, program))
+                .saturating_sub(program.reserved_agents)
+        })
+    }
+    
+    /// Roll up request statuses, agent counts and networks for a program
+    pub fn get_program_status(owner_principal: &str, program_id: &str) -> Result<ProgramStatus, String> {
+        with_state(|state| {
+            let program = state.programs
+                .get(program_id)
+                .filter(|p| p.owner_principal == owner_principal)
+                .ok_or_else(|| "Program not found or access denied".to_string())?;
+            
+            let results: Vec<&AgentCreationResult> = program.request_ids
+                .iter()
+                .filter_map(|id| state.agent_creation_results.get(id))
+                .collect();
+            
+            let count = |status: AgentCreationStatus| results.iter().filter(|r| r.status == status).count() as u32;
+            let completed_requests = count(AgentCreationStatus::Completed);
+            let failed_requests = count(AgentCreationStatus::Failed) + count(AgentCreationStatus::QuotaExceeded);
+            let in_progress_requests = program.request_ids.len() as u32 - completed_requests - failed_requests;
+            
+            let agents_created = Self::agents_created(state, program);
+            let coordination_networks = results
+                .iter()
+                .filter_map(|r| r.coordination_network_id.clone())
+                .collect();
+            
+            Ok(ProgramStatus {
+                program_id: program.program_id.clone(),
+                name: program.name.clone(),
+                status: Self::roll_up_status(in_progress_requests, completed_requests, failed_requests),
+                total_requests: program.request_ids.len() as u32,
+                completed_requests,
+                in_progress_requests,
+                failed_requests,
+                agents_created,
+                agent_budget: program.agent_budget,
+                budget_remaining: Self::budget_remaining(state, program),
+                coordination_networks,
+                last_activity: program.last_activity,
+            })
+        })
+    }
+    
+    /// List programs owned by a principal
+    pub fn list_programs(owner_principal: &str) -> Vec<Program> {
+        with_state(|state| {
+            state.programs
+                .values()
+                .filter(|p| p.owner_principal == owner_principal)
+                .cloned()
+                .collect()
+        })
+    }
+    
+    /// Overall program status: in progress while anything is pending, failed only if everything failed
+    pub(crate) fn roll_up_status(in_progress: u32, completed: u32, failed: u32) -> AgentCreationStatus {
+        if in_progress > 0 {
+            AgentCreationStatus::InProgress
+        } else if failed > 0 && completed == 0 {
+            AgentCreationStatus::Failed
+        } else {
+            AgentCreationStatus::Completed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roll_up_status() {
+        assert_eq!(ProgramService::roll_up_status(1, 3, 0), AgentCreationStatus::InProgress);
+        assert_eq!(ProgramService::roll_up_status(0, 0, 2), AgentCreationStatus::Failed);
+        assert_eq!(ProgramService::roll_up_status(0, 2, 1), AgentCreationStatus::Completed);
+        assert_eq!(ProgramService::roll_up_status(0, 0, 0), AgentCreationStatus::Completed);
+    }
+
+    #[test]
+    fn test_reservations_hold_budget() {
+        let mut state = CoordinatorState::default();
+        state.programs.insert("p".to_string(), Program {
+            program_id: "p".to_string(),
+            owner_principal: "owner".to_string(),
+            name: "launch".to_string(),
+            request_ids: vec![],
+            agent_budget: Some(5),
+            created_at: 0,
+            last_activity: 0,
+            reserved_agents: 0,
+        });
+
+        assert!(ProgramService::reserve_budget_in(&mut state, "other", "p", 1).is_err());
+        assert!(ProgramService::reserve_budget_in(&mut state, "owner", "p", 3).is_ok());
+        // A second spawn started before the first finishes sees the held agents
+        assert!(ProgramService::reserve_budget_in(&mut state, "owner", "p", 3).is_err());
+        assert!(ProgramService::reserve_budget_in(&mut state, "owner", "p", 2).is_ok());
+        assert!(ProgramService::reserve_budget_in(&mut state, "owner", "p", 1).is_err());
+        assert_eq!(state.programs["p"].reserved_agents, 5);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::services::{with_state, with_state_mut};
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Provenance chains for results that pass through several agents. Each step names
+/// the agent, the part of the result it touched and hashes of what went in and came
+/// out; `link_sha256` chains every step to the one before, so the chain head commits
+/// to the whole history. Only hashes are kept, never the content itself.
+pub struct ProvenanceService;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum ProvenanceStage {
+    Map,
+    Reduce,
+    Fanout,
+    Review,
+    Repair,
+    Delegation,
+    TaskResult,
+    Chain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ProvenanceStep {
+    pub sequence: u32,
+    pub stage: ProvenanceStage,
+    pub agent_id: String,
+    /// Which part of the result the step produced or modified, e.g. `chunk 3`
+    pub part: String,
+    pub input_sha256: Option<String>,
+    pub output_sha256: Option<String>,
+    pub recorded_at: u64,
+    pub link_sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ResultProvenance {
+    pub request_id: String,
+    pub steps: Vec<ProvenanceStep>,
+    pub final_output_sha256: Option<String>,
+    /// Link hash of the last step
+    pub chain_sha256: String,
+    pub updated_at: u64,
+}
+
+/// One step as reported by a routing stage
+pub struct StepRecord<'a> {
+    pub stage: ProvenanceStage,
+    pub agent_id: &'a str,
+    pub part: String,
+    pub input: Option<&'a str>,
+    pub output: Option<&'a str>,
+}
+
+impl ProvenanceService {
+    const MAX_CHAINS: usize = 5000;
+    const MAX_STEPS: usize = 256;
+
+    /// Start a fresh chain, dropping whatever an earlier run under the same id recorded
+    pub fn begin(request_id: &str) {
+        with_state_mut(|state| state.result_provenance.remove(request_id));
+    }
+
+    pub fn record(request_id: &str, step: StepRecord) {
+        let now = time();
+        with_state_mut(|state| {
+            if !state.result_provenance.contains_key(request_id) && state.result_provenance.len() >= Self::MAX_CHAINS {
+                // Forget the chain that has been idle the longest
+                let oldest = state.result_provenance
+                    .values()
+                    .min_by_key(|chain| chain.updated_at)
+                    .map(|chain| chain.request_id.clone());
+                if let Some(oldest) = oldest {
+                    state.result_provenance.remove(&oldest);
+                }
+            }
+            let chain = state.result_provenance
+                .entry(request_id.to_string())
+                .or_insert_with(|| ResultProvenance {
+                    request_id: request_id.to_string(),
+                    steps: Vec::new(),
+                    final_output_sha256: None,
+                    chain_sha256: String::new(),
+                    updated_at: now,
+                });
+            Self::append(chain, step, now);
+        });
+    }
+
+    fn append(chain: &mut ResultProvenance, step: StepRecord, now: u64) {
+        if chain.steps.len() >= Self::MAX_STEPS {
+            return;
+        }
+        let mut recorded = ProvenanceStep {
+            sequence: chain.steps.len() as u32,
+            stage: step.stage,
+            agent_id: step.agent_id.to_string(),
+            part: step.part,
+            input_sha256: step.input.map(|text| Self::digest(text.as_bytes())),
+            output_sha256: step.output.map(|text| Self::digest(text.as_bytes())),
+            recorded_at: now,
+            link_sha256: String::new(),
+        };
+        recorded.link_sha256 = Self::link(&chain.chain_sha256, &recorded);
+        chain.chain_sha256 = recorded.link_sha256.clone();
+        chain.steps.push(recorded);
+        chain.updated_at = now;
+    }
+
+    /// Hash of `step` chained onto the link before it
+    fn link(previous: &str, step: &ProvenanceStep) -> String {
+        let link = format!(
+            "{}|{}|{:?}|{}|{}|{}|{}|{}",
+            previous,
+            step.sequence,
+            step.stage,
+            step.agent_id,
+            step.part,
+            step.input_sha256.as_deref().unwrap_or_default(),
+            step.output_sha256.as_deref().unwrap_or_default(),
+            step.recorded_at,
+        );
+        Self::digest(link.as_bytes())
+    }
+
+    /// Attach the final result; returns the chain head to hand back with it
+    pub fn finalize(request_id: &str, final_output: &str) -> Option<String> {
+        with_state_mut(|state| {
+            let chain = state.result_provenance.get_mut(request_id)?;
+            chain.final_output_sha256 = Some(Self::digest(final_output.as_bytes()));
+            chain.updated_at = time();
+            Some(chain.chain_sha256.clone())
+        })
+    }
+
+    pub fn get(request_id: &str) -> Option<ResultProvenance> {
+        with_state(|state| state.result_provenance.get(request_id).cloned())
+    }
+
+    /// Recompute every link; false when any step was altered, dropped or reordered
+    pub fn verify(chain: &ResultProvenance) -> bool {
+        let mut previous = String::new();
+        for (index, step) in chain.steps.iter().enumerate() {
+            if step.sequence as usize != index || Self::link(&previous, step) != step.link_sha256 {
+                return false;
+            }
+            previous = step.link_sha256.clone();
+        }
+        previous == chain.chain_sha256
+    }
+
+    fn digest(bytes: &[u8]) -> String {
+        Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_links_every_step() {
+        let mut chain = ResultProvenance {
+            request_id: "r".to_string(),
+            steps: Vec::new(),
+            final_output_sha256: None,
+            chain_sha256: String::new(),
+            updated_at: 0,
+        };
+        let step = |stage, agent_id, part: &str, output| StepRecord {
+            stage,
+            agent_id,
+            part: part.to_string(),
+            input: Some("chunk"),
+            output,
+        };
+        ProvenanceService::append(&mut chain, step(ProvenanceStage::Map, "mapper", "chunk 0", Some("partial")), 1);
+        ProvenanceService::append(&mut chain, step(ProvenanceStage::Reduce, "reducer", "reduced output", Some("final")), 2);
+        assert_eq!(chain.steps.len(), 2);
+        assert_eq!(chain.chain_sha256, chain.steps[1].link_sha256);
+        assert!(ProvenanceService::verify(&chain));
+
+        chain.steps[0].agent_id = "impostor".to_string();
+        assert!(!ProvenanceService::verify(&chain));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::services::{with_state, with_state_mut, AuditService, AutonomousCoordinationService, CoordinatorState, NotificationService, RegistryService};
+use crate::services::audit::AuditAction;
+use crate::services::autonomous_coord::TaskStatus;
+use crate::services::jobs::JobStatus;
+use crate::services::notifications::NotificationKind;
+use crate::services::registry_events::{RegistryEventKind, RegistryEventService};
+use crate::domain::{AgentRegistration, AgentState};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Incident kill switch: a quarantined agent stays in the registry for inspection but
+/// is never selected, loses its queued messages and running jobs, has its open tasks
+/// handed to other agents, and its canister cannot register again until released.
+pub struct QuarantineService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct QuarantineRecord {
+    pub agent_id: String,
+    pub canister_id: String,
+    pub reason: String,
+    pub quarantined_by: String,
+    pub quarantined_at: u64,
+    /// Queued messages dropped and running jobs failed
+    pub cancelled_deliveries: u32,
+    pub reassigned_tasks: Vec<String>,
+    /// Open tasks no other agent could take; left awaiting reassignment
+    pub unassigned_tasks: Vec<String>,
+}
+
+impl QuarantineService {
+    /// Refuse registrations from a canister that hosts a quarantined agent
+    pub fn check_registration(canister_id: &str) -> Result<(), String> {
+        with_state(|state| match Self::quarantine_for_canister(state, canister_id) {
+            Some(record) => Err(format!(
+                "Quarantined: canister {} hosts quarantined agent {} ({})",
+                canister_id, record.agent_id, record.reason
+            )),
+            None => Ok(()),
+        })
+    }
+
+    fn quarantine_for_canister<'a>(state: &'a CoordinatorState, canister_id: &str) -> Option<&'a QuarantineRecord> {
+        state.quarantined_agents.values().find(|record| record.canister_id == canister_id)
+    }
+
+    pub async fn quarantine(admin: &str, agent_id: &str, reason: String) -> Result<QuarantineRecord, String> {
+        if reason.trim().is_empty() {
+            return Err("A quarantine reason is required".to_string());
+        }
+        let agent = RegistryService::get_agent(agent_id)?;
+        let now = time();
+        let (cancelled_deliveries, open_tasks) = with_state_mut(|state| {
+            if state.quarantined_agents.contains_key(agent_id) {
+                return Err(format!("Agent {} is already quarantined", agent_id));
+            }
+            if agent.agent_state() == AgentState::Retired {
+                return Err(format!("Agent {} is retired", agent_id));
+            }
+            Self::set_state_in(state, agent_id, AgentState::Quarantined, now);
+            state.quarantined_agents.insert(agent_id.to_string(), QuarantineRecord {
+                agent_id: agent_id.to_string(),
+                canister_id: agent.canister_id.clone(),
+                reason: reason.clone(),
+                quarantined_by: admin.to_string(),
+                quarantined_at: now,
+                cancelled_deliveries: 0,
+                reassigned_tasks: Vec::new(),
+                unassigned_tasks: Vec::new(),
+            });
+            RegistryService::mark_changed(state, agent_id);
+            Ok(Self::cancel_deliveries(state, agent_id, &reason, now))
+        })?;
+
+        let mut reassigned_tasks = Vec::new();
+        let mut unassigned_tasks = Vec::new();
+        for task_id in open_tasks {
+            match AutonomousCoordinationService::reassign_task(&task_id).await {
+                Some(_) => reassigned_tasks.push(task_id),
+                None => unassigned_tasks.push(task_id),
+            }
+        }
+
+        let record = with_state_mut(|state| {
+            let record = state.quarantined_agents.get_mut(agent_id)?;
+            record.cancelled_deliveries = cancelled_deliveries;
+            record.reassigned_tasks = reassigned_tasks;
+            record.unassigned_tasks = unassigned_tasks;
+            Some(record.clone())
+        })
+        .ok_or_else(|| format!("Agent {} was released during quarantine", agent_id))?;
+
+        Metrics::increment_counter("agents_quarantined_total");
+        AuditService::record(&agent.agent_principal, AuditAction::AgentQuarantined, agent_id, format!("by {}: {}", admin, reason));
+        NotificationService::notify(
+            &agent.agent_principal,
+            NotificationKind::AgentQuarantined,
+            format!("Agent {} was quarantined and will receive no work until released: {}", agent_id, reason),
+        );
+        Ok(record)
+    }
+
+    /// Drop the agent's queued messages and fail its running jobs; returns how many were
+    /// cancelled and the open tasks that need a new agent
+    fn cancel_deliveries(state: &mut CoordinatorState, agent_id: &str, reason: &str, now: u64) -> (u32, Vec<String>) {
+        let mut cancelled = state.agent_message_queues
+            .as_mut()
+            .and_then(|queues| queues.remove(agent_id))
+            .map_or(0, |queue| queue.len() as u32);
+        for job in state.inference_jobs.values_mut() {
+            if job.agent_id == agent_id && matches!(job.status, JobStatus::Dispatched | JobStatus::Running) {
+                job.status = JobStatus::Failed;
+                job.error = Some(format!("Agent quarantined: {}", reason));
+                job.updated_at = now;
+                cancelled += 1;
+            }
+        }
+        let mut open_tasks: Vec<String> = state.task_ledger
+            .values()
+            .filter(|task| task.assigned_agent == agent_id)
+            .filter(|task| matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress | TaskStatus::AwaitingReassignment))
+            .map(|task| task.task_id.clone())
+            .collect();
+        open_tasks.sort();
+        (cancelled, open_tasks)
+    }
+
+    pub fn release(admin: &str, agent_id: &str) -> Result<QuarantineRecord, String> {
+        let now = time();
+        let record = with_state_mut(|state| {
+            let record = state.quarantined_agents.remove(agent_id)?;
+            if state.agents.get(agent_id).is_some_and(|agent| agent.agent_state() == AgentState::Quarantined) {
+                Self::set_state_in(state, agent_id, AgentState::Active, now);
+            }
+            RegistryService::mark_changed(state, agent_id);
+            Some(record)
+        })
+        .ok_or_else(|| format!("Agent {} is not quarantined", agent_id))?;
+        let owner = RegistryService::get_agent(agent_id).map(|agent| agent.agent_principal).unwrap_or_default();
+        AuditService::record(&owner, AuditAction::AgentReleased, agent_id, format!("by {}", admin));
+        Ok(record)
+    }
+
+    /// Hold an active agent out of routing without touching its tasks or deliveries
+    pub fn suspend(admin: &str, agent_id: &str, reason: String) -> Result<AgentRegistration, String> {
+        if reason.trim().is_empty() {
+            return Err("A suspension reason is required".to_string());
+        }
+        let agent = Self::transition(agent_id, &[AgentState::Active], AgentState::Suspended, time())?;
+        Metrics::increment_counter("agents_suspended_total");
+        AuditService::record(&agent.agent_principal, AuditAction::AgentSuspended, agent_id, format!("by {}: {}", admin, reason));
+        NotificationService::notify(
+            &agent.agent_principal,
+            NotificationKind::AgentSuspended,
+            format!("Agent {} was suspended and will receive no new work until reinstated: {}", agent_id, reason),
+        );
+        Ok(agent)
+    }
+
+    pub fn reinstate(admin: &str, agent_id: &str) -> Result<AgentRegistration, String> {
+        let agent = Self::transition(agent_id, &[AgentState::Suspended], AgentState::Active, time())?;
+        AuditService::record(&agent.agent_principal, AuditAction::AgentReinstated, agent_id, format!("by {}", admin));
+        Ok(agent)
+    }
+
+    /// Take an agent out of service for good. It stays in the registry for its history;
+    /// its open tasks are handed to other agents.
+    pub async fn retire(admin: &str, agent_id: &str, reason: String) -> Result<AgentRegistration, String> {
+        if reason.trim().is_empty() {
+            return Err("A retirement reason is required".to_string());
+        }
+        let now = time();
+        let agent = Self::transition(
+            agent_id,
+            &[AgentState::Active, AgentState::Suspended, AgentState::Quarantined],
+            AgentState::Retired,
+            now,
+        )?;
+        let open_tasks = with_state_mut(|state| RegistryService::release_open_tasks(state, agent_id, now));
+        let mut reassigned = 0;
+        for task_id in &open_tasks {
+            if AutonomousCoordinationService::reassign_task(task_id).await.is_some() {
+                reassigned += 1;
+            }
+        }
+        AuditService::record(
+            &agent.agent_principal,
+            AuditAction::AgentRetired,
+            agent_id,
+            format!("by {}: {}; {} of {} open tasks reassigned", admin, reason, reassigned, open_tasks.len()),
+        );
+        NotificationService::notify(
+            &agent.agent_principal,
+            NotificationKind::AgentRetired,
+            format!("Agent {} was retired: {}", agent_id, reason),
+        );
+        Ok(agent)
+    }
+
+    fn transition(agent_id: &str, from: &[AgentState], to: AgentState, now: u64) -> Result<AgentRegistration, String> {
+        with_state_mut(|state| {
+            let current = state.agents
+                .get(agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?
+                .agent_state();
+            if !from.contains(&current) {
+                return Err(format!("Agent {} is {:?} and cannot become {:?}", agent_id, current, to));
+            }
+            Self::set_state_in(state, agent_id, to, now);
+            Ok(state.agents[agent_id].clone())
+        })
+    }
+
+    fn set_state_in(state: &mut CoordinatorState, agent_id: &str, to: AgentState, now: u64) {
+        if let Some(agent) = state.agents.get_mut(agent_id) {
+            let before = agent.agent_state();
+            agent.state = Some(to);
+            RegistryService::mark_changed(state, agent_id);
+            RegistryEventService::publish_in(state, agent_id, RegistryEventKind::StateChanged { before, after: to }, now);
+        }
+    }
+
+    pub fn list() -> Vec<QuarantineRecord> {
+        with_state(|state| {
+            let mut records: Vec<QuarantineRecord> = state.quarantined_agents.values().cloned().collect();
+            records.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+            records
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::autonomous_coord::TaskLedgerEntry;
+
+    #[test]
+    fn test_cancel_deliveries_collects_open_work() {
+        let mut state = CoordinatorState::default();
+        for (task_id, agent_id, status) in [
+            ("t1", "bad", TaskStatus::InProgress),
+            ("t2", "bad", TaskStatus::Completed),
+            ("t3", "good", TaskStatus::Pending),
+            ("t4", "bad", TaskStatus::AwaitingReassignment),
+        ] {
+            state.task_ledger.insert(task_id.to_string(), TaskLedgerEntry::test(task_id, agent_id, status));
+        }
+        state.agent_message_queues = Some([("bad".to_string(), Vec::new())].into_iter().collect());
+
+        let (cancelled, open_tasks) = QuarantineService::cancel_deliveries(&mut state, "bad", "incident", 1);
+        assert_eq!(cancelled, 0);
+        assert_eq!(open_tasks, vec!["t1".to_string(), "t4".to_string()]);
+        assert!(!state.agent_message_queues.unwrap().contains_key("bad"));
+    }
+
+    #[test]
+    fn test_suspended_agents_are_skipped_at_full_health() {
+        let agent = AgentRegistration::test("a");
+        with_state_mut(|state| RegistryService::insert_registration(state, agent, "owner"));
+        let coding = vec!["coding".to_string()];
+
+        QuarantineService::transition("a", &[AgentState::Active], AgentState::Suspended, 0).unwrap();
+        assert!(RegistryService::get_capable_agents(&coding, 0.1).is_empty());
+        assert!(QuarantineService::transition("a", &[AgentState::Active], AgentState::Suspended, 0).is_err());
+
+        QuarantineService::transition("a", &[AgentState::Suspended], AgentState::Active, 0).unwrap();
+        assert_eq!(RegistryService::get_capable_agents(&coding, 0.1).len(), 1);
+    }
+
+    #[test]
+    fn test_canister_lookup_blocks_registration() {
+        let mut state = CoordinatorState::default();
+        state.quarantined_agents.insert("bad".to_string(), QuarantineRecord {
+            agent_id: "bad".to_string(),
+            canister_id: "rrkah-fqaaa-aaaaa-aaaaq-cai".to_string(),
+            reason: "spam".to_string(),
+            quarantined_by: "admin".to_string(),
+            quarantined_at: 0,
+            cancelled_deliveries: 0,
+            reassigned_tasks: Vec::new(),
+            unassigned_tasks: Vec::new(),
+        });
+        assert!(QuarantineService::quarantine_for_canister(&state, "rrkah-fqaaa-aaaaa-aaaaq-cai").is_some());
+        assert!(QuarantineService::quarantine_for_canister(&state, "ryjl3-tyaaa-aaaaa-aaaba-cai").is_none());
+    }
+}
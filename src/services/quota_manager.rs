@@ -1,8 +1,11 @@
 use ic_cdk::api::time;
 use serde::{Deserialize, Serialize};
 use candid::CandidType;
-use std::collections::HashMap;
-use crate::services::{with_state, with_state_mut};
+use std::collections::{BTreeMap, HashMap};
+use crate::services::{with_state, with_state_mut, CoordinatorState, SafetyLimitService, SiemExportService};
+use crate::services::siem_export::SiemEventCategory;
+use crate::services::autonomous_coord::AgentMessage;
+use crate::services::audit::{AuditAction, AuditService};
 
 /// Quota manager service for enforcing subscription limits
 pub struct QuotaManager;
@@ -23,9 +26,62 @@ pub struct QuotaUsage {
     pub agents_created_this_month: u32,
     pub tokens_used_this_month: u64,
     pub inferences_this_month: u32,
+    pub weighted_units_used_this_month: u32,
+    pub creations_refunded_this_month: u32,
+    /// Creations granted while economics was unreachable, which economics never counted
+    #[serde(default)]
+    pub offline_creations_this_month: u32,
+    /// Creations held for spawns still in flight, which economics has not counted yet
+    #[serde(default)]
+    pub creations_reserved: u32,
     pub last_reset_date: u64,
 }
 
+/// Usage for one quota period, kept after the monthly reset for trends and billing disputes
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct UsageSnapshot {
+    pub subscription_tier: String,
+    pub period_start: u64,
+    /// None for the period still in progress
+    pub period_end: Option<u64>,
+    pub agents_created: u32,
+    pub tokens_used: u64,
+    pub inferences: u32,
+    pub weighted_units_used: u32,
+    pub creations_refunded: u32,
+}
+
+/// Usage across all users for periods starting in one calendar month (`YYYY-MM`, UTC)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct UsageTotals {
+    pub month: String,
+    pub users: u32,
+    pub agents_created: u64,
+    pub tokens_used: u64,
+    pub inferences: u64,
+    pub weighted_units_used: u64,
+}
+
+/// Coordination messages sent by a principal's agents on one UTC day
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyMessageCount {
+    /// Days since the epoch
+    pub day: u64,
+    pub messages: u32,
+}
+
+/// A principal's coordination usage against their tier's limits
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CoordinationUsage {
+    pub max_active_sessions: u32,
+    pub active_sessions: u32,
+    pub daily_messages: u32,
+    pub messages_today: u32,
+    pub artifact_storage_bytes: u64,
+    /// Task results and coordination payloads held in the principal's sessions
+    pub artifact_bytes_stored: u64,
+}
+
 /// Quota limits based on subscription tier
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct QuotaLimits {
@@ -33,6 +89,7 @@ pub struct QuotaLimits {
     pub monthly_agent_creations: u32,
     pub token_limit: u64,
     pub inference_rate: InferenceRate,
+    pub monthly_weighted_units: u32,
 }
 
 /// Inference rate priority levels
@@ -68,6 +125,10 @@ pub enum QuotaAction {
 }
 
 impl QuotaManager {
+    /// Archived periods kept per user
+    const MAX_HISTORY_PERIODS: usize = 24;
+    const DAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
     /// Initialize user quota tracking
     pub fn initialize_user_quota(
         principal_id: String,
@@ -83,6 +144,10 @@ impl QuotaManager {
                 agents_created_this_month: 0,
                 tokens_used_this_month: 0,
                 inferences_this_month: 0,
+                weighted_units_used_this_month: 0,
+                creations_refunded_this_month: 0,
+                offline_creations_this_month: 0,
+                creations_reserved: 0,
                 last_reset_date: now,
             },
             limits,
@@ -96,18 +161,111 @@ impl QuotaManager {
         Ok(())
     }
 
+    pub const TIERS: &'static [&'static str] = &["Free", "Basic", "Pro", "Enterprise"];
+
+    /// Most agents a single fanout may query for a subscription tier
+    pub fn fanout_cap_for_tier(tier: &str) -> usize {
+        match tier {
+            "Pro" => 4,
+            "Enterprise" => 5,
+            _ => 3,
+        }
+    }
+
+    /// Most of a subscriber's agents that may be active at once
+    pub fn concurrency_cap_for_tier(tier: &str) -> u32 {
+        match tier {
+            "Basic" => 5,
+            "Pro" => 10,
+            "Enterprise" => 50,
+            _ => 2,
+        }
+    }
+
+    /// Most coordination sessions a subscriber's agents may coordinate at once
+    pub fn session_cap_for_tier(tier: &str) -> u32 {
+        match tier {
+            "Basic" => 5,
+            "Pro" => 20,
+            "Enterprise" => 100,
+            _ => 2,
+        }
+    }
+
+    /// Coordination messages a subscriber's agents may send per UTC day
+    pub fn daily_message_cap_for_tier(tier: &str) -> u32 {
+        match tier {
+            "Basic" => 1_000,
+            "Pro" => 10_000,
+            "Enterprise" => 100_000,
+            _ => 200,
+        }
+    }
+
+    /// Bytes of task results and coordination payloads a subscriber's sessions may hold
+    pub fn artifact_storage_cap_for_tier(tier: &str) -> u64 {
+        const MIB: u64 = 1024 * 1024;
+        match tier {
+            "Basic" => 10 * MIB,
+            "Pro" => 100 * MIB,
+            "Enterprise" => 1024 * MIB,
+            _ => MIB,
+        }
+    }
+
+    /// Fanout cap for a principal; principals without a quota record get the Free cap
+    pub fn fanout_cap(principal_id: &str) -> usize {
+        let tier = with_state(|state| state.user_quotas.get(principal_id).map(|q| q.subscription_tier.clone()));
+        Self::fanout_cap_for_tier(tier.as_deref().unwrap_or("Free"))
+    }
+
+    /// Standard limits for a subscription tier
+    pub fn limits_for_tier(tier: &str) -> Option<QuotaLimits> {
+        let limits = match tier {
+            "Free" => QuotaLimits {
+                max_agents: 3,
+                monthly_agent_creations: 5,
+                token_limit: 1024,
+                inference_rate: InferenceRate::Standard,
+                monthly_weighted_units: 10,
+            },
+            "Basic" => QuotaLimits {
+                max_agents: 10,
+                monthly_agent_creations: 15,
+                token_limit: 2048,
+                inference_rate: InferenceRate::Standard,
+                monthly_weighted_units: 30,
+            },
+            "Pro" => QuotaLimits {
+                max_agents: 25,
+                monthly_agent_creations: 25,
+                token_limit: 4096,
+                inference_rate: InferenceRate::Priority,
+                monthly_weighted_units: 50,
+            },
+            "Enterprise" => QuotaLimits {
+                max_agents: 100,
+                monthly_agent_creations: 100,
+                token_limit: 8192,
+                inference_rate: InferenceRate::Premium,
+                monthly_weighted_units: 200,
+            },
+            _ => return None,
+        };
+        Some(limits)
+    }
+
     /// Validate quota for a specific action
     pub fn validate_quota(
         principal_id: &str,
         action: QuotaAction,
         amount: Option<u64>,
     ) -> Result<QuotaValidation, String> {
-        let mut user_quota = Self::get_user_quota(principal_id)
+        // Reset monthly usage if needed; the rollover is stored whether or not the action is allowed
+        let now = time();
+        let mut user_quota = with_state_mut(|state| Self::roll_over_in(state, principal_id, now))
             .ok_or("No quota found for user")?;
 
-        // Reset monthly usage if needed
-        Self::reset_monthly_usage_if_needed(&mut user_quota);
-
         let validation = match action {
             QuotaAction::AgentCreation => {
                 Self::validate_agent_creation_quota(&user_quota)
@@ -125,6 +283,9 @@ impl QuotaManager {
         if validation.allowed {
             Self::update_usage(&mut user_quota, &action, amount);
             Self::store_user_quota(user_quota);
+            SiemExportService::emit(principal_id, SiemEventCategory::Quota, "quota_consumed", &format!("{:?}", action), format!("amount {}", amount.unwrap_or(1)));
+        } else {
+            SiemExportService::emit(principal_id, SiemEventCategory::Quota, "quota_denied", &format!("{:?}", action), validation.reason.clone().unwrap_or_default());
         }
 
         Ok(validation)
@@ -214,6 +375,163 @@ impl QuotaManager {
         user_quota.last_updated = time();
     }
 
+    /// Charge weighted spawn units; quota is created on analysis, so a missing entry is a no-op
+    pub fn record_weighted_usage(principal_id: &str, units: u32) {
+        with_state_mut(|state| {
+            if let Some(quota) = state.user_quotas.get_mut(principal_id) {
+                quota.current_usage.weighted_units_used_this_month =
+                    quota.current_usage.weighted_units_used_this_month.saturating_add(units);
+                quota.last_updated = time();
+            }
+        });
+        SiemExportService::emit(principal_id, SiemEventCategory::Quota, "quota_consumed", "WeightedUnits", format!("amount {}", units));
+    }
+
+    /// Debit creations for a spawn about to start, so concurrent spawns see them used;
+    /// returns the number debited, which `settle_creation_credits` takes back
+    pub fn reserve_creation_credits(principal_id: &str, specs: u32) -> u32 {
+        with_state_mut(|state| {
+            let quota = state.user_quotas.get_mut(principal_id)?;
+            let usage = &mut quota.current_usage;
+            usage.agents_created_this_month = usage.agents_created_this_month.saturating_add(specs);
+            usage.creations_reserved = usage.creations_reserved.saturating_add(specs);
+            quota.last_updated = time();
+            Some(specs)
+        }).unwrap_or(0)
+    }
+
+    /// Release a spawn's reservation once it has run. Spawned agents are then charged by
+    /// economics tracking (or the offline count), so only the `reserved - spawned`
+    /// creations that never came up are refunded; returns that number.
+    pub fn settle_creation_credits(principal_id: &str, request_id: &str, reserved: u32, spawned: u32, reason: &str) -> u32 {
+        let credited = with_state_mut(|state| {
+            let quota = state.user_quotas.get_mut(principal_id)?;
+            let usage = &mut quota.current_usage;
+            let released = reserved.min(usage.creations_reserved);
+            usage.creations_reserved -= released;
+            usage.agents_created_this_month = usage.agents_created_this_month.saturating_sub(released);
+            let credited = released.saturating_sub(spawned);
+            usage.creations_refunded_this_month = usage.creations_refunded_this_month.saturating_add(credited);
+            quota.last_updated = time();
+            Some(credited)
+        }).unwrap_or(0);
+
+        if credited > 0 {
+            AuditService::record(
+                principal_id,
+                AuditAction::CreationCreditRefund,
+                request_id,
+                format!("Refunded {} of {} reserved agent creations: {}", credited, reserved, reason),
+            );
+            SiemExportService::emit(principal_id, SiemEventCategory::Quota, "quota_refunded", request_id, format!("{} agent creations", credited));
+        }
+        credited
+    }
+
+    /// Weighted spawn units left this month
+    pub fn remaining_weighted_units(user_quota: &UserQuota) -> u32 {
+        user_quota.limits.monthly_weighted_units
+            .saturating_sub(user_quota.current_usage.weighted_units_used_this_month)
+    }
+
+    pub fn coordination_usage(principal_id: &str) -> CoordinationUsage {
+        let now = time();
+        with_state(|state| Self::coordination_usage_in(state, principal_id, now))
+    }
+
+    fn coordination_usage_in(state: &CoordinatorState, principal_id: &str, now: u64) -> CoordinationUsage {
+        let tier = state.user_quotas.get(principal_id).map_or("Free", |q| q.subscription_tier.as_str());
+        CoordinationUsage {
+            max_active_sessions: Self::session_cap_for_tier(tier),
+            active_sessions: SafetyLimitService::active_sessions(state, principal_id, now),
+            daily_messages: Self::daily_message_cap_for_tier(tier),
+            messages_today: state.message_usage
+                .get(principal_id)
+                .filter(|usage| usage.day == now / Self::DAY_NS)
+                .map_or(0, |usage| usage.messages),
+            artifact_storage_bytes: Self::artifact_storage_cap_for_tier(tier),
+            artifact_bytes_stored: state.coordination_sessions
+                .iter()
+                .flat_map(|sessions| sessions.values())
+                .filter(|session| Self::agent_owner(state, &session.coordinator_agent) == principal_id)
+                .flat_map(|session| &session.messages)
+                .map(|message| Self::artifact_bytes(&message.message_type))
+                .sum(),
+        }
+    }
+
+    /// The principal an agent's coordination usage is charged to; unregistered agents stand for themselves
+    fn agent_owner<'a>(state: &'a CoordinatorState, agent_id: &'a str) -> &'a str {
+        state.agents.get(agent_id).map_or(agent_id, |agent| agent.agent_principal.as_str())
+    }
+
+    fn artifact_bytes(message: &AgentMessage) -> u64 {
+        match message {
+            AgentMessage::TaskResponse { result, .. } => result.as_ref().map_or(0, |r| r.len() as u64),
+            AgentMessage::CoordinationRequest { data, .. } => data.len() as u64,
+            _ => 0,
+        }
+    }
+
+    /// Refuse a new session coordinated by `coordinator_agent` when its owner is at their tier's session limit
+    pub fn admit_session(coordinator_agent: &str) -> Result<(), String> {
+        let now = time();
+        let (owner, admitted) = with_state(|state| {
+            let owner = Self::agent_owner(state, coordinator_agent).to_string();
+            let usage = Self::coordination_usage_in(state, &owner, now);
+            let admitted = if usage.active_sessions >= usage.max_active_sessions {
+                Err(format!(
+                    "Quota exceeded: tier allows {} active coordination sessions ({} active)",
+                    usage.max_active_sessions, usage.active_sessions
+                ))
+            } else {
+                Ok(())
+            };
+            (owner, admitted)
+        });
+        admitted.inspect_err(|e| SiemExportService::emit(&owner, SiemEventCategory::Quota, "quota_denied", "CoordinationSession", e.clone()))
+    }
+
+    /// Charge a message to the sender's daily allowance and its payload to the session owner's
+    /// artifact storage, or refuse it naming the limit it would cross
+    pub fn admit_message(session_id: &str, from_agent: &str, message: &AgentMessage) -> Result<(), String> {
+        let now = time();
+        let (sender, admitted) = with_state_mut(|state| {
+            let sender = Self::agent_owner(state, from_agent).to_string();
+            let admitted = Self::admit_message_in(state, session_id, &sender, message, now);
+            (sender, admitted)
+        });
+        admitted.inspect_err(|e| SiemExportService::emit(&sender, SiemEventCategory::Quota, "quota_denied", "CoordinationMessage", e.clone()))
+    }
+
+    fn admit_message_in(state: &mut CoordinatorState, session_id: &str, sender: &str, message: &AgentMessage, now: u64) -> Result<(), String> {
+        let usage = Self::coordination_usage_in(state, sender, now);
+        if usage.messages_today >= usage.daily_messages {
+            return Err(format!("Quota exceeded: tier allows {} coordination messages per day", usage.daily_messages));
+        }
+        let bytes = Self::artifact_bytes(message);
+        let session_owner = state.coordination_sessions
+            .as_ref()
+            .and_then(|sessions| sessions.get(session_id))
+            .map(|session| Self::agent_owner(state, &session.coordinator_agent).to_string());
+        if let Some(owner) = session_owner.filter(|_| bytes > 0) {
+            let storage = Self::coordination_usage_in(state, &owner, now);
+            if storage.artifact_bytes_stored + bytes > storage.artifact_storage_bytes {
+                return Err(format!(
+                    "Quota exceeded: session owner's artifact storage of {} bytes is full ({} stored, {} more requested)",
+                    storage.artifact_storage_bytes, storage.artifact_bytes_stored, bytes
+                ));
+            }
+        }
+
+        let count = state.message_usage.entry(sender.to_string()).or_default();
+        if count.day != now / Self::DAY_NS {
+            *count = DailyMessageCount { day: now / Self::DAY_NS, messages: 0 };
+        }
+        count.messages += 1;
+        Ok(())
+    }
+
     /// Get user quota
     pub fn get_user_quota(principal_id: &str) -> Option<UserQuota> {
         with_state(|state| {
@@ -228,20 +546,104 @@ impl QuotaManager {
         });
     }
 
-    /// Reset monthly usage if a new month has started
-    fn reset_monthly_usage_if_needed(user_quota: &mut UserQuota) {
-        let now = time();
+    /// Reset the stored monthly usage if a new month has started, archiving the finished
+    /// period once, and return the quota as it now stands
+    fn roll_over_in(state: &mut CoordinatorState, principal_id: &str, now: u64) -> Option<UserQuota> {
+        let user_quota = state.user_quotas.get_mut(principal_id)?;
         let last_reset = user_quota.current_usage.last_reset_date;
         
         // Check if we're in a new month (simple check: 30 days passed)
-        if now - last_reset > 30 * 24 * 60 * 60 * 1_000_000_000 {
+        if now.saturating_sub(last_reset) > 30 * 24 * 60 * 60 * 1_000_000_000 {
+            Self::archive_usage(&mut state.usage_history, user_quota, now);
             user_quota.current_usage = QuotaUsage {
                 agents_created_this_month: 0,
                 tokens_used_this_month: 0,
                 inferences_this_month: 0,
+                weighted_units_used_this_month: 0,
+                creations_refunded_this_month: 0,
+                offline_creations_this_month: 0,
+                creations_reserved: 0,
                 last_reset_date: now,
             };
         }
+        Some(user_quota.clone())
+    }
+
+    /// Store a quota synced from economics, archiving local usage when economics has started a new period
+    pub fn store_synced_quota(user_quota: UserQuota) {
+        let now = time();
+        with_state_mut(|state| {
+            if let Some(previous) = state.user_quotas.get(&user_quota.principal_id) {
+                if previous.current_usage.last_reset_date != user_quota.current_usage.last_reset_date {
+                    Self::archive_usage(&mut state.usage_history, previous, now);
+                }
+            }
+            state.user_quotas.insert(user_quota.principal_id.clone(), user_quota);
+        });
+    }
+
+    fn archive_usage(history: &mut HashMap<String, Vec<UsageSnapshot>>, user_quota: &UserQuota, period_end: u64) {
+        let snapshots = history.entry(user_quota.principal_id.clone()).or_default();
+        snapshots.push(Self::snapshot(user_quota, Some(period_end)));
+        if snapshots.len() > Self::MAX_HISTORY_PERIODS {
+            snapshots.remove(0);
+        }
+    }
+
+    fn snapshot(user_quota: &UserQuota, period_end: Option<u64>) -> UsageSnapshot {
+        let usage = &user_quota.current_usage;
+        UsageSnapshot {
+            subscription_tier: user_quota.subscription_tier.clone(),
+            period_start: usage.last_reset_date,
+            period_end,
+            agents_created: usage.agents_created_this_month,
+            tokens_used: usage.tokens_used_this_month,
+            inferences: usage.inferences_this_month,
+            weighted_units_used: usage.weighted_units_used_this_month,
+            creations_refunded: usage.creations_refunded_this_month,
+        }
+    }
+
+    /// The current period followed by up to `months - 1` archived ones, newest first
+    pub fn get_usage_history(principal_id: &str, months: u32) -> Vec<UsageSnapshot> {
+        with_state(|state| {
+            let current = state.user_quotas.get(principal_id).map(|quota| Self::snapshot(quota, None));
+            let archived = state.usage_history.get(principal_id).into_iter().flat_map(|s| s.iter().rev().cloned());
+            current.into_iter().chain(archived).take(months as usize).collect()
+        })
+    }
+
+    /// Archived usage summed per calendar month, newest first
+    pub fn usage_totals(months: u32) -> Vec<UsageTotals> {
+        with_state(|state| Self::totals_by_month(state.usage_history.values().flatten(), months))
+    }
+
+    fn totals_by_month<'a>(snapshots: impl Iterator<Item = &'a UsageSnapshot>, months: u32) -> Vec<UsageTotals> {
+        let mut totals: BTreeMap<String, UsageTotals> = BTreeMap::new();
+        for snapshot in snapshots {
+            let month = Self::month_label(snapshot.period_start);
+            let entry = totals.entry(month.clone()).or_insert_with(|| UsageTotals { month, ..Default::default() });
+            entry.users += 1;
+            entry.agents_created += snapshot.agents_created as u64;
+            entry.tokens_used += snapshot.tokens_used;
+            entry.inferences += snapshot.inferences as u64;
+            entry.weighted_units_used += snapshot.weighted_units_used as u64;
+        }
+        totals.into_values().rev().take(months as usize).collect()
+    }
+
+    /// `YYYY-MM` of a nanosecond timestamp, UTC
+    fn month_label(nanos: u64) -> String {
+        // Civil-from-days conversion over the proleptic Gregorian calendar
+        let days = (nanos / 86_400_000_000_000) as i64 + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        format!("{:04}-{:02}", year, month)
     }
 
     /// Get user usage metrics
@@ -307,3 +709,130 @@ pub struct QuotaStats {
     pub total_tokens_used: u64,
     pub total_inferences: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(period_start: u64, tokens_used: u64) -> UsageSnapshot {
+        UsageSnapshot {
+            subscription_tier: "Free".to_string(),
+            period_start,
+            period_end: None,
+            agents_created: 1,
+            tokens_used,
+            inferences: 0,
+            weighted_units_used: 2,
+            creations_refunded: 0,
+        }
+    }
+
+    #[test]
+    fn test_month_label() {
+        assert_eq!(QuotaManager::month_label(0), "1970-01");
+        // 2024-02-29T12:00:00Z
+        assert_eq!(QuotaManager::month_label(1_709_208_000_000_000_000), "2024-02");
+    }
+
+    #[test]
+    fn test_totals_group_by_month_newest_first() {
+        let jan = 1_704_067_200_000_000_000; // 2024-01-01
+        let feb = 1_706_745_600_000_000_000; // 2024-02-01
+        let snapshots = [snapshot(jan, 10), snapshot(jan + 1, 5), snapshot(feb, 7)];
+        let totals = QuotaManager::totals_by_month(snapshots.iter(), 12);
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].month, "2024-02");
+        assert_eq!(totals[1].users, 2);
+        assert_eq!(totals[1].tokens_used, 15);
+        assert_eq!(QuotaManager::totals_by_month(snapshots.iter(), 1).len(), 1);
+    }
+
+    #[test]
+    fn test_rollover_archives_the_period_once() {
+        let mut state = CoordinatorState::default();
+        state.user_quotas.insert("alice".to_string(), UserQuota {
+            principal_id: "alice".to_string(),
+            subscription_tier: "Free".to_string(),
+            current_usage: QuotaUsage {
+                agents_created_this_month: 3,
+                tokens_used_this_month: 0,
+                inferences_this_month: 0,
+                weighted_units_used_this_month: 0,
+                creations_refunded_this_month: 0,
+                offline_creations_this_month: 0,
+                creations_reserved: 0,
+                last_reset_date: 0,
+            },
+            limits: QuotaLimits {
+                max_agents: 1,
+                monthly_agent_creations: 1,
+                token_limit: 1024,
+                inference_rate: InferenceRate::Standard,
+                monthly_weighted_units: 3,
+            },
+            last_updated: 0,
+        });
+
+        let now = 31 * QuotaManager::DAY_NS;
+        let rolled = QuotaManager::roll_over_in(&mut state, "alice", now).unwrap();
+        assert_eq!(rolled.current_usage.agents_created_this_month, 0);
+        // Later checks in the new period, allowed or denied, archive nothing more
+        QuotaManager::roll_over_in(&mut state, "alice", now + 1);
+        QuotaManager::roll_over_in(&mut state, "alice", now + 2);
+        assert_eq!(state.usage_history["alice"].len(), 1);
+        assert_eq!(state.usage_history["alice"][0].agents_created, 3);
+        assert!(QuotaManager::roll_over_in(&mut state, "bob", now).is_none());
+    }
+
+    #[test]
+    fn test_coordination_limits_follow_the_tier() {
+        use crate::services::autonomous_coord::*;
+        use crate::services::session_templates::MessageProtocol;
+
+        let mut state = CoordinatorState::default();
+        crate::services::RegistryService::insert_registration(&mut state, crate::domain::AgentRegistration {
+            agent_principal: "alice".to_string(),
+            ..crate::domain::AgentRegistration::test("agent")
+        }, "alice");
+        let session = CoordinationSession {
+            session_id: "s1".to_string(),
+            participants: vec!["agent".to_string()],
+            coordinator_agent: "agent".to_string(),
+            objective: "test".to_string(),
+            status: SessionStatus::Active,
+            created_at: 0,
+            last_activity: 0,
+            messages: Vec::new(),
+            resource_constraints: ResourceConstraints {
+                max_execution_time_ms: 0,
+                max_memory_usage_bytes: 0,
+                max_concurrent_tasks: 1,
+                allowed_capabilities: None,
+            },
+            template_id: None,
+            role_bindings: Vec::new(),
+            protocol: MessageProtocol::Mesh,
+        };
+        state.coordination_sessions = Some([("s1".to_string(), session)].into_iter().collect());
+        let usage = QuotaManager::coordination_usage_in(&state, "alice", 0);
+        assert_eq!((usage.active_sessions, usage.max_active_sessions), (1, 2));
+
+        let payload = |bytes: usize| AgentMessage::CoordinationRequest {
+            requesting_agent: "agent".to_string(),
+            coordination_type: CoordinationType::CollaborativePlanning,
+            data: "x".repeat(bytes),
+        };
+        let big = payload(QuotaManager::artifact_storage_cap_for_tier("Free") as usize + 1);
+        let error = QuotaManager::admit_message_in(&mut state, "s1", "alice", &big, 0).unwrap_err();
+        assert!(error.contains("artifact storage"), "{}", error);
+
+        let cap = QuotaManager::daily_message_cap_for_tier("Free");
+        for _ in 0..cap {
+            assert!(QuotaManager::admit_message_in(&mut state, "s1", "alice", &payload(0), 0).is_ok());
+        }
+        let error = QuotaManager::admit_message_in(&mut state, "s1", "alice", &payload(0), 0).unwrap_err();
+        assert!(error.starts_with("Quota exceeded"), "{}", error);
+        // The allowance resets with the UTC day
+        assert!(QuotaManager::admit_message_in(&mut state, "s1", "alice", &payload(0), QuotaManager::DAY_NS).is_ok());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, EconIntegrationService, QuotaManager, RegistryAuditService, RegistryService};
+use crate::services::audit::{AuditAction, AuditService};
+use crate::services::notifications::{NotificationKind, NotificationService};
+use crate::infra::Log;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Reclaims resources held by principals that have gone dormant
+pub struct ReclamationService;
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Dormancy thresholds and what to do once they are crossed
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ReclamationPolicy {
+    pub enabled: bool,
+    pub inactivity_months: u32,
+    pub notice_days: u32,
+    pub downgrade_dormant_paid: bool,
+}
+
+impl Default for ReclamationPolicy {
+    fn default() -> Self {
+        Self { enabled: false, inactivity_months: 6, notice_days: 14, downgrade_dormant_paid: false }
+    }
+}
+
+/// Pending or completed reclamation of a dormant principal
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ReclamationNotice {
+    pub principal: String,
+    pub issued_at: u64,
+    pub reclaim_after: u64,
+    pub reclaimed_at: Option<u64>,
+}
+
+/// Outcome of a reclamation pass
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct ReclamationReport {
+    pub notified: Vec<String>,
+    pub reclaimed: Vec<String>,
+    pub downgraded: Vec<String>,
+    pub cancelled: Vec<String>,
+    pub agents_released: u32,
+    pub requests_archived: u32,
+}
+
+impl ReclamationService {
+    /// Record that a principal acted; clears any pending or completed reclamation
+    pub fn record_activity(principal: &str) {
+        with_state_mut(|state| {
+            state.user_activity.insert(principal.to_string(), time());
+            state.reclamation_notices.remove(principal);
+        });
+    }
+
+    pub fn set_policy(policy: ReclamationPolicy) -> Result<(), String> {
+        if policy.inactivity_months == 0 {
+            return Err("inactivity_months must be at least 1".to_string());
+        }
+        with_state_mut(|state| state.reclamation_policy = policy);
+        Ok(())
+    }
+
+    pub fn get_policy() -> ReclamationPolicy {
+        with_state(|state| state.reclamation_policy.clone())
+    }
+
+    /// Most recent user-initiated activity we can attribute to a principal
+    fn last_activity(state: &crate::services::CoordinatorState, principal: &str) -> u64 {
+        let explicit = state.user_activity.get(principal).copied().unwrap_or(0);
+        let requests = state.instruction_requests.values()
+            .filter(|r| r.user_principal == principal)
+            .map(|r| r.created_at)
+            .max()
+            .unwrap_or(0);
+        let preferences = state.user_preferences.get(principal).map(|p| p.updated_at).unwrap_or(0);
+        let latest = explicit.max(requests).max(preferences);
+        if latest > 0 {
+            return latest;
+        }
+        // Principals seen only through quota syncs before activity was tracked
+        state.user_quotas.get(principal).map(|q| q.last_updated).unwrap_or(0)
+    }
+
+    /// Issue notices for newly dormant principals and reclaim those whose notice expired
+    pub async fn run() -> Result<ReclamationReport, String> {
+        let policy = Self::get_policy();
+        if !policy.enabled {
+            return Err("Reclamation policy is disabled".to_string());
+        }
+
+        let now = time();
+        let dormant_after = policy.inactivity_months as u64 * 30 * NANOS_PER_DAY;
+        let notice_period = policy.notice_days as u64 * NANOS_PER_DAY;
+        let mut report = ReclamationReport::default();
+
+        let (to_notify, to_reclaim, to_cancel) = with_state(|state| {
+            let principals: HashSet<String> = state.user_quotas.keys().cloned()
+                .chain(state.instruction_requests.values().map(|r| r.user_principal.clone()))
+                .chain(state.user_activity.keys().cloned())
+                .collect();
+
+            let mut to_notify = Vec::new();
+            let mut to_reclaim = Vec::new();
+            let mut to_cancel = Vec::new();
+            for principal in principals {
+                let dormant = now.saturating_sub(Self::last_activity(state, &principal)) >= dormant_after;
+                match (state.reclamation_notices.get(&principal), dormant) {
+                    (None, true) => to_notify.push(principal),
+                    (Some(notice), true) if notice.reclaimed_at.is_none() && now >= notice.reclaim_after => {
+                        to_reclaim.push(principal)
+                    }
+                    (Some(_), false) => to_cancel.push(principal),
+                    _ => {}
+                }
+            }
+            (to_notify, to_reclaim, to_cancel)
+        });
+
+        with_state_mut(|state| {
+            for principal in &to_cancel {
+                state.reclamation_notices.remove(principal);
+            }
+        });
+        report.cancelled = to_cancel;
+
+        for principal in to_notify {
+            with_state_mut(|state| {
+                state.reclamation_notices.insert(principal.clone(), ReclamationNotice {
+                    principal: principal.clone(),
+                    issued_at: now,
+                    reclaim_after: now + notice_period,
+                    reclaimed_at: None,
+                });
+            });
+            NotificationService::notify(
+                &principal,
+                NotificationKind::ReclamationPending,
+                format!(
+                    "No activity for {} months. Instruction history will be archived and dedicated agents released in {} days unless the account is used.",
+                    policy.inactivity_months, policy.notice_days
+                ),
+            );
+            report.notified.push(principal);
+        }
+
+        for principal in to_reclaim {
+            let (agents_released, requests_archived) = Self::reclaim(&principal, now);
+            report.agents_released += agents_released;
+            report.requests_archived += requests_archived;
+
+            if policy.downgrade_dormant_paid && Self::downgrade_if_lapsed(&principal).await {
+                report.downgraded.push(principal.clone());
+            }
+
+            NotificationService::notify(
+                &principal,
+                NotificationKind::Reclaimed,
+                format!(
+                    "Account reclaimed after inactivity: {} agents released, {} instruction requests archived.",
+                    agents_released, requests_archived
+                ),
+            );
+            report.reclaimed.push(principal);
+        }
+
+        Ok(report)
+    }
+
+    /// Archive instruction history and release agents owned by the principal
+    fn reclaim(principal: &str, now: u64) -> (u32, u32) {
+        let (agents_released, requests_archived) = with_state_mut(|state| {
+            let request_ids: Vec<String> = state.instruction_requests.iter()
+                .filter(|(_, r)| r.user_principal == principal)
+                .map(|(id, _)| id.clone())
+                .collect();
+            let archive = state.archived_instructions.entry(principal.to_string()).or_default();
+            for id in &request_ids {
+                if let Some(request) = state.instruction_requests.remove(id) {
+                    archive.push(request);
+                }
+            }
+
+            let agent_ids: Vec<String> = state.agents.values()
+                .filter(|a| a.agent_principal == principal)
+                .map(|a| a.agent_id.clone())
+                .collect();
+            for agent_id in &agent_ids {
+                RegistryService::remove_registration(state, agent_id, RegistryAuditService::RECLAMATION, now);
+            }
+
+            if let Some(notice) = state.reclamation_notices.get_mut(principal) {
+                notice.reclaimed_at = Some(now);
+            }
+            (agent_ids.len() as u32, request_ids.len() as u32)
+        });
+
+        AuditService::record(
+            principal,
+            AuditAction::DormantAccountReclaimed,
+            principal,
+            format!("Released {} agents, archived {} instruction requests", agents_released, requests_archived),
+        );
+        (agents_released, requests_archived)
+    }
+
+    /// Drop a paid principal to Free when economics no longer reports an active subscription
+    async fn downgrade_if_lapsed(principal: &str) -> bool {
+        let tier = with_state(|state| state.user_quotas.get(principal).map(|q| q.subscription_tier.clone()));
+        match tier {
+            Some(tier) if tier != "Free" => {}
+            _ => return false,
+        }
+
+        match EconIntegrationService::has_active_subscription(principal).await {
+            Ok(false) => {}
+            Ok(true) => return false,
+            Err(e) => {
+                Log::warn("reclamation", format!("Skipping downgrade of {}: {}", principal, e));
+                return false;
+            }
+        }
+
+        let Some(free_limits) = QuotaManager::limits_for_tier("Free") else {
+            return false;
+        };
+        with_state_mut(|state| {
+            if let Some(quota) = state.user_quotas.get_mut(principal) {
+                quota.subscription_tier = "Free".to_string();
+                quota.limits = free_limits;
+                quota.last_updated = time();
+            }
+        });
+        AuditService::record(principal, AuditAction::DormantAccountDowngraded, principal, "Downgraded to Free after lapsed subscription".to_string());
+        true
+    }
+
+    /// Archived instruction history for a principal
+    pub fn list_archived(principal: &str) -> Vec<InstructionRequest> {
+        with_state(|state| state.archived_instructions.get(principal).cloned().unwrap_or_default())
+    }
+}
@@ -1,43 +1,368 @@
 use crate::domain::*;
-use crate::services::{with_state, with_state_mut};
+use crate::services::{with_state, with_state_mut, AuditService, AutonomousCoordinationService, CoordinatorState, OnboardingService, QuarantineService, SlaService};
+use crate::services::audit::AuditAction;
+use crate::services::onboarding::ApplicationStatus;
+use crate::services::registry_audit::{RegistryAuditService, RegistryChange};
+use crate::services::registry_events::{RegistryEventKind, RegistryEventService};
+use crate::services::autonomous_coord::{AgentCapabilityProfile, TaskStatus};
+use crate::infra::Metrics;
 use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use base64::{Engine as _, engine::general_purpose};
 
+/// Agent ids per declared capability, so routing looks up candidates instead of scanning
+/// every agent. Derived from `agents`: updated wherever agents enter or leave the registry
+/// or change capabilities, and rebuilt after restores. Hierarchy is applied at lookup time,
+/// so taxonomy edits need no reindexing.
+#[derive(Debug, Default)]
+pub struct CapabilityIndex {
+    agents_by_capability: HashMap<String, HashSet<String>>,
+}
+
+impl CapabilityIndex {
+    pub(crate) fn add(&mut self, agent_id: &str, capabilities: &[String]) {
+        for capability in capabilities {
+            self.agents_by_capability.entry(capability.clone()).or_default().insert(agent_id.to_string());
+        }
+    }
+
+    pub(crate) fn remove(&mut self, agent_id: &str, capabilities: &[String]) {
+        for capability in capabilities {
+            if let Some(agent_ids) = self.agents_by_capability.get_mut(capability) {
+                agent_ids.remove(agent_id);
+                if agent_ids.is_empty() {
+                    self.agents_by_capability.remove(capability);
+                }
+            }
+        }
+    }
+}
+
 pub struct RegistryService;
 
+/// Outcome of one entry of `register_agents_batch`, by its position in the batch
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct BatchRegistrationResult {
+    pub index: u32,
+    pub agent_id: Option<String>,
+    /// Queued for admin approval rather than registered
+    pub pending_approval: bool,
+    pub error: Option<String>,
+}
+
 impl RegistryService {
-    pub async fn register_agent(registration: AgentRegistration) -> Result<String, String> {
+    const MAX_REGISTRATION_BATCH: usize = 50;
+
+    /// Register an agent, or queue it for admin approval when the deployment requires it.
+    /// A registration with the same owner, canister and model as a registered agent is
+    /// rejected, or with `update_existing` applied to that agent in place, keeping its ID.
+    /// Returns the agent ID and whether an existing agent was updated.
+    pub async fn register_agent(registrant: &str, registration: AgentRegistration, update_existing: bool) -> Result<(String, bool), String> {
         let now = time();
-        let agent_id = Self::generate_agent_id(&registration.agent_principal, &registration.model_id);
+        Self::check_registrant(registrant, &registration)?;
+        let duplicate = with_state(|state| Self::find_duplicate_in(state, &registration))?;
+        if let Some(existing) = duplicate {
+            if !update_existing {
+                return Err(format!(
+                    "Agent {} is already registered for this principal, canister and model; re-register with update_existing to update it",
+                    existing
+                ));
+            }
+            Self::validate_tags(&registration.tags)?;
+            with_state_mut(|state| Self::update_in_place_in(state, &existing, registration, registrant, now))?;
+            return Ok((existing, true));
+        }
+
+        let agent_reg = Self::prepare_registration(registration, now, 0)?;
+        let agent_id = agent_reg.agent_id.clone();
+        
+        if OnboardingService::approval_required() && !OnboardingService::redeem_invitation(registrant) {
+            OnboardingService::enqueue(registrant, agent_reg);
+            return Ok((agent_id, false));
+        }
         
+        with_state_mut(|state| {
+            Self::insert_registration(state, agent_reg, registrant);
+        });
+        
+        Ok((agent_id, false))
+    }
+
+    /// A registration names its owner, so only that principal or the agent canister itself
+    /// may submit it; otherwise anyone could squat another owner's identity
+    fn check_registrant(registrant: &str, registration: &AgentRegistration) -> Result<(), String> {
+        if registration.agent_principal != registrant && registration.canister_id != registrant {
+            return Err("agent_principal must be the caller, or the caller must be the agent canister".to_string());
+        }
+        Ok(())
+    }
+
+    /// ID of the registered agent with the same owner, canister and model. A matching
+    /// registration still awaiting approval is an error, as it cannot be updated. Retired
+    /// and decommissioned agents are history only and do not block a new registration.
+    fn find_duplicate_in(state: &CoordinatorState, registration: &AgentRegistration) -> Result<Option<String>, String> {
+        if let Some(application) = state.agent_applications
+            .values()
+            .find(|a| a.status == ApplicationStatus::Pending && Self::same_identity(&a.registration, registration))
+        {
+            return Err(format!("Agent {} is already awaiting approval", application.registration.agent_id));
+        }
+        Ok(state.agents
+            .values()
+            .filter(|agent| agent.agent_state() != AgentState::Retired && agent.lifecycle() != AgentLifecycle::Decommissioned)
+            .find(|agent| Self::same_identity(agent, registration))
+            .map(|agent| agent.agent_id.clone()))
+    }
+
+    fn same_identity(a: &AgentRegistration, b: &AgentRegistration) -> bool {
+        a.agent_principal == b.agent_principal && a.canister_id == b.canister_id && a.model_id == b.model_id
+    }
+
+    /// Apply a re-registration to the agent it duplicates: capabilities, tags and versions
+    /// are replaced, while its ID, health, state, lifecycle and history are kept
+    fn update_in_place_in(
+        state: &mut CoordinatorState,
+        agent_id: &str,
+        registration: AgentRegistration,
+        caller: &str,
+        now: u64,
+    ) -> Result<(), String> {
+        let current = state.agents.get(agent_id).ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+        if current.agent_principal != caller && current.canister_id != caller {
+            return Err("Only the agent or its owner can update its registration".to_string());
+        }
+        let add: Vec<String> = registration.capabilities
+            .iter()
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty() && !current.capabilities.contains(c))
+            .collect();
+        let remove: Vec<String> = current.capabilities
+            .iter()
+            .filter(|c| !registration.capabilities.iter().any(|r| r.trim() == c.as_str()))
+            .cloned()
+            .collect();
+        if !add.is_empty() || !remove.is_empty() {
+            Self::change_capabilities_in(state, agent_id, &add, &remove, caller, now)?;
+        }
+
+        let agent = state.agents.get_mut(agent_id).ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+        agent.tags = registration.tags;
+        agent.agent_version = registration.agent_version;
+        agent.protocol_version = registration.protocol_version;
+        agent.last_seen = now;
+        let after = agent.clone();
+        Self::mark_changed(state, agent_id);
+        RegistryAuditService::record_in(state, agent_id, caller, now, RegistryChange::Registered { after });
+        RegistryEventService::publish_in(state, agent_id, RegistryEventKind::Registered, now);
+        Metrics::increment_counter("agent_registrations_updated_total");
+        Ok(())
+    }
+
+    /// Register many agents in one call. Each entry is validated on its own and
+    /// invalid entries do not stop the rest. Each invitation admits one agent, so while
+    /// approval is required the entries past the invitation wait in the approval queue.
+    pub fn register_agents_batch(registrant: &str, registrations: Vec<AgentRegistration>) -> Result<Vec<BatchRegistrationResult>, String> {
+        if registrations.is_empty() {
+            return Err("Batch contains no registrations".to_string());
+        }
+        if registrations.len() > Self::MAX_REGISTRATION_BATCH {
+            return Err(format!("A batch may hold at most {} registrations", Self::MAX_REGISTRATION_BATCH));
+        }
+        let now = time();
+        let approval_required = OnboardingService::approval_required();
+
+        let mut results = Vec::with_capacity(registrations.len());
+        let mut accepted = Vec::new();
+        for (index, registration) in registrations.into_iter().enumerate() {
+            let index = index as u32;
+            let duplicate = match Self::check_registrant(registrant, &registration)
+                .and_then(|()| with_state(|state| Self::find_duplicate_in(state, &registration)))
+            {
+                Err(e) => Some(e),
+                Ok(Some(agent_id)) => Some(format!("Agent {} is already registered for this principal, canister and model", agent_id)),
+                Ok(None) if accepted.iter().any(|(a, _)| Self::same_identity(a, &registration)) => {
+                    Some("Duplicates an earlier entry in the batch".to_string())
+                }
+                Ok(None) => None,
+            };
+            if let Some(e) = duplicate {
+                results.push(BatchRegistrationResult { index, agent_id: None, pending_approval: false, error: Some(e) });
+                continue;
+            }
+            match Self::prepare_registration(registration, now, index) {
+                Ok(agent_reg) => {
+                    let pending_approval = approval_required && !OnboardingService::redeem_invitation(registrant);
+                    results.push(BatchRegistrationResult {
+                        index,
+                        agent_id: Some(agent_reg.agent_id.clone()),
+                        pending_approval,
+                        error: None,
+                    });
+                    accepted.push((agent_reg, pending_approval));
+                }
+                Err(e) => results.push(BatchRegistrationResult { index, agent_id: None, pending_approval: false, error: Some(e) }),
+            }
+        }
+
+        let (pending, admitted): (Vec<_>, Vec<_>) = accepted.into_iter().partition(|(_, pending_approval)| *pending_approval);
+        for (agent_reg, _) in pending {
+            OnboardingService::enqueue(registrant, agent_reg);
+        }
+        with_state_mut(|state| {
+            for (agent_reg, _) in admitted {
+                Self::insert_registration(state, agent_reg, registrant);
+            }
+        });
+        Ok(results)
+    }
+
+    /// Validate a submitted registration and stamp it with a fresh ID, timestamps and full health.
+    /// `salt` tells apart agents with the same owner and model submitted in the same round.
+    fn prepare_registration(registration: AgentRegistration, now: u64, salt: u32) -> Result<AgentRegistration, String> {
+        QuarantineService::check_registration(&registration.canister_id)?;
+        Self::validate_tags(&registration.tags)?;
         let mut agent_reg = registration;
-        agent_reg.agent_id = agent_id.clone();
+        agent_reg.agent_id = Self::generate_agent_id(&agent_reg.agent_principal, &agent_reg.model_id, salt);
         agent_reg.registered_at = now;
         agent_reg.last_seen = now;
         agent_reg.health_score = 1.0; // Start with perfect health
+        agent_reg.state = None;
+        agent_reg.lifecycle = Some(AgentLifecycle::Ready);
+        Ok(agent_reg)
+    }
+    
+    /// Take an agent's record out of the registry without deregistering it, when a
+    /// registry shard takes it over. Its stats, SLA and profile stay here; the audit
+    /// trail, change log and subscribers see it leave like a deregistration.
+    pub(crate) fn detach_in(state: &mut CoordinatorState, agent_id: &str, now: u64) -> Option<AgentRegistration> {
+        let agent = state.agents.remove(agent_id)?;
+        state.capability_index.remove(&agent.agent_id, &agent.capabilities);
+        RegistryAuditService::record_in(state, agent_id, RegistryAuditService::REGISTRY_SHARDS, now, RegistryChange::Deregistered { before: agent.clone() });
+        RegistryEventService::publish_in(state, agent_id, RegistryEventKind::Deregistered, now);
+        Self::mark_removed(state, agent_id);
+        Some(agent)
+    }
+
+    /// Put back a record taken out with `detach_in`
+    pub(crate) fn reattach_in(state: &mut CoordinatorState, agent: AgentRegistration, now: u64) {
+        state.capability_index.add(&agent.agent_id, &agent.capabilities);
+        RegistryAuditService::record_in(state, &agent.agent_id, RegistryAuditService::REGISTRY_SHARDS, now, RegistryChange::Registered { after: agent.clone() });
+        RegistryEventService::publish_in(state, &agent.agent_id, RegistryEventKind::Registered, now);
+        Self::mark_changed(state, &agent.agent_id);
+        state.agents.insert(agent.agent_id.clone(), agent);
+    }
+
+    /// Single insertion path for agent registrations. Every agent that enters
+    /// the registry (direct registration or spawning) gets routing stats and a
+    /// capability profile initialized here so downstream bookkeeping never no-ops.
+    /// `caller` is recorded in the registry audit trail.
+    pub fn insert_registration(state: &mut CoordinatorState, registration: AgentRegistration, caller: &str) {
+        let agent_id = registration.agent_id.clone();
+        let now = registration.registered_at;
+        RegistryAuditService::record_in(state, &agent_id, caller, now, RegistryChange::Registered { after: registration.clone() });
+        RegistryEventService::publish_in(state, &agent_id, RegistryEventKind::Registered, now);
         
-        with_state_mut(|state| {
-            state.agents.insert(agent_id.clone(), agent_reg.clone());
-            
-            // Initialize routing stats for this agent
-            let stats = RoutingStats {
-                agent_id: agent_id.clone(),
-                total_requests: 0,
-                success_rate: 1.0,
-                average_response_time_ms: 0.0,
-                capability_scores: agent_reg.capabilities
-                    .iter()
-                    .map(|cap| (cap.clone(), 1.0))
-                    .collect(),
-            };
-            state.routing_stats.insert(agent_id.clone(), stats);
-            
-            state.metrics.total_agents += 1;
-            state.metrics.last_activity = now;
-        });
+        state.routing_stats.insert(agent_id.clone(), Self::initial_stats(&registration));
+        
+        state.agent_capability_profiles
+            .get_or_insert_with(Default::default)
+            .entry(agent_id.clone())
+            .or_insert_with(|| AgentCapabilityProfile::initial(&agent_id, registration.capabilities.clone()));
         
-        Ok(agent_id)
+        Self::mark_changed(state, &agent_id);
+        state.capability_index.add(&registration.agent_id, &registration.capabilities);
+        state.agents.insert(agent_id, registration);
+        state.metrics.total_agents += 1;
+        state.metrics.last_activity = now;
+    }
+    
+    /// Single removal path, the counterpart of `insert_registration`: drops the agent
+    /// with its routing stats, circuit, SLA, profile, history and queued messages
+    pub fn remove_registration(state: &mut CoordinatorState, agent_id: &str, caller: &str, now: u64) -> Option<AgentRegistration> {
+        let registration = state.agents.remove(agent_id)?;
+        state.capability_index.remove(agent_id, &registration.capabilities);
+        RegistryAuditService::record_in(state, agent_id, caller, now, RegistryChange::Deregistered { before: registration.clone() });
+        RegistryEventService::publish_in(state, agent_id, RegistryEventKind::Deregistered, now);
+        Self::mark_removed(state, agent_id);
+        state.routing_stats.remove(agent_id);
+        state.agent_circuits.remove(agent_id);
+        state.agent_slas.remove(agent_id);
+        state.capability_history.remove(agent_id);
+        state.stale_agents.remove(agent_id);
+        state.awaiting_first_heartbeat.remove(agent_id);
+        state.agent_loads.remove(agent_id);
+        if let Some(profiles) = state.agent_capability_profiles.as_mut() {
+            profiles.remove(agent_id);
+        }
+        if let Some(queues) = state.agent_message_queues.as_mut() {
+            queues.remove(agent_id);
+        }
+        state.metrics.total_agents = state.metrics.total_agents.saturating_sub(1);
+        Some(registration)
+    }
+
+    /// Remove an agent at its owner's or its own canister's request. Its open tasks are
+    /// handed to other agents, or left awaiting reassignment when none can take them.
+    pub async fn deregister_agent(caller: &str, agent_id: &str) -> Result<(), String> {
+        let now = time();
+        let (registration, open_tasks) = with_state_mut(|state| {
+            let agent = state.agents
+                .get(agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            if agent.agent_principal != caller && agent.canister_id != caller {
+                return Err("Only the agent or its owner can deregister it".to_string());
+            }
+            let registration = Self::remove_registration(state, agent_id, caller, now)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            Ok((registration, Self::release_open_tasks(state, agent_id, now)))
+        })?;
+
+        let mut reassigned = 0;
+        for task_id in &open_tasks {
+            if AutonomousCoordinationService::reassign_task(task_id).await.is_some() {
+                reassigned += 1;
+            }
+        }
+        Metrics::increment_counter("agents_deregistered_total");
+        AuditService::record(
+            &registration.agent_principal,
+            AuditAction::AgentDeregistered,
+            agent_id,
+            format!("by {}; {} of {} open tasks reassigned", caller, reassigned, open_tasks.len()),
+        );
+        Ok(())
+    }
+
+    /// Leave the removed agent's open tasks awaiting reassignment, returning their ids
+    pub(crate) fn release_open_tasks(state: &mut CoordinatorState, agent_id: &str, now: u64) -> Vec<String> {
+        let mut open_tasks = Vec::new();
+        for task in state.task_ledger.values_mut() {
+            if task.assigned_agent == agent_id && matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress) {
+                task.status = TaskStatus::AwaitingReassignment;
+                task.updated_at = now;
+                open_tasks.push(task.task_id.clone());
+            }
+        }
+        open_tasks.sort();
+        open_tasks
+    }
+
+    /// Fresh routing stats for a newly registered agent
+    pub fn initial_stats(registration: &AgentRegistration) -> RoutingStats {
+        RoutingStats {
+            agent_id: registration.agent_id.clone(),
+            total_requests: 0,
+            success_rate: 1.0,
+            average_response_time_ms: 0.0,
+            capability_scores: registration.capabilities
+                .iter()
+                .map(|cap| (cap.clone(), 1.0))
+                .collect(),
+            latency_ewma_ms: None,
+        }
     }
     
     pub fn get_agent(agent_id: &str) -> Result<AgentRegistration, String> {
@@ -53,41 +378,393 @@ impl RegistryService {
         with_state(|state| state.agents.values().cloned().collect())
     }
     
-    pub fn update_agent_health(agent_id: String, health_score: f32) -> Result<(), String> {
+    /// Health reports count as a heartbeat, so only the agent or its owner may send them
+    pub fn update_agent_health(caller: &str, agent_id: String, health_score: f32) -> Result<(), String> {
         let now = time();
         let clamped_score = health_score.max(0.0).min(1.0);
         
         with_state_mut(|state| {
-            if let Some(agent) = state.agents.get_mut(&agent_id) {
-                agent.health_score = clamped_score;
-                agent.last_seen = now;
-                Ok(())
-            } else {
-                Err(format!("Agent not found: {}", agent_id))
+            let agent = state.agents
+                .get_mut(&agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            if agent.agent_principal != caller && agent.canister_id != caller {
+                return Err("Only the agent or its owner can report its health".to_string());
             }
+            agent.last_seen = now;
+            Self::set_health_in(state, &agent_id, clamped_score, caller, now);
+            state.stale_agents.remove(&agent_id);
+            state.awaiting_first_heartbeat.remove(&agent_id);
+            Ok(())
         })
     }
+
+    /// Single path for health score changes; records changed scores in the registry
+    /// audit trail. Returns false when the agent is not registered.
+    pub fn set_health_in(state: &mut CoordinatorState, agent_id: &str, health_score: f32, caller: &str, now: u64) -> bool {
+        let Some(before) = Self::apply_health_in(state, agent_id, health_score, now) else { return false };
+        if before != health_score {
+            RegistryAuditService::record_in(state, agent_id, caller, now, RegistryChange::HealthUpdated { before, after: health_score });
+        }
+        true
+    }
+
+    /// Routine liveness decay: changes the score like `set_health_in` without an audit
+    /// entry, so a silent fleet does not write to the trail on every sweep
+    pub fn decay_health_in(state: &mut CoordinatorState, agent_id: &str, health_score: f32, now: u64) -> bool {
+        Self::apply_health_in(state, agent_id, health_score, now).is_some()
+    }
+
+    /// Previous score, or None when the agent is not registered
+    fn apply_health_in(state: &mut CoordinatorState, agent_id: &str, health_score: f32, now: u64) -> Option<f32> {
+        let agent = state.agents.get_mut(agent_id)?;
+        let before = agent.health_score;
+        agent.health_score = health_score;
+        Self::mark_changed(state, agent_id);
+        if before != health_score {
+            SlaService::record_health_change_in(state, agent_id, before, health_score, now);
+        }
+        Some(before)
+    }
+    
+    /// Single path for lifecycle changes. Moving to the current stage is a no-op;
+    /// anything `AgentLifecycle::can_transition_to` rejects is an error. Each change is
+    /// recorded in the registry audit trail and published in the agents delta.
+    pub fn transition_lifecycle_in(
+        state: &mut CoordinatorState,
+        agent_id: &str,
+        to: AgentLifecycle,
+        caller: &str,
+        now: u64,
+    ) -> Result<AgentLifecycle, String> {
+        let agent = state.agents.get_mut(agent_id).ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+        let before = agent.lifecycle();
+        if before == to {
+            return Ok(to);
+        }
+        if !before.can_transition_to(to) {
+            return Err(format!("Agent {} cannot move from {:?} to {:?}", agent_id, before, to));
+        }
+        agent.lifecycle = Some(to);
+        Self::mark_changed(state, agent_id);
+        RegistryAuditService::record_in(state, agent_id, caller, now, RegistryChange::LifecycleChanged { before, after: to });
+        RegistryEventService::publish_in(state, agent_id, RegistryEventKind::LifecycleChanged { before, after: to }, now);
+        Metrics::increment_counter("agent_lifecycle_transitions_total");
+        Ok(to)
+    }
+    
+    /// Add and remove capabilities in place, keeping the agent's ID and history.
+    /// Callable by the owning principal or the agent's own canister.
+    pub fn update_agent_capabilities(
+        caller: &str,
+        agent_id: &str,
+        add: Vec<String>,
+        remove: Vec<String>,
+    ) -> Result<AgentRegistration, String> {
+        let now = time();
+        let add: Vec<String> = add.into_iter().map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect();
+        if add.is_empty() && remove.is_empty() {
+            return Err("No capability changes requested".to_string());
+        }
+        if add.iter().any(|c| remove.contains(c)) {
+            return Err("A capability cannot be both added and removed".to_string());
+        }
+        
+        with_state_mut(|state| {
+            let agent = state.agents
+                .get(agent_id)
+                .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+            if agent.agent_principal != caller && agent.canister_id != caller {
+                return Err("Only the agent or its owner can update its capabilities".to_string());
+            }
+            Self::change_capabilities_in(state, agent_id, &add, &remove, caller, now)
+        })
+    }
+
+    /// Capability change shared by capability updates and in-place re-registration: keeps
+    /// the index, routing scores and profile in step and records the change in the history
+    fn change_capabilities_in(
+        state: &mut CoordinatorState,
+        agent_id: &str,
+        add: &[String],
+        remove: &[String],
+        caller: &str,
+        now: u64,
+    ) -> Result<AgentRegistration, String> {
+        let agent = state.agents
+            .get_mut(agent_id)
+            .ok_or_else(|| format!("Agent not found: {}", agent_id))?;
+        let (capabilities, change) = Self::apply_capability_change(&agent.capabilities, add, remove);
+        if capabilities.is_empty() {
+            return Err("An agent must keep at least one capability".to_string());
+        }
+        let previous = std::mem::replace(&mut agent.capabilities, capabilities.clone());
+        agent.last_seen = now;
+        let updated = agent.clone();
+        state.capability_index.remove(agent_id, &previous);
+        state.capability_index.add(agent_id, &updated.capabilities);
+        
+        if let Some(stats) = state.routing_stats.get_mut(agent_id) {
+            for capability in &change.removed {
+                stats.capability_scores.remove(capability);
+            }
+            for capability in &change.added {
+                stats.capability_scores.entry(capability.clone()).or_insert(1.0);
+            }
+        }
+        if let Some(profile) = state.agent_capability_profiles.as_mut().and_then(|p| p.get_mut(agent_id)) {
+            profile.capabilities = capabilities;
+        }
+        
+        Self::mark_changed(state, agent_id);
+        if !change.added.is_empty() || !change.removed.is_empty() {
+            state.capability_history
+                .entry(agent_id.to_string())
+                .or_default()
+                .push(CapabilityChange { changed_by: caller.to_string(), changed_at: now, ..change });
+        }
+        Ok(updated)
+    }
+    
+    /// Retained removals; older cursors get a full resync
+    const MAX_TOMBSTONES: usize = 1000;
+
+    /// Record a create or update of `agent_id` in the registry change log
+    pub fn mark_changed(state: &mut CoordinatorState, agent_id: &str) {
+        state.agent_registry_version += 1;
+        let version = state.agent_registry_version;
+        let mark = state.agent_changes.entry(agent_id.to_string()).or_insert(AgentChangeMark {
+            created_version: version,
+            changed_version: version,
+            removed: false,
+        });
+        if mark.removed {
+            // Re-registered under the same id
+            mark.created_version = version;
+            mark.removed = false;
+        }
+        mark.changed_version = version;
+    }
+
+    /// Record removal of `agent_id`, forgetting the oldest tombstones past the retention limit
+    pub fn mark_removed(state: &mut CoordinatorState, agent_id: &str) {
+        state.agent_registry_version += 1;
+        let version = state.agent_registry_version;
+        let mark = state.agent_changes.entry(agent_id.to_string()).or_insert(AgentChangeMark {
+            created_version: version,
+            changed_version: version,
+            removed: true,
+        });
+        mark.changed_version = version;
+        mark.removed = true;
+
+        let mut tombstones: Vec<(u64, String)> = state.agent_changes.iter()
+            .filter(|(_, mark)| mark.removed)
+            .map(|(id, mark)| (mark.changed_version, id.clone()))
+            .collect();
+        if tombstones.len() > Self::MAX_TOMBSTONES {
+            tombstones.sort_unstable();
+            for (version, id) in tombstones.drain(..tombstones.len() - Self::MAX_TOMBSTONES) {
+                state.agent_changes.remove(&id);
+                state.agent_tombstone_floor = state.agent_tombstone_floor.max(version);
+            }
+        }
+    }
+
+    /// Registrations created, updated or removed after `since_version`
+    pub fn get_agents_delta(since_version: u64) -> AgentRegistryDelta {
+        with_state(|state| Self::delta(state, since_version))
+    }
+
+    pub(crate) fn delta(state: &CoordinatorState, since_version: u64) -> AgentRegistryDelta {
+        let version = state.agent_registry_version;
+        if since_version < state.agent_tombstone_floor || since_version > version {
+            return AgentRegistryDelta {
+                version,
+                full_resync: true,
+                created: state.agents.values().cloned().collect(),
+                updated: Vec::new(),
+                removed: Vec::new(),
+            };
+        }
+
+        let mut delta = AgentRegistryDelta { version, full_resync: false, created: Vec::new(), updated: Vec::new(), removed: Vec::new() };
+        for (agent_id, mark) in &state.agent_changes {
+            if mark.changed_version <= since_version {
+                continue;
+            }
+            let known_to_client = mark.created_version <= since_version;
+            match (mark.removed, state.agents.get(agent_id)) {
+                (true, _) if known_to_client => delta.removed.push(agent_id.clone()),
+                (false, Some(agent)) if known_to_client => delta.updated.push(agent.clone()),
+                (false, Some(agent)) => delta.created.push(agent.clone()),
+                _ => {}
+            }
+        }
+        delta
+    }
+
+    /// New capability list plus the change that actually took effect
+    fn apply_capability_change(current: &[String], add: &[String], remove: &[String]) -> (Vec<String>, CapabilityChange) {
+        let removed: Vec<String> = remove.iter().filter(|c| current.contains(c)).cloned().collect();
+        let mut capabilities: Vec<String> = current.iter().filter(|c| !removed.contains(c)).cloned().collect();
+        let mut added = Vec::new();
+        for capability in add {
+            if !capabilities.contains(capability) {
+                capabilities.push(capability.clone());
+                added.push(capability.clone());
+            }
+        }
+        (capabilities, CapabilityChange { changed_by: String::new(), added, removed, changed_at: 0 })
+    }
+    
+    pub fn get_capability_history(agent_id: &str) -> Vec<CapabilityChange> {
+        with_state(|state| state.capability_history.get(agent_id).cloned().unwrap_or_default())
+    }
     
     pub fn get_agents_by_capability(capability: &str) -> Vec<AgentRegistration> {
         with_state(|state| {
-            state.agents
-                .values()
-                .filter(|agent| agent.capabilities.contains(&capability.to_string()))
+            Self::capable_ids_in(state, std::slice::from_ref(&capability.to_string()))
+                .into_iter()
+                .filter_map(|agent_id| state.agents.get(agent_id))
                 .cloned()
                 .collect()
         })
     }
+
+    /// Healthy, unquarantined agents offering any of `capabilities`, looked up in the
+    /// capability index rather than by scanning the registry
+    pub fn get_capable_agents(capabilities: &[String], min_health: f32) -> Vec<AgentRegistration> {
+        with_state(|state| {
+            Self::capable_ids_in(state, capabilities)
+                .into_iter()
+                .filter_map(|agent_id| state.agents.get(agent_id))
+                .filter(|agent| agent.health_score >= min_health)
+                .filter(|agent| agent.is_routable())
+                .filter(|agent| !state.quarantined_agents.contains_key(&agent.agent_id))
+                .cloned()
+                .collect()
+        })
+    }
+
+    fn capable_ids_in<'a>(state: &'a CoordinatorState, capabilities: &[String]) -> HashSet<&'a String> {
+        capabilities
+            .iter()
+            .flat_map(|required| state.capability_taxonomy.satisfying(required))
+            .filter_map(|capability| state.capability_index.agents_by_capability.get(&capability))
+            .flatten()
+            .collect()
+    }
+
+    pub fn rebuild_capability_index(state: &mut CoordinatorState) {
+        let mut index = CapabilityIndex::default();
+        for agent in state.agents.values() {
+            index.add(&agent.agent_id, &agent.capabilities);
+        }
+        state.capability_index = index;
+    }
+
+    /// Whether `capabilities` provide `required` directly or through a more specific capability
+    pub fn has_capability(capabilities: &[String], required: &str) -> bool {
+        with_state(|state| state.capability_taxonomy.satisfies(capabilities, required))
+    }
+
+    pub fn list_capability_taxonomy() -> Vec<CapabilityEdge> {
+        with_state(|state| state.capability_taxonomy.edges())
+    }
+
+    /// Place `capability` under `parent` in the taxonomy, or detach it with `None`
+    pub fn set_capability_parent(capability: &str, parent: Option<&str>) -> Result<(), String> {
+        with_state_mut(|state| state.capability_taxonomy.set_parent(capability, parent))
+    }
     
     pub fn get_healthy_agents(min_health: f32) -> Vec<AgentRegistration> {
         with_state(|state| {
             state.agents
                 .values()
                 .filter(|agent| agent.health_score >= min_health)
+                .filter(|agent| agent.is_routable())
+                .filter(|agent| !state.quarantined_agents.contains_key(&agent.agent_id))
                 .cloned()
                 .collect()
         })
     }
     
+    const MAX_TAGS: usize = 32;
+    const MAX_TAG_LEN: usize = 64;
+
+    fn validate_tags(tags: &BTreeMap<String, String>) -> Result<(), String> {
+        if tags.len() > Self::MAX_TAGS {
+            return Err(format!("An agent may carry at most {} tags", Self::MAX_TAGS));
+        }
+        if tags.iter().any(|(key, value)| key.trim().is_empty() || key.len() > Self::MAX_TAG_LEN || value.len() > Self::MAX_TAG_LEN) {
+            return Err(format!("Tag keys must be non-empty and keys and values at most {} bytes", Self::MAX_TAG_LEN));
+        }
+        Ok(())
+    }
+
+    /// Agents matching every predicate in `filter`, annotated with SLA standing
+    pub fn search_agents(filter: &AgentSearchFilter) -> Vec<AgentSearchResult> {
+        let agents: Vec<AgentRegistration> = with_state(|state| {
+            state.agents
+                .values()
+                .filter(|agent| Self::matches(agent, filter))
+                .cloned()
+                .collect()
+        });
+        
+        agents
+            .into_iter()
+            .map(|agent| {
+                let sla_compliance = SlaService::get_compliance(&agent.agent_id);
+                AgentSearchResult { agent, sla_compliance }
+            })
+            .filter(|result| {
+                !filter.sla_compliant_only
+                    || result.sla_compliance.as_ref().map(|c| c.compliant).unwrap_or(false)
+            })
+            .collect()
+    }
+    
+    /// Registry predicates of `filter`; SLA standing is checked separately
+    pub(crate) fn matches(agent: &AgentRegistration, filter: &AgentSearchFilter) -> bool {
+        filter.capabilities.iter().all(|cap| Self::has_capability(&agent.capabilities, cap))
+            && filter.model_id.as_ref().is_none_or(|model_id| &agent.model_id == model_id)
+            && filter.min_health.is_none_or(|min| agent.health_score >= min)
+            && filter.max_health.is_none_or(|max| agent.health_score <= max)
+            && filter.tags.iter().flatten().all(|(key, value)| {
+                agent.tags.get(key).is_some_and(|tag| value.is_empty() || tag == value)
+            })
+    }
+
+    /// Agents running `model_id`, in id order
+    pub fn get_agents_by_model(model_id: &str) -> Vec<AgentRegistration> {
+        with_state(|state| {
+            let mut agents: Vec<AgentRegistration> = state.agents
+                .values()
+                .filter(|agent| agent.model_id == model_id)
+                .cloned()
+                .collect();
+            agents.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+            agents
+        })
+    }
+
+    fn model_fleet_in(state: &CoordinatorState) -> Vec<ModelFleetCount> {
+        let mut fleet: BTreeMap<&str, ModelFleetCount> = BTreeMap::new();
+        for agent in state.agents.values() {
+            let count = fleet.entry(agent.model_id.as_str()).or_insert_with(|| ModelFleetCount {
+                model_id: agent.model_id.clone(),
+                total_agents: 0,
+                active_agents: 0,
+            });
+            count.total_agents += 1;
+            if agent.health_score > 0.5 && agent.is_routable() {
+                count.active_agents += 1;
+            }
+        }
+        fleet.into_values().collect()
+    }
+
     pub fn get_health() -> CoordinatorHealth {
         with_state(|state| {
             let total_agents = state.agents.len() as u32;
@@ -109,16 +786,205 @@ impl RegistryService {
                 total_routes_processed: state.metrics.total_routes,
                 average_routing_time_ms: state.metrics.average_routing_time_ms,
                 dedup_cache_size: state.dedup_cache.len() as u32,
+                model_fleet: Self::model_fleet_in(state),
             }
         })
     }
     
-    fn generate_agent_id(principal: &str, model_id: &str) -> String {
+    fn generate_agent_id(principal: &str, model_id: &str, salt: u32) -> String {
         let mut hasher = Sha256::new();
         hasher.update(principal.as_bytes());
         hasher.update(model_id.as_bytes());
         hasher.update(time().to_be_bytes());
+        hasher.update(salt.to_be_bytes());
         let hash = hasher.finalize();
         format!("agent_{}", general_purpose::STANDARD.encode(&hash[..8]))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_capability_change() {
+        let current = vec!["coding".to_string(), "testing".to_string()];
+        let (capabilities, change) = RegistryService::apply_capability_change(
+            &current,
+            &["review".to_string(), "coding".to_string()],
+            &["testing".to_string(), "unknown".to_string()],
+        );
+        assert_eq!(capabilities, vec!["coding".to_string(), "review".to_string()]);
+        assert_eq!(change.added, vec!["review".to_string()]);
+        assert_eq!(change.removed, vec!["testing".to_string()]);
+    }
+
+    #[test]
+    fn test_agents_delta_since_cursor() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("a"), "owner");
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("b"), "owner");
+        let cursor = state.agent_registry_version;
+
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("c"), "owner");
+        RegistryService::mark_changed(&mut state, "a");
+        RegistryService::remove_registration(&mut state, "b", "owner", 0);
+        // Created and removed after the cursor: the client never saw it
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("d"), "owner");
+        RegistryService::remove_registration(&mut state, "d", "owner", 0);
+
+        let delta = RegistryService::delta(&state, cursor);
+        assert!(!delta.full_resync);
+        assert_eq!(delta.created.iter().map(|a| a.agent_id.as_str()).collect::<Vec<_>>(), vec!["c"]);
+        assert_eq!(delta.updated.iter().map(|a| a.agent_id.as_str()).collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(delta.removed, vec!["b".to_string()]);
+        assert!(RegistryService::delta(&state, delta.version).created.is_empty());
+
+        state.agent_tombstone_floor = cursor + 1;
+        let resync = RegistryService::delta(&state, cursor);
+        assert!(resync.full_resync);
+        assert_eq!(resync.created.len(), 2);
+    }
+
+    #[test]
+    fn test_search_matches_tags_model_and_health() {
+        let mut tagged = AgentRegistration::test("a");
+        tagged.tags.insert("region".to_string(), "eu".to_string());
+        tagged.tags.insert("team".to_string(), "infra".to_string());
+        tagged.health_score = 0.6;
+        let mut filter = AgentSearchFilter {
+            capabilities: vec!["coding".to_string()],
+            sla_compliant_only: false,
+            tags: Some([("region".to_string(), "eu".to_string()), ("team".to_string(), String::new())].into_iter().collect()),
+            model_id: Some("model".to_string()),
+            min_health: Some(0.5),
+            max_health: Some(0.8),
+        };
+        assert!(RegistryService::matches(&tagged, &filter));
+        assert!(!RegistryService::matches(&AgentRegistration::test("b"), &filter));
+
+        filter.max_health = Some(0.5);
+        assert!(!RegistryService::matches(&tagged, &filter));
+        filter.max_health = None;
+        filter.model_id = Some("other".to_string());
+        assert!(!RegistryService::matches(&tagged, &filter));
+    }
+
+    #[test]
+    fn test_taxonomy_expands_to_ancestors_and_rejects_cycles() {
+        let mut taxonomy = CapabilityTaxonomy::default();
+        let rust = vec!["rust_programming".to_string()];
+        assert!(taxonomy.satisfies(&rust, "coding"));
+        assert!(!taxonomy.satisfies(&["coding".to_string()], "rust_programming"));
+
+        taxonomy.set_parent("coding", Some("engineering")).unwrap();
+        assert!(taxonomy.satisfies(&rust, "engineering"));
+        assert!(taxonomy.set_parent("engineering", Some("rust_programming")).is_err());
+        assert!(taxonomy.set_parent("coding", Some("coding")).is_err());
+
+        taxonomy.set_parent("rust_programming", None).unwrap();
+        assert!(!taxonomy.satisfies(&rust, "coding"));
+    }
+
+    #[test]
+    fn test_remove_registration_clears_agent_records() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("a"), "owner");
+        state.agent_message_queues.get_or_insert_with(Default::default).insert("a".to_string(), Vec::new());
+
+        assert!(RegistryService::remove_registration(&mut state, "a", "owner", 0).is_some());
+        assert!(state.agents.is_empty());
+        assert!(state.routing_stats.is_empty());
+        assert!(state.agent_capability_profiles.as_ref().unwrap().is_empty());
+        assert!(state.agent_message_queues.as_ref().unwrap().is_empty());
+        assert_eq!(state.metrics.total_agents, 0);
+        assert!(RegistryService::remove_registration(&mut state, "a", "owner", 0).is_none());
+        assert!(state.capability_index.agents_by_capability.is_empty());
+    }
+
+    #[test]
+    fn test_capability_index_follows_registry_and_taxonomy() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("a"), "owner");
+        RegistryService::insert_registration(&mut state, AgentRegistration { capabilities: vec!["rust_programming".to_string()], ..AgentRegistration::test("b") }, "owner");
+        RegistryService::insert_registration(&mut state, AgentRegistration { capabilities: vec!["writing".to_string()], ..AgentRegistration::test("c") }, "owner");
+
+        let mut coding: Vec<_> = RegistryService::capable_ids_in(&state, &["coding".to_string()]).into_iter().cloned().collect();
+        coding.sort();
+        assert_eq!(coding, vec!["a".to_string(), "b".to_string()], "rust_programming satisfies coding");
+
+        let version = state.agent_registry_version;
+        let detached = RegistryService::detach_in(&mut state, "b", 10).unwrap();
+        assert_eq!(RegistryService::capable_ids_in(&state, &["coding".to_string()]).len(), 1);
+        assert!(state.agent_changes["b"].removed, "delta readers see the agent leave");
+        RegistryService::reattach_in(&mut state, detached, 20);
+        assert!(!state.agent_changes["b"].removed);
+        assert!(state.agent_registry_version > version);
+        assert_eq!(state.registry_audit.iter().filter(|entry| entry.caller == RegistryAuditService::REGISTRY_SHARDS).count(), 2);
+
+        let mut rebuilt = CoordinatorState { agents: state.agents.clone(), ..Default::default() };
+        RegistryService::rebuild_capability_index(&mut rebuilt);
+        assert_eq!(rebuilt.capability_index.agents_by_capability, state.capability_index.agents_by_capability);
+    }
+
+    #[test]
+    fn test_lifecycle_transitions_are_validated_and_audited() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, AgentRegistration { lifecycle: Some(AgentLifecycle::Provisioning), ..AgentRegistration::test("a") }, "owner");
+        assert!(!state.agents["a"].is_routable(), "a provisioning agent takes no work");
+        assert!(RegistryService::transition_lifecycle_in(&mut state, "a", AgentLifecycle::Active, "owner", 20).is_err());
+
+        for to in [AgentLifecycle::Ready, AgentLifecycle::Active, AgentLifecycle::Active, AgentLifecycle::Draining] {
+            RegistryService::transition_lifecycle_in(&mut state, "a", to, "owner", 20).unwrap();
+        }
+        assert!(!state.agents["a"].is_routable());
+        RegistryService::transition_lifecycle_in(&mut state, "a", AgentLifecycle::Decommissioned, "owner", 30).unwrap();
+        assert!(RegistryService::transition_lifecycle_in(&mut state, "a", AgentLifecycle::Ready, "owner", 40).is_err(), "decommissioning is final");
+
+        let changes = state.registry_audit.iter().filter(|e| matches!(e.change, RegistryChange::LifecycleChanged { .. })).count();
+        assert_eq!(changes, 4, "moving to the current stage is not recorded");
+        assert!(AgentRegistration::test("legacy").is_routable(), "records without a lifecycle count as active");
+    }
+
+    #[test]
+    fn test_model_fleet_counts_live_agents_per_model() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, AgentRegistration { model_id: "mistral".to_string(), ..AgentRegistration::test("a") }, "owner");
+        RegistryService::insert_registration(&mut state, AgentRegistration { model_id: "code-llama".to_string(), ..AgentRegistration::test("b") }, "owner");
+        RegistryService::insert_registration(&mut state, AgentRegistration { model_id: "code-llama".to_string(), health_score: 0.2, ..AgentRegistration::test("c") }, "owner");
+        RegistryService::insert_registration(&mut state, AgentRegistration { model_id: "code-llama".to_string(), state: Some(AgentState::Suspended), ..AgentRegistration::test("d") }, "owner");
+
+        let fleet = RegistryService::model_fleet_in(&state);
+        assert_eq!(fleet.iter().map(|m| m.model_id.as_str()).collect::<Vec<_>>(), vec!["code-llama", "mistral"]);
+        assert_eq!((fleet[0].total_agents, fleet[0].active_agents), (3, 1));
+        assert_eq!((fleet[1].total_agents, fleet[1].active_agents), (1, 1));
+    }
+
+    #[test]
+    fn test_duplicate_registration_is_found_and_updated_in_place() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("a"), "owner");
+        let resubmitted = AgentRegistration {
+            agent_id: String::new(),
+            capabilities: vec!["coding".to_string(), "review".to_string()],
+            agent_version: Some("2.0".to_string()),
+            ..AgentRegistration::test("a")
+        };
+        assert_eq!(RegistryService::find_duplicate_in(&state, &resubmitted), Ok(Some("a".to_string())));
+        let other_model = AgentRegistration { model_id: "mistral".to_string(), ..resubmitted.clone() };
+        assert_eq!(RegistryService::find_duplicate_in(&state, &other_model), Ok(None));
+        assert!(RegistryService::check_registrant("owner", &resubmitted).is_ok());
+        assert!(RegistryService::check_registrant("mallory", &resubmitted).is_err());
+
+        assert!(RegistryService::update_in_place_in(&mut state, "a", resubmitted.clone(), "someone-else", 20).is_err());
+        RegistryService::update_in_place_in(&mut state, "a", resubmitted, "owner", 20).unwrap();
+        assert_eq!(state.agents.len(), 1);
+        let updated = &state.agents["a"];
+        assert_eq!(updated.agent_version.as_deref(), Some("2.0"));
+        assert_eq!(updated.capabilities, vec!["coding".to_string(), "review".to_string()]);
+        assert_eq!(RegistryService::capable_ids_in(&state, &["review".to_string()]).len(), 1);
+
+        state.agents.get_mut("a").unwrap().lifecycle = Some(AgentLifecycle::Decommissioned);
+        assert_eq!(RegistryService::find_duplicate_in(&state, &AgentRegistration::test("a")), Ok(None));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::domain::*;
+use crate::services::{with_state, CoordinatorState};
+use crate::services::persistence::{PersistenceService, REGISTRY_AUDIT_DATA, REGISTRY_AUDIT_INDEX};
+use candid::CandidType;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableLog, Storable};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+/// Append-only trail of registry changes: agents entering and leaving the registry,
+/// health score and lifecycle changes, each with the principal or subsystem responsible and
+/// the values before and after. Kept for compliance review and for debugging why an
+/// agent stopped receiving traffic. The newest entries stay in the heap; once the heap
+/// trail is full its oldest entries move to a stable-memory archive, so nothing is lost
+/// and the upgrade image stays bounded. Routine liveness decay is not recorded.
+pub struct RegistryAuditService;
+
+type ArchiveMemory = VirtualMemory<DefaultMemoryImpl>;
+
+thread_local! {
+    /// Entries moved out of the heap trail, oldest first
+    static ARCHIVE: RefCell<StableLog<RegistryAuditEntry, ArchiveMemory, ArchiveMemory>> = RefCell::new(
+        StableLog::init(PersistenceService::memory(REGISTRY_AUDIT_INDEX), PersistenceService::memory(REGISTRY_AUDIT_DATA))
+            .expect("registry audit archive is unreadable"),
+    );
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub enum RegistryChange {
+    /// The agent entered the registry, or its record was replaced by an import or re-registration
+    Registered { after: AgentRegistration },
+    Deregistered { before: AgentRegistration },
+    HealthUpdated { before: f32, after: f32 },
+    LifecycleChanged { before: AgentLifecycle, after: AgentLifecycle },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RegistryAuditEntry {
+    pub sequence: u64,
+    pub agent_id: String,
+    /// Principal that made the change, or `coordinator:<subsystem>` for automatic ones
+    pub caller: String,
+    pub recorded_at: u64,
+    pub change: RegistryChange,
+}
+
+impl Storable for RegistryAuditEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(candid::encode_one(self).expect("registry audit entry encodes"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("registry audit entry decodes")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Time range over `recorded_at`; `from` is inclusive and `to` exclusive
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct RegistryAuditRange {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+impl RegistryAuditService {
+    const MAX_ENTRIES: usize = 20_000;
+
+    pub const LIVENESS: &'static str = "coordinator:liveness";
+    pub const CIRCUIT_BREAKER: &'static str = "coordinator:circuit_breaker";
+    pub const RECLAMATION: &'static str = "coordinator:reclamation";
+    pub const WARM_POOL: &'static str = "coordinator:warm_pool";
+    pub const LOAD_TEST: &'static str = "coordinator:load_test";
+    pub const ROUTING: &'static str = "coordinator:routing";
+    pub const SPAWNING: &'static str = "coordinator:spawning";
+    pub const REGISTRY_SHARDS: &'static str = "coordinator:registry_shards";
+
+    pub fn record_in(state: &mut CoordinatorState, agent_id: &str, caller: &str, recorded_at: u64, change: RegistryChange) {
+        state.registry_audit_sequence += 1;
+        if state.registry_audit.len() >= Self::MAX_ENTRIES {
+            if let Some(oldest) = state.registry_audit.pop_front() {
+                Self::archive(&oldest);
+            }
+        }
+        state.registry_audit.push_back(RegistryAuditEntry {
+            sequence: state.registry_audit_sequence,
+            agent_id: agent_id.to_string(),
+            caller: caller.to_string(),
+            recorded_at,
+            change,
+        });
+    }
+
+    fn archive(entry: &RegistryAuditEntry) {
+        ARCHIVE.with(|archive| {
+            archive.borrow().append(entry).expect("stable memory exhausted archiving registry audit");
+        });
+    }
+
+    /// Entries for one agent within `range`, oldest first, archived ones included
+    pub fn query(agent_id: &str, range: &RegistryAuditRange) -> Vec<RegistryAuditEntry> {
+        with_state(|state| Self::query_in(state, agent_id, range))
+    }
+
+    fn query_in(state: &CoordinatorState, agent_id: &str, range: &RegistryAuditRange) -> Vec<RegistryAuditEntry> {
+        let in_range = |entry: &RegistryAuditEntry| {
+            entry.agent_id == agent_id
+                && range.from.is_none_or(|from| entry.recorded_at >= from)
+                && range.to.is_none_or(|to| entry.recorded_at < to)
+        };
+        let mut entries: Vec<RegistryAuditEntry> = ARCHIVE.with(|archive| {
+            let archive = archive.borrow();
+            (0..archive.len()).filter_map(|i| archive.get(i)).filter(|entry| in_range(entry)).collect()
+        });
+        entries.extend(state.registry_audit.iter().filter(|entry| in_range(entry)).cloned());
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::RegistryService;
+
+    fn agent(id: &str) -> AgentRegistration {
+        AgentRegistration { model_id: "llama".to_string(), registered_at: 10, last_seen: 10, ..AgentRegistration::test(id) }
+    }
+
+    #[test]
+    fn test_registry_paths_leave_a_trail() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, agent("a"), "owner");
+        RegistryService::insert_registration(&mut state, agent("b"), "owner");
+        RegistryService::set_health_in(&mut state, "a", 0.4, RegistryAuditService::LIVENESS, 20);
+        RegistryService::set_health_in(&mut state, "a", 0.4, RegistryAuditService::LIVENESS, 25);
+        RegistryService::remove_registration(&mut state, "a", "owner", 30);
+
+        let trail = RegistryAuditService::query_in(&state, "a", &RegistryAuditRange::default());
+        assert_eq!(trail.len(), 3, "an unchanged health score is not recorded");
+        assert!(matches!(trail[0].change, RegistryChange::Registered { .. }));
+        assert!(matches!(trail[1].change, RegistryChange::HealthUpdated { before, after } if before == 1.0 && after == 0.4));
+        assert_eq!(trail[1].caller, RegistryAuditService::LIVENESS);
+        assert!(matches!(&trail[2].change, RegistryChange::Deregistered { before } if before.health_score == 0.4));
+
+        let range = RegistryAuditRange { from: Some(20), to: Some(30) };
+        let trail = RegistryAuditService::query_in(&state, "a", &range);
+        assert_eq!(trail.len(), 1);
+        assert!(trail.iter().all(|e| e.sequence > 1));
+    }
+
+    #[test]
+    fn test_full_trail_archives_its_oldest_entries() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, agent("a"), "owner");
+        for i in 0..RegistryAuditService::MAX_ENTRIES as u64 {
+            let change = RegistryChange::HealthUpdated { before: 1.0, after: 0.5 };
+            RegistryAuditService::record_in(&mut state, "b", RegistryAuditService::LIVENESS, 20 + i, change);
+        }
+        assert_eq!(state.registry_audit.len(), RegistryAuditService::MAX_ENTRIES);
+        assert!(state.registry_audit.iter().all(|e| e.agent_id == "b"));
+
+        let trail = RegistryAuditService::query_in(&state, "a", &RegistryAuditRange::default());
+        assert_eq!(trail.len(), 1);
+        assert!(matches!(trail[0].change, RegistryChange::Registered { .. }));
+        assert_eq!(RegistryAuditService::query_in(&state, "b", &RegistryAuditRange::default()).len(), RegistryAuditService::MAX_ENTRIES);
+    }
+}
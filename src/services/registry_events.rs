@@ -0,0 +1,227 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, CoordinatorState};
+use crate::infra::Metrics;
+use ic_cdk::api::{call, time};
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Push of registry changes to subscribed canisters, so downstream systems need not
+/// poll `list_agents`. Changes queue while anyone is subscribed and go out from the
+/// maintenance timer as one-way `on_registry_event` notifications carrying a batch of
+/// events. Delivery is at most once: a subscriber that sees a gap in `sequence`
+/// resynchronizes with `get_agents_delta`. A canister subscribes itself, and one that
+/// keeps rejecting notifications is dropped.
+pub struct RegistryEventService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub enum RegistryEventKind {
+    /// The agent entered the registry or re-registered in place
+    Registered,
+    Deregistered,
+    LifecycleChanged { before: AgentLifecycle, after: AgentLifecycle },
+    StateChanged { before: AgentState, after: AgentState },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RegistryEvent {
+    pub sequence: u64,
+    pub agent_id: String,
+    pub kind: RegistryEventKind,
+    pub occurred_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RegistrySubscription {
+    pub callback_canister: Principal,
+    /// Principal that subscribed and may unsubscribe
+    pub subscriber: String,
+    pub subscribed_at: u64,
+    pub notifications_sent: u64,
+    pub failed_notifications: u64,
+    /// Failures since the last successful notification; the subscription is dropped past a limit
+    #[serde(default)]
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistryEventState {
+    /// Keyed by callback canister text
+    pub subscriptions: BTreeMap<String, RegistrySubscription>,
+    pub pending: VecDeque<RegistryEvent>,
+    pub sequence: u64,
+}
+
+impl RegistryEventService {
+    const CALLBACK_METHOD: &'static str = "on_registry_event";
+    const MAX_SUBSCRIPTIONS: usize = 50;
+    /// Oldest undelivered events are dropped past this
+    const MAX_PENDING: usize = 5000;
+    const MAX_BATCH: usize = 200;
+    /// Failed notifications in a row after which a subscription is dropped
+    const MAX_CONSECUTIVE_FAILURES: u32 = 10;
+
+    /// Only the callback canister itself may subscribe, so each subscriber holds at most one slot
+    pub fn subscribe(caller: &str, callback_canister: Principal) -> Result<RegistrySubscription, String> {
+        if callback_canister == Principal::anonymous() || callback_canister == Principal::management_canister() {
+            return Err("Callback must be a canister".to_string());
+        }
+        if callback_canister.to_text() != caller {
+            return Err("A canister can only subscribe itself to registry events".to_string());
+        }
+        let now = time();
+        with_state_mut(|state| {
+            let subscriptions = &mut state.registry_events.subscriptions;
+            let key = callback_canister.to_text();
+            if let Some(existing) = subscriptions.get(&key) {
+                return Ok(existing.clone());
+            }
+            if subscriptions.len() >= Self::MAX_SUBSCRIPTIONS {
+                return Err(format!("At most {} canisters may subscribe to registry events", Self::MAX_SUBSCRIPTIONS));
+            }
+            let subscription = RegistrySubscription {
+                callback_canister,
+                subscriber: caller.to_string(),
+                subscribed_at: now,
+                notifications_sent: 0,
+                failed_notifications: 0,
+                consecutive_failures: 0,
+                last_error: None,
+            };
+            subscriptions.insert(key, subscription.clone());
+            Ok(subscription)
+        })
+    }
+
+    /// Removable by whoever subscribed it or by the callback canister itself
+    pub fn unsubscribe(caller: &str, callback_canister: Principal) -> Result<(), String> {
+        with_state_mut(|state| {
+            let subscriptions = &mut state.registry_events.subscriptions;
+            let key = callback_canister.to_text();
+            let subscription = subscriptions.get(&key).ok_or_else(|| format!("{} is not subscribed", key))?;
+            if subscription.subscriber != caller && key != caller {
+                return Err("Only the subscriber or the callback canister can unsubscribe".to_string());
+            }
+            subscriptions.remove(&key);
+            if subscriptions.is_empty() {
+                state.registry_events.pending.clear();
+            }
+            Ok(())
+        })
+    }
+
+    pub fn list() -> Vec<RegistrySubscription> {
+        with_state(|state| state.registry_events.subscriptions.values().cloned().collect())
+    }
+
+    /// Queue a change for subscribers; nothing is kept while nobody listens
+    pub fn publish_in(state: &mut CoordinatorState, agent_id: &str, kind: RegistryEventKind, now: u64) {
+        let events = &mut state.registry_events;
+        if events.subscriptions.is_empty() {
+            return;
+        }
+        events.sequence += 1;
+        if events.pending.len() >= Self::MAX_PENDING {
+            events.pending.pop_front();
+            Metrics::increment_counter("registry_events_dropped_total");
+        }
+        events.pending.push_back(RegistryEvent {
+            sequence: events.sequence,
+            agent_id: agent_id.to_string(),
+            kind,
+            occurred_at: now,
+        });
+    }
+
+    /// Called from the maintenance timer: sends one batch of queued events to every subscriber
+    pub fn tick() {
+        let (batch, targets) = with_state_mut(|state| {
+            let events = &mut state.registry_events;
+            let count = events.pending.len().min(Self::MAX_BATCH);
+            let batch: Vec<RegistryEvent> = events.pending.drain(..count).collect();
+            let targets: Vec<Principal> = events.subscriptions.values().map(|s| s.callback_canister).collect();
+            (batch, targets)
+        });
+        if batch.is_empty() {
+            return;
+        }
+        let outcomes: Vec<(Principal, Result<(), String>)> = targets
+            .into_iter()
+            .map(|target| {
+                let outcome = call::notify(target, Self::CALLBACK_METHOD, (batch.clone(),))
+                    .map_err(|code| format!("{:?}", code));
+                (target, outcome)
+            })
+            .collect();
+        with_state_mut(|state| {
+            for (target, outcome) in outcomes {
+                Self::record_outcome_in(state, &target.to_text(), outcome);
+            }
+        });
+        Metrics::add_to_counter("registry_events_sent_total", batch.len() as u64);
+    }
+
+    fn record_outcome_in(state: &mut CoordinatorState, key: &str, outcome: Result<(), String>) {
+        let subscriptions = &mut state.registry_events.subscriptions;
+        let Some(subscription) = subscriptions.get_mut(key) else { return };
+        match outcome {
+            Ok(()) => {
+                subscription.notifications_sent += 1;
+                subscription.consecutive_failures = 0;
+            }
+            Err(e) => {
+                subscription.failed_notifications += 1;
+                subscription.consecutive_failures += 1;
+                subscription.last_error = Some(e);
+                Metrics::increment_counter("registry_event_notify_failures_total");
+                if subscription.consecutive_failures >= Self::MAX_CONSECUTIVE_FAILURES {
+                    subscriptions.remove(key);
+                    Metrics::increment_counter("registry_subscriptions_evicted_total");
+                    if subscriptions.is_empty() {
+                        state.registry_events.pending.clear();
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_queue_only_while_subscribed() {
+        let mut state = CoordinatorState::default();
+        RegistryEventService::publish_in(&mut state, "a", RegistryEventKind::Registered, 10);
+        assert!(state.registry_events.pending.is_empty());
+
+        let callback = Principal::from_text("rrkah-fqaaa-aaaaa-aaaaq-cai").unwrap();
+        state.registry_events.subscriptions.insert(callback.to_text(), RegistrySubscription {
+            callback_canister: callback,
+            subscriber: "owner".to_string(),
+            subscribed_at: 0,
+            notifications_sent: 0,
+            failed_notifications: 0,
+            consecutive_failures: 0,
+            last_error: None,
+        });
+        RegistryEventService::publish_in(&mut state, "a", RegistryEventKind::Registered, 10);
+        RegistryEventService::publish_in(&mut state, "a", RegistryEventKind::Deregistered, 20);
+        let sequences: Vec<u64> = state.registry_events.pending.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, vec![1, 2]);
+
+        // A success resets the failure run; a long enough run drops the subscriber
+        let key = callback.to_text();
+        RegistryEventService::record_outcome_in(&mut state, &key, Err("rejected".to_string()));
+        RegistryEventService::record_outcome_in(&mut state, &key, Ok(()));
+        assert_eq!(state.registry_events.subscriptions[&key].consecutive_failures, 0);
+        for _ in 0..RegistryEventService::MAX_CONSECUTIVE_FAILURES {
+            RegistryEventService::record_outcome_in(&mut state, &key, Err("rejected".to_string()));
+        }
+        assert!(state.registry_events.subscriptions.is_empty());
+        assert!(state.registry_events.pending.is_empty());
+    }
+}
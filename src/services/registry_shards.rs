@@ -0,0 +1,374 @@
+use crate::domain::{AgentLifecycle, AgentRegistration, AgentSearchFilter, AgentSearchResult, AgentState};
+use crate::services::{with_state, with_state_mut, CoordinatorState, RegistryService, SlaService};
+use crate::infra::{Log, Metrics, Page, PageOrder, PageRequest, Paginator};
+use futures::future::join_all;
+use ic_cdk::api::{call, time};
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+
+/// Moves agent records out to child registry canisters once the local registry grows past
+/// `spill_threshold`. Only agents out of service are spilled (quarantined, suspended,
+/// retired, decommissioned or silent past the liveness window), least recently seen
+/// first, so routing never needs a child. Each spilled agent goes to the child its id
+/// hashes to, and the parent keeps a stub naming that child. `get_agent`, `list_agents`
+/// and `search_agents` stay plain queries over the local registry, callable from other
+/// canisters; `find_agent`, `list_all_agents` and `search_all_agents` also ask the
+/// children. A spilled agent is recalled when it heartbeats, or by an admin. The same
+/// canister code serves as a child once configured with a `parent`, which is the only
+/// caller its child endpoints accept. The fan-out reads are composite queries, so
+/// children must share the parent's subnet.
+pub struct RegistryShardService;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, CandidType)]
+pub struct RegistryShardConfig {
+    /// Child registry canisters records are spilled to; empty to stop spilling
+    pub children: Vec<String>,
+    /// Local agent count above which `spill` moves records out
+    pub spill_threshold: u32,
+    /// Set on a child: the coordinator allowed to store and read records here
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistryShardState {
+    pub config: RegistryShardConfig,
+    /// Agents spilled from here, keyed by agent id
+    pub spilled: HashMap<String, SpilledAgent>,
+    /// Records held here for a parent, keyed by agent id
+    pub held: HashMap<String, AgentRegistration>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpilledAgent {
+    pub child: String,
+    pub agent_principal: String,
+    pub spilled_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RegistryShardStatus {
+    pub config: RegistryShardConfig,
+    pub local_agents: u32,
+    pub spilled_agents: u32,
+    pub held_agents: u32,
+}
+
+impl RegistryShardService {
+    const MAX_CHILDREN: usize = 32;
+    const MAX_SPILL_BATCH: u32 = 1_000;
+
+    pub fn configure(config: RegistryShardConfig) -> Result<(), String> {
+        if config.children.len() > Self::MAX_CHILDREN {
+            return Err(format!("At most {} child registries are supported", Self::MAX_CHILDREN));
+        }
+        let mut children = BTreeSet::new();
+        for child in config.children.iter().chain(config.parent.iter()) {
+            Principal::from_text(child).map_err(|e| format!("Invalid canister id {}: {}", child, e))?;
+            if !children.insert(child.as_str()) {
+                return Err(format!("Canister {} is listed twice", child));
+            }
+        }
+        with_state_mut(|state| {
+            // Children still holding spilled records must stay reachable
+            if let Some(stub) = state.registry_shards.spilled.values().find(|stub| !config.children.contains(&stub.child)) {
+                return Err(format!("Child {} still holds spilled agents; recall them first", stub.child));
+            }
+            state.registry_shards.config = config;
+            Ok(())
+        })
+    }
+
+    pub fn status() -> RegistryShardStatus {
+        with_state(|state| RegistryShardStatus {
+            config: state.registry_shards.config.clone(),
+            local_agents: state.agents.len() as u32,
+            spilled_agents: state.registry_shards.spilled.len() as u32,
+            held_agents: state.registry_shards.held.len() as u32,
+        })
+    }
+
+    pub fn is_spilled(agent_id: &str) -> bool {
+        with_state(|state| state.registry_shards.spilled.contains_key(agent_id))
+    }
+
+    /// The child an agent id hashes to
+    fn child_for<'a>(children: &'a [String], agent_id: &str) -> &'a String {
+        let digest = Sha256::digest(agent_id.as_bytes());
+        let bucket = u64::from_be_bytes(digest[..8].try_into().expect("8 bytes")) % children.len() as u64;
+        &children[bucket as usize]
+    }
+
+    // Parent side
+
+    /// Move up to `limit` out-of-service agents to the children while the registry is over its threshold; returns how many moved
+    pub async fn spill(limit: u32) -> Result<u32, String> {
+        let limit = limit.min(Self::MAX_SPILL_BATCH) as usize;
+        let now = time();
+        let outgoing = with_state_mut(|state| Self::detach_in(state, limit, now))?;
+        let mut moved = 0;
+        for (child, agents) in outgoing {
+            let outcome = match Principal::from_text(&child) {
+                Ok(target) => match call::call::<_, (Result<u32, String>,)>(target, "registry_child_store", (agents.clone(),)).await {
+                    Ok((result,)) => result,
+                    Err((code, msg)) => Err(format!("{:?}: {}", code, msg)),
+                },
+                Err(e) => Err(e.to_string()),
+            };
+            match outcome {
+                Ok(_) => {
+                    moved += agents.len() as u32;
+                    Metrics::record_dependency_success("registry_child.store");
+                }
+                Err(e) => {
+                    // Nothing was stored, so take the records back
+                    Metrics::record_dependency_failure("registry_child.store", &e);
+                    Log::warn("registry_shards", format!("Failed to spill {} agents to {}: {}", agents.len(), child, e));
+                    with_state_mut(|state| Self::restore_in(state, agents, now));
+                }
+            }
+        }
+        Metrics::add_to_counter("registry_agents_spilled_total", moved as u64);
+        Ok(moved)
+    }
+
+    /// Take the agents to spill out of the registry, grouped by the child they go to
+    fn detach_in(state: &mut CoordinatorState, limit: usize, now: u64) -> Result<HashMap<String, Vec<AgentRegistration>>, String> {
+        let config = &state.registry_shards.config;
+        if config.children.is_empty() {
+            return Err("No child registries are configured".to_string());
+        }
+        let excess = state.agents.len().saturating_sub(config.spill_threshold as usize);
+        let mut candidates: Vec<(u64, String)> = state.agents
+            .values()
+            .filter(|agent| Self::out_of_service(state, agent))
+            .map(|agent| (agent.last_seen, agent.agent_id.clone()))
+            .collect();
+        candidates.sort();
+        let children = config.children.clone();
+        let mut outgoing: HashMap<String, Vec<AgentRegistration>> = HashMap::new();
+        for (_, agent_id) in candidates.into_iter().take(excess.min(limit)) {
+            let Some(agent) = RegistryService::detach_in(state, &agent_id, now) else { continue };
+            let child = Self::child_for(&children, &agent_id).clone();
+            state.registry_shards.spilled.insert(agent_id, SpilledAgent {
+                child: child.clone(),
+                agent_principal: agent.agent_principal.clone(),
+                spilled_at: now,
+            });
+            outgoing.entry(child).or_default().push(agent);
+        }
+        Ok(outgoing)
+    }
+
+    /// Agents routing will not pick until someone acts on them
+    fn out_of_service(state: &CoordinatorState, agent: &AgentRegistration) -> bool {
+        state.quarantined_agents.contains_key(&agent.agent_id)
+            || state.stale_agents.contains_key(&agent.agent_id)
+            || matches!(agent.agent_state(), AgentState::Suspended | AgentState::Quarantined | AgentState::Retired)
+            || agent.lifecycle() == AgentLifecycle::Decommissioned
+    }
+
+    fn restore_in(state: &mut CoordinatorState, agents: Vec<AgentRegistration>, now: u64) {
+        for agent in agents {
+            state.registry_shards.spilled.remove(&agent.agent_id);
+            RegistryService::reattach_in(state, agent, now);
+        }
+    }
+
+    /// Bring a spilled agent's record back into the local registry
+    pub async fn recall(agent_id: &str) -> Result<(), String> {
+        let child = with_state(|state| state.registry_shards.spilled.get(agent_id).map(|stub| stub.child.clone()))
+            .ok_or_else(|| format!("Agent {} is not spilled", agent_id))?;
+        let target = Principal::from_text(&child).map_err(|e| e.to_string())?;
+        let (result,): (Result<AgentRegistration, String>,) = call::call(target, "registry_child_take", (agent_id.to_string(),))
+            .await
+            .map_err(|(code, msg)| format!("Failed to recall agent {} from {}: {:?}: {}", agent_id, child, code, msg))?;
+        let agent = result?;
+        let now = time();
+        with_state_mut(|state| {
+            // A concurrent recall may have got there first
+            if state.registry_shards.spilled.contains_key(agent_id) {
+                Self::restore_in(state, vec![agent], now);
+            }
+        });
+        Metrics::increment_counter("registry_agents_recalled_total");
+        Ok(())
+    }
+
+    /// Read a spilled agent's record from its child
+    pub async fn lookup(agent_id: &str) -> Result<AgentRegistration, String> {
+        let child = with_state(|state| state.registry_shards.spilled.get(agent_id).map(|stub| stub.child.clone()))
+            .ok_or_else(|| "Agent not found".to_string())?;
+        let target = Principal::from_text(&child).map_err(|e| e.to_string())?;
+        let (result,): (Result<AgentRegistration, String>,) = call::call(target, "registry_child_get", (agent_id.to_string(),))
+            .await
+            .map_err(|(code, msg)| format!("Child registry {} unavailable: {:?}: {}", child, code, msg))?;
+        result
+    }
+
+    /// Agents matching `filter` here and on every child, paged together by agent id. Each
+    /// child is asked for enough agents after the cursor to fill the page; when a child has
+    /// more, the page stops at its last agent so none is skipped.
+    pub async fn list_everywhere(filter: Option<AgentSearchFilter>, page: Option<PageRequest>) -> Result<Page<AgentRegistration>, String> {
+        let page = page.unwrap_or_default();
+        let (mut merged, mut total, children) = with_state(|state| {
+            let local: Vec<AgentRegistration> = state.agents
+                .values()
+                .filter(|agent| filter.as_ref().is_none_or(|filter| RegistryService::matches(agent, filter)))
+                .cloned()
+                .collect();
+            let total = local.len() as u64;
+            (local, total, state.registry_shards.config.children.clone())
+        });
+        let wanted = page.limit.map_or(Paginator::DEFAULT_PAGE_SIZE, |limit| limit as usize) + page.offset.unwrap_or(0) as usize;
+        let child_page = PageRequest { cursor: page.cursor.clone(), limit: Some(wanted.min(Paginator::MAX_PAGE_SIZE) as u32), offset: None };
+        let calls = children.iter().map(|child| {
+            let args = (filter.clone(), child_page.clone());
+            async move {
+                let target = Principal::from_text(child).map_err(|e| e.to_string())?;
+                let (result,): (Result<Page<AgentRegistration>, String>,) = call::call(target, "registry_child_list", args)
+                    .await
+                    .map_err(|(code, msg)| format!("Child registry {} unavailable: {:?}: {}", child, code, msg))?;
+                result
+            }
+        });
+        let mut bound: Option<String> = None;
+        for result in join_all(calls).await {
+            let held = result?;
+            total += held.total;
+            if let (Some(_), Some(last)) = (&held.next_cursor, held.items.last()) {
+                if bound.as_ref().is_none_or(|bound| last.agent_id < *bound) {
+                    bound = Some(last.agent_id.clone());
+                }
+            }
+            merged.extend(held.items);
+        }
+        if let Some(bound) = &bound {
+            merged.retain(|agent| agent.agent_id <= *bound);
+        }
+        let mut result = Paginator::paginate(&merged, |agent| agent.agent_id.clone(), PageOrder::Ascending, Some(page));
+        result.total = total;
+        // A child still has agents past the bound
+        if bound.is_some() && result.next_cursor.is_none() {
+            result.next_cursor = result.items.last().map(|agent| agent.agent_id.clone());
+        }
+        Ok(result)
+    }
+
+    /// `search_agents` across the local registry and every child. SLA standing is kept
+    /// here, so it is judged after the merge and a page may come back short.
+    pub async fn search_everywhere(filter: AgentSearchFilter, page: Option<PageRequest>) -> Result<Page<AgentSearchResult>, String> {
+        let sla_compliant_only = filter.sla_compliant_only;
+        let page = Self::list_everywhere(Some(filter), page).await?;
+        let mut results = page.map(|agent| {
+            let sla_compliance = SlaService::get_compliance(&agent.agent_id);
+            AgentSearchResult { agent, sla_compliance }
+        });
+        if sla_compliant_only {
+            results.items.retain(|result| result.sla_compliance.as_ref().is_some_and(|c| c.compliant));
+        }
+        Ok(results)
+    }
+
+    // Child side
+
+    /// Err unless the caller is the configured parent
+    pub fn require_parent(caller: &str) -> Result<(), String> {
+        with_state(|state| match &state.registry_shards.config.parent {
+            Some(parent) if parent == caller => Ok(()),
+            _ => Err("Only the parent coordinator may use the child registry".to_string()),
+        })
+    }
+
+    pub fn store(agents: Vec<AgentRegistration>) -> u32 {
+        with_state_mut(|state| {
+            let count = agents.len() as u32;
+            state.registry_shards.held.extend(agents.into_iter().map(|agent| (agent.agent_id.clone(), agent)));
+            count
+        })
+    }
+
+    pub fn list_held(filter: Option<&AgentSearchFilter>, page: PageRequest) -> Page<AgentRegistration> {
+        with_state(|state| {
+            let held = state.registry_shards.held
+                .values()
+                .filter(|agent| filter.is_none_or(|filter| RegistryService::matches(agent, filter)));
+            Paginator::paginate(held, |agent| agent.agent_id.clone(), PageOrder::Ascending, Some(page))
+        })
+    }
+
+    pub fn get_held(agent_id: &str) -> Result<AgentRegistration, String> {
+        with_state(|state| state.registry_shards.held.get(agent_id).cloned()).ok_or_else(|| "Agent not found".to_string())
+    }
+
+    pub fn take_held(agent_id: &str) -> Result<AgentRegistration, String> {
+        with_state_mut(|state| state.registry_shards.held.remove(agent_id)).ok_or_else(|| "Agent not found".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::quarantine::QuarantineRecord;
+
+    fn agent(agent_id: &str, last_seen: u64) -> AgentRegistration {
+        AgentRegistration { last_seen, ..AgentRegistration::test(agent_id) }
+    }
+
+    fn quarantine(state: &mut CoordinatorState, agent_id: &str) {
+        state.quarantined_agents.insert(agent_id.to_string(), QuarantineRecord {
+            agent_id: agent_id.to_string(),
+            canister_id: "canister".to_string(),
+            reason: "misbehaving".to_string(),
+            quarantined_by: "admin".to_string(),
+            quarantined_at: 0,
+            cancelled_deliveries: 0,
+            reassigned_tasks: vec![],
+            unassigned_tasks: vec![],
+        });
+    }
+
+    #[test]
+    fn test_out_of_service_covers_every_reason_routing_skips_an_agent() {
+        let mut state = CoordinatorState::default();
+        let live = agent("live", 0);
+        assert!(!RegistryShardService::out_of_service(&state, &live));
+        assert!(RegistryShardService::out_of_service(&state, &AgentRegistration { state: Some(AgentState::Suspended), ..agent("a", 0) }));
+        assert!(RegistryShardService::out_of_service(&state, &AgentRegistration { lifecycle: Some(AgentLifecycle::Decommissioned), ..agent("a", 0) }));
+        state.stale_agents.insert("live".to_string(), 1.0);
+        assert!(RegistryShardService::out_of_service(&state, &live), "silent past the liveness window");
+    }
+
+    #[test]
+    fn test_spill_takes_oldest_out_of_service_agents_over_the_threshold() {
+        let mut state = CoordinatorState::default();
+        assert!(RegistryShardService::detach_in(&mut state, 10, 0).is_err());
+
+        state.registry_shards.config = RegistryShardConfig {
+            children: vec!["child-a".to_string(), "child-b".to_string()],
+            spill_threshold: 2,
+            parent: None,
+        };
+        for (agent_id, last_seen) in [("live", 1), ("old", 1), ("newer", 5), ("newest", 9)] {
+            RegistryService::insert_registration(&mut state, agent(agent_id, last_seen), "owner");
+            if agent_id != "live" {
+                quarantine(&mut state, agent_id);
+            }
+        }
+
+        // Four agents against a threshold of two: the two least recently seen out-of-service agents go
+        let outgoing = RegistryShardService::detach_in(&mut state, 10, 100).unwrap();
+        let mut spilled: Vec<String> = outgoing.values().flatten().map(|agent| agent.agent_id.clone()).collect();
+        spilled.sort();
+        assert_eq!(spilled, vec!["newer".to_string(), "old".to_string()]);
+        assert_eq!(state.agents.len(), 2);
+        assert_eq!(state.registry_shards.spilled["old"].child, *RegistryShardService::child_for(&state.registry_shards.config.children, "old"));
+
+        // A failed store puts the records back
+        RegistryShardService::restore_in(&mut state, outgoing.into_values().flatten().collect(), 200);
+        assert_eq!(state.agents.len(), 4);
+        assert!(state.registry_shards.spilled.is_empty());
+    }
+}
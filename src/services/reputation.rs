@@ -0,0 +1,370 @@
+use crate::services::{with_state, with_state_mut, AuditService, NotificationService, RegistryService};
+use crate::services::audit::AuditAction;
+use crate::services::notifications::NotificationKind;
+use crate::services::review::ReviewVerdict;
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Long-lived agent reputation built from finished tasks, peer review verdicts and
+/// user ratings. Unlike the health score, which tracks how an agent is doing right
+/// now, reputation accumulates over the agent's whole history and is kept when the
+/// agent leaves the registry, so re-registering does not wipe a poor record.
+///
+/// Evidence loses half its weight every `HALF_LIFE_NS`, so old outcomes fade and
+/// the score drifts back toward the baseline. An agent owner can dispute a
+/// rejecting review verdict; if an admin accepts the dispute, the rejection is
+/// taken back out of the agent's evidence.
+pub struct ReputationService;
+
+/// Evidence behind an agent's reputation, as weights decayed up to `updated_at`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReputationRecord {
+    pub tasks_completed: f64,
+    pub tasks_failed: f64,
+    pub verifications_passed: f64,
+    pub verifications_failed: f64,
+    /// Latest rating per rating principal
+    pub ratings: HashMap<String, Rating>,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    /// 1 to 5
+    pub rating: u8,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentReputation {
+    pub agent_id: String,
+    /// 0.0 to 1.0; agents without recent history sit at `ReputationService::BASELINE`
+    pub score: f32,
+    pub task_success: f32,
+    pub verification_pass_rate: f32,
+    pub user_rating: f32,
+    /// Decayed weight of each kind of evidence; a fresh outcome counts 1.0
+    pub tasks_completed: f64,
+    pub tasks_failed: f64,
+    pub verifications_passed: f64,
+    pub verifications_failed: f64,
+    pub ratings: u32,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum DisputeStatus {
+    Open,
+    Accepted,
+    Rejected,
+}
+
+/// An owner's challenge to a rejecting review verdict, keyed by the reviewed request
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct VerificationDispute {
+    pub request_id: String,
+    pub agent_id: String,
+    pub opened_by: String,
+    pub reason: String,
+    pub status: DisputeStatus,
+    pub opened_at: u64,
+    pub resolved_by: Option<String>,
+    pub resolution_note: Option<String>,
+    pub resolved_at: Option<u64>,
+}
+
+impl ReputationService {
+    /// Score of an agent with no history
+    pub const BASELINE: f32 = 0.5;
+    /// Observations worth of baseline each signal starts with, so a handful of outcomes cannot swing it
+    const PRIOR_WEIGHT: f64 = 5.0;
+    const TASK_WEIGHT: f32 = 0.4;
+    const VERIFICATION_WEIGHT: f32 = 0.35;
+    const RATING_WEIGHT: f32 = 0.25;
+    const MAX_RATING: u8 = 5;
+    const HALF_LIFE_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+    /// Verdicts older than this can no longer be disputed
+    const DISPUTE_WINDOW_NS: u64 = 14 * 24 * 60 * 60 * 1_000_000_000;
+    const MAX_REASON_LEN: usize = 2000;
+
+    /// A ledger task assigned to `agent_id` finished
+    pub fn record_task(agent_id: &str, completed: bool) {
+        Self::update(agent_id, |record| {
+            if completed {
+                record.tasks_completed += 1.0;
+            } else {
+                record.tasks_failed += 1.0;
+            }
+        });
+    }
+
+    /// A peer reviewer approved or rejected output produced by `agent_id`
+    pub fn record_verification(agent_id: &str, passed: bool) {
+        Self::update(agent_id, |record| {
+            if passed {
+                record.verifications_passed += 1.0;
+            } else {
+                record.verifications_failed += 1.0;
+            }
+        });
+    }
+
+    /// Rate a registered agent from 1 to 5; a later rating by the same principal replaces the earlier one
+    pub fn submit_feedback(rater: &str, agent_id: &str, rating: u8) -> Result<AgentReputation, String> {
+        if !(1..=Self::MAX_RATING).contains(&rating) {
+            return Err(format!("rating must be between 1 and {}", Self::MAX_RATING));
+        }
+        let agent = RegistryService::get_agent(agent_id)?;
+        if agent.agent_principal == rater {
+            return Err("Owners cannot rate their own agents".to_string());
+        }
+        Self::update(agent_id, |record| {
+            record.ratings.insert(rater.to_string(), Rating { rating, weight: 1.0 });
+        });
+        Metrics::increment_counter("agent_feedback_total");
+        Self::get_reputation(agent_id)
+    }
+
+    /// Decay the agent's evidence up to now, then apply `f`
+    fn update(agent_id: &str, f: impl FnOnce(&mut ReputationRecord)) {
+        let now = time();
+        with_state_mut(|state| {
+            let record = state.agent_reputation.entry(agent_id.to_string()).or_default();
+            Self::decay(record, now);
+            f(record);
+        });
+    }
+
+    /// Share of its weight that evidence keeps after `elapsed_ns`
+    fn decay_factor(elapsed_ns: u64) -> f64 {
+        0.5f64.powf(elapsed_ns as f64 / Self::HALF_LIFE_NS as f64)
+    }
+
+    fn decay(record: &mut ReputationRecord, now: u64) {
+        let factor = Self::decay_factor(now.saturating_sub(record.updated_at));
+        record.tasks_completed *= factor;
+        record.tasks_failed *= factor;
+        record.verifications_passed *= factor;
+        record.verifications_failed *= factor;
+        for rating in record.ratings.values_mut() {
+            rating.weight *= factor;
+        }
+        record.updated_at = record.updated_at.max(now);
+    }
+
+    /// Reputation of a registered agent, or of one with recorded history
+    pub fn get_reputation(agent_id: &str) -> Result<AgentReputation, String> {
+        let now = time();
+        with_state(|state| match state.agent_reputation.get(agent_id) {
+            Some(record) => Ok(Self::summarize(agent_id, record, now)),
+            None if state.agents.contains_key(agent_id) => Ok(Self::summarize(agent_id, &ReputationRecord::default(), now)),
+            None => Err(format!("Agent not found: {}", agent_id)),
+        })
+    }
+
+    /// Reputation score used in routing
+    pub fn score(agent_id: &str) -> f32 {
+        with_state(|state| {
+            state.agent_reputation
+                .get(agent_id)
+                .map_or(Self::BASELINE, |record| Self::summarize(agent_id, record, time()).score)
+        })
+    }
+
+    /// Share of `positive` out of `total` observations, pulled toward the baseline while evidence is thin
+    fn smoothed(positive: f64, total: f64) -> f32 {
+        ((positive + Self::PRIOR_WEIGHT * Self::BASELINE as f64) / (total + Self::PRIOR_WEIGHT)) as f32
+    }
+
+    fn summarize(agent_id: &str, record: &ReputationRecord, now: u64) -> AgentReputation {
+        let mut record = record.clone();
+        Self::decay(&mut record, now);
+
+        let task_success = Self::smoothed(record.tasks_completed, record.tasks_completed + record.tasks_failed);
+        let verification_pass_rate = Self::smoothed(
+            record.verifications_passed,
+            record.verifications_passed + record.verifications_failed,
+        );
+        // Ratings map 1..=5 onto 0.0..=1.0
+        let (rating_sum, rating_weight) = record.ratings.values().fold((0.0, 0.0), |(sum, weight), r| {
+            let normalized = (r.rating - 1) as f64 / (Self::MAX_RATING - 1) as f64;
+            (sum + normalized * r.weight, weight + r.weight)
+        });
+        let user_rating = Self::smoothed(rating_sum, rating_weight);
+        let score = Self::TASK_WEIGHT * task_success
+            + Self::VERIFICATION_WEIGHT * verification_pass_rate
+            + Self::RATING_WEIGHT * user_rating;
+
+        AgentReputation {
+            agent_id: agent_id.to_string(),
+            score: score.clamp(0.0, 1.0),
+            task_success,
+            verification_pass_rate,
+            user_rating,
+            tasks_completed: record.tasks_completed,
+            tasks_failed: record.tasks_failed,
+            verifications_passed: record.verifications_passed,
+            verifications_failed: record.verifications_failed,
+            ratings: record.ratings.len() as u32,
+            updated_at: record.updated_at,
+        }
+    }
+
+    /// Contest the rejecting verdict recorded for `request_id`, as the reviewed agent's owner
+    pub fn open_dispute(owner: &str, request_id: &str, reason: String) -> Result<VerificationDispute, String> {
+        if reason.trim().is_empty() || reason.len() > Self::MAX_REASON_LEN {
+            return Err(format!("reason must be between 1 and {} characters", Self::MAX_REASON_LEN));
+        }
+        let now = time();
+        let dispute = with_state_mut(|state| {
+            let record = state.verification_evidence
+                .get(request_id)
+                .ok_or_else(|| "No review recorded for request".to_string())?;
+            if record.verdict != ReviewVerdict::Rejected {
+                return Err("Only rejecting verdicts can be disputed".to_string());
+            }
+            if now.saturating_sub(record.reviewed_at) > Self::DISPUTE_WINDOW_NS {
+                return Err("The dispute window for this verdict has closed".to_string());
+            }
+            let agent = state.agents
+                .get(&record.reviewed_agent)
+                .ok_or_else(|| format!("Agent not found: {}", record.reviewed_agent))?;
+            if agent.agent_principal != owner {
+                return Err("Only the reviewed agent's owner can dispute its verdict".to_string());
+            }
+            if state.verification_disputes.contains_key(request_id) {
+                return Err("This verdict has already been disputed".to_string());
+            }
+            let dispute = VerificationDispute {
+                request_id: request_id.to_string(),
+                agent_id: record.reviewed_agent.clone(),
+                opened_by: owner.to_string(),
+                reason,
+                status: DisputeStatus::Open,
+                opened_at: now,
+                resolved_by: None,
+                resolution_note: None,
+                resolved_at: None,
+            };
+            state.verification_disputes.insert(request_id.to_string(), dispute.clone());
+            Ok(dispute)
+        })?;
+        Metrics::increment_counter("verification_disputes_opened_total");
+        Ok(dispute)
+    }
+
+    /// Settle an open dispute; accepting it removes the disputed rejection from the agent's evidence
+    pub fn resolve_dispute(admin: &str, request_id: &str, accept: bool, note: Option<String>) -> Result<VerificationDispute, String> {
+        let now = time();
+        let dispute = with_state_mut(|state| {
+            let dispute = state.verification_disputes
+                .get_mut(request_id)
+                .ok_or_else(|| format!("No dispute for request {}", request_id))?;
+            if dispute.status != DisputeStatus::Open {
+                return Err("Dispute is already resolved".to_string());
+            }
+            dispute.status = if accept { DisputeStatus::Accepted } else { DisputeStatus::Rejected };
+            dispute.resolved_by = Some(admin.to_string());
+            dispute.resolution_note = note;
+            dispute.resolved_at = Some(now);
+            let dispute = dispute.clone();
+
+            if accept {
+                // The rejection has decayed alongside the rest of the evidence since it was recorded
+                let reviewed_at = state.verification_evidence.get(request_id).map_or(dispute.opened_at, |r| r.reviewed_at);
+                if let Some(record) = state.agent_reputation.get_mut(&dispute.agent_id) {
+                    Self::decay(record, now);
+                    let weight = Self::decay_factor(now.saturating_sub(reviewed_at));
+                    record.verifications_failed = (record.verifications_failed - weight).max(0.0);
+                }
+            }
+            Ok(dispute)
+        })?;
+
+        Metrics::increment_counter(if accept { "verification_disputes_accepted_total" } else { "verification_disputes_rejected_total" });
+        AuditService::record(
+            &dispute.opened_by,
+            AuditAction::VerificationDisputeResolved,
+            &dispute.agent_id,
+            format!("request {} {:?} by {}", request_id, dispute.status, admin),
+        );
+        NotificationService::notify(
+            &dispute.opened_by,
+            NotificationKind::DisputeResolved,
+            format!(
+                "Your dispute of the review verdict for request {} was {}",
+                request_id,
+                if accept { "accepted; the rejection no longer counts against the agent" } else { "rejected" }
+            ),
+        );
+        Ok(dispute)
+    }
+
+    /// A dispute as seen by the owner who opened it; admins use `list_disputes`
+    pub fn get_dispute(owner: &str, request_id: &str) -> Result<VerificationDispute, String> {
+        with_state(|state| {
+            state.verification_disputes
+                .get(request_id)
+                .filter(|d| d.opened_by == owner)
+                .cloned()
+                .ok_or_else(|| format!("No dispute for request {}", request_id))
+        })
+    }
+
+    /// Disputes, optionally only those in `status`, oldest first
+    pub fn list_disputes(status: Option<DisputeStatus>) -> Vec<VerificationDispute> {
+        with_state(|state| {
+            let mut disputes: Vec<VerificationDispute> = state.verification_disputes
+                .values()
+                .filter(|d| status.is_none_or(|s| d.status == s))
+                .cloned()
+                .collect();
+            disputes.sort_by(|a, b| a.opened_at.cmp(&b.opened_at).then_with(|| a.request_id.cmp(&b.request_id)));
+            disputes
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_history_sits_at_baseline() {
+        let reputation = ReputationService::summarize("a", &ReputationRecord::default(), 0);
+        assert!((reputation.score - ReputationService::BASELINE).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_signals_move_score_gradually() {
+        let rating = |rating| Rating { rating, weight: 1.0 };
+        let good = ReputationRecord {
+            tasks_completed: 20.0,
+            verifications_passed: 10.0,
+            ratings: [("u1".to_string(), rating(5)), ("u2".to_string(), rating(5))].into_iter().collect(),
+            ..Default::default()
+        };
+        let one_failure = ReputationRecord { tasks_failed: 1.0, ..Default::default() };
+
+        let good = ReputationService::summarize("a", &good, 0).score;
+        let one_failure = ReputationService::summarize("b", &one_failure, 0).score;
+        assert!(good > 0.8);
+        assert!(one_failure < ReputationService::BASELINE && one_failure > 0.4);
+    }
+
+    #[test]
+    fn test_penalties_decay_toward_baseline() {
+        let record = ReputationRecord { tasks_failed: 10.0, verifications_failed: 10.0, ..Default::default() };
+        let fresh = ReputationService::summarize("a", &record, 0);
+        let month_later = ReputationService::summarize("a", &record, ReputationService::HALF_LIFE_NS);
+        let year_later = ReputationService::summarize("a", &record, 12 * ReputationService::HALF_LIFE_NS);
+
+        assert!((month_later.tasks_failed - 5.0).abs() < 1e-9);
+        assert!(fresh.score < month_later.score && month_later.score < year_later.score);
+        assert!((year_later.score - ReputationService::BASELINE).abs() < 0.01);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, ProvenanceService, RegistryService, ReputationService, RoutingService};
+use crate::services::provenance::{ProvenanceStage, StepRecord};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Peer review stage: a reviewer agent checks the winning fanout output before it is finalized
+pub struct ReviewService;
+
+/// Per-request opt-in to peer review
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ReviewOptions {
+    /// Capabilities a reviewer must have; defaults to `reviewer` when empty
+    pub reviewer_capabilities: Vec<String>,
+    /// Ask the original agent to repair a rejected output, then review once more; the
+    /// repaired output replaces the original in the response
+    pub repair_on_reject: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum ReviewVerdict {
+    Approved,
+    Rejected,
+    Inconclusive,
+}
+
+/// Stored outcome of a review, kept as verification evidence for the request
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct VerificationRecord {
+    pub request_id: String,
+    pub reviewed_agent: String,
+    pub reviewer_agent: Option<String>,
+    pub verdict: ReviewVerdict,
+    pub evidence: VerifierEvidence,
+    pub score_adjustment: f32,
+    pub repaired: bool,
+    pub reviewed_at: u64,
+}
+
+impl ReviewService {
+    const APPROVE_BONUS: f32 = 0.2;
+    const REJECT_PENALTY: f32 = 0.3;
+    const DEFAULT_REVIEWER_CAPABILITY: &'static str = "reviewer";
+
+    /// Review `output` produced by `agent_id`, repairing once on rejection if requested.
+    /// Returns the repaired output alongside the record when a repair was made.
+    pub async fn review(
+        request: &RouteRequest,
+        options: &ReviewOptions,
+        agent_id: &str,
+        output: &str,
+        seed: u64,
+        decode_profile: DecodeProfile,
+    ) -> (VerificationRecord, Option<String>) {
+        let prompt = String::from_utf8_lossy(&request.payload).to_string();
+        let Some(reviewer) = Self::select_reviewer(request, options, agent_id) else {
+            return (Self::store(request, agent_id, None, ReviewVerdict::Inconclusive, "No reviewer agent available".to_string(), false), None);
+        };
+
+        let (mut verdict, mut feedback) = Self::ask_reviewer(&reviewer, request, &prompt, output, seed, decode_profile).await;
+        Self::record_review(request, &reviewer.agent_id, output, &feedback);
+        let mut repaired = None;
+
+        if verdict == ReviewVerdict::Rejected && options.repair_on_reject {
+            if let Ok(agent) = RegistryService::get_agent(agent_id) {
+                let repair_prompt = format!(
+                    "{}\n\nA reviewer rejected your previous answer:\n{}\n\nPrevious answer:\n{}\n\nProvide a corrected answer.",
+                    prompt, feedback, output
+                );
+                let msg_id = format!("{}:repair", request.request_id);
+                if let Ok((resp, _)) = RoutingService::dispatch_inference(&agent, &repair_prompt, &msg_id, seed, decode_profile).await {
+                    Metrics::increment_counter("review_repairs_total");
+                    ProvenanceService::record(&request.request_id, StepRecord {
+                        stage: ProvenanceStage::Repair,
+                        agent_id,
+                        part: "candidate answer".to_string(),
+                        input: Some(&feedback),
+                        output: Some(&resp.generated_text),
+                    });
+                    (verdict, feedback) = Self::ask_reviewer(&reviewer, request, &prompt, &resp.generated_text, seed, decode_profile).await;
+                    Self::record_review(request, &reviewer.agent_id, &resp.generated_text, &feedback);
+                    repaired = Some(resp.generated_text);
+                }
+            }
+        }
+
+        let record = Self::store(request, agent_id, Some(reviewer.agent_id), verdict, feedback, repaired.is_some());
+        (record, repaired)
+    }
+
+    fn record_review(request: &RouteRequest, reviewer_agent: &str, reviewed_output: &str, feedback: &str) {
+        ProvenanceService::record(&request.request_id, StepRecord {
+            stage: ProvenanceStage::Review,
+            agent_id: reviewer_agent,
+            part: "review verdict".to_string(),
+            input: Some(reviewed_output),
+            output: Some(feedback),
+        });
+    }
+
+    fn select_reviewer(request: &RouteRequest, options: &ReviewOptions, reviewed_agent: &str) -> Option<AgentRegistration> {
+        let capabilities_required = if options.reviewer_capabilities.is_empty() {
+            vec![Self::DEFAULT_REVIEWER_CAPABILITY.to_string()]
+        } else {
+            options.reviewer_capabilities.clone()
+        };
+        // Reviewers are not bound to the model family that served the request
+        let reviewer_request = RouteRequest { capabilities_required, model_preference: None, ..request.clone() };
+
+        // An agent never reviews its own output
+        RoutingService::select_multiple_agents(&reviewer_request, 2)
+            .ok()?
+            .into_iter()
+            .find(|agent| agent.agent_id != reviewed_agent)
+    }
+
+    async fn ask_reviewer(
+        reviewer: &AgentRegistration,
+        request: &RouteRequest,
+        prompt: &str,
+        output: &str,
+        seed: u64,
+        decode_profile: DecodeProfile,
+    ) -> (ReviewVerdict, String) {
+        let review_prompt = format!(
+            "Review the answer below for correctness and completeness. Reply with APPROVE or REJECT on the first line, followed by your reasons.\n\nTask:\n{}\n\nAnswer:\n{}",
+            prompt, output
+        );
+        let msg_id = format!("{}:review", request.request_id);
+        match RoutingService::dispatch_inference(reviewer, &review_prompt, &msg_id, seed, decode_profile).await {
+            Ok((resp, _)) => Self::parse_verdict(&resp.generated_text),
+            Err(e) => (ReviewVerdict::Inconclusive, format!("Reviewer call failed: {}", e)),
+        }
+    }
+
+    /// Verdict from the first line of the reviewer's reply; the rest is feedback
+    fn parse_verdict(text: &str) -> (ReviewVerdict, String) {
+        let text = text.trim();
+        let (first_line, rest) = text.split_once('\n').unwrap_or((text, ""));
+        let first_line = first_line.to_uppercase();
+        let verdict = if first_line.contains("REJECT") {
+            ReviewVerdict::Rejected
+        } else if first_line.contains("APPROVE") {
+            ReviewVerdict::Approved
+        } else {
+            ReviewVerdict::Inconclusive
+        };
+        let feedback = if rest.trim().is_empty() { text } else { rest.trim() };
+        (verdict, feedback.to_string())
+    }
+
+    fn score_adjustment(verdict: ReviewVerdict) -> f32 {
+        match verdict {
+            ReviewVerdict::Approved => Self::APPROVE_BONUS,
+            ReviewVerdict::Rejected => -Self::REJECT_PENALTY,
+            ReviewVerdict::Inconclusive => 0.0,
+        }
+    }
+
+    fn store(
+        request: &RouteRequest,
+        agent_id: &str,
+        reviewer_agent: Option<String>,
+        verdict: ReviewVerdict,
+        feedback: String,
+        repaired: bool,
+    ) -> VerificationRecord {
+        let record = VerificationRecord {
+            request_id: request.request_id.clone(),
+            reviewed_agent: agent_id.to_string(),
+            reviewer_agent,
+            verdict,
+            evidence: VerifierEvidence { passed: verdict == ReviewVerdict::Approved, details: feedback },
+            score_adjustment: Self::score_adjustment(verdict),
+            repaired,
+            reviewed_at: time(),
+        };
+        with_state_mut(|state| {
+            state.verification_evidence.insert(record.request_id.clone(), record.clone());
+        });
+        if verdict != ReviewVerdict::Inconclusive {
+            ReputationService::record_verification(agent_id, verdict == ReviewVerdict::Approved);
+        }
+        record
+    }
+
+    pub fn get_evidence(request_id: &str) -> Option<VerificationRecord> {
+        with_state(|state| state.verification_evidence.get(request_id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verdict() {
+        let (verdict, feedback) = ReviewService::parse_verdict("REJECT\nThe total is off by one.");
+        assert_eq!(verdict, ReviewVerdict::Rejected);
+        assert_eq!(feedback, "The total is off by one.");
+
+        assert_eq!(ReviewService::parse_verdict("Approve - looks right").0, ReviewVerdict::Approved);
+        assert_eq!(ReviewService::parse_verdict("I am not sure").0, ReviewVerdict::Inconclusive);
+    }
+}
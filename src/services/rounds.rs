@@ -0,0 +1,303 @@
+use crate::domain::MessagePriority;
+use crate::services::{with_state, with_state_mut, AutonomousCoordinationService, CoordinatorState};
+use crate::services::autonomous_coord::{SessionStatus, TaskStatus};
+use crate::infra::{Log, Metrics};
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Synchronization points for multi-agent sessions. A session's tasks are grouped into
+/// rounds that run one after another: a round's tasks are distributed together, and the
+/// next round waits at the barrier until every task of the current one has finished
+/// (completed, failed or cancelled) or the round's timeout passes. Tasks still open at the
+/// timeout keep running but no longer hold the barrier. Round tasks only go to the
+/// session's participants. Rounds queued behind a session that is no longer active are
+/// abandoned. Barriers are checked from the maintenance timer.
+pub struct RoundService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RoundTaskSpec {
+    pub description: String,
+    pub required_capabilities: Vec<String>,
+    pub priority: MessagePriority,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RoundTask {
+    pub spec: RoundTaskSpec,
+    /// Ledger task once distributed
+    pub task_id: Option<String>,
+    /// Why the task could not be distributed; such tasks do not hold the barrier
+    pub dispatch_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, CandidType)]
+pub enum RoundStatus {
+    /// Queued behind an earlier round's barrier
+    Waiting,
+    Running,
+    Completed,
+    /// The barrier was released by the timeout with tasks still open
+    TimedOut,
+    /// The session ended before the round started
+    Abandoned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CoordinationRound {
+    pub round: u32,
+    pub tasks: Vec<RoundTask>,
+    pub timeout_ms: u64,
+    pub status: RoundStatus,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+}
+
+impl RoundService {
+    const MAX_ROUNDS_PER_SESSION: usize = 32;
+    const MAX_TASKS_PER_ROUND: usize = 32;
+    const MAX_TIMEOUT_MS: u64 = 24 * 60 * 60 * 1_000;
+
+    /// Queue a round of tasks on a session; returns its round number. Only the owner of
+    /// the session's coordinator agent, or an admin, may add rounds.
+    pub fn add_round(caller: &str, is_admin: bool, session_id: &str, tasks: Vec<RoundTaskSpec>, timeout_ms: u64) -> Result<u32, String> {
+        if tasks.is_empty() || tasks.len() > Self::MAX_TASKS_PER_ROUND {
+            return Err(format!("A round needs between 1 and {} tasks", Self::MAX_TASKS_PER_ROUND));
+        }
+        if timeout_ms == 0 || timeout_ms > Self::MAX_TIMEOUT_MS {
+            return Err(format!("Round timeout must be between 1 and {} ms", Self::MAX_TIMEOUT_MS));
+        }
+        with_state_mut(|state| {
+            let session = state.coordination_sessions.as_ref()
+                .and_then(|sessions| sessions.get(session_id))
+                .ok_or_else(|| "Coordination session not found".to_string())?;
+            if !is_admin && !state.agents.get(&session.coordinator_agent).is_some_and(|a| a.agent_principal == caller || a.canister_id == caller) {
+                return Err("Only the session coordinator's owner can add rounds".to_string());
+            }
+            if !matches!(session.status, SessionStatus::Active | SessionStatus::Coordinating) {
+                return Err("Coordination session is no longer active".to_string());
+            }
+            Self::push_round_in(state, session_id, tasks, timeout_ms)
+        })
+    }
+
+    fn push_round_in(state: &mut CoordinatorState, session_id: &str, tasks: Vec<RoundTaskSpec>, timeout_ms: u64) -> Result<u32, String> {
+        let rounds = state.session_rounds.entry(session_id.to_string()).or_default();
+        if rounds.len() >= Self::MAX_ROUNDS_PER_SESSION {
+            return Err(format!("A session can have at most {} rounds", Self::MAX_ROUNDS_PER_SESSION));
+        }
+        let round = rounds.len() as u32;
+        rounds.push(CoordinationRound {
+            round,
+            tasks: tasks.into_iter().map(|spec| RoundTask { spec, task_id: None, dispatch_error: None }).collect(),
+            timeout_ms,
+            status: RoundStatus::Waiting,
+            started_at: None,
+            finished_at: None,
+        });
+        Ok(round)
+    }
+
+    pub fn get_rounds(session_id: &str) -> Result<Vec<CoordinationRound>, String> {
+        with_state(|state| {
+            if !state.coordination_sessions.as_ref().is_some_and(|sessions| sessions.contains_key(session_id)) {
+                return Err("Coordination session not found".to_string());
+            }
+            Ok(state.session_rounds.get(session_id).cloned().unwrap_or_default())
+        })
+    }
+
+    /// Release the session's barrier if it is met and distribute the next round's tasks,
+    /// repeating while rounds finish straight away (e.g. none of their tasks could be placed)
+    pub async fn advance(session_id: &str) {
+        loop {
+            let now = time();
+            let Some((round, specs, timeout_ms, participants)) = with_state_mut(|state| Self::advance_in(state, session_id, now)) else { return };
+            let deadline = now.saturating_add(Self::timeout_ns(timeout_ms));
+            for (index, spec) in specs.into_iter().enumerate() {
+                let outcome = AutonomousCoordinationService::distribute_task_within(
+                    spec.description,
+                    spec.required_capabilities,
+                    spec.priority,
+                    Some(deadline),
+                    &participants,
+                ).await;
+                if let Err(e) = &outcome {
+                    Log::warn("rounds", format!("Round {} of session {} could not place task {}: {}", round, session_id, index, e));
+                }
+                with_state_mut(|state| {
+                    let Some(task) = state.session_rounds.get_mut(session_id).and_then(|rounds| rounds.get_mut(round as usize)?.tasks.get_mut(index)) else { return };
+                    match outcome {
+                        Ok(task_id) => task.task_id = Some(task_id),
+                        Err(e) => task.dispatch_error = Some(e),
+                    }
+                });
+            }
+            Metrics::increment_counter("coordination_rounds_started_total");
+        }
+    }
+
+    fn timeout_ns(timeout_ms: u64) -> u64 {
+        timeout_ms.saturating_mul(1_000_000)
+    }
+
+    /// Settle the running round if its barrier is met, then start the next waiting round;
+    /// returns the started round's number, tasks and timeout, and the session's participants
+    fn advance_in(state: &mut CoordinatorState, session_id: &str, now: u64) -> Option<(u32, Vec<RoundTaskSpec>, u64, Vec<String>)> {
+        let session = state.coordination_sessions.as_ref().and_then(|sessions| sessions.get(session_id));
+        let active = session.is_some_and(|session| matches!(session.status, SessionStatus::Active | SessionStatus::Coordinating));
+        let participants = session.map(|session| session.participants.clone()).unwrap_or_default();
+        let barrier = Self::barrier_in(state, session_id, now);
+        let rounds = state.session_rounds.get_mut(session_id)?;
+        if let Some(running) = rounds.iter_mut().find(|round| round.status == RoundStatus::Running) {
+            running.status = barrier?;
+            running.finished_at = Some(now);
+        }
+        if !active {
+            for round in rounds.iter_mut().filter(|round| round.status == RoundStatus::Waiting) {
+                round.status = RoundStatus::Abandoned;
+                round.finished_at = Some(now);
+            }
+            return None;
+        }
+        let next = rounds.iter_mut().find(|round| round.status == RoundStatus::Waiting)?;
+        next.status = RoundStatus::Running;
+        next.started_at = Some(now);
+        Some((next.round, next.tasks.iter().map(|task| task.spec.clone()).collect(), next.timeout_ms, participants))
+    }
+
+    /// How the running round would finish now: `Completed` once every task has finished,
+    /// `TimedOut` once its timeout has passed; None while it holds the barrier
+    fn barrier_in(state: &CoordinatorState, session_id: &str, now: u64) -> Option<RoundStatus> {
+        let running = state.session_rounds.get(session_id)?.iter().find(|round| round.status == RoundStatus::Running)?;
+        let finished = running.tasks.iter().all(|task| {
+            task.dispatch_error.is_some()
+                || task.task_id.as_ref().and_then(|id| state.task_ledger.get(id)).is_some_and(|entry| {
+                    matches!(entry.status, TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled)
+                })
+        });
+        if finished {
+            Some(RoundStatus::Completed)
+        } else if now >= running.started_at.unwrap_or(now).saturating_add(Self::timeout_ns(running.timeout_ms)) {
+            Some(RoundStatus::TimedOut)
+        } else {
+            None
+        }
+    }
+
+    /// Called from the maintenance timer; advances sessions whose barrier is met and drops
+    /// the rounds of sessions that no longer exist
+    pub fn tick() {
+        let now = time();
+        let ready: Vec<String> = with_state_mut(|state| {
+            let sessions = &state.coordination_sessions;
+            state.session_rounds.retain(|session_id, _| sessions.as_ref().is_some_and(|s| s.contains_key(session_id)));
+            state.session_rounds
+                .iter()
+                .filter(|(session_id, rounds)| {
+                    if rounds.iter().any(|round| round.status == RoundStatus::Running) {
+                        Self::barrier_in(state, session_id, now).is_some()
+                    } else {
+                        rounds.iter().any(|round| round.status == RoundStatus::Waiting)
+                    }
+                })
+                .map(|(session_id, _)| session_id.clone())
+                .collect()
+        });
+        for session_id in ready {
+            ic_cdk::spawn(async move { Self::advance(&session_id).await });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::autonomous_coord::{CoordinationSession, ResourceConstraints, TaskLedgerEntry};
+    use crate::services::session_templates::MessageProtocol;
+
+    const MS: u64 = 1_000_000;
+
+    fn session(session_id: &str) -> CoordinationSession {
+        CoordinationSession {
+            session_id: session_id.to_string(),
+            participants: vec!["b".to_string()],
+            coordinator_agent: "a".to_string(),
+            objective: "objective".to_string(),
+            status: SessionStatus::Active,
+            created_at: 0,
+            last_activity: 0,
+            messages: vec![],
+            resource_constraints: ResourceConstraints {
+                max_execution_time_ms: 1,
+                max_memory_usage_bytes: 1,
+                max_concurrent_tasks: 1,
+                allowed_capabilities: None,
+            },
+            template_id: None,
+            role_bindings: vec![],
+            protocol: MessageProtocol::Mesh,
+        }
+    }
+
+    fn spec(description: &str) -> RoundTaskSpec {
+        RoundTaskSpec { description: description.to_string(), required_capabilities: vec![], priority: MessagePriority::Normal }
+    }
+
+    /// Stand in for `advance` distributing the round's tasks
+    fn distribute(state: &mut CoordinatorState, round: u32, task_ids: &[&str]) {
+        let tasks = &mut state.session_rounds.get_mut("s1").unwrap()[round as usize].tasks;
+        for (task, task_id) in tasks.iter_mut().zip(task_ids) {
+            task.task_id = Some(task_id.to_string());
+            state.task_ledger.insert(task_id.to_string(), TaskLedgerEntry::test(task_id, "b", TaskStatus::InProgress));
+        }
+    }
+
+    #[test]
+    fn test_next_round_waits_for_the_barrier() {
+        let mut state = CoordinatorState {
+            coordination_sessions: Some([("s1".to_string(), session("s1"))].into_iter().collect()),
+            ..Default::default()
+        };
+        RoundService::push_round_in(&mut state, "s1", vec![spec("research a"), spec("research b")], 100).unwrap();
+        RoundService::push_round_in(&mut state, "s1", vec![spec("draft")], 100).unwrap();
+
+        let (round, specs, _, participants) = RoundService::advance_in(&mut state, "s1", 0).unwrap();
+        assert_eq!((round, specs.len()), (0, 2));
+        assert_eq!(participants, vec!["b".to_string()], "round tasks go to participants only");
+        distribute(&mut state, 0, &["t1", "t2"]);
+
+        // One research task still open: the draft round waits
+        state.task_ledger.get_mut("t1").unwrap().status = TaskStatus::Completed;
+        assert!(RoundService::advance_in(&mut state, "s1", 10 * MS).is_none());
+        state.task_ledger.get_mut("t2").unwrap().status = TaskStatus::Failed;
+        let (round, specs, _, _) = RoundService::advance_in(&mut state, "s1", 20 * MS).unwrap();
+        assert_eq!((round, specs[0].description.as_str()), (1, "draft"));
+        assert_eq!(state.session_rounds["s1"][0].status, RoundStatus::Completed);
+
+        // The timeout releases a barrier held by open tasks
+        distribute(&mut state, 1, &["t3"]);
+        assert!(RoundService::advance_in(&mut state, "s1", 119 * MS).is_none());
+        assert_eq!(state.session_rounds["s1"][1].status, RoundStatus::Running);
+        assert!(RoundService::advance_in(&mut state, "s1", 120 * MS).is_none());
+        assert_eq!(state.session_rounds["s1"][1].status, RoundStatus::TimedOut);
+    }
+
+    #[test]
+    fn test_rounds_queued_behind_an_ended_session_are_abandoned() {
+        let mut state = CoordinatorState {
+            coordination_sessions: Some([("s1".to_string(), session("s1"))].into_iter().collect()),
+            ..Default::default()
+        };
+        RoundService::push_round_in(&mut state, "s1", vec![spec("research")], 100).unwrap();
+        RoundService::push_round_in(&mut state, "s1", vec![spec("draft")], 100).unwrap();
+        RoundService::advance_in(&mut state, "s1", 0).unwrap();
+        // The only task could not be placed, so it does not hold the barrier
+        state.session_rounds.get_mut("s1").unwrap()[0].tasks[0].dispatch_error = Some("No suitable agents".to_string());
+
+        state.coordination_sessions.as_mut().unwrap().get_mut("s1").unwrap().status = SessionStatus::Cancelled;
+        assert!(RoundService::advance_in(&mut state, "s1", MS).is_none());
+        let statuses: Vec<RoundStatus> = state.session_rounds["s1"].iter().map(|round| round.status.clone()).collect();
+        assert_eq!(statuses, vec![RoundStatus::Completed, RoundStatus::Abandoned]);
+    }
+}
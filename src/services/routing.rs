@@ -1,191 +1,510 @@
 use crate::domain::*;
-use crate::services::{with_state, with_state_mut, RegistryService, DedupService};
+use crate::services::{with_state, with_state_mut, RegistryService, DedupService, PartialResultService, CircuitBreakerService, SlaService, AvailabilityService, SpecializationService, ProvenanceService, CapacityForecastService, StandbyService, FeatureFlagService, ShardService, QuotaManager, AgentLoadService, SessionAffinityService, RoutingQueueService, DeliveryService, PreferencesService};
+use crate::services::routing_strategies::{RoutingStrategy, RoutingStrategyRegistry, TopKStrategy};
+use crate::services::review::ReviewService;
+use crate::services::provenance::{ProvenanceStage, StepRecord};
+use crate::services::registry_audit::RegistryAuditService;
+use crate::services::partial_results::CachedAnswer;
+use crate::services::delivery::DeliveryPayload;
+use crate::services::aggregation::{AggregationService, FanoutAnswer};
+use crate::infra::Metrics;
 use ic_cdk::api::time;
 use candid::{Principal, CandidType};
 use serde::Deserialize;
 use ic_cdk::api::call::call;
-use futures::future::join_all;
+use futures::future::{join_all, select, Either};
+use futures::channel::oneshot;
 use sha2::{Sha256, Digest};
+use std::future::Future;
+use std::ops::RangeInclusive;
+use std::pin::Pin;
+use std::time::Duration;
 
 pub struct RoutingService;
 
 impl RoutingService {
-    pub async fn route_request(request: RouteRequest) -> Result<RouteResponse, String> {
+    /// Revisions of the agent `infer` interface this coordinator encodes requests for;
+    /// agents outside the range would fail to decode them, so routing skips them
+    pub const INFER_PROTOCOL_VERSIONS: RangeInclusive<u32> = 1..=1;
+    /// Revision assumed for agents that registered without declaring one
+    const UNDECLARED_PROTOCOL_VERSION: u32 = 1;
+    const MAX_POLICY_TOP_K: u32 = 10;
+    pub const DEFAULT_HEDGE_DELAY_MS: u64 = 500;
+
+    pub fn speaks_infer_protocol(agent: &AgentRegistration) -> bool {
+        Self::INFER_PROTOCOL_VERSIONS.contains(&agent.protocol_version.unwrap_or(Self::UNDECLARED_PROTOCOL_VERSION))
+    }
+
+    /// Route for `caller` and run the request on the selected agents, returning their
+    /// outputs. When every capable agent is saturated, a request with a callback waits in
+    /// the tier-ordered queue and is answered with its place in line; the outcome goes to
+    /// the callback once it has run.
+    pub async fn route_request(caller: &str, mut request: RouteRequest, decode_profile: DecodeProfile) -> Result<RouteResponse, String> {
         let start_time = time();
         
-        // Check for duplicate request
-        if DedupService::is_duplicate(&request.request_id) {
-            return Err("Duplicate request ID".to_string());
+        // A retry of a request already routed gets the original decision
+        let request_sha256 = PartialResultService::request_hash(&request.payload, &request.capabilities_required);
+        if let Some(cached) = DedupService::cached_response(caller, &request.request_id, &request_sha256)? {
+            return Ok(cached);
         }
+        Self::apply_capability_policy(&mut request);
+        // Demand is counted once here; a queued request is not counted again on each drain
+        CapacityForecastService::record_demand(&request.capabilities_required);
         
-        let selected_agents = match request.routing_mode {
-            RoutingMode::Unicast => Self::select_best_agent(&request.capabilities_required)?,
-            RoutingMode::Broadcast => Self::select_multiple_agents(&request.capabilities_required, 3)?,
-            RoutingMode::AgentSpawning => Self::select_spawning_agents(&request.capabilities_required, 5)?,
+        let strategy = RoutingStrategyRegistry::resolve(&request)?;
+        let (candidates, model_fallback_level) = Self::eligible_agents(&request)?;
+        if request.callback.is_some() && RoutingQueueService::at_capacity(&candidates, start_time) {
+            return RoutingQueueService::enqueue(caller, request, start_time);
+        }
+        let (response, selected_agents) = Self::route_now(caller, &request, strategy, candidates, model_fallback_level, start_time)?;
+        Self::execute(caller, &request, response, &selected_agents, decode_profile).await
+    }
+
+    /// A capability policy's mode and strategy replace the request's own
+    fn apply_capability_policy(request: &mut RouteRequest) {
+        if let Some((_, policy)) = Self::capability_policy(&request.capabilities_required) {
+            request.routing_mode = policy.routing_mode;
+            request.strategy = policy.strategy.or(request.strategy.take());
+        }
+    }
+
+    /// Agent calls a request from `caller` may make once routed, for admission before
+    /// routing: the resolved strategy's selection size after any capability policy and
+    /// fanout cap. A hedged request calls at most its first choice and one runner-up.
+    pub fn dispatch_calls(caller: &str, request: &RouteRequest) -> Result<u32, String> {
+        let policy = Self::capability_policy(&request.capabilities_required).map(|(_, policy)| policy);
+        let mode = policy.as_ref().map_or(&request.routing_mode, |policy| &policy.routing_mode);
+        let name = policy.as_ref()
+            .and_then(|policy| policy.strategy.as_deref())
+            .or(request.strategy.as_deref())
+            .unwrap_or(mode.strategy_name());
+        let strategy = RoutingStrategyRegistry::resolve_name(name)?;
+        let limit = Self::selection_limit(caller, mode, strategy, policy.as_ref());
+        let calls = match mode {
+            RoutingMode::AgentSpawning => 0,
+            RoutingMode::Hedged => limit.min(2),
+            _ => limit,
         };
+        Ok(calls as u32)
+    }
+
+    /// Most agents a request may be routed to: the policy's top_k or the strategy's own
+    /// limit, with competitions capped to the caller's tier fanout cap
+    fn selection_limit(caller: &str, mode: &RoutingMode, strategy: &dyn RoutingStrategy, policy: Option<&CapabilityRoutingPolicy>) -> usize {
+        let limit = match policy.and_then(|policy| policy.top_k.map(|k| (k as usize, policy.strategy.is_some()))) {
+            // Policies stored before strategy and top_k were exclusive: k cuts the strategy's selection
+            Some((k, true)) => k.min(strategy.max_agents()),
+            Some((k, false)) => k,
+            None => strategy.max_agents(),
+        };
+        match mode {
+            RoutingMode::Competition => limit.min(QuotaManager::fanout_cap(caller)),
+            _ => limit,
+        }
+    }
+
+    /// Selection: the response carries the chosen agents, which are returned for dispatch.
+    /// The decision is recorded for dedup before any agent call, so a retry arriving
+    /// while the agents run gets it back instead of being admitted and dispatched again.
+    fn route_now(
+        caller: &str,
+        request: &RouteRequest,
+        strategy: &dyn RoutingStrategy,
+        candidates: Vec<AgentRegistration>,
+        model_fallback_level: Option<u32>,
+        start_time: u64,
+    ) -> Result<(RouteResponse, Vec<AgentRegistration>), String> {
+        let pinned = request.session_key
+            .as_deref()
+            .and_then(|key| SessionAffinityService::pinned_agent(caller, key, &candidates, start_time));
+        let sticky = pinned.is_some();
+        let policy = Self::capability_policy(&request.capabilities_required);
+        // A policy's top_k picks agents by score in place of the mode's strategy, never of a strategy it names
+        let policy_top_k = policy.as_ref()
+            .filter(|(_, policy)| policy.strategy.is_none())
+            .and_then(|(_, policy)| policy.top_k);
+        let mut selected_agents = match policy_top_k {
+            Some(k) => TopKStrategy { name: strategy.name(), k: k as usize }.select(request, candidates, start_time)?,
+            None => strategy.select(request, candidates, start_time)?,
+        };
+        if let Some(agent) = pinned {
+            SessionAffinityService::put_first(&mut selected_agents, agent);
+        }
+        // Never more agents than admission charged for
+        selected_agents.truncate(Self::selection_limit(caller, &request.routing_mode, strategy, policy.as_ref().map(|(_, policy)| policy)));
         
-        let routing_time_ms = time() - start_time;
+        let routing_time_ms = (time() - start_time) / 1_000_000;
         
         let response = RouteResponse {
             request_id: request.request_id.clone(),
             selected_agents: selected_agents.iter().map(|a| a.agent_id.clone()).collect(),
             routing_time_ms,
-            selection_criteria: format!("Selected by {:?} routing", request.routing_mode),
+            selection_criteria: format!(
+                "Selected by {} routing{}{}{}",
+                strategy.name(),
+                if request.guaranteed_service { " (SLA-compliant preferred)" } else { "" },
+                if sticky { " (session affinity)" } else { "" },
+                policy.map(|(capability, _)| format!(" (policy for {})", capability)).unwrap_or_default()
+            ),
+            model_fallback_level,
+            results: Vec::new(),
+            aggregated_output: None,
         };
         
-        // Record the routing decision in dedup cache
-        DedupService::record_request(&request.request_id, &response)?;
-        
-        // Update metrics
+        // Update metrics; agents serving their first request become Active
         with_state_mut(|state| {
+            let now = time();
+            for agent in &selected_agents {
+                if agent.lifecycle() == AgentLifecycle::Ready {
+                    let _ = RegistryService::transition_lifecycle_in(state, &agent.agent_id, AgentLifecycle::Active, RegistryAuditService::ROUTING, now);
+                }
+            }
+            if let (Some(key), Some(agent)) = (&request.session_key, selected_agents.first()) {
+                SessionAffinityService::pin_in(state, caller, key, &agent.agent_id, now);
+            }
             state.metrics.total_routes += 1;
             let new_avg = (state.metrics.average_routing_time_ms * (state.metrics.total_routes - 1) as f64 
                 + routing_time_ms as f64) / state.metrics.total_routes as f64;
             state.metrics.average_routing_time_ms = new_avg;
             state.metrics.last_activity = time();
         });
+        let request_sha256 = PartialResultService::request_hash(&request.payload, &request.capabilities_required);
+        DedupService::record_request(caller, &request.request_id, &request_sha256, &response)?;
         
-        // Optionally trigger downstream calls (not returning results here; response carries selection)
+        Ok((response, selected_agents))
+    }
+
+    /// Execution: send the payload to the selected agents and fill their results into the
+    /// dedup entry recorded at selection. Hedged requests race the runner-up against a slow
+    /// first choice; the rest call every selected agent at once, and under Competition
+    /// the answers are ranked by fanout score with the winner first. Agent spawning only
+    /// coordinates agent creation, so nothing is dispatched.
+    async fn execute(
+        caller: &str,
+        request: &RouteRequest,
+        mut response: RouteResponse,
+        selected_agents: &[AgentRegistration],
+        decode_profile: DecodeProfile,
+    ) -> Result<RouteResponse, String> {
+        let prompt = String::from_utf8_lossy(&request.payload).to_string();
+        let seed = Self::derive_seed(&request.request_id);
+        let to_result = |agent_id: String, result: Result<(AInferenceResponse, u64), String>| match result {
+            Ok((resp, elapsed)) => AgentDispatchResult {
+                agent_id,
+                output: Some(resp.generated_text),
+                error: None,
+                elapsed_ms: elapsed / 1_000_000,
+            },
+            Err(e) => AgentDispatchResult { agent_id, output: None, error: Some(e), elapsed_ms: 0 },
+        };
+        response.results = match (&request.routing_mode, selected_agents) {
+            (RoutingMode::AgentSpawning, _) | (_, []) => Vec::new(),
+            (RoutingMode::Hedged, [primary, rest @ ..]) => {
+                let ((agent_id, result), _) = Self::hedged_dispatch(
+                    request,
+                    primary.clone(),
+                    rest.first().cloned(),
+                    Self::DEFAULT_HEDGE_DELAY_MS,
+                    decode_profile,
+                ).await;
+                vec![to_result(agent_id, result)]
+            }
+            (RoutingMode::Competition, agents) => {
+                let calls = agents.iter().map(|agent| {
+                    let prompt = &prompt;
+                    async move {
+                        (agent.agent_id.clone(), Self::dispatch_inference(agent, prompt, &request.request_id, seed, decode_profile).await)
+                    }
+                });
+                let mut ranked: Vec<(f32, AgentDispatchResult)> = join_all(calls)
+                    .await
+                    .into_iter()
+                    .map(|(agent_id, result)| {
+                        let score = result.as_ref().map_or(f32::NEG_INFINITY, |(resp, elapsed)| {
+                            let verified = Self::run_verifiers(&resp.generated_text).passed;
+                            Self::score_response(resp, elapsed / 1_000_000) + Self::verifier_bonus(verified)
+                        });
+                        (score, to_result(agent_id, result))
+                    })
+                    .collect();
+                ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+                if let Some((_, winner)) = ranked.first().filter(|(score, _)| score.is_finite()) {
+                    response.selection_criteria.push_str(&format!(" winner={}", winner.agent_id));
+                }
+                response.selected_agents = ranked.iter().map(|(_, result)| result.agent_id.clone()).collect();
+                ranked.into_iter().map(|(_, result)| result).collect()
+            }
+            (_, agents) => {
+                let calls = agents.iter().map(|agent| {
+                    let prompt = &prompt;
+                    let to_result = &to_result;
+                    async move {
+                        let result = Self::dispatch_inference(agent, prompt, &request.request_id, seed, decode_profile).await;
+                        to_result(agent.agent_id.clone(), result)
+                    }
+                });
+                join_all(calls).await
+            }
+        };
+        let failures = response.results.iter().filter(|r| r.error.is_some()).count();
+        Metrics::add_to_counter("route_dispatch_failures_total", failures as u64);
+
+        let request_sha256 = PartialResultService::request_hash(&request.payload, &request.capabilities_required);
+        DedupService::record_request(caller, &request.request_id, &request_sha256, &response)?;
         Ok(response)
     }
-    
-    fn select_best_agent(capabilities: &[String]) -> Result<Vec<AgentRegistration>, String> {
-        let candidates = Self::get_capable_agents(capabilities);
-        if candidates.is_empty() {
-            return Err("No agents available with required capabilities".to_string());
+
+    /// Called from the maintenance timer: routes queued requests in tier order where an
+    /// agent has capacity, or that have waited too long, and delivers each outcome
+    pub fn drain_queue() {
+        let now = time();
+        let mut routed = 0;
+        for entry in RoutingQueueService::ordered(now) {
+            if routed >= RoutingQueueService::DRAIN_PER_TICK {
+                break;
+            }
+            let overdue = RoutingQueueService::is_overdue(&entry, now);
+            let outcome = RoutingStrategyRegistry::resolve(&entry.request).and_then(|strategy| {
+                let (candidates, model_fallback_level) = Self::eligible_agents(&entry.request)?;
+                if !overdue && RoutingQueueService::at_capacity(&candidates, now) {
+                    return Ok(None);
+                }
+                Self::route_now(&entry.caller, &entry.request, strategy, candidates, model_fallback_level, now).map(Some)
+            });
+            let routed_now = match outcome {
+                Ok(None) => continue,
+                Ok(Some(routed_now)) => Ok(routed_now),
+                Err(e) => Err(RouteResponse {
+                    request_id: entry.request.request_id.clone(),
+                    selected_agents: Vec::new(),
+                    routing_time_ms: 0,
+                    selection_criteria: format!("queued route failed: {}", e),
+                    model_fallback_level: None,
+                    results: Vec::new(),
+                    aggregated_output: None,
+                }),
+            };
+            RoutingQueueService::remove(&entry.caller, &entry.request.request_id);
+            routed += 1;
+            let decode_profile = PreferencesService::get_preferences(&entry.caller)
+                .default_decode_profile
+                .unwrap_or_default();
+            ic_cdk::spawn(async move {
+                let response = match routed_now {
+                    Ok((response, selected_agents)) => {
+                        let fallback = response.clone();
+                        Self::execute(&entry.caller, &entry.request, response, &selected_agents, decode_profile).await.unwrap_or(fallback)
+                    }
+                    Err(failed) => failed,
+                };
+                if let Some(target) = entry.request.callback.clone() {
+                    DeliveryService::enqueue(&entry.caller, target, DeliveryPayload::Route(response));
+                }
+            });
         }
-        
-        // Select agent with best health * capability fit score
-        let best = candidates
-            .into_iter()
-            .max_by(|a, b| {
-                let score_a = Self::calculate_agent_score(a, capabilities);
-                let score_b = Self::calculate_agent_score(b, capabilities);
-                score_a.partial_cmp(&score_b).unwrap()
-            })
-            .unwrap();
-        
-        Ok(vec![best])
     }
     
-    fn select_multiple_agents(capabilities: &[String], k: usize) -> Result<Vec<AgentRegistration>, String> {
-        let mut candidates = Self::get_capable_agents(capabilities);
-        if candidates.is_empty() {
-            return Err("No agents available with required capabilities".to_string());
+    /// Admin routing policy for the first required capability that has one
+    pub fn capability_policy(capabilities: &[String]) -> Option<(String, CapabilityRoutingPolicy)> {
+        with_state(|state| {
+            capabilities.iter().find_map(|capability| {
+                state.config.capability_policies
+                    .get(capability)
+                    .map(|policy| (capability.clone(), policy.clone()))
+            })
+        })
+    }
+
+    /// Attach `policy` to `capability`, or clear it with `None`
+    pub fn set_capability_policy(capability: &str, policy: Option<CapabilityRoutingPolicy>) -> Result<(), String> {
+        let capability = capability.trim();
+        if capability.is_empty() {
+            return Err("Capability is required".to_string());
         }
-        
-        // Sort by score and take top K
-        candidates.sort_by(|a, b| {
-            let score_a = Self::calculate_agent_score(a, capabilities);
-            let score_b = Self::calculate_agent_score(b, capabilities);
-            score_b.partial_cmp(&score_a).unwrap() // Descending order
+        if let Some(policy) = &policy {
+            let name = policy.strategy.as_deref().unwrap_or(policy.routing_mode.strategy_name());
+            RoutingStrategyRegistry::lookup(name).ok_or_else(|| format!("Unknown routing strategy: {}", name))?;
+            if policy.top_k.is_some_and(|k| !(1..=Self::MAX_POLICY_TOP_K).contains(&k)) {
+                return Err(format!("top_k must be between 1 and {}", Self::MAX_POLICY_TOP_K));
+            }
+            // top_k selects by score, which would silently replace the named strategy
+            if policy.strategy.is_some() && policy.top_k.is_some() {
+                return Err("A policy sets either a strategy or top_k, not both".to_string());
+            }
+        }
+        with_state_mut(|state| match policy {
+            Some(policy) => state.config.capability_policies.insert(capability.to_string(), policy),
+            None => state.config.capability_policies.remove(capability),
         });
-        
-        candidates.truncate(k);
-        Ok(candidates)
+        Ok(())
     }
-    
-    fn select_spawning_agents(capabilities: &[String], max_agents: usize) -> Result<Vec<AgentRegistration>, String> {
-        let candidates = Self::get_capable_agents(capabilities);
-        if candidates.is_empty() {
-            return Err("No agents available for competition".to_string());
+
+    /// Top `k` agents by health and capability fit, as used by fanout
+    pub(crate) fn select_multiple_agents(request: &RouteRequest, k: usize) -> Result<Vec<AgentRegistration>, String> {
+        let (candidates, _) = Self::eligible_agents(request)?;
+        TopKStrategy { name: "broadcast", k }.select(request, candidates, time())
+    }
+
+    /// Capable agents narrowed by the request's model preference, with the fallback level used
+    pub(crate) fn eligible_agents(request: &RouteRequest) -> Result<(Vec<AgentRegistration>, Option<u32>), String> {
+        let capable = Self::get_capable_agents(request);
+        match &request.model_preference {
+            Some(preference) => {
+                let (agents, level) = Self::apply_model_preference(capable, preference)?;
+                Metrics::increment_counter(&format!("model_fallback_level_{}", level));
+                Ok((agents, Some(level)))
+            }
+            None => Ok((capable, None)),
         }
-        
-        // For competition mode, include top scored agents up to max_agents
-        let mut pool = candidates;
-        pool.sort_by(|a, b| {
-            let score_a = Self::calculate_agent_score(a, capabilities);
-            let score_b = Self::calculate_agent_score(b, capabilities);
-            score_b.partial_cmp(&score_a).unwrap()
-        });
-        let selected: Vec<AgentRegistration> = pool.into_iter().take(max_agents).collect();
-        
-        Ok(selected)
     }
-    
-    fn get_capable_agents(capabilities: &[String]) -> Vec<AgentRegistration> {
-        let healthy_agents = RegistryService::get_healthy_agents(0.1);
-        healthy_agents
-            .into_iter()
-            .filter(|agent| {
-                capabilities.iter().any(|cap| agent.capabilities.contains(cap))
-            })
-            .collect()
+
+    /// Agents of the first model family in the chain that has any; `level` is that family's position
+    fn apply_model_preference(candidates: Vec<AgentRegistration>, preference: &ModelPreference) -> Result<(Vec<AgentRegistration>, u32), String> {
+        for (level, family) in preference.fallback_chain.iter().enumerate() {
+            let family = family.trim().to_lowercase();
+            let matching: Vec<AgentRegistration> = candidates
+                .iter()
+                .filter(|agent| agent.model_id.to_lowercase().starts_with(&family))
+                .cloned()
+                .collect();
+            if !matching.is_empty() {
+                return Ok((matching, level as u32));
+            }
+        }
+        if preference.allow_any_model && !candidates.is_empty() {
+            return Ok((candidates, preference.fallback_chain.len() as u32));
+        }
+        Err(format!("No healthy agents for preferred models: {}", preference.fallback_chain.join(" -> ")))
     }
     
-    fn calculate_agent_score(agent: &AgentRegistration, required_capabilities: &[String]) -> f32 {
-        let health_weight = 0.6;
-        let capability_weight = 0.4;
-        
-        let health_score = agent.health_score;
+    pub(crate) fn get_capable_agents(request: &RouteRequest) -> Vec<AgentRegistration> {
+        let capabilities = &request.capabilities_required;
+        let now = time();
+        // Critical traffic may reach agents outside their declared availability windows
+        let critical = request.priority == Some(MessagePriority::Critical);
+        let serving = |capabilities: &[String]| -> Vec<AgentRegistration> {
+            RegistryService::get_capable_agents(capabilities, 0.1)
+                .into_iter()
+                .chain(ShardService::peer_capable_agents(capabilities, 0.1))
+                .filter(|agent| agent.is_routable())
+                .filter(Self::speaks_infer_protocol)
+                .filter(|agent| !CircuitBreakerService::is_open(&agent.agent_id))
+                .filter(|agent| !StandbyService::is_held_back(&agent.agent_id))
+                .filter(|agent| !AvailabilityService::is_in_maintenance(&agent.agent_id, now))
+                .filter(|agent| critical || AvailabilityService::is_agent_available(&agent.agent_id, now))
+                .collect()
+        };
+        let mut capable = serving(capabilities);
+        if capable.is_empty() && !capabilities.is_empty() {
+            // No exact specialist: fall back to roles the ontology lets stand in
+            capable = serving(&SpecializationService::fallback_capabilities(capabilities));
+            if !capable.is_empty() {
+                Metrics::increment_counter("specialization_fallback_routes");
+            }
+        }
         
-        let capability_score = required_capabilities
+        // Agents reporting saturation only take work when every capable agent does
+        let unsaturated: Vec<AgentRegistration> = capable
             .iter()
-            .map(|cap| {
-                if agent.capabilities.contains(cap) { 1.0 } else { 0.0 }
-            })
-            .sum::<f32>() / required_capabilities.len().max(1) as f32;
+            .filter(|agent| !AgentLoadService::is_saturated(&agent.agent_id, now))
+            .cloned()
+            .collect();
+        if !unsaturated.is_empty() {
+            capable = unsaturated;
+        }
+        
+        if !request.guaranteed_service {
+            return capable;
+        }
         
-        health_weight * health_score + capability_weight * capability_score
+        // Guaranteed service narrows to SLA-compliant agents whenever any are available
+        let compliant: Vec<AgentRegistration> = capable
+            .iter()
+            .filter(|agent| SlaService::is_compliant(&agent.agent_id))
+            .cloned()
+            .collect();
+        if compliant.is_empty() { capable } else { compliant }
     }
-
-    pub async fn fanout_best_result(request: RouteRequest, k: usize, window_ms: u64) -> Result<RouteResponse, String> {
-        // Enforce subscription tier cap (temporary: cap to 3)
-        let cap_k = k.min(3);
-        let agents = Self::select_multiple_agents(&request.capabilities_required, cap_k)?;
-        if agents.is_empty() { return Err("No agents available".to_string()); }
-
+    
+    pub async fn fanout_best_result(caller: &str, request: RouteRequest, k: usize, window_ms: u64, decode_profile: DecodeProfile) -> Result<RouteResponse, String> {
+        FeatureFlagService::require(FeatureFlagService::ENSEMBLE)?;
+        // Callers cap k to the requester's tier with QuotaManager::fanout_cap, after any capability policy's top_k
+        let cap_k = k;
         let start = time();
+        let consensus = Self::capability_policy(&request.capabilities_required).is_some_and(|(_, policy)| policy.verifier_consensus);
+        CapacityForecastService::record_demand(&request.capabilities_required);
+        let (candidates, model_fallback_level) = Self::eligible_agents(&request)?;
+        // Answers from an earlier attempt under this request id count toward k and are not asked again
+        let cached = PartialResultService::begin(
+            &request.request_id,
+            &PartialResultService::request_hash(&request.payload, &request.capabilities_required),
+        );
+        let fresh: Vec<AgentRegistration> = candidates.into_iter().filter(|a| !cached.contains_key(&a.agent_id)).collect();
+        let agents = if cached.len() >= cap_k {
+            Vec::new()
+        } else {
+            TopKStrategy { name: "broadcast", k: cap_k - cached.len() }.select(&request, fresh, start).unwrap_or_default()
+        };
+        if agents.is_empty() && cached.is_empty() { return Err("No agents available".to_string()); }
 
         // Build prompt and request payload for agents
         let prompt = String::from_utf8(request.payload.clone()).unwrap_or_else(|_| "".to_string());
         let seed = Self::derive_seed(&request.request_id);
         let msg_id = request.request_id.clone();
 
-        // Dispatch concurrent calls
+        // Dispatch concurrent calls, persisting each answer as it arrives
         let futures = agents.iter().map(|agent| {
-            let canister_id = agent.canister_id.clone();
-            let agent_id = agent.agent_id.clone();
-            let req = AInferenceRequest::new(seed, &prompt, &msg_id);
+            let prompt = &prompt;
+            let msg_id = &msg_id;
             async move {
-                let started = time();
-                let pr = Principal::from_text(canister_id.clone())
-                    .map_err(|e| format!("Invalid canister id for agent {}: {}", agent_id, e))?;
-                // Call agent.infer(InferenceRequest)
-                let (result,): (AResult2,) = call(pr, "infer", (req,)).await
-                    .map_err(|e| format!("infer call failed for {}: {:?}", agent_id, e))?;
-                let elapsed = time() - started;
-
-                let scored = match result {
-                    AResult2::Ok(resp) => {
-                        // Run lightweight verifiers
-                        let evidence = Self::run_verifiers(&resp);
-                        let score = Self::score_response(&resp, elapsed) + if evidence.passed { 0.1 } else { 0.0 };
-                        Ok((agent_id, elapsed, Some(resp), score))
-                    },
-                    AResult2::Err(err) => Err(format!("agent {} error: {}", agent_id, err)),
-                };
-                scored
+                let (resp, elapsed) = Self::dispatch_inference(agent, prompt, msg_id, seed, decode_profile).await?;
+                let elapsed = elapsed / 1_000_000;
+                // Run lightweight verifiers
+                let evidence = Self::run_verifiers(&resp.generated_text);
+                let score = Self::score_response(&resp, elapsed) + Self::verifier_bonus(evidence.passed);
+                PartialResultService::record(msg_id, &agent.agent_id, CachedAnswer {
+                    output: resp.generated_text.clone(),
+                    elapsed,
+                    score,
+                });
+                Ok::<_, String>((agent.agent_id.clone(), elapsed, Some(resp.generated_text), score, evidence.passed))
             }
         });
 
-        let results = join_all(futures).await;
+        let reused = cached
+            .into_iter()
+            .map(|(agent_id, answer)| {
+                let verified = Self::run_verifiers(&answer.output).passed;
+                Ok((agent_id, answer.elapsed, Some(answer.output), answer.score, verified))
+            });
+        let results: Vec<Result<_, String>> = reused.chain(join_all(futures).await).collect();
+        ProvenanceService::begin(&request.request_id);
 
-        // Choose best among those within window
-        let mut best_agent: Option<(String, u64, f32)> = None; // (agent_id, elapsed, score)
+        // Aggregate the answers within window
+        let mut candidates: Vec<FanoutAnswer> = Vec::new();
         let mut selected_ids: Vec<String> = Vec::new();
+        let mut answers: Vec<AgentDispatchResult> = Vec::new();
         for res in results.into_iter() {
             match res {
-                Ok((agent_id, elapsed, _resp_opt, score)) => {
+                Ok((agent_id, elapsed, resp_opt, score, verified)) => {
                     selected_ids.push(agent_id.clone());
-                    if elapsed <= window_ms {
-                        if let Some((_, _, best_score)) = &best_agent {
-                            if score > *best_score {
-                                best_agent = Some((agent_id.clone(), elapsed, score));
-                            }
-                        } else {
-                            best_agent = Some((agent_id.clone(), elapsed, score));
-                        }
+                    answers.push(AgentDispatchResult {
+                        agent_id: agent_id.clone(),
+                        output: resp_opt.clone(),
+                        error: None,
+                        elapsed_ms: elapsed,
+                    });
+                    ProvenanceService::record(&request.request_id, StepRecord {
+                        stage: ProvenanceStage::Fanout,
+                        agent_id: &agent_id,
+                        part: "candidate answer".to_string(),
+                        input: Some(&prompt),
+                        output: resp_opt.as_deref(),
+                    });
+                    // Under verifier consensus, answers failing a verifier cannot win
+                    if elapsed <= window_ms && (verified || !consensus) {
+                        let output = resp_opt.unwrap_or_default();
+                        candidates.push(FanoutAnswer { agent_id, output, score, elapsed, verified });
                     }
                 }
                 Err(_e) => {
@@ -194,22 +513,207 @@ impl RoutingService {
                 }
             }
         }
+        if consensus && candidates.is_empty() {
+            return Err("No fanout answer passed verifier consensus".to_string());
+        }
+        let aggregation = request.aggregation.unwrap_or_default();
+        let mut outcome = AggregationService::aggregate(aggregation, &candidates);
+
+        // Optional peer review: the verdict adjusts the winner's score and may hand the win to the
+        // runner-up; a repaired answer replaces the reviewed one in the candidates and results
+        let mut review_note = String::new();
+        if let (Some(options), Some(i)) = (&request.review, outcome.as_ref().and_then(|o| o.winner)) {
+            let FanoutAnswer { agent_id, output, .. } = &candidates[i];
+            let (record, repaired) = ReviewService::review(&request, options, agent_id, output, seed, decode_profile).await;
+            if let Some(repaired) = repaired {
+                if let Some(answer) = answers.iter_mut().find(|answer| answer.agent_id == candidates[i].agent_id) {
+                    answer.output = Some(repaired.clone());
+                }
+                candidates[i].output = repaired;
+            }
+            candidates[i].score += record.score_adjustment;
+            review_note = format!(" review={:?}{}", record.verdict, if record.repaired { " (repaired)" } else { "" });
+            outcome = AggregationService::aggregate(aggregation, &candidates);
+        }
+        let best_agent = outcome.as_ref().and_then(|o| o.winner).map(|i| candidates[i].agent_id.clone());
+        let support = outcome.as_ref().map_or(0, |o| o.support);
+        if let Some(outcome) = &outcome {
+            ProvenanceService::finalize(&request.request_id, &outcome.output);
+        }
 
         // Winner prioritization: put winner first if exists
-        if let Some((winner_id, _elapsed, _score)) = &best_agent {
+        if let Some(winner_id) = &best_agent {
             selected_ids.sort_by_key(|id| if id == winner_id { 0 } else { 1 });
+            answers.sort_by_key(|answer| if &answer.agent_id == winner_id { 0 } else { 1 });
         }
 
         let resp = RouteResponse {
             request_id: request.request_id.clone(),
             selected_agents: selected_ids,
-            routing_time_ms: time() - start,
-            selection_criteria: format!("fanout_top_k={} window_ms={} winner={}", cap_k, window_ms, best_agent.as_ref().map(|(w,_,_)| w.clone()).unwrap_or_default()),
+            routing_time_ms: (time() - start) / 1_000_000,
+            selection_criteria: format!(
+                "fanout_top_k={} window_ms={} aggregation={:?} support={} winner={}{}",
+                cap_k, window_ms, aggregation, support, best_agent.unwrap_or_default(), review_note
+            ),
+            model_fallback_level,
+            results: answers,
+            aggregated_output: outcome.map(|outcome| outcome.output),
         };
-        DedupService::record_request(&request.request_id, &resp)?;
+        DedupService::record_request(
+            caller,
+            &request.request_id,
+            &PartialResultService::request_hash(&request.payload, &request.capabilities_required),
+            &resp,
+        )?;
         Ok(resp)
     }
     
+    /// Hedged dispatch: the best agent gets the request, and if it has not answered within
+    /// `hedge_delay_ms` (or fails first) the runner-up gets a duplicate; whichever answers
+    /// first wins. The loser keeps running detached so its outcome still feeds the agent's
+    /// stats and circuit breaker.
+    pub async fn hedged_route(caller: &str, request: RouteRequest, hedge_delay_ms: u64, decode_profile: DecodeProfile) -> Result<RouteResponse, String> {
+        let strategy = RoutingStrategyRegistry::lookup(RoutingMode::Hedged.strategy_name())
+            .ok_or_else(|| "Hedged routing strategy not registered".to_string())?;
+        CapacityForecastService::record_demand(&request.capabilities_required);
+        let start = time();
+        let (candidates, model_fallback_level) = Self::eligible_agents(&request)?;
+        let mut agents = strategy.select(&request, candidates, start)?.into_iter();
+        let primary = agents.next().ok_or_else(|| "No agents available".to_string())?;
+        let backup = agents.next();
+
+        let ((agent_id, result), hedge_fired) = Self::hedged_dispatch(&request, primary, backup, hedge_delay_ms, decode_profile).await;
+        let (resp, elapsed) = result?;
+        let prompt = String::from_utf8_lossy(&request.payload).to_string();
+        ProvenanceService::begin(&request.request_id);
+        ProvenanceService::record(&request.request_id, StepRecord {
+            stage: ProvenanceStage::Fanout,
+            agent_id: &agent_id,
+            part: "hedged answer".to_string(),
+            input: Some(&prompt),
+            output: Some(&resp.generated_text),
+        });
+        ProvenanceService::finalize(&request.request_id, &resp.generated_text);
+
+        let response = RouteResponse {
+            request_id: request.request_id.clone(),
+            selected_agents: vec![agent_id.clone()],
+            routing_time_ms: (time() - start) / 1_000_000,
+            selection_criteria: format!("hedged delay_ms={} hedge_fired={} winner={}", hedge_delay_ms, hedge_fired, agent_id),
+            model_fallback_level,
+            results: vec![AgentDispatchResult {
+                agent_id,
+                output: Some(resp.generated_text),
+                error: None,
+                elapsed_ms: elapsed / 1_000_000,
+            }],
+            aggregated_output: None,
+        };
+        DedupService::record_request(
+            caller,
+            &request.request_id,
+            &PartialResultService::request_hash(&request.payload, &request.capabilities_required),
+            &response,
+        )?;
+        Ok(response)
+    }
+
+    /// Race `primary` against a delayed duplicate to `backup`; returns the answering
+    /// agent with its outcome, and whether the duplicate was sent
+    #[allow(clippy::type_complexity)]
+    async fn hedged_dispatch(
+        request: &RouteRequest,
+        primary: AgentRegistration,
+        backup: Option<AgentRegistration>,
+        hedge_delay_ms: u64,
+        decode_profile: DecodeProfile,
+    ) -> ((String, Result<(AInferenceResponse, u64), String>), bool) {
+        let prompt = String::from_utf8_lossy(&request.payload).to_string();
+        let seed = Self::derive_seed(&request.request_id);
+        let dispatch = |agent: AgentRegistration| -> Pin<Box<dyn Future<Output = (String, Result<(AInferenceResponse, u64), String>)>>> {
+            let prompt = prompt.clone();
+            let msg_id = request.request_id.clone();
+            Box::pin(async move {
+                let result = Self::dispatch_inference(&agent, &prompt, &msg_id, seed, decode_profile).await;
+                (agent.agent_id, result)
+            })
+        };
+
+        let first = dispatch(primary);
+        match backup {
+            None => (first.await, false),
+            Some(backup) => match select(first, Box::pin(Self::delay(hedge_delay_ms))).await {
+                Either::Left((outcome @ (_, Ok(_)), _)) => (outcome, false),
+                Either::Left(((_, Err(_)), _)) => (dispatch(backup).await, true),
+                Either::Right(((), first)) => {
+                    Metrics::increment_counter("hedged_requests_fired_total");
+                    match select(first, dispatch(backup)).await {
+                        Either::Left((outcome @ (_, Ok(_)), loser)) | Either::Right((outcome @ (_, Ok(_)), loser)) => {
+                            ic_cdk::spawn(async move {
+                                let _ = loser.await;
+                            });
+                            (outcome, true)
+                        }
+                        Either::Left(((_, Err(_)), other)) | Either::Right(((_, Err(_)), other)) => (other.await, true),
+                    }
+                }
+            },
+        }
+    }
+
+    /// Resolves once `ms` has passed, woken by a one-shot timer
+    pub(crate) fn delay(ms: u64) -> impl Future<Output = ()> {
+        let (tx, rx) = oneshot::channel();
+        ic_cdk_timers::set_timer(Duration::from_millis(ms), move || {
+            let _ = tx.send(());
+        });
+        async move {
+            let _ = rx.await;
+        }
+    }
+
+    /// Call `infer` on one agent, recording dependency metrics, circuit state and SLA
+    /// observations. Returns the response and the elapsed time in IC time units.
+    pub(crate) async fn dispatch_inference(
+        agent: &AgentRegistration,
+        prompt: &str,
+        msg_id: &str,
+        seed: u64,
+        decode_profile: DecodeProfile,
+    ) -> Result<(AInferenceResponse, u64), String> {
+        let agent_id = &agent.agent_id;
+        let started = time();
+        let pr = Principal::from_text(&agent.canister_id)
+            .map_err(|e| format!("Invalid canister id for agent {}: {}", agent_id, e))?;
+        let req = AInferenceRequest::new(seed, prompt, msg_id, decode_profile);
+        
+        // Call agent.infer(InferenceRequest)
+        let call_result: Result<(AResult2,), _> = call(pr, "infer", (req,)).await;
+        let elapsed_ms = (time() - started) / 1_000_000;
+        let (result,) = match call_result {
+            Ok(r) => {
+                Metrics::record_dependency_success("agent.infer");
+                CircuitBreakerService::record_success(agent_id);
+                SlaService::record_call(agent_id, true, started);
+                Self::update_agent_stats(agent_id, true, elapsed_ms);
+                r
+            }
+            Err((code, msg)) => {
+                let class = CircuitBreakerService::classify(code, &msg);
+                Metrics::record_dependency_failure("agent.infer", &format!("{}: {:?} ({:?}): {}", agent_id, class, code, msg));
+                CircuitBreakerService::record_failure(agent_id, class.clone());
+                SlaService::record_call(agent_id, false, started);
+                Self::update_agent_stats(agent_id, false, elapsed_ms);
+                return Err(format!("infer call failed for {} ({:?}): {}", agent_id, class, msg));
+            }
+        };
+        
+        match result {
+            AResult2::Ok(resp) => Ok((resp, time() - started)),
+            AResult2::Err(err) => Err(format!("agent {} error: {}", agent_id, err)),
+        }
+    }
+    
     pub fn get_stats(agent_id: Option<String>) -> Vec<RoutingStats> {
         with_state(|state| {
             match agent_id {
@@ -219,8 +723,22 @@ impl RoutingService {
         })
     }
     
+    /// Weight of the newest sample in the latency EWMA
+    const LATENCY_EWMA_ALPHA: f64 = 0.2;
+
+    /// Fold one call outcome into the agent's routing stats: success rate and mean
+    /// response time over its lifetime, plus the latency EWMA of successful calls that
+    /// low-latency routing reads
     pub fn update_agent_stats(agent_id: &str, success: bool, response_time_ms: u64) {
         with_state_mut(|state| {
+            // Backfill stats for registered agents that predate the shared registration path
+            if !state.routing_stats.contains_key(agent_id) {
+                if let Some(agent) = state.agents.get(agent_id) {
+                    let stats = RegistryService::initial_stats(agent);
+                    state.routing_stats.insert(agent_id.to_string(), stats);
+                }
+            }
+            
             if let Some(stats) = state.routing_stats.get_mut(agent_id) {
                 stats.total_requests += 1;
                 
@@ -236,6 +754,14 @@ impl RoutingService {
                 let new_avg_time = (stats.average_response_time_ms * old_total as f64 
                     + response_time_ms as f64) / stats.total_requests as f64;
                 stats.average_response_time_ms = new_avg_time;
+                // A failure says nothing about how fast the agent answers, and a fast
+                // rejection would make a failing agent look quick
+                if success {
+                    stats.latency_ewma_ms = Some(match stats.latency_ewma_ms {
+                        Some(ewma) => Self::LATENCY_EWMA_ALPHA * response_time_ms as f64 + (1.0 - Self::LATENCY_EWMA_ALPHA) * ewma,
+                        None => response_time_ms as f64,
+                    });
+                }
             }
         });
     }
@@ -260,20 +786,21 @@ struct AInferenceRequest {
 }
 
 impl AInferenceRequest {
-    fn new(seed: u64, prompt: &str, msg_id: &str) -> Self {
+    fn new(seed: u64, prompt: &str, msg_id: &str, profile: DecodeProfile) -> Self {
+        let (max_tokens, temperature, top_p) = profile.params();
         Self {
             seed,
             prompt: prompt.to_string(),
-            decode_params: ADecodeParams { max_tokens: Some(128), temperature: Some(0.7), top_p: Some(0.9), top_k: None, repetition_penalty: None },
+            decode_params: ADecodeParams { max_tokens: Some(max_tokens), temperature: Some(temperature), top_p: Some(top_p), top_k: None, repetition_penalty: None },
             msg_id: msg_id.to_string(),
         }
     }
 }
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
-struct AInferenceResponse {
+pub(crate) struct AInferenceResponse {
     tokens: Vec<String>,
-    generated_text: String,
+    pub(crate) generated_text: String,
     inference_time_ms: u64,
     cache_hits: u32,
     cache_misses: u32,
@@ -286,7 +813,7 @@ enum AResult2 {
 }
 
 impl RoutingService {
-    fn derive_seed(msg_id: &str) -> u64 {
+    pub(crate) fn derive_seed(msg_id: &str) -> u64 {
         let mut hasher = Sha256::new();
         hasher.update(msg_id.as_bytes());
         let digest = hasher.finalize();
@@ -297,25 +824,176 @@ impl RoutingService {
 
     fn score_response(resp: &AInferenceResponse, elapsed_ms: u64) -> f32 {
         // Simple heuristic: positive credit for content length and tokens count; negative for latency
+        let weights = with_state(|state| state.config.routing_weights);
         let len_score = (resp.generated_text.len() as f32).min(1000.0) / 1000.0; // cap
         let tok_score = (resp.tokens.len() as f32).min(256.0) / 256.0;
-        let latency_penalty = (elapsed_ms as f32) / 5000.0; // 5s baseline
-        let cache_bonus = if resp.cache_hits + resp.cache_misses > 0 { (resp.cache_hits as f32) / ((resp.cache_hits + resp.cache_misses) as f32) * 0.1 } else { 0.0 };
-        (0.6 * len_score) + (0.3 * tok_score) + cache_bonus - (0.4 * latency_penalty)
+        let latency_penalty = (elapsed_ms as f32) / weights.latency_baseline_ms as f32;
+        let cache_ratio = if resp.cache_hits + resp.cache_misses > 0 { (resp.cache_hits as f32) / ((resp.cache_hits + resp.cache_misses) as f32) } else { 0.0 };
+        (weights.answer_length * len_score) + (weights.answer_tokens * tok_score) + (weights.cache_hits * cache_ratio) - (weights.latency * latency_penalty)
+    }
+
+    fn verifier_bonus(passed: bool) -> f32 {
+        if passed { with_state(|state| state.config.routing_weights.verifier_bonus) } else { 0.0 }
+    }
+
+    /// Replace the routing weights after checking they are usable
+    pub fn set_routing_weights(weights: RoutingWeights) -> Result<(), String> {
+        let all = [
+            weights.health,
+            weights.capability,
+            weights.reputation,
+            weights.load_penalty,
+            weights.answer_length,
+            weights.answer_tokens,
+            weights.cache_hits,
+            weights.latency,
+            weights.verifier_bonus,
+        ];
+        if all.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err("Routing weights must be finite and non-negative".to_string());
+        }
+        if weights.health + weights.capability + weights.reputation <= 0.0 {
+            return Err("At least one of the health, capability and reputation weights must be positive".to_string());
+        }
+        if weights.load_penalty > 1.0 {
+            return Err("load_penalty must be at most 1.0".to_string());
+        }
+        if weights.latency_baseline_ms == 0 {
+            return Err("latency_baseline_ms must be positive".to_string());
+        }
+        with_state_mut(|state| state.config.routing_weights = weights);
+        Ok(())
     }
 
-    fn run_verifiers(resp: &AInferenceResponse) -> VerifierEvidence {
+    /// Checks `run_verifiers` applies to every fanout response
+    pub const VERIFIERS: &'static [&'static str] = &["non_empty_output", "json_shape"];
+
+    fn run_verifiers(output: &str) -> VerifierEvidence {
         // Simple validators: ensure non-empty, attempt JSON parse if starts with '{'
-        if resp.generated_text.trim().is_empty() {
+        if output.trim().is_empty() {
             return VerifierEvidence { passed: false, details: "empty output".to_string() };
         }
-        if resp.generated_text.trim_start().starts_with('{') {
+        if output.trim_start().starts_with('{') {
             // shallow JSON key check for demo
-            let has_colon = resp.generated_text.contains(':');
+            let has_colon = output.contains(':');
             if !has_colon {
                 return VerifierEvidence { passed: false, details: "invalid json shape".to_string() };
             }
         }
         VerifierEvidence { passed: true, details: "basic checks pass".to_string() }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(id: &str, model: &str) -> AgentRegistration {
+        AgentRegistration { model_id: model.to_string(), ..AgentRegistration::test(id) }
+    }
+
+    #[test]
+    fn test_model_preference_falls_back_down_the_chain() {
+        let candidates = vec![agent("a", "starcoder-15b"), agent("b", "mistral-7b")];
+        let preference = ModelPreference {
+            fallback_chain: vec!["code-llama".to_string(), "StarCoder".to_string()],
+            allow_any_model: false,
+        };
+        let (agents, level) = RoutingService::apply_model_preference(candidates.clone(), &preference).unwrap();
+        assert_eq!(level, 1);
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].agent_id, "a");
+
+        let strict = ModelPreference { fallback_chain: vec!["code-llama".to_string()], allow_any_model: false };
+        assert!(RoutingService::apply_model_preference(candidates.clone(), &strict).is_err());
+
+        let any = ModelPreference { allow_any_model: true, ..strict };
+        let (agents, level) = RoutingService::apply_model_preference(candidates, &any).unwrap();
+        assert_eq!((agents.len(), level), (2, 1));
+    }
+
+    #[test]
+    fn test_undeclared_protocol_is_compatible_and_unknown_is_not() {
+        let mut candidate = agent("a", "llama");
+        assert!(RoutingService::speaks_infer_protocol(&candidate));
+        candidate.protocol_version = Some(*RoutingService::INFER_PROTOCOL_VERSIONS.end() + 1);
+        assert!(!RoutingService::speaks_infer_protocol(&candidate));
+        candidate.protocol_version = Some(0);
+        assert!(!RoutingService::speaks_infer_protocol(&candidate));
+    }
+
+    #[test]
+    fn test_capability_policy_applies_to_first_matching_capability() {
+        let policy = CapabilityRoutingPolicy {
+            routing_mode: RoutingMode::Broadcast,
+            strategy: None,
+            top_k: Some(2),
+            verifier_consensus: true,
+        };
+        RoutingService::set_capability_policy("code_review", Some(policy.clone())).unwrap();
+        assert!(RoutingService::set_capability_policy("code_review", Some(CapabilityRoutingPolicy { top_k: Some(0), ..policy.clone() })).is_err());
+        assert!(RoutingService::set_capability_policy("code_review", Some(CapabilityRoutingPolicy { strategy: Some("nope".to_string()), ..policy.clone() })).is_err());
+        let named = CapabilityRoutingPolicy { strategy: Some(RoutingMode::Broadcast.strategy_name().to_string()), ..policy };
+        assert!(RoutingService::set_capability_policy("code_review", Some(named)).is_err(), "strategy and top_k together");
+
+        let (capability, applied) = RoutingService::capability_policy(&["coding".to_string(), "code_review".to_string()]).unwrap();
+        assert_eq!((capability.as_str(), applied.top_k), ("code_review", Some(2)));
+        assert!(RoutingService::capability_policy(&["coding".to_string()]).is_none());
+
+        RoutingService::set_capability_policy("code_review", None).unwrap();
+        assert!(RoutingService::capability_policy(&["code_review".to_string()]).is_none());
+    }
+
+    #[test]
+    fn test_dispatch_calls_follow_mode_and_policy() {
+        let request = |capability: &str, routing_mode: RoutingMode| RouteRequest {
+            request_id: "r".to_string(),
+            requester: "u".to_string(),
+            capabilities_required: vec![capability.to_string()],
+            payload: b"hello".to_vec(),
+            routing_mode,
+            callback: None,
+            tags: vec![],
+            guaranteed_service: false,
+            priority: None,
+            strategy: None,
+            review: None,
+            model_preference: None,
+            session_key: None,
+            partition_key: None,
+            aggregation: None,
+        };
+        let calls = |request: &RouteRequest| RoutingService::dispatch_calls("u", request).unwrap();
+        assert_eq!(calls(&request("chat", RoutingMode::Unicast)), 1);
+        assert_eq!(calls(&request("chat", RoutingMode::Hedged)), 2);
+        assert_eq!(calls(&request("chat", RoutingMode::AgentSpawning)), 0);
+        // Free tier competes at most 3 agents
+        assert_eq!(calls(&request("chat", RoutingMode::Competition)), 3);
+
+        // A wider strategy is charged for its own selection size
+        with_state_mut(|state| state.config.enabled_strategies = vec!["diversity_max".to_string()]);
+        let wide = RouteRequest { strategy: Some("diversity_max".to_string()), ..request("chat", RoutingMode::Unicast) };
+        assert_eq!(calls(&wide), 3);
+
+        RoutingService::set_capability_policy("summarize", Some(CapabilityRoutingPolicy {
+            routing_mode: RoutingMode::Broadcast,
+            strategy: None,
+            top_k: Some(4),
+            verifier_consensus: false,
+        })).unwrap();
+        assert_eq!(calls(&request("summarize", RoutingMode::Unicast)), 4);
+    }
+
+    #[test]
+    fn test_routing_weights_are_validated() {
+        let defaults = RoutingWeights::default();
+        assert!(RoutingService::set_routing_weights(RoutingWeights { latency: -0.1, ..defaults }).is_err());
+        assert!(RoutingService::set_routing_weights(RoutingWeights { load_penalty: 1.5, ..defaults }).is_err());
+        assert!(RoutingService::set_routing_weights(RoutingWeights { health: 0.0, capability: 0.0, reputation: 0.0, ..defaults }).is_err());
+        assert!(RoutingService::set_routing_weights(RoutingWeights { health: f32::NAN, ..defaults }).is_err());
+
+        let tuned = RoutingWeights { health: 0.6, capability: 0.4, reputation: 0.0, ..defaults };
+        RoutingService::set_routing_weights(tuned).unwrap();
+        assert_eq!(with_state(|state| state.config.routing_weights), tuned);
+    }
+}
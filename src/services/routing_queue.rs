@@ -0,0 +1,162 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, AgentLoadService, CoordinatorState, QuotaManager};
+use crate::infra::Metrics;
+use serde::{Deserialize, Serialize};
+
+/// Waiting room for route requests that arrive while every capable agent is saturated.
+/// Only requests with a callback can wait, since the decision reaches them later; one
+/// without a callback is routed at once onto the saturated agents, whatever its tier. The
+/// maintenance timer drains the queue highest subscription tier first, so Enterprise traffic
+/// does not sit behind Free traffic; waiting raises a request's rank one tier per aging
+/// step, and past the maximum wait it is routed even onto saturated agents, so lower
+/// tiers are never starved.
+pub struct RoutingQueueService;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedRoute {
+    /// Principal the request was admitted for and the decision is delivered to
+    pub caller: String,
+    pub tier: String,
+    pub request: RouteRequest,
+    pub enqueued_at: u64,
+}
+
+impl RoutingQueueService {
+    const MAX_QUEUED: usize = 1000;
+    /// Waiting this long counts as one tier higher
+    const AGING_STEP_NS: u64 = 30 * 1_000_000_000;
+    const MAX_WAIT_NS: u64 = 5 * 60 * 1_000_000_000;
+    /// Queued requests routed per maintenance tick
+    pub const DRAIN_PER_TICK: usize = 10;
+
+    fn tier_rank(tier: &str) -> u64 {
+        QuotaManager::TIERS.iter().position(|t| *t == tier).unwrap_or(0) as u64
+    }
+
+    fn effective_rank(entry: &QueuedRoute, now: u64) -> u64 {
+        Self::tier_rank(&entry.tier) + now.saturating_sub(entry.enqueued_at) / Self::AGING_STEP_NS
+    }
+
+    pub fn is_overdue(entry: &QueuedRoute, now: u64) -> bool {
+        now.saturating_sub(entry.enqueued_at) >= Self::MAX_WAIT_NS
+    }
+
+    /// Every candidate is saturated, so routing now would only pile onto busy agents
+    pub fn at_capacity(candidates: &[AgentRegistration], now: u64) -> bool {
+        !candidates.is_empty() && candidates.iter().all(|agent| AgentLoadService::is_saturated(&agent.agent_id, now))
+    }
+
+    /// Queue `request` for `caller` and answer with its place in line
+    pub fn enqueue(caller: &str, request: RouteRequest, now: u64) -> Result<RouteResponse, String> {
+        with_state_mut(|state| {
+            if state.routing_queue.len() >= Self::MAX_QUEUED {
+                return Err("Routing queue is full; retry later".to_string());
+            }
+            let tier = state.user_quotas
+                .get(caller)
+                .map_or_else(|| "Free".to_string(), |q| q.subscription_tier.clone());
+            let request_id = request.request_id.clone();
+            state.routing_queue.push(QueuedRoute { caller: caller.to_string(), tier, request, enqueued_at: now });
+            Metrics::increment_counter("routes_queued_total");
+            Ok(Self::queued_response_in(state, caller, &request_id, now).expect("just queued"))
+        })
+    }
+
+    /// Place in line of a request `caller` still has waiting, for retries of the same request id
+    pub fn queued_response(caller: &str, request_id: &str, now: u64) -> Option<RouteResponse> {
+        with_state(|state| Self::queued_response_in(state, caller, request_id, now))
+    }
+
+    // Checked on every route, so the queue is scanned without being copied or sorted
+    fn queued_response_in(state: &CoordinatorState, caller: &str, request_id: &str, now: u64) -> Option<RouteResponse> {
+        let queue = &state.routing_queue;
+        let index = queue.iter().position(|entry| entry.caller == caller && entry.request.request_id == request_id)?;
+        let serve_key = |i: usize| (std::cmp::Reverse(Self::effective_rank(&queue[i], now)), queue[i].enqueued_at, i);
+        let own = serve_key(index);
+        let ahead = (0..queue.len()).filter(|&i| serve_key(i) < own).count();
+        Some(RouteResponse {
+            request_id: request_id.to_string(),
+            selected_agents: Vec::new(),
+            routing_time_ms: 0,
+            selection_criteria: format!("queued tier={} position={}", queue[index].tier, ahead + 1),
+            model_fallback_level: None,
+            results: Vec::new(),
+            aggregated_output: None,
+        })
+    }
+
+    /// Waiting requests in the order they will be served
+    pub fn ordered(now: u64) -> Vec<QueuedRoute> {
+        with_state(|state| Self::ordered_in(state, now))
+    }
+
+    // Ranks change as requests age, so the queue is ordered on read rather than kept in a heap
+    fn ordered_in(state: &CoordinatorState, now: u64) -> Vec<QueuedRoute> {
+        let mut entries = state.routing_queue.clone();
+        entries.sort_by(|a, b| {
+            Self::effective_rank(b, now)
+                .cmp(&Self::effective_rank(a, now))
+                .then(a.enqueued_at.cmp(&b.enqueued_at))
+        });
+        entries
+    }
+
+    pub fn remove(caller: &str, request_id: &str) {
+        with_state_mut(|state| state.routing_queue.retain(|entry| entry.caller != caller || entry.request.request_id != request_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(request_id: &str, tier: &str, enqueued_at: u64) -> QueuedRoute {
+        QueuedRoute {
+            caller: "user".to_string(),
+            tier: tier.to_string(),
+            request: RouteRequest {
+                request_id: request_id.to_string(),
+                requester: "user".to_string(),
+                capabilities_required: vec!["chat".to_string()],
+                payload: vec![],
+                routing_mode: RoutingMode::Unicast,
+                callback: None,
+                tags: vec![],
+                guaranteed_service: false,
+                priority: None,
+                strategy: None,
+                review: None,
+                model_preference: None,
+                session_key: None,
+                partition_key: None,
+                aggregation: None,
+            },
+            enqueued_at,
+        }
+    }
+
+    #[test]
+    fn test_higher_tiers_first_until_lower_tiers_age() {
+        let step = RoutingQueueService::AGING_STEP_NS;
+        let mut state = CoordinatorState::default();
+        state.routing_queue.push(queued("free", "Free", 0));
+        state.routing_queue.push(queued("pro", "Pro", 0));
+        state.routing_queue.push(queued("enterprise", "Enterprise", 0));
+
+        let order = |state: &CoordinatorState, now: u64| -> Vec<String> {
+            RoutingQueueService::ordered_in(state, now).into_iter().map(|e| e.request.request_id).collect()
+        };
+        assert_eq!(order(&state, 0), vec!["enterprise", "pro", "free"]);
+        let response = RoutingQueueService::queued_response_in(&state, "user", "pro", 0).unwrap();
+        assert!(response.selected_agents.is_empty());
+        assert_eq!(response.selection_criteria, "queued tier=Pro position=2");
+        assert!(RoutingQueueService::queued_response_in(&state, "other", "pro", 0).is_none(), "ids are per caller");
+
+        // Four steps of waiting lift Free past a fresh Enterprise request
+        state.routing_queue.push(queued("fresh", "Enterprise", 4 * step));
+        assert_eq!(order(&state, 4 * step), vec!["enterprise", "pro", "free", "fresh"]);
+        let position = |request_id: &str| RoutingQueueService::queued_response_in(&state, "user", request_id, 4 * step).unwrap().selection_criteria;
+        assert_eq!(position("free"), "queued tier=Free position=3");
+        assert_eq!(position("fresh"), "queued tier=Enterprise position=4");
+    }
+}
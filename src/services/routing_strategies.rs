@@ -0,0 +1,456 @@
+use crate::domain::*;
+use crate::services::{with_state, AgentLoadService, RegistryService, ReputationService};
+use crate::services::safety_limits::DispatchCost;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Agent selection strategy, looked up by name from the strategy registry
+pub trait RoutingStrategy: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Experimental strategies are only selectable once enabled in the coordinator config
+    fn experimental(&self) -> bool {
+        false
+    }
+
+    /// Most agents `select` returns
+    fn max_agents(&self) -> usize {
+        1
+    }
+
+    /// Pick agents for `request` from `candidates`, which are already filtered for
+    /// capability, health, open circuits, availability windows and SLA preference;
+    /// `now` is the routing time load reports are judged against
+    fn select(&self, request: &RouteRequest, candidates: Vec<AgentRegistration>, now: u64) -> Result<Vec<AgentRegistration>, String>;
+}
+
+/// Registered strategy as exposed to callers
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RoutingStrategyInfo {
+    pub name: String,
+    pub experimental: bool,
+    pub enabled: bool,
+}
+
+static STRATEGIES: &[&dyn RoutingStrategy] = &[
+    &TopKStrategy { name: "unicast", k: 1 },
+    &TopKStrategy { name: "broadcast", k: 3 },
+    &TopKStrategy { name: "agent_spawning", k: 5 },
+    &TopKStrategy { name: "hedged", k: 2 },
+    // Competition is then capped to the caller's tier fanout cap
+    &TopKStrategy { name: "competition", k: 5 },
+    &ConsistentHashStrategy,
+    &CostOptimizedStrategy,
+    &LowLatencyStrategy,
+    &DiversityMaxStrategy { k: 3 },
+];
+
+pub struct RoutingStrategyRegistry;
+
+impl RoutingStrategyRegistry {
+    pub fn lookup(name: &str) -> Option<&'static dyn RoutingStrategy> {
+        STRATEGIES.iter().copied().find(|s| s.name() == name)
+    }
+
+    /// Strategy a request asked for: an explicit strategy name wins over its routing mode
+    pub fn resolve(request: &RouteRequest) -> Result<&'static dyn RoutingStrategy, String> {
+        Self::resolve_name(request.strategy.as_deref().unwrap_or(request.routing_mode.strategy_name()))
+    }
+
+    /// Registered strategy `name`, provided it is enabled
+    pub fn resolve_name(name: &str) -> Result<&'static dyn RoutingStrategy, String> {
+        let strategy = Self::lookup(name).ok_or_else(|| format!("Unknown routing strategy: {}", name))?;
+        if !Self::is_enabled(strategy) {
+            return Err(format!("Routing strategy '{}' is experimental and not enabled", name));
+        }
+        Ok(strategy)
+    }
+
+    fn is_enabled(strategy: &dyn RoutingStrategy) -> bool {
+        !strategy.experimental()
+            || with_state(|state| state.config.enabled_strategies.iter().any(|s| s == strategy.name()))
+    }
+
+    pub fn list() -> Vec<RoutingStrategyInfo> {
+        STRATEGIES
+            .iter()
+            .map(|s| RoutingStrategyInfo {
+                name: s.name().to_string(),
+                experimental: s.experimental(),
+                enabled: Self::is_enabled(*s),
+            })
+            .collect()
+    }
+
+    /// Names of experimental strategies to enable; anything else is rejected
+    pub fn validate_enabled(names: &[String]) -> Result<(), String> {
+        for name in names {
+            match Self::lookup(name) {
+                Some(s) if s.experimental() => {}
+                Some(_) => return Err(format!("Routing strategy '{}' is always enabled", name)),
+                None => return Err(format!("Unknown routing strategy: {}", name)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Health-weighted capability fit, nudged by reputation and discounted by load, shared
+/// by the built-in strategies. With the default weights a saturated agent keeps half its
+/// score, so a healthy but busy agent stops winning every route while an idle peer is
+/// nearly as good.
+pub fn agent_score(agent: &AgentRegistration, required_capabilities: &[String], now: u64) -> f32 {
+    let weights = with_state(|state| state.config.routing_weights);
+
+    let capability_score = required_capabilities
+        .iter()
+        .map(|cap| if RegistryService::has_capability(&agent.capabilities, cap) { 1.0 } else { 0.0 })
+        .sum::<f32>() / required_capabilities.len().max(1) as f32;
+
+    let fit = weights.health * agent.health_score
+        + weights.capability * capability_score
+        + weights.reputation * ReputationService::score(&agent.agent_id);
+    fit * (1.0 - weights.load_penalty * AgentLoadService::routing_load(&agent.agent_id, now))
+}
+
+fn sort_by_score(candidates: &mut [AgentRegistration], required_capabilities: &[String], now: u64) {
+    candidates.sort_by(|a, b| {
+        let score_a = agent_score(a, required_capabilities, now);
+        let score_b = agent_score(b, required_capabilities, now);
+        score_b.partial_cmp(&score_a).unwrap() // Descending order
+    });
+}
+
+/// Top `k` agents by health and capability fit
+pub struct TopKStrategy {
+    pub name: &'static str,
+    pub k: usize,
+}
+
+impl RoutingStrategy for TopKStrategy {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn max_agents(&self) -> usize {
+        self.k
+    }
+
+    fn select(&self, request: &RouteRequest, mut candidates: Vec<AgentRegistration>, now: u64) -> Result<Vec<AgentRegistration>, String> {
+        if candidates.is_empty() {
+            return Err("No agents available with required capabilities".to_string());
+        }
+        sort_by_score(&mut candidates, &request.capabilities_required, now);
+        candidates.truncate(self.k);
+        Ok(candidates)
+    }
+}
+
+/// Cheapest agent to call among reasonably fit agents. An agent is priced at the published
+/// task prices of every capability it carries, so a narrow specialist undercuts a generalist
+/// offering the same capability; equal prices go to the better fit.
+pub struct CostOptimizedStrategy;
+
+impl CostOptimizedStrategy {
+    fn price(agent: &AgentRegistration) -> f64 {
+        with_state(|state| DispatchCost::task_price(state, &agent.capabilities))
+    }
+}
+
+impl RoutingStrategy for CostOptimizedStrategy {
+    fn name(&self) -> &'static str {
+        "cost_optimized"
+    }
+
+    fn experimental(&self) -> bool {
+        true
+    }
+
+    fn select(&self, request: &RouteRequest, candidates: Vec<AgentRegistration>, now: u64) -> Result<Vec<AgentRegistration>, String> {
+        let required = &request.capabilities_required;
+        let scored: Vec<(f32, AgentRegistration)> = candidates
+            .into_iter()
+            .map(|a| (agent_score(&a, required, now), a))
+            .collect();
+        let best_score = scored.iter().map(|(score, _)| *score).fold(f32::NEG_INFINITY, f32::max);
+
+        // Within 20% of the best fit, the cheapest agent wins
+        scored
+            .into_iter()
+            .filter(|(score, _)| *score >= best_score * 0.8)
+            .map(|(score, agent)| (Self::price(&agent), score, agent))
+            .min_by(|(price_a, score_a, _), (price_b, score_b, _)| {
+                price_a.total_cmp(price_b).then(score_b.total_cmp(score_a))
+            })
+            .map(|(_, _, agent)| vec![agent])
+            .ok_or_else(|| "No agents available with required capabilities".to_string())
+    }
+}
+
+/// For interactive requests: the agent answering fastest lately, by latency EWMA, among
+/// agents within 30% of the best fit. Agents without samples count as slow, so a fresh
+/// agent is tried once measured agents degrade past the prior.
+pub struct LowLatencyStrategy;
+
+impl LowLatencyStrategy {
+    const UNMEASURED_LATENCY_MS: f64 = 1000.0;
+}
+
+impl RoutingStrategy for LowLatencyStrategy {
+    fn name(&self) -> &'static str {
+        "low_latency"
+    }
+
+    fn select(&self, request: &RouteRequest, candidates: Vec<AgentRegistration>, now: u64) -> Result<Vec<AgentRegistration>, String> {
+        let required = &request.capabilities_required;
+        let best_score = candidates
+            .iter()
+            .map(|a| agent_score(a, required, now))
+            .fold(f32::NEG_INFINITY, f32::max);
+        let latency = |agent: &AgentRegistration| {
+            with_state(|state| {
+                state.routing_stats
+                    .get(&agent.agent_id)
+                    .and_then(|s| s.latency_ewma_ms)
+                    .unwrap_or(Self::UNMEASURED_LATENCY_MS)
+            })
+        };
+
+        candidates
+            .into_iter()
+            .filter(|a| agent_score(a, required, now) >= best_score * 0.7)
+            .min_by(|a, b| latency(a).partial_cmp(&latency(b)).unwrap())
+            .map(|agent| vec![agent])
+            .ok_or_else(|| "No agents available with required capabilities".to_string())
+    }
+}
+
+/// Anycast: the agent owning the request's partition key on a consistent-hash ring, so
+/// repeated work for one key lands on one agent. Each agent holds many points on the
+/// ring; when an agent joins or leaves, only the keys next to its points move.
+pub struct ConsistentHashStrategy;
+
+impl ConsistentHashStrategy {
+    const POINTS_PER_AGENT: u32 = 64;
+
+    fn hash(value: &str) -> u64 {
+        let digest = Sha256::digest(value.as_bytes());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        u64::from_be_bytes(bytes)
+    }
+}
+
+impl RoutingStrategy for ConsistentHashStrategy {
+    fn name(&self) -> &'static str {
+        "anycast"
+    }
+
+    fn select(&self, request: &RouteRequest, candidates: Vec<AgentRegistration>, _now: u64) -> Result<Vec<AgentRegistration>, String> {
+        let key = Self::hash(request.partition_key.as_deref().unwrap_or(&request.request_id));
+        let mut ring: Vec<(u64, usize)> = candidates
+            .iter()
+            .enumerate()
+            .flat_map(|(i, agent)| {
+                (0..Self::POINTS_PER_AGENT).map(move |point| (Self::hash(&format!("{}#{}", agent.agent_id, point)), i))
+            })
+            .collect();
+        ring.sort_unstable();
+        // First point clockwise from the key, wrapping around the ring
+        let owner = ring
+            .iter()
+            .find(|(point, _)| *point >= key)
+            .or_else(|| ring.first())
+            .map(|(_, i)| *i)
+            .ok_or_else(|| "No agents available with required capabilities".to_string())?;
+        Ok(candidates.into_iter().nth(owner).into_iter().collect())
+    }
+}
+
+/// Spread a request across distinct models while covering as many required capabilities as possible
+pub struct DiversityMaxStrategy {
+    pub k: usize,
+}
+
+impl RoutingStrategy for DiversityMaxStrategy {
+    fn name(&self) -> &'static str {
+        "diversity_max"
+    }
+
+    fn experimental(&self) -> bool {
+        true
+    }
+
+    fn max_agents(&self) -> usize {
+        self.k
+    }
+
+    fn select(&self, request: &RouteRequest, mut candidates: Vec<AgentRegistration>, now: u64) -> Result<Vec<AgentRegistration>, String> {
+        if candidates.is_empty() {
+            return Err("No agents available with required capabilities".to_string());
+        }
+        let required = &request.capabilities_required;
+        sort_by_score(&mut candidates, required, now);
+
+        let mut selected: Vec<AgentRegistration> = Vec::new();
+        while selected.len() < self.k && !candidates.is_empty() {
+            // Greedy: prefer agents adding uncovered capabilities, then unseen models, then score
+            let gain = |agent: &AgentRegistration| {
+                let new_capabilities = required
+                    .iter()
+                    .filter(|cap| RegistryService::has_capability(&agent.capabilities, cap))
+                    .filter(|cap| !selected.iter().any(|s| RegistryService::has_capability(&s.capabilities, cap)))
+                    .count();
+                let new_model = !selected.iter().any(|s| s.model_id == agent.model_id);
+                (new_capabilities, new_model)
+            };
+            let next = candidates
+                .iter()
+                .enumerate()
+                .max_by(|(i, a), (j, b)| gain(a).cmp(&gain(b)).then(j.cmp(i)))
+                .map(|(i, _)| i)
+                .unwrap();
+            selected.push(candidates.remove(next));
+        }
+        Ok(selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(id: &str, model: &str, capabilities: &[&str], health: f32) -> AgentRegistration {
+        AgentRegistration {
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            model_id: model.to_string(),
+            health_score: health,
+            ..AgentRegistration::test(id)
+        }
+    }
+
+    fn request(capabilities: &[&str]) -> RouteRequest {
+        RouteRequest {
+            request_id: "r".to_string(),
+            requester: "u".to_string(),
+            capabilities_required: capabilities.iter().map(|c| c.to_string()).collect(),
+            payload: vec![],
+            routing_mode: RoutingMode::Unicast,
+            callback: None,
+            tags: vec![],
+            guaranteed_service: false,
+            priority: None,
+            strategy: None,
+            review: None,
+            model_preference: None,
+            session_key: None,
+            partition_key: None,
+            aggregation: None,
+        }
+    }
+
+    #[test]
+    fn test_builtin_strategies_registered_by_mode() {
+        for mode in [RoutingMode::Unicast, RoutingMode::Broadcast, RoutingMode::AgentSpawning, RoutingMode::Hedged, RoutingMode::Anycast, RoutingMode::Competition] {
+            let strategy = RoutingStrategyRegistry::lookup(mode.strategy_name()).unwrap();
+            assert!(!strategy.experimental());
+        }
+        assert!(RoutingStrategyRegistry::lookup("cost_optimized").unwrap().experimental());
+        assert!(!RoutingStrategyRegistry::lookup("low_latency").unwrap().experimental());
+        assert!(RoutingStrategyRegistry::lookup("nope").is_none());
+    }
+
+    #[test]
+    fn test_diversity_max_prefers_new_capabilities_and_models() {
+        let candidates = vec![
+            agent("a", "llama", &["coding"], 1.0),
+            agent("b", "llama", &["coding"], 0.9),
+            agent("c", "mistral", &["coding"], 0.5),
+            agent("d", "llama", &["testing"], 0.4),
+        ];
+        let selected = DiversityMaxStrategy { k: 3 }
+            .select(&request(&["coding", "testing"]), candidates, 0)
+            .unwrap();
+        let ids: Vec<&str> = selected.iter().map(|a| a.agent_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "d", "c"]);
+    }
+
+    #[test]
+    fn test_saturated_agent_loses_to_idle_peer() {
+        use crate::services::autonomous_coord::{TaskLedgerEntry, TaskStatus};
+        use crate::services::with_state_mut;
+
+        with_state_mut(|state| {
+            for i in 0..8 {
+                state.workload_index.update_task(&TaskLedgerEntry::test(&format!("t{}", i), "busy", TaskStatus::InProgress));
+            }
+        });
+        let candidates = vec![agent("busy", "llama", &["coding"], 1.0), agent("idle", "llama", &["coding"], 0.9)];
+        let selected = TopKStrategy { name: "unicast", k: 1 }.select(&request(&["coding"]), candidates, 0).unwrap();
+        assert_eq!(selected[0].agent_id, "idle");
+    }
+
+    #[test]
+    fn test_cost_optimized_picks_the_cheapest_fit_agent() {
+        use crate::services::with_state_mut;
+
+        with_state_mut(|state| {
+            state.capability_pricing.insert("coding".to_string(), 0.02);
+            state.capability_pricing.insert("vision".to_string(), 0.10);
+        });
+        let candidates = vec![
+            agent("generalist", "llama", &["coding", "vision"], 1.0),
+            agent("specialist", "llama", &["coding"], 0.95),
+            agent("unfit", "llama", &["coding"], 0.1),
+        ];
+        let selected = CostOptimizedStrategy.select(&request(&["coding"]), candidates, 0).unwrap();
+        assert_eq!(selected[0].agent_id, "specialist");
+    }
+
+    #[test]
+    fn test_low_latency_follows_recent_response_times() {
+        use crate::services::{with_state_mut, RoutingService};
+
+        with_state_mut(|state| {
+            for id in ["steady", "slowing", "unmeasured"] {
+                state.agents.insert(id.to_string(), agent(id, "llama", &["chat"], 1.0));
+            }
+        });
+        RoutingService::update_agent_stats("steady", true, 300);
+        RoutingService::update_agent_stats("slowing", true, 100);
+        RoutingService::update_agent_stats("slowing", true, 2000);
+        RoutingService::update_agent_stats("slowing", false, 1);
+        let ewma = with_state(|state| state.routing_stats["slowing"].latency_ewma_ms.unwrap());
+        assert!((ewma - 480.0).abs() < 1e-9, "the fast failure is left out");
+
+        let candidates = vec![
+            agent("slowing", "llama", &["chat"], 1.0),
+            agent("unmeasured", "llama", &["chat"], 1.0),
+            agent("steady", "llama", &["chat"], 0.9),
+        ];
+        let selected = LowLatencyStrategy.select(&request(&["chat"]), candidates, 0).unwrap();
+        assert_eq!(selected[0].agent_id, "steady");
+    }
+
+    #[test]
+    fn test_anycast_keeps_keys_on_their_agent() {
+        let fleet = |ids: &[&str]| -> Vec<AgentRegistration> { ids.iter().map(|id| agent(id, "llama", &["chat"], 1.0)).collect() };
+        let owner = |key: &str, ids: &[&str]| {
+            let request = RouteRequest { partition_key: Some(key.to_string()), ..request(&["chat"]) };
+            ConsistentHashStrategy.select(&request, fleet(ids), 0).unwrap()[0].agent_id.clone()
+        };
+        let keys: Vec<String> = (0..200).map(|i| format!("user-{}", i)).collect();
+        let before: Vec<String> = keys.iter().map(|k| owner(k, &["a", "b", "c", "d"])).collect();
+        assert_eq!(before, keys.iter().map(|k| owner(k, &["d", "c", "b", "a"])).collect::<Vec<_>>());
+
+        // Only keys owned by the departed agent move
+        let after: Vec<String> = keys.iter().map(|k| owner(k, &["a", "b", "c"])).collect();
+        for (old, new) in before.iter().zip(&after) {
+            if old != "d" {
+                assert_eq!(old, new);
+            }
+        }
+        assert!(before.iter().any(|owner| owner == "d"));
+    }
+}
@@ -0,0 +1,340 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, CoordinatorState, EconFallbackService, EconIntegrationService, SiemExportService};
+use crate::services::siem_export::SiemEventCategory;
+use crate::services::autonomous_coord::SessionStatus;
+use crate::infra::{Log, Metrics};
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Ceilings a user sets on their own account, independent of and usually below their
+/// tier's maximums. Dispatch paths estimate what a call could consume and are refused
+/// with `SafetyLimit` naming the ceiling before any tier limit is consulted. Usage is
+/// charged when work is dispatched, at the estimate, and resets each UTC day. Once a
+/// dispatch's calls have returned its charge is settled, and the maintenance timer
+/// replaces settled estimates with the tokens the economics canister has counted, so
+/// estimates do not hold back users for work they never used. Charges for work still in
+/// flight stay reserved at their estimates.
+pub struct SafetyLimitService;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct SafetyLimits {
+    pub max_tokens_per_day: Option<u64>,
+    pub max_concurrent_sessions: Option<u32>,
+    /// Against the published per-capability task prices
+    pub max_spend_usd_per_day: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyUsage {
+    /// Days since the epoch
+    pub day: u64,
+    pub tokens: u64,
+    pub spend_usd: f64,
+    /// Economics' monthly token count at the last reconciliation
+    #[serde(default)]
+    pub econ_tokens_baseline: Option<u64>,
+    /// (settled at, estimated tokens) of returned dispatches not reconciled yet
+    #[serde(default)]
+    pub settled: Vec<(u64, u64)>,
+    #[serde(default)]
+    pub reconciled_at: Option<u64>,
+}
+
+/// Tokens `admit` charged for one dispatch; hand it to `settle` once the calls return
+#[derive(Debug, Clone, Default)]
+pub struct Reservation {
+    principal: String,
+    day: u64,
+    tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SafetyLimitStatus {
+    pub limits: SafetyLimits,
+    pub tokens_today: u64,
+    pub spend_today_usd: f64,
+    pub active_sessions: u32,
+}
+
+/// Upper bound of what one dispatch may consume
+#[derive(Debug, Clone, Default)]
+pub struct DispatchCost {
+    pub tokens: u64,
+    pub spend_usd: f64,
+    pub sessions: u32,
+}
+
+impl DispatchCost {
+    /// `calls` agent calls for `capabilities`, each generating up to the profile's token budget
+    pub fn inference(state: &CoordinatorState, capabilities: &[String], calls: u32, decode_profile: DecodeProfile) -> Self {
+        let (max_tokens, _, _) = decode_profile.params();
+        Self {
+            tokens: max_tokens as u64 * calls as u64,
+            spend_usd: Self::task_price(state, capabilities) * calls as f64,
+            sessions: 0,
+        }
+    }
+
+    /// Sum of the published prices of the capabilities; unpriced ones are free
+    pub fn task_price(state: &CoordinatorState, capabilities: &[String]) -> f64 {
+        capabilities.iter().filter_map(|capability| state.capability_pricing.get(capability)).sum()
+    }
+}
+
+impl std::ops::Add for DispatchCost {
+    type Output = Self;
+
+    fn add(mut self, other: DispatchCost) -> Self {
+        self.tokens += other.tokens;
+        self.spend_usd += other.spend_usd;
+        self.sessions += other.sessions;
+        self
+    }
+}
+
+impl SafetyLimitService {
+    const DAY_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+    /// Sessions idle this long stop counting, matching session cleanup
+    const SESSION_IDLE_NS: u64 = 3600 * 1_000_000_000;
+    const RECONCILE_INTERVAL_NS: u64 = 5 * 60 * 1_000_000_000;
+    /// Users reconciled per maintenance tick
+    const RECONCILE_BATCH: usize = 10;
+
+    pub fn set_limits(principal: &str, limits: SafetyLimits) -> Result<SafetyLimitStatus, String> {
+        if limits.max_spend_usd_per_day.is_some_and(|spend| !spend.is_finite() || spend < 0.0) {
+            return Err("max_spend_usd_per_day must be a non-negative amount".to_string());
+        }
+        with_state_mut(|state| {
+            if limits.max_tokens_per_day.is_none() && limits.max_concurrent_sessions.is_none() && limits.max_spend_usd_per_day.is_none() {
+                state.safety_limits.remove(principal);
+            } else {
+                state.safety_limits.insert(principal.to_string(), limits);
+            }
+        });
+        Ok(Self::status(principal))
+    }
+
+    pub fn status(principal: &str) -> SafetyLimitStatus {
+        let now = time();
+        with_state(|state| {
+            let usage = Self::usage_today(state, principal, now);
+            SafetyLimitStatus {
+                limits: state.safety_limits.get(principal).cloned().unwrap_or_default(),
+                tokens_today: usage.tokens,
+                spend_today_usd: usage.spend_usd,
+                active_sessions: Self::active_sessions(state, principal, now),
+            }
+        })
+    }
+
+    /// Refuse with the ceiling `cost` would cross, or charge it to today's usage
+    pub fn admit(principal: &str, cost: impl FnOnce(&CoordinatorState) -> DispatchCost) -> Result<Reservation, String> {
+        let now = time();
+        with_state_mut(|state| {
+            let cost = cost(state);
+            Self::admit_in(state, principal, &cost, now)
+        })
+        .inspect_err(|e| SiemExportService::emit(principal, SiemEventCategory::Quota, "safety_limit_hit", principal, e.clone()))
+    }
+
+    fn admit_in(state: &mut CoordinatorState, principal: &str, cost: &DispatchCost, now: u64) -> Result<Reservation, String> {
+        let Some(limits) = state.safety_limits.get(principal) else { return Ok(Reservation::default()) };
+        let usage = Self::usage_today(state, principal, now);
+
+        let error = if limits.max_tokens_per_day.is_some_and(|max| usage.tokens + cost.tokens > max) {
+            Some(format!(
+                "max_tokens_per_day of {} reached ({} used today, up to {} requested)",
+                limits.max_tokens_per_day.unwrap_or_default(), usage.tokens, cost.tokens
+            ))
+        } else if limits.max_spend_usd_per_day.is_some_and(|max| usage.spend_usd + cost.spend_usd > max) {
+            Some(format!(
+                "max_spend_usd_per_day of ${:.4} reached (${:.4} spent today, up to ${:.4} requested)",
+                limits.max_spend_usd_per_day.unwrap_or_default(), usage.spend_usd, cost.spend_usd
+            ))
+        } else if cost.sessions > 0 && limits.max_concurrent_sessions
+            .is_some_and(|max| Self::active_sessions(state, principal, now) + cost.sessions > max)
+        {
+            Some(format!(
+                "max_concurrent_sessions of {} reached ({} active)",
+                limits.max_concurrent_sessions.unwrap_or_default(), Self::active_sessions(state, principal, now)
+            ))
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            Metrics::increment_counter("safety_limit_rejections");
+            return Err(format!("SafetyLimit: {}", error));
+        }
+
+        let usage = state.safety_usage.entry(principal.to_string()).or_default();
+        if usage.day != now / Self::DAY_NS {
+            *usage = DailyUsage { day: now / Self::DAY_NS, ..Default::default() };
+        }
+        usage.tokens += cost.tokens;
+        usage.spend_usd += cost.spend_usd;
+        Ok(Reservation { principal: principal.to_string(), day: usage.day, tokens: cost.tokens })
+    }
+
+    /// The reserved calls have returned, so economics can count what they used
+    pub fn settle(reservation: Reservation) {
+        if reservation.tokens == 0 {
+            return;
+        }
+        let now = time();
+        with_state_mut(|state| Self::settle_in(state, reservation, now));
+    }
+
+    fn settle_in(state: &mut CoordinatorState, reservation: Reservation, now: u64) {
+        if let Some(usage) = state.safety_usage.get_mut(&reservation.principal).filter(|usage| usage.day == reservation.day) {
+            usage.settled.push((now, reservation.tokens));
+        }
+    }
+
+    /// Called from the maintenance timer; reconciles a batch of users with ceilings and
+    /// usage today, least recently reconciled first
+    pub fn tick() {
+        if EconFallbackService::is_degraded() {
+            return;
+        }
+        let now = time();
+        let due = with_state_mut(|state| Self::due_for_reconciliation_in(state, now));
+        for principal in due {
+            ic_cdk::spawn(async move {
+                match EconIntegrationService::get_user_subscription(&principal).await {
+                    Ok(Some(subscription)) => {
+                        let tokens = subscription.current_usage.tokens_used_this_month;
+                        with_state_mut(|state| Self::reconcile_in(state, &principal, tokens, now));
+                    }
+                    Ok(None) => {}
+                    Err(e) => Log::warn("safety_limits", format!("Failed to reconcile usage for {}: {}", principal, e)),
+                }
+            });
+        }
+    }
+
+    /// Picks the batch and stamps it, so a slow reply is not asked for again next tick
+    fn due_for_reconciliation_in(state: &mut CoordinatorState, now: u64) -> Vec<String> {
+        let today = now / Self::DAY_NS;
+        let limits = &state.safety_limits;
+        let mut due: Vec<(&String, &mut DailyUsage)> = state.safety_usage
+            .iter_mut()
+            .filter(|(principal, usage)| usage.day == today && limits.contains_key(*principal))
+            .filter(|(_, usage)| usage.reconciled_at.is_none_or(|at| now.saturating_sub(at) >= Self::RECONCILE_INTERVAL_NS))
+            .collect();
+        due.sort_by_key(|(principal, usage)| (usage.reconciled_at, principal.to_string()));
+        due.into_iter()
+            .take(Self::RECONCILE_BATCH)
+            .map(|(principal, usage)| {
+                usage.reconciled_at = Some(now);
+                principal.clone()
+            })
+            .collect()
+    }
+
+    /// Replace the estimates of dispatches settled before economics was asked, at `read_at`,
+    /// with what economics counted since the previous reading. Reserved charges are kept.
+    /// The day's first reading only sets the baseline, as does one after economics starts
+    /// a new month, and the estimates it covers stand.
+    fn reconcile_in(state: &mut CoordinatorState, principal: &str, econ_tokens_this_month: u64, read_at: u64) {
+        let Some(usage) = state.safety_usage.get_mut(principal).filter(|usage| usage.day == read_at / Self::DAY_NS) else { return };
+        let (counted, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut usage.settled).into_iter().partition(|(settled_at, _)| *settled_at <= read_at);
+        usage.settled = pending;
+        if let Some(baseline) = usage.econ_tokens_baseline.filter(|baseline| econ_tokens_this_month >= *baseline) {
+            let estimated: u64 = counted.iter().map(|(_, tokens)| tokens).sum();
+            usage.tokens = usage.tokens.saturating_sub(estimated) + (econ_tokens_this_month - baseline);
+            Metrics::increment_counter("safety_usage_reconciled_total");
+        }
+        usage.econ_tokens_baseline = Some(econ_tokens_this_month);
+        usage.reconciled_at = Some(read_at);
+    }
+
+    fn usage_today(state: &CoordinatorState, principal: &str, now: u64) -> DailyUsage {
+        state.safety_usage
+            .get(principal)
+            .filter(|usage| usage.day == now / Self::DAY_NS)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Live sessions coordinated by one of the principal's agents
+    pub(crate) fn active_sessions(state: &CoordinatorState, principal: &str, now: u64) -> u32 {
+        state.coordination_sessions
+            .iter()
+            .flat_map(|sessions| sessions.values())
+            .filter(|session| matches!(session.status, SessionStatus::Active | SessionStatus::Coordinating))
+            .filter(|session| now.saturating_sub(session.last_activity) <= Self::SESSION_IDLE_NS)
+            .filter(|session| state.agents.get(&session.coordinator_agent).is_some_and(|a| a.agent_principal == principal))
+            .count() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admit_names_the_ceiling_and_charges_usage() {
+        let mut state = CoordinatorState::default();
+        state.capability_pricing.insert("coding".to_string(), 0.5);
+        state.safety_limits.insert("alice".to_string(), SafetyLimits {
+            max_tokens_per_day: Some(300),
+            max_concurrent_sessions: Some(0),
+            max_spend_usd_per_day: Some(1.0),
+        });
+        let capabilities = vec!["coding".to_string()];
+        let cost = DispatchCost::inference(&state, &capabilities, 1, DecodeProfile::Balanced);
+        assert_eq!(cost.tokens, 128);
+
+        assert!(SafetyLimitService::admit_in(&mut state, "alice", &cost, 0).is_ok());
+        assert!(SafetyLimitService::admit_in(&mut state, "alice", &cost, 0).is_ok());
+        let error = SafetyLimitService::admit_in(&mut state, "alice", &cost, 0).unwrap_err();
+        assert!(error.starts_with("SafetyLimit: max_tokens_per_day"), "{}", error);
+
+        // A new day starts from zero; spend is the next ceiling hit
+        let next_day = SafetyLimitService::DAY_NS;
+        let pricey = DispatchCost { tokens: 0, spend_usd: 1.5, sessions: 0 };
+        let error = SafetyLimitService::admit_in(&mut state, "alice", &pricey, next_day).unwrap_err();
+        assert!(error.starts_with("SafetyLimit: max_spend_usd_per_day"), "{}", error);
+
+        let session = DispatchCost { sessions: 1, ..Default::default() };
+        let error = SafetyLimitService::admit_in(&mut state, "alice", &session, next_day).unwrap_err();
+        assert!(error.starts_with("SafetyLimit: max_concurrent_sessions"), "{}", error);
+
+        // Principals without ceilings are never refused
+        assert!(SafetyLimitService::admit_in(&mut state, "bob", &pricey, 0).is_ok());
+    }
+
+    #[test]
+    fn test_reconcile_replaces_settled_estimates_and_keeps_reservations() {
+        let mut state = CoordinatorState::default();
+        state.safety_limits.insert("alice".to_string(), SafetyLimits { max_tokens_per_day: Some(1_000), ..Default::default() });
+        let estimate = DispatchCost { tokens: 400, ..Default::default() };
+        let first = SafetyLimitService::admit_in(&mut state, "alice", &estimate, 0).unwrap();
+        SafetyLimitService::admit_in(&mut state, "bob", &estimate, 0).unwrap();
+        assert_eq!(SafetyLimitService::due_for_reconciliation_in(&mut state, 0), vec!["alice".to_string()]);
+        assert!(SafetyLimitService::due_for_reconciliation_in(&mut state, 1).is_empty(), "stamped when picked");
+
+        // The first reading only sets the baseline
+        SafetyLimitService::settle_in(&mut state, first, 1);
+        SafetyLimitService::reconcile_in(&mut state, "alice", 5_000, 2);
+        assert_eq!(state.safety_usage["alice"].tokens, 400);
+
+        let returned = SafetyLimitService::admit_in(&mut state, "alice", &estimate, 3).unwrap();
+        let in_flight = SafetyLimitService::admit_in(&mut state, "alice", &DispatchCost { tokens: 200, ..Default::default() }, 3).unwrap();
+        assert_eq!(state.safety_usage["alice"].tokens, 1_000);
+        SafetyLimitService::settle_in(&mut state, returned, 4);
+
+        // The returned call only used 50 tokens; the one still running stays reserved
+        SafetyLimitService::reconcile_in(&mut state, "alice", 5_050, 5);
+        assert_eq!(state.safety_usage["alice"].tokens, 650);
+        assert!(SafetyLimitService::admit_in(&mut state, "alice", &estimate, 6).is_err());
+
+        // Settled after the reading: economics may not have counted it, so it waits for the next
+        SafetyLimitService::settle_in(&mut state, in_flight, 6);
+        SafetyLimitService::reconcile_in(&mut state, "alice", 5_050, 5);
+        assert_eq!(state.safety_usage["alice"].tokens, 650);
+        SafetyLimitService::reconcile_in(&mut state, "alice", 5_080, 7);
+        assert_eq!(state.safety_usage["alice"].tokens, 480);
+    }
+}
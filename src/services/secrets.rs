@@ -0,0 +1,201 @@
+use crate::services::{with_state, with_state_mut};
+use crate::services::audit::{AuditAction, AuditService};
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Per-user vault of write-only values referenced by label from tool integrations.
+/// Values are never returned by any endpoint; they are only substituted into
+/// outbound integration configs at use time, and every such use is audited.
+pub struct SecretsService;
+
+/// Stored secret; the value never leaves the canister through the API
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredSecret {
+    pub value: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub last_accessed_at: Option<u64>,
+}
+
+// Keep values out of debug output of the coordinator state
+impl std::fmt::Debug for StoredSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoredSecret")
+            .field("value", &"[redacted]")
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("last_accessed_at", &self.last_accessed_at)
+            .finish()
+    }
+}
+
+/// What callers may see about a secret
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SecretMetadata {
+    pub label: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub last_accessed_at: Option<u64>,
+}
+
+const REFERENCE_PREFIX: &str = "{{secret:";
+const REFERENCE_SUFFIX: &str = "}}";
+
+impl SecretsService {
+    const MAX_SECRETS_PER_USER: usize = 50;
+    const MAX_LABEL_LEN: usize = 64;
+    const MAX_VALUE_BYTES: usize = 4096;
+
+    fn validate_label(label: &str) -> Result<(), String> {
+        if label.is_empty() || label.len() > Self::MAX_LABEL_LEN {
+            return Err(format!("Secret label must be 1-{} characters", Self::MAX_LABEL_LEN));
+        }
+        if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+            return Err("Secret label may only contain letters, digits, '_', '-' and '.'".to_string());
+        }
+        Ok(())
+    }
+
+    /// Create or overwrite a secret
+    pub fn put_secret(owner: &str, label: &str, value: String) -> Result<(), String> {
+        Self::validate_label(label)?;
+        if value.is_empty() || value.len() > Self::MAX_VALUE_BYTES {
+            return Err(format!("Secret value must be 1-{} bytes", Self::MAX_VALUE_BYTES));
+        }
+
+        let now = time();
+        with_state_mut(|state| {
+            let vault = state.secrets.entry(owner.to_string()).or_default();
+            match vault.get_mut(label) {
+                Some(existing) => {
+                    existing.value = value;
+                    existing.updated_at = now;
+                }
+                None => {
+                    if vault.len() >= Self::MAX_SECRETS_PER_USER {
+                        return Err(format!("At most {} secrets may be stored", Self::MAX_SECRETS_PER_USER));
+                    }
+                    vault.insert(label.to_string(), StoredSecret {
+                        value,
+                        created_at: now,
+                        updated_at: now,
+                        last_accessed_at: None,
+                    });
+                }
+            }
+            Ok(())
+        })?;
+
+        AuditService::record(owner, AuditAction::SecretStored, label, "Secret value written".to_string());
+        Ok(())
+    }
+
+    pub fn delete_secret(owner: &str, label: &str) -> Result<(), String> {
+        with_state_mut(|state| {
+            state.secrets
+                .get_mut(owner)
+                .and_then(|vault| vault.remove(label))
+                .map(|_| ())
+                .ok_or_else(|| "Secret not found".to_string())
+        })?;
+        AuditService::record(owner, AuditAction::SecretDeleted, label, "Secret deleted".to_string());
+        Ok(())
+    }
+
+    /// Labels and timestamps only, sorted by label
+    pub fn list_secrets(owner: &str) -> Vec<SecretMetadata> {
+        with_state(|state| {
+            let mut secrets: Vec<SecretMetadata> = state.secrets
+                .get(owner)
+                .map(|vault| {
+                    vault.iter()
+                        .map(|(label, secret)| SecretMetadata {
+                            label: label.clone(),
+                            created_at: secret.created_at,
+                            updated_at: secret.updated_at,
+                            last_accessed_at: secret.last_accessed_at,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            secrets.sort_by(|a, b| a.label.cmp(&b.label));
+            secrets
+        })
+    }
+
+    /// Internal lookup for integrations; `purpose` is recorded in the audit log
+    pub fn resolve(owner: &str, label: &str, purpose: &str) -> Result<String, String> {
+        let now = time();
+        let value = with_state_mut(|state| {
+            state.secrets
+                .get_mut(owner)
+                .and_then(|vault| vault.get_mut(label))
+                .map(|secret| {
+                    secret.last_accessed_at = Some(now);
+                    secret.value.clone()
+                })
+                .ok_or_else(|| format!("Secret '{}' not found", label))
+        })?;
+        AuditService::record(owner, AuditAction::SecretAccessed, label, format!("Resolved for {}", purpose));
+        Ok(value)
+    }
+
+    /// Substitute every `{{secret:<label>}}` reference in an integration config
+    pub fn resolve_references(owner: &str, template: &str, purpose: &str) -> Result<String, String> {
+        let mut resolved = String::with_capacity(template.len());
+        for segment in Self::parse_references(template)? {
+            match segment {
+                Segment::Literal(text) => resolved.push_str(text),
+                Segment::Reference(label) => resolved.push_str(&Self::resolve(owner, label, purpose)?),
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn parse_references(template: &str) -> Result<Vec<Segment<'_>>, String> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find(REFERENCE_PREFIX) {
+            if start > 0 {
+                segments.push(Segment::Literal(&rest[..start]));
+            }
+            let after = &rest[start + REFERENCE_PREFIX.len()..];
+            let end = after
+                .find(REFERENCE_SUFFIX)
+                .ok_or_else(|| "Unterminated secret reference".to_string())?;
+            let label = after[..end].trim();
+            Self::validate_label(label)?;
+            segments.push(Segment::Reference(label));
+            rest = &after[end + REFERENCE_SUFFIX.len()..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest));
+        }
+        Ok(segments)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Literal(&'a str),
+    Reference(&'a str),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_references() {
+        let segments = SecretsService::parse_references("Bearer {{secret:api_key}} for {{secret:org.id}}").unwrap();
+        assert_eq!(segments, vec![
+            Segment::Literal("Bearer "),
+            Segment::Reference("api_key"),
+            Segment::Literal(" for "),
+            Segment::Reference("org.id"),
+        ]);
+        assert!(SecretsService::parse_references("{{secret:open").is_err());
+        assert!(SecretsService::parse_references("{{secret:bad label}}").is_err());
+    }
+}
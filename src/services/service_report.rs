@@ -0,0 +1,454 @@
+use crate::domain::OperationalMode;
+use crate::services::{with_state, with_state_mut, CoordinatorState};
+use crate::infra::Metrics;
+use candid::CandidType;
+use ic_cdk::api::management_canister::ecdsa::{
+    ecdsa_public_key, sign_with_ecdsa, EcdsaCurve, EcdsaKeyId, EcdsaPublicKeyArgument, SignWithEcdsaArgument,
+};
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Service reports for customers: request success rate, latency percentiles, coordinator
+/// incidents and quota utilization over a trailing period, signed with the coordinator's
+/// threshold ECDSA attestation key. A customer checks a report by Candid-encoding
+/// `report`, hashing it with SHA-256 and verifying `signature` against `public_key`.
+/// Principals report under the organization an admin assigned them to, or as their own
+/// organization. Signing costs cycles, so each organization gets one signed report a day.
+pub struct ServiceReportService;
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// One organization's requests for one day. Latencies go into power-of-two buckets:
+/// bucket `i` holds values in `[2^(i-1), 2^i)`, bucket 0 holds zero.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageBucket {
+    pub requests: u64,
+    pub succeeded: u64,
+    pub latency_histogram: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, CandidType)]
+pub enum IncidentKind {
+    /// Economics unreachable; quota decisions were made locally
+    EconomicsDegraded,
+    /// The coordinator was read-only or paused by an operator
+    RestrictedMode { mode: OperationalMode },
+    /// An agent's circuit breaker opened and it received no traffic
+    BreakerOpened { agent_id: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ServiceIncident {
+    pub kind: IncidentKind,
+    /// Principal whose agent the incident concerns; None for coordinator-wide incidents
+    #[serde(default)]
+    pub owner: Option<String>,
+    pub started_at: u64,
+    /// None while the incident is ongoing
+    pub ended_at: Option<u64>,
+}
+
+/// Quota use in the current quota month, summed over the organization's members
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct QuotaUtilization {
+    /// The organization principal's own tier, or the first member's when it has none
+    pub subscription_tier: String,
+    pub agents_created: u32,
+    pub monthly_agent_creations: u32,
+    pub weighted_units_used: u32,
+    pub monthly_weighted_units: u32,
+    pub tokens_used: u64,
+    pub inferences: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ServiceReport {
+    pub organization: String,
+    pub period_days: u32,
+    pub period_start: u64,
+    pub period_end: u64,
+    pub requests: u64,
+    pub succeeded: u64,
+    pub success_rate: f32,
+    /// Latencies of successful requests
+    pub median_latency_ms: u64,
+    pub p95_latency_ms: u64,
+    /// Coordinator incidents overlapping the period, oldest first
+    pub incidents: Vec<ServiceIncident>,
+    pub quota: Option<QuotaUtilization>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SignedServiceReport {
+    pub report: ServiceReport,
+    /// Hex SHA-256 of the Candid-encoded report; this is the signed message hash
+    pub report_sha256: String,
+    /// secp256k1 signature, 64 bytes r || s
+    pub signature: Vec<u8>,
+    /// SEC1 compressed public key of the coordinator's attestation key
+    pub public_key: Vec<u8>,
+    pub key_name: String,
+}
+
+/// The report signed for an organization today; `signed` is None while the signature is pending
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedServiceReport {
+    pub day: u64,
+    pub period_days: u32,
+    pub signed: Option<SignedServiceReport>,
+}
+
+impl ServiceReportService {
+    const RETENTION_DAYS: u64 = 90;
+    const MAX_INCIDENTS: usize = 2000;
+    pub const DEFAULT_ATTESTATION_KEY: &'static str = "key_1";
+
+    /// Record a request `principal` sent at `started_at` and that has just finished, so the
+    /// latency covers routing and dispatch end to end
+    pub fn record_request(principal: &str, success: bool, started_at: u64) {
+        let now = time();
+        let latency_ms = now.saturating_sub(started_at) / 1_000_000;
+        with_state_mut(|state| Self::record_request_in(state, principal, success, latency_ms, now / NANOS_PER_DAY));
+    }
+
+    /// The organization `principal` reports under
+    pub fn organization_of<'a>(state: &'a CoordinatorState, principal: &'a str) -> &'a str {
+        state.report_organizations.get(principal).map_or(principal, String::as_str)
+    }
+
+    /// Assign `principal` to `organization`, or back to reporting on its own with None
+    pub fn set_organization(principal: &str, organization: Option<String>) -> Result<(), String> {
+        with_state_mut(|state| {
+            match organization {
+                Some(organization) if organization == principal => {
+                    state.report_organizations.remove(principal);
+                }
+                Some(organization) => {
+                    // Organizations do not nest, so membership is one level deep
+                    if state.report_organizations.contains_key(&organization) {
+                        return Err(format!("{} is itself a member of another organization", organization));
+                    }
+                    if state.report_organizations.values().any(|org| org == principal) {
+                        return Err(format!("{} already has members of its own", principal));
+                    }
+                    state.report_organizations.insert(principal.to_string(), organization);
+                }
+                None => {
+                    state.report_organizations.remove(principal);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// The organization principal followed by every principal assigned to it
+    fn members_in<'a>(state: &'a CoordinatorState, organization: &'a str) -> Vec<&'a str> {
+        let mut members = vec![organization];
+        members.extend(state.report_organizations
+            .iter()
+            .filter(|(_, org)| org.as_str() == organization)
+            .map(|(member, _)| member.as_str()));
+        members
+    }
+
+    fn record_request_in(state: &mut CoordinatorState, principal: &str, success: bool, latency_ms: u64, day: u64) {
+        let organization = Self::organization_of(state, principal).to_string();
+        let buckets = state.service_usage.entry(organization).or_default();
+        let bucket = buckets.entry(day).or_default();
+        bucket.requests += 1;
+        if success {
+            bucket.succeeded += 1;
+            let index = Self::histogram_index(latency_ms);
+            if bucket.latency_histogram.len() <= index {
+                bucket.latency_histogram.resize(index + 1, 0);
+            }
+            bucket.latency_histogram[index] += 1;
+        }
+
+        let cutoff = day.saturating_sub(Self::RETENTION_DAYS);
+        buckets.retain(|d, _| *d >= cutoff);
+    }
+
+    fn histogram_index(value: u64) -> usize {
+        (u64::BITS - value.leading_zeros()) as usize
+    }
+
+    /// Value at quantile `q`, interpolated within the bucket it falls in
+    fn percentile(histogram: &[u64], q: f64) -> u64 {
+        let total: u64 = histogram.iter().sum();
+        if total == 0 {
+            return 0;
+        }
+        let rank = ((q * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in histogram.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            if seen + count >= rank {
+                if index == 0 {
+                    return 0;
+                }
+                let low = 1u64 << (index - 1);
+                let high = low.saturating_mul(2) - 1;
+                let fraction = (rank - seen) as f64 / *count as f64;
+                return low + ((high - low) as f64 * fraction) as u64;
+            }
+            seen += count;
+        }
+        0
+    }
+
+    /// Record an incident; one with an `owner` is reported only to that owner's organization
+    pub fn open_incident_in(state: &mut CoordinatorState, kind: IncidentKind, owner: Option<String>, started_at: u64, ended_at: Option<u64>) {
+        if state.service_incidents.len() >= Self::MAX_INCIDENTS {
+            state.service_incidents.pop_front();
+        }
+        state.service_incidents.push_back(ServiceIncident { kind, owner, started_at, ended_at });
+    }
+
+    /// End the most recent ongoing incident of this kind
+    pub fn close_incident_in(state: &mut CoordinatorState, kind: &IncidentKind, ended_at: u64) {
+        if let Some(incident) = state.service_incidents
+            .iter_mut()
+            .rev()
+            .find(|incident| incident.ended_at.is_none() && &incident.kind == kind)
+        {
+            incident.ended_at = Some(ended_at);
+        }
+    }
+
+    /// Close the window of the previous restricted mode, if any, and open one for the new mode
+    pub fn record_mode_change_in(state: &mut CoordinatorState, previous: OperationalMode, mode: OperationalMode, now: u64) {
+        if previous == mode {
+            return;
+        }
+        if previous != OperationalMode::Normal {
+            Self::close_incident_in(state, &IncidentKind::RestrictedMode { mode: previous }, now);
+        }
+        if mode != OperationalMode::Normal {
+            Self::open_incident_in(state, IncidentKind::RestrictedMode { mode }, None, now, None);
+        }
+    }
+
+    fn build_in(state: &CoordinatorState, organization: &str, period_days: u32, now: u64) -> ServiceReport {
+        let members = Self::members_in(state, organization);
+        let today = now / NANOS_PER_DAY;
+        let from_day = today.saturating_sub(period_days.saturating_sub(1) as u64);
+        let period_start = from_day * NANOS_PER_DAY;
+
+        let mut requests = 0;
+        let mut succeeded = 0;
+        let mut histogram: Vec<u64> = Vec::new();
+        if let Some(buckets) = state.service_usage.get(organization) {
+            for bucket in buckets.range(from_day..=today).map(|(_, b)| b) {
+                requests += bucket.requests;
+                succeeded += bucket.succeeded;
+                if histogram.len() < bucket.latency_histogram.len() {
+                    histogram.resize(bucket.latency_histogram.len(), 0);
+                }
+                for (total, count) in histogram.iter_mut().zip(&bucket.latency_histogram) {
+                    *total += count;
+                }
+            }
+        }
+
+        let incidents = state.service_incidents
+            .iter()
+            .filter(|incident| incident.started_at < now && incident.ended_at.is_none_or(|end| end >= period_start))
+            .filter(|incident| incident.owner.as_deref().is_none_or(|owner| members.contains(&owner)))
+            .cloned()
+            .collect();
+
+        let quota = members.iter().filter_map(|member| state.user_quotas.get(*member)).fold(None, |total: Option<QuotaUtilization>, quota| {
+            let mut total = total.unwrap_or_else(|| QuotaUtilization {
+                subscription_tier: quota.subscription_tier.clone(),
+                agents_created: 0,
+                monthly_agent_creations: 0,
+                weighted_units_used: 0,
+                monthly_weighted_units: 0,
+                tokens_used: 0,
+                inferences: 0,
+            });
+            total.agents_created += quota.current_usage.agents_created_this_month;
+            total.monthly_agent_creations += quota.limits.monthly_agent_creations;
+            total.weighted_units_used += quota.current_usage.weighted_units_used_this_month;
+            total.monthly_weighted_units += quota.limits.monthly_weighted_units;
+            total.tokens_used += quota.current_usage.tokens_used_this_month;
+            total.inferences += quota.current_usage.inferences_this_month;
+            Some(total)
+        });
+
+        ServiceReport {
+            organization: organization.to_string(),
+            period_days,
+            period_start,
+            period_end: now,
+            requests,
+            succeeded,
+            success_rate: if requests > 0 { succeeded as f32 / requests as f32 } else { 0.0 },
+            median_latency_ms: Self::percentile(&histogram, 0.5),
+            p95_latency_ms: Self::percentile(&histogram, 0.95),
+            incidents,
+            quota,
+        }
+    }
+
+    /// Claim today's signature for `organization`: Ok(Some) with the report already signed
+    /// today for this period, Ok(None) when the caller should sign one now
+    fn claim_signature_in(state: &mut CoordinatorState, organization: &str, period_days: u32, today: u64) -> Result<Option<SignedServiceReport>, String> {
+        if let Some(cached) = state.service_report_cache.get(organization).filter(|cached| cached.day == today) {
+            return match &cached.signed {
+                Some(signed) if cached.period_days == period_days => Ok(Some(signed.clone())),
+                Some(_) => Err(format!(
+                    "A {}-day report was already signed today; one signed report per organization per day",
+                    cached.period_days
+                )),
+                None => Err("A report for this organization is being signed; retry shortly".to_string()),
+            };
+        }
+        state.service_report_cache.insert(organization.to_string(), CachedServiceReport { day: today, period_days, signed: None });
+        Ok(None)
+    }
+
+    /// Report for the organization `caller` belongs to over the trailing `period_days`,
+    /// signed with the attestation key. Repeat requests the same day get the same report.
+    pub async fn signed_report(caller: &str, period_days: u32) -> Result<SignedServiceReport, String> {
+        let now = time();
+        let today = now / NANOS_PER_DAY;
+        let (organization, cached) = with_state_mut(|state| {
+            let organization = Self::organization_of(state, caller).to_string();
+            let cached = Self::claim_signature_in(state, &organization, period_days, today)?;
+            Ok::<_, String>((organization, cached))
+        })?;
+        if let Some(signed) = cached {
+            return Ok(signed);
+        }
+        let result = Self::sign(&organization, period_days, now).await;
+        with_state_mut(|state| match &result {
+            Ok(signed) => {
+                state.service_report_cache.insert(organization.clone(), CachedServiceReport { day: today, period_days, signed: Some(signed.clone()) });
+            }
+            // Release the claim so the organization can try again today
+            Err(_) => {
+                state.service_report_cache.remove(&organization);
+            }
+        });
+        result
+    }
+
+    async fn sign(organization: &str, period_days: u32, now: u64) -> Result<SignedServiceReport, String> {
+        let (report, key_name) = with_state(|state| {
+            let key_name = state.config.attestation_key
+                .clone()
+                .unwrap_or_else(|| Self::DEFAULT_ATTESTATION_KEY.to_string());
+            (Self::build_in(state, organization, period_days, now), key_name)
+        });
+
+        let encoded = candid::encode_one(&report).map_err(|e| format!("Failed to encode report: {}", e))?;
+        let digest = Sha256::digest(&encoded).to_vec();
+        let key_id = EcdsaKeyId { curve: EcdsaCurve::Secp256k1, name: key_name.clone() };
+
+        let signature = match sign_with_ecdsa(SignWithEcdsaArgument {
+            message_hash: digest.clone(),
+            derivation_path: vec![],
+            key_id: key_id.clone(),
+        }).await {
+            Ok((response,)) => {
+                Metrics::record_dependency_success("management.sign_with_ecdsa");
+                response.signature
+            }
+            Err((code, msg)) => {
+                let error = format!("{:?}: {}", code, msg);
+                Metrics::record_dependency_failure("management.sign_with_ecdsa", &error);
+                return Err(format!("Failed to sign report: {}", error));
+            }
+        };
+        let (public_key,) = ecdsa_public_key(EcdsaPublicKeyArgument {
+            canister_id: None,
+            derivation_path: vec![],
+            key_id,
+        }).await.map_err(|(code, msg)| format!("Failed to read attestation key: {:?}: {}", code, msg))?;
+
+        Metrics::increment_counter("service_reports_signed_total");
+        Ok(SignedServiceReport {
+            report,
+            report_sha256: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+            signature,
+            public_key: public_key.public_key,
+            key_name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_summarizes_period() {
+        let mut state = CoordinatorState::default();
+        let day = 100;
+        for latency in [100, 200, 300, 400, 5000] {
+            ServiceReportService::record_request_in(&mut state, "org", true, latency, day);
+        }
+        ServiceReportService::record_request_in(&mut state, "org", false, 0, day);
+        ServiceReportService::record_request_in(&mut state, "org", true, 50, day - 10);
+
+        ServiceReportService::open_incident_in(&mut state, IncidentKind::EconomicsDegraded, None, (day - 20) * NANOS_PER_DAY, Some((day - 19) * NANOS_PER_DAY));
+        ServiceReportService::open_incident_in(&mut state, IncidentKind::BreakerOpened { agent_id: "a".to_string() }, Some("org".to_string()), day * NANOS_PER_DAY, None);
+        ServiceReportService::open_incident_in(&mut state, IncidentKind::BreakerOpened { agent_id: "b".to_string() }, Some("other".to_string()), day * NANOS_PER_DAY, None);
+
+        let now = day * NANOS_PER_DAY + 1000;
+        let report = ServiceReportService::build_in(&state, "org", 7, now);
+        assert_eq!(report.requests, 6, "the request ten days back is outside the period");
+        assert_eq!(report.succeeded, 5);
+        assert!((report.success_rate - 5.0 / 6.0).abs() < 1e-6);
+        assert!((256..512).contains(&report.median_latency_ms));
+        assert!(report.p95_latency_ms >= 4096);
+        assert_eq!(report.incidents.len(), 1, "old incidents and other owners' breakers are left out");
+        assert!(report.quota.is_none());
+    }
+
+    #[test]
+    fn test_members_report_under_their_organization() {
+        let mut state = CoordinatorState::default();
+        ServiceReportService::record_request_in(&mut state, "member", true, 100, 5);
+        assert!(state.service_usage.contains_key("member"));
+
+        state.report_organizations.insert("member".to_string(), "org".to_string());
+        ServiceReportService::record_request_in(&mut state, "member", true, 100, 5);
+        ServiceReportService::record_request_in(&mut state, "org", false, 0, 5);
+        ServiceReportService::open_incident_in(&mut state, IncidentKind::BreakerOpened { agent_id: "a".to_string() }, Some("member".to_string()), 5 * NANOS_PER_DAY, None);
+
+        let report = ServiceReportService::build_in(&state, "org", 1, 5 * NANOS_PER_DAY + 1);
+        assert_eq!((report.requests, report.succeeded), (2, 1));
+        assert_eq!(report.incidents.len(), 1, "a member's breaker belongs to the organization");
+    }
+
+    #[test]
+    fn test_one_signature_per_organization_per_day() {
+        let mut state = CoordinatorState::default();
+        assert!(ServiceReportService::claim_signature_in(&mut state, "org", 30, 5).unwrap().is_none());
+        assert!(ServiceReportService::claim_signature_in(&mut state, "org", 30, 5).is_err(), "still signing");
+
+        let report = ServiceReportService::build_in(&state, "org", 30, 5 * NANOS_PER_DAY);
+        let signed = SignedServiceReport { report, report_sha256: "hash".to_string(), signature: vec![], public_key: vec![], key_name: "key_1".to_string() };
+        state.service_report_cache.get_mut("org").unwrap().signed = Some(signed);
+        assert_eq!(ServiceReportService::claim_signature_in(&mut state, "org", 30, 5).unwrap().unwrap().report_sha256, "hash");
+        assert!(ServiceReportService::claim_signature_in(&mut state, "org", 7, 5).is_err(), "one report a day");
+        assert!(ServiceReportService::claim_signature_in(&mut state, "org", 7, 6).unwrap().is_none(), "a new day");
+    }
+
+    #[test]
+    fn test_mode_changes_open_and_close_windows() {
+        let mut state = CoordinatorState::default();
+        ServiceReportService::record_mode_change_in(&mut state, OperationalMode::Normal, OperationalMode::ReadOnly, 10);
+        ServiceReportService::record_mode_change_in(&mut state, OperationalMode::ReadOnly, OperationalMode::Paused, 20);
+        ServiceReportService::record_mode_change_in(&mut state, OperationalMode::Paused, OperationalMode::Normal, 30);
+
+        let windows: Vec<_> = state.service_incidents.iter().map(|i| (i.started_at, i.ended_at)).collect();
+        assert_eq!(windows, vec![(10, Some(20)), (20, Some(30))]);
+    }
+}
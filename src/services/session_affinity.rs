@@ -0,0 +1,107 @@
+use crate::domain::*;
+use crate::services::{with_state, AgentLoadService, CoordinatorState};
+use serde::{Deserialize, Serialize};
+
+/// Sticky routing for conversations: requests carrying the same `session_key` from the
+/// same calling principal go back to the agent that served the last one, keeping its cache and
+/// context warm. The pin holds only while that agent is still a candidate, healthy and
+/// not saturated; otherwise the strategy picks afresh and the session moves with it.
+pub struct SessionAffinityService;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPin {
+    pub agent_id: String,
+    pub last_routed_at: u64,
+}
+
+impl SessionAffinityService {
+    /// Pins idle this long are forgotten
+    const IDLE_TTL_NS: u64 = 30 * 60 * 1_000_000_000;
+    const MAX_PINS: usize = 10_000;
+    const MIN_HEALTH: f32 = 0.5;
+
+    /// Session keys are scoped to the authenticated caller, never the self-declared
+    /// `RouteRequest::requester`, so one caller cannot steer another's sessions
+    fn key(requester: &str, session_key: &str) -> String {
+        format!("{}:{}", requester, session_key)
+    }
+
+    pub fn pinned_agent(requester: &str, session_key: &str, candidates: &[AgentRegistration], now: u64) -> Option<AgentRegistration> {
+        with_state(|state| Self::pinned_agent_in(state, requester, session_key, candidates, now))
+    }
+
+    fn pinned_agent_in(
+        state: &CoordinatorState,
+        requester: &str,
+        session_key: &str,
+        candidates: &[AgentRegistration],
+        now: u64,
+    ) -> Option<AgentRegistration> {
+        let pin = state.session_pins
+            .get(&Self::key(requester, session_key))
+            .filter(|pin| now.saturating_sub(pin.last_routed_at) < Self::IDLE_TTL_NS)?;
+        candidates
+            .iter()
+            .find(|agent| agent.agent_id == pin.agent_id)
+            .filter(|agent| agent.health_score >= Self::MIN_HEALTH)
+            .filter(|agent| AgentLoadService::current_load_in(state, &agent.agent_id, now) < 1.0)
+            .cloned()
+    }
+
+    /// Put the pinned agent first in the selection, keeping its size
+    pub fn put_first(selected: &mut Vec<AgentRegistration>, pinned: AgentRegistration) {
+        let len = selected.len();
+        selected.retain(|agent| agent.agent_id != pinned.agent_id);
+        selected.insert(0, pinned);
+        selected.truncate(len.max(1));
+    }
+
+    pub fn pin_in(state: &mut CoordinatorState, requester: &str, session_key: &str, agent_id: &str, now: u64) {
+        let pins = &mut state.session_pins;
+        let key = Self::key(requester, session_key);
+        if !pins.contains_key(&key) && pins.len() >= Self::MAX_PINS {
+            pins.retain(|_, pin| now.saturating_sub(pin.last_routed_at) < Self::IDLE_TTL_NS);
+            if pins.len() >= Self::MAX_PINS {
+                let oldest = pins.iter().min_by_key(|(_, pin)| pin.last_routed_at).map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    pins.remove(&oldest);
+                }
+            }
+        }
+        pins.insert(key, SessionPin { agent_id: agent_id.to_string(), last_routed_at: now });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(id: &str, health: f32) -> AgentRegistration {
+        AgentRegistration {
+            capabilities: vec!["chat".to_string()],
+            model_id: "llama".to_string(),
+            health_score: health,
+            ..AgentRegistration::test(id)
+        }
+    }
+
+    #[test]
+    fn test_session_sticks_while_agent_stays_healthy() {
+        let mut state = CoordinatorState::default();
+        let candidates = vec![agent("a", 1.0), agent("b", 0.9)];
+        SessionAffinityService::pin_in(&mut state, "user", "conv-1", "b", 10);
+
+        let pinned = SessionAffinityService::pinned_agent_in(&state, "user", "conv-1", &candidates, 20).unwrap();
+        assert_eq!(pinned.agent_id, "b");
+        assert!(SessionAffinityService::pinned_agent_in(&state, "other", "conv-1", &candidates, 20).is_none());
+        assert!(SessionAffinityService::pinned_agent_in(&state, "user", "conv-1", &candidates, 10 + SessionAffinityService::IDLE_TTL_NS).is_none());
+
+        let unhealthy = vec![agent("a", 1.0), agent("b", 0.2)];
+        assert!(SessionAffinityService::pinned_agent_in(&state, "user", "conv-1", &unhealthy, 20).is_none());
+
+        let mut selected = vec![agent("a", 1.0)];
+        SessionAffinityService::put_first(&mut selected, pinned);
+        let ids: Vec<&str> = selected.iter().map(|a| a.agent_id.as_str()).collect();
+        assert_eq!(ids, vec!["b"]);
+    }
+}
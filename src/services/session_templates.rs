@@ -0,0 +1,358 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, AvailabilityService, CircuitBreakerService, ConcurrencyService, CoordinationPreferenceService, IdGenerator, QuotaManager, RegistryService};
+use crate::services::autonomous_coord::{CoordinationSession, ResourceConstraints, SessionStatus};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Reusable coordination session layouts: which roles a session has, what each
+/// role must be able to do, the default resource constraints and how members
+/// may message each other.
+pub struct SessionTemplateService;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq, Eq, Hash)]
+pub enum SessionRole {
+    Coordinator,
+    Worker,
+    Reviewer,
+}
+
+/// Who may message whom inside a session
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq, Default)]
+pub enum MessageProtocol {
+    /// Any participant may message any other
+    #[default]
+    Mesh,
+    /// Members talk only to the coordinator; the coordinator may also broadcast
+    HubAndSpoke,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct TemplateRole {
+    pub role: SessionRole,
+    pub required_capabilities: Vec<String>,
+    /// Agents filling the role; the coordinator role takes exactly one
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SessionTemplateSpec {
+    pub name: String,
+    pub objective: String,
+    pub roles: Vec<TemplateRole>,
+    pub resource_constraints: ResourceConstraints,
+    pub protocol: MessageProtocol,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SessionTemplate {
+    pub template_id: String,
+    pub owner: String,
+    pub name: String,
+    pub objective: String,
+    pub roles: Vec<TemplateRole>,
+    pub resource_constraints: ResourceConstraints,
+    pub protocol: MessageProtocol,
+    pub created_at: u64,
+}
+
+/// Agents bound to one role of a session
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub struct RoleBinding {
+    pub role: SessionRole,
+    pub agent_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct TemplateSessionStart {
+    pub session_id: String,
+    pub template_id: String,
+    pub coordinator_agent: String,
+    /// Every role with its agents, including those selected automatically
+    pub role_bindings: Vec<RoleBinding>,
+}
+
+impl SessionTemplateService {
+    const MAX_TEMPLATES_PER_OWNER: usize = 20;
+    const MAX_AGENTS_PER_ROLE: u32 = 10;
+
+    pub fn create_template(owner: &str, spec: SessionTemplateSpec) -> Result<SessionTemplate, String> {
+        Self::validate(&spec)?;
+        let owned = with_state(|state| state.session_templates.values().filter(|t| t.owner == owner).count());
+        if owned >= Self::MAX_TEMPLATES_PER_OWNER {
+            return Err(format!("Template limit of {} reached", Self::MAX_TEMPLATES_PER_OWNER));
+        }
+        with_state_mut(|state| {
+            let template = SessionTemplate {
+                template_id: IdGenerator::next_in(state, "template", owner),
+                owner: owner.to_string(),
+                name: spec.name,
+                objective: spec.objective,
+                roles: spec.roles,
+                resource_constraints: spec.resource_constraints,
+                protocol: spec.protocol,
+                created_at: time(),
+            };
+            state.session_templates.insert(template.template_id.clone(), template.clone());
+            Ok(template)
+        })
+    }
+
+    fn validate(spec: &SessionTemplateSpec) -> Result<(), String> {
+        if spec.name.trim().is_empty() {
+            return Err("Template name cannot be empty".to_string());
+        }
+        for role in [SessionRole::Coordinator, SessionRole::Worker, SessionRole::Reviewer] {
+            if spec.roles.iter().filter(|r| r.role == role).count() > 1 {
+                return Err(format!("Role {:?} is declared more than once", role));
+            }
+        }
+        match spec.roles.iter().find(|r| r.role == SessionRole::Coordinator) {
+            Some(coordinator) if coordinator.count == 1 => {}
+            Some(_) => return Err("The coordinator role takes exactly one agent".to_string()),
+            None => return Err("A template needs a coordinator role".to_string()),
+        }
+        if let Some(role) = spec.roles.iter().find(|r| r.count == 0 || r.count > Self::MAX_AGENTS_PER_ROLE) {
+            return Err(format!("Role {:?} must take between 1 and {} agents", role.role, Self::MAX_AGENTS_PER_ROLE));
+        }
+        Ok(())
+    }
+
+    pub fn list_templates() -> Vec<SessionTemplate> {
+        let mut templates: Vec<SessionTemplate> = with_state(|state| state.session_templates.values().cloned().collect());
+        templates.sort_by(|a, b| a.template_id.cmp(&b.template_id));
+        templates
+    }
+
+    /// Instantiate `template_id`: roles left unbound (or bound short) are filled with the
+    /// healthiest of the caller's agents that have the role's capabilities
+    pub fn start_session(
+        caller: &str,
+        is_admin: bool,
+        template_id: &str,
+        role_bindings: Vec<RoleBinding>,
+    ) -> Result<TemplateSessionStart, String> {
+        let template = with_state(|state| state.session_templates.get(template_id).cloned())
+            .ok_or_else(|| "Session template not found".to_string())?;
+
+        let mut requested: HashMap<SessionRole, Vec<String>> = HashMap::new();
+        for binding in role_bindings {
+            if !template.roles.iter().any(|r| r.role == binding.role) {
+                return Err(format!("Template has no {:?} role", binding.role));
+            }
+            requested.entry(binding.role).or_default().extend(binding.agent_ids);
+        }
+        for agent_id in requested.values().flatten() {
+            let agent = RegistryService::get_agent(agent_id)?;
+            if !is_admin && agent.agent_principal != caller {
+                return Err(format!("Caller does not control agent {}", agent_id));
+            }
+        }
+
+        let now = time();
+        let candidates: Vec<AgentRegistration> = RegistryService::get_healthy_agents(0.1)
+            .into_iter()
+            .filter(|agent| is_admin || agent.agent_principal == caller)
+            .filter(|agent| !CircuitBreakerService::is_open(&agent.agent_id))
+            .filter(|agent| !AvailabilityService::is_in_maintenance(&agent.agent_id, now))
+            .collect();
+        let bindings = Self::bind_roles(&template, requested, candidates)?;
+        let participants: Vec<String> = bindings.iter().flat_map(|b| b.agent_ids.clone()).collect();
+        ConcurrencyService::check_activation(&participants)?;
+        CoordinationPreferenceService::check_collaborations(&participants)?;
+
+        let coordinator_agent = bindings
+            .iter()
+            .find(|b| b.role == SessionRole::Coordinator)
+            .and_then(|b| b.agent_ids.first().cloned())
+            .ok_or_else(|| "No coordinator bound".to_string())?;
+        QuotaManager::admit_session(&coordinator_agent)?;
+        let session = CoordinationSession {
+            session_id: IdGenerator::next("coord", &coordinator_agent),
+            participants,
+            coordinator_agent: coordinator_agent.clone(),
+            objective: template.objective.clone(),
+            status: SessionStatus::Active,
+            created_at: now,
+            last_activity: now,
+            messages: Vec::new(),
+            resource_constraints: template.resource_constraints.clone(),
+            template_id: Some(template.template_id.clone()),
+            role_bindings: bindings.clone(),
+            protocol: template.protocol,
+        };
+        let start = TemplateSessionStart {
+            session_id: session.session_id.clone(),
+            template_id: template.template_id,
+            coordinator_agent,
+            role_bindings: bindings,
+        };
+        with_state_mut(|state| {
+            state.workload_index.add_session(&session);
+            state.coordination_sessions
+                .get_or_insert_with(HashMap::new)
+                .insert(session.session_id.clone(), session);
+        });
+        Metrics::increment_counter("template_sessions_started");
+        Ok(start)
+    }
+
+    /// Resolve each role to its agents; an agent may hold only one role in a session
+    fn bind_roles(
+        template: &SessionTemplate,
+        mut requested: HashMap<SessionRole, Vec<String>>,
+        mut candidates: Vec<AgentRegistration>,
+    ) -> Result<Vec<RoleBinding>, String> {
+        let mut taken: Vec<String> = Vec::new();
+        for agent_id in requested.values().flatten() {
+            if taken.contains(agent_id) {
+                return Err(format!("Agent {} is bound more than once", agent_id));
+            }
+            taken.push(agent_id.clone());
+        }
+        candidates.sort_by(|a, b| {
+            b.health_score.partial_cmp(&a.health_score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.agent_id.cmp(&b.agent_id))
+        });
+
+        let mut bindings = Vec::new();
+        for role in &template.roles {
+            let mut agent_ids = requested.remove(&role.role).unwrap_or_default();
+            if agent_ids.len() > role.count as usize {
+                return Err(format!("Role {:?} takes at most {} agents", role.role, role.count));
+            }
+            for candidate in &candidates {
+                if agent_ids.len() >= role.count as usize {
+                    break;
+                }
+                let capable = role.required_capabilities.iter().all(|cap| RegistryService::has_capability(&candidate.capabilities, cap));
+                if capable && !taken.contains(&candidate.agent_id) {
+                    taken.push(candidate.agent_id.clone());
+                    agent_ids.push(candidate.agent_id.clone());
+                }
+            }
+            if agent_ids.len() < role.count as usize {
+                return Err(format!(
+                    "Not enough agents for role {:?}: need {}, found {}",
+                    role.role, role.count, agent_ids.len()
+                ));
+            }
+            bindings.push(RoleBinding { role: role.role, agent_ids });
+        }
+        Ok(bindings)
+    }
+
+    /// Whether `session`'s protocol lets `from_agent` send to `to_agent` (`None` broadcasts)
+    pub fn permits(session: &CoordinationSession, from_agent: &str, to_agent: Option<&str>) -> Result<(), String> {
+        // Only members may speak, under any protocol, and only to other members
+        for agent in std::iter::once(from_agent).chain(to_agent) {
+            if !session.is_member(agent) {
+                return Err(format!("Agent {} is not a participant of session {}", agent, session.session_id));
+            }
+        }
+        match session.protocol {
+            MessageProtocol::Mesh => Ok(()),
+            MessageProtocol::HubAndSpoke => {
+                if from_agent == session.coordinator_agent || to_agent == Some(session.coordinator_agent.as_str()) {
+                    Ok(())
+                } else {
+                    Err("Session protocol routes all member messages through the coordinator".to_string())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(agent_id: &str, capabilities: &[&str], health_score: f32) -> AgentRegistration {
+        AgentRegistration {
+            canister_id: format!("{}-canister", agent_id),
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            model_id: "llama".to_string(),
+            health_score,
+            ..AgentRegistration::test(agent_id)
+        }
+    }
+
+    fn template() -> SessionTemplate {
+        let role = |role, capability: &str, count| TemplateRole {
+            role,
+            required_capabilities: vec![capability.to_string()],
+            count,
+        };
+        SessionTemplate {
+            template_id: "template_1".to_string(),
+            owner: "owner".to_string(),
+            name: "review loop".to_string(),
+            objective: "ship it".to_string(),
+            roles: vec![
+                role(SessionRole::Coordinator, "planning", 1),
+                role(SessionRole::Worker, "coding", 2),
+                role(SessionRole::Reviewer, "code_review", 1),
+            ],
+            resource_constraints: ResourceConstraints {
+                max_execution_time_ms: 1000,
+                max_memory_usage_bytes: 1024,
+                max_concurrent_tasks: 2,
+                allowed_capabilities: None,
+            },
+            protocol: MessageProtocol::HubAndSpoke,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_bind_roles_fills_unbound_roles_by_health() {
+        let candidates = vec![
+            agent("lead", &["planning", "coding"], 0.9),
+            agent("dev_a", &["coding"], 0.5),
+            agent("dev_b", &["coding"], 0.8),
+            agent("dev_c", &["coding"], 0.7),
+            agent("rev", &["code_review"], 0.6),
+        ];
+        let mut requested = HashMap::new();
+        requested.insert(SessionRole::Worker, vec!["dev_a".to_string()]);
+        let bindings = SessionTemplateService::bind_roles(&template(), requested, candidates.clone()).unwrap();
+        assert_eq!(bindings[0].agent_ids, vec!["lead".to_string()]);
+        // The coordinator is not reused as a worker
+        assert_eq!(bindings[1].agent_ids, vec!["dev_a".to_string(), "dev_b".to_string()]);
+        assert_eq!(bindings[2].agent_ids, vec!["rev".to_string()]);
+
+        let short = candidates.into_iter().filter(|a| a.agent_id != "rev").collect();
+        assert!(SessionTemplateService::bind_roles(&template(), HashMap::new(), short).is_err());
+    }
+
+    #[test]
+    fn test_hub_and_spoke_routes_through_coordinator() {
+        let session = CoordinationSession {
+            session_id: "coord_1".to_string(),
+            participants: vec!["lead".to_string(), "dev".to_string(), "rev".to_string()],
+            coordinator_agent: "lead".to_string(),
+            objective: String::new(),
+            status: SessionStatus::Active,
+            created_at: 0,
+            last_activity: 0,
+            messages: Vec::new(),
+            resource_constraints: template().resource_constraints,
+            template_id: Some("template_1".to_string()),
+            role_bindings: Vec::new(),
+            protocol: MessageProtocol::HubAndSpoke,
+        };
+        assert!(SessionTemplateService::permits(&session, "lead", None).is_ok());
+        assert!(SessionTemplateService::permits(&session, "dev", Some("lead")).is_ok());
+        assert!(SessionTemplateService::permits(&session, "dev", Some("rev")).is_err());
+        assert!(SessionTemplateService::permits(&session, "dev", None).is_err());
+        assert!(SessionTemplateService::permits(&session, "outsider", Some("lead")).is_err());
+
+        let mesh = CoordinationSession { protocol: MessageProtocol::Mesh, ..session };
+        assert!(SessionTemplateService::permits(&mesh, "dev", Some("rev")).is_ok());
+        assert!(SessionTemplateService::permits(&mesh, "outsider", None).is_err());
+        assert!(SessionTemplateService::permits(&mesh, "dev", Some("outsider")).is_err());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::domain::{AgentRegistration, AgentRegistryDelta};
+use crate::services::{with_state, with_state_mut, CoordinatorState};
+use crate::services::notifications::Notification;
+use crate::services::preferences::UserPreferences;
+use crate::services::quota_manager::{UsageSnapshot, UserQuota};
+use crate::infra::{Log, Metrics};
+use ic_cdk::api::{call, time};
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Spreads users over several coordinator canisters. Each user is served by one shard,
+/// picked by rendezvous hashing of their principal over the configured shards, so adding
+/// or removing a shard only moves the users that hash to it. Requests from users of
+/// another shard are refused with that shard's canister id. After the shard set changes,
+/// `rebalance` pushes per-user state (quota, usage history, preferences, activity,
+/// notifications) to the users' new shards. Agents stay registered with the shard they
+/// registered at; every shard pulls its peers' registry deltas so routing sees the
+/// whole agent pool.
+pub struct ShardService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ShardInfo {
+    pub shard_id: u32,
+    pub canister_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ShardConfig {
+    /// Every shard, this one included
+    pub shards: Vec<ShardInfo>,
+    pub local_shard_id: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ShardAssignment {
+    pub shard_id: u32,
+    pub canister_id: String,
+    /// This canister serves the principal
+    pub local: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShardingState {
+    /// None when sharding is off
+    pub config: Option<ShardConfig>,
+    /// Registry mirrors of the other shards, keyed by shard id
+    pub peers: BTreeMap<u32, PeerRegistry>,
+    pub last_sync_at: u64,
+    #[serde(skip)]
+    pub sync_in_flight: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PeerRegistry {
+    /// `get_agents_delta` cursor
+    pub version: u64,
+    pub agents: HashMap<String, AgentRegistration>,
+    pub synced_at: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct PeerShardStatus {
+    pub shard_id: u32,
+    pub version: u64,
+    pub agents: u32,
+    pub synced_at: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ShardStatus {
+    pub config: Option<ShardConfig>,
+    pub peers: Vec<PeerShardStatus>,
+}
+
+/// One user's state as moved between shards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserShardRecord {
+    pub principal: String,
+    pub quota: Option<UserQuota>,
+    pub usage_history: Vec<UsageSnapshot>,
+    pub preferences: Option<UserPreferences>,
+    pub last_activity: Option<u64>,
+    pub notifications: Vec<Notification>,
+}
+
+impl ShardService {
+    const MAX_SHARDS: usize = 64;
+    const MAX_REBALANCE_BATCH: u32 = 500;
+    const SYNC_INTERVAL_NS: u64 = 30 * 1_000_000_000;
+
+    pub fn configure(config: Option<ShardConfig>) -> Result<(), String> {
+        if let Some(config) = &config {
+            Self::validate(config)?;
+        }
+        with_state_mut(|state| {
+            let shard_ids: BTreeSet<u32> = config.iter().flat_map(|c| c.shards.iter().map(|s| s.shard_id)).collect();
+            state.sharding.peers.retain(|shard_id, _| shard_ids.contains(shard_id));
+            state.sharding.config = config;
+        });
+        Ok(())
+    }
+
+    fn validate(config: &ShardConfig) -> Result<(), String> {
+        if config.shards.is_empty() || config.shards.len() > Self::MAX_SHARDS {
+            return Err(format!("Between 1 and {} shards are required", Self::MAX_SHARDS));
+        }
+        let mut shard_ids = BTreeSet::new();
+        let mut canisters = BTreeSet::new();
+        for shard in &config.shards {
+            Principal::from_text(&shard.canister_id).map_err(|e| format!("Invalid canister id for shard {}: {}", shard.shard_id, e))?;
+            if !shard_ids.insert(shard.shard_id) || !canisters.insert(shard.canister_id.as_str()) {
+                return Err(format!("Shard {} is listed twice", shard.shard_id));
+            }
+        }
+        if !shard_ids.contains(&config.local_shard_id) {
+            return Err(format!("Local shard {} is not among the shards", config.local_shard_id));
+        }
+        Ok(())
+    }
+
+    /// The shard with the highest hash of (principal, shard id)
+    fn shard_for<'a>(config: &'a ShardConfig, principal: &str) -> &'a ShardInfo {
+        config.shards
+            .iter()
+            .max_by_key(|shard| {
+                let digest = Sha256::new()
+                    .chain_update(principal.as_bytes())
+                    .chain_update(shard.shard_id.to_be_bytes())
+                    .finalize();
+                (u64::from_be_bytes(digest[..8].try_into().expect("8 bytes")), shard.shard_id)
+            })
+            .expect("validated config has shards")
+    }
+
+    fn assignment_in(state: &CoordinatorState, principal: &str) -> Option<ShardAssignment> {
+        let config = state.sharding.config.as_ref()?;
+        let shard = Self::shard_for(config, principal);
+        Some(ShardAssignment {
+            shard_id: shard.shard_id,
+            canister_id: shard.canister_id.clone(),
+            local: shard.shard_id == config.local_shard_id,
+        })
+    }
+
+    pub fn get_user_shard(principal: &str) -> Result<ShardAssignment, String> {
+        with_state(|state| Self::assignment_in(state, principal)).ok_or_else(|| "Sharding is not enabled".to_string())
+    }
+
+    /// Refuse users served by another shard, naming the canister to use instead
+    pub fn ensure_local(principal: &str) -> Result<(), String> {
+        match with_state(|state| Self::assignment_in(state, principal)) {
+            Some(assignment) if !assignment.local => {
+                Metrics::increment_counter("shard_redirects_total");
+                Err(format!("Principal {} is served by shard {} (canister {})", principal, assignment.shard_id, assignment.canister_id))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_peer(canister_id: &str) -> bool {
+        with_state(|state| {
+            state.sharding.config.as_ref().is_some_and(|config| {
+                config.shards.iter().any(|shard| shard.canister_id == canister_id && shard.shard_id != config.local_shard_id)
+            })
+        })
+    }
+
+    pub fn status() -> ShardStatus {
+        with_state(|state| ShardStatus {
+            config: state.sharding.config.clone(),
+            peers: state.sharding.peers
+                .iter()
+                .map(|(shard_id, peer)| PeerShardStatus {
+                    shard_id: *shard_id,
+                    version: peer.version,
+                    agents: peer.agents.len() as u32,
+                    synced_at: peer.synced_at,
+                    last_error: peer.last_error.clone(),
+                })
+                .collect(),
+        })
+    }
+
+    // Rebalancing
+
+    /// Push up to `limit` users that now belong to other shards to those shards; returns how many moved
+    pub async fn rebalance(limit: u32) -> Result<u32, String> {
+        let limit = limit.min(Self::MAX_REBALANCE_BATCH) as usize;
+        let outgoing = with_state(|state| Self::outgoing_in(state, limit))?;
+        let mut moved = 0;
+        for (canister_id, records) in outgoing {
+            let payload = serde_cbor::to_vec(&records).map_err(|e| format!("Failed to encode shard export: {}", e))?;
+            let target = Principal::from_text(&canister_id).map_err(|e| e.to_string())?;
+            let outcome = call::call::<_, (Result<u32, String>,)>(target, "import_shard_users", (payload,)).await;
+            match outcome {
+                Ok((Ok(_),)) => {
+                    let principals: Vec<String> = records.into_iter().map(|record| record.principal).collect();
+                    with_state_mut(|state| Self::release_in(state, &principals));
+                    moved += principals.len() as u32;
+                    Metrics::record_dependency_success("shard.import_shard_users");
+                }
+                Ok((Err(e),)) => Log::warn("sharding", format!("Shard {} refused {} users: {}", canister_id, records.len(), e)),
+                Err((code, msg)) => {
+                    Metrics::record_dependency_failure("shard.import_shard_users", &format!("{:?}: {}", code, msg));
+                    Log::warn("sharding", format!("Failed to move {} users to {}: {:?}: {}", records.len(), canister_id, code, msg));
+                }
+            }
+        }
+        Metrics::add_to_counter("shard_users_moved_total", moved as u64);
+        Ok(moved)
+    }
+
+    /// Users with state here whose shard is elsewhere, grouped by the canister they move to
+    fn outgoing_in(state: &CoordinatorState, limit: usize) -> Result<BTreeMap<String, Vec<UserShardRecord>>, String> {
+        if state.sharding.config.is_none() {
+            return Err("Sharding is not enabled".to_string());
+        }
+        let principals: BTreeSet<&String> = state.user_quotas.keys()
+            .chain(state.usage_history.keys())
+            .chain(state.user_preferences.keys())
+            .chain(state.user_activity.keys())
+            .chain(state.notifications.keys())
+            .collect();
+        let mut outgoing: BTreeMap<String, Vec<UserShardRecord>> = BTreeMap::new();
+        for principal in principals {
+            let Some(assignment) = Self::assignment_in(state, principal).filter(|a| !a.local) else { continue };
+            outgoing.entry(assignment.canister_id).or_default().push(UserShardRecord {
+                principal: principal.clone(),
+                quota: state.user_quotas.get(principal).cloned(),
+                usage_history: state.usage_history.get(principal).cloned().unwrap_or_default(),
+                preferences: state.user_preferences.get(principal).cloned(),
+                last_activity: state.user_activity.get(principal).copied(),
+                notifications: state.notifications.get(principal).cloned().unwrap_or_default(),
+            });
+            if outgoing.values().map(Vec::len).sum::<usize>() >= limit {
+                break;
+            }
+        }
+        Ok(outgoing)
+    }
+
+    fn release_in(state: &mut CoordinatorState, principals: &[String]) {
+        for principal in principals {
+            state.user_quotas.remove(principal);
+            state.usage_history.remove(principal);
+            state.user_preferences.remove(principal);
+            state.user_activity.remove(principal);
+            state.notifications.remove(principal);
+        }
+    }
+
+    /// Take in users moved here by a peer; records for users of other shards are refused
+    pub fn import(payload: &[u8]) -> Result<u32, String> {
+        let records: Vec<UserShardRecord> = serde_cbor::from_slice(payload).map_err(|e| format!("Failed to decode shard export: {}", e))?;
+        with_state_mut(|state| Self::import_in(state, records))
+    }
+
+    fn import_in(state: &mut CoordinatorState, records: Vec<UserShardRecord>) -> Result<u32, String> {
+        if let Some(record) = records.iter().find(|r| !Self::assignment_in(state, &r.principal).is_some_and(|a| a.local)) {
+            return Err(format!("Principal {} is not served by this shard", record.principal));
+        }
+        let count = records.len() as u32;
+        for record in records {
+            let principal = record.principal;
+            if let Some(quota) = record.quota {
+                state.user_quotas.insert(principal.clone(), quota);
+            }
+            if !record.usage_history.is_empty() {
+                state.usage_history.insert(principal.clone(), record.usage_history);
+            }
+            if let Some(preferences) = record.preferences {
+                state.user_preferences.insert(principal.clone(), preferences);
+            }
+            if let Some(last_activity) = record.last_activity {
+                state.user_activity.insert(principal.clone(), last_activity);
+            }
+            if !record.notifications.is_empty() {
+                state.notifications.insert(principal, record.notifications);
+            }
+        }
+        Ok(count)
+    }
+
+    // Registry digests
+
+    /// Called from the maintenance timer; pulls each peer's registry changes every `SYNC_INTERVAL_NS`
+    pub fn tick() {
+        let now = time();
+        let peers: Vec<(u32, String, u64)> = with_state_mut(|state| {
+            let sharding = &mut state.sharding;
+            let Some(config) = &sharding.config else { return Vec::new() };
+            if sharding.sync_in_flight || now.saturating_sub(sharding.last_sync_at) < Self::SYNC_INTERVAL_NS {
+                return Vec::new();
+            }
+            let peers: Vec<(u32, String, u64)> = config.shards
+                .iter()
+                .filter(|shard| shard.shard_id != config.local_shard_id)
+                .map(|shard| (shard.shard_id, shard.canister_id.clone(), sharding.peers.get(&shard.shard_id).map_or(0, |p| p.version)))
+                .collect();
+            sharding.sync_in_flight = !peers.is_empty();
+            sharding.last_sync_at = now;
+            peers
+        });
+        if peers.is_empty() {
+            return;
+        }
+        ic_cdk::spawn(async move {
+            for (shard_id, canister_id, since_version) in peers {
+                let outcome = match Principal::from_text(&canister_id) {
+                    Ok(peer) => match call::call::<_, (Result<AgentRegistryDelta, String>,)>(peer, "get_agents_delta", (since_version,)).await {
+                        Ok((delta,)) => delta,
+                        Err((code, msg)) => Err(format!("{:?}: {}", code, msg)),
+                    },
+                    Err(e) => Err(e.to_string()),
+                };
+                let now = time();
+                with_state_mut(|state| match outcome {
+                    Ok(delta) => Self::apply_delta_in(state, shard_id, delta, now),
+                    Err(e) => state.sharding.peers.entry(shard_id).or_default().last_error = Some(e),
+                });
+            }
+            with_state_mut(|state| state.sharding.sync_in_flight = false);
+        });
+    }
+
+    fn apply_delta_in(state: &mut CoordinatorState, shard_id: u32, delta: AgentRegistryDelta, now: u64) {
+        let peer = state.sharding.peers.entry(shard_id).or_default();
+        if delta.full_resync {
+            peer.agents.clear();
+        }
+        for agent in delta.created.into_iter().chain(delta.updated) {
+            peer.agents.insert(agent.agent_id.clone(), agent);
+        }
+        for agent_id in &delta.removed {
+            peer.agents.remove(agent_id);
+        }
+        peer.version = delta.version;
+        peer.synced_at = now;
+        peer.last_error = None;
+    }
+
+    /// Agents registered at other shards that offer any of `required` and accept work
+    pub fn peer_capable_agents(required: &[String], min_health: f32) -> Vec<AgentRegistration> {
+        with_state(|state| Self::peer_capable_agents_in(state, required, min_health))
+    }
+
+    fn peer_capable_agents_in(state: &CoordinatorState, required: &[String], min_health: f32) -> Vec<AgentRegistration> {
+        let offers = |agent: &AgentRegistration| {
+            agent.capabilities.iter().any(|offered| {
+                required.contains(offered) || state.capability_taxonomy.ancestors(offered).iter().any(|a| required.contains(a))
+            })
+        };
+        let mut agents: Vec<AgentRegistration> = state.sharding.peers
+            .values()
+            .flat_map(|peer| peer.agents.values())
+            .filter(|agent| !state.agents.contains_key(&agent.agent_id))
+            .filter(|agent| agent.health_score >= min_health && offers(agent))
+            .cloned()
+            .collect();
+        agents.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        agents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(shards: u32, local_shard_id: u32) -> ShardConfig {
+        let canisters = ["rrkah-fqaaa-aaaaa-aaaaq-cai", "ryjl3-tyaaa-aaaaa-aaaba-cai", "r7inp-6aaaa-aaaaa-aaabq-cai"];
+        ShardConfig {
+            shards: (0..shards).map(|i| ShardInfo { shard_id: i, canister_id: canisters[i as usize].to_string() }).collect(),
+            local_shard_id,
+        }
+    }
+
+    fn agent(agent_id: &str, capability: &str) -> AgentRegistration {
+        AgentRegistration { capabilities: vec![capability.to_string()], ..AgentRegistration::test(agent_id) }
+    }
+
+    #[test]
+    fn test_users_hash_to_shards_and_move_only_to_added_shards() {
+        assert!(ShardService::validate(&ShardConfig { local_shard_id: 9, ..config(2, 0) }).is_err());
+        let two = config(2, 0);
+        let three = config(3, 0);
+        let users: Vec<String> = (0..200).map(|i| format!("user-{}", i)).collect();
+        let on = |config: &ShardConfig, user: &str| ShardService::shard_for(config, user).shard_id;
+        assert!(users.iter().any(|u| on(&two, u) == 0) && users.iter().any(|u| on(&two, u) == 1));
+        // Adding a shard only moves users onto it
+        assert!(users.iter().all(|u| on(&two, u) == on(&three, u) || on(&three, u) == 2));
+
+        let mut state = CoordinatorState::default();
+        state.sharding.config = Some(two.clone());
+        for user in &users {
+            state.user_activity.insert(user.clone(), 1);
+        }
+        state.sharding.config = Some(three);
+        let outgoing = ShardService::outgoing_in(&state, 1_000).unwrap();
+        let moving: Vec<String> = outgoing.values().flatten().map(|record| record.principal.clone()).collect();
+        assert!(moving.iter().all(|u| on(&two, u) == 1 || on(&config(3, 0), u) == 2));
+
+        // The receiving shard accepts only its own users
+        let mut receiver = CoordinatorState::default();
+        receiver.sharding.config = Some(config(3, 2));
+        let to_shard_2 = outgoing["r7inp-6aaaa-aaaaa-aaabq-cai"].clone();
+        assert_eq!(ShardService::import_in(&mut receiver, to_shard_2.clone()).unwrap(), to_shard_2.len() as u32);
+        assert!(ShardService::import_in(&mut receiver, outgoing["ryjl3-tyaaa-aaaaa-aaaba-cai"].clone()).is_err());
+        ShardService::release_in(&mut state, &moving);
+        assert_eq!(state.user_activity.len(), users.len() - moving.len());
+    }
+
+    #[test]
+    fn test_peer_registry_deltas_feed_routing_candidates() {
+        let mut state = CoordinatorState::default();
+        let delta = AgentRegistryDelta { version: 3, full_resync: true, created: vec![agent("p1", "coding"), agent("p2", "math")], updated: vec![], removed: vec![] };
+        ShardService::apply_delta_in(&mut state, 1, delta, 10);
+        assert_eq!(ShardService::peer_capable_agents_in(&state, &["coding".to_string()], 0.1).len(), 1);
+
+        let delta = AgentRegistryDelta { version: 4, full_resync: false, created: vec![], updated: vec![], removed: vec!["p1".to_string()] };
+        ShardService::apply_delta_in(&mut state, 1, delta, 20);
+        assert!(ShardService::peer_capable_agents_in(&state, &["coding".to_string()], 0.1).is_empty());
+        assert_eq!(state.sharding.peers[&1].version, 4);
+    }
+}
@@ -0,0 +1,322 @@
+use crate::services::{with_state, with_state_mut, IdGenerator, QuotaManager, SecretsService};
+use crate::infra::Metrics;
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs, TransformContext,
+};
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+
+/// Streaming export of routing and quota events to an Enterprise organization's SIEM.
+/// Events queue per organization and are POSTed in JSON batches over HTTPS outcalls,
+/// signed with HMAC-SHA256 under a key from the organization's secret vault. A batch
+/// leaves the queue only once the collector answers 2xx; failures back off
+/// exponentially and resend, so delivery is at least once and collectors should
+/// deduplicate on `event_id`.
+pub struct SiemExportService;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, CandidType)]
+pub enum SiemEventCategory {
+    /// Routing decisions and routing failures
+    Routing,
+    /// Quota consumption, denials, refunds and safety limit hits
+    Quota,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SiemExportConfig {
+    /// Collector endpoint; must be HTTPS
+    pub endpoint_url: String,
+    /// Label of the vault secret used as the signing key
+    pub signing_secret_label: String,
+    pub categories: Vec<SiemEventCategory>,
+    /// Paused streams keep their queue but collect and send nothing
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SiemEvent {
+    pub event_id: String,
+    pub category: SiemEventCategory,
+    pub action: String,
+    pub principal: String,
+    pub subject: String,
+    pub detail: String,
+    pub occurred_at: u64,
+}
+
+/// One organization's export queue and delivery state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiemStream {
+    pub config: SiemExportConfig,
+    pub pending: VecDeque<SiemEvent>,
+    pub delivered_events: u64,
+    /// Oldest events dropped because the queue was full
+    pub dropped_events: u64,
+    pub consecutive_failures: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    pub last_delivered_at: Option<u64>,
+    /// A batch is on its way; not kept across upgrades, where the call is abandoned
+    #[serde(skip)]
+    pub in_flight: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SiemExportStatus {
+    pub config: SiemExportConfig,
+    pub pending_events: u64,
+    pub delivered_events: u64,
+    pub dropped_events: u64,
+    pub consecutive_failures: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    pub last_delivered_at: Option<u64>,
+}
+
+/// Body of one POST
+#[derive(Serialize)]
+struct SiemBatch<'a> {
+    organization: &'a str,
+    batch_id: &'a str,
+    events: &'a [SiemEvent],
+}
+
+impl SiemExportService {
+    pub const TRANSFORM_METHOD: &'static str = "siem_transform";
+    const REQUIRED_TIER: &'static str = "Enterprise";
+    const MAX_PENDING: usize = 10_000;
+    const BATCH_SIZE: usize = 100;
+    /// Pause after a delivered batch so the next one can fill up
+    const FLUSH_INTERVAL_NS: u64 = 10 * 1_000_000_000;
+    const BASE_BACKOFF_NS: u64 = 30 * 1_000_000_000;
+    const MAX_BACKOFF_NS: u64 = 3600 * 1_000_000_000;
+    const MAX_RESPONSE_BYTES: u64 = 16 * 1024;
+    const MAX_URL_LEN: usize = 2048;
+
+    pub fn configure(principal: &str, config: SiemExportConfig) -> Result<SiemExportStatus, String> {
+        let tier = QuotaManager::get_user_quota(principal).map(|quota| quota.subscription_tier).unwrap_or_default();
+        if tier != Self::REQUIRED_TIER {
+            return Err(format!("SIEM export requires the {} tier", Self::REQUIRED_TIER));
+        }
+        if !config.endpoint_url.starts_with("https://") || config.endpoint_url.len() > Self::MAX_URL_LEN {
+            return Err(format!("endpoint_url must be an https:// URL of at most {} characters", Self::MAX_URL_LEN));
+        }
+        if config.categories.is_empty() {
+            return Err("Select at least one event category".to_string());
+        }
+        with_state_mut(|state| {
+            let has_key = state.secrets.get(principal).is_some_and(|vault| vault.contains_key(&config.signing_secret_label));
+            if !has_key {
+                return Err(format!("Secret '{}' not found; store the signing key with put_secret first", config.signing_secret_label));
+            }
+            match state.siem_streams.get_mut(principal) {
+                Some(stream) => stream.config = config,
+                None => {
+                    state.siem_streams.insert(principal.to_string(), SiemStream {
+                        config,
+                        pending: VecDeque::new(),
+                        delivered_events: 0,
+                        dropped_events: 0,
+                        consecutive_failures: 0,
+                        next_attempt_at: 0,
+                        last_error: None,
+                        last_delivered_at: None,
+                        in_flight: false,
+                    });
+                }
+            }
+            Ok(())
+        })?;
+        Self::status(principal)
+    }
+
+    pub fn status(principal: &str) -> Result<SiemExportStatus, String> {
+        with_state(|state| {
+            let stream = state.siem_streams
+                .get(principal)
+                .ok_or_else(|| "No SIEM export is configured".to_string())?;
+            Ok(SiemExportStatus {
+                config: stream.config.clone(),
+                pending_events: stream.pending.len() as u64,
+                delivered_events: stream.delivered_events,
+                dropped_events: stream.dropped_events,
+                consecutive_failures: stream.consecutive_failures,
+                next_attempt_at: stream.next_attempt_at,
+                last_error: stream.last_error.clone(),
+                last_delivered_at: stream.last_delivered_at,
+            })
+        })
+    }
+
+    /// Queue an event for the principal's organization if it exports this category
+    pub fn emit(principal: &str, category: SiemEventCategory, action: &str, subject: &str, detail: String) {
+        let wanted = with_state(|state| {
+            state.siem_streams
+                .get(principal)
+                .is_some_and(|stream| stream.config.enabled && stream.config.categories.contains(&category))
+        });
+        if !wanted {
+            return;
+        }
+        let now = time();
+        with_state_mut(|state| {
+            let event = SiemEvent {
+                event_id: IdGenerator::next_at(state, "siem", principal, now),
+                category,
+                action: action.to_string(),
+                principal: principal.to_string(),
+                subject: subject.to_string(),
+                detail,
+                occurred_at: now,
+            };
+            if let Some(stream) = state.siem_streams.get_mut(principal) {
+                if stream.pending.len() >= Self::MAX_PENDING {
+                    stream.pending.pop_front();
+                    stream.dropped_events += 1;
+                }
+                stream.pending.push_back(event);
+            }
+        });
+    }
+
+    /// Called from the maintenance timer; starts a batch for every stream that is due
+    pub fn tick() {
+        let now = time();
+        let due: Vec<(String, SiemExportConfig, Vec<SiemEvent>)> = with_state_mut(|state| {
+            state.siem_streams
+                .iter_mut()
+                .filter(|(_, stream)| {
+                    stream.config.enabled && !stream.in_flight && !stream.pending.is_empty() && stream.next_attempt_at <= now
+                })
+                .map(|(principal, stream)| {
+                    stream.in_flight = true;
+                    let batch = stream.pending.iter().take(Self::BATCH_SIZE).cloned().collect();
+                    (principal.clone(), stream.config.clone(), batch)
+                })
+                .collect()
+        });
+        for (principal, config, batch) in due {
+            ic_cdk::spawn(async move {
+                let outcome = Self::send(&principal, &config, &batch).await;
+                Self::settle(&principal, &batch, outcome, time());
+            });
+        }
+    }
+
+    async fn send(principal: &str, config: &SiemExportConfig, batch: &[SiemEvent]) -> Result<(), String> {
+        let batch_id = format!("{}+{}", batch[0].event_id, batch.len());
+        let body = serde_json::to_vec(&SiemBatch { organization: principal, batch_id: &batch_id, events: batch })
+            .map_err(|e| format!("Failed to encode batch: {}", e))?;
+        let key = SecretsService::resolve(principal, &config.signing_secret_label, "SIEM export signing")?;
+        let signature = Self::hex(&Self::hmac_sha256(key.as_bytes(), &body));
+
+        let request = CanisterHttpRequestArgument {
+            url: config.endpoint_url.clone(),
+            max_response_bytes: Some(Self::MAX_RESPONSE_BYTES),
+            method: HttpMethod::POST,
+            headers: vec![
+                HttpHeader { name: "Content-Type".to_string(), value: "application/json".to_string() },
+                HttpHeader { name: "X-OHMS-Signature".to_string(), value: format!("sha256={}", signature) },
+                // Every replica sends the request, so collectors see duplicates even without retries
+                HttpHeader { name: "Idempotency-Key".to_string(), value: batch_id },
+            ],
+            transform: Some(TransformContext::from_name(Self::TRANSFORM_METHOD.to_string(), Vec::new())),
+            body: Some(body.clone()),
+        };
+        let cycles = Self::outcall_cycles(body.len() as u64 + config.endpoint_url.len() as u64);
+        let (response,) = http_request(request, cycles)
+            .await
+            .map_err(|(code, message)| format!("Outcall failed ({:?}): {}", code, message))?;
+        if response.status >= 200u32 && response.status < 300u32 {
+            Ok(())
+        } else {
+            Err(format!("Collector answered {}", response.status))
+        }
+    }
+
+    fn settle(principal: &str, batch: &[SiemEvent], outcome: Result<(), String>, now: u64) {
+        with_state_mut(|state| {
+            let Some(stream) = state.siem_streams.get_mut(principal) else { return };
+            stream.in_flight = false;
+            match outcome {
+                Ok(()) => {
+                    // Match by id: events may have been dropped from the head while in flight
+                    let sent: HashSet<&str> = batch.iter().map(|event| event.event_id.as_str()).collect();
+                    stream.pending.retain(|event| !sent.contains(event.event_id.as_str()));
+                    stream.delivered_events += batch.len() as u64;
+                    stream.consecutive_failures = 0;
+                    stream.last_error = None;
+                    stream.last_delivered_at = Some(now);
+                    stream.next_attempt_at = now + Self::FLUSH_INTERVAL_NS;
+                    Metrics::increment_counter("siem_batches_delivered");
+                }
+                Err(e) => {
+                    stream.consecutive_failures += 1;
+                    stream.next_attempt_at = now + Self::backoff(stream.consecutive_failures);
+                    stream.last_error = Some(e);
+                    Metrics::increment_counter("siem_batches_failed");
+                }
+            }
+        });
+    }
+
+    /// Delay before the next attempt after `failures` consecutive failures
+    fn backoff(failures: u32) -> u64 {
+        Self::BASE_BACKOFF_NS
+            .saturating_mul(1u64 << failures.saturating_sub(1).min(16))
+            .min(Self::MAX_BACKOFF_NS)
+    }
+
+    /// Keep only the status so every replica agrees on the response
+    pub fn transform(args: TransformArgs) -> HttpResponse {
+        HttpResponse { status: args.response.status, headers: Vec::new(), body: Vec::new() }
+    }
+
+    /// Outcall fee on a 13-node subnet for a request of `request_bytes`
+    fn outcall_cycles(request_bytes: u64) -> u128 {
+        let nodes: u128 = 13;
+        (3_000_000 + 60_000 * nodes) * nodes + 400 * nodes * request_bytes as u128 + 800 * nodes * Self::MAX_RESPONSE_BYTES as u128
+    }
+
+    fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+        const BLOCK: usize = 64;
+        let mut block = [0u8; BLOCK];
+        if key.len() > BLOCK {
+            block[..32].copy_from_slice(&Sha256::digest(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+        let mut inner = Sha256::new();
+        inner.update(block.map(|b| b ^ 0x36));
+        inner.update(message);
+        let mut outer = Sha256::new();
+        outer.update(block.map(|b| b ^ 0x5c));
+        outer.update(inner.finalize());
+        outer.finalize().into()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_matches_rfc_4231() {
+        let mac = SiemExportService::hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(SiemExportService::hex(&mac), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        assert_eq!(SiemExportService::backoff(1), SiemExportService::BASE_BACKOFF_NS);
+        assert_eq!(SiemExportService::backoff(2), 2 * SiemExportService::BASE_BACKOFF_NS);
+        assert_eq!(SiemExportService::backoff(40), SiemExportService::MAX_BACKOFF_NS);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, CoordinatorState};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+
+/// Owner-declared agent SLAs and tracking of observed performance against them
+pub struct SlaService;
+
+impl SlaService {
+    /// Calls observed before compliance is judged; new SLAs are presumed compliant
+    const MIN_SAMPLE_CALLS: u64 = 10;
+
+    /// Declare or replace the SLA for an agent owned by `owner`; observations restart
+    pub fn declare_sla(owner: &str, agent_id: &str, max_latency_ms: u64, min_availability: f32) -> Result<(), String> {
+        if max_latency_ms == 0 {
+            return Err("max_latency_ms must be greater than zero".to_string());
+        }
+        if !(min_availability > 0.0 && min_availability <= 1.0) {
+            return Err("min_availability must be within (0, 1]".to_string());
+        }
+
+        with_state_mut(|state| {
+            let agent = state.agents.get(agent_id).ok_or_else(|| "Agent not found".to_string())?;
+            if agent.agent_principal != owner {
+                return Err("Only the agent owner can declare its SLA".to_string());
+            }
+            state.agent_slas.insert(agent_id.to_string(), SlaTracker {
+                sla: AgentSla { max_latency_ms, min_availability, declared_at: time() },
+                total_calls: 0,
+                successful_calls: 0,
+                average_latency_ms: 0.0,
+                violations: 0,
+                last_violation_at: None,
+                last_violation: None,
+                deadline_misses: 0,
+                downtime_ns: 0,
+                down_since: None,
+            });
+            Ok(())
+        })
+    }
+
+    pub fn clear_sla(owner: &str, agent_id: &str) -> Result<(), String> {
+        with_state_mut(|state| {
+            let agent = state.agents.get(agent_id).ok_or_else(|| "Agent not found".to_string())?;
+            if agent.agent_principal != owner {
+                return Err("Only the agent owner can clear its SLA".to_string());
+            }
+            state.agent_slas.remove(agent_id);
+            Ok(())
+        })
+    }
+
+    /// Record one dispatched call started at IC time `started_at` and finished just now;
+    /// failures and slow responses count as violations
+    pub fn record_call(agent_id: &str, success: bool, started_at: u64) {
+        let now = time();
+        if with_state_mut(|state| Self::record_call_in(state, agent_id, success, started_at, now)) {
+            Metrics::increment_counter("sla_violations_total");
+        }
+    }
+
+    /// Returns whether the call violated the agent's SLA
+    fn record_call_in(state: &mut CoordinatorState, agent_id: &str, success: bool, started_at: u64, now: u64) -> bool {
+        let Some(tracker) = state.agent_slas.get_mut(agent_id) else { return false };
+        // IC time is in nanoseconds; SLAs are declared in milliseconds
+        let latency_ms = now.saturating_sub(started_at) / 1_000_000;
+        tracker.total_calls += 1;
+        if success {
+            tracker.successful_calls += 1;
+            let n = tracker.successful_calls as f64;
+            tracker.average_latency_ms += (latency_ms as f64 - tracker.average_latency_ms) / n;
+        }
+
+        let violation = if !success {
+            Some("call failed".to_string())
+        } else if latency_ms > tracker.sla.max_latency_ms {
+            tracker.deadline_misses += 1;
+            Some(format!("latency {}ms exceeded {}ms", latency_ms, tracker.sla.max_latency_ms))
+        } else {
+            None
+        };
+        let violated = violation.is_some();
+        if let Some(reason) = violation {
+            tracker.violations += 1;
+            tracker.last_violation_at = Some(now);
+            tracker.last_violation = Some(reason);
+        }
+        violated
+    }
+
+    /// Health dropping to zero starts an outage and recovering from zero ends it; called
+    /// from the single health update path
+    pub fn record_health_change_in(state: &mut CoordinatorState, agent_id: &str, before: f32, after: f32, now: u64) {
+        let Some(tracker) = state.agent_slas.get_mut(agent_id) else { return };
+        if before > 0.0 && after == 0.0 && tracker.down_since.is_none() {
+            tracker.down_since = Some(now);
+        } else if after > 0.0 {
+            if let Some(since) = tracker.down_since.take() {
+                tracker.downtime_ns += now.saturating_sub(since);
+            }
+        }
+    }
+
+    fn uptime_ratio(tracker: &SlaTracker, now: u64) -> f32 {
+        let elapsed = now.saturating_sub(tracker.sla.declared_at);
+        if elapsed == 0 {
+            return 1.0;
+        }
+        let ongoing = tracker.down_since.map_or(0, |since| now.saturating_sub(since));
+        let downtime = (tracker.downtime_ns + ongoing).min(elapsed);
+        1.0 - downtime as f32 / elapsed as f32
+    }
+
+    fn error_budget_consumed(tracker: &SlaTracker) -> f32 {
+        let failures = tracker.total_calls - tracker.successful_calls;
+        let allowed = (1.0 - tracker.sla.min_availability) * tracker.total_calls as f32;
+        match (failures, allowed > 0.0) {
+            (0, _) => 0.0,
+            (_, true) => failures as f32 / allowed,
+            // A 100% availability SLA has no budget to spend
+            (_, false) => 1.0,
+        }
+    }
+
+    fn compliance(agent_id: &str, tracker: &SlaTracker, now: u64) -> SlaCompliance {
+        let observed_availability = if tracker.total_calls == 0 {
+            1.0
+        } else {
+            tracker.successful_calls as f32 / tracker.total_calls as f32
+        };
+        let uptime_ratio = Self::uptime_ratio(tracker, now);
+        let compliant = uptime_ratio >= tracker.sla.min_availability
+            && (tracker.total_calls < Self::MIN_SAMPLE_CALLS
+                || (observed_availability >= tracker.sla.min_availability
+                    && tracker.average_latency_ms <= tracker.sla.max_latency_ms as f64));
+
+        SlaCompliance {
+            agent_id: agent_id.to_string(),
+            sla: tracker.sla.clone(),
+            observed_availability,
+            observed_latency_ms: tracker.average_latency_ms,
+            total_calls: tracker.total_calls,
+            violations: tracker.violations,
+            last_violation_at: tracker.last_violation_at,
+            uptime_ratio,
+            deadline_misses: tracker.deadline_misses,
+            error_budget_consumed: Self::error_budget_consumed(tracker),
+            compliant,
+        }
+    }
+
+    pub fn get_compliance(agent_id: &str) -> Option<SlaCompliance> {
+        let now = time();
+        with_state(|state| {
+            state.agent_slas.get(agent_id).map(|tracker| Self::compliance(agent_id, tracker, now))
+        })
+    }
+
+    /// Agents with a declared SLA they are currently meeting
+    pub fn is_compliant(agent_id: &str) -> bool {
+        Self::get_compliance(agent_id).map(|c| c.compliant).unwrap_or(false)
+    }
+
+    /// Declared SLAs that are currently being missed
+    pub fn list_violating() -> Vec<SlaCompliance> {
+        let now = time();
+        with_state(|state| {
+            state.agent_slas
+                .iter()
+                .map(|(agent_id, tracker)| Self::compliance(agent_id, tracker, now))
+                .filter(|c| !c.compliant)
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(total_calls: u64, successful_calls: u64, average_latency_ms: f64) -> SlaTracker {
+        SlaTracker {
+            sla: AgentSla { max_latency_ms: 500, min_availability: 0.9, declared_at: 0 },
+            total_calls,
+            successful_calls,
+            average_latency_ms,
+            violations: 0,
+            last_violation_at: None,
+            last_violation: None,
+            deadline_misses: 0,
+            downtime_ns: 0,
+            down_since: None,
+        }
+    }
+
+    #[test]
+    fn test_new_sla_presumed_compliant() {
+        assert!(SlaService::compliance("a", &tracker(3, 0, 0.0), 0).compliant);
+    }
+
+    #[test]
+    fn test_compliance_checks_availability_and_latency() {
+        assert!(SlaService::compliance("a", &tracker(20, 19, 400.0), 0).compliant);
+        assert!(!SlaService::compliance("a", &tracker(20, 15, 400.0), 0).compliant);
+        assert!(!SlaService::compliance("a", &tracker(20, 20, 800.0), 0).compliant);
+    }
+
+    #[test]
+    fn test_uptime_and_error_budget() {
+        let mut state = CoordinatorState::default();
+        state.agent_slas.insert("a".to_string(), tracker(20, 19, 400.0));
+        SlaService::record_health_change_in(&mut state, "a", 1.0, 0.0, 100);
+        SlaService::record_health_change_in(&mut state, "a", 0.0, 0.0, 150);
+        let tracked = &state.agent_slas["a"];
+        assert_eq!(SlaService::uptime_ratio(tracked, 200), 0.5, "an ongoing outage counts");
+
+        SlaService::record_health_change_in(&mut state, "a", 0.0, 1.0, 120);
+        let compliance = SlaService::compliance("a", &state.agent_slas["a"], 1000);
+        assert!((compliance.uptime_ratio - 0.98).abs() < 1e-6);
+        assert!((compliance.error_budget_consumed - 0.5).abs() < 1e-6, "1 of 2 allowed failures");
+        assert!(compliance.compliant);
+    }
+
+    #[test]
+    fn test_deadline_misses_are_judged_in_milliseconds() {
+        let mut state = CoordinatorState::default();
+        state.agent_slas.insert("a".to_string(), tracker(0, 0, 0.0));
+        let started = 5_000_000_000;
+
+        // 400 ms against a 500 ms deadline, as dispatch_inference sees it in IC time
+        assert!(!SlaService::record_call_in(&mut state, "a", true, started, started + 400_000_000));
+        assert!(SlaService::record_call_in(&mut state, "a", true, started, started + 600_000_000));
+        let tracked = &state.agent_slas["a"];
+        assert_eq!(tracked.deadline_misses, 1);
+        assert_eq!(tracked.average_latency_ms, 500.0);
+        assert!(!SlaService::record_call_in(&mut state, "unknown", false, started, started));
+    }
+}
@@ -0,0 +1,288 @@
+use crate::domain::CoordinatorHealth;
+use crate::services::quota_manager::UsageTotals;
+use crate::services::{with_state, with_state_mut, CoordinatorState, IdGenerator};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Spawn latency and failure-rate objectives, evaluated over a rolling window. The
+/// objectives are re-evaluated whenever what they watch changes: on each finished spawn,
+/// when the targets change, and from the maintenance timer as samples age out of the
+/// window, so a breach raises its alert and clears without waiting for the next spawn.
+pub struct SloService;
+
+/// Operator-configured spawn objectives
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SpawnSloTargets {
+    pub p95_spawn_time_ms: u64,
+    pub max_failure_rate: f32,
+    /// Rolling window the p95 and failure rate are computed over
+    pub window_secs: u64,
+    /// How long a breach must persist before an alert is raised
+    pub alert_after_secs: u64,
+    /// Spawns needed in the window before the objectives are judged
+    pub min_samples: u32,
+}
+
+impl Default for SpawnSloTargets {
+    fn default() -> Self {
+        Self {
+            p95_spawn_time_ms: 30_000,
+            max_failure_rate: 0.05,
+            window_secs: 3600,
+            alert_after_secs: 3600,
+            min_samples: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnSample {
+    pub at: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum AlertKind {
+    SpawnLatencySlo,
+    SpawnFailureSlo,
+}
+
+/// Alert raised for operators; resolved automatically once the breach clears
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct OperatorAlert {
+    pub alert_id: String,
+    pub kind: AlertKind,
+    pub message: String,
+    pub raised_at: u64,
+    pub resolved_at: Option<u64>,
+}
+
+/// Current standing against the spawn objectives
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SpawnSloStatus {
+    pub targets: SpawnSloTargets,
+    pub sample_count: u32,
+    pub p95_spawn_time_ms: u64,
+    pub failure_rate: f32,
+    pub latency_breached: bool,
+    pub failure_breached: bool,
+    pub latency_breach_since: Option<u64>,
+    pub failure_breach_since: Option<u64>,
+}
+
+/// What operators see first: coordinator health, spawn objectives and open alerts
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AdminOverview {
+    pub health: CoordinatorHealth,
+    pub spawn_slo: SpawnSloStatus,
+    pub open_alerts: Vec<OperatorAlert>,
+    /// Archived quota usage summed per month, newest first
+    pub usage_totals: Vec<UsageTotals>,
+}
+
+/// Breach tracking between evaluations
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SloBreachState {
+    pub latency_since: Option<u64>,
+    pub failure_since: Option<u64>,
+}
+
+impl SloService {
+    const MAX_SAMPLES: usize = 2000;
+    const MAX_ALERTS: usize = 200;
+    const NANOS_PER_SEC: u64 = 1_000_000_000;
+    const ALERT_SOURCE: &'static str = "slo";
+
+    /// Record a finished spawn request and re-evaluate the objectives
+    pub fn record_spawn(duration_ms: u64, success: bool) {
+        let now = time();
+        let raised = with_state_mut(|state| {
+            if state.spawn_samples.len() >= Self::MAX_SAMPLES {
+                state.spawn_samples.remove(0);
+            }
+            state.spawn_samples.push(SpawnSample { at: now, duration_ms, success });
+            Self::evaluate(state, now)
+        });
+        Self::count_raised(raised);
+    }
+
+    /// Called from the maintenance timer; the window moves on even when nothing spawns
+    pub fn tick() {
+        let now = time();
+        let raised = with_state_mut(|state| Self::evaluate(state, now));
+        Self::count_raised(raised);
+    }
+
+    fn count_raised(raised: usize) {
+        for _ in 0..raised {
+            Metrics::increment_counter("operator_alerts_total");
+        }
+    }
+
+    /// Update breach tracking, raising or resolving alerts; returns the number raised
+    fn evaluate(state: &mut CoordinatorState, now: u64) -> usize {
+        let status = Self::compute(state, now);
+        let alert_after = status.targets.alert_after_secs * Self::NANOS_PER_SEC;
+        let mut raised = 0;
+
+        let checks = [
+            (AlertKind::SpawnLatencySlo, status.latency_breached, format!(
+                "Spawn p95 {} ms exceeds the {} ms objective", status.p95_spawn_time_ms, status.targets.p95_spawn_time_ms
+            )),
+            (AlertKind::SpawnFailureSlo, status.failure_breached, format!(
+                "Spawn failure rate {:.1}% exceeds the {:.1}% objective",
+                status.failure_rate * 100.0, status.targets.max_failure_rate * 100.0
+            )),
+        ];
+        for (kind, breached, message) in checks {
+            let since = match kind {
+                AlertKind::SpawnLatencySlo => &mut state.slo_breaches.latency_since,
+                AlertKind::SpawnFailureSlo => &mut state.slo_breaches.failure_since,
+            };
+            let open = state.operator_alerts.iter_mut().find(|a| a.kind == kind && a.resolved_at.is_none());
+            if !breached {
+                *since = None;
+                if let Some(alert) = open {
+                    alert.resolved_at = Some(now);
+                }
+                continue;
+            }
+            let started = *since.get_or_insert(now);
+            if open.is_none() && now - started >= alert_after {
+                let alert_id = IdGenerator::next_at(state, "alert", Self::ALERT_SOURCE, now);
+                if state.operator_alerts.len() >= Self::MAX_ALERTS {
+                    state.operator_alerts.remove(0);
+                }
+                state.operator_alerts.push(OperatorAlert {
+                    alert_id,
+                    kind,
+                    message,
+                    raised_at: now,
+                    resolved_at: None,
+                });
+                raised += 1;
+            }
+        }
+        raised
+    }
+
+    fn compute(state: &CoordinatorState, now: u64) -> SpawnSloStatus {
+        let targets = state.spawn_slo_targets.clone();
+        let window_start = now.saturating_sub(targets.window_secs * Self::NANOS_PER_SEC);
+        let samples: Vec<&SpawnSample> = state.spawn_samples.iter().filter(|s| s.at >= window_start).collect();
+
+        let mut durations: Vec<u64> = samples.iter().filter(|s| s.success).map(|s| s.duration_ms).collect();
+        durations.sort_unstable();
+        let p95_spawn_time_ms = Self::percentile(&durations, 0.95);
+        let failures = samples.iter().filter(|s| !s.success).count();
+        let failure_rate = if samples.is_empty() { 0.0 } else { failures as f32 / samples.len() as f32 };
+
+        let judged = samples.len() as u32 >= targets.min_samples;
+        SpawnSloStatus {
+            sample_count: samples.len() as u32,
+            p95_spawn_time_ms,
+            failure_rate,
+            latency_breached: judged && p95_spawn_time_ms > targets.p95_spawn_time_ms,
+            failure_breached: judged && failure_rate > targets.max_failure_rate,
+            latency_breach_since: state.slo_breaches.latency_since,
+            failure_breach_since: state.slo_breaches.failure_since,
+            targets,
+        }
+    }
+
+    /// Nearest-rank percentile of sorted values
+    fn percentile(sorted: &[u64], p: f64) -> u64 {
+        if sorted.is_empty() {
+            return 0;
+        }
+        let rank = (p * sorted.len() as f64).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+
+    pub fn get_status() -> SpawnSloStatus {
+        with_state(|state| Self::compute(state, time()))
+    }
+
+    pub fn set_targets(targets: SpawnSloTargets) -> Result<(), String> {
+        if targets.p95_spawn_time_ms == 0 || targets.window_secs == 0 {
+            return Err("p95_spawn_time_ms and window_secs must be greater than zero".to_string());
+        }
+        if !(0.0..=1.0).contains(&targets.max_failure_rate) {
+            return Err("max_failure_rate must be within [0, 1]".to_string());
+        }
+        let now = time();
+        let raised = with_state_mut(|state| {
+            state.spawn_slo_targets = targets;
+            // Breaches are re-judged against the new objectives
+            state.slo_breaches = SloBreachState::default();
+            Self::evaluate(state, now)
+        });
+        Self::count_raised(raised);
+        Ok(())
+    }
+
+    /// Alerts still open, oldest first
+    pub fn open_alerts() -> Vec<OperatorAlert> {
+        with_state(|state| state.operator_alerts.iter().filter(|a| a.resolved_at.is_none()).cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(at: u64, duration_ms: u64, success: bool) -> SpawnSample {
+        SpawnSample { at, duration_ms, success }
+    }
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let values: Vec<u64> = (1..=100).collect();
+        assert_eq!(SloService::percentile(&values, 0.95), 95);
+        assert_eq!(SloService::percentile(&[7], 0.95), 7);
+        assert_eq!(SloService::percentile(&[], 0.95), 0);
+    }
+
+    #[test]
+    fn test_status_judges_only_samples_in_window() {
+        let mut state = CoordinatorState::default();
+        state.spawn_slo_targets.min_samples = 2;
+        let window = state.spawn_slo_targets.window_secs * SloService::NANOS_PER_SEC;
+        let now = 10 * window;
+        state.spawn_samples = vec![
+            sample(0, 90_000, false),
+            sample(now - 5, 40_000, true),
+            sample(now - 1, 45_000, true),
+        ];
+
+        let status = SloService::compute(&state, now);
+        assert_eq!(status.sample_count, 2);
+        assert_eq!(status.p95_spawn_time_ms, 45_000);
+        assert!(status.latency_breached);
+        assert!(!status.failure_breached);
+    }
+
+    #[test]
+    fn test_alerts_follow_the_window_between_spawns() {
+        let sec = SloService::NANOS_PER_SEC;
+        let mut state = CoordinatorState {
+            spawn_slo_targets: SpawnSloTargets { min_samples: 2, window_secs: 7200, alert_after_secs: 3600, ..Default::default() },
+            spawn_samples: vec![sample(0, 40_000, true), sample(sec, 45_000, true)],
+            ..Default::default()
+        };
+
+        assert_eq!(SloService::evaluate(&mut state, sec), 0);
+        assert_eq!(state.slo_breaches.latency_since, Some(sec));
+        // No further spawns: the breach still raises its alert once it has lasted long enough
+        assert_eq!(SloService::evaluate(&mut state, 3601 * sec), 1);
+        assert!(state.operator_alerts[0].resolved_at.is_none());
+
+        // And resolves once the slow spawns leave the window
+        assert_eq!(SloService::evaluate(&mut state, 7202 * sec), 0);
+        assert_eq!(state.operator_alerts[0].resolved_at, Some(7202 * sec));
+        assert_eq!(state.slo_breaches.latency_since, None);
+    }
+}
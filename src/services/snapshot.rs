@@ -0,0 +1,364 @@
+use crate::domain::AgentRegistration;
+use crate::services::{with_state, with_state_mut, CoordinatorState, IdGenerator, RegistryAuditService, RegistryService, WorkloadService};
+use crate::services::registry_audit::RegistryChange;
+use crate::services::audit::{AuditAction, AuditService};
+use crate::infra::Log;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+
+/// Chunked export and import of the coordinator state for bringing up a warm standby.
+///
+/// The snapshot is the CBOR encoding of `CoordinatorState`. Secret values are
+/// never part of it; owners re-enter them on the standby.
+///
+/// Registry snapshots carry only the agent registrations, for backing up the fleet or
+/// seeding a staging coordinator; importing one replaces the registry and leaves the
+/// rest of the state alone.
+pub struct SnapshotService;
+
+/// What a snapshot holds; each scope has its own export and import in progress
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SnapshotScope {
+    State,
+    Registry,
+}
+
+impl SnapshotScope {
+    fn encode(self, state: &CoordinatorState) -> Result<Vec<u8>, serde_cbor::Error> {
+        match self {
+            SnapshotScope::State => serde_cbor::to_vec(state),
+            SnapshotScope::Registry => {
+                let mut agents: Vec<&AgentRegistration> = state.agents.values().collect();
+                agents.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+                serde_cbor::to_vec(&agents)
+            }
+        }
+    }
+
+    fn prepared(self, state: &mut CoordinatorState) -> &mut Option<PreparedSnapshot> {
+        match self {
+            SnapshotScope::State => &mut state.snapshot_export,
+            SnapshotScope::Registry => &mut state.registry_snapshot_export,
+        }
+    }
+
+    fn pending(self, state: &mut CoordinatorState) -> &mut Option<PendingImport> {
+        match self {
+            SnapshotScope::State => &mut state.snapshot_import,
+            SnapshotScope::Registry => &mut state.registry_snapshot_import,
+        }
+    }
+
+    fn id_prefix(self) -> &'static str {
+        match self {
+            SnapshotScope::State => "snapshot",
+            SnapshotScope::Registry => "registry_snapshot",
+        }
+    }
+
+    /// Audit actions for (export, import)
+    fn audit_actions(self) -> (AuditAction, AuditAction) {
+        match self {
+            SnapshotScope::State => (AuditAction::StateSnapshotExported, AuditAction::StateSnapshotImported),
+            SnapshotScope::Registry => (AuditAction::RegistrySnapshotExported, AuditAction::RegistrySnapshotImported),
+        }
+    }
+}
+
+/// One piece of a snapshot; hashes are lowercase hex SHA-256
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SnapshotChunk {
+    pub snapshot_id: String,
+    pub chunk_index: u32,
+    pub total_chunks: u32,
+    pub total_bytes: u64,
+    pub taken_at: u64,
+    pub data: Vec<u8>,
+    pub chunk_sha256: String,
+    pub snapshot_sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SnapshotImportProgress {
+    pub snapshot_id: String,
+    pub received_chunks: u32,
+    pub total_chunks: u32,
+    /// The snapshot was complete, verified and has replaced the local state
+    pub applied: bool,
+}
+
+/// Snapshot prepared by the last `export` of chunk 0
+#[derive(Debug, Clone, Default)]
+pub struct PreparedSnapshot {
+    pub snapshot_id: String,
+    pub bytes: Vec<u8>,
+    pub sha256: String,
+    pub taken_at: u64,
+}
+
+/// Chunks received so far for an import in progress
+#[derive(Debug, Clone, Default)]
+pub struct PendingImport {
+    pub snapshot_id: String,
+    pub total_chunks: u32,
+    pub total_bytes: u64,
+    pub snapshot_sha256: String,
+    pub chunks: BTreeMap<u32, Vec<u8>>,
+}
+
+impl SnapshotService {
+    /// Kept well under the 2 MiB reply limit
+    const CHUNK_BYTES: usize = 1536 * 1024;
+
+    /// Chunk 0 takes a fresh snapshot; later chunks are served from it
+    pub fn export(admin: &str, chunk_index: u32) -> Result<SnapshotChunk, String> {
+        Self::export_scope(SnapshotScope::State, admin, chunk_index)
+    }
+
+    /// As `export`, for the agent registry alone
+    pub fn export_registry(admin: &str, chunk_index: u32) -> Result<SnapshotChunk, String> {
+        Self::export_scope(SnapshotScope::Registry, admin, chunk_index)
+    }
+
+    fn export_scope(scope: SnapshotScope, admin: &str, chunk_index: u32) -> Result<SnapshotChunk, String> {
+        if chunk_index == 0 {
+            let bytes = with_state(|state| scope.encode(state))
+                .map_err(|e| format!("Failed to encode state: {}", e))?;
+            let snapshot = PreparedSnapshot {
+                snapshot_id: IdGenerator::next(scope.id_prefix(), admin),
+                sha256: Self::digest(&bytes),
+                taken_at: time(),
+                bytes,
+            };
+            AuditService::record(
+                admin,
+                scope.audit_actions().0,
+                &snapshot.snapshot_id,
+                format!("{} bytes, sha256 {}", snapshot.bytes.len(), snapshot.sha256),
+            );
+            with_state_mut(|state| *scope.prepared(state) = Some(snapshot));
+        }
+
+        with_state_mut(|state| {
+            let snapshot = scope.prepared(state).as_ref()
+                .ok_or_else(|| "No snapshot prepared; export chunk 0 first".to_string())?;
+            Self::chunk(snapshot, chunk_index)
+        })
+    }
+
+    fn chunk(snapshot: &PreparedSnapshot, chunk_index: u32) -> Result<SnapshotChunk, String> {
+        let total_chunks = snapshot.bytes.len().div_ceil(Self::CHUNK_BYTES).max(1) as u32;
+        if chunk_index >= total_chunks {
+            return Err(format!("Chunk {} out of range; snapshot has {} chunks", chunk_index, total_chunks));
+        }
+        let start = chunk_index as usize * Self::CHUNK_BYTES;
+        let end = (start + Self::CHUNK_BYTES).min(snapshot.bytes.len());
+        let data = snapshot.bytes[start..end].to_vec();
+        Ok(SnapshotChunk {
+            snapshot_id: snapshot.snapshot_id.clone(),
+            chunk_index,
+            total_chunks,
+            total_bytes: snapshot.bytes.len() as u64,
+            taken_at: snapshot.taken_at,
+            chunk_sha256: Self::digest(&data),
+            data,
+            snapshot_sha256: snapshot.sha256.clone(),
+        })
+    }
+
+    /// Accept a chunk; once every chunk of the snapshot is in, verify it and replace the local state
+    pub fn import(admin: &str, chunk: SnapshotChunk) -> Result<SnapshotImportProgress, String> {
+        Self::import_scope(SnapshotScope::State, admin, chunk)
+    }
+
+    /// As `import`, for a registry snapshot; the completed snapshot replaces the agent registry
+    pub fn import_registry(admin: &str, chunk: SnapshotChunk) -> Result<SnapshotImportProgress, String> {
+        Self::import_scope(SnapshotScope::Registry, admin, chunk)
+    }
+
+    fn import_scope(scope: SnapshotScope, admin: &str, chunk: SnapshotChunk) -> Result<SnapshotImportProgress, String> {
+        if Self::digest(&chunk.data) != chunk.chunk_sha256 {
+            return Err(format!("Chunk {} failed its integrity check", chunk.chunk_index));
+        }
+        if chunk.chunk_index >= chunk.total_chunks {
+            return Err("Chunk index out of range".to_string());
+        }
+
+        let complete = with_state_mut(|state| {
+            let import = scope.pending(state);
+            let restart = import.as_ref()
+                .map(|pending| pending.snapshot_id != chunk.snapshot_id)
+                .unwrap_or(true);
+            if restart {
+                // A new snapshot id abandons whatever was being imported before
+                *import = Some(PendingImport {
+                    snapshot_id: chunk.snapshot_id.clone(),
+                    total_chunks: chunk.total_chunks,
+                    total_bytes: chunk.total_bytes,
+                    snapshot_sha256: chunk.snapshot_sha256.clone(),
+                    chunks: BTreeMap::new(),
+                });
+            }
+            let pending = import.as_mut().unwrap();
+            if pending.total_chunks != chunk.total_chunks || pending.snapshot_sha256 != chunk.snapshot_sha256 {
+                return Err("Chunk does not match the snapshot being imported".to_string());
+            }
+            pending.chunks.insert(chunk.chunk_index, chunk.data);
+            let done = pending.chunks.len() as u32 == pending.total_chunks;
+            Ok(if done { import.take() } else { None })
+        })?;
+
+        let Some(pending) = complete else {
+            let received_chunks = with_state_mut(|state| {
+                scope.pending(state).as_ref().map(|p| p.chunks.len() as u32).unwrap_or(0)
+            });
+            return Ok(SnapshotImportProgress {
+                snapshot_id: chunk.snapshot_id,
+                received_chunks,
+                total_chunks: chunk.total_chunks,
+                applied: false,
+            });
+        };
+
+        let bytes = Self::assemble(&pending)?;
+        let detail = match scope {
+            SnapshotScope::State => {
+                let restored: CoordinatorState = Self::decode(&bytes)?;
+                with_state_mut(|state| {
+                    // Secrets are not part of snapshots; keep any already entered on this canister
+                    let secrets = std::mem::take(&mut state.secrets);
+                    *state = restored;
+                    state.secrets = secrets;
+                    IdGenerator::observe_existing(state);
+                    WorkloadService::rebuild(state);
+                    RegistryService::rebuild_capability_index(state);
+                    Log::configure(state.config.logging.clone());
+                });
+                format!("{} bytes, sha256 {}", pending.total_bytes, pending.snapshot_sha256)
+            }
+            SnapshotScope::Registry => {
+                let agents: Vec<AgentRegistration> = Self::decode(&bytes)?;
+                let count = agents.len();
+                let now = time();
+                let removed = with_state_mut(|state| Self::replace_registry(state, agents, admin, now));
+                format!(
+                    "{} agents restored, {} removed; {} bytes, sha256 {}",
+                    count, removed, pending.total_bytes, pending.snapshot_sha256
+                )
+            }
+        };
+        AuditService::record(admin, scope.audit_actions().1, &pending.snapshot_id, detail);
+
+        Ok(SnapshotImportProgress {
+            snapshot_id: pending.snapshot_id,
+            received_chunks: pending.total_chunks,
+            total_chunks: pending.total_chunks,
+            applied: true,
+        })
+    }
+
+    fn assemble(pending: &PendingImport) -> Result<Vec<u8>, String> {
+        let bytes: Vec<u8> = pending.chunks.values().flatten().copied().collect();
+        if bytes.len() as u64 != pending.total_bytes || Self::digest(&bytes) != pending.snapshot_sha256 {
+            return Err("Snapshot failed its integrity check; import aborted".to_string());
+        }
+        Ok(bytes)
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+        serde_cbor::from_slice(bytes).map_err(|e| format!("Failed to decode snapshot: {}", e))
+    }
+
+    /// Make the registry exactly `agents`; returns how many agents were removed
+    fn replace_registry(state: &mut CoordinatorState, agents: Vec<AgentRegistration>, admin: &str, now: u64) -> usize {
+        let keep: HashSet<&str> = agents.iter().map(|a| a.agent_id.as_str()).collect();
+        let stale: Vec<String> = state.agents.keys().filter(|id| !keep.contains(id.as_str())).cloned().collect();
+        for agent_id in &stale {
+            RegistryService::remove_registration(state, agent_id, admin, now);
+        }
+        for agent in agents {
+            if state.agents.contains_key(&agent.agent_id) {
+                RegistryService::mark_changed(state, &agent.agent_id);
+                RegistryAuditService::record_in(state, &agent.agent_id, admin, now, RegistryChange::Registered { after: agent.clone() });
+                if let Some(previous) = state.agents.get(&agent.agent_id) {
+                    state.capability_index.remove(&previous.agent_id, &previous.capabilities);
+                }
+                state.capability_index.add(&agent.agent_id, &agent.capabilities);
+                state.agents.insert(agent.agent_id.clone(), agent);
+            } else {
+                RegistryService::insert_registration(state, agent, admin);
+            }
+        }
+        stale.len()
+    }
+
+    fn digest(bytes: &[u8]) -> String {
+        Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_reassemble_into_state() {
+        let mut state = CoordinatorState { id_sequence: 42, ..Default::default() };
+        state.user_activity.insert("user".to_string(), 7);
+        let bytes = serde_cbor::to_vec(&state).unwrap();
+        let snapshot = PreparedSnapshot {
+            snapshot_id: "snapshot_1".to_string(),
+            sha256: SnapshotService::digest(&bytes),
+            taken_at: 0,
+            bytes,
+        };
+
+        let chunk = SnapshotService::chunk(&snapshot, 0).unwrap();
+        assert_eq!(chunk.total_chunks, 1);
+        assert!(SnapshotService::chunk(&snapshot, 1).is_err());
+
+        let mut pending = PendingImport {
+            snapshot_id: chunk.snapshot_id.clone(),
+            total_chunks: chunk.total_chunks,
+            total_bytes: chunk.total_bytes,
+            snapshot_sha256: chunk.snapshot_sha256.clone(),
+            chunks: BTreeMap::new(),
+        };
+        pending.chunks.insert(0, chunk.data.clone());
+        let restored: CoordinatorState = SnapshotService::decode(&SnapshotService::assemble(&pending).unwrap()).unwrap();
+        assert_eq!(restored.id_sequence, 42);
+        assert_eq!(restored.user_activity.get("user"), Some(&7));
+
+        pending.chunks.insert(0, chunk.data[1..].to_vec());
+        assert!(SnapshotService::assemble(&pending).is_err());
+    }
+
+    #[test]
+    fn test_registry_snapshot_replaces_the_registry() {
+        let agent = |agent_id: &str, health_score: f32| AgentRegistration { health_score, ..AgentRegistration::test(agent_id) };
+        let mut source = CoordinatorState::default();
+        RegistryService::insert_registration(&mut source, agent("a", 0.9), "owner");
+        RegistryService::insert_registration(&mut source, agent("b", 0.8), "owner");
+        source.user_activity.insert("user".to_string(), 7);
+        let bytes = SnapshotScope::Registry.encode(&source).unwrap();
+
+        let mut target = CoordinatorState::default();
+        RegistryService::insert_registration(&mut target, agent("b", 0.1), "owner");
+        RegistryService::insert_registration(&mut target, agent("c", 0.5), "owner");
+        let version = target.agent_registry_version;
+        let removed = SnapshotService::replace_registry(&mut target, SnapshotService::decode(&bytes).unwrap(), "admin", 0);
+
+        assert_eq!(removed, 1);
+        let mut ids: Vec<_> = target.agents.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(target.agents["b"].health_score, 0.8);
+        assert!(target.routing_stats.contains_key("a"));
+        assert!(target.agent_registry_version > version);
+        // Only the registry travels
+        assert!(target.user_activity.is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::services::{with_state, with_state_mut};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Specialization ontology: roles with parent roles and substitution rules, so
+/// routing and spawning can fall back to a covering role when no exact specialist
+/// is available.
+///
+/// A role can always stand in for its ancestors (a "Full-stack Developer" is a
+/// "Software Developer"); `covers` adds rules outside the hierarchy.
+pub struct SpecializationService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub struct SpecializationRole {
+    pub name: String,
+    /// More general role this one refines
+    pub parent: Option<String>,
+    pub capabilities: Vec<String>,
+    pub models: Vec<String>,
+    /// Roles outside its ancestry this role may stand in for
+    pub covers: Vec<String>,
+}
+
+type Ontology = BTreeMap<String, SpecializationRole>;
+/// (name, parent, capabilities, models, covers)
+type BuiltInRole = (&'static str, Option<&'static str>, &'static [&'static str], &'static [&'static str], &'static [&'static str]);
+
+impl SpecializationService {
+    /// Root of the hierarchy; what spawning falls back to for unknown roles
+    pub const GENERALIST: &'static str = "General Assistant";
+
+    const MAX_DEPTH: usize = 16;
+
+    const BUILT_IN: &'static [BuiltInRole] = &[
+        ("General Assistant", None, &["general_assistance"], &["llama"], &[]),
+        ("Software Developer", Some("General Assistant"),
+            &["coding", "software_development", "programming", "debugging"],
+            &["code-llama", "starcoder", "wizardcoder"], &[]),
+        ("Full-stack Developer", Some("Software Developer"),
+            &["full_stack_development", "frontend", "backend", "coding", "software_development", "programming", "debugging"],
+            &["code-llama", "starcoder", "wizardcoder"], &[]),
+        ("Test Engineer", Some("General Assistant"),
+            &["testing", "quality_assurance", "verification", "automation"],
+            &["code-llama", "starcoder", "wizardcoder"], &[]),
+        ("Code Reviewer", Some("General Assistant"),
+            &["code_review", "quality_assurance", "best_practices", "security"],
+            &["code-llama", "starcoder", "wizardcoder"], &["Test Engineer"]),
+        ("Content Creator", Some("General Assistant"),
+            &["content_creation", "writing", "documentation", "editing"],
+            &["llama", "mistral", "gemma"], &[]),
+        ("Technical Writer", Some("Content Creator"),
+            &["technical_writing", "content_creation", "writing", "documentation", "editing"],
+            &["llama", "mistral", "gemma"], &[]),
+        ("Marketing Specialist", Some("General Assistant"),
+            &["marketing", "social_media", "campaign_management", "analytics"],
+            &["llama", "mistral", "gemma"], &[]),
+        ("Data Analyst", Some("General Assistant"),
+            &["data_analysis", "analytics", "reporting", "visualization"],
+            &["llama", "mistral", "gemma"], &[]),
+        ("Data Scientist", Some("Data Analyst"),
+            &["machine_learning", "statistics", "data_analysis", "analytics", "reporting", "visualization"],
+            &["llama", "mistral", "gemma"], &[]),
+        ("Research Analyst", Some("General Assistant"),
+            &["research", "investigation", "analysis", "synthesis"],
+            &["llama", "mistral", "gemma"], &["Data Analyst"]),
+    ];
+
+    fn built_in() -> Ontology {
+        Self::BUILT_IN
+            .iter()
+            .map(|(name, parent, capabilities, models, covers)| {
+                let role = SpecializationRole {
+                    name: name.to_string(),
+                    parent: parent.map(|p| p.to_string()),
+                    capabilities: capabilities.iter().map(|s| s.to_string()).collect(),
+                    models: models.iter().map(|s| s.to_string()).collect(),
+                    covers: covers.iter().map(|s| s.to_string()).collect(),
+                };
+                (role.name.clone(), role)
+            })
+            .collect()
+    }
+
+    /// Built-in roles with operator additions and overrides applied
+    fn ontology() -> Ontology {
+        let mut ontology = Self::built_in();
+        with_state(|state| {
+            for role in state.specialization_roles.values() {
+                ontology.insert(role.name.clone(), role.clone());
+            }
+        });
+        ontology
+    }
+
+    pub fn list_roles() -> Vec<SpecializationRole> {
+        Self::ontology().into_values().collect()
+    }
+
+    pub fn resolve(name: &str) -> Option<SpecializationRole> {
+        Self::ontology().remove(name)
+    }
+
+    /// Add or replace a role; the parent and covered roles must already exist
+    pub fn set_role(role: SpecializationRole) -> Result<(), String> {
+        let mut ontology = Self::ontology();
+        Self::validate(&ontology, &role)?;
+        ontology.insert(role.name.clone(), role.clone());
+        if Self::ancestors(&ontology, &role.name).len() >= Self::MAX_DEPTH {
+            return Err(format!("Role {} would be nested too deeply or in a cycle", role.name));
+        }
+        with_state_mut(|state| state.specialization_roles.insert(role.name.clone(), role));
+        Ok(())
+    }
+
+    fn validate(ontology: &Ontology, role: &SpecializationRole) -> Result<(), String> {
+        if role.name.trim().is_empty() {
+            return Err("Role name cannot be empty".to_string());
+        }
+        if role.capabilities.is_empty() {
+            return Err("A role needs at least one capability".to_string());
+        }
+        if role.name == Self::GENERALIST && role.parent.is_some() {
+            return Err(format!("{} is the root role and cannot have a parent", Self::GENERALIST));
+        }
+        if let Some(parent) = &role.parent {
+            if parent == &role.name || !ontology.contains_key(parent) {
+                return Err(format!("Unknown parent role: {}", parent));
+            }
+        }
+        if let Some(unknown) = role.covers.iter().find(|c| *c == &role.name || !ontology.contains_key(*c)) {
+            return Err(format!("Unknown covered role: {}", unknown));
+        }
+        Ok(())
+    }
+
+    /// Parent chain of `name`, nearest first; stops at `MAX_DEPTH` so a cycle cannot loop forever
+    fn ancestors(ontology: &Ontology, name: &str) -> Vec<String> {
+        let mut chain = Vec::new();
+        let mut current = ontology.get(name).and_then(|role| role.parent.clone());
+        while let Some(parent) = current {
+            if chain.len() >= Self::MAX_DEPTH {
+                break;
+            }
+            current = ontology.get(&parent).and_then(|role| role.parent.clone());
+            chain.push(parent);
+        }
+        chain
+    }
+
+    /// Roles able to stand in for `name`, best substitute first: explicit rules,
+    /// then descendants by closeness
+    fn fallbacks_in(ontology: &Ontology, name: &str) -> Vec<String> {
+        let mut ranked: Vec<(usize, &String)> = ontology
+            .keys()
+            .filter(|candidate| *candidate != name)
+            .filter_map(|candidate| {
+                if ontology[candidate].covers.iter().any(|c| c == name) {
+                    return Some((0, candidate));
+                }
+                Self::ancestors(ontology, candidate)
+                    .iter()
+                    .position(|ancestor| ancestor == name)
+                    .map(|depth| (depth + 1, candidate))
+            })
+            .collect();
+        ranked.sort();
+        ranked.into_iter().map(|(_, candidate)| candidate.clone()).collect()
+    }
+
+    pub fn fallbacks(name: &str) -> Vec<String> {
+        Self::fallbacks_in(&Self::ontology(), name)
+    }
+
+    /// Capabilities of roles that substitute for the roles owning `required`, excluding `required` itself
+    pub fn fallback_capabilities(required: &[String]) -> Vec<String> {
+        let ontology = Self::ontology();
+        let mut capabilities: Vec<String> = Vec::new();
+        let owners = ontology
+            .values()
+            .filter(|role| role.name != Self::GENERALIST)
+            .filter(|role| role.capabilities.iter().any(|c| required.contains(c)));
+        for owner in owners {
+            for substitute in Self::fallbacks_in(&ontology, &owner.name) {
+                for capability in &ontology[&substitute].capabilities {
+                    if !required.contains(capability) && !capabilities.contains(capability) {
+                        capabilities.push(capability.clone());
+                    }
+                }
+            }
+        }
+        capabilities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descendants_and_cover_rules_substitute() {
+        let ontology = SpecializationService::built_in();
+        assert_eq!(
+            SpecializationService::fallbacks_in(&ontology, "Software Developer"),
+            vec!["Full-stack Developer".to_string()]
+        );
+        assert_eq!(
+            SpecializationService::fallbacks_in(&ontology, "Data Analyst"),
+            vec!["Research Analyst".to_string(), "Data Scientist".to_string()]
+        );
+        assert!(SpecializationService::fallbacks_in(&ontology, "Full-stack Developer").is_empty());
+        // Every specialist can stand in for the generalist
+        assert_eq!(SpecializationService::fallbacks_in(&ontology, "General Assistant").len(), ontology.len() - 1);
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_relations() {
+        let ontology = SpecializationService::built_in();
+        let mut role = SpecializationRole {
+            name: "Security Auditor".to_string(),
+            parent: Some("Code Reviewer".to_string()),
+            capabilities: vec!["security_audit".to_string()],
+            models: vec![],
+            covers: vec![],
+        };
+        assert!(SpecializationService::validate(&ontology, &role).is_ok());
+        role.covers = vec!["Penetration Tester".to_string()];
+        assert!(SpecializationService::validate(&ontology, &role).is_err());
+        role.covers.clear();
+        role.parent = Some("Security Auditor".to_string());
+        assert!(SpecializationService::validate(&ontology, &role).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::services::{with_state, with_state_mut, AuditService, CoordinatorState, NotificationService, RegistryService};
+use crate::services::audit::AuditAction;
+use crate::services::notifications::NotificationKind;
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+/// Warm standbys for critical capabilities. An operator pairs a primary agent with a
+/// standby that is registered and heartbeating but kept out of routing; once the
+/// primary's health falls below the promotion threshold, or it leaves the registry,
+/// the next maintenance tick promotes the standby into the pool. Promotions are audited
+/// and both owners are notified. A promoted standby stays in the pool until an
+/// operator designates a new standby for the capability.
+pub struct StandbyService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct StandbyAssignment {
+    pub capability: String,
+    pub primary_agent_id: String,
+    pub standby_agent_id: String,
+    /// The standby is promoted when the primary's health drops below this
+    pub promote_below_health: f32,
+    pub designated_by: String,
+    pub designated_at: u64,
+    pub promoted_at: Option<u64>,
+}
+
+/// A standby promoted by one tick, with what triggered it
+#[derive(Debug, Clone)]
+pub struct StandbyPromotion {
+    pub assignment: StandbyAssignment,
+    pub primary_owner: Option<String>,
+    pub standby_owner: String,
+    pub reason: String,
+}
+
+impl StandbyService {
+    const DEFAULT_PROMOTE_BELOW_HEALTH: f32 = 0.5;
+
+    pub fn designate(
+        admin: &str,
+        capability: &str,
+        primary_agent_id: &str,
+        standby_agent_id: &str,
+        promote_below_health: Option<f32>,
+    ) -> Result<StandbyAssignment, String> {
+        let promote_below_health = promote_below_health.unwrap_or(Self::DEFAULT_PROMOTE_BELOW_HEALTH);
+        if !(promote_below_health > 0.0 && promote_below_health <= 1.0) {
+            return Err("promote_below_health must be in (0, 1]".to_string());
+        }
+        if primary_agent_id == standby_agent_id {
+            return Err("The standby must be a different agent from the primary".to_string());
+        }
+        for agent_id in [primary_agent_id, standby_agent_id] {
+            let agent = RegistryService::get_agent(agent_id)?;
+            if !RegistryService::has_capability(&agent.capabilities, capability) {
+                return Err(format!("Agent {} does not offer {}", agent_id, capability));
+            }
+        }
+        let assignment = StandbyAssignment {
+            capability: capability.to_string(),
+            primary_agent_id: primary_agent_id.to_string(),
+            standby_agent_id: standby_agent_id.to_string(),
+            promote_below_health,
+            designated_by: admin.to_string(),
+            designated_at: time(),
+            promoted_at: None,
+        };
+        with_state_mut(|state| state.standby_assignments.insert(capability.to_string(), assignment.clone()));
+        Ok(assignment)
+    }
+
+    pub fn remove(capability: &str) -> Result<(), String> {
+        with_state_mut(|state| state.standby_assignments.remove(capability))
+            .map(|_| ())
+            .ok_or_else(|| format!("No standby designated for {}", capability))
+    }
+
+    pub fn list() -> Vec<StandbyAssignment> {
+        let mut assignments: Vec<StandbyAssignment> = with_state(|state| state.standby_assignments.values().cloned().collect());
+        assignments.sort_by(|a, b| a.capability.cmp(&b.capability));
+        assignments
+    }
+
+    /// Whether routing must skip the agent because it is an unpromoted standby
+    pub fn is_held_back(agent_id: &str) -> bool {
+        with_state(|state| {
+            state.standby_assignments
+                .values()
+                .any(|a| a.standby_agent_id == agent_id && a.promoted_at.is_none())
+        })
+    }
+
+    /// Called from the maintenance timer; promotes the standbys whose primary has failed
+    pub fn tick() {
+        let now = time();
+        let promotions = with_state_mut(|state| Self::promote_in(state, now));
+        for promotion in promotions {
+            let assignment = &promotion.assignment;
+            Metrics::increment_counter("standby_promotions_total");
+            AuditService::record(
+                &promotion.standby_owner,
+                AuditAction::StandbyPromoted,
+                &assignment.standby_agent_id,
+                format!("promoted for {} replacing {}: {}", assignment.capability, assignment.primary_agent_id, promotion.reason),
+            );
+            let message = format!(
+                "Standby agent {} was promoted for {} because primary {} {}",
+                assignment.standby_agent_id, assignment.capability, assignment.primary_agent_id, promotion.reason
+            );
+            if let Some(primary_owner) = promotion.primary_owner.as_deref().filter(|owner| *owner != promotion.standby_owner) {
+                NotificationService::notify(primary_owner, NotificationKind::StandbyPromoted, message.clone());
+            }
+            NotificationService::notify(&promotion.standby_owner, NotificationKind::StandbyPromoted, message);
+        }
+    }
+
+    fn promote_in(state: &mut CoordinatorState, now: u64) -> Vec<StandbyPromotion> {
+        let mut promotions = Vec::new();
+        for assignment in state.standby_assignments.values_mut().filter(|a| a.promoted_at.is_none()) {
+            // A standby that has itself left the registry has nothing to promote
+            let Some(standby) = state.agents.get(&assignment.standby_agent_id) else { continue };
+            let primary = state.agents.get(&assignment.primary_agent_id);
+            let reason = match primary {
+                None => "left the registry".to_string(),
+                Some(primary) if primary.health_score < assignment.promote_below_health => format!(
+                    "dropped to health {:.2}, below {:.2}",
+                    primary.health_score, assignment.promote_below_health
+                ),
+                Some(_) => continue,
+            };
+            assignment.promoted_at = Some(now);
+            promotions.push(StandbyPromotion {
+                assignment: assignment.clone(),
+                primary_owner: primary.map(|p| p.agent_principal.clone()),
+                standby_owner: standby.agent_principal.clone(),
+                reason,
+            });
+        }
+        promotions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AgentRegistration;
+
+    fn agent(agent_id: &str, health_score: f32) -> AgentRegistration {
+        AgentRegistration { capabilities: vec!["incident_response".to_string()], health_score, ..AgentRegistration::test(agent_id) }
+    }
+
+    #[test]
+    fn test_standby_promoted_once_primary_degrades() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, agent("primary", 0.9), "owner");
+        RegistryService::insert_registration(&mut state, agent("standby", 0.9), "owner");
+        state.standby_assignments.insert("incident_response".to_string(), StandbyAssignment {
+            capability: "incident_response".to_string(),
+            primary_agent_id: "primary".to_string(),
+            standby_agent_id: "standby".to_string(),
+            promote_below_health: 0.5,
+            designated_by: "admin".to_string(),
+            designated_at: 0,
+            promoted_at: None,
+        });
+
+        assert!(StandbyService::promote_in(&mut state, 1).is_empty());
+
+        state.agents.get_mut("primary").unwrap().health_score = 0.2;
+        let promotions = StandbyService::promote_in(&mut state, 2);
+        assert_eq!(promotions.len(), 1);
+        assert!(promotions[0].reason.contains("0.20"), "{}", promotions[0].reason);
+        assert_eq!(state.standby_assignments["incident_response"].promoted_at, Some(2));
+
+        // Promotion happens once
+        assert!(StandbyService::promote_in(&mut state, 3).is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::services::{with_state, with_state_mut};
+use ic_cdk::api::time;
+use serde::{Deserialize, Serialize};
+use candid::CandidType;
+use std::collections::BTreeMap;
+
+/// Per-tag usage aggregation for chargeback and project-level observability. Tags are
+/// scoped to the principal that sent the requests, so one caller never sees another's.
+pub struct TagAnalyticsService;
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Kind of tagged request being recorded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaggedRequestKind {
+    Route,
+    AgentCreation,
+}
+
+/// Daily aggregate for a single tag
+#[derive(Debug, Clone, Default, Serialize, Deserialize, CandidType)]
+pub struct TagBucket {
+    pub route_requests: u64,
+    pub creation_requests: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub total_latency_ms: u64,
+    pub agents_created: u64,
+}
+
+/// Aggregated report for a tag over a trailing period
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct TagReport {
+    pub tag: String,
+    pub period_days: u32,
+    pub route_requests: u64,
+    pub creation_requests: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub success_rate: f32,
+    pub average_latency_ms: f64,
+    pub agents_created: u64,
+}
+
+impl TagAnalyticsService {
+    const RETENTION_DAYS: u64 = 90;
+    
+    /// Record one tagged request outcome of `principal` against every tag it carries
+    pub fn record(principal: &str, tags: &[String], kind: TaggedRequestKind, success: bool, latency_ms: u64, agents_created: u32) {
+        if tags.is_empty() {
+            return;
+        }
+        
+        let day = time() / NANOS_PER_DAY;
+        with_state_mut(|state| {
+            let usage = state.principal_tag_usage.entry(principal.to_string()).or_default();
+            for tag in tags {
+                let buckets = usage.entry(tag.clone()).or_insert_with(BTreeMap::new);
+                let bucket = buckets.entry(day).or_default();
+                match kind {
+                    TaggedRequestKind::Route => bucket.route_requests += 1,
+                    TaggedRequestKind::AgentCreation => bucket.creation_requests += 1,
+                }
+                if success {
+                    bucket.successes += 1;
+                } else {
+                    bucket.failures += 1;
+                }
+                bucket.total_latency_ms += latency_ms;
+                bucket.agents_created += agents_created as u64;
+                
+                // Drop buckets past retention
+                let cutoff = day.saturating_sub(Self::RETENTION_DAYS);
+                buckets.retain(|d, _| *d >= cutoff);
+            }
+        });
+    }
+    
+    /// Report for `principal`'s `tag` over the trailing `period_days`
+    pub fn get_tag_report(principal: &str, tag: &str, period_days: u32) -> TagReport {
+        let today = time() / NANOS_PER_DAY;
+        let from_day = today.saturating_sub(period_days.saturating_sub(1) as u64);
+        
+        let buckets: Vec<TagBucket> = with_state(|state| {
+            state.principal_tag_usage
+                .get(principal)
+                .and_then(|usage| usage.get(tag))
+                .map(|b| b.range(from_day..=today).map(|(_, v)| v.clone()).collect())
+                .unwrap_or_default()
+        });
+        
+        Self::summarize(tag, period_days, &buckets)
+    }
+    
+    fn summarize(tag: &str, period_days: u32, buckets: &[TagBucket]) -> TagReport {
+        let mut total = TagBucket::default();
+        for b in buckets {
+            total.route_requests += b.route_requests;
+            total.creation_requests += b.creation_requests;
+            total.successes += b.successes;
+            total.failures += b.failures;
+            total.total_latency_ms += b.total_latency_ms;
+            total.agents_created += b.agents_created;
+        }
+        
+        let count = total.successes + total.failures;
+        TagReport {
+            tag: tag.to_string(),
+            period_days,
+            route_requests: total.route_requests,
+            creation_requests: total.creation_requests,
+            successes: total.successes,
+            failures: total.failures,
+            success_rate: if count > 0 { total.successes as f32 / count as f32 } else { 0.0 },
+            average_latency_ms: if count > 0 { total.total_latency_ms as f64 / count as f64 } else { 0.0 },
+            agents_created: total.agents_created,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_buckets() {
+        let buckets = vec![
+            TagBucket { route_requests: 3, creation_requests: 0, successes: 2, failures: 1, total_latency_ms: 300, agents_created: 0 },
+            TagBucket { route_requests: 0, creation_requests: 1, successes: 1, failures: 0, total_latency_ms: 100, agents_created: 2 },
+        ];
+        let report = TagAnalyticsService::summarize("proj-x", 7, &buckets);
+        assert_eq!(report.route_requests, 3);
+        assert_eq!(report.creation_requests, 1);
+        assert_eq!(report.success_rate, 0.75);
+        assert_eq!(report.average_latency_ms, 100.0);
+        assert_eq!(report.agents_created, 2);
+    }
+
+    #[test]
+    fn test_summarize_empty() {
+        let report = TagAnalyticsService::summarize("none", 30, &[]);
+        assert_eq!(report.success_rate, 0.0);
+        assert_eq!(report.average_latency_ms, 0.0);
+    }
+}
@@ -0,0 +1,302 @@
+use crate::domain::*;
+use crate::services::{with_state, with_state_mut, AgentSpawningService, IdGenerator, RegistryAuditService, RegistryService, SpecializationService, FeatureFlagService};
+use crate::services::agent_spawning::{AgentStatus, SpawnedAgent};
+use crate::infra::Metrics;
+use ic_cdk::api::time;
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Pre-provisioned generic agents for the most requested specializations, claimed by
+/// instruction-based spawning before it falls back to cold creation. Pooled agents are
+/// held by the coordinator and only enter the registry once claimed, so they are never
+/// routed to and never count against anyone's quota while idle.
+pub struct WarmPoolService;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct WarmPoolConfig {
+    pub enabled: bool,
+    pub agents_per_specialization: u32,
+    /// Global budget across every specialization
+    pub max_pooled_agents: u32,
+    /// How many of the most requested specializations get a pool
+    pub popular_specializations: u32,
+}
+
+impl Default for WarmPoolConfig {
+    fn default() -> Self {
+        Self { enabled: false, agents_per_specialization: 2, max_pooled_agents: 20, popular_specializations: 3 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PooledAgent {
+    pub agent_id: String,
+    pub canister_id: String,
+    pub specialization: String,
+    pub model_id: String,
+    pub provisioned_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PoolCounters {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarmPoolState {
+    pub config: WarmPoolConfig,
+    pub agents: Vec<PooledAgent>,
+    /// Agents requested per specialization, pooled or not; decides what is popular
+    pub demand: HashMap<String, u64>,
+    pub counters: HashMap<String, PoolCounters>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct WarmPoolSpecialization {
+    pub specialization: String,
+    pub available: u32,
+    pub target: u32,
+    pub demand: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct WarmPoolStatus {
+    pub config: WarmPoolConfig,
+    pub total_pooled: u32,
+    pub hits: u64,
+    pub misses: u64,
+    /// Share of spawns served from the pool while it was enabled
+    pub hit_rate: f32,
+    pub specializations: Vec<WarmPoolSpecialization>,
+}
+
+impl WarmPoolService {
+    /// Owner of pooled agents until they are claimed
+    pub const POOL_PRINCIPAL: &'static str = "warm-pool";
+    const MAX_POOLED_AGENTS: u32 = 500;
+
+    pub fn set_config(config: WarmPoolConfig) -> Result<WarmPoolStatus, String> {
+        if config.max_pooled_agents > Self::MAX_POOLED_AGENTS {
+            return Err(format!("max_pooled_agents cannot exceed {}", Self::MAX_POOLED_AGENTS));
+        }
+        if config.enabled && (config.agents_per_specialization == 0 || config.popular_specializations == 0) {
+            return Err("An enabled pool needs agents_per_specialization and popular_specializations above zero".to_string());
+        }
+        with_state_mut(|state| state.warm_pool.config = config);
+        Ok(Self::replenish())
+    }
+
+    pub fn record_demand(specialization: &str) {
+        with_state_mut(|state| *state.warm_pool.demand.entry(specialization.to_string()).or_insert(0) += 1);
+    }
+
+    /// Pool size per specialization: the most requested first, each up to the per-role
+    /// size, until the global budget runs out
+    fn targets(config: &WarmPoolConfig, demand: &HashMap<String, u64>) -> Vec<(String, u32)> {
+        if !config.enabled {
+            return Vec::new();
+        }
+        let mut ranked: Vec<(&String, &u64)> = demand.iter().collect();
+        ranked.sort_by(|(a_name, a_count), (b_name, b_count)| b_count.cmp(a_count).then(a_name.cmp(b_name)));
+        let mut budget = config.max_pooled_agents;
+        ranked
+            .into_iter()
+            .take(config.popular_specializations as usize)
+            .map(|(name, _)| {
+                let target = config.agents_per_specialization.min(budget);
+                budget -= target;
+                (name.clone(), target)
+            })
+            .filter(|(_, target)| *target > 0)
+            .collect()
+    }
+
+    /// Pooled agent that can serve `spec` as is: same specialization and, when the spec
+    /// names models, the one it would deploy
+    fn find_match(agents: &[PooledAgent], spec: &AgentSpec) -> Option<usize> {
+        agents.iter().position(|agent| {
+            agent.specialization == spec.specialization
+                && spec.model_requirements.first().is_none_or(|model| *model == agent.model_id)
+        })
+    }
+
+    /// Hand a pooled agent to `user_principal`, reconfigured with the spec's capabilities
+    pub fn claim(spec: &AgentSpec, user_principal: &str) -> Option<SpawnedAgent> {
+        let now = time();
+        with_state_mut(|state| {
+            if !state.warm_pool.config.enabled || !FeatureFlagService::enabled_in(state, FeatureFlagService::WARM_POOL) {
+                return None;
+            }
+            let index = Self::find_match(&state.warm_pool.agents, spec);
+            let counters = state.warm_pool.counters.entry(spec.specialization.clone()).or_default();
+            let Some(index) = index else {
+                counters.misses += 1;
+                Metrics::increment_counter("warm_pool_misses");
+                return None;
+            };
+            counters.hits += 1;
+            Metrics::increment_counter("warm_pool_hits");
+            let pooled = state.warm_pool.agents.remove(index);
+
+            RegistryService::insert_registration(state, AgentRegistration {
+                agent_id: pooled.agent_id.clone(),
+                agent_principal: user_principal.to_string(),
+                canister_id: pooled.canister_id.clone(),
+                capabilities: spec.required_capabilities.clone(),
+                model_id: pooled.model_id.clone(),
+                health_score: 1.0,
+                registered_at: now,
+                last_seen: now,
+                tags: Default::default(),
+                agent_version: None,
+                protocol_version: None,
+                state: None,
+                lifecycle: Some(AgentLifecycle::Ready),
+            }, RegistryAuditService::WARM_POOL);
+            state.awaiting_first_heartbeat.insert(pooled.agent_id.clone());
+            Some(SpawnedAgent {
+                agent_id: pooled.agent_id,
+                canister_id: pooled.canister_id,
+                specialization: spec.specialization.clone(),
+                weight_class: AgentWeightClass::from_models(std::slice::from_ref(&pooled.model_id)),
+                model_id: pooled.model_id,
+                capabilities: spec.required_capabilities.clone(),
+                // Already initialized while it waited in the pool
+                status: AgentStatus::Ready,
+            })
+        })
+    }
+
+    /// Bring every popular specialization up to its target and release agents no longer wanted
+    pub fn replenish() -> WarmPoolStatus {
+        Self::tick();
+        Self::status()
+    }
+
+    /// Run from the maintenance timer, so claims made by a spawn are refilled after it
+    /// rather than inside it
+    pub fn tick() {
+        if !FeatureFlagService::is_enabled(FeatureFlagService::WARM_POOL) {
+            return;
+        }
+        let now = time();
+        with_state_mut(|state| {
+            let targets = Self::targets(&state.warm_pool.config, &state.warm_pool.demand);
+            let mut kept: Vec<PooledAgent> = Vec::new();
+            for agent in std::mem::take(&mut state.warm_pool.agents) {
+                let target = targets.iter().find(|(name, _)| *name == agent.specialization).map_or(0, |(_, t)| *t);
+                let pooled = kept.iter().filter(|a| a.specialization == agent.specialization).count() as u32;
+                if pooled < target {
+                    kept.push(agent);
+                } else {
+                    Metrics::increment_counter("warm_pool_released");
+                }
+            }
+
+            for (specialization, target) in &targets {
+                let pooled = kept.iter().filter(|a| a.specialization == *specialization).count() as u32;
+                let model_id = SpecializationService::resolve(specialization)
+                    .and_then(|role| role.models.first().cloned())
+                    .unwrap_or_else(|| "llama".to_string());
+                for _ in pooled..*target {
+                    kept.push(PooledAgent {
+                        agent_id: IdGenerator::next_in(state, "agent", Self::POOL_PRINCIPAL),
+                        canister_id: AgentSpawningService::agent_canister_id(state),
+                        specialization: specialization.clone(),
+                        model_id: model_id.clone(),
+                        provisioned_at: now,
+                    });
+                    Metrics::increment_counter("warm_pool_provisioned");
+                }
+            }
+            state.warm_pool.agents = kept;
+        });
+    }
+
+    pub fn status() -> WarmPoolStatus {
+        with_state(|state| {
+            let pool = &state.warm_pool;
+            let targets = Self::targets(&pool.config, &pool.demand);
+            let mut names: Vec<&String> = pool.counters.keys()
+                .chain(targets.iter().map(|(name, _)| name))
+                .chain(pool.agents.iter().map(|agent| &agent.specialization))
+                .collect();
+            names.sort();
+            names.dedup();
+
+            let specializations: Vec<WarmPoolSpecialization> = names
+                .into_iter()
+                .map(|name| {
+                    let counters = pool.counters.get(name).copied().unwrap_or_default();
+                    WarmPoolSpecialization {
+                        specialization: name.clone(),
+                        available: pool.agents.iter().filter(|a| a.specialization == *name).count() as u32,
+                        target: targets.iter().find(|(n, _)| n == name).map_or(0, |(_, t)| *t),
+                        demand: pool.demand.get(name).copied().unwrap_or(0),
+                        hits: counters.hits,
+                        misses: counters.misses,
+                    }
+                })
+                .collect();
+            let hits: u64 = specializations.iter().map(|s| s.hits).sum();
+            let misses: u64 = specializations.iter().map(|s| s.misses).sum();
+            WarmPoolStatus {
+                config: pool.config.clone(),
+                total_pooled: pool.agents.len() as u32,
+                hits,
+                misses,
+                hit_rate: if hits + misses == 0 { 0.0 } else { hits as f32 / (hits + misses) as f32 },
+                specializations,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_targets_follow_demand_within_budget() {
+        let config = WarmPoolConfig { enabled: true, agents_per_specialization: 3, max_pooled_agents: 4, popular_specializations: 2 };
+        let demand: HashMap<String, u64> = [("Data Analyst", 5), ("Software Developer", 9), ("Content Creator", 1)]
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        assert_eq!(
+            WarmPoolService::targets(&config, &demand),
+            vec![("Software Developer".to_string(), 3), ("Data Analyst".to_string(), 1)]
+        );
+        let disabled = WarmPoolConfig { enabled: false, ..config };
+        assert!(WarmPoolService::targets(&disabled, &demand).is_empty());
+    }
+
+    #[test]
+    fn test_match_requires_specialization_and_deployed_model() {
+        let pooled = |specialization: &str, model_id: &str| PooledAgent {
+            agent_id: format!("{}-{}", specialization, model_id),
+            canister_id: "ohms-agent".to_string(),
+            specialization: specialization.to_string(),
+            model_id: model_id.to_string(),
+            provisioned_at: 0,
+        };
+        let agents = vec![pooled("Data Analyst", "llama"), pooled("Software Developer", "code-llama")];
+        let mut spec = AgentSpec {
+            agent_type: "Developer".to_string(),
+            required_capabilities: vec!["coding".to_string()],
+            model_requirements: vec!["code-llama".to_string()],
+            specialization: "Software Developer".to_string(),
+            weight_class: AgentWeightClass::Standard,
+        };
+        assert_eq!(WarmPoolService::find_match(&agents, &spec), Some(1));
+        spec.model_requirements = vec!["starcoder".to_string()];
+        assert_eq!(WarmPoolService::find_match(&agents, &spec), None);
+        spec.model_requirements.clear();
+        assert_eq!(WarmPoolService::find_match(&agents, &spec), Some(1));
+    }
+}
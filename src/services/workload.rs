@@ -0,0 +1,180 @@
+use crate::services::{with_state, CoordinatorState};
+use crate::services::autonomous_coord::{CoordinationSession, SessionStatus, TaskLedgerEntry, TaskStatus};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Sessions and open tasks per agent, so questions like "what is this agent part of"
+/// do not scan every session and task. The index is derived from `coordination_sessions`
+/// and `task_ledger`: it is updated wherever sessions are added or removed and tasks are
+/// assigned or change status, and rebuilt after restores.
+pub struct WorkloadService;
+
+#[derive(Debug, Default)]
+pub struct WorkloadIndex {
+    sessions_by_agent: HashMap<String, BTreeSet<String>>,
+    open_tasks_by_agent: HashMap<String, BTreeSet<String>>,
+    /// Agent each indexed open task is assigned to
+    open_task_agents: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentSessionRef {
+    pub session_id: String,
+    pub status: SessionStatus,
+    /// The agent coordinates the session rather than only taking part
+    pub coordinator: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AgentWorkload {
+    pub agent_id: String,
+    pub sessions: Vec<AgentSessionRef>,
+    /// Pending and in-progress tasks assigned to the agent
+    pub open_task_ids: Vec<String>,
+}
+
+impl WorkloadIndex {
+    pub(crate) fn add_session(&mut self, session: &CoordinationSession) {
+        for agent_id in Self::members(session) {
+            self.sessions_by_agent.entry(agent_id.clone()).or_default().insert(session.session_id.clone());
+        }
+    }
+
+    pub(crate) fn remove_session(&mut self, session: &CoordinationSession) {
+        for agent_id in Self::members(session) {
+            if let Some(session_ids) = self.sessions_by_agent.get_mut(agent_id) {
+                session_ids.remove(&session.session_id);
+                if session_ids.is_empty() {
+                    self.sessions_by_agent.remove(agent_id);
+                }
+            }
+        }
+    }
+
+    /// Re-index a task after it was created, reassigned or changed status
+    pub(crate) fn update_task(&mut self, task: &TaskLedgerEntry) {
+        if let Some(previous) = self.open_task_agents.remove(&task.task_id) {
+            if let Some(task_ids) = self.open_tasks_by_agent.get_mut(&previous) {
+                task_ids.remove(&task.task_id);
+                if task_ids.is_empty() {
+                    self.open_tasks_by_agent.remove(&previous);
+                }
+            }
+        }
+        if matches!(task.status, TaskStatus::Pending | TaskStatus::InProgress) {
+            self.open_tasks_by_agent.entry(task.assigned_agent.clone()).or_default().insert(task.task_id.clone());
+            self.open_task_agents.insert(task.task_id.clone(), task.assigned_agent.clone());
+        }
+    }
+
+    pub(crate) fn open_task_count(&self, agent_id: &str) -> usize {
+        self.open_tasks_by_agent.get(agent_id).map_or(0, |ids| ids.len())
+    }
+
+    pub(crate) fn open_tasks(&self, agent_id: &str) -> Vec<String> {
+        self.open_tasks_by_agent.get(agent_id).map(|ids| ids.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    fn members(session: &CoordinationSession) -> impl Iterator<Item = &String> {
+        std::iter::once(&session.coordinator_agent).chain(session.participants.iter())
+    }
+}
+
+impl WorkloadService {
+    pub fn rebuild(state: &mut CoordinatorState) {
+        let mut index = WorkloadIndex::default();
+        for session in state.coordination_sessions.iter().flat_map(|sessions| sessions.values()) {
+            index.add_session(session);
+        }
+        for task in state.task_ledger.values() {
+            index.update_task(task);
+        }
+        state.workload_index = index;
+    }
+
+    pub fn get_agent_workload(agent_id: &str) -> Result<AgentWorkload, String> {
+        with_state(|state| Self::workload_in(state, agent_id))
+    }
+
+    fn workload_in(state: &CoordinatorState, agent_id: &str) -> Result<AgentWorkload, String> {
+        if !state.agents.contains_key(agent_id) {
+            return Err(format!("Agent not found: {}", agent_id));
+        }
+        let sessions = state.workload_index.sessions_by_agent
+            .get(agent_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|session_id| state.coordination_sessions.as_ref()?.get(session_id))
+            .map(|session| AgentSessionRef {
+                session_id: session.session_id.clone(),
+                status: session.status.clone(),
+                coordinator: session.coordinator_agent == agent_id,
+            })
+            .collect();
+        Ok(AgentWorkload {
+            agent_id: agent_id.to_string(),
+            sessions,
+            open_task_ids: state.workload_index.open_tasks(agent_id),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AgentRegistration;
+    use crate::services::RegistryService;
+    use crate::services::autonomous_coord::ResourceConstraints;
+    use crate::services::session_templates::MessageProtocol;
+
+    #[test]
+    fn test_index_follows_sessions_and_task_assignments() {
+        let mut state = CoordinatorState::default();
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("a"), "owner");
+        RegistryService::insert_registration(&mut state, AgentRegistration::test("b"), "owner");
+        let session = CoordinationSession {
+            session_id: "s1".to_string(),
+            participants: vec!["b".to_string()],
+            coordinator_agent: "a".to_string(),
+            objective: "objective".to_string(),
+            status: SessionStatus::Active,
+            created_at: 0,
+            last_activity: 0,
+            messages: vec![],
+            resource_constraints: ResourceConstraints {
+                max_execution_time_ms: 1,
+                max_memory_usage_bytes: 1,
+                max_concurrent_tasks: 1,
+                allowed_capabilities: None,
+            },
+            template_id: None,
+            role_bindings: vec![],
+            protocol: MessageProtocol::Mesh,
+        };
+        state.workload_index.add_session(&session);
+        state.coordination_sessions = Some([("s1".to_string(), session.clone())].into_iter().collect());
+        for entry in [TaskLedgerEntry::test("t1", "a", TaskStatus::Pending), TaskLedgerEntry::test("t2", "a", TaskStatus::Completed)] {
+            state.workload_index.update_task(&entry);
+            state.task_ledger.insert(entry.task_id.clone(), entry);
+        }
+
+        let workload = WorkloadService::workload_in(&state, "a").unwrap();
+        assert_eq!(workload.open_task_ids, vec!["t1".to_string()]);
+        assert!(workload.sessions[0].coordinator);
+        assert!(!WorkloadService::workload_in(&state, "b").unwrap().sessions[0].coordinator);
+
+        // Reassignment moves the task; the rebuilt index agrees with the maintained one
+        state.task_ledger.get_mut("t1").unwrap().assigned_agent = "b".to_string();
+        state.workload_index.update_task(&state.task_ledger["t1"]);
+        assert!(WorkloadService::workload_in(&state, "a").unwrap().open_task_ids.is_empty());
+        assert_eq!(WorkloadService::workload_in(&state, "b").unwrap().open_task_ids, vec!["t1".to_string()]);
+        let maintained = (state.workload_index.sessions_by_agent.clone(), state.workload_index.open_tasks_by_agent.clone());
+        WorkloadService::rebuild(&mut state);
+        assert_eq!((state.workload_index.sessions_by_agent.clone(), state.workload_index.open_tasks_by_agent.clone()), maintained);
+
+        state.workload_index.remove_session(&session);
+        assert!(state.workload_index.sessions_by_agent.is_empty());
+        assert!(WorkloadService::workload_in(&state, "nobody").is_err());
+    }
+}